@@ -0,0 +1,155 @@
+import { useEffect, useState } from "react";
+import {
+  Account,
+  ApiToken,
+  ApiTokenScope,
+  API_TOKEN_SCOPES,
+  createApiToken,
+  fetchApiTokens,
+  revokeApiToken,
+} from "./account";
+
+type Props = {
+  account: Account;
+};
+
+/**
+ * Personal API tokens of the account, for bots and stats dashboards.
+ */
+export default function ApiTokens({ account }: Props) {
+  const [tokens, setTokens] = useState<ApiToken[]>([]);
+  const [name, setName] = useState("");
+  const [scopes, setScopes] = useState<ApiTokenScope[]>(["read:results"]);
+  const [secret, setSecret] = useState<string | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const [loading, setLoading] = useState(false);
+
+  useEffect(() => {
+    fetchApiTokens(account)
+      .then(setTokens)
+      .catch(() => setTokens([]));
+  }, [account.sessionToken]);
+
+  const toggleScope = (scope: ApiTokenScope) => {
+    setScopes(
+      scopes.includes(scope)
+        ? scopes.filter((s) => s !== scope)
+        : [...scopes, scope]
+    );
+  };
+
+  const handleCreate = async (e: React.FormEvent) => {
+    e.preventDefault();
+    setLoading(true);
+    setError(null);
+
+    try {
+      const { secret, ...token } = await createApiToken(
+        account,
+        name.trim(),
+        scopes
+      );
+      setTokens([token, ...tokens]);
+      setSecret(secret);
+      setName("");
+    } catch (err) {
+      setError(err instanceof Error ? err.message : "Failed to create token");
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleRevoke = async (tokenId: string) => {
+    setLoading(true);
+    setError(null);
+
+    try {
+      await revokeApiToken(account, tokenId);
+      setTokens(tokens.filter((t) => t.id !== tokenId));
+    } catch (err) {
+      setError(err instanceof Error ? err.message : "Failed to revoke token");
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  return (
+    <div className="border-t border-slate-800 mt-4 pt-4 text-sm">
+      <h3 className="text-white font-medium mb-2">API tokens</h3>
+
+      {error && <p className="text-red-400 text-xs mb-2">{error}</p>}
+
+      {secret && (
+        <div className="bg-slate-800 rounded-lg px-3 py-2 mb-2">
+          <p className="text-slate-400 text-xs mb-1">
+            Copy this token now, it won't be shown again:
+          </p>
+          <code className="text-cyan-400 text-xs break-all select-all">
+            {secret}
+          </code>
+        </div>
+      )}
+
+      <div className="space-y-1 mb-2">
+        {tokens.map((token) => (
+          <div
+            key={token.id}
+            className="flex items-center gap-2 bg-slate-800 rounded-lg px-3 py-1.5"
+          >
+            <div className="flex-1 min-w-0">
+              <div className="text-white truncate">{token.name}</div>
+              <div className="text-slate-500 text-xs truncate">
+                {token.prefix}… · {token.scopes.join(", ")}
+                {token.lastUsedAt &&
+                  ` · used ${new Date(token.lastUsedAt).toLocaleDateString()}`}
+              </div>
+            </div>
+            <button
+              onClick={() => handleRevoke(token.id)}
+              disabled={loading}
+              className="text-slate-400 hover:text-red-400 text-xs"
+            >
+              Revoke
+            </button>
+          </div>
+        ))}
+      </div>
+
+      <form onSubmit={handleCreate} className="space-y-2">
+        <div className="flex flex-wrap gap-1">
+          {API_TOKEN_SCOPES.map((scope) => (
+            <button
+              key={scope}
+              type="button"
+              onClick={() => toggleScope(scope)}
+              className={`px-1.5 py-0.5 rounded text-xs transition-all ${
+                scopes.includes(scope)
+                  ? "bg-blue-600 text-white"
+                  : "bg-slate-700 text-slate-400 hover:text-white"
+              }`}
+            >
+              {scope}
+            </button>
+          ))}
+        </div>
+        <div className="flex gap-2">
+          <input
+            type="text"
+            value={name}
+            onChange={(e) => setName(e.target.value)}
+            placeholder="Token name"
+            maxLength={40}
+            className="flex-1 bg-slate-800 text-white px-3 py-1.5 rounded-lg border border-slate-700 focus:border-blue-500 focus:outline-none"
+          />
+          <button
+            type="submit"
+            disabled={loading || !name.trim() || scopes.length === 0}
+            className="bg-blue-600 hover:bg-blue-500 disabled:bg-slate-700 disabled:text-slate-500 text-white px-3 py-1.5 rounded-lg font-medium transition-all"
+          >
+            Create
+          </button>
+        </div>
+      </form>
+    </div>
+  );
+}
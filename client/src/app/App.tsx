@@ -18,14 +18,25 @@ import {
   useWindSourceUpdater,
   useSessionRefs,
   useIdleWind,
+  useReplay,
 } from "./hooks";
 import { CountdownDisplay } from "./race";
 import { calculateTWA } from "./polar";
 import { getWindDirection, getWindSpeedKnots } from "../utils";
+import { EnergySystem } from "../models";
 import FinishOverlay from "./FinishOverlay";
 import KeyBindings from "./KeyBindings";
+import MiniMap from "./MiniMap";
+import ReplayViewer from "./ReplayViewer";
 import RaceTimer from "./RaceTimer";
 import { RaceContext, RaceContextValue } from "./race-context";
+import {
+  I18nContext,
+  createI18n,
+  loadLanguage,
+  saveLanguage,
+  type Language,
+} from "./i18n";
 import {
   ghostLeaderboardEntries,
   mergeLeaderboards,
@@ -35,13 +46,32 @@ import {
 // Re-export for backward compatibility
 export type { RecordedGhost } from "./hooks/useGhosts";
 
-type View = "race" | "admin";
+type View = "race" | "admin" | "replay";
+
+/** Result to replay from a `?replay={id}` link */
+function replayIdFromUrl(): number | null {
+  const id = Number(new URLSearchParams(window.location.search).get("replay"));
+  return Number.isInteger(id) && id > 0 ? id : null;
+}
 
 export default function App() {
-  const [view, setView] = useState<View>("race");
+  const [replayId, setReplayId] = useState<number | null>(replayIdFromUrl);
+  const [view, setView] = useState<View>(() =>
+    replayId !== null ? "replay" : "race",
+  );
   const [state, dispatch] = useReducer(appReducer, initialState);
   const [showQuitConfirm, setShowQuitConfirm] = useState(false);
   const [adminAccount, setAdminAccount] = useState<Account | null>(null);
+  const [language, setLanguage] = useState<Language>(loadLanguage);
+  const i18n = useMemo(
+    () =>
+      createI18n(language, (language) => {
+        saveLanguage(language);
+        setLanguage(language);
+      }),
+    [language],
+  );
+  const { t } = i18n;
 
   // Courses management
   const {
@@ -62,9 +92,17 @@ export default function App() {
     useSphereView(session, lobbyCourse);
 
   // Load random wind for idle globe view (before any race is created)
-  const isIdleWithoutRace = state.tag === "Idle";
+  const isIdleWithoutRace = state.tag === "Idle" && view !== "replay";
   useIdleWind(isIdleWithoutRace, sphereViewRef, interpolatedWindRef);
 
+  // Replay of a stored result, once courses are known
+  const replayState = useReplay(
+    view === "replay" && courses.length > 0 ? replayId : null,
+    coursesRef,
+    sphereViewRef,
+    interpolatedWindRef,
+  );
+
   // Sync selected course to SphereView and focus viewport
   useEffect(() => {
     if (selectedCourseKey && sphereViewRef.current && coursesRef.current) {
@@ -145,13 +183,42 @@ export default function App() {
   // Handle quitting the race
   const handleQuitRace = useCallback(() => {
     if (multiplayerRef.current) {
+      if (state.tag === "Playing" && state.session.finishTime === null) {
+        multiplayerRef.current.retireFromRace();
+      }
       multiplayerRef.current.leaveRace();
       multiplayerRef.current.disconnect();
     }
     resetWind();
     dispatch({ type: "LEAVE_RACE" });
     setShowQuitConfirm(false);
-  }, [multiplayerRef, resetWind]);
+  }, [state, multiplayerRef, resetWind]);
+
+  // Move to a rematch lobby once our race is over
+  const handleRematch = useCallback(() => {
+    resetWind();
+    multiplayerCallbacks.onProposeRematch();
+  }, [multiplayerCallbacks, resetWind]);
+
+  const handleChangeSail = useCallback(
+    (sail: string) => {
+      dispatch({ type: "CHANGE_SAIL", sail });
+      multiplayerRef.current?.sendChangeSail(sail);
+    },
+    [multiplayerRef],
+  );
+
+  const handleAskCoach = useCallback(() => {
+    multiplayerRef.current?.askCoach();
+  }, [multiplayerRef]);
+
+  // The server switches systems and reports the new energy state
+  const handleToggleSystem = useCallback(
+    (system: EnergySystem, on: boolean) => {
+      multiplayerRef.current?.sendToggleSystem(system, on);
+    },
+    [multiplayerRef],
+  );
 
   // Handle quit button click - show confirmation or quit directly
   const handleQuitClick = useCallback(() => {
@@ -246,6 +313,37 @@ export default function App() {
     }
   }, [countdown]);
 
+  // Leave any race to watch the replay of a result
+  const handleWatchReplay = useCallback(
+    (resultId: number) => {
+      if (multiplayerRef.current) {
+        multiplayerRef.current.leaveRace();
+        multiplayerRef.current.disconnect();
+      }
+      resetWind();
+      dispatch({ type: "LEAVE_RACE" });
+      setReplayId(resultId);
+      setView("replay");
+      window.history.replaceState(null, "", `?replay=${resultId}`);
+    },
+    [multiplayerRef, resetWind],
+  );
+
+  const handleCloseReplay = useCallback(() => {
+    setReplayId(null);
+    setView("race");
+    resetWind();
+    window.history.replaceState(null, "", window.location.pathname);
+    const course =
+      selectedCourseKey !== null
+        ? coursesRef.current.get(selectedCourseKey)
+        : undefined;
+    if (course && sphereViewRef.current) {
+      sphereViewRef.current.setCourse(course);
+      sphereViewRef.current.focusOnCourseStart();
+    }
+  }, [selectedCourseKey, coursesRef, sphereViewRef, resetWind]);
+
   const handleOpenAdmin = useCallback(() => {
     const account = loadAccount();
     setAdminAccount(account);
@@ -267,17 +365,22 @@ export default function App() {
         state.tag === "Idle" || (state.tag === "Lobby" && state.race.isCreator),
       players: state.tag === "Lobby" ? state.race.players : new Map(),
       windStatus: state.tag === "Lobby" ? state.wind.status : "idle",
+      matchQueue: state.tag === "Idle" ? (state.matchQueue ?? null) : null,
       courses,
       selectedCourseKey,
       recordedGhosts,
       createRace: multiplayerCallbacks.onCreateRace,
       joinRace: multiplayerCallbacks.onJoinRace,
+      quickMatch: multiplayerCallbacks.onQuickMatch,
+      startDaily: multiplayerCallbacks.onStartDaily,
       startRace: multiplayerCallbacks.onStartRace,
       leaveRace: multiplayerCallbacks.onLeaveRace,
       selectCourse: handleSelectCourse,
       openAdmin: handleOpenAdmin,
       addGhost,
+      addServerGhosts: multiplayerCallbacks.onAddServerGhosts,
       removeGhost,
+      watchReplay: handleWatchReplay,
     }),
     [
       state,
@@ -289,13 +392,14 @@ export default function App() {
       handleOpenAdmin,
       addGhost,
       removeGhost,
+      handleWatchReplay,
     ],
   );
 
   const inAdmin = view === "admin";
 
   return (
-    <>
+    <I18nContext.Provider value={i18n}>
       <div ref={sphereNodeRef} id="sphere" className={`fixed inset-0 ${inAdmin ? "invisible" : ""}`} />
       {inAdmin && (
         adminAccount ? (
@@ -321,7 +425,11 @@ export default function App() {
       {!inAdmin && (
         <>
           <div id="app" className="fixed inset-0 z-10 pointer-events-none">
-            {(state.tag === "Idle" || state.tag === "Lobby") &&
+            {view === "replay" && (
+              <ReplayViewer state={replayState} onClose={handleCloseReplay} />
+            )}
+            {view === "race" &&
+              (state.tag === "Idle" || state.tag === "Lobby") &&
               courses.length > 0 && (
                 <div className="pointer-events-auto">
                   <RaceContext.Provider value={raceContextValue}>
@@ -338,7 +446,7 @@ export default function App() {
                   onClick={handleQuitClick}
                   className="fixed bottom-4 left-4 px-4 py-2 text-slate-400 hover:text-white text-sm transition-all pointer-events-auto"
                 >
-                  Cancel
+                  {t("race.cancel")}
                 </button>
               </>
             )}
@@ -348,29 +456,50 @@ export default function App() {
                   courseTime={state.session.courseTime}
                   startTime={state.session.course.startTime}
                 />
-                <Hud session={state.session} />
+                <Hud
+                  session={state.session}
+                  onChangeSail={handleChangeSail}
+                  onToggleSystem={handleToggleSystem}
+                  onAskCoach={handleAskCoach}
+                />
                 <KeyBindings />
+                <MiniMap
+                  course={state.session.course}
+                  position={state.session.position}
+                  nextGateIndex={state.session.nextGateIndex}
+                  sphereView={sphereViewRef.current}
+                />
                 <Leaderboard
                   entries={mergedLeaderboard}
                   myPlayerId={state.race.myPlayerId}
                   courseStartTime={state.session.course.startTime}
                   onQuit={handleQuitClick}
                 />
-                <PolarDiagram
-                  polar={state.session.polar}
-                  tws={getWindSpeedKnots(state.session.windSpeed)}
-                  twa={calculateTWA(
-                    state.session.heading,
-                    getWindDirection(state.session.windSpeed),
-                  )}
-                  bsp={state.session.boatSpeed}
-                  vmgBad={vmgBad}
-                  twaLocked={state.session.lockedTWA !== null}
-                />
+                {/* The polar diagram reads the wind instruments */}
+                {(state.session.energy?.instruments ?? true) && (
+                  <PolarDiagram
+                    polar={
+                      (state.session.sail !== null &&
+                        state.session.sails[state.session.sail]) ||
+                      state.session.polar
+                    }
+                    tws={getWindSpeedKnots(state.session.windSpeed)}
+                    twa={calculateTWA(
+                      state.session.heading,
+                      getWindDirection(state.session.windSpeed),
+                    )}
+                    bsp={state.session.boatSpeed}
+                    vmgBad={vmgBad}
+                    twaLocked={state.session.lockedTWA !== null}
+                  />
+                )}
                 {state.session.finishTime !== null && (
                   <FinishOverlay
                     finishTime={state.session.finishTime}
                     courseStartTime={state.session.course.startTime}
+                    rematchProposer={state.rematchProposer}
+                    personalBest={state.personalBest}
+                    onRematch={handleRematch}
                     onBack={handleQuitRace}
                   />
                 )}
@@ -379,7 +508,11 @@ export default function App() {
           </div>
           <CursorWind
             sphereView={sphereViewRef.current}
-            courseTime={session?.courseTime ?? 0}
+            courseTime={
+              view === "replay"
+                ? replayState.courseTime
+                : (session?.courseTime ?? 0)
+            }
           />
         </>
       )}
@@ -387,25 +520,25 @@ export default function App() {
         <div className="fixed inset-0 flex items-center justify-center bg-black bg-opacity-50 z-50 pointer-events-auto">
           <div className="bg-slate-900 rounded-lg p-6 max-w-sm w-full mx-4">
             <h2 className="text-white text-lg font-semibold mb-4">
-              Quit this race?
+              {t("race.quitConfirm")}
             </h2>
             <div className="flex gap-3">
               <button
                 onClick={() => setShowQuitConfirm(false)}
                 className="flex-1 px-4 py-2 bg-slate-700 hover:bg-slate-600 text-white rounded-lg transition-all"
               >
-                Cancel
+                {t("race.cancel")}
               </button>
               <button
                 onClick={handleQuitRace}
                 className="flex-1 px-4 py-2 bg-red-600 hover:bg-red-500 text-white rounded-lg transition-all"
               >
-                Quit race
+                {t("race.quit")}
               </button>
             </div>
           </div>
         </div>
       )}
-    </>
+    </I18nContext.Provider>
   );
 }
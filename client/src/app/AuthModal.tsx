@@ -1,19 +1,37 @@
 import { useState, useRef, useEffect } from "react";
-import { startAuth, verifyAuth, Account } from "./account";
+import {
+  startAuth,
+  startMagicLink,
+  verifyAuth,
+  fetchAuthProviders,
+  oauthUrl,
+  Account,
+  OAUTH_PROVIDER_NAMES,
+} from "./account";
 
-type Step = "email" | "code";
+type Step = "email" | "code" | "link";
 
 type AuthModalProps = {
   onClose: () => void;
   onSuccess: (account: Account) => void;
+  initialError?: string | null;
 };
 
-export default function AuthModal({ onClose, onSuccess }: AuthModalProps) {
+export default function AuthModal({
+  onClose,
+  onSuccess,
+  initialError = null,
+}: AuthModalProps) {
   const [step, setStep] = useState<Step>("email");
   const [email, setEmail] = useState("");
   const [code, setCode] = useState(["", "", "", "", "", ""]);
-  const [error, setError] = useState<string | null>(null);
+  const [error, setError] = useState<string | null>(initialError);
   const [loading, setLoading] = useState(false);
+  const [providers, setProviders] = useState<string[]>([]);
+
+  useEffect(() => {
+    fetchAuthProviders().then(setProviders);
+  }, []);
 
   const codeInputRefs = useRef<(HTMLInputElement | null)[]>([]);
 
@@ -46,6 +64,27 @@ export default function AuthModal({ onClose, onSuccess }: AuthModalProps) {
     }
   };
 
+  const handleMagicLink = async () => {
+    const trimmedEmail = email.trim().toLowerCase();
+
+    if (!trimmedEmail || !trimmedEmail.includes("@")) {
+      setError("Please enter a valid email address");
+      return;
+    }
+
+    setLoading(true);
+    setError(null);
+
+    try {
+      await startMagicLink(trimmedEmail);
+      setStep("link");
+    } catch (err) {
+      setError(err instanceof Error ? err.message : "Failed to send link");
+    } finally {
+      setLoading(false);
+    }
+  };
+
   const handleCodeChange = (index: number, value: string) => {
     // Only allow single digit
     const digit = value.replace(/\D/g, "").slice(-1);
@@ -138,7 +177,11 @@ export default function AuthModal({ onClose, onSuccess }: AuthModalProps) {
         </button>
 
         <h2 className="text-xl font-semibold text-white mb-2">
-          {step === "email" ? "Sign In" : "Enter Code"}
+          {step === "email"
+            ? "Sign In"
+            : step === "code"
+              ? "Enter Code"
+              : "Check Your Email"}
         </h2>
 
         {step === "email" ? (
@@ -168,7 +211,48 @@ export default function AuthModal({ onClose, onSuccess }: AuthModalProps) {
               >
                 {loading ? "Sending..." : "Continue"}
               </button>
+              <button
+                type="button"
+                onClick={handleMagicLink}
+                disabled={loading}
+                className="w-full text-slate-500 hover:text-slate-300 disabled:text-slate-600 mt-3 text-sm"
+              >
+                Email me a sign in link instead
+              </button>
             </form>
+
+            {providers.length > 0 && (
+              <div className="mt-4 space-y-2">
+                <div className="text-slate-500 text-xs text-center">or</div>
+                {providers.map((provider) => (
+                  <a
+                    key={provider}
+                    href={oauthUrl(provider)}
+                    className="block w-full text-center bg-slate-800 hover:bg-slate-700 text-white py-3 rounded-lg font-medium transition-all"
+                  >
+                    Continue with {OAUTH_PROVIDER_NAMES[provider] ?? provider}
+                  </a>
+                ))}
+              </div>
+            )}
+          </>
+        ) : step === "link" ? (
+          <>
+            <p className="text-slate-400 text-sm mb-4">
+              We sent a sign in link to{" "}
+              <span className="text-white">{email}</span>. Open it on this
+              device to continue.
+            </p>
+
+            <button
+              onClick={() => {
+                setStep("email");
+                setError(null);
+              }}
+              className="w-full text-slate-500 hover:text-slate-300 mt-4 text-sm"
+            >
+              Use different email
+            </button>
           </>
         ) : (
           <>
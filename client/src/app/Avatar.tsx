@@ -0,0 +1,30 @@
+type Props = {
+  name: string;
+  url?: string | null;
+  /** Tailwind size and text classes, e.g. "w-8 h-8 text-sm" */
+  className?: string;
+};
+
+/** Profile avatar, or the initial of the name when there is none */
+export default function Avatar({
+  name,
+  url,
+  className = "w-8 h-8 text-sm",
+}: Props) {
+  if (url) {
+    return (
+      <img
+        src={url}
+        alt={name}
+        className={`${className} rounded-full object-cover flex-shrink-0`}
+      />
+    );
+  }
+  return (
+    <div
+      className={`${className} rounded-full bg-gradient-to-br from-cyan-400 to-blue-500 flex items-center justify-center text-white font-bold flex-shrink-0`}
+    >
+      {name.charAt(0).toUpperCase()}
+    </div>
+  );
+}
@@ -1,34 +1,60 @@
+import { PersonalBest } from "../multiplayer/types";
+import { useI18n } from "./i18n";
+
 type Props = {
   finishTime: number;
   courseStartTime: number;
+  rematchProposer: string | null;
+  personalBest: PersonalBest | null;
+  onRematch: () => void;
   onBack: () => void;
 };
 
 export default function FinishOverlay({
   finishTime,
   courseStartTime,
+  rematchProposer,
+  personalBest,
+  onRematch,
   onBack,
 }: Props) {
-  const elapsedMs = finishTime - courseStartTime;
-  const days = Math.floor(elapsedMs / (24 * 60 * 60 * 1000));
-  const hours = Math.floor(
-    (elapsedMs % (24 * 60 * 60 * 1000)) / (60 * 60 * 1000),
-  );
-  const minutes = Math.floor((elapsedMs % (60 * 60 * 1000)) / (60 * 1000));
+  const { t, formatDuration } = useI18n();
 
   return (
     <div className="absolute top-1/2 left-1/2 transform -translate-x-1/2 -translate-y-1/2 bg-black/80 text-white px-8 py-6 rounded-lg text-center pointer-events-auto">
-      <h2 className="text-3xl font-bold text-green-400 mb-4">FINISHED!</h2>
+      <h2 className="text-3xl font-bold text-green-400 mb-4">{t("finish.title")}</h2>
       <p className="text-xl">
-        Race Time: {days > 0 ? `${days}d ` : ""}
-        {hours}h {minutes}m
+        {t("finish.raceTime", {
+          time: formatDuration(finishTime - courseStartTime),
+        })}
       </p>
-      <button
-        onClick={onBack}
-        className="mt-6 px-6 py-2 bg-slate-700 hover:bg-slate-600 text-white rounded-lg transition-all"
-      >
-        Back to Lobby
-      </button>
+      {personalBest && (
+        <p className="mt-2 text-amber-400 font-semibold">
+          {t("finish.personalBest")}
+          {personalBest.previousBest !== null &&
+            ` ${t("finish.faster", {
+              time: formatDuration(
+                personalBest.previousBest - personalBest.finishTime,
+              ),
+            })}`}
+        </p>
+      )}
+      <div className="mt-6 flex justify-center gap-3">
+        <button
+          onClick={onRematch}
+          className="px-6 py-2 bg-blue-600 hover:bg-blue-500 text-white rounded-lg transition-all"
+        >
+          {rematchProposer
+            ? t("finish.joinRematch", { name: rematchProposer })
+            : t("finish.rematch")}
+        </button>
+        <button
+          onClick={onBack}
+          className="px-6 py-2 bg-slate-700 hover:bg-slate-600 text-white rounded-lg transition-all"
+        >
+          {t("finish.back")}
+        </button>
+      </div>
     </div>
   );
 }
@@ -1,5 +1,7 @@
 import { useState, useEffect } from "react";
-import { formatDuration } from "../utils";
+import { formatDuration, formatRaceStats, formatSplits } from "../utils";
+import type { RaceStats } from "../models";
+import Avatar from "./Avatar";
 
 const serverUrl = import.meta.env.REWIND_SERVER_URL;
 
@@ -7,8 +9,13 @@ export type HallOfFameEntry = {
   id: number;
   rank: number;
   playerName: string;
+  avatarUrl: string | null;
   finishTime: number;
   raceDate: number;
+  /** Elapsed time at each mark before the finish (ms) */
+  splits: number[];
+  /** Null for results saved before stats were computed */
+  stats: RaceStats | null;
 };
 
 type Props = {
@@ -56,7 +63,18 @@ export default function HallOfFameList({
 
   return (
     <div className="space-y-4">
-      <h2 className="text-amber-400 font-semibold">Hall of Fame</h2>
+      <div className="flex items-center justify-between">
+        <h2 className="text-amber-400 font-semibold">Hall of Fame</h2>
+        {entries.length > 0 && (
+          <a
+            href={`${serverUrl}/courses/${encodeURIComponent(courseKey)}/leaderboard.csv`}
+            title="Download every result of the course as CSV"
+            className="text-slate-400 hover:text-slate-300 text-xs"
+          >
+            CSV
+          </a>
+        )}
+      </div>
 
       {loading ? (
         <div className="text-slate-400 text-sm py-4">Loading...</div>
@@ -85,17 +103,35 @@ export default function HallOfFameList({
                 >
                   #{entry.rank}
                 </span>
+                <Avatar name={entry.playerName} url={entry.avatarUrl} />
                 <div>
                   <div className="text-white text-sm">{entry.playerName}</div>
                   <div className="text-slate-500 text-xs">
                     {formatDate(entry.raceDate)}
                   </div>
+                  {entry.splits.length > 0 && (
+                    <div className="text-slate-500 font-mono text-xs">
+                      {formatSplits(entry.splits)}
+                    </div>
+                  )}
+                  {entry.stats && (
+                    <div className="text-slate-500 text-xs">
+                      {formatRaceStats(entry.stats)}
+                    </div>
+                  )}
                 </div>
               </div>
               <div className="flex items-center gap-3">
                 <span className="text-green-400 font-mono text-sm">
                   {formatDuration(entry.finishTime)}
                 </span>
+                <a
+                  href={`${serverUrl}/replay/${entry.id}/export?format=gpx`}
+                  title="Download the track as GPX"
+                  className="text-slate-400 hover:text-slate-300 text-xs"
+                >
+                  GPX
+                </a>
                 {activeGhostIds.has(entry.id) ? (
                   <span className="text-amber-400 text-xs">Added</span>
                 ) : (
@@ -1,10 +1,21 @@
 import { Session } from "./state";
+import { EnergySystem } from "../models";
 import { getWindSpeedKnots } from "../utils";
+import { useI18n } from "./i18n";
 
 type Props = {
   session: Session;
+  onChangeSail: (sail: string) => void;
+  onToggleSystem: (system: EnergySystem, on: boolean) => void;
+  onAskCoach: () => void;
 };
 
+const SYSTEMS: { system: EnergySystem; label: string }[] = [
+  { system: "autopilot", label: "AP" },
+  { system: "instruments", label: "INST" },
+  { system: "hydro", label: "HYDRO" },
+];
+
 function formatCoord(value: number, pos: string, neg: string): string {
   const dir = value >= 0 ? pos : neg;
   const abs = Math.abs(value);
@@ -23,12 +34,28 @@ function formatCourseTime(timestamp: number): string {
   return `${year}-${month}-${day} ${hours}:${minutes}Z`;
 }
 
-export default function Hud({ session }: Props) {
+export default function Hud({
+  session,
+  onChangeSail,
+  onToggleSystem,
+  onAskCoach,
+}: Props) {
+  const { t } = useI18n();
   const lat = formatCoord(session.position.lat, "N", "S");
   const lng = formatCoord(session.position.lng, "E", "W");
+  const sails = Object.keys(session.sails);
+  const energy = session.energy;
+  // Wind readings take the instruments
+  const instruments = energy === null || energy.instruments;
+  const hint = session.coachHint;
   return (
     <div className="absolute bottom-4 right-4 bg-black/60 text-white px-4 py-3 rounded-lg font-mono text-sm">
       <div className="flex flex-col gap-1">
+        {session.ocs && (
+          <div className="text-red-400">
+            {t("hud.ocs")}
+          </div>
+        )}
         <div>
           <span className="text-gray-400">UTC </span>
           <span>{formatCourseTime(session.courseTime)}</span>
@@ -43,8 +70,77 @@ export default function Hud({ session }: Props) {
           <span className="text-gray-400">BSP </span>
           <span>{session.boatSpeed.toFixed(1)}kts</span>
           <span className="text-gray-400 ml-2">TWS </span>
-          <span>{getWindSpeedKnots(session.windSpeed).toFixed(1)}kts</span>
+          <span>
+            {instruments
+              ? `${getWindSpeedKnots(session.windSpeed).toFixed(1)}kts`
+              : "--"}
+          </span>
+          {instruments && session.windShadow < 1 && (
+            <span className="text-amber-400 ml-2">
+              {t("hud.shadow")} -{Math.round((1 - session.windShadow) * 100)}%
+            </span>
+          )}
+        </div>
+        {sails.length > 0 && (
+          <div className="flex items-center gap-1 pointer-events-auto">
+            <span className="text-gray-400">SAIL</span>
+            {sails.map((sail) => (
+              <button
+                key={sail}
+                onClick={() => onChangeSail(sail)}
+                className={`px-1.5 rounded transition-all cursor-pointer ${
+                  sail === session.sail
+                    ? "bg-white/20 text-white"
+                    : "text-gray-400 hover:text-white"
+                }`}
+              >
+                {sail}
+              </button>
+            ))}
+            {session.sailChange > 0 && (
+              <span className="text-amber-400 ml-1">{t("hud.changing")}</span>
+            )}
+          </div>
+        )}
+        <div className="flex items-center gap-1 pointer-events-auto">
+          <button
+            onClick={onAskCoach}
+            className="px-1.5 rounded text-gray-400 hover:text-white transition-all cursor-pointer"
+          >
+            {t("hud.coach")}
+          </button>
+          {hint !== null && (
+            <span>
+              TWA {Math.round(hint.twa)}° HDG {Math.round(hint.heading)}° VMG{" "}
+              {hint.vmg.toFixed(1)}kts
+            </span>
+          )}
         </div>
+        {energy !== null && (
+          <div className="flex items-center gap-1 pointer-events-auto">
+            <span className="text-gray-400">BAT</span>
+            <span
+              className={
+                energy.level < 0.2 ? "text-red-400 mr-1" : "text-white mr-1"
+              }
+            >
+              {Math.round(energy.level * 100)}%
+            </span>
+            {SYSTEMS.map(({ system, label }) => (
+              <button
+                key={system}
+                onClick={() => onToggleSystem(system, !energy[system])}
+                className={`px-1.5 rounded transition-all cursor-pointer ${
+                  energy[system]
+                    ? "bg-white/20 text-white"
+                    : "text-gray-400 hover:text-white"
+                }`}
+              >
+                {label}
+              </button>
+            ))}
+          </div>
+        )}
       </div>
     </div>
   );
@@ -1,11 +1,15 @@
+import { useI18n } from "./i18n";
+
 export default function KeyBindings() {
+  const { t } = useI18n();
   const bindings = [
-    { key: "← →", action: "Turn" },
-    { key: "Space", action: "Tack" },
-    { key: "Enter", action: "Lock TWA" },
-    { key: "Shift", action: "Best VMG" },
-    { key: "↑", action: "Zoom in" },
-    { key: "↓", action: "Zoom out" },
+    { key: "← →", action: t("keys.turn") },
+    { key: "- +", action: t("keys.turn") },
+    { key: "Space", action: t("keys.tack") },
+    { key: "Enter", action: t("keys.lockTwa") },
+    { key: "Shift", action: t("keys.bestVmg") },
+    { key: "↑", action: t("keys.zoomIn") },
+    { key: "↓", action: t("keys.zoomOut") },
   ];
 
   return (
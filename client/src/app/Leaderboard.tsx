@@ -1,4 +1,5 @@
 import { LeaderboardEntry } from "../multiplayer/types";
+import { useI18n } from "./i18n";
 
 type Props = {
   entries: LeaderboardEntry[];
@@ -7,25 +8,18 @@ type Props = {
   onQuit: () => void;
 };
 
-function formatDistance(nm: number): string {
-  if (nm < 10) {
-    return `${nm.toFixed(1)} nm`;
-  }
-  return `${Math.round(nm)} nm`;
-}
-
-function formatRaceTime(finishTime: number, startTime: number): string {
-  const elapsedMs = finishTime - startTime;
-  const days = Math.floor(elapsedMs / (24 * 60 * 60 * 1000));
-  const hours = Math.floor(
-    (elapsedMs % (24 * 60 * 60 * 1000)) / (60 * 60 * 1000),
-  );
-  const minutes = Math.floor((elapsedMs % (60 * 60 * 1000)) / (60 * 1000));
-
-  if (days > 0) {
-    return `${days}d ${hours}h ${minutes}m`;
-  }
-  return `${hours}h ${minutes}m`;
+// Time sailed on each lap, the first one from the course start
+function formatLapSplits(
+  lapTimes: number[],
+  startTime: number,
+  formatDuration: (ms: number) => string,
+): string {
+  return lapTimes
+    .map((time, i) => {
+      const from = i === 0 ? startTime : lapTimes[i - 1];
+      return `L${i + 1} ${formatDuration(time - from)}`;
+    })
+    .join(" · ");
 }
 
 export default function Leaderboard({
@@ -34,39 +28,69 @@ export default function Leaderboard({
   courseStartTime,
   onQuit,
 }: Props) {
+  const { t, formatDuration, formatDistance } = useI18n();
   if (entries.length === 0) {
     return null;
   }
 
+  // Entries come grouped by boat class: rank within each class
+  const multiClass = entries.some((e) => e.polar !== entries[0].polar);
+
   return (
     <div className="absolute top-4 left-4 bg-black/60 text-white px-4 py-3 rounded-lg font-mono text-sm min-w-48 pointer-events-auto">
       <div className="text-gray-400 text-xs mb-2 uppercase tracking-wide">
-        Leaderboard
+        {t("leaderboard.title")}
       </div>
       <div className="flex flex-col gap-1">
         {entries.map((entry, index) => {
+          const classStart = entries.findIndex((e) => e.polar === entry.polar);
+          const showClass = multiClass && classStart === index;
           const isMe = entry.playerId === myPlayerId;
           const isGhost = entry.playerId.startsWith("ghost:");
-          const isFinished = entry.finishTime !== null;
+          const isFinished = entry.status === "finished";
+          const isOut = entry.status === "retired" || entry.status === "dnf";
           const nameClass = isMe
             ? "text-pink-400"
             : isGhost
               ? "text-amber-400"
               : "";
           return (
-            <div
-              key={entry.playerId}
-              className={`flex justify-between gap-4 ${nameClass}`}
-            >
-              <span>
-                {index + 1}. {entry.playerName}
-                {isFinished && " \u2713"}
-              </span>
-              <span className={isFinished ? "text-green-400" : "text-gray-400"}>
-                {isFinished
-                  ? formatRaceTime(entry.finishTime!, courseStartTime)
-                  : formatDistance(entry.distanceToNextGate)}
-              </span>
+            <div key={entry.playerId}>
+              {showClass && (
+                <div className="text-gray-500 text-xs mt-1">{entry.polar}</div>
+              )}
+              <div className={`flex justify-between gap-4 ${nameClass}`}>
+                <span>
+                  {index - classStart + 1}. {entry.playerName}
+                  {isFinished && " \u2713"}
+                </span>
+                <span
+                  className={
+                    isFinished
+                      ? "text-green-400"
+                      : isOut
+                        ? "text-red-400"
+                        : "text-gray-400"
+                  }
+                >
+                  {isFinished
+                    ? formatDuration(entry.finishTime! - courseStartTime)
+                    : isOut
+                      ? entry.status === "dnf"
+                        ? t("leaderboard.dnf")
+                        : t("leaderboard.retired")
+                      : formatDistance(entry.distanceToNextGate)}
+                </span>
+              </div>
+              {entry.lapTimes.length > 0 && (
+                <div className="text-gray-500 text-xs pl-4">
+                  {formatLapSplits(
+                    entry.lapTimes,
+                    courseStartTime,
+                    formatDuration,
+                  )}
+                </div>
+              )}
             </div>
           );
         })}
@@ -75,7 +99,7 @@ export default function Leaderboard({
         onClick={onQuit}
         className="mt-3 w-full px-3 py-1.5 text-xs text-slate-400 hover:text-white underline decoration-slate-600 hover:decoration-white rounded transition-all cursor-pointer"
       >
-        Quit race
+        {t("race.quit")}
       </button>
     </div>
   );
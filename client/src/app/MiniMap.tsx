@@ -0,0 +1,141 @@
+import React, { useEffect, useMemo, useRef, useState } from "react";
+import * as d3 from "d3";
+import { Course, LngLat } from "../models";
+import { SphereView } from "../sphere";
+import { getLand, LOW_RES_PATH } from "../sphere/land";
+import { boundsCenter, courseBounds, coursePoints } from "./course-geometry";
+import { courseMark, gateEndpoints } from "./gate-crossing";
+
+type Props = {
+  course: Course;
+  position: LngLat;
+  nextGateIndex: number;
+  sphereView: SphereView | null;
+};
+
+const WIDTH = 200;
+const HEIGHT = 140;
+const PADDING = 8;
+
+// Loaded once, shared by the mini-maps of every race
+let landPromise: Promise<d3.GeoPermissibleObjects> | null = null;
+
+/**
+ * Flat map of the whole course: route, marks, the player's boat and the
+ * other boats, to judge progress the globe view only shows up close.
+ */
+export default React.memo(function MiniMap({
+  course,
+  position,
+  nextGateIndex,
+  sphereView,
+}: Props) {
+  const canvasRef = useRef<HTMLCanvasElement>(null);
+  const [land, setLand] = useState<d3.GeoPermissibleObjects | null>(null);
+
+  useEffect(() => {
+    landPromise ??= getLand(LOW_RES_PATH);
+    landPromise.then(setLand).catch(() => {});
+  }, []);
+
+  // Centered on the course so routes across the antimeridian stay in one piece
+  const projection = useMemo(() => {
+    const center = boundsCenter(courseBounds(course));
+    const projection = d3.geoEquirectangular().rotate([-center.lng, 0]);
+    projection.fitExtent(
+      [
+        [PADDING, PADDING],
+        [WIDTH - PADDING, HEIGHT - PADDING],
+      ],
+      {
+        type: "MultiPoint",
+        coordinates: coursePoints(course).map((p) => [p.lng, p.lat]),
+      },
+    );
+    return projection;
+  }, [course]);
+
+  useEffect(() => {
+    const context = canvasRef.current?.getContext("2d");
+    if (!context) return;
+    const dpr = window.devicePixelRatio || 1;
+    context.setTransform(dpr, 0, 0, dpr, 0, 0);
+    context.clearRect(0, 0, WIDTH, HEIGHT);
+    const path = d3.geoPath(projection, context);
+
+    if (land) {
+      context.fillStyle = "rgba(194, 178, 148, 0.35)";
+      context.beginPath();
+      path(land);
+      context.fill();
+    }
+
+    // Route, leg after leg
+    const marks = [course.start, ...course.gates.map((g) => g.center)];
+    context.strokeStyle = "rgba(250, 204, 21, 0.5)";
+    context.lineWidth = 1;
+    context.beginPath();
+    path({
+      type: "LineString",
+      coordinates: [
+        ...marks.flatMap((mark, leg) => [
+          mark,
+          ...(course.routeWaypoints[leg] ?? []),
+        ]),
+        course.finishLine.center,
+      ].map((p) => [p.lng, p.lat]),
+    });
+    context.stroke();
+
+    // Marks, the next one highlighted
+    const next = courseMark(course, nextGateIndex);
+    [...course.gates, course.finishLine].forEach((gate) => {
+      const [a, b] = gateEndpoints(gate);
+      context.strokeStyle =
+        gate === next ? "rgba(250, 204, 21, 0.9)" : "rgba(34, 197, 94, 0.9)";
+      context.lineWidth = 2;
+      context.beginPath();
+      path({
+        type: "LineString",
+        coordinates: [
+          [a.lng, a.lat],
+          [b.lng, b.lat],
+        ],
+      });
+      context.stroke();
+    });
+
+    const dot = (p: LngLat, radius: number, color: string) => {
+      const xy = projection([p.lng, p.lat]);
+      if (!xy) return;
+      context.fillStyle = color;
+      context.beginPath();
+      context.arc(xy[0], xy[1], radius, 0, 2 * Math.PI);
+      context.fill();
+    };
+
+    // Other players and recorded ghosts, then the player on top
+    if (sphereView) {
+      const { peers, recordedGhosts } = sphereView.ghostBoats;
+      peers.forEach((peer) => {
+        if (peer.position) dot(peer.position, 2, "rgba(148, 163, 184, 0.9)");
+      });
+      recordedGhosts.forEach((ghost) => {
+        dot(ghost, 2, "rgba(148, 163, 184, 0.5)");
+      });
+    }
+    dot(position, 3, "#ffffff");
+  }, [projection, land, course, position, nextGateIndex, sphereView]);
+
+  const dpr = window.devicePixelRatio || 1;
+  return (
+    <div className="absolute top-1/2 right-4 -translate-y-1/2 rounded-lg p-1 bg-black/60">
+      <canvas
+        ref={canvasRef}
+        width={WIDTH * dpr}
+        height={HEIGHT * dpr}
+        style={{ width: WIDTH, height: HEIGHT }}
+      />
+    </div>
+  );
+});
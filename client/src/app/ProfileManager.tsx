@@ -5,7 +5,16 @@ import {
   createProfile,
   updateProfile,
   deleteProfile,
+  uploadAvatar,
+  removeAvatar,
+  downloadAccountData,
+  requestAccountDeletion,
+  cancelAccountDeletion,
+  setRaceSummaryEmails,
 } from "./account";
+import ApiTokens from "./ApiTokens";
+import Avatar from "./Avatar";
+import Sessions from "./Sessions";
 
 type ProfileManagerProps = {
   account: Account;
@@ -74,6 +83,44 @@ export default function ProfileManager({
     }
   };
 
+  const handleAvatarFile = (profileId: string, file: File | undefined) => {
+    if (!file) return;
+    handleAccountAction(
+      () => uploadAvatar(account, profileId, file),
+      "Failed to upload avatar"
+    );
+  };
+
+  const handleAccountAction = async (
+    action: () => Promise<Account | void>,
+    failure: string
+  ) => {
+    setLoading(true);
+    setError(null);
+
+    try {
+      const updated = await action();
+      if (updated) onAccountChange(updated);
+    } catch (err) {
+      setError(err instanceof Error ? err.message : failure);
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleDeleteAccount = () => {
+    if (
+      !confirm(
+        "Delete your account? It is deleted in 14 days unless you cancel, and your results stay on the leaderboards anonymously."
+      )
+    )
+      return;
+    handleAccountAction(
+      () => requestAccountDeletion(account),
+      "Failed to delete account"
+    );
+  };
+
   const handleDelete = async (profileId: string) => {
     if (account.profiles.length <= 1) {
       setError("Cannot delete the last profile");
@@ -137,9 +184,22 @@ export default function ProfileManager({
               key={profile.id}
               className="flex items-center gap-2 bg-slate-800 rounded-lg px-3 py-2"
             >
-              <div className="w-8 h-8 rounded-full bg-gradient-to-br from-cyan-400 to-blue-500 flex items-center justify-center text-white text-sm font-bold flex-shrink-0">
-                {profile.name.charAt(0).toUpperCase()}
-              </div>
+              <label
+                className="cursor-pointer hover:opacity-80"
+                title="Change avatar"
+              >
+                <Avatar name={profile.name} url={profile.avatarUrl} />
+                <input
+                  type="file"
+                  accept="image/*"
+                  className="hidden"
+                  disabled={loading}
+                  onChange={(e) => {
+                    handleAvatarFile(profile.id, e.target.files?.[0]);
+                    e.target.value = "";
+                  }}
+                />
+              </label>
 
               {editingId === profile.id ? (
                 <>
@@ -203,6 +263,21 @@ export default function ProfileManager({
                       Active
                     </span>
                   )}
+                  {profile.avatarUrl && (
+                    <button
+                      onClick={() =>
+                        handleAccountAction(
+                          () => removeAvatar(account, profile.id),
+                          "Failed to remove avatar"
+                        )
+                      }
+                      disabled={loading}
+                      className="text-slate-400 hover:text-white text-xs p-1"
+                      title="Remove avatar"
+                    >
+                      Remove avatar
+                    </button>
+                  )}
                   <button
                     onClick={() => handleStartEdit(profile)}
                     className="text-slate-400 hover:text-white p-1"
@@ -272,6 +347,74 @@ export default function ProfileManager({
         <p className="text-slate-500 text-xs mt-3">
           Maximum 10 profiles per account
         </p>
+
+        <Sessions account={account} />
+
+        <ApiTokens account={account} />
+
+        {/* Account data */}
+        <div className="border-t border-slate-800 mt-4 pt-4">
+          {account.deleteAt && (
+            <div className="bg-red-900/30 border border-red-800 rounded-lg px-3 py-2 mb-3">
+              <p className="text-red-400 text-sm">
+                This account will be deleted on{" "}
+                {new Date(account.deleteAt).toLocaleDateString()}.
+              </p>
+            </div>
+          )}
+          <label className="flex items-center gap-2 text-slate-400 text-sm cursor-pointer mb-3">
+            <input
+              type="checkbox"
+              checked={account.raceSummaryEmails ?? true}
+              onChange={(e) => {
+                const enabled = e.target.checked;
+                handleAccountAction(
+                  () => setRaceSummaryEmails(account, enabled),
+                  "Failed to update email preferences"
+                );
+              }}
+              disabled={loading}
+              className="accent-blue-500"
+            />
+            Email me a summary after each finish
+          </label>
+          <div className="flex gap-2 text-sm">
+            <button
+              onClick={() =>
+                handleAccountAction(
+                  () => downloadAccountData(account),
+                  "Failed to export account data"
+                )
+              }
+              disabled={loading}
+              className="flex-1 text-slate-400 hover:text-white py-2 border border-slate-700 hover:border-slate-500 rounded-lg transition-all"
+            >
+              Download my data
+            </button>
+            {account.deleteAt ? (
+              <button
+                onClick={() =>
+                  handleAccountAction(
+                    () => cancelAccountDeletion(account),
+                    "Failed to cancel account deletion"
+                  )
+                }
+                disabled={loading}
+                className="flex-1 text-white bg-slate-700 hover:bg-slate-600 py-2 rounded-lg transition-all"
+              >
+                Keep my account
+              </button>
+            ) : (
+              <button
+                onClick={handleDeleteAccount}
+                disabled={loading}
+                className="flex-1 text-red-400 hover:text-red-300 py-2 border border-red-900 hover:border-red-700 rounded-lg transition-all"
+              >
+                Delete account
+              </button>
+            )}
+          </div>
+        </div>
       </div>
     </div>
   );
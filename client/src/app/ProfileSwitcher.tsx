@@ -1,5 +1,6 @@
 import { useState, useRef, useEffect } from "react";
 import { Account, Profile, setActiveProfile, logout } from "./account";
+import Avatar from "./Avatar";
 
 type ProfileSwitcherProps = {
   account: Account;
@@ -54,9 +55,11 @@ export default function ProfileSwitcher({
         onClick={() => setIsOpen(!isOpen)}
         className="flex items-center gap-2 bg-slate-800 hover:bg-slate-700 px-3 py-2 rounded-lg transition-all"
       >
-        <div className="w-6 h-6 rounded-full bg-gradient-to-br from-cyan-400 to-blue-500 flex items-center justify-center text-white text-xs font-bold">
-          {activeProfile?.name.charAt(0).toUpperCase() ?? "?"}
-        </div>
+        <Avatar
+          name={activeProfile?.name ?? "?"}
+          url={activeProfile?.avatarUrl}
+          className="w-6 h-6 text-xs"
+        />
         <span className="text-white text-sm max-w-24 truncate">
           {activeProfile?.name ?? "Select profile"}
         </span>
@@ -9,24 +9,58 @@ import {
   saveAccount,
   getActiveProfile,
   refreshAccount,
+  completeSignInRedirect,
 } from "./account";
 import AuthModal from "./AuthModal";
 import ProfileSwitcher from "./ProfileSwitcher";
 import ProfileManager from "./ProfileManager";
-import { formatDuration } from "../utils";
+import Avatar from "./Avatar";
+import { formatSplits, isCourseOpen } from "../utils";
+import { LANGUAGES, useI18n } from "./i18n";
+import { Course, CourseMetrics, CourseTag, COURSE_TAGS } from "../models";
 
 const PLAYER_NAME_KEY = "rewind:player_name";
 const serverUrl = import.meta.env.REWIND_SERVER_URL;
 
+function formatMetrics(
+  metrics: CourseMetrics,
+  formatDistance: (nm: number) => string,
+): string {
+  const route = formatDistance(metrics.routeNm);
+  const avg = Math.round(metrics.avgTws);
+  const max = Math.round(metrics.maxTws);
+  const difficulty = metrics.difficulty.toFixed(1);
+  return `${route} · wind ${avg} kts avg, ${max} max · difficulty ${difficulty}/10`;
+}
+
+type DailyChallenge = {
+  date: string;
+  courseKey: string;
+  startTime: number;
+  resetsAt: number;
+};
+
 type HallOfFameEntry = {
   id: number;
   rank: number;
   playerName: string;
   playerId: string | null;
+  avatarUrl: string | null;
   finishTime: number;
   raceDate: number;
+  /** Elapsed time at each mark before the finish (ms) */
+  splits: number[];
 };
 
+/** Race dates a Hall of Fame ranks: all time, this month or the current season */
+type LeaderboardPeriod = "all" | "month" | "season";
+
+const LEADERBOARD_PERIODS: { period: LeaderboardPeriod; label: string }[] = [
+  { period: "all", label: "All time" },
+  { period: "month", label: "Month" },
+  { period: "season", label: "Season" },
+];
+
 export default function RaceChoiceScreen() {
   const {
     raceId,
@@ -38,17 +72,25 @@ export default function RaceChoiceScreen() {
     recordedGhosts,
     createRace,
     joinRace,
+    quickMatch,
+    startDaily,
+    matchQueue,
     startRace,
     leaveRace,
     selectCourse,
     openAdmin,
     addGhost,
+    addServerGhosts,
     removeGhost,
+    watchReplay,
   } = useRaceContext();
+  const { t, language, setLanguage, formatDuration, formatDistance } =
+    useI18n();
 
   // Account state
   const [account, setAccount] = useState<Account | null>(() => loadAccount());
   const [showAuthModal, setShowAuthModal] = useState(false);
+  const [authError, setAuthError] = useState<string | null>(null);
   const [showProfileManager, setShowProfileManager] = useState(false);
 
   // Player name for guests (accounts use profile name)
@@ -56,6 +98,16 @@ export default function RaceChoiceScreen() {
 
   const [availableRaces, setAvailableRaces] = useState<RaceInfo[]>([]);
   const [hallOfFame, setHallOfFame] = useState<HallOfFameEntry[]>([]);
+  const [hallOfFamePeriod, setHallOfFamePeriod] =
+    useState<LeaderboardPeriod>("all");
+  const [randomWeather, setRandomWeather] = useState(false);
+  const [boatClass, setBoatClass] = useState<string | null>(null);
+  const [daily, setDaily] = useState<DailyChallenge | null>(null);
+  const [courseTag, setCourseTag] = useState<CourseTag | null>(null);
+  const [taggedKeys, setTaggedKeys] = useState<Set<string> | null>(null);
+  const [dailyLeaderboard, setDailyLeaderboard] = useState<HallOfFameEntry[]>(
+    [],
+  );
 
   // Get current player ID (from account or guest mode)
   const myPersistentId = useMemo(() => getOrCreatePlayerId(), [account]);
@@ -76,6 +128,22 @@ export default function RaceChoiceScreen() {
 
   const selectedCourse = courses.find((c) => c.key === selectedCourseKey);
 
+  // Boat classes of the selected course, the default polar first
+  const boatClasses = useMemo(() => {
+    if (!selectedCourse) return [];
+    return [
+      selectedCourse.polar,
+      ...(selectedCourse.boatClasses ?? []).filter(
+        (c) => c !== selectedCourse.polar,
+      ),
+    ];
+  }, [selectedCourse]);
+
+  // Back to the default class when switching course
+  useEffect(() => {
+    setBoatClass(null);
+  }, [selectedCourseKey]);
+
   // Load guest player name from localStorage on mount
   useEffect(() => {
     if (!account) {
@@ -95,6 +163,16 @@ export default function RaceChoiceScreen() {
   // (including account in deps would loop since refresh can update it).
   const mountAccountRef = useRef(account);
   useEffect(() => {
+    // Back from an OAuth sign in or a magic link
+    completeSignInRedirect()
+      .then((signedIn) => {
+        if (signedIn) setAccount(signedIn);
+      })
+      .catch((err) => {
+        setAuthError(err instanceof Error ? err.message : "Sign in failed");
+        setShowAuthModal(true);
+      });
+
     const a = mountAccountRef.current;
     if (a) {
       refreshAccount(a).then((updated) => {
@@ -140,19 +218,78 @@ export default function RaceChoiceScreen() {
 
     const fetchHallOfFame = async () => {
       try {
+        const polarParam = boatClass
+          ? `&polar=${encodeURIComponent(boatClass)}`
+          : "";
         const res = await fetch(
-          `${serverUrl}/leaderboard/${selectedCourseKey}?limit=10`,
+          `${serverUrl}/leaderboard/${selectedCourseKey}?limit=10${polarParam}&period=${hallOfFamePeriod}`,
         );
-        if (res.ok) {
-          setHallOfFame(await res.json());
-        }
+        // No season is running when the current one isn't found
+        setHallOfFame(res.ok ? await res.json() : []);
       } catch (err) {
         console.error("Failed to fetch leaderboard:", err);
       }
     };
 
     fetchHallOfFame();
-  }, [selectedCourseKey]);
+  }, [selectedCourseKey, boatClass, hallOfFamePeriod]);
+
+  // Fetch the courses of the picked tag
+  useEffect(() => {
+    if (!courseTag) {
+      setTaggedKeys(null);
+      return;
+    }
+    const controller = new AbortController();
+    const fetchTagged = async () => {
+      try {
+        const res = await fetch(`${serverUrl}/courses?tag=${courseTag}`, {
+          signal: controller.signal,
+        });
+        const tagged: Course[] = await res.json();
+        setTaggedKeys(new Set(tagged.map((c) => c.key)));
+      } catch (err) {
+        if (err instanceof DOMException && err.name === "AbortError") return;
+        console.error("Failed to fetch courses:", err);
+      }
+    };
+
+    fetchTagged();
+    return () => controller.abort();
+  }, [courseTag]);
+
+  // Courses races can be created on, of the picked tag
+  const listedCourses = courses.filter(
+    (c) =>
+      isCourseOpen(c, Date.now()) && (!taggedKeys || taggedKeys.has(c.key)),
+  );
+
+  // Fetch today's daily challenge and its leaderboard
+  useEffect(() => {
+    const controller = new AbortController();
+    const fetchDaily = async () => {
+      try {
+        const [challengeRes, leaderboardRes] = await Promise.all([
+          fetch(`${serverUrl}/daily`, { signal: controller.signal }),
+          fetch(`${serverUrl}/daily/leaderboard?limit=3`, {
+            signal: controller.signal,
+          }),
+        ]);
+        if (challengeRes.ok) setDaily(await challengeRes.json());
+        if (leaderboardRes.ok) setDailyLeaderboard(await leaderboardRes.json());
+      } catch (err) {
+        if (err instanceof DOMException && err.name === "AbortError") return;
+        console.error("Failed to fetch daily challenge:", err);
+      }
+    };
+
+    fetchDaily();
+    return () => controller.abort();
+  }, []);
+
+  const dailyCourse = daily
+    ? courses.find((c) => c.key === daily.courseKey)
+    : undefined;
 
   const handleGuestPlayerNameChange = (newName: string) => {
     setGuestPlayerName(newName);
@@ -182,11 +319,26 @@ export default function RaceChoiceScreen() {
   }, [account, guestPlayerName]);
 
   const handleJoinRace = (targetRaceId: string) => {
-    joinRace(targetRaceId, getPlayerNameForRace());
+    // The picked class only applies to races on the selected course
+    const race = availableRaces.find((r) => r.id === targetRaceId);
+    const polar = race?.course_key === selectedCourseKey ? boatClass : null;
+    joinRace(targetRaceId, getPlayerNameForRace(), polar);
   };
 
   const handleCreateRace = () => {
-    createRace(getPlayerNameForRace());
+    createRace(getPlayerNameForRace(), false, randomWeather, boatClass);
+  };
+
+  const handleStartDaily = () => {
+    startDaily(getPlayerNameForRace());
+  };
+
+  const handleQuickMatch = () => {
+    quickMatch(getPlayerNameForRace(), boatClass);
+  };
+
+  const handleSoloRace = () => {
+    createRace(getPlayerNameForRace(), true, randomWeather, boatClass);
   };
 
   const handleAuthSuccess = (newAccount: Account) => {
@@ -206,6 +358,22 @@ export default function RaceChoiceScreen() {
 
   return (
     <div className="fixed inset-0 flex flex-col items-center justify-center bg-black bg-opacity-10">
+      <div className="absolute top-4 right-4 flex gap-2">
+        {LANGUAGES.map(({ code, name }) => (
+          <button
+            key={code}
+            onClick={() => setLanguage(code)}
+            title={name}
+            className={`text-xs uppercase transition-all ${
+              code === language
+                ? "text-white"
+                : "text-slate-500 hover:text-slate-300"
+            }`}
+          >
+            {code}
+          </button>
+        ))}
+      </div>
       <h1 className="logo mb-6">Re:wind</h1>
 
       <div className="bg-slate-900 bg-opacity-80 rounded-xl p-8 w-full max-w-3xl mx-4 flex gap-8">
@@ -215,14 +383,14 @@ export default function RaceChoiceScreen() {
           <div>
             <div className="flex items-center justify-between mb-2">
               <h2 className="text-slate-400 text-xs uppercase tracking-wide">
-                {account ? "Profile" : "Player Name"}
+                {account ? t("choice.profile") : t("choice.playerName")}
               </h2>
               {!account && (
                 <button
                   onClick={() => setShowAuthModal(true)}
                   className="text-xs text-blue-400 hover:text-blue-300 transition-all"
                 >
-                  Sign In
+                  {t("choice.signIn")}
                 </button>
               )}
             </div>
@@ -258,13 +426,52 @@ export default function RaceChoiceScreen() {
             )}
           </div>
 
+          {/* Daily challenge */}
+          {!inRace && daily && dailyCourse && (
+            <div>
+              <h2 className="text-slate-400 text-xs uppercase tracking-wide mb-2">
+                {t("choice.daily")}
+              </h2>
+              <div className="bg-slate-800 rounded-lg px-4 py-3 space-y-2">
+                <div className="flex items-center justify-between">
+                  <div>
+                    <div className="text-white text-sm">{dailyCourse.name}</div>
+                    <div className="text-slate-500 text-xs">
+                      Weather of{" "}
+                      {new Date(daily.startTime).toISOString().slice(0, 10)}
+                    </div>
+                  </div>
+                  <button
+                    onClick={handleStartDaily}
+                    className="bg-amber-500 hover:bg-amber-400 text-white px-4 py-2 rounded-lg text-sm font-semibold transition-all"
+                  >
+                    {t("choice.play")}
+                  </button>
+                </div>
+                {dailyLeaderboard.map((entry) => (
+                  <div
+                    key={entry.id}
+                    className="flex items-center justify-between text-xs"
+                  >
+                    <span className="text-slate-300">
+                      #{entry.rank} {entry.playerName}
+                    </span>
+                    <span className="text-green-400 font-mono">
+                      {formatDuration(entry.finishTime)}
+                    </span>
+                  </div>
+                ))}
+              </div>
+            </div>
+          )}
+
           {/* Courses */}
           <div>
             <div className="flex items-center justify-between mb-2">
               <h2 className="text-slate-400 text-xs uppercase tracking-wide">
-                Courses
+                {t("choice.courses")}
               </h2>
-              {account?.isAdmin && (
+              {!!account?.roles?.length && (
                 <button
                   onClick={openAdmin}
                   className="text-xs text-slate-500 hover:text-slate-300 transition-all"
@@ -273,8 +480,23 @@ export default function RaceChoiceScreen() {
                 </button>
               )}
             </div>
+            <div className="flex flex-wrap gap-1 mb-2">
+              {COURSE_TAGS.map((tag) => (
+                <button
+                  key={tag}
+                  onClick={() => setCourseTag(courseTag === tag ? null : tag)}
+                  className={`px-2 py-0.5 rounded text-xs capitalize transition-all ${
+                    courseTag === tag
+                      ? "bg-blue-600 text-white"
+                      : "bg-slate-800 text-slate-400 hover:text-white"
+                  }`}
+                >
+                  {tag}
+                </button>
+              ))}
+            </div>
             <div className="bg-slate-800 rounded-lg divide-y divide-slate-700 max-h-48 overflow-y-auto">
-              {courses.map((course) => (
+              {listedCourses.map((course) => (
                 <button
                   key={course.key}
                   onClick={() => selectCourse(course.key)}
@@ -292,6 +514,15 @@ export default function RaceChoiceScreen() {
                     }`}
                   />
                   {course.name}
+                  {course.metrics && (
+                    <span
+                      className="ml-auto text-xs opacity-60"
+                      title="Route length · difficulty"
+                    >
+                      {formatDistance(course.metrics.routeNm)} ·{" "}
+                      {course.metrics.difficulty.toFixed(1)}
+                    </span>
+                  )}
                 </button>
               ))}
             </div>
@@ -301,7 +532,7 @@ export default function RaceChoiceScreen() {
           {availableRaces.length > 0 && (
             <div>
               <h2 className="text-slate-400 text-xs uppercase tracking-wide mb-2">
-                Open Races
+                {t("choice.openRaces")}
               </h2>
               <div className="space-y-1">
                 {availableRaces.map((race) => {
@@ -364,13 +595,35 @@ export default function RaceChoiceScreen() {
                 <p className="text-slate-500 text-xs mt-1">
                   {selectedCourse.timeFactor}x time acceleration
                 </p>
+                {selectedCourse.metrics && (
+                  <p className="text-slate-500 text-xs mt-1">
+                    {formatMetrics(selectedCourse.metrics, formatDistance)}
+                  </p>
+                )}
               </div>
 
               {/* Hall of Fame */}
               <div>
-                <h3 className="text-amber-400 text-xs uppercase tracking-wide mb-2">
-                  Hall of Fame
-                </h3>
+                <div className="flex items-center justify-between mb-2">
+                  <h3 className="text-amber-400 text-xs uppercase tracking-wide">
+                    {t("choice.hallOfFame")}
+                  </h3>
+                  <div className="flex gap-2">
+                    {LEADERBOARD_PERIODS.map(({ period, label }) => (
+                      <button
+                        key={period}
+                        onClick={() => setHallOfFamePeriod(period)}
+                        className={`text-xs ${
+                          hallOfFamePeriod === period
+                            ? "text-amber-400"
+                            : "text-slate-500 hover:text-slate-300"
+                        }`}
+                      >
+                        {label}
+                      </button>
+                    ))}
+                  </div>
+                </div>
                 {hallOfFame.length === 0 ? (
                   <div className="text-slate-500 text-sm py-2">
                     No records yet. Be the first!
@@ -398,14 +651,30 @@ export default function RaceChoiceScreen() {
                             >
                               #{entry.rank}
                             </span>
+                            <Avatar
+                              name={entry.playerName}
+                              url={entry.avatarUrl}
+                              className="w-6 h-6 text-xs"
+                            />
                             <span className={`text-sm ${isMe ? "text-cyan-300" : "text-white"}`}>
                               {entry.playerName}
                               {isMe && <span className="text-cyan-400 text-xs ml-1">(you)</span>}
                             </span>
                           </div>
-                          <span className="text-green-400 font-mono text-xs">
-                            {formatDuration(entry.finishTime)}
-                          </span>
+                          <div className="flex items-center gap-3">
+                            <span
+                              className="text-green-400 font-mono text-xs"
+                              title={formatSplits(entry.splits)}
+                            >
+                              {formatDuration(entry.finishTime)}
+                            </span>
+                            <button
+                              onClick={() => watchReplay(entry.id)}
+                              className="text-blue-400 hover:text-blue-300 text-xs"
+                            >
+                              Watch
+                            </button>
+                          </div>
                         </div>
                       );
                     })}
@@ -413,6 +682,33 @@ export default function RaceChoiceScreen() {
                 )}
               </div>
 
+              <label className="flex items-center gap-2 text-slate-400 text-sm cursor-pointer">
+                <input
+                  type="checkbox"
+                  checked={randomWeather}
+                  onChange={(e) => setRandomWeather(e.target.checked)}
+                  className="accent-blue-500"
+                />
+                Random historical weather
+              </label>
+
+              {boatClasses.length > 1 && (
+                <label className="flex items-center justify-between gap-2 text-slate-400 text-sm">
+                  Boat class
+                  <select
+                    value={boatClass ?? boatClasses[0]}
+                    onChange={(e) => setBoatClass(e.target.value)}
+                    className="bg-slate-800 text-white rounded-lg px-2 py-1"
+                  >
+                    {boatClasses.map((c) => (
+                      <option key={c} value={c}>
+                        {c}
+                      </option>
+                    ))}
+                  </select>
+                </label>
+              )}
+
               {/* Race this button */}
               <button
                 onClick={handleCreateRace}
@@ -420,6 +716,34 @@ export default function RaceChoiceScreen() {
               >
                 Race this
               </button>
+              {matchQueue ? (
+                <div className="flex items-center justify-between bg-slate-800 rounded-lg px-4 py-3">
+                  <span className="flex items-center gap-2 text-slate-300 text-sm">
+                    <span className="w-4 h-4 border-2 border-slate-500 border-t-blue-400 rounded-full animate-spin" />
+                    Looking for players ({matchQueue.queuedPlayers}/
+                    {matchQueue.minPlayers})...
+                  </span>
+                  <button
+                    onClick={leaveRace}
+                    className="text-slate-500 hover:text-slate-300 text-sm"
+                  >
+                    Cancel
+                  </button>
+                </div>
+              ) : (
+                <button
+                  onClick={handleQuickMatch}
+                  className="w-full bg-slate-700 hover:bg-slate-600 text-white py-3 rounded-lg font-semibold transition-all"
+                >
+                  Quick match
+                </button>
+              )}
+              <button
+                onClick={handleSoloRace}
+                className="w-full text-slate-400 hover:text-slate-200 py-2 text-sm transition-all"
+              >
+                Solo time trial
+              </button>
             </div>
           )}
 
@@ -429,7 +753,7 @@ export default function RaceChoiceScreen() {
               {/* Competitors */}
               <div>
                 <h2 className="text-slate-400 text-xs uppercase tracking-wide mb-2">
-                  Competitors
+                  {t("choice.competitors")}
                 </h2>
                 <div className="bg-slate-800 rounded-lg divide-y divide-slate-700">
                   {/* You */}
@@ -480,9 +804,19 @@ export default function RaceChoiceScreen() {
 
               {/* Hall of Fame for ghost selection */}
               <div>
-                <h3 className="text-amber-400 text-xs uppercase tracking-wide mb-2">
-                  Add Ghosts
-                </h3>
+                <div className="flex items-center justify-between mb-2">
+                  <h3 className="text-amber-400 text-xs uppercase tracking-wide">
+                    {t("choice.addGhosts")}
+                  </h3>
+                  {hallOfFame.length > 0 && (
+                    <button
+                      onClick={() => addServerGhosts(3)}
+                      className="text-blue-400 hover:text-blue-300 text-xs"
+                    >
+                      Race top 3
+                    </button>
+                  )}
+                </div>
                 {hallOfFame.length === 0 ? (
                   <div className="text-slate-500 text-sm py-2">
                     No records yet.
@@ -511,13 +845,21 @@ export default function RaceChoiceScreen() {
                             >
                               #{entry.rank}
                             </span>
+                            <Avatar
+                              name={entry.playerName}
+                              url={entry.avatarUrl}
+                              className="w-6 h-6 text-xs"
+                            />
                             <span className={`text-sm ${isMe ? "text-cyan-300" : "text-white"}`}>
                               {entry.playerName}
                               {isMe && <span className="text-cyan-400 text-xs ml-1">(you)</span>}
                             </span>
                           </div>
                           <div className="flex items-center gap-3">
-                            <span className="text-green-400 font-mono text-xs">
+                            <span
+                              className="text-green-400 font-mono text-xs"
+                              title={formatSplits(entry.splits)}
+                            >
                               {formatDuration(entry.finishTime)}
                             </span>
                             {isAdded ? (
@@ -585,8 +927,12 @@ export default function RaceChoiceScreen() {
       {/* Auth Modal */}
       {showAuthModal && (
         <AuthModal
-          onClose={() => setShowAuthModal(false)}
+          onClose={() => {
+            setShowAuthModal(false);
+            setAuthError(null);
+          }}
           onSuccess={handleAuthSuccess}
+          initialError={authError}
         />
       )}
 
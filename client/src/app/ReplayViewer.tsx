@@ -0,0 +1,110 @@
+import { useEffect } from "react";
+import { formatDuration } from "../utils";
+import RaceTimer from "./RaceTimer";
+import { REPLAY_SPEEDS, type ReplayState } from "./hooks/useReplay";
+
+type Props = {
+  state: ReplayState;
+  onClose: () => void;
+};
+
+export default function ReplayViewer({ state, onClose }: Props) {
+  const { replay, error, courseTime, playing, speed } = state;
+  const end = replay ? replay.path[replay.path.length - 1].raceTime : 0;
+
+  // Play again from the start once the end is reached
+  const togglePlaying = () => {
+    if (!replay) return;
+    if (!playing && courseTime >= end) {
+      state.seek(replay.path[0].raceTime);
+    }
+    state.setPlaying(!playing);
+  };
+
+  // Space plays and pauses, Escape closes the replay
+  useEffect(() => {
+    const handleKeyDown = (e: KeyboardEvent) => {
+      if (e.key === " ") {
+        e.preventDefault();
+        togglePlaying();
+      } else if (e.key === "Escape") {
+        onClose();
+      }
+    };
+    window.addEventListener("keydown", handleKeyDown);
+    return () => window.removeEventListener("keydown", handleKeyDown);
+  });
+
+  if (error) {
+    return (
+      <div className="fixed inset-0 flex items-center justify-center pointer-events-auto">
+        <div className="bg-slate-900 rounded-lg p-6 w-80 text-center">
+          <p className="text-slate-300 mb-4">{error}</p>
+          <button
+            onClick={onClose}
+            className="text-sm text-slate-400 hover:text-white py-2 px-4 border border-slate-700 rounded transition-all"
+          >
+            Back
+          </button>
+        </div>
+      </div>
+    );
+  }
+
+  if (!replay) {
+    return (
+      <div className="fixed bottom-16 inset-x-0 flex justify-center text-slate-300 text-sm">
+        Loading replay...
+      </div>
+    );
+  }
+
+  return (
+    <>
+      <RaceTimer courseTime={courseTime} startTime={replay.startTime} />
+      <div className="absolute top-4 left-4 bg-black/60 text-white px-3 py-2 rounded-lg text-sm">
+        <div>{replay.playerName}</div>
+        <div className="text-slate-400 text-xs">
+          {replay.course.name} · {formatDuration(replay.finishTime)}
+        </div>
+      </div>
+      <div className="fixed bottom-4 inset-x-4 flex items-center gap-3 bg-black/60 text-white px-4 py-2 rounded-lg pointer-events-auto">
+        <button
+          onClick={togglePlaying}
+          className="w-16 text-sm text-blue-400 hover:text-blue-300"
+        >
+          {playing ? "Pause" : "Play"}
+        </button>
+        <input
+          type="range"
+          min={replay.path[0].raceTime}
+          max={end}
+          value={courseTime}
+          onChange={(e) => state.seek(Number(e.target.value))}
+          className="flex-1 accent-blue-500"
+        />
+        <div className="flex gap-2">
+          {REPLAY_SPEEDS.map((s) => (
+            <button
+              key={s}
+              onClick={() => state.setSpeed(s)}
+              className={`text-xs font-mono ${
+                s === speed
+                  ? "text-amber-400"
+                  : "text-slate-400 hover:text-slate-300"
+              }`}
+            >
+              {s}×
+            </button>
+          ))}
+        </div>
+        <button
+          onClick={onClose}
+          className="text-sm text-slate-400 hover:text-white"
+        >
+          Close
+        </button>
+      </div>
+    </>
+  );
+}
@@ -0,0 +1,73 @@
+import { useEffect, useState } from "react";
+import { Account, Session, fetchSessions, revokeSession } from "./account";
+
+type Props = {
+  account: Account;
+};
+
+/**
+ * Devices signed in to the account, each of which can be signed out.
+ */
+export default function Sessions({ account }: Props) {
+  const [sessions, setSessions] = useState<Session[]>([]);
+  const [error, setError] = useState<string | null>(null);
+  const [loading, setLoading] = useState(false);
+
+  useEffect(() => {
+    fetchSessions(account)
+      .then(setSessions)
+      .catch(() => setSessions([]));
+  }, [account.sessionToken]);
+
+  const handleRevoke = async (sessionId: string) => {
+    setLoading(true);
+    setError(null);
+
+    try {
+      await revokeSession(account, sessionId);
+      setSessions(sessions.filter((s) => s.id !== sessionId));
+    } catch (err) {
+      setError(err instanceof Error ? err.message : "Failed to sign out");
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  return (
+    <div className="border-t border-slate-800 mt-4 pt-4 text-sm">
+      <h3 className="text-white font-medium mb-2">Signed in devices</h3>
+
+      {error && <p className="text-red-400 text-xs mb-2">{error}</p>}
+
+      <div className="space-y-1">
+        {sessions.map((session) => (
+          <div
+            key={session.id}
+            className="flex items-center gap-2 bg-slate-800 rounded-lg px-3 py-1.5"
+          >
+            <div className="flex-1 min-w-0">
+              <div className="text-white truncate">
+                {session.userAgent ?? "Unknown device"}
+              </div>
+              <div className="text-slate-500 text-xs truncate">
+                Signed in {new Date(session.createdAt).toLocaleDateString()} ·
+                active {new Date(session.lastActiveAt).toLocaleDateString()}
+              </div>
+            </div>
+            {session.current ? (
+              <span className="text-xs text-cyan-400">This device</span>
+            ) : (
+              <button
+                onClick={() => handleRevoke(session.id)}
+                disabled={loading}
+                className="text-slate-400 hover:text-red-400 text-xs"
+              >
+                Sign out
+              </button>
+            )}
+          </div>
+        ))}
+      </div>
+    </div>
+  );
+}
@@ -1,17 +1,28 @@
+import { loadLanguage } from "./i18n";
+
 const serverUrl = import.meta.env.REWIND_SERVER_URL;
 const ACCOUNT_KEY = "rewind:account";
 
 export type Profile = {
   id: string;
   name: string;
+  avatarUrl?: string | null;
 };
 
+/** Staff roles, each giving access to parts of the admin */
+export type Role = "admin" | "moderator" | "course-editor";
+
 export type Account = {
   email: string;
   sessionToken: string;
   profiles: Profile[];
   activeProfileId: string;
   isAdmin: boolean;
+  roles?: Role[];
+  /** Set while the account is scheduled for deletion (unix ms) */
+  deleteAt?: number | null;
+  /** Summary emailed after each finish */
+  raceSummaryEmails?: boolean;
 };
 
 // ===== Local Storage =====
@@ -64,7 +75,7 @@ export async function startAuth(email: string): Promise<void> {
   const res = await fetch(`${serverUrl}/auth/start`, {
     method: "POST",
     headers: { "Content-Type": "application/json" },
-    body: JSON.stringify({ email }),
+    body: JSON.stringify({ email, language: loadLanguage() }),
   });
   if (!res.ok) {
     const text = await res.text();
@@ -72,11 +83,32 @@ export async function startAuth(email: string): Promise<void> {
   }
 }
 
+/**
+ * Send a one-click sign in link, landing back on the current page.
+ */
+export async function startMagicLink(email: string): Promise<void> {
+  const res = await fetch(`${serverUrl}/auth/start`, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({
+      email,
+      mode: "magic",
+      redirect: window.location.pathname,
+      language: loadLanguage(),
+    }),
+  });
+  if (!res.ok) {
+    const text = await res.text();
+    throw new Error(text || "Failed to send sign in link");
+  }
+}
+
 type VerifyAuthResponse = {
   accountId: string;
   sessionToken: string;
   profiles: Profile[];
   isAdmin: boolean;
+  roles: Role[];
 };
 
 export async function verifyAuth(
@@ -101,6 +133,7 @@ export async function verifyAuth(
     profiles: data.profiles,
     activeProfileId: data.profiles[0]?.id ?? "",
     isAdmin: data.isAdmin,
+    roles: data.roles,
   };
 
   saveAccount(account);
@@ -128,8 +161,14 @@ export async function refreshAccount(account: Account): Promise<Account | null>
       clearAccount();
       return null;
     }
-    const data: { isAdmin: boolean } = await res.json();
-    const updated = { ...account, isAdmin: data.isAdmin };
+    const data: AccountMeResponse = await res.json();
+    const updated = {
+      ...account,
+      isAdmin: data.isAdmin,
+      roles: data.roles,
+      deleteAt: data.deleteAt,
+      raceSummaryEmails: data.raceSummaryEmails,
+    };
     saveAccount(updated);
     return updated;
   } catch {
@@ -138,6 +177,246 @@ export async function refreshAccount(account: Account): Promise<Account | null>
   }
 }
 
+// ===== Sign in by redirect =====
+
+export const OAUTH_PROVIDER_NAMES: Record<string, string> = {
+  google: "Google",
+  github: "GitHub",
+};
+
+type AccountMeResponse = {
+  email: string;
+  profiles: Profile[];
+  isAdmin: boolean;
+  roles: Role[];
+  deleteAt: number | null;
+  raceSummaryEmails: boolean;
+};
+
+export async function fetchAuthProviders(): Promise<string[]> {
+  try {
+    const res = await fetch(`${serverUrl}/auth/providers`);
+    return res.ok ? res.json() : [];
+  } catch {
+    return [];
+  }
+}
+
+export function oauthUrl(provider: string): string {
+  return `${serverUrl}/auth/oauth/${provider}`;
+}
+
+/**
+ * Complete a sign in by redirect (OAuth, magic link): the server sends the
+ * browser back with the session token, or the error, in the URL fragment.
+ * Returns null when the page wasn't opened by a sign in.
+ */
+export async function completeSignInRedirect(): Promise<Account | null> {
+  const params = new URLSearchParams(window.location.hash.slice(1));
+  const token = params.get("session");
+  const error = params.get("authError");
+  if (!token && !error) return null;
+
+  // Keep the token out of the history
+  history.replaceState(
+    null,
+    "",
+    window.location.pathname + window.location.search
+  );
+  if (!token) throw new Error(error ?? "Sign in failed");
+
+  const res = await fetch(`${serverUrl}/account/me`, {
+    headers: { Authorization: `Bearer ${token}` },
+  });
+  if (!res.ok) throw new Error("Sign in failed");
+  const data: AccountMeResponse = await res.json();
+
+  const account: Account = {
+    email: data.email,
+    sessionToken: token,
+    profiles: data.profiles,
+    activeProfileId: data.profiles[0]?.id ?? "",
+    isAdmin: data.isAdmin,
+    roles: data.roles,
+    deleteAt: data.deleteAt,
+    raceSummaryEmails: data.raceSummaryEmails,
+  };
+  saveAccount(account);
+  return account;
+}
+
+// ===== Account data API =====
+
+/**
+ * Download everything stored about the account as a JSON file.
+ */
+export async function downloadAccountData(account: Account): Promise<void> {
+  const res = await fetch(`${serverUrl}/account/export`, {
+    headers: authHeaders(account),
+  });
+  if (!res.ok) {
+    throw new Error("Failed to export account data");
+  }
+  const url = URL.createObjectURL(await res.blob());
+  const a = document.createElement("a");
+  a.href = url;
+  a.download = "rewind-account.json";
+  a.click();
+  URL.revokeObjectURL(url);
+}
+
+/**
+ * Schedule the deletion of the account, after a grace period.
+ */
+export async function requestAccountDeletion(
+  account: Account
+): Promise<Account> {
+  const res = await fetch(`${serverUrl}/account/deletion`, {
+    method: "POST",
+    headers: authHeaders(account),
+  });
+  if (!res.ok) {
+    const text = await res.text();
+    throw new Error(text || "Failed to delete account");
+  }
+  const data: { deleteAt: number } = await res.json();
+  const updated = { ...account, deleteAt: data.deleteAt };
+  saveAccount(updated);
+  return updated;
+}
+
+export async function cancelAccountDeletion(
+  account: Account
+): Promise<Account> {
+  const res = await fetch(`${serverUrl}/account/deletion`, {
+    method: "DELETE",
+    headers: authHeaders(account),
+  });
+  if (!res.ok) {
+    const text = await res.text();
+    throw new Error(text || "Failed to cancel account deletion");
+  }
+  const updated = { ...account, deleteAt: null };
+  saveAccount(updated);
+  return updated;
+}
+
+/**
+ * Turn the summary emailed after each finish on or off.
+ */
+export async function setRaceSummaryEmails(
+  account: Account,
+  enabled: boolean
+): Promise<Account> {
+  const res = await fetch(`${serverUrl}/account/email-preferences`, {
+    method: "PUT",
+    headers: authHeaders(account),
+    body: JSON.stringify({ raceSummaries: enabled }),
+  });
+  if (!res.ok) {
+    const text = await res.text();
+    throw new Error(text || "Failed to update email preferences");
+  }
+  const updated = { ...account, raceSummaryEmails: enabled };
+  saveAccount(updated);
+  return updated;
+}
+
+// ===== API tokens =====
+
+export const API_TOKEN_SCOPES = [
+  "read:account",
+  "read:results",
+  "create:race",
+] as const;
+export type ApiTokenScope = (typeof API_TOKEN_SCOPES)[number];
+
+export type ApiToken = {
+  id: string;
+  name: string;
+  prefix: string;
+  scopes: ApiTokenScope[];
+  createdAt: number;
+  lastUsedAt: number | null;
+};
+
+export async function fetchApiTokens(account: Account): Promise<ApiToken[]> {
+  const res = await fetch(`${serverUrl}/account/tokens`, {
+    headers: authHeaders(account),
+  });
+  if (!res.ok) {
+    throw new Error("Failed to fetch API tokens");
+  }
+  return res.json();
+}
+
+/**
+ * Create a token. Its `secret` is only ever returned here.
+ */
+export async function createApiToken(
+  account: Account,
+  name: string,
+  scopes: ApiTokenScope[]
+): Promise<ApiToken & { secret: string }> {
+  const res = await fetch(`${serverUrl}/account/tokens`, {
+    method: "POST",
+    headers: authHeaders(account),
+    body: JSON.stringify({ name, scopes }),
+  });
+  if (!res.ok) {
+    const text = await res.text();
+    throw new Error(text || "Failed to create API token");
+  }
+  return res.json();
+}
+
+export async function revokeApiToken(
+  account: Account,
+  tokenId: string
+): Promise<void> {
+  const res = await fetch(`${serverUrl}/account/tokens/${tokenId}`, {
+    method: "DELETE",
+    headers: authHeaders(account),
+  });
+  if (!res.ok) {
+    throw new Error("Failed to revoke API token");
+  }
+}
+
+// ===== Sessions =====
+
+export type Session = {
+  id: string;
+  userAgent: string | null;
+  createdAt: number;
+  lastActiveAt: number;
+  /** The session making the request */
+  current: boolean;
+};
+
+export async function fetchSessions(account: Account): Promise<Session[]> {
+  const res = await fetch(`${serverUrl}/auth/sessions`, {
+    headers: authHeaders(account),
+  });
+  if (!res.ok) {
+    throw new Error("Failed to fetch sessions");
+  }
+  return res.json();
+}
+
+export async function revokeSession(
+  account: Account,
+  sessionId: string
+): Promise<void> {
+  const res = await fetch(`${serverUrl}/auth/sessions/${sessionId}`, {
+    method: "DELETE",
+    headers: authHeaders(account),
+  });
+  if (!res.ok) {
+    throw new Error("Failed to sign out session");
+  }
+}
+
 // ===== Profile API =====
 
 export async function fetchProfiles(account: Account): Promise<Profile[]> {
@@ -229,3 +508,90 @@ export async function deleteProfile(
   saveAccount(updated);
   return updated;
 }
+
+// ===== Avatar API =====
+
+/** Longest side of the image sent to the server, which resizes it further */
+const AVATAR_UPLOAD_SIZE = 512;
+
+/** Crop an image file to a centered square and convert it to PNG */
+async function toSquarePng(file: Blob): Promise<Blob> {
+  const image = await createImageBitmap(file);
+  const side = Math.min(image.width, image.height);
+  const size = Math.min(side, AVATAR_UPLOAD_SIZE);
+  const canvas = document.createElement("canvas");
+  canvas.width = size;
+  canvas.height = size;
+  canvas
+    .getContext("2d")!
+    .drawImage(
+      image,
+      (image.width - side) / 2,
+      (image.height - side) / 2,
+      side,
+      side,
+      0,
+      0,
+      size,
+      size
+    );
+  image.close();
+  return new Promise((resolve, reject) =>
+    canvas.toBlob(
+      (blob) => (blob ? resolve(blob) : reject(new Error("Invalid image"))),
+      "image/png"
+    )
+  );
+}
+
+function withAvatar(
+  account: Account,
+  profileId: string,
+  avatarUrl: string | null
+): Account {
+  const updated: Account = {
+    ...account,
+    profiles: account.profiles.map((p) =>
+      p.id === profileId ? { ...p, avatarUrl } : p
+    ),
+  };
+  saveAccount(updated);
+  return updated;
+}
+
+export async function uploadAvatar(
+  account: Account,
+  profileId: string,
+  file: Blob
+): Promise<Account> {
+  const png = await toSquarePng(file);
+  const res = await fetch(`${serverUrl}/account/profiles/${profileId}/avatar`, {
+    method: "PUT",
+    headers: {
+      ...authHeaders(account),
+      "Content-Type": "image/png",
+    },
+    body: png,
+  });
+  if (!res.ok) {
+    const text = await res.text();
+    throw new Error(text || "Failed to upload avatar");
+  }
+  const { avatarUrl }: { avatarUrl: string } = await res.json();
+  return withAvatar(account, profileId, avatarUrl);
+}
+
+export async function removeAvatar(
+  account: Account,
+  profileId: string
+): Promise<Account> {
+  const res = await fetch(`${serverUrl}/account/profiles/${profileId}/avatar`, {
+    method: "DELETE",
+    headers: authHeaders(account),
+  });
+  if (!res.ok) {
+    const text = await res.text();
+    throw new Error(text || "Failed to remove avatar");
+  }
+  return withAvatar(account, profileId, null);
+}
@@ -1,5 +1,6 @@
 import { useState, useEffect, useCallback } from "react";
-import { fetchAccounts, deleteAccount, type AdminAccount } from "./api";
+import { type Role } from "../account";
+import { fetchAccounts, deleteAccount, setRole, type AdminAccount } from "./api";
 
 type Props = {
   sessionToken: string;
@@ -8,6 +9,8 @@ type Props = {
 
 const PAGE_SIZE = 50;
 
+const ROLES: Role[] = ["admin", "moderator", "course-editor"];
+
 export default function AccountsTab({ sessionToken, onUnauthorized }: Props) {
   const [accounts, setAccounts] = useState<AdminAccount[]>([]);
   const [total, setTotal] = useState(0);
@@ -51,6 +54,19 @@ export default function AccountsTab({ sessionToken, onUnauthorized }: Props) {
     }
   }, [sessionToken, offset, load, onUnauthorized]);
 
+  const handleToggleRole = useCallback(async (account: AdminAccount, role: Role) => {
+    try {
+      await setRole(sessionToken, account.id, role, !account.roles.includes(role));
+      load(offset);
+    } catch (err) {
+      if (err instanceof Error && err.message === "Unauthorized") {
+        onUnauthorized();
+        return;
+      }
+      setError(err instanceof Error ? err.message : "Role update failed");
+    }
+  }, [sessionToken, offset, load, onUnauthorized]);
+
   const hasNext = offset + PAGE_SIZE < total;
   const hasPrev = offset > 0;
 
@@ -77,6 +93,7 @@ export default function AccountsTab({ sessionToken, onUnauthorized }: Props) {
                   <th className="text-left py-2 px-3">Created</th>
                   <th className="text-right py-2 px-3">Profiles</th>
                   <th className="text-right py-2 px-3">Sessions</th>
+                  <th className="text-left py-2 px-3">Roles</th>
                   <th className="text-right py-2 px-3"></th>
                 </tr>
               </thead>
@@ -89,6 +106,23 @@ export default function AccountsTab({ sessionToken, onUnauthorized }: Props) {
                     </td>
                     <td className="py-2 px-3 text-right text-slate-400">{account.profileCount}</td>
                     <td className="py-2 px-3 text-right text-slate-400">{account.sessionCount}</td>
+                    <td className="py-2 px-3">
+                      <div className="flex gap-1">
+                        {ROLES.map((role) => (
+                          <button
+                            key={role}
+                            onClick={() => handleToggleRole(account, role)}
+                            className={`px-1.5 py-0.5 rounded text-xs transition-all ${
+                              account.roles.includes(role)
+                                ? "bg-blue-600 text-white"
+                                : "bg-slate-800 text-slate-500 hover:text-white"
+                            }`}
+                          >
+                            {role}
+                          </button>
+                        ))}
+                      </div>
+                    </td>
                     <td className="py-2 px-3 text-right">
                       <button
                         onClick={() => handleDelete(account)}
@@ -101,7 +135,7 @@ export default function AccountsTab({ sessionToken, onUnauthorized }: Props) {
                 ))}
                 {accounts.length === 0 && (
                   <tr>
-                    <td colSpan={5} className="py-4 text-center text-slate-500">
+                    <td colSpan={6} className="py-4 text-center text-slate-500">
                       No accounts found.
                     </td>
                   </tr>
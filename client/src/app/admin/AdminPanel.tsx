@@ -1,12 +1,21 @@
 import { useState, useEffect } from "react";
-import { type Account } from "../account";
-import { verifyEditorAccess } from "../editor/api";
+import { type Account, type Role } from "../account";
 import { type AsyncState, asyncState } from "../state";
 import CourseEditor from "../CourseEditor";
 import AccountsTab from "./AccountsTab";
 import RaceResultsTab from "./RaceResultsTab";
+import AuditTab from "./AuditTab";
+import { fetchMyRoles } from "./api";
 
-type Tab = "accounts" | "results" | "courses";
+type Tab = "accounts" | "results" | "courses" | "audit";
+
+// Roles allowed on the endpoints behind each tab
+const TAB_ROLES: Record<Tab, Role[]> = {
+  accounts: ["admin"],
+  results: ["admin", "moderator"],
+  courses: ["admin", "course-editor"],
+  audit: ["admin"],
+};
 
 type Props = {
   account: Account;
@@ -15,21 +24,26 @@ type Props = {
 };
 
 export default function AdminPanel({ account, onBack, onUnauthorized }: Props) {
-  const [accessState, setAccessState] = useState<AsyncState<void>>(asyncState.loading());
-  const [tab, setTab] = useState<Tab>("accounts");
+  const [accessState, setAccessState] = useState<AsyncState<Role[]>>(asyncState.loading());
+  const [tab, setTab] = useState<Tab | null>(null);
 
   const sessionToken = account.sessionToken;
 
   // Verify admin access on mount
   useEffect(() => {
-    verifyEditorAccess(sessionToken).then((ok) => {
-      if (ok) {
-        setAccessState(asyncState.success(undefined));
-      } else {
+    fetchMyRoles(sessionToken)
+      .then((roles) => {
+        if (roles.length > 0) {
+          setAccessState(asyncState.success(roles));
+        } else {
+          setAccessState(asyncState.error("Unauthorized"));
+          onUnauthorized();
+        }
+      })
+      .catch(() => {
         setAccessState(asyncState.error("Unauthorized"));
         onUnauthorized();
-      }
-    });
+      });
   }, [sessionToken, onUnauthorized]);
 
   if (accessState.status === "loading") {
@@ -56,11 +70,14 @@ export default function AdminPanel({ account, onBack, onUnauthorized }: Props) {
     );
   }
 
+  const roles = accessState.status === "success" ? accessState.data : [];
   const tabs: { key: Tab; label: string }[] = [
-    { key: "accounts", label: "Accounts" },
-    { key: "results", label: "Race Results" },
-    { key: "courses", label: "Courses" },
-  ];
+    { key: "accounts" as Tab, label: "Accounts" },
+    { key: "results" as Tab, label: "Race Results" },
+    { key: "courses" as Tab, label: "Courses" },
+    { key: "audit" as Tab, label: "Audit Log" },
+  ].filter((t) => TAB_ROLES[t.key].some((role) => roles.includes(role)));
+  const activeTab = tab ?? tabs[0]?.key;
 
   return (
     <div className="fixed inset-0 flex flex-col bg-slate-950">
@@ -74,7 +91,7 @@ export default function AdminPanel({ account, onBack, onUnauthorized }: Props) {
                 key={t.key}
                 onClick={() => setTab(t.key)}
                 className={`px-3 py-1.5 rounded text-sm transition-all ${
-                  tab === t.key
+                  activeTab === t.key
                     ? "bg-blue-600 text-white"
                     : "text-slate-400 hover:text-white hover:bg-slate-800"
                 }`}
@@ -94,19 +111,25 @@ export default function AdminPanel({ account, onBack, onUnauthorized }: Props) {
 
       {/* Tab content */}
       <div className="flex-1 overflow-hidden">
-        {tab === "accounts" && (
+        {activeTab === "accounts" && (
           <AccountsTab
             sessionToken={sessionToken}
             onUnauthorized={onUnauthorized}
           />
         )}
-        {tab === "results" && (
+        {activeTab === "results" && (
           <RaceResultsTab
             sessionToken={sessionToken}
             onUnauthorized={onUnauthorized}
           />
         )}
-        {tab === "courses" && (
+        {activeTab === "audit" && (
+          <AuditTab
+            sessionToken={sessionToken}
+            onUnauthorized={onUnauthorized}
+          />
+        )}
+        {activeTab === "courses" && (
           <CourseEditor
             account={account}
             onBack={onBack}
@@ -0,0 +1,141 @@
+import { useState, useEffect, useCallback } from "react";
+import { fetchAuditLog, type AuditEntry } from "./api";
+
+type Props = {
+  sessionToken: string;
+  onUnauthorized: () => void;
+};
+
+const PAGE_SIZE = 50;
+
+export default function AuditTab({ sessionToken, onUnauthorized }: Props) {
+  const [entries, setEntries] = useState<AuditEntry[]>([]);
+  const [total, setTotal] = useState(0);
+  const [offset, setOffset] = useState(0);
+  const [loading, setLoading] = useState(true);
+  const [error, setError] = useState<string | null>(null);
+  const [actionFilter, setActionFilter] = useState("");
+
+  const load = useCallback(async (off: number, action: string) => {
+    setLoading(true);
+    setError(null);
+    try {
+      const data = await fetchAuditLog(
+        sessionToken,
+        PAGE_SIZE,
+        off,
+        action || undefined,
+      );
+      setEntries(data.entries);
+      setTotal(data.total);
+    } catch (err) {
+      if (err instanceof Error && err.message === "Unauthorized") {
+        onUnauthorized();
+        return;
+      }
+      setError(err instanceof Error ? err.message : "Failed to load");
+    } finally {
+      setLoading(false);
+    }
+  }, [sessionToken, onUnauthorized]);
+
+  useEffect(() => {
+    load(offset, actionFilter);
+  }, [offset, actionFilter, load]);
+
+  const handleActionFilterChange = (value: string) => {
+    setActionFilter(value.trim());
+    setOffset(0);
+  };
+
+  const hasNext = offset + PAGE_SIZE < total;
+  const hasPrev = offset > 0;
+
+  return (
+    <div className="p-4 space-y-4 h-full overflow-y-auto">
+      <div className="flex items-center justify-between">
+        <h2 className="text-white font-semibold">Audit Log ({total})</h2>
+        <input
+          type="text"
+          placeholder="Action, e.g. course.update"
+          onBlur={(e) => handleActionFilterChange(e.target.value)}
+          onKeyDown={(e) => {
+            if (e.key === "Enter") handleActionFilterChange(e.currentTarget.value);
+          }}
+          className="bg-slate-800 text-slate-300 text-xs border border-slate-700 rounded px-2 py-1"
+        />
+      </div>
+
+      {error && <div className="text-red-400 text-sm">{error}</div>}
+
+      {loading ? (
+        <div className="flex items-center gap-2 text-slate-400 py-4">
+          <span className="w-4 h-4 border-2 border-slate-500 border-t-blue-400 rounded-full animate-spin" />
+          Loading...
+        </div>
+      ) : (
+        <>
+          <div className="overflow-x-auto">
+            <table className="w-full text-sm">
+              <thead>
+                <tr className="text-slate-400 text-xs uppercase tracking-wide border-b border-slate-700">
+                  <th className="text-left py-2 px-3">Date</th>
+                  <th className="text-left py-2 px-3">Actor</th>
+                  <th className="text-left py-2 px-3">Action</th>
+                  <th className="text-left py-2 px-3">Target</th>
+                  <th className="text-left py-2 px-3">Details</th>
+                </tr>
+              </thead>
+              <tbody className="divide-y divide-slate-800">
+                {entries.map((entry) => (
+                  <tr key={entry.id} className="hover:bg-slate-800/50 align-top">
+                    <td className="py-2 px-3 text-slate-400 whitespace-nowrap">
+                      {new Date(entry.createdAt).toLocaleString()}
+                    </td>
+                    <td className="py-2 px-3 text-white">
+                      {entry.actorEmail ?? entry.actorId ?? "system"}
+                    </td>
+                    <td className="py-2 px-3 text-cyan-400 font-mono text-xs">{entry.action}</td>
+                    <td className="py-2 px-3 text-slate-400 font-mono text-xs">{entry.target}</td>
+                    <td className="py-2 px-3 text-slate-500 font-mono text-xs break-all">
+                      {entry.payload === null ? "" : JSON.stringify(entry.payload)}
+                    </td>
+                  </tr>
+                ))}
+                {entries.length === 0 && (
+                  <tr>
+                    <td colSpan={5} className="py-4 text-center text-slate-500">
+                      No entries found.
+                    </td>
+                  </tr>
+                )}
+              </tbody>
+            </table>
+          </div>
+
+          <div className="flex items-center justify-between text-sm">
+            <span className="text-slate-500">
+              Showing {offset + 1}–{Math.min(offset + PAGE_SIZE, total)} of {total}
+            </span>
+            <div className="flex gap-2">
+              <button
+                onClick={() => setOffset(Math.max(0, offset - PAGE_SIZE))}
+                disabled={!hasPrev}
+                className="px-3 py-1 text-slate-400 hover:text-white border border-slate-700 rounded disabled:opacity-30 disabled:hover:text-slate-400 transition-all"
+              >
+                Previous
+              </button>
+              <button
+                onClick={() => setOffset(offset + PAGE_SIZE)}
+                disabled={!hasNext}
+                className="px-3 py-1 text-slate-400 hover:text-white border border-slate-700 rounded disabled:opacity-30 disabled:hover:text-slate-400 transition-all"
+              >
+                Next
+              </button>
+            </div>
+          </div>
+        </>
+      )}
+    </div>
+  );
+}
@@ -2,10 +2,13 @@ import { useState, useEffect, useMemo } from "react";
 import { MapContainer, TileLayer, Polyline, useMap } from "react-leaflet";
 import L from "leaflet";
 import "leaflet/dist/leaflet.css";
-import { fetchReplayPath, type PathPoint } from "../../replay-path";
+import { type PathPoint } from "../../replay-path";
 
 const serverUrl = import.meta.env.REWIND_SERVER_URL;
 
+// Path simplification tolerance (nm): an overview is enough for the trace
+const TRACE_TOLERANCE_NM = 1;
+
 /**
  * Split a [lat, lng] polyline at antimeridian crossings so Leaflet
  * never draws a map-spanning line from 178° to -178°.
@@ -73,11 +76,12 @@ export default function ResultTraceMap({ resultId }: Props) {
 
     (async () => {
       try {
-        const res = await fetch(`${serverUrl}/replay/${resultId}`);
+        const res = await fetch(
+          `${serverUrl}/replay/${resultId}?tolerance=${TRACE_TOLERANCE_NM}`,
+        );
         if (!res.ok) throw new Error("Failed to fetch replay");
-        const { pathUrl } = await res.json();
-        const path = await fetchReplayPath(pathUrl);
-        setPoints(path);
+        const { points }: { points: PathPoint[] } = await res.json();
+        setPoints(points);
       } catch (err) {
         setError(err instanceof Error ? err.message : "Failed to load trace");
       }
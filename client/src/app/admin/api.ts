@@ -1,3 +1,5 @@
+import { type Role } from "../account";
+
 const serverUrl = import.meta.env.REWIND_SERVER_URL;
 
 function authHeaders(sessionToken: string): HeadersInit {
@@ -13,6 +15,7 @@ export type AdminAccount = {
   createdAt: number;
   profileCount: number;
   sessionCount: number;
+  roles: Role[];
 };
 
 export type AdminRaceResult = {
@@ -24,6 +27,22 @@ export type AdminRaceResult = {
   raceStartTime: number;
   pathS3Key: string;
   createdAt: number | null;
+  status: "finished" | "retired" | "dnf";
+};
+
+export type AuditEntry = {
+  id: number;
+  actorId: string | null;
+  actorEmail: string | null;
+  action: string;
+  target: string;
+  payload: unknown;
+  createdAt: number;
+};
+
+type AuditResponse = {
+  entries: AuditEntry[];
+  total: number;
 };
 
 type AccountsResponse = {
@@ -65,6 +84,56 @@ export async function deleteAccount(
   if (!res.ok) throw new Error("Failed to delete account");
 }
 
+/**
+ * Roles of the signed in account, deciding which admin tabs it sees.
+ */
+export async function fetchMyRoles(sessionToken: string): Promise<Role[]> {
+  const res = await fetch(`${serverUrl}/account/me`, {
+    headers: { Authorization: `Bearer ${sessionToken}` },
+  });
+  if (res.status === 401) throw new Error("Unauthorized");
+  if (!res.ok) throw new Error("Failed to fetch roles");
+  const data: { roles: Role[] } = await res.json();
+  return data.roles;
+}
+
+export async function setRole(
+  sessionToken: string,
+  accountId: string,
+  role: Role,
+  granted: boolean,
+): Promise<void> {
+  const res = await fetch(
+    `${serverUrl}/admin/accounts/${encodeURIComponent(accountId)}/roles/${role}`,
+    {
+      method: granted ? "PUT" : "DELETE",
+      headers: { Authorization: `Bearer ${sessionToken}` },
+    },
+  );
+  if (res.status === 401) throw new Error("Unauthorized");
+  if (res.status === 400) throw new Error(await res.text());
+  if (!res.ok) throw new Error("Failed to update role");
+}
+
+export async function fetchAuditLog(
+  sessionToken: string,
+  limit = 50,
+  offset = 0,
+  action?: string,
+): Promise<AuditResponse> {
+  const params = new URLSearchParams({
+    limit: String(limit),
+    offset: String(offset),
+  });
+  if (action) params.set("action", action);
+  const res = await fetch(`${serverUrl}/admin/audit?${params}`, {
+    headers: authHeaders(sessionToken),
+  });
+  if (res.status === 401) throw new Error("Unauthorized");
+  if (!res.ok) throw new Error("Failed to fetch audit log");
+  return res.json();
+}
+
 export async function fetchResults(
   sessionToken: string,
   limit = 50,
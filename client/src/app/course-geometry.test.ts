@@ -0,0 +1,34 @@
+import { describe, it, expect } from "vitest";
+import { boundsCenter, boundsOf, courseBounds } from "./course-geometry";
+import { Course, Gate } from "../models";
+
+function horizontalGate(lng: number, lat: number, lengthNm: number): Gate {
+  return { center: { lng, lat }, orientation: 90, lengthNm };
+}
+
+describe("courseBounds", () => {
+  it("covers the start, mark ends and waypoints", () => {
+    const course = {
+      start: { lng: -5, lat: 48 },
+      gates: [horizontalGate(-20, 30, 120)],
+      finishLine: horizontalGate(-60, 15, 60),
+      routeWaypoints: [[{ lng: -10, lat: 50 }], []],
+    } as unknown as Course;
+    const bounds = courseBounds(course);
+    expect(bounds.north).toBe(50);
+    expect(bounds.south).toBe(15);
+    expect(bounds.east).toBe(-5);
+    expect(bounds.west).toBeLessThan(-60.5);
+  });
+});
+
+describe("boundsOf", () => {
+  it("goes across the antimeridian the short way", () => {
+    const bounds = boundsOf([
+      { lng: 170, lat: -40 },
+      { lng: -170, lat: -45 },
+    ]);
+    expect(bounds).toEqual({ west: 170, south: -45, east: 190, north: -40 });
+    expect(boundsCenter(bounds)).toEqual({ lng: -180, lat: -42.5 });
+  });
+});
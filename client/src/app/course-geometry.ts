@@ -0,0 +1,51 @@
+import { Course, LngLat } from "../models";
+import { wrapLng } from "../utils";
+import { gateEndpoints } from "./gate-crossing";
+
+/**
+ * Box around points, the short way around from the first one: `east` may
+ * be past 180 for boxes across the antimeridian.
+ */
+export type Bounds = {
+  west: number;
+  south: number;
+  east: number;
+  north: number;
+};
+
+/** The start, mark ends and route waypoints of a course */
+export function coursePoints(course: Course): LngLat[] {
+  return [
+    course.start,
+    ...course.gates.flatMap(gateEndpoints),
+    ...gateEndpoints(course.finishLine),
+    ...course.routeWaypoints.flat(),
+  ];
+}
+
+/** Bounds of points, longitudes taken the shortest way from the first one */
+export function boundsOf(points: LngLat[]): Bounds {
+  const origin = points[0];
+  const bounds = {
+    west: origin.lng,
+    south: origin.lat,
+    east: origin.lng,
+    north: origin.lat,
+  };
+  for (const p of points.slice(1)) {
+    const lng = origin.lng + wrapLng(p.lng - origin.lng);
+    bounds.west = Math.min(bounds.west, lng);
+    bounds.east = Math.max(bounds.east, lng);
+    bounds.south = Math.min(bounds.south, p.lat);
+    bounds.north = Math.max(bounds.north, p.lat);
+  }
+  return bounds;
+}
+
+export const courseBounds = (course: Course): Bounds =>
+  boundsOf(coursePoints(course));
+
+export const boundsCenter = (bounds: Bounds): LngLat => ({
+  lng: wrapLng((bounds.west + bounds.east) / 2),
+  lat: (bounds.south + bounds.north) / 2,
+});
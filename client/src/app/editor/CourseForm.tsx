@@ -1,5 +1,5 @@
 import { useEffect } from "react";
-import { Course } from "../../models";
+import { Course, COURSE_TAGS, CourseTag } from "../../models";
 import type { AsyncState } from "../state";
 import type { MapSelection } from "./EditorMap";
 
@@ -69,6 +69,13 @@ export default function CourseForm({
     onChange({ ...course, ...partial });
   };
 
+  const toggleTag = (tag: CourseTag) => {
+    const tags = course.tags ?? [];
+    update({
+      tags: tags.includes(tag) ? tags.filter((t) => t !== tag) : [...tags, tag],
+    });
+  };
+
   useEffect(() => {
     if (!focusTarget) return;
     const { selection } = focusTarget;
@@ -189,6 +196,87 @@ export default function CourseForm({
         </div>
       </div>
 
+      <div>
+        <label className="block text-slate-400 text-xs mb-1">
+          Laps (gates sailed in order each lap)
+        </label>
+        <input
+          type="number"
+          min={1}
+          value={course.laps ?? 1}
+          onChange={(e) => update({ laps: Number(e.target.value) })}
+          className="w-full bg-slate-800 text-white px-2 py-1.5 rounded border border-slate-700 focus:border-blue-500 focus:outline-none"
+        />
+      </div>
+
+      <div>
+        <label className="block text-slate-400 text-xs mb-1">Tags</label>
+        <div className="flex flex-wrap gap-1">
+          {COURSE_TAGS.map((tag) => (
+            <button
+              key={tag}
+              type="button"
+              onClick={() => toggleTag(tag)}
+              className={`px-1.5 py-0.5 rounded text-xs capitalize transition-all ${
+                course.tags?.includes(tag)
+                  ? "bg-blue-600 text-white"
+                  : "bg-slate-700 text-slate-400 hover:text-white"
+              }`}
+            >
+              {tag}
+            </button>
+          ))}
+        </div>
+      </div>
+
+      <div>
+        <label className="flex items-center gap-2 text-slate-400 text-xs mb-1 cursor-pointer">
+          <input
+            type="checkbox"
+            checked={course.published !== false}
+            onChange={(e) => update({ published: e.target.checked })}
+            className="accent-blue-500"
+          />
+          Published (races can be created)
+        </label>
+        <div className="grid grid-cols-2 gap-2">
+          <div>
+            <label className="block text-slate-500 text-xs mb-0.5">
+              Opens (optional)
+            </label>
+            <input
+              type="datetime-local"
+              value={course.opensAt ? toDatetimeLocal(course.opensAt) : ""}
+              onChange={(e) =>
+                update({
+                  opensAt: e.target.value
+                    ? fromDatetimeLocal(e.target.value)
+                    : null,
+                })
+              }
+              className="w-full bg-slate-800 text-white px-2 py-1 rounded border border-slate-700 focus:border-blue-500 focus:outline-none text-xs"
+            />
+          </div>
+          <div>
+            <label className="block text-slate-500 text-xs mb-0.5">
+              Closes (optional)
+            </label>
+            <input
+              type="datetime-local"
+              value={course.closesAt ? toDatetimeLocal(course.closesAt) : ""}
+              onChange={(e) =>
+                update({
+                  closesAt: e.target.value
+                    ? fromDatetimeLocal(e.target.value)
+                    : null,
+                })
+              }
+              className="w-full bg-slate-800 text-white px-2 py-1 rounded border border-slate-700 focus:border-blue-500 focus:outline-none text-xs"
+            />
+          </div>
+        </div>
+      </div>
+
       {/* Start position (read-only, drag on map) */}
       <div>
         <label className="block text-slate-400 text-xs mb-1">
@@ -5,6 +5,7 @@ import "leaflet/dist/leaflet.css";
 import { Course, LngLat } from "../../models";
 import GateMarker from "./GateMarker";
 import WaypointPolyline from "./WaypointPolyline";
+import FleetHeatmap from "./FleetHeatmap";
 import { catmullRomSplineGeo } from "../../catmull-rom";
 import type { FocusTarget } from "./CourseForm";
 
@@ -277,6 +278,7 @@ export default function EditorMap({ course, onChange, onSelect, focusTarget }: P
       />
       <MapCenter lat={course.start.lat} lng={course.start.lng} />
       <MapFocus position={focusPosition} focusKey={focusTarget?.key ?? null} />
+      <FleetHeatmap courseKey={course.key} />
 
       {/* Start marker */}
       <Marker
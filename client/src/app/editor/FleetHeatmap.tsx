@@ -0,0 +1,46 @@
+import { useEffect, useState } from "react";
+import { ImageOverlay, LayersControl } from "react-leaflet";
+import { fetchHeatmap, type Heatmap } from "./api";
+
+type Props = {
+  courseKey: string;
+};
+
+/** Toggleable overlay of where the fleet sails the course */
+export default function FleetHeatmap({ courseKey }: Props) {
+  const [heatmap, setHeatmap] = useState<Heatmap | null>(null);
+
+  useEffect(() => {
+    setHeatmap(null);
+    if (!courseKey) return;
+    let cancelled = false;
+    fetchHeatmap(courseKey)
+      .then((data) => {
+        if (!cancelled) setHeatmap(data);
+      })
+      .catch(() => {});
+    return () => {
+      cancelled = true;
+    };
+  }, [courseKey]);
+
+  if (!heatmap) return null;
+
+  return (
+    <LayersControl position="topright">
+      <LayersControl.Overlay
+        name={`Fleet heatmap (${heatmap.paths} paths)`}
+        checked
+      >
+        <ImageOverlay
+          url={heatmap.pngUrl}
+          bounds={[
+            [heatmap.south, heatmap.west],
+            [heatmap.north, heatmap.east],
+          ]}
+          opacity={0.7}
+        />
+      </LayersControl.Overlay>
+    </LayersControl>
+  );
+}
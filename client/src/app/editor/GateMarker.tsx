@@ -2,21 +2,8 @@ import { useRef, useMemo } from "react";
 import { Marker, Polyline, Tooltip } from "react-leaflet";
 import L from "leaflet";
 import { Gate } from "../../models";
-
-// 1 nautical mile in degrees latitude (approximate)
-const NM_TO_DEG = 1 / 60;
-
-function gateEndpoints(gate: Gate): [L.LatLng, L.LatLng] {
-  const rad = (gate.orientation * Math.PI) / 180;
-  const halfLen = (gate.lengthNm / 2) * NM_TO_DEG;
-  const dlng = Math.sin(rad) * halfLen;
-  const dlat = Math.cos(rad) * halfLen;
-  const c = gate.center;
-  return [
-    L.latLng(c.lat - dlat, c.lng - dlng),
-    L.latLng(c.lat + dlat, c.lng + dlng),
-  ];
-}
+import { wrapLng } from "../../utils";
+import { gateEndpoints } from "../gate-crossing";
 
 type Props = {
   gate: Gate;
@@ -43,7 +30,11 @@ export default function GateMarker({ gate, color, label, onDrag, onClick, select
     [color, selected],
   );
 
-  const endpoints = gateEndpoints(gate);
+  // Unwrapped around the center, so Leaflet draws gates on the antimeridian
+  // in one piece
+  const endpoints = gateEndpoints(gate).map((p) =>
+    L.latLng(p.lat, gate.center.lng + wrapLng(p.lng - gate.center.lng)),
+  );
 
   const eventHandlers = useMemo(
     () => ({
@@ -9,6 +9,15 @@ function authHeaders(sessionToken: string): HeadersInit {
   };
 }
 
+// Validation errors come back as 400 with the reason as text
+async function failure(res: Response, fallback: string): Promise<Error> {
+  if (res.status === 400) {
+    const reason = await res.text();
+    if (reason) return new Error(reason);
+  }
+  return new Error(fallback);
+}
+
 export async function verifyEditorAccess(
   sessionToken: string,
   signal?: AbortSignal,
@@ -26,6 +35,29 @@ export async function fetchCourses(): Promise<Course[]> {
   return res.json();
 }
 
+/** Where the fleet sails a course, see `heatmaps::Heatmap` on the server */
+export type Heatmap = {
+  courseKey: string;
+  west: number;
+  south: number;
+  east: number;
+  north: number;
+  paths: number;
+  maxCount: number;
+  pngUrl: string;
+  computedAt: number;
+};
+
+// Null until the course has results binned into a heatmap
+export async function fetchHeatmap(courseKey: string): Promise<Heatmap | null> {
+  const res = await fetch(
+    `${serverUrl}/courses/${encodeURIComponent(courseKey)}/heatmap`,
+  );
+  if (res.status === 404) return null;
+  if (!res.ok) throw new Error("Failed to fetch heatmap");
+  return res.json();
+}
+
 export async function createCourse(
   course: Course,
   sessionToken: string,
@@ -36,7 +68,7 @@ export async function createCourse(
     body: JSON.stringify(course),
   });
   if (res.status === 401) throw new Error("Unauthorized");
-  if (!res.ok) throw new Error("Failed to create course");
+  if (!res.ok) throw await failure(res, "Failed to create course");
 }
 
 export async function updateCourse(
@@ -50,7 +82,7 @@ export async function updateCourse(
     body: JSON.stringify(course),
   });
   if (res.status === 401) throw new Error("Unauthorized");
-  if (!res.ok) throw new Error("Failed to update course");
+  if (!res.ok) throw await failure(res, "Failed to update course");
 }
 
 export async function deleteCourse(
@@ -62,7 +94,7 @@ export async function deleteCourse(
     headers: { Authorization: `Bearer ${sessionToken}` },
   });
   if (res.status === 401) throw new Error("Unauthorized");
-  if (!res.ok) throw new Error("Failed to delete course");
+  if (!res.ok) throw await failure(res, "Failed to delete course");
 }
 
 export async function reorderCourses(
@@ -75,5 +107,5 @@ export async function reorderCourses(
     body: JSON.stringify(keys),
   });
   if (res.status === 401) throw new Error("Unauthorized");
-  if (!res.ok) throw new Error("Failed to reorder courses");
+  if (!res.ok) throw await failure(res, "Failed to reorder courses");
 }
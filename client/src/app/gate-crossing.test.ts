@@ -0,0 +1,92 @@
+import { describe, it, expect } from "vitest";
+import {
+  checkGateCrossing,
+  completesLap,
+  courseMark,
+  crossesGate,
+  finishIndex,
+  gateEndpoints,
+} from "./gate-crossing";
+import { Course, Gate } from "../models";
+
+function verticalGate(lng: number, lat: number, lengthNm: number): Gate {
+  return { center: { lng, lat }, orientation: 0, lengthNm };
+}
+
+describe("crossesGate", () => {
+  const gate = verticalGate(1, 0, 60);
+
+  it("detects a move across the gate, both ways", () => {
+    const west = { lng: 0.5, lat: 0 };
+    const east = { lng: 1.5, lat: 0 };
+    expect(crossesGate(west, east, gate)).toBe(true);
+    expect(crossesGate(east, west, gate)).toBe(true);
+  });
+
+  it("ignores moves short of the gate or past its ends", () => {
+    expect(crossesGate({ lng: 0.5, lat: 0 }, { lng: 0.9, lat: 0 }, gate)).toBe(
+      false,
+    );
+    expect(crossesGate({ lng: 0.5, lat: 2 }, { lng: 1.5, lat: 2 }, gate)).toBe(
+      false,
+    );
+  });
+
+  it("works across the antimeridian", () => {
+    const west = { lng: 179.9, lat: -50 };
+    const east = { lng: -179.9, lat: -50 };
+    for (const lng of [180, -180, -179.95]) {
+      expect(crossesGate(west, east, verticalGate(lng, -50, 10))).toBe(true);
+    }
+    expect(
+      crossesGate(
+        west,
+        { lng: 179.95, lat: -50 },
+        verticalGate(-179.95, -50, 10),
+      ),
+    ).toBe(false);
+  });
+});
+
+describe("gateEndpoints", () => {
+  it("wraps longitudes", () => {
+    const [west, east] = gateEndpoints({
+      center: { lng: 179.99, lat: 0 },
+      orientation: 90,
+      lengthNm: 6,
+    });
+    expect(west.lng).toBeCloseTo(179.94);
+    expect(east.lng).toBeCloseTo(-179.96);
+  });
+});
+
+describe("laps", () => {
+  const windward = verticalGate(-10, 0, 10);
+  const leeward = verticalGate(-20, 0, 10);
+  const course = {
+    gates: [windward, leeward],
+    finishLine: verticalGate(-30, 0, 10),
+    laps: 3,
+  } as Course;
+
+  it("sails the gates lap after lap, then the finish", () => {
+    expect(finishIndex(course)).toBe(6);
+    expect(courseMark(course, 4)).toBe(windward);
+    expect(courseMark(course, 5)).toBe(leeward);
+    expect(courseMark(course, 6)).toBe(course.finishLine);
+    expect(courseMark(course, 7)).toBeNull();
+    expect(finishIndex({ ...course, laps: undefined })).toBe(2);
+  });
+
+  it("completes a lap at the last gate", () => {
+    const laps = [0, 1, 2, 3, 4, 5, 6].filter((i) => completesLap(course, i));
+    expect(laps).toEqual([1, 3, 5]);
+  });
+
+  it("checks the gate of the current lap", () => {
+    const from = { lng: -9.5, lat: 0 };
+    const to = { lng: -10.5, lat: 0 };
+    expect(checkGateCrossing(from, to, course, 2)).toBe(2);
+    expect(checkGateCrossing(from, to, course, 3)).toBeNull();
+  });
+});
@@ -1,4 +1,9 @@
 import { Course, Gate, LngLat } from "../models";
+import { wrapLng } from "../utils";
+
+// Mirrors `server/src/gates.rs`. Positions are taken relative to the gate
+// center, with longitude differences wrapped, so gates and moves across the
+// antimeridian work like any other.
 
 /**
  * Compute the two endpoints of a gate from its center, orientation, and length.
@@ -17,16 +22,26 @@ export function gateEndpoints(gate: Gate): [LngLat, LngLat] {
 
   return [
     {
-      lng: gate.center.lng - lngOffset,
+      lng: wrapLng(gate.center.lng - lngOffset),
       lat: gate.center.lat - latOffset,
     },
     {
-      lng: gate.center.lng + lngOffset,
+      lng: wrapLng(gate.center.lng + lngOffset),
       lat: gate.center.lat + latOffset,
     },
   ];
 }
 
+/**
+ * Offset of a point from an origin, the shortest way around.
+ */
+function offset(origin: LngLat, point: LngLat): LngLat {
+  return {
+    lng: wrapLng(point.lng - origin.lng),
+    lat: point.lat - origin.lat,
+  };
+}
+
 /**
  * Check if two line segments intersect using cross-product method.
  * Segment 1: p1 -> p2 (boat movement)
@@ -77,18 +92,55 @@ function segmentsIntersect(
 /**
  * Check if boat movement crosses a gate.
  */
-function crossesGate(prevPos: LngLat, newPos: LngLat, gate: Gate): boolean {
+export function crossesGate(
+  prevPos: LngLat,
+  newPos: LngLat,
+  gate: Gate,
+): boolean {
   const [point1, point2] = gateEndpoints(gate);
-  return segmentsIntersect(prevPos, newPos, point1, point2);
+  const center = gate.center;
+  return segmentsIntersect(
+    offset(center, prevPos),
+    offset(center, newPos),
+    offset(center, point1),
+    offset(center, point2),
+  );
+}
+
+/**
+ * Index of the finish line among the marks: the gates of every lap come
+ * first. Mirrors `Course::finish_index` on the server.
+ */
+export function finishIndex(course: Course): number {
+  return course.gates.length * Math.max(course.laps ?? 1, 1);
+}
+
+/**
+ * Mark `index` in sailing order: gate `index % gates.length` on each lap,
+ * then the finish line at `finishIndex`.
+ */
+export function courseMark(course: Course, index: number): Gate | null {
+  const finish = finishIndex(course);
+  if (index < finish) return course.gates[index % course.gates.length];
+  return index === finish ? course.finishLine : null;
+}
+
+/** Whether crossing mark `index` completes a lap, on courses of several laps */
+export function completesLap(course: Course, index: number): boolean {
+  return (
+    (course.laps ?? 1) > 1 &&
+    index < finishIndex(course) &&
+    (index + 1) % course.gates.length === 0
+  );
 }
 
 /**
- * Check if boat has crossed its next required gate.
- * Returns the gate index if crossed, null otherwise.
+ * Check if boat has crossed its next required mark.
+ * Returns the mark index if crossed, null otherwise.
  *
- * Gate indices:
- * - 0 to gates.length-1: intermediate gates
- * - gates.length: finish line
+ * Mark indices (see `courseMark`):
+ * - 0 to finishIndex-1: gates, lap after lap
+ * - finishIndex: finish line
  */
 export function checkGateCrossing(
   prevPos: LngLat,
@@ -96,21 +148,9 @@ export function checkGateCrossing(
   course: Course,
   nextGateIndex: number,
 ): number | null {
-  const numGates = course.gates.length;
-
-  // Check if we're at an intermediate gate or the finish line
-  if (nextGateIndex < numGates) {
-    // Check intermediate gate
-    const gate = course.gates[nextGateIndex];
-    if (crossesGate(prevPos, newPos, gate)) {
-      return nextGateIndex;
-    }
-  } else if (nextGateIndex === numGates) {
-    // Check finish line
-    if (crossesGate(prevPos, newPos, course.finishLine)) {
-      return nextGateIndex;
-    }
+  const mark = courseMark(course, nextGateIndex);
+  if (mark && crossesGate(prevPos, newPos, mark)) {
+    return nextGateIndex;
   }
-
   return null;
 }
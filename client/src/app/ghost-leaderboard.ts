@@ -1,9 +1,14 @@
 import { Course, LngLat } from "../models";
-import { LeaderboardEntry } from "../multiplayer/types";
+import { LeaderboardEntry, PlayerStatus } from "../multiplayer/types";
 import { RecordedGhost } from "./hooks/useGhosts";
 import { interpolatePosition, PathPoint } from "../replay-path";
-import { checkGateCrossing } from "./gate-crossing";
-import { haversineDistanceNm } from "../utils";
+import {
+  checkGateCrossing,
+  completesLap,
+  courseMark,
+  finishIndex,
+} from "./gate-crossing";
+import { haversineDistanceNm, wrapLng } from "../utils";
 
 export type GhostGateCrossing = {
   gateIndex: number;
@@ -18,7 +23,7 @@ export function computeGateCrossings(
   course: Course,
 ): GhostGateCrossing[] {
   const crossings: GhostGateCrossing[] = [];
-  const totalGates = course.gates.length + 1; // intermediate gates + finish
+  const totalGates = finishIndex(course) + 1; // gates of every lap + finish
   let nextGateIndex = 0;
 
   for (let i = 1; i < path.length && nextGateIndex < totalGates; i++) {
@@ -35,6 +40,74 @@ export function computeGateCrossings(
   return crossings;
 }
 
+/**
+ * Route of a leg: starting mark, route waypoints, then ending mark.
+ * Leg `i` ends at mark `i`; the last leg (`finishIndex`) ends at the finish.
+ * Legs of later laps follow the waypoints of the first lap.
+ * Mirrors `Course::leg_path` on the server.
+ */
+export function legPath(course: Course, leg: number): LngLat[] {
+  const finish = finishIndex(course);
+  const l = Math.min(leg, finish);
+  const from = l === 0 ? course.start : courseMark(course, l - 1)!.center;
+  const to = courseMark(course, l)!.center;
+  const waypoints =
+    l === finish ? course.gates.length : l % course.gates.length;
+  return [from, ...(course.routeWaypoints[waypoints] ?? []), to];
+}
+
+function pathLengthNm(path: LngLat[]): number {
+  let total = 0;
+  for (let i = 1; i < path.length; i++) {
+    total += haversineDistanceNm(path[i - 1], path[i]);
+  }
+  return total;
+}
+
+function closestPointOnSegment(a: LngLat, b: LngLat, p: LngLat): LngLat {
+  const cosLat = Math.cos((((a.lat + b.lat) / 2) * Math.PI) / 180);
+  const dx = wrapLng(b.lng - a.lng) * cosLat;
+  const dy = b.lat - a.lat;
+  const px = wrapLng(p.lng - a.lng) * cosLat;
+  const py = p.lat - a.lat;
+  const len2 = dx * dx + dy * dy;
+  const t =
+    len2 > 0 ? Math.min(1, Math.max(0, (px * dx + py * dy) / len2)) : 0;
+  return {
+    lng: wrapLng(a.lng + wrapLng(b.lng - a.lng) * t),
+    lat: a.lat + dy * t,
+  };
+}
+
+/**
+ * Distance (NM) from a position to the end of a leg, measured to the closest
+ * point on the leg route and then along the remaining waypoints.
+ * Mirrors `distance_along_leg` on the server so ghosts rank like real players.
+ */
+export function distanceAlongLeg(
+  course: Course,
+  leg: number,
+  pos: LngLat,
+): number {
+  const path = legPath(course, leg);
+  let best: { offset: number; along: number } | null = null;
+  let remainingAfter = 0;
+
+  for (let i = path.length - 2; i >= 0; i--) {
+    const a = path[i];
+    const b = path[i + 1];
+    const closest = closestPointOnSegment(a, b, pos);
+    const offset = haversineDistanceNm(pos, closest);
+    const along = haversineDistanceNm(closest, b) + remainingAfter;
+    if (best === null || offset < best.offset) {
+      best = { offset, along };
+    }
+    remainingAfter += haversineDistanceNm(a, b);
+  }
+
+  return best === null ? 0 : best.offset + best.along;
+}
+
 /**
  * Build leaderboard entries for recorded ghosts at the given course time.
  */
@@ -60,7 +133,7 @@ export function ghostLeaderboardEntries(
     const nextGateIndex = crossedBefore.length;
 
     const finishCrossing = crossings.find(
-      (c) => c.gateIndex === course.gates.length,
+      (c) => c.gateIndex === finishIndex(course),
     );
     const finishTime =
       finishCrossing && finishCrossing.raceTime <= courseTime
@@ -68,13 +141,15 @@ export function ghostLeaderboardEntries(
         : null;
 
     let distanceToNextGate = 0;
+    let distanceToFinish = 0;
     if (finishTime === null) {
       const currentPos: LngLat = { lng: pos.lng, lat: pos.lat };
-      const target =
-        nextGateIndex < course.gates.length
-          ? course.gates[nextGateIndex].center
-          : course.finishLine.center;
-      distanceToNextGate = haversineDistanceNm(currentPos, target);
+      const leg = Math.min(nextGateIndex, finishIndex(course));
+      distanceToNextGate = distanceAlongLeg(course, leg, currentPos);
+      distanceToFinish = distanceToNextGate;
+      for (let l = leg + 1; l <= finishIndex(course); l++) {
+        distanceToFinish += pathLengthNm(legPath(course, l));
+      }
     }
 
     entries.push({
@@ -82,17 +157,30 @@ export function ghostLeaderboardEntries(
       playerName: ghost.name,
       nextGateIndex,
       distanceToNextGate,
+      distanceToFinish,
       finishTime,
+      status: finishTime === null ? "racing" : "finished",
+      lapTimes: crossedBefore
+        .filter((c) => completesLap(course, c.gateIndex))
+        .map((c) => c.raceTime),
     });
   });
 
   return entries;
 }
 
+const STATUS_RANK: Record<PlayerStatus, number> = {
+  finished: 0,
+  racing: 1,
+  dnf: 2,
+  retired: 3,
+};
+
 /**
  * Merge and sort leaderboard entries (server + ghost).
  * Sort order mirrors the server: finished first (by finish time),
- * then by gate progress (descending), then by distance (ascending).
+ * then racing, DNF and retired boats, each by gate progress (descending),
+ * then by distance (ascending).
  */
 export function mergeLeaderboards(
   serverEntries: LeaderboardEntry[],
@@ -101,6 +189,9 @@ export function mergeLeaderboards(
   const all = [...serverEntries, ...ghostEntries];
 
   all.sort((a, b) => {
+    if (a.status !== b.status)
+      return STATUS_RANK[a.status] - STATUS_RANK[b.status];
+
     const aFinished = a.finishTime !== null;
     const bFinished = b.finishTime !== null;
 
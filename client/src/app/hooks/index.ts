@@ -7,3 +7,4 @@ export { useSphereView } from "./useSphereView";
 export { useRaceDataLoader, useWindSourceUpdater } from "./useRaceDataLoader";
 export { useSessionRefs } from "./useSessionRefs";
 export { useIdleWind } from "./useIdleWind";
+export { useReplay } from "./useReplay";
@@ -11,6 +11,7 @@ type GameLoopRefs = {
   courseTime: React.MutableRefObject<number>;
   heading: React.MutableRefObject<number>;
   nextGateIndex: React.MutableRefObject<number>;
+  lockedTWA: React.MutableRefObject<number | null>;
   interpolatedWind: React.MutableRefObject<InterpolatedWind>;
   multiplayer: React.MutableRefObject<MultiplayerClient | null>;
 };
@@ -21,6 +22,7 @@ type GameLoopRefs = {
  * - Tick dispatch for physics updates
  * - Wind refresh at intervals
  * - Position broadcasting to multiplayer peers
+ * - Helm updates for the server-side simulation
  */
 export function useGameLoop(
   isPlaying: boolean,
@@ -37,6 +39,7 @@ export function useGameLoop(
     let lastTime: number | null = null;
     let accumulatedClock = session.clock;
     let lastGateIndex = session.nextGateIndex;
+    let lastHelm: { heading: number; twa: number | null } | null = null;
     lastWindRefreshRef.current = 0;
 
     const tick = (time: number) => {
@@ -77,6 +80,21 @@ export function useGameLoop(
           }
         }
 
+        // Send the helm to the server when it changes
+        const helm = {
+          heading: refs.heading.current,
+          twa: refs.lockedTWA.current,
+        };
+        if (
+          refs.multiplayer.current &&
+          (lastHelm === null ||
+            helm.twa !== lastHelm.twa ||
+            (helm.twa === null && helm.heading !== lastHelm.heading))
+        ) {
+          refs.multiplayer.current.sendHelm(helm.heading, helm.twa);
+          lastHelm = helm;
+        }
+
         // Broadcast position to multiplayer peers
         if (refs.multiplayer.current && refs.position.current) {
           refs.multiplayer.current.broadcastPosition(
@@ -2,9 +2,13 @@ import { useEffect } from "react";
 import { AppAction } from "../state";
 import { SphereView } from "../../sphere";
 
+/** Keys turning to port, then to starboard; `=` is `+` without Shift */
+const PORT_KEYS = ["ArrowLeft", "-"];
+const STARBOARD_KEYS = ["ArrowRight", "+", "="];
+
 /**
  * Hook to handle keyboard controls when playing.
- * - Arrow Left/Right: Turn boat
+ * - Arrow Left/Right, - and +: Turn boat
  * - Enter: Toggle TWA lock
  * - Shift: Lock to closest VMG (upwind or downwind based on current TWA)
  * - Arrow Up: Zoom in and center on boat
@@ -20,9 +24,9 @@ export function useKeyboardControls(
     if (!isPlaying) return;
 
     const handleKeyDown = (e: KeyboardEvent) => {
-      if (e.key === "ArrowLeft") {
+      if (PORT_KEYS.includes(e.key)) {
         dispatch({ type: "TURN", direction: "left" });
-      } else if (e.key === "ArrowRight") {
+      } else if (STARBOARD_KEYS.includes(e.key)) {
         dispatch({ type: "TURN", direction: "right" });
       } else if (e.key === "Enter") {
         e.preventDefault();
@@ -48,7 +52,7 @@ export function useKeyboardControls(
     };
 
     const handleKeyUp = (e: KeyboardEvent) => {
-      if (e.key === "ArrowLeft" || e.key === "ArrowRight") {
+      if (PORT_KEYS.includes(e.key) || STARBOARD_KEYS.includes(e.key)) {
         dispatch({ type: "TURN", direction: null });
       }
     };
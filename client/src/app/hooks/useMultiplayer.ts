@@ -7,10 +7,23 @@ import { AppAction } from "../state";
 import { getOrCreatePlayerId } from "../player-id";
 
 type MultiplayerCallbacks = {
-  onCreateRace: (playerName: string) => Promise<void>;
-  onJoinRace: (raceId: string, playerName: string) => Promise<void>;
+  onCreateRace: (
+    playerName: string,
+    solo: boolean,
+    randomWeather: boolean,
+    polar: string | null,
+  ) => Promise<void>;
+  onJoinRace: (
+    raceId: string,
+    playerName: string,
+    polar: string | null,
+  ) => Promise<void>;
+  onQuickMatch: (playerName: string, polar: string | null) => Promise<void>;
+  onStartDaily: (playerName: string) => Promise<void>;
   onStartRace: () => void;
   onLeaveRace: () => void;
+  onAddServerGhosts: (count: number) => void;
+  onProposeRematch: () => void;
 };
 
 /**
@@ -26,10 +39,17 @@ export function useMultiplayer(
   const multiplayerRef = useRef<MultiplayerClient | null>(null);
 
   const createMultiplayerClient = useCallback(() => {
-    return new MultiplayerClient({
-      onRaceCreated: (raceId, playerId, windRasterSources) => {
-        const course = courseRef.current;
-        if (!course) return;
+    const client: MultiplayerClient = new MultiplayerClient({
+      onRaceCreated: (
+        raceId,
+        playerId,
+        windRasterSources,
+        courseStartTime,
+        polar,
+      ) => {
+        const baseCourse = courseRef.current;
+        if (!baseCourse) return;
+        const course = { ...baseCourse, startTime: courseStartTime, polar };
         dispatch({
           type: "RACE_CREATED",
           raceId,
@@ -45,9 +65,14 @@ export function useMultiplayer(
         isCreator,
         courseKey,
         windRasterSources,
+        courseStartTime,
+        polar,
       ) => {
-        const course = coursesRef.current?.get(courseKey);
-        if (!course) return;
+        const baseCourse = coursesRef.current?.get(courseKey);
+        if (!baseCourse) return;
+        // The race may sail the course in another weather window, and the
+        // player may sail another boat class than the course default
+        const course = { ...baseCourse, startTime: courseStartTime, polar };
         const playerMap = new Map<string, PeerState>();
         players.forEach((p: PlayerInfo) => {
           if (p.id !== playerId) {
@@ -77,14 +102,21 @@ export function useMultiplayer(
         dispatch({ type: "PLAYER_LEFT", playerId });
         sphereViewRef.current?.removePeer(playerId);
       },
-      onPeerPositionUpdate: (peerId, position, heading, name) => {
+      onMatchQueued: (queue) => {
+        dispatch({ type: "MATCH_QUEUED", queue });
+      },
+      onPeerPositionUpdate: (peerId, position, heading, name, raceTime) => {
         sphereViewRef.current?.updatePeerPosition(
           peerId,
           position,
           heading,
           name,
+          raceTime,
         );
       },
+      onOwnPositionUpdate: (position, heading) => {
+        dispatch({ type: "SERVER_POSITION", position, heading });
+      },
       onCountdown: (seconds) => {
         dispatch({ type: "COUNTDOWN", seconds });
         // Zoom to max when countdown starts
@@ -96,9 +128,27 @@ export function useMultiplayer(
       onSyncRaceTime: (raceTime) => {
         dispatch({ type: "SYNC_RACE_TIME", raceTime });
       },
+      onOcs: (playerId, over) => {
+        dispatch({ type: "OCS", playerId, over });
+      },
+      onWindShadow: (factor) => {
+        dispatch({ type: "WIND_SHADOW", factor });
+      },
+      onEnergy: (energy) => {
+        dispatch({ type: "ENERGY_UPDATE", energy });
+      },
+      onCoachHint: (hint) => {
+        dispatch({ type: "COACH_HINT", hint });
+      },
       onRaceEnded: (reason) => {
         dispatch({ type: "RACE_ENDED", reason });
       },
+      onRematchProposed: (_raceId, proposerName) => {
+        dispatch({ type: "REMATCH_PROPOSED", proposerName });
+      },
+      onPersonalBest: (personalBest) => {
+        dispatch({ type: "PERSONAL_BEST", personalBest });
+      },
       onLeaderboardUpdate: (entries) => {
         dispatch({ type: "LEADERBOARD_UPDATE", entries });
       },
@@ -109,23 +159,50 @@ export function useMultiplayer(
         // Don't null the ref here - it may have already been replaced
         // by a new manager when switching races
       },
+      onReconnecting: () => {
+        console.warn("Connection lost, reconnecting to resume the race");
+      },
+      onRaceResumed: (raceTime) => {
+        if (raceTime !== null) {
+          dispatch({ type: "SYNC_RACE_TIME", raceTime });
+        }
+      },
+      onResumeFailed: () => {
+        if (multiplayerRef.current !== client) return;
+        client.disconnect();
+        multiplayerRef.current = null;
+        dispatch({ type: "LEAVE_RACE" });
+      },
     });
+    return client;
   }, [dispatch, sphereViewRef, courseRef, coursesRef]);
 
   const handleCreateRace = useCallback(
-    async (playerName: string) => {
+    async (
+      playerName: string,
+      solo: boolean,
+      randomWeather: boolean,
+      polar: string | null,
+    ) => {
       const course = courseRef.current;
       if (!course) return;
       const client = createMultiplayerClient();
       multiplayerRef.current = client;
       await client.connect();
-      client.createRace(course.key, playerName, getOrCreatePlayerId());
+      client.createRace(
+        course.key,
+        playerName,
+        getOrCreatePlayerId(),
+        solo,
+        randomWeather,
+        polar,
+      );
     },
     [createMultiplayerClient, courseRef],
   );
 
   const handleJoinRace = useCallback(
-    async (raceId: string, playerName: string) => {
+    async (raceId: string, playerName: string, polar: string | null) => {
       // Leave current race if we're in one
       if (multiplayerRef.current) {
         multiplayerRef.current.leaveRace();
@@ -136,15 +213,54 @@ export function useMultiplayer(
       const client = createMultiplayerClient();
       multiplayerRef.current = client;
       await client.connect();
-      client.joinRace(raceId, playerName, getOrCreatePlayerId());
+      client.joinRace(raceId, playerName, getOrCreatePlayerId(), polar);
     },
     [createMultiplayerClient],
   );
 
+  const handleStartDaily = useCallback(
+    async (playerName: string) => {
+      if (multiplayerRef.current) {
+        multiplayerRef.current.leaveRace();
+        multiplayerRef.current.disconnect();
+        multiplayerRef.current = null;
+      }
+
+      const client = createMultiplayerClient();
+      multiplayerRef.current = client;
+      await client.connect();
+      client.startDaily(playerName, getOrCreatePlayerId());
+    },
+    [createMultiplayerClient],
+  );
+
+  const handleQuickMatch = useCallback(
+    async (playerName: string, polar: string | null) => {
+      const course = courseRef.current;
+      if (!course) return;
+      let client = multiplayerRef.current;
+      if (!client) {
+        client = createMultiplayerClient();
+        multiplayerRef.current = client;
+        await client.connect();
+      }
+      client.quickMatch(course.key, playerName, getOrCreatePlayerId(), polar);
+    },
+    [createMultiplayerClient, courseRef],
+  );
+
   const handleStartRace = useCallback(() => {
     multiplayerRef.current?.startRace();
   }, []);
 
+  const handleAddServerGhosts = useCallback((count: number) => {
+    multiplayerRef.current?.addServerGhosts(count);
+  }, []);
+
+  const handleProposeRematch = useCallback(() => {
+    multiplayerRef.current?.proposeRematch();
+  }, []);
+
   const handleLeaveRace = useCallback(() => {
     multiplayerRef.current?.leaveRace();
     multiplayerRef.current?.disconnect();
@@ -157,8 +273,12 @@ export function useMultiplayer(
     {
       onCreateRace: handleCreateRace,
       onJoinRace: handleJoinRace,
+      onQuickMatch: handleQuickMatch,
+      onStartDaily: handleStartDaily,
       onStartRace: handleStartRace,
       onLeaveRace: handleLeaveRace,
+      onAddServerGhosts: handleAddServerGhosts,
+      onProposeRematch: handleProposeRematch,
     },
   ];
 }
@@ -5,7 +5,7 @@ import { Course, WindRasterSource } from "../../models";
 import { AppAction } from "../state";
 import { initLandData } from "../land";
 import { currentWindContext } from "../wind-context";
-import { loadPolar } from "../polar";
+import { loadPolar, loadSails } from "../polar";
 
 /**
  * Hook to handle race data loading when entering Lobby state.
@@ -35,14 +35,15 @@ export function useRaceDataLoader(
           windRasterSources,
         );
 
-        // Load wind rasters and polar in parallel
-        const [, polar] = await Promise.all([
+        // Load wind rasters, polar and sails in parallel
+        const [, polar, sails] = await Promise.all([
           interpolatedWindRef.current.update(
             currentWindSource,
             nextWindSources,
             true, // awaitAll
           ),
           loadPolar(course.polar),
+          loadSails(course.polar),
         ]);
 
         // Update visualization
@@ -52,7 +53,7 @@ export function useRaceDataLoader(
         sphereViewRef.current?.updateWind(interpolatedWindRef.current, factor);
 
         // Dispatch polar loaded first, then wind success
-        dispatch({ type: "POLAR_LOADED", polar });
+        dispatch({ type: "POLAR_LOADED", polar, sails });
         dispatch({
           type: "WIND_LOAD_RESULT",
           result: { status: "success", data: undefined },
@@ -0,0 +1,218 @@
+import { useState, useEffect, useCallback, useRef } from "react";
+import { SphereView } from "../../sphere";
+import InterpolatedWind from "../../interpolated-wind";
+import { Course, WindRasterSource } from "../../models";
+import {
+  fetchReplayPath,
+  interpolatePosition,
+  type PathPoint,
+} from "../../replay-path";
+import { currentWindContext } from "../wind-context";
+import { haversineDistanceNm } from "../../utils";
+
+const serverUrl = import.meta.env.REWIND_SERVER_URL;
+
+/** Playback speeds, as multiples of the race speed of the course */
+export const REPLAY_SPEEDS = [1, 4, 16, 64];
+
+export type Replay = {
+  resultId: number;
+  course: Course;
+  playerName: string;
+  /** Course time the race started at */
+  startTime: number;
+  finishTime: number;
+  path: PathPoint[];
+  windRasterSources: WindRasterSource[];
+};
+
+export type ReplayState = {
+  replay: Replay | null;
+  error: string | null;
+  courseTime: number;
+  playing: boolean;
+  speed: number;
+  setPlaying: (playing: boolean) => void;
+  setSpeed: (speed: number) => void;
+  seek: (courseTime: number) => void;
+};
+
+/** Boat speed in knots between two samples */
+function speedBetween(a: PathPoint, b: PathPoint): number {
+  const hours = (b.raceTime - a.raceTime) / 3_600_000;
+  return hours > 0 ? haversineDistanceNm(a, b) / hours : 0;
+}
+
+/** Boat speed in knots between the samples around a course time */
+function speedAt(path: PathPoint[], courseTime: number): number {
+  const i = path.findIndex((p) => p.raceTime > courseTime);
+  return i > 0 ? speedBetween(path[i - 1], path[i]) : 0;
+}
+
+/**
+ * Hook to play back a stored result on the globe: loads its path and the
+ * wind it was sailed in, then moves the boat along the path in course time.
+ */
+export function useReplay(
+  resultId: number | null,
+  coursesRef: React.MutableRefObject<Map<string, Course>>,
+  sphereViewRef: React.MutableRefObject<SphereView | null>,
+  interpolatedWindRef: React.MutableRefObject<InterpolatedWind>,
+): ReplayState {
+  const [replay, setReplay] = useState<Replay | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const [courseTime, setCourseTime] = useState(0);
+  const [playing, setPlaying] = useState(false);
+  const [speed, setSpeed] = useState(REPLAY_SPEEDS[1]);
+  const replayRef = useRef<Replay | null>(null);
+  replayRef.current = replay;
+
+  // The wake shows the path sailed until a course time
+  const drawWake = useCallback(
+    (replay: Replay, time: number) => {
+      const sphereView = sphereViewRef.current;
+      if (!sphereView) return;
+      sphereView.wake.clear();
+      const { path } = replay;
+      for (let i = 0; i < path.length && path[i].raceTime <= time; i++) {
+        const speed = i + 1 < path.length ? speedBetween(path[i], path[i + 1]) : 0;
+        sphereView.wake.addPoint(path[i], speed);
+      }
+    },
+    [sphereViewRef],
+  );
+
+  // Load the replay
+  useEffect(() => {
+    setReplay(null);
+    setError(null);
+    setPlaying(false);
+    if (resultId === null) return;
+
+    const controller = new AbortController();
+    const load = async () => {
+      try {
+        const res = await fetch(`${serverUrl}/replay/${resultId}`, {
+          signal: controller.signal,
+        });
+        if (!res.ok) throw new Error("Replay not found");
+        const info = await res.json();
+        const course = coursesRef.current.get(info.courseKey);
+        if (!course) throw new Error("Course not found");
+
+        const path = await fetchReplayPath(info.pathUrl);
+        if (path.length === 0) throw new Error("Empty replay path");
+
+        const [current, next] = currentWindContext(
+          info.startTime,
+          null,
+          info.windRasterSources,
+        );
+        await interpolatedWindRef.current.update(current, next, true);
+        if (controller.signal.aborted) return;
+
+        const loaded: Replay = {
+          resultId,
+          course,
+          playerName: info.playerName,
+          startTime: info.startTime,
+          finishTime: info.finishTime,
+          path,
+          windRasterSources: info.windRasterSources,
+        };
+        sphereViewRef.current?.setCourse(course);
+        sphereViewRef.current?.focusOnCourseStart();
+        setReplay(loaded);
+        setCourseTime(path[0].raceTime);
+        setPlaying(true);
+      } catch (err) {
+        if (err instanceof DOMException && err.name === "AbortError") return;
+        console.error("Failed to load replay:", err);
+        setError(err instanceof Error ? err.message : "Failed to load replay");
+      }
+    };
+
+    load();
+    return () => controller.abort();
+  }, [resultId, coursesRef, sphereViewRef, interpolatedWindRef]);
+
+  // Advance course time while playing, at the race speed times the speed
+  useEffect(() => {
+    if (!playing || !replay) return;
+    const end = replay.path[replay.path.length - 1].raceTime;
+
+    let frame: number;
+    let last = performance.now();
+    const step = (now: number) => {
+      const elapsed = now - last;
+      last = now;
+      setCourseTime((time) =>
+        Math.min(end, time + elapsed * replay.course.timeFactor * speed),
+      );
+      frame = requestAnimationFrame(step);
+    };
+    frame = requestAnimationFrame(step);
+    return () => cancelAnimationFrame(frame);
+  }, [playing, replay, speed]);
+
+  // Stop at the end of the path
+  useEffect(() => {
+    if (replay && courseTime >= replay.path[replay.path.length - 1].raceTime) {
+      setPlaying(false);
+    }
+  }, [replay, courseTime]);
+
+  // Move the boat and the wind to the course time
+  const [currentSource, nextSources] = replay
+    ? currentWindContext(courseTime, null, replay.windRasterSources)
+    : [null, []];
+  useEffect(() => {
+    if (!replay || !sphereViewRef.current) return;
+    const position = interpolatePosition(replay.path, courseTime);
+    if (!position) return;
+    sphereViewRef.current.updatePosition(
+      position,
+      position.heading,
+      speedAt(replay.path, courseTime),
+    );
+    const interpolatedWind = interpolatedWindRef.current;
+    sphereViewRef.current.updateWind(
+      interpolatedWind,
+      interpolatedWind.getInterpolationFactor(courseTime),
+    );
+  }, [replay, courseTime, sphereViewRef, interpolatedWindRef]);
+
+  useEffect(() => {
+    if (!replay) return;
+    const interpolatedWind = interpolatedWindRef.current;
+    interpolatedWind.update(currentSource, nextSources).then(() => {
+      sphereViewRef.current?.updateWind(
+        interpolatedWind,
+        interpolatedWind.getInterpolationFactor(courseTime),
+      );
+    });
+    // Reload rasters only when the report in effect changes
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, [replay, currentSource?.time, nextSources[0]?.time]);
+
+  const seek = useCallback(
+    (time: number) => {
+      const replay = replayRef.current;
+      if (!replay) return;
+      setCourseTime(time);
+      drawWake(replay, time);
+    },
+    [drawWake],
+  );
+
+  return {
+    replay,
+    error,
+    courseTime,
+    playing,
+    speed,
+    setPlaying,
+    setSpeed,
+    seek,
+  };
+}
@@ -7,6 +7,7 @@ export type SessionRefs = {
   courseTime: React.MutableRefObject<number>;
   heading: React.MutableRefObject<number>;
   nextGateIndex: React.MutableRefObject<number>;
+  lockedTWA: React.MutableRefObject<number | null>;
 };
 
 /**
@@ -21,6 +22,7 @@ export function useSessionRefs(session: Session | null): SessionRefs {
   const courseTimeRef = useRef<number>(session?.courseTime ?? 0);
   const headingRef = useRef<number>(session?.heading ?? 0);
   const nextGateIndexRef = useRef<number>(session?.nextGateIndex ?? 0);
+  const lockedTWARef = useRef<number | null>(session?.lockedTWA ?? null);
 
   // Keep refs in sync with session
   useEffect(() => {
@@ -29,17 +31,20 @@ export function useSessionRefs(session: Session | null): SessionRefs {
       courseTimeRef.current = session.courseTime;
       headingRef.current = session.heading;
       nextGateIndexRef.current = session.nextGateIndex;
+      lockedTWARef.current = session.lockedTWA;
     } else {
       positionRef.current = null;
       courseTimeRef.current = 0;
       headingRef.current = 0;
       nextGateIndexRef.current = 0;
+      lockedTWARef.current = null;
     }
   }, [
     session?.position,
     session?.courseTime,
     session?.heading,
     session?.nextGateIndex,
+    session?.lockedTWA,
   ]);
 
   return {
@@ -47,5 +52,6 @@ export function useSessionRefs(session: Session | null): SessionRefs {
     courseTime: courseTimeRef,
     heading: headingRef,
     nextGateIndex: nextGateIndexRef,
+    lockedTWA: lockedTWARef,
   };
 }
@@ -55,9 +55,14 @@ export function useSphereView(
 
   // Sync position, heading, and wind to SphereView during gameplay
   useEffect(() => {
-    if (!session) return;
+    if (!session) {
+      sphereViewRef.current?.updatePeersTime(null);
+      return;
+    }
     if (!sphereViewRef.current) return;
 
+    // Peers move with the race clock, rendered with our boat below
+    sphereViewRef.current.updatePeersTime(session.courseTime);
     sphereViewRef.current.updatePosition(
       session.position,
       session.heading,
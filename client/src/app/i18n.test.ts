@@ -0,0 +1,34 @@
+import { describe, it, expect } from "vitest";
+import { formatDistanceIn, formatDurationIn, translate } from "./i18n";
+
+describe("translate", () => {
+  it("fills placeholders", () => {
+    expect(translate("en", "finish.joinRematch", { name: "Alice" })).toBe(
+      "Join Alice's rematch",
+    );
+    expect(translate("fr", "finish.joinRematch", { name: "Alice" })).toBe(
+      "Rejoindre la revanche de Alice",
+    );
+  });
+
+  it("leaves missing placeholders", () => {
+    expect(translate("en", "finish.faster")).toBe("{time} faster");
+  });
+});
+
+describe("formatDurationIn", () => {
+  it("uses the day unit of the language", () => {
+    const ms = ((3 * 24 + 4) * 60 + 12) * 60 * 1000;
+    expect(formatDurationIn("en", ms)).toBe("3d 04:12");
+    expect(formatDurationIn("fr", ms)).toBe("3j 04:12");
+    expect(formatDurationIn("fr", 5 * 60 * 1000)).toBe("00:05");
+  });
+});
+
+describe("formatDistanceIn", () => {
+  it("formats numbers the way of the language", () => {
+    expect(formatDistanceIn("en", 4.25)).toBe("4.3 nm");
+    expect(formatDistanceIn("fr", 4.25)).toBe("4,3 M");
+    expect(formatDistanceIn("en", 1234.4)).toBe("1,234 nm");
+  });
+});
@@ -0,0 +1,166 @@
+import { createContext, useContext } from "react";
+
+export type Language = "en" | "fr";
+
+export const LANGUAGES: { code: Language; name: string }[] = [
+  { code: "en", name: "English" },
+  { code: "fr", name: "Français" },
+];
+
+const LANGUAGE_KEY = "rewind:language";
+
+const en = {
+  "choice.profile": "Profile",
+  "choice.playerName": "Player Name",
+  "choice.signIn": "Sign In",
+  "choice.daily": "Daily Challenge",
+  "choice.courses": "Courses",
+  "choice.openRaces": "Open Races",
+  "choice.hallOfFame": "Hall of Fame",
+  "choice.competitors": "Competitors",
+  "choice.addGhosts": "Add Ghosts",
+  "choice.play": "Play",
+  "race.cancel": "Cancel",
+  "race.quit": "Quit race",
+  "race.quitConfirm": "Quit this race?",
+  "leaderboard.title": "Leaderboard",
+  "leaderboard.dnf": "DNF",
+  "leaderboard.retired": "RET",
+  "finish.title": "FINISHED!",
+  "finish.raceTime": "Race Time: {time}",
+  "finish.personalBest": "New personal best!",
+  "finish.faster": "{time} faster",
+  "finish.rematch": "Rematch",
+  "finish.joinRematch": "Join {name}'s rematch",
+  "finish.back": "Back to Lobby",
+  "hud.ocs": "OCS: sail back behind the start line",
+  "hud.shadow": "shadow",
+  "hud.changing": "changing",
+  "hud.coach": "COACH",
+  "keys.turn": "Turn",
+  "keys.tack": "Tack",
+  "keys.lockTwa": "Lock TWA",
+  "keys.bestVmg": "Best VMG",
+  "keys.zoomIn": "Zoom in",
+  "keys.zoomOut": "Zoom out",
+};
+
+export type MessageKey = keyof typeof en;
+
+const fr: Record<MessageKey, string> = {
+  "choice.profile": "Profil",
+  "choice.playerName": "Nom du joueur",
+  "choice.signIn": "Connexion",
+  "choice.daily": "Défi du jour",
+  "choice.courses": "Parcours",
+  "choice.openRaces": "Courses ouvertes",
+  "choice.hallOfFame": "Palmarès",
+  "choice.competitors": "Concurrents",
+  "choice.addGhosts": "Ajouter des fantômes",
+  "choice.play": "Jouer",
+  "race.cancel": "Annuler",
+  "race.quit": "Quitter la course",
+  "race.quitConfirm": "Quitter cette course ?",
+  "leaderboard.title": "Classement",
+  "leaderboard.dnf": "DNF",
+  "leaderboard.retired": "ABD",
+  "finish.title": "ARRIVÉE !",
+  "finish.raceTime": "Temps de course : {time}",
+  "finish.personalBest": "Nouveau record personnel !",
+  "finish.faster": "{time} plus rapide",
+  "finish.rematch": "Revanche",
+  "finish.joinRematch": "Rejoindre la revanche de {name}",
+  "finish.back": "Retour au salon",
+  "hud.ocs": "OCS : repassez derrière la ligne de départ",
+  "hud.shadow": "dévent",
+  "hud.changing": "changement",
+  "hud.coach": "COACH",
+  "keys.turn": "Virer",
+  "keys.tack": "Virement",
+  "keys.lockTwa": "Bloquer le TWA",
+  "keys.bestVmg": "Meilleur VMG",
+  "keys.zoomIn": "Zoom avant",
+  "keys.zoomOut": "Zoom arrière",
+};
+
+const MESSAGES: Record<Language, Record<MessageKey, string>> = { en, fr };
+
+// Unit suffixes of formatted durations and distances
+const UNITS: Record<Language, { day: string; nm: string }> = {
+  en: { day: "d", nm: "nm" },
+  fr: { day: "j", nm: "M" },
+};
+
+function isLanguage(value: unknown): value is Language {
+  return LANGUAGES.some((l) => l.code === value);
+}
+
+/** The language picked by the player, else the browser's when supported */
+export function loadLanguage(): Language {
+  const stored = localStorage.getItem(LANGUAGE_KEY);
+  if (isLanguage(stored)) return stored;
+  const browser = navigator.language.slice(0, 2);
+  return isLanguage(browser) ? browser : "en";
+}
+
+export function saveLanguage(language: Language): void {
+  localStorage.setItem(LANGUAGE_KEY, language);
+}
+
+/** Message `key` in `language`, with `{name}` placeholders filled from `params` */
+export function translate(
+  language: Language,
+  key: MessageKey,
+  params: Record<string, string | number> = {},
+): string {
+  return MESSAGES[language][key].replace(/\{(\w+)\}/g, (match, name) =>
+    name in params ? String(params[name]) : match,
+  );
+}
+
+/** A duration in milliseconds as "14d 06:37" or "06:37" ("14j 06:37" in French) */
+export function formatDurationIn(language: Language, ms: number): string {
+  const totalSeconds = Math.floor(ms / 1000);
+  const days = Math.floor(totalSeconds / 86400);
+  const hours = Math.floor((totalSeconds % 86400) / 3600);
+  const minutes = Math.floor((totalSeconds % 3600) / 60);
+
+  const hhmm = `${String(hours).padStart(2, "0")}:${String(minutes).padStart(2, "0")}`;
+  return days > 0 ? `${days}${UNITS[language].day} ${hhmm}` : hhmm;
+}
+
+/** A distance in nautical miles, to a tenth under 10 */
+export function formatDistanceIn(language: Language, nm: number): string {
+  const number = new Intl.NumberFormat(language, {
+    maximumFractionDigits: nm < 10 ? 1 : 0,
+    minimumFractionDigits: nm < 10 ? 1 : 0,
+  }).format(nm);
+  return `${number} ${UNITS[language].nm}`;
+}
+
+export type I18n = {
+  language: Language;
+  setLanguage: (language: Language) => void;
+  t: (key: MessageKey, params?: Record<string, string | number>) => string;
+  formatDuration: (ms: number) => string;
+  formatDistance: (nm: number) => string;
+};
+
+export function createI18n(
+  language: Language,
+  setLanguage: (language: Language) => void,
+): I18n {
+  return {
+    language,
+    setLanguage,
+    t: (key, params) => translate(language, key, params),
+    formatDuration: (ms) => formatDurationIn(language, ms),
+    formatDistance: (nm) => formatDistanceIn(language, nm),
+  };
+}
+
+export const I18nContext = createContext<I18n>(createI18n("en", () => {}));
+
+export function useI18n(): I18n {
+  return useContext(I18nContext);
+}
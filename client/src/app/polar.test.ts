@@ -2,9 +2,12 @@ import { describe, it, expect } from "vitest";
 import {
   getBoatSpeed,
   calculateTWA,
+  foilingFactor,
   parsePolarTable,
   PolarData,
 } from "./polar";
+import fixture from "./polar.fixture.json";
+import vrImoca from "../../public/static/vr-imoca-full-pack.json";
 
 // Inline test polar data (subset of IMOCA polar for testing)
 const testPolarTable: Record<string, Record<string, number>> = {
@@ -160,6 +163,16 @@ describe("getBoatSpeed", () => {
   });
 });
 
+describe("getBoatSpeed against the server", () => {
+  // Speeds computed by the server `Polar::speed`, checked there too
+  it("matches the server to the bit", () => {
+    const imoca = parsePolarTable(vrImoca);
+    for (const [tws, twa, bsp] of fixture.cases) {
+      expect(getBoatSpeed(imoca, tws, twa)).toBe(bsp);
+    }
+  });
+});
+
 describe("calculateTWA", () => {
   describe("basic angles", () => {
     it("returns 0 when heading into the wind", () => {
@@ -214,3 +227,31 @@ describe("calculateTWA", () => {
     });
   });
 });
+
+describe("foilingFactor", () => {
+  const foiling = {
+    minTws: 12,
+    minTwa: 60,
+    maxTwa: 150,
+    boost: 0.25,
+    waveTws: 20,
+    instability: 0.5,
+  };
+
+  it("keeps the polar speed without foils or out of the regime", () => {
+    expect(foilingFactor(null, 20, 90, 0)).toBe(1);
+    expect(foilingFactor(foiling, 10, 90, 0)).toBe(1);
+    expect(foilingFactor(foiling, 18, 170, 0)).toBe(1);
+  });
+
+  it("lifts progressively above the wind threshold", () => {
+    expect(foilingFactor(foiling, 14, 90, 0)).toBeCloseTo(1.125);
+    expect(foilingFactor(foiling, 18, -90, 0)).toBeCloseTo(1.25);
+  });
+
+  it("loses speed over the swell in waves", () => {
+    expect(foilingFactor(foiling, 30, 90, 0)).toBeCloseTo(1);
+    expect(foilingFactor(foiling, 30, 90, 1.25)).toBeCloseTo(0.75);
+    expect(foilingFactor(foiling, 30, 90, 3.75)).toBeCloseTo(1.25);
+  });
+});
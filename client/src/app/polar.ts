@@ -7,20 +7,74 @@ export type PolarData = {
   twsValues: number[];
   twaValues: number[];
   maxSpeed: number;
+  foiling: Foiling | null;
 };
 
 /**
- * Load polar data from a JSON file.
- * @param polarName Name of the polar file (without .json extension)
+ * Foiling regime of a polar, mirrors the server `polars::Foiling`: from
+ * `minTws` and between `minTwa` and `maxTwa` the boat gains up to `boost` of
+ * its polar speed, and from `waveTws` loses up to `instability` over each swell.
+ */
+export type Foiling = {
+  minTws: number;
+  minTwa: number;
+  maxTwa: number;
+  boost: number;
+  waveTws: number;
+  instability: number;
+};
+
+// Wind over which the foils lift fully past minTws, in knots
+const FOIL_RAMP_KNOTS = 4;
+// Wind over which waves build up past waveTws, in knots
+const WAVE_RAMP_KNOTS = 10;
+// Swell period in seconds (wall-clock)
+const WAVE_PERIOD_SECONDS = 5;
+
+const serverUrl = import.meta.env.REWIND_SERVER_URL;
+
+/**
+ * Load polar data from the server.
+ * @param polarName Polar name (the course `polar` key)
  * @returns Promise resolving to parsed PolarData
  */
 export async function loadPolar(polarName: string): Promise<PolarData> {
-  const response = await fetch(`/static/${polarName}.json`);
-  if (!response.ok) {
+  const url = `${serverUrl}/polars/${encodeURIComponent(polarName)}`;
+  const [response, foilingResponse] = await Promise.all([
+    fetch(url),
+    fetch(`${url}/foiling`),
+  ]);
+  if (!response.ok || !foilingResponse.ok) {
     throw new Error(`Failed to load polar: ${polarName}`);
   }
   const table: Record<string, Record<string, number>> = await response.json();
-  return parsePolarTable(table);
+  const foiling: Foiling | null = await foilingResponse.json();
+  return { ...parsePolarTable(table), foiling };
+}
+
+/**
+ * Load the sail inventory of a boat class from the server.
+ * @param polarName Polar name of the boat class
+ * @returns Promise resolving to the polar of each sail, in inventory order
+ * (empty for classes sailing on their polar alone)
+ */
+export async function loadSails(
+  polarName: string,
+): Promise<Record<string, PolarData>> {
+  const response = await fetch(
+    `${serverUrl}/polars/${encodeURIComponent(polarName)}/sails`,
+  );
+  if (!response.ok) {
+    throw new Error(`Failed to load sails: ${polarName}`);
+  }
+  const tables: Record<string, Record<string, Record<string, number>>> =
+    await response.json();
+  return Object.fromEntries(
+    Object.entries(tables).map(([sail, table]) => [
+      sail,
+      parsePolarTable(table),
+    ]),
+  );
 }
 
 /**
@@ -45,7 +99,7 @@ export function parsePolarTable(
     }
   }
 
-  return { table, twsValues, twaValues, maxSpeed };
+  return { table, twsValues, twaValues, maxSpeed, foiling: null };
 }
 
 /**
@@ -75,10 +129,37 @@ export function getMaxPolarSpeed(polar: PolarData): number {
 }
 
 /**
- * Calculate boat speed from polar diagram using bilinear interpolation.
+ * TWA folded to 0-180: polars are symmetric. Mirrors the server `fold_twa`.
+ */
+function foldTwa(twa: number): number {
+  twa = Math.abs(twa) % 360;
+  return twa > 180 ? 360 - twa : twa;
+}
+
+/**
+ * Indices of the values surrounding `x` and its fraction between them,
+ * clamped to the first and last values. Mirrors the server `bracket`.
+ */
+function bracket(values: number[], x: number): [number, number, number] {
+  const last = values.length - 1;
+  if (x <= values[0]) return [0, 0, 0];
+  if (x >= values[last]) return [last, last, 0];
+  let i = 0;
+  while (values[i + 1] <= x) i++;
+  return [i, i + 1, (x - values[i]) / (values[i + 1] - values[i])];
+}
+
+function lerp(a: number, b: number, t: number): number {
+  return a + (b - a) * t;
+}
+
+/**
+ * Calculate boat speed from polar diagram using bilinear interpolation,
+ * clamped to the table bounds. Computed like the server `Polar::speed`, to
+ * the bit, so predicted and simulated boats sail the same.
  * @param polar The polar data
  * @param tws True Wind Speed in knots
- * @param twa True Wind Angle in degrees (0-180, symmetric)
+ * @param twa True Wind Angle in degrees (symmetric)
  * @returns Boat speed in knots
  */
 export function getBoatSpeed(
@@ -87,56 +168,43 @@ export function getBoatSpeed(
   twa: number,
 ): number {
   const { table, twsValues, twaValues } = polar;
+  const [i0, i1, twsFrac] = bracket(twsValues, tws);
+  const [j0, j1, twaFrac] = bracket(twaValues, foldTwa(twa));
+  const [twsLow, twsHigh] = [twsValues[i0], twsValues[i1]];
+  const [twaLow, twaHigh] = [twaValues[j0], twaValues[j1]];
+
+  const low = lerp(table[twsLow][twaLow], table[twsLow][twaHigh], twaFrac);
+  const high = lerp(table[twsHigh][twaLow], table[twsHigh][twaHigh], twaFrac);
+  return lerp(low, high, twsFrac);
+}
 
-  // Normalize TWA to 0-180 (polar is symmetric)
-  twa = Math.abs(twa);
-  if (twa > 180) twa = 360 - twa;
-
-  // Clamp to polar bounds
-  const minTws = twsValues[0];
-  const maxTws = twsValues[twsValues.length - 1];
-  const minTwa = twaValues[0];
-  const maxTwa = twaValues[twaValues.length - 1];
-
-  tws = Math.max(minTws, Math.min(maxTws, tws));
-  twa = Math.max(minTwa, Math.min(maxTwa, twa));
-
-  // Find surrounding TWS values
-  let twsLow = minTws;
-  let twsHigh = maxTws;
-  for (let i = 0; i < twsValues.length - 1; i++) {
-    if (tws >= twsValues[i] && tws <= twsValues[i + 1]) {
-      twsLow = twsValues[i];
-      twsHigh = twsValues[i + 1];
-      break;
-    }
-  }
-
-  // Find surrounding TWA values
-  let twaLow = minTwa;
-  let twaHigh = maxTwa;
-  for (let i = 0; i < twaValues.length - 1; i++) {
-    if (twa >= twaValues[i] && twa <= twaValues[i + 1]) {
-      twaLow = twaValues[i];
-      twaHigh = twaValues[i + 1];
-      break;
-    }
+/**
+ * Speed factor of the foiling regime, mirrors the server `Foiling::factor`.
+ * @param foiling Foiling regime of the boat polar, null without foils
+ * @param tws True Wind Speed in knots
+ * @param twa True Wind Angle in degrees (symmetric)
+ * @param clock Seconds of wall-clock time into the race (swell phase)
+ * @returns Factor of the polar boat speed
+ */
+export function foilingFactor(
+  foiling: Foiling | null,
+  tws: number,
+  twa: number,
+  clock: number,
+): number {
+  if (!foiling) return 1;
+  twa = foldTwa(twa);
+  if (tws < foiling.minTws || twa < foiling.minTwa || twa > foiling.maxTwa) {
+    return 1;
   }
-
-  // Get four corner values
-  const v00 = table[twsLow][twaLow];
-  const v01 = table[twsLow][twaHigh];
-  const v10 = table[twsHigh][twaLow];
-  const v11 = table[twsHigh][twaHigh];
-
-  // Bilinear interpolation
-  const twsFrac = twsHigh === twsLow ? 0 : (tws - twsLow) / (twsHigh - twsLow);
-  const twaFrac = twaHigh === twaLow ? 0 : (twa - twaLow) / (twaHigh - twaLow);
-
-  const v0 = v00 + (v01 - v00) * twaFrac;
-  const v1 = v10 + (v11 - v10) * twaFrac;
-
-  return v0 + (v1 - v0) * twsFrac;
+  const lift = Math.min(1, (tws - foiling.minTws) / FOIL_RAMP_KNOTS);
+  const waves = Math.max(
+    0,
+    Math.min(1, (tws - foiling.waveTws) / WAVE_RAMP_KNOTS),
+  );
+  const swell =
+    0.5 + 0.5 * Math.sin((2 * Math.PI * clock) / WAVE_PERIOD_SECONDS);
+  return 1 + foiling.boost * lift - foiling.instability * waves * swell;
 }
 
 /**
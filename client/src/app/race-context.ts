@@ -1,6 +1,6 @@
 import { createContext, useContext } from "react";
 import { Course } from "../models";
-import { PeerState } from "../multiplayer/types";
+import { MatchQueueInfo, PeerState } from "../multiplayer/types";
 import { AsyncState } from "./state";
 import { RecordedGhost } from "./App";
 
@@ -12,6 +12,7 @@ export type RaceContextValue = {
   canSelectCourse: boolean;
   players: Map<string, PeerState>;
   windStatus: AsyncState<void>["status"];
+  matchQueue: MatchQueueInfo | null;
 
   // Course state
   courses: Course[];
@@ -21,8 +22,15 @@ export type RaceContextValue = {
   recordedGhosts: Map<number, RecordedGhost>;
 
   // Race actions
-  createRace: (playerName: string) => void;
-  joinRace: (raceId: string, playerName: string) => void;
+  createRace: (
+    playerName: string,
+    solo: boolean,
+    randomWeather: boolean,
+    polar: string | null,
+  ) => void;
+  joinRace: (raceId: string, playerName: string, polar: string | null) => void;
+  quickMatch: (playerName: string, polar: string | null) => void;
+  startDaily: (playerName: string) => void;
   startRace: () => void;
   leaveRace: () => void;
 
@@ -32,7 +40,11 @@ export type RaceContextValue = {
 
   // Ghost actions
   addGhost: (entryId: number, playerName: string) => void;
+  addServerGhosts: (count: number) => void;
   removeGhost: (ghostId: number) => void;
+
+  // Replay actions
+  watchReplay: (resultId: number) => void;
 };
 
 export const RaceContext = createContext<RaceContextValue | null>(null);
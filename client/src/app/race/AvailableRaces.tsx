@@ -1,4 +1,5 @@
 import { PlayerInfo } from "../../multiplayer/types";
+import Avatar from "../Avatar";
 
 type RaceInfo = {
   id: string;
@@ -7,6 +8,7 @@ type RaceInfo = {
   max_players: number;
   race_started: boolean;
   creator_id: string;
+  scheduled_start: number | null;
 };
 
 type Props = {
@@ -28,7 +30,15 @@ export default function AvailableRaces({ races, onJoinRace }: Props) {
             className="w-full px-4 py-3 flex items-center justify-between hover:bg-slate-700 transition-all"
           >
             {race.players.map((playerInfo) => (
-              <span key={playerInfo.id} className="text-white font-mono">
+              <span
+                key={playerInfo.id}
+                className="flex items-center gap-2 text-white font-mono"
+              >
+                <Avatar
+                  name={playerInfo.name}
+                  url={playerInfo.avatarUrl}
+                  className="w-6 h-6 text-xs"
+                />
                 {playerInfo.name}{" "}
                 {playerInfo.id === race.creator_id ? "(Host)" : ""}
               </span>
@@ -7,6 +7,7 @@ import {
   AppAction,
   Session,
   RaceState,
+  SAIL_CHANGE_SECONDS,
 } from "./state";
 import { Course, WindRasterSource } from "../models";
 import { PolarData } from "./polar";
@@ -37,6 +38,7 @@ const testPolar: PolarData = {
   twsValues: [10],
   twaValues: [0, 90, 180],
   maxSpeed: 10,
+  foiling: null,
 };
 
 const testRace: RaceState = {
@@ -64,6 +66,7 @@ function makeLobbyState(
     windRasterSources: [],
     wind: asyncState.success(undefined),
     polar: testPolar,
+    sails: {},
     ...overrides,
   };
 }
@@ -82,6 +85,7 @@ function makeCountdownState(
     race: testRace,
     windRasterSources: [],
     polar: testPolar,
+    sails: {},
     ...overrides,
   };
 }
@@ -93,6 +97,8 @@ function makePlayingState(
     tag: "Playing",
     race: { ...testRace },
     raceEndedReason: null,
+    rematchProposer: null,
+    personalBest: null,
     leaderboard: [],
     session: {
       clock: 0,
@@ -108,9 +114,16 @@ function makePlayingState(
       boatSpeed: 10,
       course: testCourse,
       polar: testPolar,
+      sails: {},
+      sail: null,
+      sailChange: 0,
       currentSource: testWindRasterSources[0],
       nextSources: [testWindRasterSources[1]],
       windSpeed: { u: 5, v: -10 },
+      windShadow: 1,
+      ocs: false,
+      energy: null,
+      coachHint: null,
       nextGateIndex: 0,
       gateTimes: [],
       finishTime: null,
@@ -199,6 +212,53 @@ describe("appReducer", () => {
     });
   });
 
+  describe("PERSONAL_BEST", () => {
+    it("records a new personal best while playing", () => {
+      const personalBest = {
+        courseKey: "vg20",
+        finishTime: 4000,
+        previousBest: 5000,
+      };
+      const result = appReducer(makePlayingState({ finishTime: 5000 }), {
+        type: "PERSONAL_BEST",
+        personalBest,
+      });
+
+      expect(result.tag).toBe("Playing");
+      if (result.tag === "Playing") {
+        expect(result.personalBest).toEqual(personalBest);
+      }
+    });
+  });
+
+  describe("REMATCH_PROPOSED", () => {
+    it("records the proposer while playing", () => {
+      const result = appReducer(makePlayingState({ finishTime: 5000 }), {
+        type: "REMATCH_PROPOSED",
+        proposerName: "Alice",
+      });
+
+      expect(result.tag).toBe("Playing");
+      if (result.tag === "Playing") {
+        expect(result.rematchProposer).toBe("Alice");
+      }
+    });
+
+    it("moves to the rematch lobby when joined", () => {
+      const result = appReducer(makePlayingState({ finishTime: 5000 }), {
+        type: "RACE_JOINED",
+        raceId: "REM001",
+        playerId: "player-1",
+        course: testCourse,
+        isCreator: true,
+        players: new Map(),
+        windRasterSources: [testWindRasterSources[1]],
+      });
+
+      expect(result.tag).toBe("Lobby");
+    });
+  });
+
   describe("PLAYER_JOINED", () => {
     it("adds player to race", () => {
       const state = makeLobbyState();
@@ -410,6 +470,94 @@ describe("appReducer", () => {
     });
   });
 
+  describe("CHANGE_SAIL", () => {
+    const sails = { jib: testPolar, spinnaker: testPolar };
+
+    it("hoists another sail of the inventory", () => {
+      const state = makePlayingState({ sails, sail: "jib" });
+      const action: AppAction = { type: "CHANGE_SAIL", sail: "spinnaker" };
+
+      const result = appReducer(state, action);
+
+      expect(result.tag).toBe("Playing");
+      if (result.tag === "Playing") {
+        expect(result.session.sail).toBe("spinnaker");
+        expect(result.session.sailChange).toBe(SAIL_CHANGE_SECONDS);
+      }
+    });
+
+    it("ignores the sail in use and unknown sails", () => {
+      const state = makePlayingState({ sails, sail: "jib" });
+
+      expect(appReducer(state, { type: "CHANGE_SAIL", sail: "jib" })).toBe(
+        state,
+      );
+      expect(appReducer(state, { type: "CHANGE_SAIL", sail: "code0" })).toBe(
+        state,
+      );
+    });
+  });
+
+  describe("ENERGY_UPDATE", () => {
+    const energy = {
+      level: 0.4,
+      autopilot: true,
+      instruments: true,
+      hydro: false,
+    };
+
+    it("stores the energy state of the boat", () => {
+      const state = makePlayingState({ lockedTWA: 90 });
+      const result = appReducer(state, { type: "ENERGY_UPDATE", energy });
+
+      expect(result.tag).toBe("Playing");
+      if (result.tag === "Playing") {
+        expect(result.session.energy).toEqual(energy);
+        expect(result.session.lockedTWA).toBe(90);
+      }
+    });
+
+    it("releases the TWA lock when the autopilot goes off", () => {
+      const state = makePlayingState({ lockedTWA: 90 });
+      const result = appReducer(state, {
+        type: "ENERGY_UPDATE",
+        energy: { ...energy, autopilot: false },
+      });
+
+      expect(result.tag).toBe("Playing");
+      if (result.tag === "Playing") {
+        expect(result.session.lockedTWA).toBeNull();
+      }
+    });
+  });
+
+  describe("OCS", () => {
+    it("flags the player over the start line", () => {
+      const state = makePlayingState();
+      const result = appReducer(state, {
+        type: "OCS",
+        playerId: state.race.myPlayerId,
+        over: true,
+      });
+
+      expect(result.tag).toBe("Playing");
+      if (result.tag === "Playing") {
+        expect(result.session.ocs).toBe(true);
+      }
+    });
+
+    it("ignores other players", () => {
+      const state = makePlayingState();
+      const result = appReducer(state, {
+        type: "OCS",
+        playerId: "someone-else",
+        over: true,
+      });
+
+      expect(result).toBe(state);
+    });
+  });
+
   describe("LOCAL_WIND_UPDATED", () => {
     it("updates wind speed in session", () => {
       const state = makePlayingState();
@@ -427,4 +575,34 @@ describe("appReducer", () => {
       }
     });
   });
+
+  describe("SERVER_POSITION", () => {
+    it("keeps the local position when close to the server", () => {
+      const state = makePlayingState();
+      const action: AppAction = {
+        type: "SERVER_POSITION",
+        position: { lng: -10.001, lat: 45 },
+        heading: 90,
+      };
+
+      expect(appReducer(state, action)).toBe(state);
+    });
+
+    it("snaps to the server position when drifting", () => {
+      const state = makePlayingState();
+      const action: AppAction = {
+        type: "SERVER_POSITION",
+        position: { lng: -9, lat: 45 },
+        heading: 90,
+      };
+
+      const result = appReducer(state, action);
+
+      expect(result.tag).toBe("Playing");
+      if (result.tag === "Playing") {
+        expect(result.session.position).toEqual({ lng: -9, lat: 45 });
+        expect(result.session.heading).toBe(90);
+      }
+    });
+  });
 });
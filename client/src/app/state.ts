@@ -1,12 +1,26 @@
 import { produce, enableMapSet } from "immer";
-import { Course, LngLat, WindSpeed, WindRasterSource } from "../models";
-import { LeaderboardEntry, PeerState } from "../multiplayer/types";
+import {
+  Course,
+  EnergyStatus,
+  LngLat,
+  WindSpeed,
+  WindRasterSource,
+} from "../models";
+import {
+  CoachHint,
+  LeaderboardEntry,
+  MatchQueueInfo,
+  PeerState,
+  PersonalBest,
+} from "../multiplayer/types";
 import { tick } from "./tick";
 import { calculateTackTarget } from "./tack";
-import { toggleTWALock } from "./twa-lock";
+import { holdsWindAngle, toggleTWALock } from "./twa-lock";
 import { calculateVMGLockHeading } from "./vmg-lock";
 import { currentWindContext } from "./wind-context";
+import { finishIndex } from "./gate-crossing";
 import { PolarData } from "./polar";
+import { haversineDistanceNm } from "../utils";
 
 // Enable Map support in Immer
 enableMapSet();
@@ -25,7 +39,7 @@ export const asyncState = {
 };
 
 export type AppState =
-  | { tag: "Idle" }
+  | { tag: "Idle"; matchQueue?: MatchQueueInfo }
   | {
       tag: "Lobby";
       course: Course;
@@ -33,6 +47,7 @@ export type AppState =
       windRasterSources: WindRasterSource[];
       wind: AsyncState<void>;
       polar: PolarData | null;
+      sails: Record<string, PolarData>;
     }
   | {
       tag: "Countdown";
@@ -40,6 +55,7 @@ export type AppState =
       course: Course;
       windRasterSources: WindRasterSource[];
       polar: PolarData;
+      sails: Record<string, PolarData>;
       race: RaceState;
     }
   | {
@@ -47,6 +63,10 @@ export type AppState =
       session: Session;
       race: RaceState;
       raceEndedReason: string | null;
+      /** Name of the player who proposed a rematch, once we're invited */
+      rematchProposer: string | null;
+      /** Set once the server saved our finish as a new personal best */
+      personalBest: PersonalBest | null;
       leaderboard: LeaderboardEntry[];
     };
 
@@ -71,10 +91,17 @@ export type Session = {
   boatSpeed: number; // in knots
   course: Course;
   polar: PolarData;
+  sails: Record<string, PolarData>; // sail inventory, empty when sailing on the class polar
+  sail: string | null; // sail in use
+  sailChange: number; // remaining sail change time in seconds (wall-clock)
   currentSource: WindRasterSource | null;
   nextSources: WindRasterSource[];
   windSpeed: WindSpeed;
-  nextGateIndex: number; // 0..gates.length for intermediate gates, gates.length for finish
+  windShadow: number; // share of the wind left by the boats upwind, from the server
+  ocs: boolean; // over the start line at the gun: gate 0 counts once back behind it
+  energy: EnergyStatus | null; // battery and systems, from the server on courses managing energy
+  coachHint: CoachHint | null; // last answer of the coach
+  nextGateIndex: number; // next mark, gates lap after lap then the finish (see courseMark)
   gateTimes: number[]; // course time when each gate was crossed
   finishTime: number | null; // null = racing, number = finished at race time
 };
@@ -86,6 +113,7 @@ export type AppAction =
   | { type: "TACK" }
   | { type: "TOGGLE_TWA_LOCK" }
   | { type: "VMG_LOCK"; mode: "upwind" | "downwind" | "closest" }
+  | { type: "CHANGE_SAIL"; sail: string }
   | { type: "GATE_CROSSED"; gateIndex: number; courseTime: number }
   // Multiplayer actions
   | {
@@ -106,19 +134,37 @@ export type AppAction =
     }
   | { type: "PLAYER_JOINED"; playerId: string; playerName: string }
   | { type: "PLAYER_LEFT"; playerId: string }
+  | { type: "MATCH_QUEUED"; queue: MatchQueueInfo }
   | { type: "COUNTDOWN"; seconds: number }
   | { type: "START_PLAYING" }
   | { type: "WIND_LOAD_RESULT"; result: AsyncState<void> }
-  | { type: "POLAR_LOADED"; polar: PolarData }
+  | {
+      type: "POLAR_LOADED";
+      polar: PolarData;
+      sails: Record<string, PolarData>;
+    }
   | { type: "LEAVE_RACE" }
   | { type: "SYNC_RACE_TIME"; raceTime: number }
+  | { type: "OCS"; playerId: string; over: boolean }
+  | { type: "WIND_SHADOW"; factor: number }
+  | { type: "ENERGY_UPDATE"; energy: EnergyStatus }
+  | { type: "COACH_HINT"; hint: CoachHint }
+  | { type: "SERVER_POSITION"; position: LngLat; heading: number }
   | { type: "RACE_ENDED"; reason: string }
+  | { type: "REMATCH_PROPOSED"; proposerName: string }
+  | { type: "PERSONAL_BEST"; personalBest: PersonalBest }
   | { type: "LEADERBOARD_UPDATE"; entries: LeaderboardEntry[] };
 
 export type Turn = "left" | "right" | null;
 
 export const initialState: AppState = { tag: "Idle" };
 
+// Distance from the server-simulated position before the local boat is snapped back
+const SERVER_POSITION_TOLERANCE_NM = 0.5;
+
+// Sail change duration in seconds (wall-clock), as on the server
+export const SAIL_CHANGE_SECONDS = 4;
+
 // Helper to create a Playing state from Countdown state
 function createPlayingState(
   state: Extract<AppState, { tag: "Countdown" }>,
@@ -133,6 +179,8 @@ function createPlayingState(
     tag: "Playing",
     race: state.race,
     raceEndedReason: null,
+    rematchProposer: null,
+    personalBest: null,
     leaderboard: [],
     session: {
       clock: 0,
@@ -148,9 +196,17 @@ function createPlayingState(
       boatSpeed: 0,
       course: state.course,
       polar: state.polar,
+      sails: state.sails,
+      // Boats start with the first sail of the inventory, like on the server
+      sail: Object.keys(state.sails)[0] ?? null,
+      sailChange: 0,
       currentSource,
       nextSources,
       windSpeed: { u: 0, v: 0 },
+      windShadow: 1,
+      ocs: false,
+      energy: null,
+      coachHint: null,
       nextGateIndex: 0,
       gateTimes: [],
       finishTime: null,
@@ -176,11 +232,11 @@ export function appReducer(state: AppState, action: AppAction): AppState {
 
         // Handle gate crossing
         if (gateCrossed !== null) {
-          const numGates = draft.session.course.gates.length;
+          const finish = finishIndex(draft.session.course);
           draft.session.gateTimes.push(tickResult.courseTime);
           draft.session.nextGateIndex = gateCrossed + 1;
           // If crossed finish line, set finish time
-          if (gateCrossed === numGates) {
+          if (gateCrossed === finish) {
             draft.session.finishTime = tickResult.courseTime;
           }
         }
@@ -222,16 +278,28 @@ export function appReducer(state: AppState, action: AppAction): AppState {
       });
     }
 
+    case "CHANGE_SAIL":
+      if (
+        state.tag !== "Playing" ||
+        !(action.sail in state.session.sails) ||
+        action.sail === state.session.sail
+      )
+        return state;
+      return produce(state, (draft) => {
+        draft.session.sail = action.sail;
+        draft.session.sailChange = SAIL_CHANGE_SECONDS;
+      });
+
     case "GATE_CROSSED": {
       if (state.tag !== "Playing") return state;
       // Validate this is the expected next gate
       if (action.gateIndex !== state.session.nextGateIndex) return state;
-      const numGates = state.session.course.gates.length;
+      const finish = finishIndex(state.session.course);
       return produce(state, (draft) => {
         draft.session.gateTimes.push(action.courseTime);
         draft.session.nextGateIndex = action.gateIndex + 1;
-        // If crossed finish line (gate index === numGates), set finish time
-        if (action.gateIndex === numGates) {
+        // If crossed finish line (gate index === finishIndex), set finish time
+        if (action.gateIndex === finish) {
           draft.session.finishTime = action.courseTime;
         }
       });
@@ -245,6 +313,7 @@ export function appReducer(state: AppState, action: AppAction): AppState {
         windRasterSources: action.windRasterSources,
         wind: asyncState.loading(),
         polar: null,
+        sails: {},
         race: {
           id: action.raceId,
           myPlayerId: action.playerId,
@@ -254,13 +323,20 @@ export function appReducer(state: AppState, action: AppAction): AppState {
       };
 
     case "RACE_JOINED":
-      if (state.tag !== "Idle" && state.tag !== "Lobby") return state;
+      // Joining from Playing moves to a rematch lobby
+      if (
+        state.tag !== "Idle" &&
+        state.tag !== "Lobby" &&
+        state.tag !== "Playing"
+      )
+        return state;
       return {
         tag: "Lobby",
         course: action.course,
         windRasterSources: action.windRasterSources,
         wind: asyncState.loading(),
         polar: null,
+        sails: {},
         race: {
           id: action.raceId,
           myPlayerId: action.playerId,
@@ -279,6 +355,7 @@ export function appReducer(state: AppState, action: AppAction): AppState {
       if (state.tag !== "Lobby") return state;
       return produce(state, (draft) => {
         draft.polar = action.polar;
+        draft.sails = action.sails;
       });
 
     case "PLAYER_JOINED":
@@ -299,6 +376,10 @@ export function appReducer(state: AppState, action: AppAction): AppState {
         draft.race.players.delete(action.playerId);
       });
 
+    case "MATCH_QUEUED":
+      if (state.tag !== "Idle") return state;
+      return { tag: "Idle", matchQueue: action.queue };
+
     case "COUNTDOWN":
       if (state.tag !== "Lobby" && state.tag !== "Countdown") return state;
       // Can only start countdown if wind and polar are loaded
@@ -318,6 +399,7 @@ export function appReducer(state: AppState, action: AppAction): AppState {
           course: state.course,
           windRasterSources: state.windRasterSources,
           polar: state.polar!, // We know polar is not null from the check above
+          sails: state.sails,
           race: state.race,
         };
       }
@@ -331,7 +413,7 @@ export function appReducer(state: AppState, action: AppAction): AppState {
       return createPlayingState(state, state.windRasterSources);
 
     case "LEAVE_RACE":
-      if (state.tag === "Idle") return state;
+      if (state.tag === "Idle" && !state.matchQueue) return state;
       return { tag: "Idle" };
 
     case "SYNC_RACE_TIME":
@@ -340,12 +422,73 @@ export function appReducer(state: AppState, action: AppAction): AppState {
         draft.session.serverRaceTime = action.raceTime;
       });
 
+    case "OCS":
+      if (
+        state.tag !== "Playing" ||
+        action.playerId !== state.race.myPlayerId
+      ) {
+        return state;
+      }
+      return produce(state, (draft) => {
+        draft.session.ocs = action.over;
+      });
+
+    case "WIND_SHADOW":
+      if (state.tag !== "Playing") return state;
+      return produce(state, (draft) => {
+        draft.session.windShadow = action.factor;
+      });
+
+    case "COACH_HINT":
+      if (state.tag !== "Playing") return state;
+      return produce(state, (draft) => {
+        draft.session.coachHint = action.hint;
+      });
+
+    case "ENERGY_UPDATE":
+      if (state.tag !== "Playing") return state;
+      return produce(state, (draft) => {
+        draft.session.energy = action.energy;
+        // Without the autopilot or instruments, the boat keeps its heading
+        if (!holdsWindAngle(draft.session)) {
+          draft.session.lockedTWA = null;
+        }
+      });
+
+    case "SERVER_POSITION": {
+      // The server simulates the boat: correct the local prediction when it drifts
+      if (state.tag !== "Playing" || state.session.finishTime !== null) {
+        return state;
+      }
+      const drift = haversineDistanceNm(
+        state.session.position,
+        action.position,
+      );
+      if (drift < SERVER_POSITION_TOLERANCE_NM) return state;
+      return produce(state, (draft) => {
+        draft.session.position = action.position;
+        draft.session.heading = action.heading;
+      });
+    }
+
     case "RACE_ENDED":
       if (state.tag !== "Playing") return state;
       return produce(state, (draft) => {
         draft.raceEndedReason = action.reason;
       });
 
+    case "REMATCH_PROPOSED":
+      if (state.tag !== "Playing") return state;
+      return produce(state, (draft) => {
+        draft.rematchProposer = action.proposerName;
+      });
+
+    case "PERSONAL_BEST":
+      if (state.tag !== "Playing") return state;
+      return produce(state, (draft) => {
+        draft.personalBest = action.personalBest;
+      });
+
     case "LEADERBOARD_UPDATE": {
       if (state.tag !== "Playing") return state;
       const myEntry = action.entries.find(
@@ -8,6 +8,7 @@ const testPolar: PolarData = {
   twsValues: [10],
   twaValues: [0, 90, 180],
   maxSpeed: 10,
+  foiling: null,
 };
 
 // Helper to create a minimal session for testing
@@ -33,6 +34,13 @@ function makeSession(overrides: Partial<Session> = {}): Session {
       maxDays: 90,
     },
     polar: testPolar,
+    sails: {},
+    sail: null,
+    sailChange: 0,
+    windShadow: 1,
+    ocs: false,
+    energy: null,
+    coachHint: null,
     clock: 0,
     lastWindRefresh: 0,
     courseTime: 0,
@@ -1,9 +1,16 @@
 import { LngLat, WindRasterSource } from "../models";
-import { getBoatSpeed, calculateTWA } from "./polar";
+import { getBoatSpeed, calculateTWA, foilingFactor } from "./polar";
 import { isPointOnLand } from "./land";
 import { Session } from "./state";
 import { currentWindContext } from "./wind-context";
-import { getWindDirection, getWindSpeed, msToKnots, reframeLongitude } from "../utils";
+import {
+  clampLat,
+  getWindDirection,
+  getWindSpeed,
+  msToKnots,
+  nmToKm,
+  reframeLongitude,
+} from "../utils";
 import { checkGateCrossing } from "./gate-crossing";
 
 export type TickResult = {
@@ -15,6 +22,7 @@ export type TickResult = {
   targetHeading: number | null;
   lockedTWA: number | null;
   turningDuration: number;
+  sailChange: number;
   currentSource: WindRasterSource | null;
   nextSources: WindRasterSource[];
   gateCrossed: number | null; // gate index if crossed this tick, null otherwise
@@ -28,6 +36,11 @@ const MAX_TURN_RATE = 70;
 // Time constant for the exponential ramp (seconds)
 const TURN_ACCEL_TAU = 0.12;
 
+// Share of the polar speed reached while changing sails
+const SAIL_CHANGE_SPEED = 0.5;
+// Share of the polar speed reached when hand steering, autopilot off
+const MANUAL_SPEED = 0.85;
+
 // Inertia time constant in seconds (wall-clock).
 // Higher = more sluggish, lower = more responsive.
 const INERTIA_TAU = 1;
@@ -93,22 +106,39 @@ export function tick(session: Session, delta: number): TickResult {
     heading = (windDirNorm - lockedTWA + 360) % 360;
   }
 
-  // Calculate TWS in knots (wind is in m/s, convert to knots)
-  const tws = msToKnots(getWindSpeed(session.windSpeed));
+  // Calculate TWS in knots (wind is in m/s, convert to knots), less the
+  // shadow of the boats upwind
+  const tws = msToKnots(getWindSpeed(session.windSpeed)) * session.windShadow;
 
   // Calculate TWA and boat speed from polar
   const twa = calculateTWA(heading, windDirNorm);
-  const targetSpeed = getBoatSpeed(session.polar, tws, twa);
+  const polar =
+    (session.sail !== null && session.sails[session.sail]) || session.polar;
   const dt = delta / 1000;
+  // Sails fly on the foils of the boat class
+  let targetSpeed =
+    getBoatSpeed(polar, tws, twa) *
+    foilingFactor(session.polar.foiling, tws, twa, newClock / 1000);
+  let sailChange = session.sailChange;
+  if (sailChange > 0) {
+    targetSpeed *= SAIL_CHANGE_SPEED;
+    sailChange = Math.max(0, sailChange - dt);
+  }
+  // Systems in use, as reported by the server
+  if (session.energy !== null) {
+    if (!session.energy.autopilot) targetSpeed *= MANUAL_SPEED;
+    if (session.energy.hydro) {
+      targetSpeed *= 1 - (session.course.energy?.hydroDrag ?? 0);
+    }
+  }
   const alpha = 1 - Math.exp(-dt / INERTIA_TAU);
   let boatSpeed = session.boatSpeed + (targetSpeed - session.boatSpeed) * alpha;
 
   // Move boat based on speed and heading
   // Boat speed is in knots, delta is in ms
-  // 1 knot = 1.852 km/h = 0.0005144 km/s
   // Simulate time is accelerated by timeFactor
   const simDeltaSeconds = (delta / 1000) * session.course.timeFactor;
-  const distanceKm = boatSpeed * 1.852 * (simDeltaSeconds / 3600);
+  const distanceKm = nmToKm(boatSpeed) * (simDeltaSeconds / 3600);
 
   // Convert heading to radians (0 = north, clockwise)
   const headingRad = (heading * Math.PI) / 180;
@@ -122,7 +152,7 @@ export function tick(session: Session, delta: number): TickResult {
     (111 * Math.cos((session.position.lat * Math.PI) / 180));
 
   let newPosition: LngLat = {
-    lat: session.position.lat + latDelta,
+    lat: clampLat(session.position.lat + latDelta),
     lng: reframeLongitude(session.position.lng + lngDelta),
   };
 
@@ -132,10 +162,12 @@ export function tick(session: Session, delta: number): TickResult {
     newPosition = session.position;
   }
 
-  // Check for gate crossing (only if position changed and not finished)
+  // Check for gate crossing (only if position changed and not finished).
+  // Over the start line early, gate 0 doesn't count until back behind it.
   let gateCrossed: number | null = null;
   if (
     session.finishTime === null &&
+    !(session.ocs && session.nextGateIndex === 0) &&
     (newPosition.lat !== session.position.lat ||
       newPosition.lng !== session.position.lng)
   ) {
@@ -156,6 +188,7 @@ export function tick(session: Session, delta: number): TickResult {
     targetHeading,
     lockedTWA,
     turningDuration,
+    sailChange,
     currentSource: currentSource,
     nextSources: nextSources,
     gateCrossed,
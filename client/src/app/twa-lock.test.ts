@@ -8,6 +8,7 @@ const testPolar: PolarData = {
   twsValues: [10],
   twaValues: [0, 90, 180],
   maxSpeed: 10,
+  foiling: null,
 };
 
 // Helper to create a minimal session for testing
@@ -33,6 +34,13 @@ function makeSession(overrides: Partial<Session> = {}): Session {
       maxDays: 90,
     },
     polar: testPolar,
+    sails: {},
+    sail: null,
+    sailChange: 0,
+    windShadow: 1,
+    ocs: false,
+    energy: null,
+    coachHint: null,
     clock: 0,
     lastWindRefresh: 0,
     courseTime: 0,
@@ -145,6 +153,29 @@ describe("toggleTWALock", () => {
 
       expect(toggleTWALock(session1)).not.toBe(toggleTWALock(session2));
     });
+
+    it("does not lock without the autopilot or instruments", () => {
+      const energy = {
+        level: 0.5,
+        autopilot: true,
+        instruments: true,
+        hydro: true,
+      };
+      expect(toggleTWALock(makeSession({ heading: 45, energy }))).toBe(-45);
+      expect(
+        toggleTWALock(
+          makeSession({ heading: 45, energy: { ...energy, autopilot: false } }),
+        ),
+      ).toBeNull();
+      expect(
+        toggleTWALock(
+          makeSession({
+            heading: 45,
+            energy: { ...energy, instruments: false },
+          }),
+        ),
+      ).toBeNull();
+    });
   });
 
   describe("unlocking", () => {
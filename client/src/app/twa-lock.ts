@@ -18,6 +18,17 @@ export function calculateSignedTWA(session: Session): number {
   return twa;
 }
 
+/**
+ * Holding a TWA takes the autopilot and the wind instruments, on courses
+ * managing energy.
+ */
+export function holdsWindAngle(session: Session): boolean {
+  return (
+    session.energy === null ||
+    (session.energy.autopilot && session.energy.instruments)
+  );
+}
+
 /**
  * Toggle TWA lock state.
  * Returns the new lockedTWA value (signed TWA if locking, null if unlocking).
@@ -26,6 +37,8 @@ export function toggleTWALock(session: Session): number | null {
   if (session.lockedTWA !== null) {
     // Unlock
     return null;
+  } else if (!holdsWindAngle(session)) {
+    return null;
   } else {
     // Lock to current signed TWA
     return calculateSignedTWA(session);
@@ -23,7 +23,8 @@ export type Course = {
   key: string;
   name: string;
   description: string;
-  polar: string;
+  polar: string; // default boat class
+  boatClasses?: string[]; // other boat classes players may pick
   startTime: number;
   start: LngLat;
   startHeading: number;
@@ -32,6 +33,61 @@ export type Course = {
   routeWaypoints: LngLat[][]; // waypoints for each leg
   timeFactor: number;
   maxDays: number;
+  energy?: Energy | null; // energy budget of the boats, when the course manages energy
+  minDepth?: number | null; // meters, boats ground in shallower water
+  laps?: number; // times the gates are sailed before the finish, 1 when absent
+  metrics?: CourseMetrics | null; // served with the courses once computed
+  tags?: CourseTag[];
+  published?: boolean; // true when absent
+  opensAt?: number | null; // window races can be created in, ms since epoch
+  closesAt?: number | null;
+};
+
+export type CourseTag = "ocean" | "offshore" | "sprint" | "historic";
+
+export const COURSE_TAGS: CourseTag[] = [
+  "ocean",
+  "offshore",
+  "sprint",
+  "historic",
+];
+
+// Route and wind of a course (see server/src/course_metrics.rs)
+export type CourseMetrics = {
+  routeNm: number;
+  avgTws: number; // knots, along the route over the course window
+  maxTws: number;
+  southernFraction: number; // share of the route south of 40°S
+  difficulty: number; // 0 to 10
+};
+
+// Currents in amps, capacity in amp hours of course time (see server/src/energy.rs)
+/** Statistics of the path of a result (mirrors server race_stats::RaceStats) */
+export type RaceStats = {
+  distanceNm: number;
+  /** Knots of course time */
+  avgSpeed: number;
+  maxSpeed: number;
+  tacks: number;
+  gybes: number;
+};
+
+export type Energy = {
+  capacity: number;
+  autopilot: number;
+  instruments: number;
+  solar: number; // with the sun overhead
+  hydro: number; // per knot of boat speed
+  hydroDrag: number; // share of the boat speed lost to the hydro generator
+};
+
+export type EnergySystem = "autopilot" | "instruments" | "hydro";
+
+export type EnergyStatus = {
+  level: number; // battery charge, 0-1
+  autopilot: boolean;
+  instruments: boolean;
+  hydro: boolean;
 };
 
 export type Pixel = { x: number; y: number };
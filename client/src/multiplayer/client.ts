@@ -1,4 +1,4 @@
-import { LngLat } from "../models";
+import { EnergySystem, LngLat } from "../models";
 import { SignalingClient } from "./signaling";
 import { PeerState, MultiplayerCallbacks } from "./types";
 
@@ -9,6 +9,10 @@ import { PeerState, MultiplayerCallbacks } from "./types";
 export class MultiplayerClient {
   private signaling: SignalingClient;
   private peerStates: Map<string, PeerState> = new Map();
+  private playerId: string | null = null;
+  // Replayed to the server once a dropped race is resumed
+  private lastHelm: { heading: number; twa: number | null } | null = null;
+  private unsentGates: { gateIndex: number; courseTime: number }[] = [];
 
   constructor(callbacks: MultiplayerCallbacks) {
     this.signaling = new SignalingClient({
@@ -20,7 +24,10 @@ export class MultiplayerClient {
         isCreator,
         courseKey,
         windSources,
+        courseStartTime,
+        polar,
       ) => {
+        this.playerId = playerId;
         // Initialize peer states for existing players
         players.forEach((p) => {
           if (p.id !== playerId) {
@@ -40,6 +47,8 @@ export class MultiplayerClient {
           isCreator,
           courseKey,
           windSources,
+          courseStartTime,
+          polar,
         );
       },
       onPlayerJoined: (playerId, playerName) => {
@@ -56,7 +65,38 @@ export class MultiplayerClient {
         this.peerStates.delete(playerId);
         callbacks.onPlayerLeft(playerId);
       },
+      onRaceCreated: (
+        raceId,
+        playerId,
+        windSources,
+        courseStartTime,
+        polar,
+      ) => {
+        this.playerId = playerId;
+        callbacks.onRaceCreated(
+          raceId,
+          playerId,
+          windSources,
+          courseStartTime,
+          polar,
+        );
+      },
+      onRaceResumed: (raceTime) => {
+        // The helm and gates crossed while offline may not have made it
+        if (this.lastHelm) {
+          this.signaling.sendHelm(this.lastHelm.heading, this.lastHelm.twa);
+        }
+        const gates = this.unsentGates;
+        this.unsentGates = [];
+        gates.forEach((g) => this.sendGateCrossed(g.gateIndex, g.courseTime));
+        callbacks.onRaceResumed(raceTime);
+      },
       onPeerPositionUpdate: (peerId, position, heading, _name, raceTime) => {
+        // Our own boat, as simulated by the server
+        if (peerId === this.playerId) {
+          callbacks.onOwnPositionUpdate(position, heading);
+          return;
+        }
         // Update peer state
         const peerState = this.peerStates.get(peerId);
         if (peerState) {
@@ -80,23 +120,83 @@ export class MultiplayerClient {
     await this.signaling.connect();
   }
 
-  createRace(courseKey: string, playerName: string, persistentId: string) {
-    this.signaling.createRace(courseKey, playerName, persistentId);
+  createRace(
+    courseKey: string,
+    playerName: string,
+    persistentId: string,
+    solo: boolean,
+    randomWeather: boolean,
+    polar: string | null,
+  ) {
+    this.signaling.createRace(
+      courseKey,
+      playerName,
+      persistentId,
+      solo,
+      randomWeather,
+      polar,
+    );
   }
 
-  joinRace(raceId: string, playerName: string, persistentId: string) {
-    this.signaling.joinRace(raceId, playerName, persistentId);
+  joinRace(
+    raceId: string,
+    playerName: string,
+    persistentId: string,
+    polar: string | null,
+  ) {
+    this.signaling.joinRace(raceId, playerName, persistentId, polar);
+  }
+
+  /**
+   * Race today's daily challenge (course and weather window picked by the server).
+   */
+  startDaily(playerName: string, persistentId: string) {
+    this.signaling.startDaily(playerName, persistentId);
+  }
+
+  quickMatch(
+    courseKey: string,
+    playerName: string,
+    persistentId: string,
+    polar: string | null,
+  ) {
+    this.signaling.quickMatch(courseKey, playerName, persistentId, polar);
   }
 
   leaveRace() {
     this.signaling.leaveRace();
     this.peerStates.clear();
+    this.lastHelm = null;
+    this.unsentGates = [];
   }
 
   startRace() {
     this.signaling.startRace();
   }
 
+  /**
+   * Give up the current race (recorded as retired rather than vanishing).
+   */
+  retireFromRace() {
+    this.signaling.retireFromRace();
+  }
+
+  /**
+   * Move to a rematch of the race once our own race is over: a fresh lobby on
+   * the same course, joined by the other players as they accept.
+   */
+  proposeRematch() {
+    this.peerStates.clear();
+    this.signaling.proposeRematch();
+  }
+
+  /**
+   * Ask the server to stream the top Hall of Fame runs as ghost boats.
+   */
+  addServerGhosts(count: number) {
+    this.signaling.addGhosts(count);
+  }
+
   disconnect() {
     this.peerStates.clear();
     this.signaling.disconnect();
@@ -113,10 +213,42 @@ export class MultiplayerClient {
     this.signaling.sendPositionUpdate(position.lng, position.lat, heading);
   }
 
+  /**
+   * Send the helm to the server-side simulation: a heading, or the signed
+   * TWA to hold when locked.
+   */
+  sendHelm(heading: number, twa: number | null) {
+    this.lastHelm = { heading, twa };
+    this.signaling.sendHelm(heading, twa);
+  }
+
+  /**
+   * Hoist another sail of the boat class inventory.
+   */
+  sendChangeSail(sail: string) {
+    this.signaling.sendChangeSail(sail);
+  }
+
+  /**
+   * Switch a system of the boat on or off, on courses managing energy.
+   */
+  sendToggleSystem(system: EnergySystem, on: boolean) {
+    this.signaling.sendToggleSystem(system, on);
+  }
+
+  /**
+   * Ask for the best way to sail toward the next mark.
+   */
+  askCoach() {
+    this.signaling.askCoach();
+  }
+
   /**
    * Notify server that a gate was crossed.
    */
   sendGateCrossed(gateIndex: number, courseTime: number) {
-    this.signaling.sendGateCrossed(gateIndex, courseTime);
+    if (!this.signaling.sendGateCrossed(gateIndex, courseTime)) {
+      this.unsentGates.push({ gateIndex, courseTime });
+    }
   }
 }
@@ -0,0 +1,40 @@
+import { describe, it, expect } from "vitest";
+import { decode, encode } from "./msgpack";
+
+describe("msgpack", () => {
+  it("round-trips messages", () => {
+    const message = {
+      type: "PositionUpdate",
+      playerId: "A1B2",
+      lng: -4.25,
+      lat: 47.5,
+      heading: 270,
+      raceTime: 1_700_000_000_000,
+      offset: -40_000,
+      finished: false,
+      polar: null,
+      lapTimes: [12, 300, 70_000],
+      name: "Étienne ".repeat(10),
+    };
+    expect(decode(encode(message))).toEqual(message);
+  });
+
+  it("encodes small values in one byte", () => {
+    expect(Array.from(encode(5))).toEqual([0x05]);
+    expect(Array.from(encode(-3))).toEqual([0xfd]);
+    expect(Array.from(encode({}))).toEqual([0x80]);
+    expect(Array.from(encode(null))).toEqual([0xc0]);
+  });
+
+  it("leaves undefined fields out", () => {
+    expect(decode(encode({ a: 1, b: undefined }))).toEqual({ a: 1 });
+  });
+
+  it("decodes the float32 of the server", () => {
+    expect(decode(Uint8Array.from([0xca, 0x3f, 0xc0, 0x00, 0x00]))).toBe(1.5);
+  });
+
+  it("rejects truncated data", () => {
+    expect(() => decode(Uint8Array.from([0xcd, 0x01]))).toThrow();
+  });
+});
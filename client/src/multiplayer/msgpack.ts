@@ -0,0 +1,209 @@
+/**
+ * MessagePack encoding of the multiplayer messages, the binary encoding
+ * negotiated with the server. Covers what JSON can hold: maps with string
+ * keys, arrays, strings, numbers, booleans and null.
+ */
+
+const textEncoder = new TextEncoder();
+const textDecoder = new TextDecoder();
+
+/** Type tags of the longer forms of a header, by size of the length */
+type HeaderTags = { u8?: number; u16: number; u32: number };
+
+class Writer {
+  bytes: number[] = [];
+  private scratch = new DataView(new ArrayBuffer(8));
+
+  byte(b: number) {
+    this.bytes.push(b);
+  }
+
+  private flush(length: number) {
+    for (let i = 0; i < length; i++) {
+      this.bytes.push(this.scratch.getUint8(i));
+    }
+  }
+
+  uint(tag: number, n: number, size: 1 | 2 | 4 | 8) {
+    this.byte(tag);
+    if (size === 1) this.scratch.setUint8(0, n);
+    else if (size === 2) this.scratch.setUint16(0, n);
+    else if (size === 4) this.scratch.setUint32(0, n);
+    else this.scratch.setBigUint64(0, BigInt(n));
+    this.flush(size);
+  }
+
+  int(tag: number, n: number, size: 1 | 2 | 4 | 8) {
+    this.byte(tag);
+    if (size === 1) this.scratch.setInt8(0, n);
+    else if (size === 2) this.scratch.setInt16(0, n);
+    else if (size === 4) this.scratch.setInt32(0, n);
+    else this.scratch.setBigInt64(0, BigInt(n));
+    this.flush(size);
+  }
+
+  float(n: number) {
+    this.byte(0xcb);
+    this.scratch.setFloat64(0, n);
+    this.flush(8);
+  }
+
+  /** Header of a string, array or map of `length` items */
+  header(length: number, fix: number, fixMax: number, tags: HeaderTags) {
+    if (length < fixMax) this.byte(fix | length);
+    else if (tags.u8 !== undefined && length <= 0xff) this.uint(tags.u8, length, 1);
+    else if (length <= 0xffff) this.uint(tags.u16, length, 2);
+    else this.uint(tags.u32, length, 4);
+  }
+}
+
+function writeNumber(w: Writer, n: number) {
+  if (!Number.isSafeInteger(n)) {
+    w.float(n);
+  } else if (n >= 0) {
+    if (n < 0x80) w.byte(n);
+    else if (n <= 0xff) w.uint(0xcc, n, 1);
+    else if (n <= 0xffff) w.uint(0xcd, n, 2);
+    else if (n <= 0xffffffff) w.uint(0xce, n, 4);
+    else w.uint(0xcf, n, 8);
+  } else {
+    if (n >= -32) w.byte(n & 0xff);
+    else if (n >= -0x80) w.int(0xd0, n, 1);
+    else if (n >= -0x8000) w.int(0xd1, n, 2);
+    else if (n >= -0x80000000) w.int(0xd2, n, 4);
+    else w.int(0xd3, n, 8);
+  }
+}
+
+function write(w: Writer, value: unknown) {
+  if (value === null || value === undefined) {
+    w.byte(0xc0);
+  } else if (typeof value === "boolean") {
+    w.byte(value ? 0xc3 : 0xc2);
+  } else if (typeof value === "number") {
+    writeNumber(w, value);
+  } else if (typeof value === "string") {
+    const utf8 = textEncoder.encode(value);
+    w.header(utf8.length, 0xa0, 32, { u8: 0xd9, u16: 0xda, u32: 0xdb });
+    utf8.forEach((b) => w.byte(b));
+  } else if (Array.isArray(value)) {
+    w.header(value.length, 0x90, 16, { u16: 0xdc, u32: 0xdd });
+    value.forEach((item) => write(w, item));
+  } else if (typeof value === "object") {
+    // Like JSON.stringify, undefined fields are left out
+    const entries = Object.entries(value).filter(([, v]) => v !== undefined);
+    w.header(entries.length, 0x80, 16, { u16: 0xde, u32: 0xdf });
+    for (const [key, item] of entries) {
+      write(w, key);
+      write(w, item);
+    }
+  } else {
+    throw new Error(`Cannot encode ${typeof value} as MessagePack`);
+  }
+}
+
+export function encode(value: unknown): Uint8Array {
+  const w = new Writer();
+  write(w, value);
+  return Uint8Array.from(w.bytes);
+}
+
+class Reader {
+  private bytes: Uint8Array;
+  private view: DataView;
+  private offset = 0;
+
+  constructor(bytes: Uint8Array) {
+    this.bytes = bytes;
+    this.view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  }
+
+  private advance(size: number): number {
+    const at = this.offset;
+    if (at + size > this.bytes.length) {
+      throw new Error("Truncated MessagePack data");
+    }
+    this.offset += size;
+    return at;
+  }
+
+  private u8 = () => this.view.getUint8(this.advance(1));
+  private u16 = () => this.view.getUint16(this.advance(2));
+  private u32 = () => this.view.getUint32(this.advance(4));
+
+  private str(length: number): string {
+    const at = this.advance(length);
+    return textDecoder.decode(this.bytes.subarray(at, at + length));
+  }
+
+  private array(length: number): unknown[] {
+    return Array.from({ length }, () => this.value());
+  }
+
+  private map(length: number): Record<string, unknown> {
+    const map: Record<string, unknown> = {};
+    for (let i = 0; i < length; i++) {
+      const key = this.value();
+      map[String(key)] = this.value();
+    }
+    return map;
+  }
+
+  value(): unknown {
+    const tag = this.u8();
+    if (tag < 0x80) return tag;
+    if (tag < 0x90) return this.map(tag & 0x0f);
+    if (tag < 0xa0) return this.array(tag & 0x0f);
+    if (tag < 0xc0) return this.str(tag & 0x1f);
+    if (tag >= 0xe0) return tag - 0x100;
+
+    switch (tag) {
+      case 0xc0:
+        return null;
+      case 0xc2:
+        return false;
+      case 0xc3:
+        return true;
+      case 0xca:
+        return this.view.getFloat32(this.advance(4));
+      case 0xcb:
+        return this.view.getFloat64(this.advance(8));
+      case 0xcc:
+        return this.u8();
+      case 0xcd:
+        return this.u16();
+      case 0xce:
+        return this.u32();
+      case 0xcf:
+        return Number(this.view.getBigUint64(this.advance(8)));
+      case 0xd0:
+        return this.view.getInt8(this.advance(1));
+      case 0xd1:
+        return this.view.getInt16(this.advance(2));
+      case 0xd2:
+        return this.view.getInt32(this.advance(4));
+      case 0xd3:
+        return Number(this.view.getBigInt64(this.advance(8)));
+      case 0xd9:
+        return this.str(this.u8());
+      case 0xda:
+        return this.str(this.u16());
+      case 0xdb:
+        return this.str(this.u32());
+      case 0xdc:
+        return this.array(this.u16());
+      case 0xdd:
+        return this.array(this.u32());
+      case 0xde:
+        return this.map(this.u16());
+      case 0xdf:
+        return this.map(this.u32());
+      default:
+        throw new Error(`Unsupported MessagePack type 0x${tag.toString(16)}`);
+    }
+  }
+}
+
+export function decode(bytes: Uint8Array): unknown {
+  return new Reader(bytes).value();
+}
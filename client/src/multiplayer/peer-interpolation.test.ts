@@ -0,0 +1,49 @@
+import { describe, it, expect } from "vitest";
+import { PeerBuffer } from "./peer-interpolation";
+
+function sample(raceTime: number, lng: number, heading = 90) {
+  return { raceTime, position: { lng, lat: 0 }, heading };
+}
+
+describe("PeerBuffer", () => {
+  it("returns null without updates", () => {
+    expect(new PeerBuffer().at(0)).toBeNull();
+  });
+
+  it("interpolates between updates", () => {
+    const buffer = new PeerBuffer();
+    buffer.push(sample(1000, 0, 350));
+    buffer.push(sample(2000, 1, 10));
+
+    const mid = buffer.at(1500)!;
+    expect(mid.position.lng).toBeCloseTo(0.5);
+    // Through north, not the long way round
+    expect(mid.heading).toBeCloseTo(0);
+    expect(buffer.at(500)!.position.lng).toBe(0);
+  });
+
+  it("extrapolates by one update interval at most", () => {
+    const buffer = new PeerBuffer();
+    buffer.push(sample(1000, 0));
+    buffer.push(sample(2000, 1));
+
+    expect(buffer.at(2500)!.position.lng).toBeCloseTo(1.5);
+    expect(buffer.at(5000)!.position.lng).toBeCloseTo(2);
+  });
+
+  it("crosses the antimeridian the short way", () => {
+    const buffer = new PeerBuffer();
+    buffer.push(sample(0, 179.5));
+    buffer.push(sample(1000, -179.5));
+
+    expect(Math.abs(buffer.at(500)!.position.lng)).toBeCloseTo(180);
+  });
+
+  it("drops updates arriving out of order", () => {
+    const buffer = new PeerBuffer();
+    buffer.push(sample(2000, 1));
+    buffer.push(sample(1000, 0));
+
+    expect(buffer.samples).toHaveLength(1);
+  });
+});
@@ -0,0 +1,72 @@
+import { LngLat } from "../models";
+import { wrapLng } from "../utils";
+
+/** Samples kept per peer, a few seconds of position updates */
+const MAX_SAMPLES = 8;
+
+export type PeerSample = {
+  /** Course time of the update */
+  raceTime: number;
+  position: LngLat;
+  heading: number;
+};
+
+/** Shortest signed turn from one heading to another */
+function headingDelta(from: number, to: number): number {
+  return ((to - from + 540) % 360) - 180;
+}
+
+function between(a: PeerSample, b: PeerSample, t: number): PeerSample {
+  const span = b.raceTime - a.raceTime;
+  const f = span > 0 ? (t - a.raceTime) / span : 1;
+  return {
+    raceTime: t,
+    position: {
+      // The short way across the antimeridian
+      lng: wrapLng(a.position.lng + f * wrapLng(b.position.lng - a.position.lng)),
+      lat: a.position.lat + f * (b.position.lat - a.position.lat),
+    },
+    heading: (a.heading + f * headingDelta(a.heading, b.heading) + 360) % 360,
+  };
+}
+
+/**
+ * Position updates of a remote boat, to move it smoothly between them.
+ * Positions are interpolated between the updates around a course time, and
+ * extrapolated past the last one by at most the interval between the last
+ * two updates, so a late update doesn't stop the boat and a lost connection
+ * doesn't send it sailing away.
+ */
+export class PeerBuffer {
+  samples: PeerSample[] = [];
+
+  push(sample: PeerSample) {
+    const last = this.samples[this.samples.length - 1];
+    // Updates arriving out of order are dropped
+    if (last && sample.raceTime <= last.raceTime) return;
+    this.samples.push(sample);
+    if (this.samples.length > MAX_SAMPLES) {
+      this.samples.shift();
+    }
+  }
+
+  /** Position and heading of the boat at a course time */
+  at(raceTime: number): PeerSample | null {
+    const n = this.samples.length;
+    if (n === 0) return null;
+    const first = this.samples[0];
+    const last = this.samples[n - 1];
+    if (n === 1 || raceTime <= first.raceTime) return first;
+
+    if (raceTime >= last.raceTime) {
+      const previous = this.samples[n - 2];
+      const interval = last.raceTime - previous.raceTime;
+      const t = Math.min(raceTime, last.raceTime + interval);
+      // The heading holds while extrapolating
+      return { ...between(previous, last, t), heading: last.heading };
+    }
+
+    const i = this.samples.findIndex((s) => s.raceTime > raceTime);
+    return between(this.samples[i - 1], this.samples[i], raceTime);
+  }
+}
@@ -1,17 +1,33 @@
-import { ClientMessage, ServerMessage, MultiplayerCallbacks } from "./types";
+import { EnergySystem } from "../models";
+import {
+  ClientMessage,
+  Encoding,
+  ServerMessage,
+  MultiplayerCallbacks,
+} from "./types";
+import * as msgpack from "./msgpack";
 
 const SERVER_MESSAGE_TYPES = new Set([
   "Error",
+  "Welcome",
   "RaceCreated",
   "RaceJoined",
   "PlayerJoined",
   "PlayerLeft",
+  "RaceResumed",
+  "MatchQueued",
   "RaceCountdown",
   "RaceStarted",
   "PositionUpdate",
   "RaceEnded",
+  "RematchProposed",
+  "PersonalBest",
   "Leaderboard",
   "SyncRaceTime",
+  "Ocs",
+  "WindShadow",
+  "Energy",
+  "CoachHint",
 ]);
 
 function isServerMessage(value: unknown): value is ServerMessage {
@@ -37,13 +53,41 @@ function getServerUrl(): string {
 
 const serverUrl = getServerUrl();
 
+/**
+ * Version of the message protocol spoken by the client, bumped along with
+ * the server one when a message changes in a way older peers can't read
+ */
+const PROTOCOL_VERSION = 1;
+
+/**
+ * Waits before each attempt to reconnect, within the 30s the server keeps
+ * the boat of a dropped racer
+ */
+const RECONNECT_DELAYS_MS = [500, 1000, 2000, 4000, 8000, 8000];
+
+/** What the server needs to hand a race over to a new connection */
+type ResumeTarget = {
+  raceId: string;
+  playerId: string;
+  persistentId: string;
+};
+
 /**
  * WebSocket client for multiplayer server communication.
- * Handles lobby management and position updates.
+ * Handles lobby management and position updates, and reconnects to resume
+ * the race when the connection drops.
  */
 export class SignalingClient {
   private ws: WebSocket | null = null;
   private callbacks: MultiplayerCallbacks;
+  private persistentId: string | null = null;
+  private resumeTarget: ResumeTarget | null = null;
+  private resuming = false;
+  private closing = false;
+  /** Protocol version agreed with the server, the lower of both */
+  protocolVersion = PROTOCOL_VERSION;
+  /** JSON until the server agrees to MessagePack */
+  private encoding: Encoding = "json";
 
   constructor(callbacks: MultiplayerCallbacks) {
     this.callbacks = callbacks;
@@ -52,23 +96,41 @@ export class SignalingClient {
   connect(): Promise<void> {
     return new Promise((resolve, reject) => {
       const wsUrl = serverUrl.replace(/^http/, "ws") + "/multiplayer/race";
-      this.ws = new WebSocket(wsUrl);
+      const ws = new WebSocket(wsUrl);
+      ws.binaryType = "arraybuffer";
+      this.ws = ws;
+      this.encoding = "json";
+      let opened = false;
 
-      this.ws.onopen = () => {
+      ws.onopen = () => {
+        opened = true;
+        this.send({
+          type: "Hello",
+          protocolVersion: PROTOCOL_VERSION,
+          encoding: "msgpack",
+        });
         resolve();
       };
 
-      this.ws.onerror = () => {
+      ws.onerror = () => {
         reject(new Error("WebSocket connection failed"));
       };
 
-      this.ws.onclose = () => {
+      ws.onclose = () => {
         this.callbacks.onDisconnect();
+        if (opened && !this.closing && this.resumeTarget) {
+          this.callbacks.onReconnecting();
+          this.reconnect();
+        }
       };
 
-      this.ws.onmessage = (event) => {
+      ws.onmessage = (event) => {
         try {
-          const parsed: unknown = JSON.parse(event.data);
+          // Text frames are JSON, binary ones MessagePack
+          const parsed: unknown =
+            typeof event.data === "string"
+              ? JSON.parse(event.data)
+              : msgpack.decode(new Uint8Array(event.data));
           if (!isServerMessage(parsed)) {
             console.error("Invalid server message:", parsed);
             return;
@@ -81,21 +143,61 @@ export class SignalingClient {
     });
   }
 
+  /**
+   * Open a new connection and ask for the race back, until the server
+   * answers or the attempts run out.
+   */
+  private async reconnect() {
+    for (const delay of RECONNECT_DELAYS_MS) {
+      await new Promise((resolve) => setTimeout(resolve, delay));
+      if (this.closing || !this.resumeTarget) return;
+      try {
+        await this.connect();
+      } catch {
+        continue;
+      }
+      this.resuming = true;
+      this.send({ type: "Resume", ...this.resumeTarget });
+      return;
+    }
+    this.resumeTarget = null;
+    this.callbacks.onResumeFailed();
+  }
+
   private handleMessage(message: ServerMessage) {
     switch (message.type) {
       case "Error":
         this.callbacks.onError(message.message);
+        if (this.resuming) {
+          this.resuming = false;
+          this.resumeTarget = null;
+          this.callbacks.onResumeFailed();
+        }
+        break;
+
+      case "Welcome":
+        this.protocolVersion = message.protocolVersion;
+        this.encoding = message.encoding;
+        break;
+
+      case "RaceResumed":
+        this.resuming = false;
+        this.callbacks.onRaceResumed(message.raceTime);
         break;
 
       case "RaceCreated":
+        this.setResumeTarget(message.raceId, message.playerId);
         this.callbacks.onRaceCreated(
           message.raceId,
           message.playerId,
           message.windRasterSources,
+          message.courseStartTime,
+          message.polar,
         );
         break;
 
       case "RaceJoined":
+        this.setResumeTarget(message.raceId, message.playerId);
         this.callbacks.onRaceJoined(
           message.raceId,
           message.playerId,
@@ -103,6 +205,8 @@ export class SignalingClient {
           message.isCreator,
           message.courseKey,
           message.windRasterSources,
+          message.courseStartTime,
+          message.polar,
         );
         break;
 
@@ -114,14 +218,36 @@ export class SignalingClient {
         this.callbacks.onPlayerLeft(message.playerId);
         break;
 
+      case "MatchQueued":
+        this.callbacks.onMatchQueued({
+          courseKey: message.courseKey,
+          queuedPlayers: message.queuedPlayers,
+          minPlayers: message.minPlayers,
+          matchDeadline: message.matchDeadline,
+        });
+        break;
+
       case "RaceCountdown":
         this.callbacks.onCountdown(message.seconds);
         break;
 
       case "RaceEnded":
+        this.resumeTarget = null;
         this.callbacks.onRaceEnded(message.reason);
         break;
 
+      case "RematchProposed":
+        this.callbacks.onRematchProposed(message.raceId, message.proposerName);
+        break;
+
+      case "PersonalBest":
+        this.callbacks.onPersonalBest({
+          courseKey: message.courseKey,
+          finishTime: message.finishTime,
+          previousBest: message.previousBest,
+        });
+        break;
+
       case "PositionUpdate":
         this.callbacks.onPeerPositionUpdate(
           message.playerId,
@@ -139,37 +265,112 @@ export class SignalingClient {
       case "SyncRaceTime":
         this.callbacks.onSyncRaceTime(message.raceTime);
         break;
+
+      case "Ocs":
+        this.callbacks.onOcs(message.playerId, message.over);
+        break;
+
+      case "WindShadow":
+        this.callbacks.onWindShadow(message.factor);
+        break;
+
+      case "Energy":
+        this.callbacks.onEnergy({
+          level: message.level,
+          autopilot: message.autopilot,
+          instruments: message.instruments,
+          hydro: message.hydro,
+        });
+        break;
+
+      case "CoachHint":
+        this.callbacks.onCoachHint(message.hint);
+        break;
     }
   }
 
+  private setResumeTarget(raceId: string, playerId: string) {
+    this.resumeTarget = this.persistentId
+      ? { raceId, playerId, persistentId: this.persistentId }
+      : null;
+  }
+
   send(message: ClientMessage): boolean {
     if (this.ws && this.ws.readyState === WebSocket.OPEN) {
-      this.ws.send(JSON.stringify(message));
+      this.ws.send(
+        this.encoding === "msgpack"
+          ? msgpack.encode(message)
+          : JSON.stringify(message),
+      );
       return true;
     }
     console.warn("WebSocket send dropped (not connected):", message.type);
     return false;
   }
 
-  createRace(courseKey: string, playerName: string, persistentId: string) {
+  createRace(
+    courseKey: string,
+    playerName: string,
+    persistentId: string,
+    solo: boolean,
+    randomWeather: boolean,
+    polar: string | null,
+  ) {
+    this.persistentId = persistentId;
     this.send({
       type: "CreateRace",
       courseKey: courseKey,
       playerName: playerName,
       persistentId: persistentId,
+      solo: solo,
+      randomWeather: randomWeather,
+      polar: polar,
     });
   }
 
-  joinRace(raceId: string, playerName: string, persistentId: string) {
+  joinRace(
+    raceId: string,
+    playerName: string,
+    persistentId: string,
+    polar: string | null,
+  ) {
+    this.persistentId = persistentId;
     this.send({
       type: "JoinRace",
       raceId: raceId,
       playerName: playerName,
       persistentId: persistentId,
+      polar: polar,
+    });
+  }
+
+  startDaily(playerName: string, persistentId: string) {
+    this.persistentId = persistentId;
+    this.send({
+      type: "StartDaily",
+      playerName: playerName,
+      persistentId: persistentId,
+    });
+  }
+
+  quickMatch(
+    courseKey: string,
+    playerName: string,
+    persistentId: string,
+    polar: string | null,
+  ) {
+    this.persistentId = persistentId;
+    this.send({
+      type: "QuickMatch",
+      courseKey: courseKey,
+      playerName: playerName,
+      persistentId: persistentId,
+      polar: polar,
     });
   }
 
   leaveRace() {
+    this.resumeTarget = null;
     this.send({ type: "LeaveRace" });
   }
 
@@ -177,6 +378,18 @@ export class SignalingClient {
     this.send({ type: "StartRace" });
   }
 
+  retireFromRace() {
+    this.send({ type: "RetireFromRace" });
+  }
+
+  proposeRematch() {
+    this.send({ type: "ProposeRematch" });
+  }
+
+  addGhosts(count: number) {
+    this.send({ type: "AddGhosts", count: count });
+  }
+
   sendPositionUpdate(lng: number, lat: number, heading: number) {
     this.send({
       type: "PositionUpdate",
@@ -186,8 +399,24 @@ export class SignalingClient {
     });
   }
 
-  sendGateCrossed(gateIndex: number, courseTime: number) {
-    this.send({
+  sendHelm(heading: number, twa: number | null) {
+    this.send({ type: "Helm", heading, twa });
+  }
+
+  sendChangeSail(sail: string) {
+    this.send({ type: "ChangeSail", sail });
+  }
+
+  sendToggleSystem(system: EnergySystem, on: boolean) {
+    this.send({ type: "ToggleSystem", system, on });
+  }
+
+  askCoach() {
+    this.send({ type: "AskCoach" });
+  }
+
+  sendGateCrossed(gateIndex: number, courseTime: number): boolean {
+    return this.send({
       type: "GateCrossed",
       gateIndex,
       courseTime,
@@ -195,6 +424,7 @@ export class SignalingClient {
   }
 
   disconnect() {
+    this.closing = true;
     if (this.ws) {
       this.ws.close();
       this.ws = null;
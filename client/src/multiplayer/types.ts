@@ -1,32 +1,98 @@
-import { LngLat, WindRasterSource } from "../models";
+import {
+  EnergyStatus,
+  EnergySystem,
+  LngLat,
+  WindRasterSource,
+} from "../models";
 
 // ============================================================================
 // Signaling Messages (match server/src/multiplayer.rs)
 // ============================================================================
 
+/** Encoding of the server messages, agreed in the handshake */
+export type Encoding = "json" | "msgpack";
+
 export type ClientMessage =
-  | { type: "CreateRace"; courseKey: string; playerName: string; persistentId: string }
-  | { type: "JoinRace"; raceId: string; playerName: string; persistentId: string }
+  | { type: "Hello"; protocolVersion: number; encoding: Encoding }
+  | {
+      type: "CreateRace";
+      courseKey: string;
+      playerName: string;
+      persistentId: string;
+      solo: boolean;
+      randomWeather: boolean;
+      polar: string | null;
+    }
+  | {
+      type: "JoinRace";
+      raceId: string;
+      playerName: string;
+      persistentId: string;
+      polar: string | null;
+    }
+  | { type: "StartDaily"; playerName: string; persistentId: string }
+  | {
+      type: "QuickMatch";
+      courseKey: string;
+      playerName: string;
+      persistentId: string;
+      polar: string | null;
+    }
   | { type: "LeaveRace" }
   | { type: "StartRace" }
+  | { type: "RetireFromRace" }
+  | { type: "ProposeRematch" }
+  | { type: "AddGhosts"; count: number }
   | { type: "PositionUpdate"; lng: number; lat: number; heading: number }
-  | { type: "GateCrossed"; gateIndex: number; courseTime: number };
+  | { type: "Helm"; heading: number; twa: number | null }
+  | { type: "ChangeSail"; sail: string }
+  | { type: "ToggleSystem"; system: EnergySystem; on: boolean }
+  | { type: "AskCoach" }
+  | { type: "GateCrossed"; gateIndex: number; courseTime: number }
+  | { type: "Resume"; raceId: string; playerId: string; persistentId: string };
+
+// Best way to sail toward the next mark (see server/src/routing.rs)
+export type CoachHint = {
+  twa: number; // signed, heading = wind direction - TWA
+  heading: number;
+  speed: number; // knots
+  vmg: number; // knots toward the mark
+  bearing: number; // of the mark
+  tws: number;
+  twd: number;
+};
+
+// Finish beating the player's best time on the course with this boat class
+export type PersonalBest = {
+  courseKey: string;
+  finishTime: number; // elapsed ms
+  previousBest: number | null; // null on the first finish
+};
+
+export type PlayerStatus = "racing" | "finished" | "retired" | "dnf";
 
 export type LeaderboardEntry = {
   playerId: string;
   playerName: string;
   nextGateIndex: number;
   distanceToNextGate: number;
+  distanceToFinish: number;
   finishTime: number | null;
+  status: PlayerStatus;
+  polar: string; // boat class, entries are grouped by class
+  lapTimes: number[]; // course times each lap was completed, on lapped courses
 };
 
 export type ServerMessage =
   | { type: "Error"; message: string }
+  | { type: "Welcome"; protocolVersion: number; encoding: Encoding }
   | {
       type: "RaceCreated";
       raceId: string;
       playerId: string;
       windRasterSources: WindRasterSource[];
+      courseStartTime: number;
+      polar: string;
     }
   | {
       type: "RaceJoined";
@@ -36,9 +102,24 @@ export type ServerMessage =
       players: PlayerInfo[];
       windRasterSources: WindRasterSource[];
       isCreator: boolean;
+      courseStartTime: number;
+      polar: string;
     }
   | { type: "PlayerJoined"; playerId: string; playerName: string }
   | { type: "PlayerLeft"; playerId: string }
+  | {
+      type: "RaceResumed";
+      raceId: string;
+      playerId: string;
+      raceTime: number | null;
+    }
+  | {
+      type: "MatchQueued";
+      courseKey: string;
+      queuedPlayers: number;
+      minPlayers: number;
+      matchDeadline: number;
+    }
   | { type: "RaceCountdown"; seconds: number }
   | { type: "RaceStarted"; startTime: number; courseKey: string }
   | {
@@ -50,8 +131,14 @@ export type ServerMessage =
       raceTime: number;
     }
   | { type: "RaceEnded"; reason: string }
+  | { type: "RematchProposed"; raceId: string; proposerName: string }
+  | ({ type: "PersonalBest" } & PersonalBest)
   | { type: "Leaderboard"; entries: LeaderboardEntry[] }
-  | { type: "SyncRaceTime"; raceTime: number };
+  | { type: "SyncRaceTime"; raceTime: number }
+  | { type: "Ocs"; playerId: string; over: boolean }
+  | { type: "WindShadow"; factor: number }
+  | ({ type: "Energy" } & EnergyStatus)
+  | { type: "CoachHint"; hint: CoachHint };
 
 // ============================================================================
 // State Types
@@ -60,6 +147,15 @@ export type ServerMessage =
 export type PlayerInfo = {
   id: string;
   name: string;
+  /** Small avatar of the player's profile, null for ghosts */
+  avatarUrl: string | null;
+};
+
+export type MatchQueueInfo = {
+  courseKey: string;
+  queuedPlayers: number;
+  minPlayers: number;
+  matchDeadline: number; // ms timestamp when a race is formed anyway
 };
 
 export type PeerState = {
@@ -85,6 +181,8 @@ export type MultiplayerCallbacks = {
     raceId: string,
     playerId: string,
     windRasterSources: WindRasterSource[],
+    courseStartTime: number,
+    polar: string,
   ) => void;
   onRaceJoined: (
     raceId: string,
@@ -93,9 +191,12 @@ export type MultiplayerCallbacks = {
     isCreator: boolean,
     courseKey: string,
     windRasterSources: WindRasterSource[],
+    courseStartTime: number,
+    polar: string,
   ) => void;
   onPlayerJoined: (playerId: string, playerName: string) => void;
   onPlayerLeft: (playerId: string) => void;
+  onMatchQueued: (queue: MatchQueueInfo) => void;
   onPeerPositionUpdate: (
     peerId: string,
     position: LngLat,
@@ -103,10 +204,22 @@ export type MultiplayerCallbacks = {
     name: string,
     raceTime: number,
   ) => void;
+  onOwnPositionUpdate: (position: LngLat, heading: number) => void;
   onCountdown: (seconds: number) => void;
   onRaceEnded: (reason: string) => void;
+  onRematchProposed: (raceId: string, proposerName: string) => void;
+  onPersonalBest: (personalBest: PersonalBest) => void;
   onLeaderboardUpdate: (entries: LeaderboardEntry[]) => void;
   onSyncRaceTime: (raceTime: number) => void;
+  onOcs: (playerId: string, over: boolean) => void;
+  onWindShadow: (factor: number) => void;
+  onEnergy: (energy: EnergyStatus) => void;
+  onCoachHint: (hint: CoachHint) => void;
   onError: (message: string) => void;
   onDisconnect: () => void;
+  /** The connection dropped mid-race, reconnecting to resume it */
+  onReconnecting: () => void;
+  onRaceResumed: (raceTime: number | null) => void;
+  /** The race could not be resumed in time */
+  onResumeFailed: () => void;
 };
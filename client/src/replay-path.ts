@@ -1,22 +1,42 @@
 /**
  * Binary path format (matches server encoding):
- * [4 bytes] Version (uint32 LE)
+ * [4 bytes] Version (uint32 LE), 1 or 2
  * [4 bytes] Point count (uint32 LE)
+ * [4 bytes] Section flags (uint32 LE), version 2 only:
+ *   1 = helm inputs, 2 = wind reports
  * [N × 20 bytes] Points:
  *   - raceTime: int64 LE (8 bytes)
  *   - lng: float32 LE (4 bytes)
  *   - lat: float32 LE (4 bytes)
  *   - heading: float32 LE (4 bytes)
+ * [N × 5 bytes] Helm inputs, with flag 1:
+ *   - kind: uint8 (0 heading, 1 TWA)
+ *   - angle: float32 LE (4 bytes)
+ * [N × 8 bytes] Wind report times, with flag 2: int64 LE
  */
 
+import { wrapLng } from "./utils";
+
 export interface PathPoint {
   raceTime: number;
   lng: number;
   lat: number;
   heading: number;
+  /** What the skipper asked for, on paths sailed on the server */
+  helm?: { kind: "heading" | "twa"; angle: number };
+  /** Time of the wind report in effect */
+  windReportTime?: number;
 }
 
-const PATH_VERSION = 1;
+const WITH_HELM = 1;
+const WITH_WIND_REPORTS = 2;
+
+function readInt64(view: DataView, offset: number): number {
+  // Read int64 as two uint32s (JS doesn't have native int64)
+  const low = view.getUint32(offset, true);
+  const high = view.getInt32(offset + 4, true);
+  return low + high * 0x100000000;
+}
 
 export async function fetchReplayPath(pathUrl: string): Promise<PathPoint[]> {
   const response = await fetch(pathUrl);
@@ -36,12 +56,20 @@ export function decodePath(buffer: ArrayBuffer): PathPoint[] {
   }
 
   const version = view.getUint32(0, true);
-  if (version !== PATH_VERSION) {
+  if (version !== 1 && version !== 2) {
     throw new Error(`Unsupported path version: ${version}`);
   }
+  if (version === 2 && buffer.byteLength < 12) {
+    throw new Error("Invalid path data: too short");
+  }
 
   const count = view.getUint32(4, true);
-  const expectedLen = 8 + count * 20;
+  const flags = version === 2 ? view.getUint32(8, true) : 0;
+  const headerLen = version === 2 ? 12 : 8;
+  const helmOffset = headerLen + count * 20;
+  const windReportsOffset = helmOffset + (flags & WITH_HELM ? count * 5 : 0);
+  const expectedLen =
+    windReportsOffset + (flags & WITH_WIND_REPORTS ? count * 8 : 0);
   if (buffer.byteLength < expectedLen) {
     throw new Error(
       `Invalid path data: expected ${expectedLen} bytes, got ${buffer.byteLength}`,
@@ -49,20 +77,26 @@ export function decodePath(buffer: ArrayBuffer): PathPoint[] {
   }
 
   const points: PathPoint[] = [];
-  let offset = 8;
+  let offset = headerLen;
 
   for (let i = 0; i < count; i++) {
-    // Read int64 as two uint32s (JS doesn't have native int64)
-    const low = view.getUint32(offset, true);
-    const high = view.getInt32(offset + 4, true);
-    const raceTime = low + high * 0x100000000;
-
-    points.push({
-      raceTime,
+    const point: PathPoint = {
+      raceTime: readInt64(view, offset),
       lng: view.getFloat32(offset + 8, true),
       lat: view.getFloat32(offset + 12, true),
       heading: view.getFloat32(offset + 16, true),
-    });
+    };
+    if (flags & WITH_HELM) {
+      const helm = helmOffset + i * 5;
+      point.helm = {
+        kind: view.getUint8(helm) === 1 ? "twa" : "heading",
+        angle: view.getFloat32(helm + 1, true),
+      };
+    }
+    if (flags & WITH_WIND_REPORTS) {
+      point.windReportTime = readInt64(view, windReportsOffset + i * 8);
+    }
+    points.push(point);
 
     offset += 20;
   }
@@ -107,7 +141,8 @@ export function interpolatePosition(
 
   return {
     raceTime,
-    lng: p1.lng + t * (p2.lng - p1.lng),
+    // The short way across the antimeridian
+    lng: wrapLng(p1.lng + t * wrapLng(p2.lng - p1.lng)),
     lat: p1.lat + t * (p2.lat - p1.lat),
     heading: (p1.heading + t * headingDiff + 360) % 360,
   };
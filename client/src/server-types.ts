@@ -0,0 +1,152 @@
+// Generated by `rewind export-types`, do not edit.
+
+export type LngLat = { lng: number, lat: number, };
+
+export type Gate = { center: LngLat, orientation: number, lengthNm: number, };
+
+export type Energy = { capacity: number, autopilot: number, instruments: number, 
+/**
+ * Solar charge with the sun overhead
+ */
+solar: number, 
+/**
+ * Hydro generator charge per knot of boat speed
+ */
+hydro: number, 
+/**
+ * Share of the boat speed lost to the drag of the hydro generator
+ */
+hydroDrag: number, };
+
+export type CourseTag = "ocean" | "offshore" | "sprint" | "historic";
+
+export type Course = { key: string, name: string, description: string, 
+/**
+ * Default boat class (polar name)
+ */
+polar: string, 
+/**
+ * Other boat classes players may pick instead of the default one
+ */
+boatClasses: Array<string>, startTime: number, start: LngLat, startHeading: number, finishLine: Gate, gates: Array<Gate>, routeWaypoints: Array<Array<LngLat>>, timeFactor: number, maxDays: number, 
+/**
+ * Energy budget of the boats, when the course manages energy
+ */
+energy: Energy | null, 
+/**
+ * Minimum depth in meters: boats ground in shallower water
+ */
+minDepth: number | null, 
+/**
+ * Times the gates are sailed, in order, before the finish line
+ */
+laps: number, 
+/**
+ * Kinds of race, to filter the course list
+ */
+tags: Array<CourseTag>, 
+/**
+ * Unpublished courses are kept but no race can be created on them
+ */
+published: boolean, 
+/**
+ * Real time window races can be created in, in ms since epoch, open
+ * ended when None (seasonal events)
+ */
+opensAt: number | null, closesAt: number | null, };
+
+export type System = "autopilot" | "instruments" | "hydro";
+
+export type Encoding = "json" | "msgpack";
+
+export type WindRasterSource = { time: number, pngUrl: string, };
+
+export type PlayerInfo = { id: string, name: string, 
+/**
+ * Small avatar of the player's profile, None for ghosts
+ */
+avatarUrl: string | null, };
+
+export type PlayerStatus = "racing" | "finished" | "retired" | "dnf";
+
+export type LeaderboardEntry = { playerId: string, playerName: string, nextGateIndex: number, distanceToNextGate: number, distanceToFinish: number, finishTime: number | null, status: PlayerStatus, 
+/**
+ * Boat class: standings are grouped by class
+ */
+polar: string, 
+/**
+ * Course times each lap was completed, on courses of several laps
+ */
+lapTimes: Array<number>, };
+
+export type Hint = { 
+/**
+ * Signed true wind angle, heading = wind direction - TWA
+ */
+twa: number, heading: number, 
+/**
+ * Boat speed in knots
+ */
+speed: number, 
+/**
+ * Speed made good toward the mark, in knots
+ */
+vmg: number, 
+/**
+ * Bearing of the mark
+ */
+bearing: number, 
+/**
+ * True wind speed in knots and direction (where it blows from)
+ */
+tws: number, twd: number, };
+
+export type Longitude = number;
+
+export type Latitude = number;
+
+export type Angle = number;
+
+export type ClientMessage = { "type": "Hello", protocolVersion: number, 
+/**
+ * Encoding of the server messages, JSON when unset
+ */
+encoding: Encoding, } | { "type": "CreateRace", courseKey: string, playerName: string, persistentId: string, 
+/**
+ * Private single-player time trial: hidden from the race list and not joinable
+ */
+solo: boolean, 
+/**
+ * Race the course in a random historical weather window instead of its own
+ */
+randomWeather: boolean, 
+/**
+ * Boat class (polar name) among the course classes; the course default when unset
+ */
+polar: string | null, } | { "type": "JoinRace", raceId: string, playerName: string, persistentId: string, 
+/**
+ * Boat class (polar name) among the course classes; the course default when unset
+ */
+polar: string | null, } | { "type": "StartDaily", playerName: string, persistentId: string, } | { "type": "QuickMatch", courseKey: string, playerName: string, persistentId: string, 
+/**
+ * Boat class (polar name) among the course classes; the course default when unset
+ */
+polar: string | null, } | { "type": "LeaveRace" } | { "type": "StartRace" } | { "type": "RetireFromRace" } | { "type": "ProposeRematch" } | { "type": "AddGhosts", count: number, } | { "type": "PositionUpdate", lng: Longitude, lat: Latitude, heading: Angle, } | { "type": "Helm", heading: Angle, twa: Angle | null, } | { "type": "ChangeSail", sail: string, } | { "type": "ToggleSystem", system: System, on: boolean, } | { "type": "AskCoach" } | { "type": "GateCrossed", gateIndex: number, courseTime: number, } | { "type": "Resume", raceId: string, playerId: string, persistentId: string, };
+
+export type ServerMessage = { "type": "Error", message: string, } | { "type": "Welcome", protocolVersion: number, encoding: Encoding, } | { "type": "RaceCreated", raceId: string, playerId: string, windRasterSources: Array<WindRasterSource>, 
+/**
+ * Start of the race's weather window, when it differs from the course default
+ */
+courseStartTime: number, 
+/**
+ * Boat class (polar name) of the player
+ */
+polar: string, } | { "type": "RaceJoined", raceId: string, playerId: string, courseKey: string, windRasterSources: Array<WindRasterSource>, players: Array<PlayerInfo>, isCreator: boolean, 
+/**
+ * Start of the race's weather window, when it differs from the course default
+ */
+courseStartTime: number, 
+/**
+ * Boat class (polar name) of the player
+ */
+polar: string, } | { "type": "PlayerJoined", playerId: string, playerName: string, } | { "type": "PlayerLeft", playerId: string, } | { "type": "RaceResumed", raceId: string, playerId: string, raceTime: number | null, } | { "type": "MatchQueued", courseKey: string, queuedPlayers: number, minPlayers: number, matchDeadline: number, } | { "type": "RaceCountdown", seconds: number, } | { "type": "PositionUpdate", playerId: string, lng: number, lat: number, heading: number, } | { "type": "SyncRaceTime", raceTime: number, } | { "type": "Ocs", playerId: string, over: boolean, } | { "type": "WindShadow", factor: number, } | { "type": "CoachHint", hint: Hint, } | { "type": "Energy", level: number, autopilot: boolean, instruments: boolean, hydro: boolean, } | { "type": "RaceEnded", reason: string, } | { "type": "PersonalBest", courseKey: string, finishTime: number, previousBest: number | null, } | { "type": "RematchProposed", raceId: string, proposerName: string, } | { "type": "Leaderboard", entries: Array<LeaderboardEntry>, };
@@ -2,6 +2,7 @@ import { geoDistance, geoPath } from "d3-geo";
 import { LngLat } from "../models";
 import { Scene } from "./scene";
 import { PeerState } from "../multiplayer/types";
+import { PeerBuffer } from "../multiplayer/peer-interpolation";
 import { BoatType, createBoatPolygon, getBoatSizeKm } from "./boat-geometry";
 
 export type RecordedGhostPosition = {
@@ -19,16 +20,31 @@ export default class GhostBoats {
   canvas: HTMLCanvasElement;
   peers: Map<string, PeerState> = new Map();
   recordedGhosts: Map<number, RecordedGhostPosition> = new Map();
+  /** Position updates of each peer, to move them between updates */
+  buffers: Map<string, PeerBuffer> = new Map();
+  /** Course time peers are shown at, null before the race clock runs */
+  courseTime: number | null = null;
 
   constructor(canvas: HTMLCanvasElement) {
     this.canvas = canvas;
   }
 
-  updatePeer(peerId: string, position: LngLat, heading: number, name: string) {
+  updatePeer(
+    peerId: string,
+    position: LngLat,
+    heading: number,
+    name: string,
+    raceTime: number,
+  ) {
+    let buffer = this.buffers.get(peerId);
+    if (!buffer) {
+      buffer = new PeerBuffer();
+      this.buffers.set(peerId, buffer);
+    }
+    buffer.push({ raceTime, position, heading });
+
     const peer = this.peers.get(peerId);
     if (peer) {
-      peer.position = position;
-      peer.heading = heading;
       peer.lastUpdate = Date.now();
     } else {
       this.peers.set(peerId, {
@@ -39,10 +55,36 @@ export default class GhostBoats {
         lastUpdate: Date.now(),
       });
     }
+    if (this.courseTime === null) {
+      this.moveTo(peerId, raceTime);
+    }
   }
 
   removePeer(peerId: string) {
     this.peers.delete(peerId);
+    this.buffers.delete(peerId);
+  }
+
+  /**
+   * Show peers where their updates put them at a course time. Without a
+   * race clock, updates of the previous race are forgotten.
+   */
+  setCourseTime(courseTime: number | null) {
+    this.courseTime = courseTime;
+    if (courseTime === null) {
+      this.buffers.clear();
+      return;
+    }
+    this.peers.forEach((_, peerId) => this.moveTo(peerId, courseTime));
+  }
+
+  private moveTo(peerId: string, courseTime: number) {
+    const peer = this.peers.get(peerId);
+    const sample = this.buffers.get(peerId)?.at(courseTime);
+    if (peer && sample) {
+      peer.position = sample.position;
+      peer.heading = sample.heading;
+    }
   }
 
   updateRecordedGhosts(ghosts: Map<number, RecordedGhostPosition>) {
@@ -18,6 +18,7 @@ import { polarToBoatType } from "./boat-geometry";
 import CourseLine from "./course-line";
 
 import Stars from "./stars";
+import { boundsCenter, boundsOf } from "../app/course-geometry";
 
 const MAX_SCALE = 50;
 
@@ -333,11 +334,17 @@ export class SphereView {
     position: LngLat,
     heading: number,
     name: string,
+    raceTime: number,
   ) {
-    this.ghostBoats.updatePeer(peerId, position, heading, name);
+    this.ghostBoats.updatePeer(peerId, position, heading, name, raceTime);
     this.render();
   }
 
+  /** Move peers to a course time, between their position updates */
+  updatePeersTime(courseTime: number | null) {
+    this.ghostBoats.setCourseTime(courseTime);
+  }
+
   removePeer(peerId: string) {
     this.ghostBoats.removePeer(peerId);
     this.render();
@@ -484,13 +491,13 @@ export class SphereView {
         ? this.course.gates[0].center
         : this.course.finishLine.center;
 
-    // Calculate center point between start and target
-    const centerLng = (start.lng + target.lng) / 2;
-    const centerLat = (start.lat + target.lat) / 2;
+    // Center between start and target, the short way around
+    const bounds = boundsOf([start, target]);
+    const center = boundsCenter(bounds);
 
     // Calculate distance in degrees (rough approximation)
-    const dLng = Math.abs(start.lng - target.lng);
-    const dLat = Math.abs(start.lat - target.lat);
+    const dLng = bounds.east - bounds.west;
+    const dLat = bounds.north - bounds.south;
     const distance = Math.sqrt(dLng * dLng + dLat * dLat);
 
     // Determine zoom level based on distance
@@ -504,7 +511,7 @@ export class SphereView {
     const baseScale = 500;
     const targetScale = baseScale * scale;
 
-    this.animateToView(centerLng, centerLat, targetScale, 1500);
+    this.animateToView(center.lng, center.lat, targetScale, 1500);
   }
 
   /**
@@ -3,7 +3,7 @@ import * as d3 from "d3";
 import { Topology } from "topojson-specification";
 import { Scene } from "./scene";
 
-const LOW_RES_PATH = "/sphere/land-110m.json";
+export const LOW_RES_PATH = "/sphere/land-110m.json";
 const HIGH_RES_PATH = "/sphere/land-50m.json";
 
 // Scale factor threshold for high-res (relative to base scale of 500)
@@ -150,7 +150,7 @@ export default class Land {
   }
 }
 
-async function getLand(path: string): Promise<d3.GeoPermissibleObjects> {
+export async function getLand(path: string): Promise<d3.GeoPermissibleObjects> {
   const world = await d3.json<Topology>(path);
   if (world) return topojson.feature(world, world.objects.land);
   else return Promise.reject(new Error("Failed to fetch land at: " + path));
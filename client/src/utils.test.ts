@@ -1,6 +1,8 @@
 import { describe, it, expect } from "vitest";
 import {
   msToKnots,
+  nmToKm,
+  kmToNm,
   getWindDirection,
   getWindSpeed,
   getWindSpeedKnots,
@@ -29,6 +31,13 @@ describe("msToKnots", () => {
   });
 });
 
+describe("nmToKm", () => {
+  it("converts nautical miles to km and back", () => {
+    expect(nmToKm(1)).toBe(1.852);
+    expect(kmToNm(nmToKm(42))).toBeCloseTo(42, 12);
+  });
+});
+
 describe("getWindDirection", () => {
   it("returns 0 for wind from the north (v negative, u zero)", () => {
     // Wind blowing TO the south means it comes FROM the north
@@ -1,4 +1,4 @@
-import { LngLat, Pixel, WindSpeed } from "./models";
+import { Course, LngLat, Pixel, RaceStats, WindSpeed } from "./models";
 
 /**
  * Format a duration in milliseconds as "14d 06:37" or "06:37".
@@ -13,14 +13,50 @@ export const formatDuration = (ms: number): string => {
   return days > 0 ? `${days}d ${hhmm}` : hhmm;
 };
 
-// Conversion factor: m/s to knots
+/**
+ * Format the split times of a result, one per mark before the finish, as
+ * "Gate 1 3d 04:12 · Gate 2 9d 18:40".
+ */
+export const formatSplits = (splits: number[]): string =>
+  splits
+    .map((split, i) => `Gate ${i + 1} ${formatDuration(split)}`)
+    .join(" · ");
+
+/**
+ * Format the statistics of a result as
+ * "2431 nm · avg 11.2 kn · max 24.8 kn · 12 tacks · 4 gybes".
+ */
+export const formatRaceStats = (stats: RaceStats): string =>
+  [
+    `${Math.round(stats.distanceNm)} nm`,
+    `avg ${stats.avgSpeed.toFixed(1)} kn`,
+    `max ${stats.maxSpeed.toFixed(1)} kn`,
+    `${stats.tacks} ${stats.tacks === 1 ? "tack" : "tacks"}`,
+    `${stats.gybes} ${stats.gybes === 1 ? "gybe" : "gybes"}`,
+  ].join(" · ");
+
+/**
+ * Whether races can be created on a course: published and within its
+ * opening window (mirrors server Course::check_open).
+ */
+export const isCourseOpen = (course: Course, now: number): boolean =>
+  course.published !== false &&
+  (course.opensAt == null || now >= course.opensAt) &&
+  (course.closesAt == null || now < course.closesAt);
+
+// Conversion factors, the same as the server's (units.rs)
 export const MS_TO_KNOTS = 1.944;
+export const KM_PER_NM = 1.852;
 
 /**
  * Convert wind speed from m/s to knots.
  */
 export const msToKnots = (ms: number): number => ms * MS_TO_KNOTS;
 
+export const nmToKm = (nm: number): number => nm * KM_PER_NM;
+
+export const kmToNm = (km: number): number => km / KM_PER_NM;
+
 /**
  * Calculate wind direction in degrees (meteorological convention: where wind comes FROM).
  * Returns value in range [0, 360).
@@ -51,6 +87,21 @@ export const toRadians = (d: number): number =>
 export const reframeLongitude = (lng: number): number =>
   lng > 180 ? lng - 360 : lng < -180 ? lng + 360 : lng;
 
+/**
+ * Longitude (or longitude difference) wrapped to -180..180, however far out.
+ */
+export const wrapLng = (lng: number): number =>
+  ((((lng + 180) % 360) + 360) % 360) - 180;
+
+/**
+ * Highest latitude boats sail to, keeping east-west moves finite near the
+ * poles. Mirrors `geo::MAX_LAT` on the server.
+ */
+export const MAX_LAT = 89;
+
+export const clampLat = (lat: number): number =>
+  Math.max(-MAX_LAT, Math.min(lat, MAX_LAT));
+
 export const clamp = (x: number, low: number, high: number): number =>
   Math.max(low, Math.min(x, high));
 
@@ -59,8 +110,6 @@ export const lngOneDegToM = (lat: number): number =>
 
 export const latOneDegToM = 111000;
 
-const KM_TO_NM = 0.539957;
-
 /**
  * Haversine distance between two coordinates in km.
  */
@@ -82,7 +131,7 @@ export function haversineDistanceKm(p1: LngLat, p2: LngLat): number {
  * Haversine distance between two coordinates in nautical miles.
  */
 export function haversineDistanceNm(p1: LngLat, p2: LngLat): number {
-  return haversineDistanceKm(p1, p2) * KM_TO_NM;
+  return kmToNm(haversineDistanceKm(p1, p2));
 }
 
 export const bilinear = ({ x, y }: Pixel, f: (p: Pixel) => number): number => {
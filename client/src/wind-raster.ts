@@ -96,7 +96,7 @@ const posToPixel = ({ lng, lat }: LngLat, pixelSize: number): Pixel | null => {
   }
 };
 
-// From [0..255] to +/-30 in m/s, inverse of ST_Reclass in server.
+// From [0..255] to +/-30 in m/s, see server/src/wind_png.rs for the format.
 function colorToSpeed(n: number): number {
   return (n * windScale * 2) / 255 - windScale;
 }
@@ -130,6 +130,43 @@ resource "aws_s3_bucket_cors_configuration" "paths" {
   }
 }
 
+# Profile avatars bucket (public read, shown in lobbies and leaderboards)
+resource "aws_s3_bucket" "avatars" {
+  bucket = "rewind-avatars"
+
+  tags = {
+    ManagedBy = "terraform"
+  }
+}
+
+resource "aws_s3_bucket_public_access_block" "avatars" {
+  bucket = aws_s3_bucket.avatars.id
+
+  block_public_acls       = false
+  block_public_policy     = false
+  ignore_public_acls      = false
+  restrict_public_buckets = false
+}
+
+resource "aws_s3_bucket_policy" "avatars_public_read" {
+  bucket = aws_s3_bucket.avatars.id
+
+  depends_on = [aws_s3_bucket_public_access_block.avatars]
+
+  policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [
+      {
+        Sid       = "PublicReadGetObject"
+        Effect    = "Allow"
+        Principal = "*"
+        Action    = "s3:GetObject"
+        Resource  = "${aws_s3_bucket.avatars.arn}/*"
+      }
+    ]
+  })
+}
+
 # IAM user for server to access both buckets
 resource "aws_iam_user" "gribs_uploader" {
   name = "rewind-gribs-uploader"
@@ -156,7 +193,9 @@ resource "aws_iam_user_policy" "gribs_uploader" {
           aws_s3_bucket.rasters.arn,
           "${aws_s3_bucket.rasters.arn}/*",
           aws_s3_bucket.paths.arn,
-          "${aws_s3_bucket.paths.arn}/*"
+          "${aws_s3_bucket.paths.arn}/*",
+          aws_s3_bucket.avatars.arn,
+          "${aws_s3_bucket.avatars.arn}/*"
         ]
       }
     ]
@@ -179,3 +218,7 @@ output "rasters_bucket_url" {
 output "paths_bucket_url" {
   value = "https://${aws_s3_bucket.paths.bucket_regional_domain_name}"
 }
+
+output "avatars_bucket_url" {
+  value = "https://${aws_s3_bucket.avatars.bucket_regional_domain_name}"
+}
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS race_results;
+DROP TABLE IF EXISTS courses;
+DROP TABLE IF EXISTS wind_reports;
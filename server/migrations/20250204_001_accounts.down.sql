@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS sessions;
+DROP TABLE IF EXISTS verification_codes;
+DROP TABLE IF EXISTS profiles;
+DROP TABLE IF EXISTS accounts;
@@ -0,0 +1,12 @@
+-- Structured per-race event log (joins, leaves, start, gates, finishes, disconnects)
+CREATE TABLE IF NOT EXISTS race_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    race_id TEXT NOT NULL,
+    course_key TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    player_id TEXT,
+    player_name TEXT,
+    payload TEXT NOT NULL DEFAULT '{}',
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now') * 1000)
+);
+CREATE INDEX IF NOT EXISTS idx_race_events_race ON race_events(race_id, id);
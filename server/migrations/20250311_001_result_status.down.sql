@@ -0,0 +1 @@
+ALTER TABLE race_results DROP COLUMN status;
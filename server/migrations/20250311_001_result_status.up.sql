@@ -0,0 +1,2 @@
+-- Result status: 'finished', 'retired' or 'dnf' (did not finish within the time limit)
+ALTER TABLE race_results ADD COLUMN status TEXT NOT NULL DEFAULT 'finished';
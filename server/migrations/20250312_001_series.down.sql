@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_race_results_race;
+ALTER TABLE race_results DROP COLUMN race_id;
+DROP TABLE IF EXISTS series_races;
+DROP TABLE IF EXISTS series;
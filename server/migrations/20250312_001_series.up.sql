@@ -0,0 +1,18 @@
+-- Tournament series: groups of races scored with points into cumulative standings
+CREATE TABLE IF NOT EXISTS series (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now') * 1000)
+);
+
+CREATE TABLE IF NOT EXISTS series_races (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    series_id INTEGER NOT NULL REFERENCES series(id) ON DELETE CASCADE,
+    race_id TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now') * 1000),
+    UNIQUE(series_id, race_id)
+);
+
+-- Results remember the multiplayer race they came from so series can score them
+ALTER TABLE race_results ADD COLUMN race_id TEXT;
+CREATE INDEX IF NOT EXISTS idx_race_results_race ON race_results(race_id);
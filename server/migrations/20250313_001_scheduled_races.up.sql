@@ -0,0 +1,12 @@
+-- Races opened and started by the server at fixed times
+CREATE TABLE IF NOT EXISTS scheduled_races (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    course_key TEXT NOT NULL,
+    start_at INTEGER NOT NULL,
+    lobby_minutes INTEGER NOT NULL DEFAULT 10,
+    series_id INTEGER REFERENCES series(id) ON DELETE SET NULL,
+    race_id TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now') * 1000)
+);
+CREATE INDEX IF NOT EXISTS idx_scheduled_races_status ON scheduled_races(status, start_at);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_race_results_daily;
+ALTER TABLE race_results DROP COLUMN daily_date;
+DROP TABLE IF EXISTS daily_challenges;
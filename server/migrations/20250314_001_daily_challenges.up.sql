@@ -0,0 +1,11 @@
+-- Daily challenge: one course and weather window per UTC day
+CREATE TABLE IF NOT EXISTS daily_challenges (
+    date TEXT PRIMARY KEY,
+    course_key TEXT NOT NULL,
+    start_time INTEGER NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now') * 1000)
+);
+
+-- Results of daily challenge runs are ranked per day, apart from the Hall of Fame
+ALTER TABLE race_results ADD COLUMN daily_date TEXT;
+CREATE INDEX IF NOT EXISTS idx_race_results_daily ON race_results(daily_date, finish_time);
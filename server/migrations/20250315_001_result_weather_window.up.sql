@@ -0,0 +1,3 @@
+-- Weather window a result was sailed in, when it differs from the course default
+-- (random weather races, daily challenges). Those results stay out of the Hall of Fame.
+ALTER TABLE race_results ADD COLUMN weather_start_time INTEGER;
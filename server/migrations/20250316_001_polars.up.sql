@@ -0,0 +1,7 @@
+-- Boat polars, keyed by the course `polar` name. `data` is the JSON table
+-- served to clients: { "<tws>": { "<twa>": <bsp> } }
+CREATE TABLE IF NOT EXISTS polars (
+    name TEXT PRIMARY KEY,
+    data TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_race_results_class;
+ALTER TABLE race_results DROP COLUMN polar;
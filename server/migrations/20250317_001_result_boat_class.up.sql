@@ -0,0 +1,7 @@
+-- Boat class (polar name) of each result: Hall of Fames are kept per class.
+-- Existing results were sailed with their course's default polar.
+ALTER TABLE race_results ADD COLUMN polar TEXT;
+UPDATE race_results SET polar = (
+    SELECT json_extract(courses.data, '$.polar') FROM courses WHERE courses.key = race_results.course_key
+);
+CREATE INDEX IF NOT EXISTS idx_race_results_class ON race_results(course_key, polar, finish_time);
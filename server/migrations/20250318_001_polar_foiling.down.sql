@@ -0,0 +1 @@
+ALTER TABLE polars DROP COLUMN foiling;
@@ -0,0 +1,3 @@
+-- Foiling regime of a polar (JSON, see `polars::Foiling`), NULL for boats
+-- without foils
+ALTER TABLE polars ADD COLUMN foiling TEXT;
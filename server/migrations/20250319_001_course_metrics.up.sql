@@ -0,0 +1,7 @@
+-- Cached course metrics (JSON, see `course_metrics::CourseMetrics`), computed
+-- from the stored wind and dropped when the course changes
+CREATE TABLE IF NOT EXISTS course_metrics (
+    course_key TEXT PRIMARY KEY,
+    data TEXT NOT NULL,
+    computed_at INTEGER NOT NULL
+);
@@ -0,0 +1,5 @@
+-- The tags stay in the course data, where older servers ignore them
+DROP TABLE IF EXISTS course_tags;
+DROP INDEX IF EXISTS idx_courses_max_days;
+ALTER TABLE courses DROP COLUMN max_days;
+ALTER TABLE courses DROP COLUMN name;
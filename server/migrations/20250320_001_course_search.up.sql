@@ -0,0 +1,23 @@
+-- Searchable copies of course fields, kept in sync with `data` on save
+ALTER TABLE courses ADD COLUMN name TEXT NOT NULL DEFAULT '';
+ALTER TABLE courses ADD COLUMN max_days INTEGER NOT NULL DEFAULT 0;
+UPDATE courses SET
+    name = COALESCE(json_extract(data, '$.name'), ''),
+    max_days = COALESCE(json_extract(data, '$.maxDays'), 0);
+CREATE INDEX IF NOT EXISTS idx_courses_max_days ON courses(max_days);
+
+-- Tags of the seeded courses
+UPDATE courses SET data = json_set(data, '$.tags', json('["ocean","historic"]'))
+WHERE key IN ('mt23', 'rdr22', 'vg20') AND json_extract(data, '$.tags') IS NULL;
+UPDATE courses SET data = json_set(data, '$.tags', json('["offshore","historic"]'))
+WHERE key = 'ore21' AND json_extract(data, '$.tags') IS NULL;
+
+-- Course tags (see `courses::CourseTag`), one row per tag
+CREATE TABLE IF NOT EXISTS course_tags (
+    course_key TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (course_key, tag)
+);
+CREATE INDEX IF NOT EXISTS idx_course_tags_tag ON course_tags(tag, course_key);
+INSERT OR IGNORE INTO course_tags (course_key, tag)
+SELECT courses.key, tags.value FROM courses, json_each(courses.data, '$.tags') AS tags;
@@ -0,0 +1,4 @@
+-- Magic links can't be typed as codes, drop them with the column
+DELETE FROM verification_codes WHERE kind <> 'code';
+DROP INDEX IF EXISTS idx_verification_codes_code;
+ALTER TABLE verification_codes DROP COLUMN kind;
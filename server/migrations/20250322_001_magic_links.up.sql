@@ -0,0 +1,4 @@
+-- Verification codes are 6-digit `code`s typed in the client or `magic`
+-- link tokens
+ALTER TABLE verification_codes ADD COLUMN kind TEXT NOT NULL DEFAULT 'code';
+CREATE INDEX IF NOT EXISTS idx_verification_codes_code ON verification_codes(code);
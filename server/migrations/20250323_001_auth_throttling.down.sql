@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS auth_lockouts;
+DROP TABLE IF EXISTS auth_failures;
+DROP INDEX IF EXISTS idx_verification_codes_ip;
+ALTER TABLE verification_codes DROP COLUMN ip;
+ALTER TABLE verification_codes DROP COLUMN attempts;
@@ -0,0 +1,18 @@
+-- Sign in throttling: wrong guesses per code, the requesting IP of codes,
+-- failed sign ins per email or IP and the lockouts they trigger
+ALTER TABLE verification_codes ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE verification_codes ADD COLUMN ip TEXT;
+CREATE INDEX IF NOT EXISTS idx_verification_codes_ip ON verification_codes(ip, created_at);
+
+CREATE TABLE IF NOT EXISTS auth_failures (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- "email:<address>" or "ip:<address>"
+    key TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_auth_failures_key ON auth_failures(key, created_at);
+
+CREATE TABLE IF NOT EXISTS auth_lockouts (
+    key TEXT PRIMARY KEY,
+    locked_until INTEGER NOT NULL
+);
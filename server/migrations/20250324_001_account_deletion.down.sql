@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_accounts_delete_at;
+ALTER TABLE accounts DROP COLUMN delete_at;
@@ -0,0 +1,4 @@
+-- Accounts whose owner asked for deletion are deleted once `delete_at` is
+-- past, unless the deletion is cancelled in between
+ALTER TABLE accounts ADD COLUMN delete_at INTEGER;
+CREATE INDEX IF NOT EXISTS idx_accounts_delete_at ON accounts(delete_at);
@@ -0,0 +1,2 @@
+ALTER TABLE scheduled_races DROP COLUMN account_id;
+DROP TABLE IF EXISTS api_tokens;
@@ -0,0 +1,17 @@
+-- Personal API tokens of accounts, for bots and dashboards. Only the SHA-256
+-- of a token is stored, `prefix` tells tokens apart in lists.
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    prefix TEXT NOT NULL,
+    -- Space separated, e.g. "read:results create:race"
+    scopes TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now') * 1000),
+    last_used_at INTEGER
+);
+CREATE INDEX IF NOT EXISTS idx_api_tokens_account ON api_tokens(account_id);
+
+-- Account that scheduled a race through the API, none for admin schedules
+ALTER TABLE scheduled_races ADD COLUMN account_id TEXT;
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_sessions_id;
+ALTER TABLE sessions DROP COLUMN user_agent;
+ALTER TABLE sessions DROP COLUMN id;
@@ -0,0 +1,6 @@
+-- Sessions listed to their owner: a public id, as the token stays secret,
+-- and the user agent that signed in
+ALTER TABLE sessions ADD COLUMN id TEXT;
+ALTER TABLE sessions ADD COLUMN user_agent TEXT;
+UPDATE sessions SET id = lower(hex(randomblob(16))) WHERE id IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_id ON sessions(id);
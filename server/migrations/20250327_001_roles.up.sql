@@ -0,0 +1,7 @@
+-- Staff roles of accounts: admin, moderator, course-editor
+CREATE TABLE IF NOT EXISTS roles (
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    role TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now') * 1000),
+    PRIMARY KEY (account_id, role)
+);
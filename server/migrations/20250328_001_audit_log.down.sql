@@ -0,0 +1,2 @@
+-- Dropping the table drops its triggers, and doesn't fire them
+DROP TABLE IF EXISTS audit_log;
@@ -0,0 +1,3 @@
+-- Version of the uploaded avatar of a profile, the upload time in ms.
+-- NULL when the profile has no avatar.
+ALTER TABLE profiles ADD COLUMN avatar_version INTEGER;
@@ -0,0 +1,8 @@
+-- Course elapsed time of each mark crossed before the finish, per result
+CREATE TABLE IF NOT EXISTS race_splits (
+    result_id INTEGER NOT NULL REFERENCES race_results(id) ON DELETE CASCADE,
+    -- Mark in sailing order, see Course::mark
+    gate_index INTEGER NOT NULL,
+    split_time INTEGER NOT NULL,
+    PRIMARY KEY (result_id, gate_index)
+);
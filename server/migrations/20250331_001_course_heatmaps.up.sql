@@ -0,0 +1,7 @@
+-- Latest published heatmap of each course (JSON, see `heatmaps::Heatmap`),
+-- rebuilt once results newer than `computed_at` come in
+CREATE TABLE IF NOT EXISTS course_heatmaps (
+    course_key TEXT PRIMARY KEY,
+    data TEXT NOT NULL,
+    computed_at INTEGER NOT NULL
+);
@@ -0,0 +1,5 @@
+ALTER TABLE race_results DROP COLUMN gybes;
+ALTER TABLE race_results DROP COLUMN tacks;
+ALTER TABLE race_results DROP COLUMN max_speed;
+ALTER TABLE race_results DROP COLUMN avg_speed;
+ALTER TABLE race_results DROP COLUMN distance_nm;
@@ -0,0 +1,7 @@
+-- Statistics of the path of a result, computed when it is saved (NULL for
+-- older results): distance in nm, speeds in knots, tacks and gybes
+ALTER TABLE race_results ADD COLUMN distance_nm REAL;
+ALTER TABLE race_results ADD COLUMN avg_speed REAL;
+ALTER TABLE race_results ADD COLUMN max_speed REAL;
+ALTER TABLE race_results ADD COLUMN tacks INTEGER;
+ALTER TABLE race_results ADD COLUMN gybes INTEGER;
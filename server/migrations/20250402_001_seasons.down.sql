@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_race_results_course_date;
+DROP TABLE IF EXISTS seasons;
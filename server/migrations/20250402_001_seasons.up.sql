@@ -0,0 +1,11 @@
+-- Seasons: named ranges of race dates ranked by period leaderboards
+CREATE TABLE IF NOT EXISTS seasons (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    start_time INTEGER NOT NULL,
+    end_time INTEGER NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now') * 1000)
+);
+
+-- Leaderboards of a period filter results of a course by race date
+CREATE INDEX IF NOT EXISTS idx_race_results_course_date ON race_results(course_key, race_start_time);
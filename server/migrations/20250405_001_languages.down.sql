@@ -0,0 +1,2 @@
+ALTER TABLE verification_codes DROP COLUMN language;
+ALTER TABLE accounts DROP COLUMN language;
@@ -0,0 +1,2 @@
+-- Accounts opt out of the summaries emailed after their finishes
+ALTER TABLE accounts ADD COLUMN race_summary_emails INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS
+    audit_log, roles, api_tokens, auth_lockouts, auth_failures,
+    account_identities, oauth_states, sessions, verification_codes, profiles,
+    accounts, seasons, daily_challenges, scheduled_races, series_races, series,
+    race_events, race_splits, race_results, polars, course_heatmaps,
+    course_metrics, course_tags, courses, wind_reports;
+DROP FUNCTION IF EXISTS audit_log_append_only();
+DROP FUNCTION IF EXISTS epoch_ms();
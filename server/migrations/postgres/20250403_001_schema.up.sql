@@ -0,0 +1,255 @@
+-- Postgres schema, the SQLite migrations up to 20250402 in one. Times are
+-- integer milliseconds and JSON is stored as text, as on SQLite, so the
+-- queries of the server run on both.
+CREATE FUNCTION epoch_ms() RETURNS BIGINT AS $$
+    SELECT (EXTRACT(EPOCH FROM now()) * 1000)::BIGINT
+$$ LANGUAGE SQL STABLE;
+
+CREATE TABLE wind_reports (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    time BIGINT NOT NULL UNIQUE,
+    grib_path TEXT NOT NULL,
+    png_path TEXT NOT NULL,
+    source TEXT NOT NULL DEFAULT 'ncar',
+    created_at BIGINT NOT NULL DEFAULT epoch_ms()
+);
+
+CREATE TABLE courses (
+    key TEXT PRIMARY KEY,
+    data TEXT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    updated_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    position BIGINT NOT NULL DEFAULT 0,
+    name TEXT NOT NULL DEFAULT '',
+    max_days BIGINT NOT NULL DEFAULT 0
+);
+CREATE INDEX idx_courses_max_days ON courses(max_days);
+
+CREATE TABLE course_tags (
+    course_key TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (course_key, tag)
+);
+CREATE INDEX idx_course_tags_tag ON course_tags(tag, course_key);
+
+CREATE TABLE course_metrics (
+    course_key TEXT PRIMARY KEY,
+    data TEXT NOT NULL,
+    computed_at BIGINT NOT NULL
+);
+
+CREATE TABLE course_heatmaps (
+    course_key TEXT PRIMARY KEY,
+    data TEXT NOT NULL,
+    computed_at BIGINT NOT NULL
+);
+
+CREATE TABLE polars (
+    name TEXT PRIMARY KEY,
+    data TEXT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    foiling TEXT
+);
+
+CREATE TABLE race_results (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    course_key TEXT NOT NULL,
+    player_name TEXT NOT NULL,
+    player_id TEXT,
+    finish_time BIGINT NOT NULL,
+    race_start_time BIGINT NOT NULL,
+    path_s3_key TEXT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    status TEXT NOT NULL DEFAULT 'finished',
+    race_id TEXT,
+    daily_date TEXT,
+    weather_start_time BIGINT,
+    polar TEXT,
+    distance_nm DOUBLE PRECISION,
+    avg_speed DOUBLE PRECISION,
+    max_speed DOUBLE PRECISION,
+    tacks BIGINT,
+    gybes BIGINT,
+    UNIQUE (course_key, player_id, race_start_time)
+);
+CREATE INDEX idx_leaderboard ON race_results(course_key, finish_time);
+CREATE INDEX idx_race_results_race ON race_results(race_id);
+CREATE INDEX idx_race_results_daily ON race_results(daily_date, finish_time);
+CREATE INDEX idx_race_results_class ON race_results(course_key, polar, finish_time);
+CREATE INDEX idx_race_results_course_date ON race_results(course_key, race_start_time);
+
+CREATE TABLE race_splits (
+    result_id BIGINT NOT NULL REFERENCES race_results(id) ON DELETE CASCADE,
+    gate_index BIGINT NOT NULL,
+    split_time BIGINT NOT NULL,
+    PRIMARY KEY (result_id, gate_index)
+);
+
+CREATE TABLE race_events (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    race_id TEXT NOT NULL,
+    course_key TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    player_id TEXT,
+    player_name TEXT,
+    payload TEXT NOT NULL DEFAULT '{}',
+    created_at BIGINT NOT NULL DEFAULT epoch_ms()
+);
+CREATE INDEX idx_race_events_race ON race_events(race_id, id);
+
+CREATE TABLE series (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms()
+);
+
+CREATE TABLE series_races (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    series_id BIGINT NOT NULL REFERENCES series(id) ON DELETE CASCADE,
+    race_id TEXT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    UNIQUE (series_id, race_id)
+);
+
+CREATE TABLE scheduled_races (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    course_key TEXT NOT NULL,
+    start_at BIGINT NOT NULL,
+    lobby_minutes BIGINT NOT NULL DEFAULT 10,
+    series_id BIGINT REFERENCES series(id) ON DELETE SET NULL,
+    race_id TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    created_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    account_id TEXT
+);
+CREATE INDEX idx_scheduled_races_status ON scheduled_races(status, start_at);
+
+CREATE TABLE daily_challenges (
+    date TEXT PRIMARY KEY,
+    course_key TEXT NOT NULL,
+    start_time BIGINT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms()
+);
+
+CREATE TABLE seasons (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    name TEXT NOT NULL,
+    start_time BIGINT NOT NULL,
+    end_time BIGINT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms()
+);
+
+CREATE TABLE accounts (
+    id TEXT PRIMARY KEY,
+    email TEXT NOT NULL UNIQUE,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    delete_at BIGINT
+);
+CREATE INDEX idx_accounts_delete_at ON accounts(delete_at);
+
+CREATE TABLE profiles (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    avatar_version BIGINT
+);
+CREATE INDEX idx_profiles_account ON profiles(account_id);
+
+CREATE TABLE verification_codes (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    email TEXT NOT NULL,
+    code TEXT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    used_at BIGINT,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    kind TEXT NOT NULL DEFAULT 'code',
+    attempts BIGINT NOT NULL DEFAULT 0,
+    ip TEXT
+);
+CREATE INDEX idx_verification_codes_email ON verification_codes(email);
+CREATE INDEX idx_verification_codes_code ON verification_codes(code);
+CREATE INDEX idx_verification_codes_ip ON verification_codes(ip, created_at);
+
+CREATE TABLE sessions (
+    token TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    expires_at BIGINT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    last_active_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    id TEXT UNIQUE,
+    user_agent TEXT
+);
+CREATE INDEX idx_sessions_account ON sessions(account_id);
+CREATE INDEX idx_sessions_expires ON sessions(expires_at);
+
+CREATE TABLE oauth_states (
+    state TEXT PRIMARY KEY,
+    provider TEXT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms()
+);
+
+CREATE TABLE account_identities (
+    provider TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    PRIMARY KEY (provider, subject)
+);
+CREATE INDEX idx_account_identities_account ON account_identities(account_id);
+
+CREATE TABLE auth_failures (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    key TEXT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX idx_auth_failures_key ON auth_failures(key, created_at);
+
+CREATE TABLE auth_lockouts (
+    key TEXT PRIMARY KEY,
+    locked_until BIGINT NOT NULL
+);
+
+CREATE TABLE api_tokens (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    prefix TEXT NOT NULL,
+    scopes TEXT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    last_used_at BIGINT
+);
+CREATE INDEX idx_api_tokens_account ON api_tokens(account_id);
+
+CREATE TABLE roles (
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    role TEXT NOT NULL,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms(),
+    PRIMARY KEY (account_id, role)
+);
+
+CREATE TABLE audit_log (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    actor_id TEXT,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    payload TEXT NOT NULL DEFAULT 'null',
+    created_at BIGINT NOT NULL DEFAULT epoch_ms()
+);
+CREATE INDEX idx_audit_log_actor ON audit_log(actor_id);
+CREATE INDEX idx_audit_log_target ON audit_log(target);
+
+CREATE FUNCTION audit_log_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
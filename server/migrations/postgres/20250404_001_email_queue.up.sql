@@ -0,0 +1,15 @@
+-- Outgoing emails, sent by a background worker with retries. Sent emails
+-- are deleted, those failing for good are kept as dead letters
+CREATE TABLE email_queue (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    recipient TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    html TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts BIGINT NOT NULL DEFAULT 0,
+    next_attempt_at BIGINT NOT NULL,
+    expires_at BIGINT,
+    last_error TEXT,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms()
+);
+CREATE INDEX idx_email_queue_due ON email_queue(status, next_attempt_at);
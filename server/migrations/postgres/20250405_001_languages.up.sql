@@ -0,0 +1,4 @@
+-- Language of the emails of an account, from the client it last signed in
+-- with. Codes carry the language until the account is known
+ALTER TABLE accounts ADD COLUMN language TEXT;
+ALTER TABLE verification_codes ADD COLUMN language TEXT;
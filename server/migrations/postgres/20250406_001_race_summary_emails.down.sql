@@ -0,0 +1 @@
+ALTER TABLE accounts DROP COLUMN race_summary_emails;
@@ -0,0 +1,25 @@
+-- External URLs notified of race and result events, with the secret
+-- signing their payloads. Empty events subscribe to all of them
+CREATE TABLE webhooks (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    events TEXT NOT NULL DEFAULT '',
+    created_by TEXT,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms()
+);
+
+-- Events to post to a webhook, retried by a background worker. Delivered
+-- ones are deleted, those failing for good are kept as dead letters
+CREATE TABLE webhook_deliveries (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    webhook_id BIGINT NOT NULL,
+    event TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts BIGINT NOT NULL DEFAULT 0,
+    next_attempt_at BIGINT NOT NULL,
+    last_error TEXT,
+    created_at BIGINT NOT NULL DEFAULT epoch_ms()
+);
+CREATE INDEX idx_webhook_deliveries_due ON webhook_deliveries(status, next_attempt_at);
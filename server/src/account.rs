@@ -0,0 +1,300 @@
+//! Self service of account data: export of everything tied to an account, and
+//! deletion after a grace period during which the owner can change their mind.
+//!
+//! Deleted accounts keep their results on the leaderboards, anonymized.
+
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    api_tokens,
+    audit::{self, Action},
+    auth, db, profiles, race_events, race_results,
+};
+
+/// Time between a deletion request and the deletion
+pub const DELETION_GRACE_MS: i64 = 14 * 24 * 60 * 60 * 1000; // 14 days
+
+/// Interval between checks for accounts due for deletion
+const PURGE_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Everything stored about an account
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountExport {
+    pub exported_at: i64,
+    pub account: ExportedAccount,
+    pub identities: Vec<ExportedIdentity>,
+    /// Personal API tokens, without their secrets
+    pub api_tokens: Vec<api_tokens::ApiToken>,
+    pub profiles: Vec<profiles::Profile>,
+    /// Results of every profile, with the S3 keys of their recorded paths
+    pub results: Vec<race_results::PlayerResult>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedAccount {
+    pub id: String,
+    pub email: String,
+    pub created_at: i64,
+    pub delete_at: Option<i64>,
+    /// Language of the emails
+    pub language: Option<String>,
+    pub race_summary_emails: bool,
+}
+
+/// A linked sign in provider account
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedIdentity {
+    pub provider: String,
+    pub email: String,
+    pub created_at: i64,
+}
+
+/// Collect the data of an account
+pub async fn export(account_id: &str) -> Result<AccountExport> {
+    let (email, created_at, delete_at, language, race_summary_emails): (
+        String,
+        i64,
+        Option<i64>,
+        Option<String>,
+        i64,
+    ) = sqlx::query_as(
+        "SELECT email, created_at, delete_at, language, race_summary_emails FROM accounts
+         WHERE id = $1",
+    )
+    .bind(account_id)
+    .fetch_optional(db::pool())
+    .await?
+    .ok_or_else(|| anyhow!("Account not found"))?;
+
+    let identities: Vec<(String, String, i64)> = sqlx::query_as(
+        "SELECT provider, email, created_at FROM account_identities WHERE account_id = $1 ORDER BY created_at",
+    )
+    .bind(account_id)
+    .fetch_all(db::pool())
+    .await?;
+
+    let profiles = profiles::list_profiles(account_id).await?;
+    let mut results = Vec::new();
+    for profile in &profiles {
+        results.extend(race_results::list_for_player(&profile.id).await?);
+    }
+
+    Ok(AccountExport {
+        exported_at: Utc::now().timestamp_millis(),
+        account: ExportedAccount {
+            id: account_id.to_string(),
+            email,
+            created_at,
+            delete_at,
+            language,
+            race_summary_emails: race_summary_emails != 0,
+        },
+        identities: identities
+            .into_iter()
+            .map(|(provider, email, created_at)| ExportedIdentity {
+                provider,
+                email,
+                created_at,
+            })
+            .collect(),
+        api_tokens: api_tokens::list(account_id).await?,
+        profiles,
+        results,
+    })
+}
+
+/// When the account is due for deletion, if its owner asked for it
+pub async fn deletion_date(account_id: &str) -> Result<Option<i64>> {
+    let row: Option<(Option<i64>,)> =
+        sqlx::query_as("SELECT delete_at FROM accounts WHERE id = $1")
+            .bind(account_id)
+            .fetch_optional(db::pool())
+            .await?;
+    Ok(row.and_then(|(delete_at,)| delete_at))
+}
+
+/// Whether the account gets a summary email after its finishes
+pub async fn race_summary_emails(account_id: &str) -> Result<bool> {
+    let row: Option<(i64,)> =
+        sqlx::query_as("SELECT race_summary_emails FROM accounts WHERE id = $1")
+            .bind(account_id)
+            .fetch_optional(db::pool())
+            .await?;
+    Ok(row.is_some_and(|(enabled,)| enabled != 0))
+}
+
+pub async fn set_race_summary_emails(account_id: &str, enabled: bool) -> Result<()> {
+    sqlx::query("UPDATE accounts SET race_summary_emails = $1 WHERE id = $2")
+        .bind(enabled as i64)
+        .bind(account_id)
+        .execute(db::pool())
+        .await?;
+    Ok(())
+}
+
+/// Schedule the deletion of an account after the grace period. Asking again
+/// keeps the first date. Returns the deletion date.
+pub async fn request_deletion(account_id: &str, now: i64) -> Result<i64> {
+    let (delete_at,): (i64,) = sqlx::query_as(
+        "UPDATE accounts SET delete_at = COALESCE(delete_at, $1) WHERE id = $2 RETURNING delete_at",
+    )
+    .bind(now + DELETION_GRACE_MS)
+    .bind(account_id)
+    .fetch_optional(db::pool())
+    .await?
+    .ok_or_else(|| anyhow!("Account not found"))?;
+
+    log::info!(
+        "Account {} scheduled for deletion at {}",
+        account_id,
+        delete_at
+    );
+    Ok(delete_at)
+}
+
+/// Keep an account scheduled for deletion
+pub async fn cancel_deletion(account_id: &str) -> Result<()> {
+    sqlx::query("UPDATE accounts SET delete_at = NULL WHERE id = $1")
+        .bind(account_id)
+        .execute(db::pool())
+        .await?;
+    log::info!("Account {} deletion cancelled", account_id);
+    Ok(())
+}
+
+/// Delete an account now: its results are anonymized, then its rows deleted.
+pub async fn delete(account_id: &str) -> Result<()> {
+    let mut anonymized = 0;
+    for profile in profiles::list_profiles(account_id).await? {
+        anonymized += race_results::anonymize_player(&profile.id).await?;
+        race_events::anonymize_player(&profile.id).await?;
+    }
+    auth::delete_account(account_id).await?;
+    log::info!(
+        "Deleted account {}, {} results anonymized",
+        account_id,
+        anonymized
+    );
+    Ok(())
+}
+
+/// Delete the accounts past their deletion date
+pub async fn purge_due(now: i64) -> Result<usize> {
+    let due: Vec<(String,)> = sqlx::query_as("SELECT id FROM accounts WHERE delete_at <= $1")
+        .bind(now)
+        .fetch_all(db::pool())
+        .await?;
+    for (account_id,) in &due {
+        delete(account_id).await?;
+        audit::record(None, Action::AccountDelete, account_id, Value::Null).await?;
+    }
+    Ok(due.len())
+}
+
+/// Delete the accounts due for deletion, periodically
+pub async fn run_purger() {
+    loop {
+        if let Err(e) = purge_due(Utc::now().timestamp_millis()).await {
+            log::error!("Account purge failed: {}", e);
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(PURGE_INTERVAL_SECS)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::race_results::{NewRaceResult, ResultStatus};
+
+    #[tokio::test]
+    async fn test_export_and_deletion() {
+        db::init_test().await.unwrap();
+
+        let account_id = auth::get_or_create_account("leaving@example.com")
+            .await
+            .unwrap();
+        let profile = profiles::list_profiles(&account_id).await.unwrap()[0]
+            .id
+            .clone();
+        race_results::save_result(&NewRaceResult {
+            race_id: "leaving-race".to_string(),
+            course_key: "vg20".to_string(),
+            player_name: "Leaving".to_string(),
+            player_id: profile.clone(),
+            finish_time: 1000,
+            race_start_time: 42,
+            path_s3_key: "paths/leaving.bin".to_string(),
+            status: ResultStatus::Finished,
+            daily_date: None,
+            weather_start_time: None,
+            polar: "imoca".to_string(),
+            splits: vec![],
+            stats: None,
+        })
+        .await
+        .unwrap();
+
+        let data = export(&account_id).await.unwrap();
+        assert_eq!(data.account.email, "leaving@example.com");
+        assert_eq!(data.profiles.len(), 1);
+        assert_eq!(data.results.len(), 1);
+        assert_eq!(data.results[0].path_s3_key, "paths/leaving.bin");
+
+        // Scheduled after the grace period, and kept until then
+        let now = Utc::now().timestamp_millis();
+        let delete_at = request_deletion(&account_id, now).await.unwrap();
+        assert_eq!(delete_at, now + DELETION_GRACE_MS);
+        assert_eq!(
+            request_deletion(&account_id, now + 1000).await.unwrap(),
+            delete_at
+        );
+        purge_due(delete_at - 1).await.unwrap();
+        assert!(
+            auth::get_account_email(&account_id)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // Cancelled
+        cancel_deletion(&account_id).await.unwrap();
+        assert_eq!(deletion_date(&account_id).await.unwrap(), None);
+        purge_due(delete_at).await.unwrap();
+        assert!(
+            auth::get_account_email(&account_id)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // Deleted, the result stays anonymized
+        request_deletion(&account_id, now).await.unwrap();
+        purge_due(delete_at).await.unwrap();
+        assert!(
+            auth::get_account_email(&account_id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            race_results::list_for_player(&profile)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let (player_name, player_id): (String, Option<String>) =
+            sqlx::query_as("SELECT player_name, player_id FROM race_results WHERE race_id = $1")
+                .bind("leaving-race")
+                .fetch_one(db::pool())
+                .await
+                .unwrap();
+        assert_eq!(player_name, race_results::ANONYMOUS_NAME);
+        assert_eq!(player_id, None);
+    }
+}
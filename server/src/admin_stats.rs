@@ -0,0 +1,174 @@
+//! Figures of the ops dashboard, served by `/admin/stats`: accounts, races
+//! created and results saved over the last days, live connections, wind
+//! data coverage and storage usage.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::multiplayer::RaceManager;
+use crate::storage_usage::{self, BucketUsage};
+use crate::{auth, db, wind_reports};
+
+/// Days covered by the daily counts, today included
+const DAYS: u64 = 30;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    pub accounts: i64,
+    /// Oldest day first
+    pub days: Vec<DayStats>,
+    /// Open websocket connections
+    pub connections: usize,
+    /// Races in memory, lobbies included
+    pub live_races: usize,
+    pub wind: WindCoverage,
+    /// Totals of the last listing when the storage can't be listed
+    pub storage: Vec<BucketUsage>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayStats {
+    pub date: NaiveDate,
+    pub accounts_created: usize,
+    pub races_created: usize,
+    /// Results saved, finishes and retirements
+    pub races_finished: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindCoverage {
+    pub reports: usize,
+    pub first: Option<i64>,
+    pub last: Option<i64>,
+    /// Report slots between the first and last reports without one
+    pub missing_slots: usize,
+}
+
+pub async fn stats(manager: &RaceManager) -> Result<Stats> {
+    let today = Utc::now().date_naive();
+    let first_day = today - Days::new(DAYS - 1);
+    let since = first_day
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc()
+        .timestamp_millis();
+
+    let accounts: Vec<(i64,)> =
+        sqlx::query_as("SELECT created_at FROM accounts WHERE created_at >= $1")
+            .bind(since)
+            .fetch_all(db::pool())
+            .await?;
+    // A race is created with its first event
+    let races: Vec<(i64,)> = sqlx::query_as(
+        "SELECT MIN(created_at) FROM race_events GROUP BY race_id HAVING MIN(created_at) >= $1",
+    )
+    .bind(since)
+    .fetch_all(db::pool())
+    .await?;
+    let results: Vec<(i64,)> =
+        sqlx::query_as("SELECT created_at FROM race_results WHERE created_at >= $1")
+            .bind(since)
+            .fetch_all(db::pool())
+            .await?;
+
+    let mut days: BTreeMap<NaiveDate, DayStats> = first_day
+        .iter_days()
+        .take(DAYS as usize)
+        .map(|date| {
+            (
+                date,
+                DayStats {
+                    date,
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+    let mut count = |times: Vec<(i64,)>, field: fn(&mut DayStats) -> &mut usize| {
+        for (time,) in times {
+            if let Some(day) = DateTime::from_timestamp_millis(time)
+                .and_then(|time| days.get_mut(&time.date_naive()))
+            {
+                *field(day) += 1;
+            }
+        }
+    };
+    count(accounts, |day| &mut day.accounts_created);
+    count(races, |day| &mut day.races_created);
+    count(results, |day| &mut day.races_finished);
+
+    // A storage outage shouldn't take the rest of the dashboard with it
+    let storage = storage_usage::usage().await.unwrap_or_else(|e| {
+        log::error!("Failed to list the storage usage: {}", e);
+        storage_usage::last_usage()
+    });
+
+    Ok(Stats {
+        accounts: auth::count_accounts().await?,
+        days: days.into_values().collect(),
+        connections: manager.connection_count(),
+        live_races: manager.race_ids().await.len(),
+        wind: wind_coverage(&wind_reports::get_all_times().await?),
+        storage,
+    })
+}
+
+/// Coverage of the report times, sorted
+fn wind_coverage(times: &[i64]) -> WindCoverage {
+    let (first, last) = (times.first().copied(), times.last().copied());
+    let slots = match (first, last) {
+        (Some(first), Some(last)) => {
+            ((last - first) / wind_reports::MAX_REPORT_GAP_MS) as usize + 1
+        }
+        _ => 0,
+    };
+    WindCoverage {
+        reports: times.len(),
+        first,
+        last,
+        missing_slots: slots.saturating_sub(times.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wind_coverage() {
+        let gap = wind_reports::MAX_REPORT_GAP_MS;
+        assert_eq!(
+            wind_coverage(&[0, gap, 3 * gap, 4 * gap]),
+            WindCoverage {
+                reports: 4,
+                first: Some(0),
+                last: Some(4 * gap),
+                missing_slots: 1,
+            }
+        );
+        assert_eq!(wind_coverage(&[]).missing_slots, 0);
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        db::init_test().await.unwrap();
+        auth::get_or_create_account("stats@example.com")
+            .await
+            .unwrap();
+
+        let stats = stats(&RaceManager::new()).await.unwrap();
+        assert_eq!(stats.days.len(), DAYS as usize);
+        let today = stats.days.last().unwrap();
+        assert_eq!(today.date, Utc::now().date_naive());
+        assert!(today.accounts_created >= 1);
+        assert!(stats.accounts >= 1);
+        assert_eq!(stats.connections, 0);
+        assert_eq!(stats.storage.len(), 5);
+    }
+}
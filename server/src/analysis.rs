@@ -0,0 +1,226 @@
+//! Post-race analysis: the path sailed by a finished result against the
+//! optimal route over the same weather.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::courses::{Course, Gate, LngLat};
+use crate::polars::Polar;
+use crate::race_results::{self, PathPoint, StoredResult};
+use crate::routing::{self, RoutePoint};
+use crate::{gates, geo};
+
+const HOUR_MS: f64 = 60.0 * 60.0 * 1000.0;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzeRequest {
+    pub result_id: i64,
+}
+
+/// Time spent on a leg, in ms of course time
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegTimes {
+    pub leg: usize,
+    pub sailed: i64,
+    pub optimal: i64,
+    /// Time lost against the optimal route, negative when the leg was sailed faster
+    pub delta: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Analysis {
+    pub result_id: i64,
+    pub legs: Vec<LegTimes>,
+    /// Finish time lost against the optimal route, in ms of course time
+    pub delta: i64,
+    pub route: Vec<RoutePoint>,
+    pub sailed: Vec<RoutePoint>,
+}
+
+/// Course times the path crossed each gate, in order. A crossing missed
+/// between two samples is taken at the sample closest to the gate center.
+pub fn gate_times(gates: &[&Gate], path: &[PathPoint]) -> Vec<i64> {
+    let position = |point: &PathPoint| LngLat {
+        lng: point.lng as f64,
+        lat: point.lat as f64,
+    };
+    let mut times = Vec::with_capacity(gates.len());
+    let mut from = 0;
+    for gate in gates {
+        let remaining = &path[from.min(path.len())..];
+        let crossed = remaining
+            .windows(2)
+            .position(|w| gates::crosses_gate(&position(&w[0]), &position(&w[1]), gate))
+            .map(|i| i + 1)
+            .or_else(|| {
+                (0..remaining.len()).min_by(|&a, &b| {
+                    let distance =
+                        |i: usize| geo::distance_nm(&position(&remaining[i]), &gate.center);
+                    distance(a).total_cmp(&distance(b))
+                })
+            });
+        let Some(index) = crossed else {
+            break;
+        };
+        times.push(remaining[index].race_time);
+        from += index;
+    }
+    times
+}
+
+/// Leg times between successive mark times, both starting at `start_time`
+pub fn compare(start_time: i64, sailed: &[i64], optimal: &[i64]) -> Vec<LegTimes> {
+    let durations = |times: &[i64]| {
+        std::iter::once(start_time)
+            .chain(times.iter().copied())
+            .collect::<Vec<_>>()
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .collect::<Vec<_>>()
+    };
+    durations(sailed)
+        .into_iter()
+        .zip(durations(optimal))
+        .enumerate()
+        .map(|(leg, (sailed, optimal))| LegTimes {
+            leg,
+            sailed,
+            optimal,
+            delta: sailed - optimal,
+        })
+        .collect()
+}
+
+/// Sailed path as route points, the speed made good between samples
+fn sailed_points(path: &[PathPoint]) -> Vec<RoutePoint> {
+    path.iter()
+        .enumerate()
+        .map(|(i, point)| {
+            let speed = path.get(i + 1).and_then(|next| {
+                let hours = (next.race_time - point.race_time) as f64 / HOUR_MS;
+                let from = LngLat {
+                    lng: point.lng as f64,
+                    lat: point.lat as f64,
+                };
+                let to = LngLat {
+                    lng: next.lng as f64,
+                    lat: next.lat as f64,
+                };
+                (hours > 0.0).then(|| geo::distance_nm(&from, &to) / hours)
+            });
+            RoutePoint {
+                lng: point.lng as f64,
+                lat: point.lat as f64,
+                time: point.race_time,
+                heading: speed.map(|_| point.heading as f64),
+                speed,
+            }
+        })
+        .collect()
+}
+
+/// Analyze a finished result sailed on `course` with `polar`. None when the
+/// stored wind doesn't take the optimal route around the course.
+pub async fn analyze(
+    result_id: i64,
+    result: &StoredResult,
+    course: &Course,
+    polar: &Polar,
+) -> Result<Option<Analysis>> {
+    let path = race_results::load_path(&result.path_s3_key).await?;
+    let start_time = result.weather_start_time.unwrap_or(course.start_time);
+    let finish_time = start_time + result.finish_time;
+
+    let gates = course.marks();
+    let marks = gates
+        .iter()
+        .map(|gate| gate.center.clone())
+        .collect::<Vec<_>>();
+    let until = start_time + course.max_days as i64 * 24 * HOUR_MS as i64;
+    let Some(legs) = routing::route_marks(polar, &course.start, &marks, start_time, until).await?
+    else {
+        return Ok(None);
+    };
+
+    let mut sailed_times = gate_times(&gates[..gates.len() - 1], &path);
+    sailed_times.push(finish_time);
+    let optimal_times = legs.iter().map(|leg| leg.eta).collect::<Vec<_>>();
+    let delta = finish_time - optimal_times.last().copied().unwrap_or(finish_time);
+
+    // Legs join at the marks: keep the point each leg leaves from
+    let leg_count = legs.len();
+    let route = legs
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, leg)| {
+            let mut path = leg.path;
+            if i + 1 < leg_count {
+                path.pop();
+            }
+            path
+        })
+        .collect();
+
+    Ok(Some(Analysis {
+        result_id,
+        legs: compare(start_time, &sailed_times, &optimal_times),
+        delta,
+        route,
+        sailed: sailed_points(&path),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(race_time: i64, lng: f32) -> PathPoint {
+        PathPoint {
+            race_time,
+            lng,
+            lat: 0.0,
+            heading: 90.0,
+        }
+    }
+
+    #[test]
+    fn test_gate_times() {
+        let path = (0..=10)
+            .map(|i| point(i * 1000, i as f32 * 0.5))
+            .collect::<Vec<_>>();
+        let (first, second) = (
+            Gate::vertical(1.2, 0.0, 10.0),
+            Gate::vertical(3.7, 0.0, 10.0),
+        );
+        assert_eq!(gate_times(&[&first, &second], &path), vec![3000, 8000]);
+
+        // Out of the gate: the closest sample
+        let wide = Gate::vertical(2.0, 5.0, 1.0);
+        assert_eq!(gate_times(&[&wide], &path), vec![4000]);
+    }
+
+    #[test]
+    fn test_compare() {
+        let legs = compare(100, &[1100, 2600], &[1000, 2000]);
+        assert_eq!(
+            legs,
+            vec![
+                LegTimes {
+                    leg: 0,
+                    sailed: 1000,
+                    optimal: 900,
+                    delta: 100,
+                },
+                LegTimes {
+                    leg: 1,
+                    sailed: 1500,
+                    optimal: 1000,
+                    delta: 500,
+                },
+            ]
+        );
+    }
+}
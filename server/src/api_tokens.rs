@@ -0,0 +1,249 @@
+//! Personal API tokens: long-lived bearer tokens of an account, limited to
+//! scopes, so players can build bots and stats dashboards against the API.
+//!
+//! Tokens are shown once at creation; only their SHA-256 is stored.
+
+use anyhow::Result;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::db;
+
+/// Start of every API token, telling them apart from session tokens
+pub const TOKEN_PREFIX: &str = "rwd_";
+
+const MAX_TOKENS_PER_ACCOUNT: i64 = 10;
+
+/// What a token may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    /// Read the account email and profiles
+    #[serde(rename = "read:account")]
+    ReadAccount,
+    /// Read the results of the account profiles
+    #[serde(rename = "read:results")]
+    ReadResults,
+    /// Schedule races
+    #[serde(rename = "create:race")]
+    CreateRace,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::ReadAccount, Scope::ReadResults, Scope::CreateRace];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ReadAccount => "read:account",
+            Scope::ReadResults => "read:results",
+            Scope::CreateRace => "create:race",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Scope> {
+        Scope::ALL.into_iter().find(|scope| scope.as_str() == s)
+    }
+}
+
+fn parse_scopes(scopes: &str) -> Vec<Scope> {
+    scopes.split_whitespace().filter_map(Scope::parse).collect()
+}
+
+/// A token as listed to its owner, without the secret
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    /// First characters of the token
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+/// A new token, with its secret shown this once
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedToken {
+    #[serde(flatten)]
+    pub token: ApiToken,
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTokenRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// Whether a bearer token is an API token rather than a session token
+pub fn is_api_token(token: &str) -> bool {
+    token.starts_with(TOKEN_PREFIX)
+}
+
+fn generate_token() -> String {
+    let bytes: [u8; 20] = rand::rng().random();
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", TOKEN_PREFIX, hex)
+}
+
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Create a token for an account.
+pub async fn create(account_id: &str, name: &str, scopes: &[Scope]) -> Result<CreatedToken> {
+    let name = name.trim();
+    if name.is_empty() {
+        anyhow::bail!("Token name cannot be empty");
+    }
+    if name.len() > 40 {
+        anyhow::bail!("Token name cannot exceed 40 characters");
+    }
+    if scopes.is_empty() {
+        anyhow::bail!("A token needs at least one scope");
+    }
+
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM api_tokens WHERE account_id = $1")
+        .bind(account_id)
+        .fetch_one(db::pool())
+        .await?;
+    if count >= MAX_TOKENS_PER_ACCOUNT {
+        anyhow::bail!("Maximum of {} tokens per account", MAX_TOKENS_PER_ACCOUNT);
+    }
+
+    let scopes: Vec<Scope> = Scope::ALL
+        .into_iter()
+        .filter(|scope| scopes.contains(scope))
+        .collect();
+    let id = uuid::Uuid::new_v4().to_string();
+    let secret = generate_token();
+    let prefix = secret[..TOKEN_PREFIX.len() + 6].to_string();
+    let scope_names: Vec<&str> = scopes.iter().map(|s| s.as_str()).collect();
+
+    let (created_at,): (i64,) = sqlx::query_as(
+        "INSERT INTO api_tokens (id, account_id, name, token_hash, prefix, scopes)
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING created_at",
+    )
+    .bind(&id)
+    .bind(account_id)
+    .bind(name)
+    .bind(hash_token(&secret))
+    .bind(&prefix)
+    .bind(scope_names.join(" "))
+    .fetch_one(db::pool())
+    .await?;
+
+    log::info!("Created API token {} for account {}", id, account_id);
+
+    Ok(CreatedToken {
+        token: ApiToken {
+            id,
+            name: name.to_string(),
+            prefix,
+            scopes,
+            created_at,
+            last_used_at: None,
+        },
+        secret,
+    })
+}
+
+/// List the tokens of an account, newest first.
+pub async fn list(account_id: &str) -> Result<Vec<ApiToken>> {
+    let rows: Vec<(String, String, String, String, i64, Option<i64>)> = sqlx::query_as(
+        "SELECT id, name, prefix, scopes, created_at, last_used_at
+         FROM api_tokens WHERE account_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(account_id)
+    .fetch_all(db::pool())
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, name, prefix, scopes, created_at, last_used_at)| ApiToken {
+                id,
+                name,
+                prefix,
+                scopes: parse_scopes(&scopes),
+                created_at,
+                last_used_at,
+            },
+        )
+        .collect())
+}
+
+/// Revoke a token of an account. Returns false if there was no such token.
+pub async fn revoke(account_id: &str, token_id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM api_tokens WHERE id = $1 AND account_id = $2")
+        .bind(token_id)
+        .bind(account_id)
+        .execute(db::pool())
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Account and scopes of a token, if valid. Records its use.
+pub async fn validate(token: &str) -> Result<Option<(String, Vec<Scope>)>> {
+    let row: Option<(String, String)> = sqlx::query_as(
+        "UPDATE api_tokens SET last_used_at = $1 WHERE token_hash = $2
+         RETURNING account_id, scopes",
+    )
+    .bind(chrono::Utc::now().timestamp_millis())
+    .bind(hash_token(token))
+    .fetch_optional(db::pool())
+    .await?;
+    Ok(row.map(|(account_id, scopes)| (account_id, parse_scopes(&scopes))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth;
+
+    #[test]
+    fn test_scopes() {
+        let scopes: Vec<Scope> = serde_json::from_str(r#"["read:results","create:race"]"#).unwrap();
+        assert_eq!(scopes, vec![Scope::ReadResults, Scope::CreateRace]);
+        assert_eq!(
+            parse_scopes("read:results unknown create:race"),
+            vec![Scope::ReadResults, Scope::CreateRace]
+        );
+        assert!(serde_json::from_str::<Vec<Scope>>(r#"["admin"]"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_token_lifecycle() {
+        db::init_test().await.unwrap();
+
+        let account_id = auth::get_or_create_account("bot@example.com")
+            .await
+            .unwrap();
+        assert!(create(&account_id, "Bot", &[]).await.is_err());
+
+        let created = create(&account_id, "Stats", &[Scope::ReadResults])
+            .await
+            .unwrap();
+        assert!(is_api_token(&created.secret));
+        assert!(created.secret.starts_with(&created.token.prefix));
+
+        let (owner, scopes) = validate(&created.secret).await.unwrap().unwrap();
+        assert_eq!(owner, account_id);
+        assert_eq!(scopes, vec![Scope::ReadResults]);
+
+        let listed = list(&account_id).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].last_used_at.is_some());
+
+        // Only the owner revokes it
+        assert!(!revoke("someone-else", &created.token.id).await.unwrap());
+        assert!(revoke(&account_id, &created.token.id).await.unwrap());
+        assert!(validate(&created.secret).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,226 @@
+//! Audit log of sensitive actions: staff actions on accounts, courses, results
+//! and races, and account security events (sign ins, sessions, tokens).
+//!
+//! The `audit_log` table is append-only, enforced by triggers.
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::db;
+
+/// What was done
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    SignIn,
+    SignOut,
+    SessionRevoke,
+    TokenCreate,
+    TokenRevoke,
+    AccountDeletionRequest,
+    AccountDeletionCancel,
+    AccountDelete,
+    RoleGrant,
+    RoleRevoke,
+    CourseCreate,
+    CourseUpdate,
+    CourseDelete,
+    CourseReorder,
+    CourseReload,
+    PolarFoiling,
+    ResultDelete,
+    RaceSchedule,
+    RaceDelete,
+    SeriesCreate,
+    SeriesDelete,
+    SeriesAddRace,
+    SeriesRemoveRace,
+    SeasonCreate,
+    SeasonDelete,
+    Announcement,
+    WebhookCreate,
+    WebhookDelete,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::SignIn => "auth.sign_in",
+            Action::SignOut => "auth.sign_out",
+            Action::SessionRevoke => "auth.session_revoke",
+            Action::TokenCreate => "token.create",
+            Action::TokenRevoke => "token.revoke",
+            Action::AccountDeletionRequest => "account.deletion_request",
+            Action::AccountDeletionCancel => "account.deletion_cancel",
+            Action::AccountDelete => "account.delete",
+            Action::RoleGrant => "role.grant",
+            Action::RoleRevoke => "role.revoke",
+            Action::CourseCreate => "course.create",
+            Action::CourseUpdate => "course.update",
+            Action::CourseDelete => "course.delete",
+            Action::CourseReorder => "course.reorder",
+            Action::CourseReload => "course.reload",
+            Action::PolarFoiling => "polar.foiling",
+            Action::ResultDelete => "result.delete",
+            Action::RaceSchedule => "race.schedule",
+            Action::RaceDelete => "race.delete",
+            Action::SeriesCreate => "series.create",
+            Action::SeriesDelete => "series.delete",
+            Action::SeriesAddRace => "series.add_race",
+            Action::SeriesRemoveRace => "series.remove_race",
+            Action::SeasonCreate => "season.create",
+            Action::SeasonDelete => "season.delete",
+            Action::Announcement => "email.announcement",
+            Action::WebhookCreate => "webhook.create",
+            Action::WebhookDelete => "webhook.delete",
+        }
+    }
+}
+
+/// A logged action
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub id: i64,
+    /// Account that acted, None for the server itself or the CLI
+    pub actor_id: Option<String>,
+    /// Email of the actor, None once the account is deleted
+    pub actor_email: Option<String>,
+    pub action: String,
+    /// What was acted upon: account, course key, result or race id...
+    pub target: String,
+    pub payload: Value,
+    pub created_at: i64,
+}
+
+/// Filters of the audit log, all optional
+#[derive(Debug, Default)]
+pub struct AuditFilter<'a> {
+    pub actor_id: Option<&'a str>,
+    pub action: Option<&'a str>,
+    pub target: Option<&'a str>,
+}
+
+/// Append an action to the audit log.
+pub async fn record(
+    actor_id: Option<&str>,
+    action: Action,
+    target: &str,
+    payload: Value,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO audit_log (actor_id, action, target, payload) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(actor_id)
+    .bind(action.as_str())
+    .bind(target)
+    .bind(payload.to_string())
+    .execute(db::pool())
+    .await?;
+    Ok(())
+}
+
+const FILTER_CLAUSE: &str = "WHERE ($1 IS NULL OR l.actor_id = $1)
+         AND ($2 IS NULL OR l.action = $2)
+         AND ($3 IS NULL OR l.target = $3)";
+
+type AuditRow = (
+    i64,
+    Option<String>,
+    Option<String>,
+    String,
+    String,
+    String,
+    i64,
+);
+
+/// Entries with pagination, newest first.
+pub async fn list(filter: &AuditFilter<'_>, limit: i64, offset: i64) -> Result<Vec<AuditEntry>> {
+    let sql = format!(
+        "SELECT l.id, l.actor_id, a.email, l.action, l.target, l.payload, l.created_at
+         FROM audit_log l LEFT JOIN accounts a ON a.id = l.actor_id
+         {}
+         ORDER BY l.id DESC
+         LIMIT $4 OFFSET $5",
+        FILTER_CLAUSE
+    );
+    let rows: Vec<AuditRow> = sqlx::query_as(&sql)
+        .bind(filter.actor_id)
+        .bind(filter.action)
+        .bind(filter.target)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db::pool())
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, actor_id, actor_email, action, target, payload, created_at)| AuditEntry {
+                id,
+                actor_id,
+                actor_email,
+                action,
+                target,
+                payload: serde_json::from_str(&payload).unwrap_or(Value::Null),
+                created_at,
+            },
+        )
+        .collect())
+}
+
+/// Count the entries matching a filter.
+pub async fn count(filter: &AuditFilter<'_>) -> Result<i64> {
+    let sql = format!("SELECT COUNT(*) FROM audit_log l {}", FILTER_CLAUSE);
+    let (count,): (i64,) = sqlx::query_as(&sql)
+        .bind(filter.actor_id)
+        .bind(filter.action)
+        .bind(filter.target)
+        .fetch_one(db::pool())
+        .await?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_append_only_log() {
+        db::init_test().await.unwrap();
+
+        record(
+            Some("audit-admin"),
+            Action::CourseDelete,
+            "audit-course",
+            json!({ "name": "Audit course" }),
+        )
+        .await
+        .unwrap();
+        record(None, Action::AccountDelete, "audit-account", Value::Null)
+            .await
+            .unwrap();
+
+        let filter = AuditFilter {
+            target: Some("audit-course"),
+            ..Default::default()
+        };
+        let entries = list(&filter, 10, 0).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].actor_id.as_deref(), Some("audit-admin"));
+        assert_eq!(entries[0].action, "course.delete");
+        assert_eq!(entries[0].payload["name"], "Audit course");
+        assert_eq!(count(&filter).await.unwrap(), 1);
+
+        // Entries can't be changed or removed
+        let update = sqlx::query("UPDATE audit_log SET target = 'x' WHERE target = 'audit-course'")
+            .execute(db::pool())
+            .await;
+        assert!(update.is_err());
+        let delete = sqlx::query("DELETE FROM audit_log WHERE target = 'audit-account'")
+            .execute(db::pool())
+            .await;
+        assert!(delete.is_err());
+    }
+}
@@ -1,13 +1,27 @@
 use anyhow::Result;
 use rand::Rng;
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-use crate::{config::config, db, email, profiles};
+use crate::{
+    avatars,
+    config::config,
+    db, email,
+    email_templates::Locale,
+    profiles,
+    roles::{self, Role},
+};
 
 const CODE_EXPIRATION_MS: i64 = 10 * 60 * 1000; // 10 minutes
 const SESSION_DURATION_MS: i64 = 30 * 24 * 60 * 60 * 1000; // 30 days
-const MAX_CODES_PER_WINDOW: i64 = 3; // Max verification codes per email per window
-const RATE_LIMIT_WINDOW_MS: i64 = 10 * 60 * 1000; // 10-minute sliding window
+const THROTTLE_WINDOW_MS: i64 = 60 * 60 * 1000; // 1-hour sliding window
+const MAX_CODES_PER_EMAIL: i64 = 5; // Max codes and links sent to an email per window
+const MAX_CODES_PER_IP: i64 = 20; // Max codes and links requested by an IP per window
+const MAX_ATTEMPTS_PER_CODE: i64 = 5; // Wrong guesses before a code is burned
+const MAX_FAILURES: i64 = 10; // Failed sign ins of an email or IP per window before a lockout
+const LOCKOUT_MS: i64 = 60 * 60 * 1000; // 1 hour
+const MAX_USER_AGENT_LEN: usize = 200; // Longer user agents are truncated
 
 /// Generate a random 6-digit verification code.
 fn generate_code() -> String {
@@ -15,52 +29,226 @@ fn generate_code() -> String {
     format!("{:06}", code)
 }
 
+/// Generate an unguessable magic link token.
+fn generate_magic_token() -> String {
+    let bytes: [u8; 32] = rand::rng().random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 /// Generate a secure session token.
 fn generate_session_token() -> String {
     uuid::Uuid::new_v4().to_string()
 }
 
-/// Start authentication by sending a verification code to the email.
-pub async fn start_auth(email_addr: &str) -> Result<()> {
+/// How a sign in is confirmed from the email
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    /// A 6-digit code typed in the client
+    #[default]
+    Code,
+    /// A one-click link to `/auth/magic`
+    Magic,
+}
+
+impl AuthMode {
+    /// `kind` of the verification_codes rows
+    fn kind(&self) -> &'static str {
+        match self {
+            AuthMode::Code => "code",
+            AuthMode::Magic => "magic",
+        }
+    }
+}
+
+/// Start authentication by sending a verification code to the email, in
+/// the language of the client. `ip` is the client address, throttled like
+/// the email.
+pub async fn start_auth(email_addr: &str, ip: Option<&str>, locale: Locale) -> Result<()> {
+    let email_addr = normalize_email(email_addr)?;
+    let now = chrono::Utc::now().timestamp_millis();
+    check_lockout(&throttle_keys(Some(&email_addr), ip), now).await?;
+    check_rate_limit(&email_addr, ip, now).await?;
+
+    let code = generate_code();
+    insert_code(&email_addr, AuthMode::Code, &code, ip, locale, now).await?;
+
+    // Queue the code for the email worker
+    email::send_verification_code(&email_addr, &code, locale, now + CODE_EXPIRATION_MS).await?;
+
+    Ok(())
+}
+
+/// Start authentication by sending a one-click sign in link to the email,
+/// landing on the client `redirect` path once signed in.
+pub async fn start_magic_link(
+    email_addr: &str,
+    redirect: Option<&str>,
+    ip: Option<&str>,
+    locale: Locale,
+) -> Result<()> {
+    let email_addr = normalize_email(email_addr)?;
+    let now = chrono::Utc::now().timestamp_millis();
+    check_lockout(&throttle_keys(Some(&email_addr), ip), now).await?;
+    check_rate_limit(&email_addr, ip, now).await?;
+
+    let token = generate_magic_token();
+    insert_code(&email_addr, AuthMode::Magic, &token, ip, locale, now).await?;
+
+    let url = Url::parse_with_params(
+        &format!("{}/auth/magic", config().public_url),
+        &[
+            ("token", token.as_str()),
+            ("redirect", safe_redirect(redirect)),
+        ],
+    )?;
+    email::send_magic_link(&email_addr, url.as_str(), locale, now + CODE_EXPIRATION_MS).await?;
+
+    Ok(())
+}
+
+/// Lowercased, trimmed email, if it looks like one.
+fn normalize_email(email_addr: &str) -> Result<String> {
     let email_addr = email_addr.to_lowercase().trim().to_string();
 
     // Validate email format (basic check)
     if !email_addr.contains('@') || !email_addr.contains('.') {
         anyhow::bail!("Invalid email format");
     }
+    Ok(email_addr)
+}
 
-    let now = chrono::Utc::now().timestamp_millis();
-    let window_start = now - RATE_LIMIT_WINDOW_MS;
+/// Client path to land on after a sign in: only paths of the client, so
+/// links can't send players to another site.
+pub fn safe_redirect(redirect: Option<&str>) -> &str {
+    match redirect {
+        Some(path) if path.starts_with('/') && !path.starts_with("//") && !path.contains('\\') => {
+            path
+        }
+        _ => "/",
+    }
+}
+
+async fn check_rate_limit(email_addr: &str, ip: Option<&str>, now: i64) -> Result<()> {
+    let window_start = now - THROTTLE_WINDOW_MS;
 
-    // Rate limit: max N codes per email per window
+    // Rate limit: max N codes per email per window, so nobody can spam an inbox
     let (recent_count,): (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM verification_codes WHERE email = ? AND created_at > ?",
+        "SELECT COUNT(*) FROM verification_codes WHERE email = $1 AND created_at > $2",
     )
-    .bind(&email_addr)
+    .bind(email_addr)
     .bind(window_start)
     .fetch_one(db::pool())
     .await?;
 
-    if recent_count >= MAX_CODES_PER_WINDOW {
+    if recent_count >= MAX_CODES_PER_EMAIL {
         anyhow::bail!("Too many attempts, please try again later");
     }
 
-    let code = generate_code();
+    // And per IP, so nobody can spray codes over many emails
+    if let Some(ip) = ip {
+        let (recent_count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM verification_codes WHERE ip = $1 AND created_at > $2",
+        )
+        .bind(ip)
+        .bind(window_start)
+        .fetch_one(db::pool())
+        .await?;
+
+        if recent_count >= MAX_CODES_PER_IP {
+            anyhow::bail!("Too many attempts, please try again later");
+        }
+    }
+    Ok(())
+}
+
+async fn insert_code(
+    email_addr: &str,
+    mode: AuthMode,
+    code: &str,
+    ip: Option<&str>,
+    locale: Locale,
+    now: i64,
+) -> Result<()> {
     let expires_at = now + CODE_EXPIRATION_MS;
 
-    // Insert verification code
+    // Insert verification code, with the language saved on the account at
+    // sign in
     sqlx::query(
-        "INSERT INTO verification_codes (email, code, expires_at) VALUES (?, ?, ?)",
+        "INSERT INTO verification_codes (email, code, kind, ip, language, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6)",
     )
-    .bind(&email_addr)
-    .bind(&code)
+    .bind(email_addr)
+    .bind(code)
+    .bind(mode.kind())
+    .bind(ip)
+    .bind(locale.as_str())
     .bind(expires_at)
     .execute(db::pool())
     .await?;
+    Ok(())
+}
+
+/// Throttled keys of a request: the email and the client IP, when known
+fn throttle_keys(email_addr: Option<&str>, ip: Option<&str>) -> Vec<String> {
+    email_addr
+        .map(|email_addr| format!("email:{}", email_addr))
+        .into_iter()
+        .chain(ip.map(|ip| format!("ip:{}", ip)))
+        .collect()
+}
+
+/// Refuse sign ins of locked out emails and IPs.
+async fn check_lockout(keys: &[String], now: i64) -> Result<()> {
+    for key in keys {
+        let locked: Option<(i64,)> = sqlx::query_as(
+            "SELECT locked_until FROM auth_lockouts WHERE key = $1 AND locked_until > $2",
+        )
+        .bind(key)
+        .bind(now)
+        .fetch_optional(db::pool())
+        .await?;
+
+        if let Some((locked_until,)) = locked {
+            let minutes = (locked_until - now + 59_999) / 60_000;
+            anyhow::bail!(
+                "Too many failed attempts, please try again in {} minutes",
+                minutes
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Count a failed sign in against its email and IP, locking out those with
+/// too many failures in the window.
+async fn record_failure(keys: &[String], now: i64) -> Result<()> {
+    for key in keys {
+        sqlx::query("INSERT INTO auth_failures (key, created_at) VALUES ($1, $2)")
+            .bind(key)
+            .bind(now)
+            .execute(db::pool())
+            .await?;
 
-    // Send the code via email
-    email::send_verification_code(&email_addr, &code).await?;
+        let (failures,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM auth_failures WHERE key = $1 AND created_at > $2")
+                .bind(key)
+                .bind(now - THROTTLE_WINDOW_MS)
+                .fetch_one(db::pool())
+                .await?;
 
+        if failures >= MAX_FAILURES {
+            sqlx::query(
+                "INSERT INTO auth_lockouts (key, locked_until) VALUES ($1, $2)
+                 ON CONFLICT(key) DO UPDATE SET locked_until = excluded.locked_until",
+            )
+            .bind(key)
+            .bind(now + LOCKOUT_MS)
+            .execute(db::pool())
+            .await?;
+            log::warn!("Locked out {} after {} failed sign ins", key, failures);
+        }
+    }
     Ok(())
 }
 
@@ -71,17 +259,26 @@ pub struct AuthResult {
     pub session_token: String,
     pub profiles: Vec<profiles::Profile>,
     pub is_admin: bool,
+    pub roles: Vec<Role>,
 }
 
 /// Verify a code and create a session. Creates the account if it doesn't exist.
-pub async fn verify_auth(email_addr: &str, code: &str) -> Result<AuthResult> {
+/// `user_agent` is the browser signing in, listed with the sessions.
+pub async fn verify_auth(
+    email_addr: &str,
+    code: &str,
+    ip: Option<&str>,
+    user_agent: Option<&str>,
+) -> Result<AuthResult> {
     let email_addr = email_addr.to_lowercase().trim().to_string();
     let now = chrono::Utc::now().timestamp_millis();
+    let keys = throttle_keys(Some(&email_addr), ip);
+    check_lockout(&keys, now).await?;
 
     // Find valid, unused code
-    let row: Option<(i64,)> = sqlx::query_as(
-        "SELECT id FROM verification_codes
-         WHERE email = ? AND code = ? AND expires_at > ? AND used_at IS NULL
+    let row: Option<(i64, Option<String>)> = sqlx::query_as(
+        "SELECT id, language FROM verification_codes
+         WHERE email = $1 AND code = $2 AND kind = 'code' AND expires_at > $3 AND used_at IS NULL
          ORDER BY created_at DESC LIMIT 1",
     )
     .bind(&email_addr)
@@ -90,27 +287,31 @@ pub async fn verify_auth(email_addr: &str, code: &str) -> Result<AuthResult> {
     .fetch_optional(db::pool())
     .await?;
 
-    let code_id = match row {
-        Some((id,)) => id,
+    let (code_id, language) = match row {
+        Some(row) => row,
         None => {
-            // Invalidate all pending codes for this email to prevent brute-force.
-            // Combined with the rate limit on start_auth, this caps total guesses.
+            // Count the guess against all pending codes for this email, burning
+            // those out of attempts. Combined with the rate limits on start_auth
+            // and the lockouts, this caps total guesses.
             sqlx::query(
-                "UPDATE verification_codes SET used_at = ?
-                 WHERE email = ? AND expires_at > ? AND used_at IS NULL",
+                "UPDATE verification_codes SET attempts = attempts + 1,
+                 used_at = CASE WHEN attempts + 1 >= $1 THEN $2 END
+                 WHERE email = $3 AND kind = 'code' AND expires_at > $4 AND used_at IS NULL",
             )
+            .bind(MAX_ATTEMPTS_PER_CODE)
             .bind(now)
             .bind(&email_addr)
             .bind(now)
             .execute(db::pool())
             .await?;
+            record_failure(&keys, now).await?;
 
             anyhow::bail!("Invalid or expired code");
         }
     };
 
     // Mark code as used
-    sqlx::query("UPDATE verification_codes SET used_at = ? WHERE id = ?")
+    sqlx::query("UPDATE verification_codes SET used_at = $1 WHERE id = $2")
         .bind(now)
         .bind(code_id)
         .execute(db::pool())
@@ -118,54 +319,116 @@ pub async fn verify_auth(email_addr: &str, code: &str) -> Result<AuthResult> {
 
     // Get or create account
     let account_id = get_or_create_account(&email_addr).await?;
+    set_language(&account_id, language.as_deref()).await?;
+
+    create_session(&account_id, user_agent).await
+}
+
+/// Sign in from a magic link. Links are single use and expire like codes.
+pub async fn verify_magic_link(
+    token: &str,
+    ip: Option<&str>,
+    user_agent: Option<&str>,
+) -> Result<AuthResult> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let keys = throttle_keys(None, ip);
+    check_lockout(&keys, now).await?;
+
+    let row: Option<(i64, String, Option<String>)> = sqlx::query_as(
+        "SELECT id, email, language FROM verification_codes
+         WHERE code = $1 AND kind = 'magic' AND expires_at > $2 AND used_at IS NULL",
+    )
+    .bind(token)
+    .bind(now)
+    .fetch_optional(db::pool())
+    .await?;
+    let Some((code_id, email_addr, language)) = row else {
+        record_failure(&keys, now).await?;
+        anyhow::bail!("This sign in link is invalid or has expired");
+    };
+
+    sqlx::query("UPDATE verification_codes SET used_at = $1 WHERE id = $2")
+        .bind(now)
+        .bind(code_id)
+        .execute(db::pool())
+        .await?;
+
+    let account_id = get_or_create_account(&email_addr).await?;
+    set_language(&account_id, language.as_deref()).await?;
+    create_session(&account_id, user_agent).await
+}
+
+/// Language of the emails of an account, kept when signing in without one
+async fn set_language(account_id: &str, language: Option<&str>) -> Result<()> {
+    if language.is_some() {
+        sqlx::query("UPDATE accounts SET language = $1 WHERE id = $2")
+            .bind(language)
+            .bind(account_id)
+            .execute(db::pool())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Client page a redirected sign in (magic link, OAuth) lands on: the client
+/// `path` with the session token or the error in the URL fragment (never
+/// sent to servers), encoded like a query string.
+pub fn landing_url(path: &str, outcome: &Result<AuthResult>) -> Result<String> {
+    let (key, value) = match outcome {
+        Ok(auth) => ("session", auth.session_token.clone()),
+        Err(e) => ("authError", e.to_string()),
+    };
+    let encoded = Url::parse_with_params("http://localhost", &[(key, value)])?;
+    let mut url = Url::parse(&config().client_url)?.join(safe_redirect(Some(path)))?;
+    url.set_fragment(encoded.query());
+    Ok(url.to_string())
+}
+
+/// Sign in to an account: create a session and return it with the profiles.
+pub async fn create_session(account_id: &str, user_agent: Option<&str>) -> Result<AuthResult> {
+    let now = chrono::Utc::now().timestamp_millis();
 
     // Create session
     let session_token = generate_session_token();
     let expires_at = now + SESSION_DURATION_MS;
+    let user_agent = user_agent.map(|ua| ua.chars().take(MAX_USER_AGENT_LEN).collect::<String>());
 
     sqlx::query(
-        "INSERT INTO sessions (token, account_id, expires_at, last_active_at) VALUES (?, ?, ?, ?)",
+        "INSERT INTO sessions (token, id, account_id, user_agent, expires_at, last_active_at)
+         VALUES ($1, $2, $3, $4, $5, $6)",
     )
     .bind(&session_token)
-    .bind(&account_id)
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(account_id)
+    .bind(user_agent)
     .bind(expires_at)
     .bind(now)
     .execute(db::pool())
     .await?;
 
     // Get profiles
-    let profiles = get_profiles_for_account(&account_id).await?;
+    let profiles = get_profiles_for_account(account_id).await?;
 
-    // Check if this is an admin account
-    let admin_email = &config().admin_email;
-    let is_admin =
-        !admin_email.is_empty() && email_addr.to_lowercase() == admin_email.to_lowercase();
+    let roles = roles::roles_of(account_id).await?;
 
     Ok(AuthResult {
-        account_id,
+        account_id: account_id.to_string(),
         session_token,
         profiles,
-        is_admin,
+        is_admin: roles.contains(&Role::Admin),
+        roles,
     })
 }
 
 /// Get or create an account for the given email.
-async fn get_or_create_account(email: &str) -> Result<String> {
-    // Check if account exists
-    let existing: Option<(String,)> = sqlx::query_as(
-        "SELECT id FROM accounts WHERE email = ?",
-    )
-    .bind(email)
-    .fetch_optional(db::pool())
-    .await?;
-
-    if let Some((id,)) = existing {
+pub async fn get_or_create_account(email: &str) -> Result<String> {
+    if let Some(id) = find_account_by_email(email).await? {
         return Ok(id);
     }
 
     // Create new account
     let account_id = uuid::Uuid::new_v4().to_string();
-    sqlx::query("INSERT INTO accounts (id, email) VALUES (?, ?)")
+    sqlx::query("INSERT INTO accounts (id, email) VALUES ($1, $2)")
         .bind(&account_id)
         .bind(email)
         .execute(db::pool())
@@ -174,7 +437,7 @@ async fn get_or_create_account(email: &str) -> Result<String> {
     // Create a default profile
     let profile_id = uuid::Uuid::new_v4().to_string();
     let default_name = email.split('@').next().unwrap_or("Player");
-    sqlx::query("INSERT INTO profiles (id, account_id, name) VALUES (?, ?, ?)")
+    sqlx::query("INSERT INTO profiles (id, account_id, name) VALUES ($1, $2, $3)")
         .bind(&profile_id)
         .bind(&account_id)
         .bind(default_name)
@@ -185,6 +448,16 @@ async fn get_or_create_account(email: &str) -> Result<String> {
     Ok(account_id)
 }
 
+/// Get the account of an email, if any.
+pub async fn find_account_by_email(email: &str) -> Result<Option<String>> {
+    let row: Option<(String,)> = sqlx::query_as("SELECT id FROM accounts WHERE email = $1")
+        .bind(email)
+        .fetch_optional(db::pool())
+        .await?;
+
+    Ok(row.map(|(id,)| id))
+}
+
 /// Get all profiles for an account.
 async fn get_profiles_for_account(account_id: &str) -> Result<Vec<profiles::Profile>> {
     profiles::list_profiles(account_id).await
@@ -193,7 +466,7 @@ async fn get_profiles_for_account(account_id: &str) -> Result<Vec<profiles::Prof
 /// Get the email address for an account.
 pub async fn get_account_email(account_id: &str) -> Result<Option<String>> {
     let row: Option<(String,)> = sqlx::query_as(
-        "SELECT email FROM accounts WHERE id = ?",
+        "SELECT email FROM accounts WHERE id = $1",
     )
     .bind(account_id)
     .fetch_optional(db::pool())
@@ -209,7 +482,7 @@ pub async fn validate_session(token: &str) -> Result<Option<String>> {
 
     // Get session if valid
     let row: Option<(String,)> = sqlx::query_as(
-        "SELECT account_id FROM sessions WHERE token = ? AND expires_at > ?",
+        "SELECT account_id FROM sessions WHERE token = $1 AND expires_at > $2",
     )
     .bind(token)
     .bind(now)
@@ -219,7 +492,7 @@ pub async fn validate_session(token: &str) -> Result<Option<String>> {
     if let Some((account_id,)) = row {
         // Update last_active_at and extend expiration
         let new_expires_at = now + SESSION_DURATION_MS;
-        sqlx::query("UPDATE sessions SET last_active_at = ?, expires_at = ? WHERE token = ?")
+        sqlx::query("UPDATE sessions SET last_active_at = $1, expires_at = $2 WHERE token = $3")
             .bind(now)
             .bind(new_expires_at)
             .bind(token)
@@ -232,9 +505,59 @@ pub async fn validate_session(token: &str) -> Result<Option<String>> {
     Ok(None)
 }
 
+/// A signed in browser, as listed to the account owner
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub created_at: i64,
+    pub last_active_at: i64,
+    /// The session of the request listing them
+    pub current: bool,
+}
+
+/// Active sessions of an account, most recently active first.
+pub async fn list_sessions(account_id: &str, current_token: &str) -> Result<Vec<SessionInfo>> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let rows: Vec<(String, String, Option<String>, i64, i64)> = sqlx::query_as(
+        "SELECT token, id, user_agent, created_at, last_active_at FROM sessions
+         WHERE account_id = $1 AND expires_at > $2
+         ORDER BY last_active_at DESC",
+    )
+    .bind(account_id)
+    .bind(now)
+    .fetch_all(db::pool())
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(token, id, user_agent, created_at, last_active_at)| SessionInfo {
+                id,
+                user_agent,
+                created_at,
+                last_active_at,
+                current: token == current_token,
+            },
+        )
+        .collect())
+}
+
+/// Revoke a session of an account, signing that browser out.
+/// Returns false if there was no such session.
+pub async fn revoke_session(account_id: &str, session_id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM sessions WHERE id = $1 AND account_id = $2")
+        .bind(session_id)
+        .bind(account_id)
+        .execute(db::pool())
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
 /// Logout by deleting the session.
 pub async fn logout(token: &str) -> Result<()> {
-    sqlx::query("DELETE FROM sessions WHERE token = ?")
+    sqlx::query("DELETE FROM sessions WHERE token = $1")
         .bind(token)
         .execute(db::pool())
         .await?;
@@ -245,18 +568,33 @@ pub async fn logout(token: &str) -> Result<()> {
 pub async fn cleanup_expired() -> Result<()> {
     let now = chrono::Utc::now().timestamp_millis();
 
-    let sessions_deleted = sqlx::query("DELETE FROM sessions WHERE expires_at < ?")
+    let sessions_deleted = sqlx::query("DELETE FROM sessions WHERE expires_at < $1")
         .bind(now)
         .execute(db::pool())
         .await?
         .rows_affected();
 
-    let codes_deleted = sqlx::query("DELETE FROM verification_codes WHERE expires_at < ?")
+    let codes_deleted = sqlx::query("DELETE FROM verification_codes WHERE expires_at < $1")
         .bind(now)
         .execute(db::pool())
         .await?
         .rows_affected();
 
+    sqlx::query("DELETE FROM oauth_states WHERE expires_at < $1")
+        .bind(now)
+        .execute(db::pool())
+        .await?;
+
+    sqlx::query("DELETE FROM auth_failures WHERE created_at < $1")
+        .bind(now - THROTTLE_WINDOW_MS)
+        .execute(db::pool())
+        .await?;
+
+    sqlx::query("DELETE FROM auth_lockouts WHERE locked_until < $1")
+        .bind(now)
+        .execute(db::pool())
+        .await?;
+
     if sessions_deleted > 0 || codes_deleted > 0 {
         log::info!(
             "Cleaned up {} expired sessions and {} expired codes",
@@ -278,6 +616,7 @@ pub struct AdminAccount {
     pub created_at: i64,
     pub profile_count: i64,
     pub session_count: i64,
+    pub roles: Vec<Role>,
 }
 
 /// List accounts with pagination, ordered by creation date descending.
@@ -288,22 +627,37 @@ pub async fn list_accounts(limit: i64, offset: i64) -> Result<Vec<AdminAccount>>
                 (SELECT COUNT(*) FROM sessions WHERE account_id = a.id) as session_count
          FROM accounts a
          ORDER BY a.created_at DESC
-         LIMIT ? OFFSET ?",
+         LIMIT $1 OFFSET $2",
     )
     .bind(limit)
     .bind(offset)
     .fetch_all(db::pool())
     .await?;
 
+    // Grouped here, string aggregates differing between SQLite and Postgres
+    let grants: Vec<(String, String)> =
+        sqlx::query_as("SELECT account_id, role FROM roles ORDER BY role")
+            .fetch_all(db::pool())
+            .await?;
+    let mut roles: HashMap<String, Vec<Role>> = HashMap::new();
+    for (account_id, role) in grants {
+        if let Some(role) = Role::parse(&role) {
+            roles.entry(account_id).or_default().push(role);
+        }
+    }
+
     Ok(rows
         .into_iter()
-        .map(|(id, email, created_at, profile_count, session_count)| AdminAccount {
-            id,
-            email,
-            created_at,
-            profile_count,
-            session_count,
-        })
+        .map(
+            |(id, email, created_at, profile_count, session_count)| AdminAccount {
+                roles: roles.remove(&id).unwrap_or_default(),
+                id,
+                email,
+                created_at,
+                profile_count,
+                session_count,
+            },
+        )
         .collect())
 }
 
@@ -318,15 +672,30 @@ pub async fn count_accounts() -> Result<i64> {
 /// Delete an account (CASCADE handles profiles + sessions).
 pub async fn delete_account(account_id: &str) -> Result<()> {
     // Delete sessions and profiles first (SQLite doesn't always cascade)
-    sqlx::query("DELETE FROM sessions WHERE account_id = ?")
+    sqlx::query("DELETE FROM sessions WHERE account_id = $1")
         .bind(account_id)
         .execute(db::pool())
         .await?;
-    sqlx::query("DELETE FROM profiles WHERE account_id = ?")
+    sqlx::query("DELETE FROM account_identities WHERE account_id = $1")
         .bind(account_id)
         .execute(db::pool())
         .await?;
-    sqlx::query("DELETE FROM accounts WHERE id = ?")
+    sqlx::query("DELETE FROM api_tokens WHERE account_id = $1")
+        .bind(account_id)
+        .execute(db::pool())
+        .await?;
+    sqlx::query("DELETE FROM roles WHERE account_id = $1")
+        .bind(account_id)
+        .execute(db::pool())
+        .await?;
+    for profile in profiles::list_profiles(account_id).await? {
+        avatars::delete_for_profile(&profile.id).await?;
+    }
+    sqlx::query("DELETE FROM profiles WHERE account_id = $1")
+        .bind(account_id)
+        .execute(db::pool())
+        .await?;
+    sqlx::query("DELETE FROM accounts WHERE id = $1")
         .bind(account_id)
         .execute(db::pool())
         .await?;
@@ -337,6 +706,18 @@ pub async fn delete_account(account_id: &str) -> Result<()> {
 #[serde(rename_all = "camelCase")]
 pub struct StartAuthRequest {
     pub email: String,
+    #[serde(default)]
+    pub mode: AuthMode,
+    /// Client path a magic link lands on
+    pub redirect: Option<String>,
+    /// Language of the client, for the email and the later ones
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MagicLinkQuery {
+    pub token: String,
+    pub redirect: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -349,6 +730,7 @@ pub struct VerifyAuthRequest {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use anyhow::anyhow;
 
     #[tokio::test]
     async fn test_generate_code() {
@@ -363,11 +745,11 @@ mod tests {
 
         // Start auth
         let email = "test@example.com";
-        start_auth(email).await.unwrap();
+        start_auth(email, None, Locale::En).await.unwrap();
 
         // Get the code from the database directly for testing
         let (code,): (String,) = sqlx::query_as(
-            "SELECT code FROM verification_codes WHERE email = ? ORDER BY created_at DESC LIMIT 1",
+            "SELECT code FROM verification_codes WHERE email = $1 ORDER BY created_at DESC LIMIT 1",
         )
         .bind(email)
         .fetch_one(db::pool())
@@ -375,7 +757,7 @@ mod tests {
         .unwrap();
 
         // Verify with correct code
-        let result = verify_auth(email, &code).await.unwrap();
+        let result = verify_auth(email, &code, None, None).await.unwrap();
         assert!(!result.account_id.is_empty());
         assert!(!result.session_token.is_empty());
         assert_eq!(result.profiles.len(), 1); // Default profile created
@@ -397,10 +779,10 @@ mod tests {
         db::init_test().await.unwrap();
 
         let email = "test2@example.com";
-        start_auth(email).await.unwrap();
+        start_auth(email, None, Locale::En).await.unwrap();
 
         // Try with wrong code
-        let result = verify_auth(email, "000000").await;
+        let result = verify_auth(email, "000000", None, None).await;
         assert!(result.is_err());
     }
 
@@ -410,13 +792,13 @@ mod tests {
 
         let email = "ratelimit@example.com";
 
-        // First MAX_CODES_PER_WINDOW requests should succeed
-        for _ in 0..MAX_CODES_PER_WINDOW {
-            start_auth(email).await.unwrap();
+        // First MAX_CODES_PER_EMAIL requests should succeed
+        for _ in 0..MAX_CODES_PER_EMAIL {
+            start_auth(email, None, Locale::En).await.unwrap();
         }
 
         // Next request should be rate-limited
-        let result = start_auth(email).await;
+        let result = start_auth(email, None, Locale::En).await;
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
@@ -425,27 +807,195 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_failed_verify_invalidates_pending_codes() {
+    async fn test_rate_limit_per_ip() {
         db::init_test().await.unwrap();
 
-        let email = "invalidate@example.com";
-        start_auth(email).await.unwrap();
+        let ip = Some("192.0.2.1");
+        for i in 0..MAX_CODES_PER_IP {
+            start_auth(&format!("spray{}@example.com", i), ip, Locale::En)
+                .await
+                .unwrap();
+        }
+
+        // Another email from the same IP is rate-limited, not from elsewhere
+        assert!(start_auth("spray@example.com", ip, Locale::En).await.is_err());
+        let elsewhere = start_auth("spray@example.com", Some("192.0.2.2"), Locale::En).await;
+        assert!(elsewhere.is_ok());
+    }
 
-        // Get the real code
-        let (real_code,): (String,) = sqlx::query_as(
-            "SELECT code FROM verification_codes WHERE email = ? ORDER BY created_at DESC LIMIT 1",
+    async fn latest_code(email: &str) -> String {
+        let (code,): (String,) = sqlx::query_as(
+            "SELECT code FROM verification_codes WHERE email = $1 ORDER BY created_at DESC LIMIT 1",
         )
         .bind(email)
         .fetch_one(db::pool())
         .await
         .unwrap();
+        code
+    }
 
-        // Submit a wrong code — this should invalidate all pending codes
-        let result = verify_auth(email, "000000").await;
-        assert!(result.is_err());
+    #[tokio::test]
+    async fn test_failed_verify_burns_code_after_max_attempts() {
+        db::init_test().await.unwrap();
+
+        // A typo doesn't burn the code
+        let email = "typo@example.com";
+        start_auth(email, None, Locale::En).await.unwrap();
+        assert!(verify_auth(email, "000000", None, None).await.is_err());
+        let code = latest_code(email).await;
+        assert!(verify_auth(email, &code, None, None).await.is_ok());
+
+        let email = "invalidate@example.com";
+        start_auth(email, None, Locale::En).await.unwrap();
+        let real_code = latest_code(email).await;
+
+        // Submit MAX_ATTEMPTS_PER_CODE wrong codes, burning the pending code
+        for _ in 0..MAX_ATTEMPTS_PER_CODE {
+            let result = verify_auth(email, "000000", None, None).await;
+            assert!(result.is_err());
+        }
 
-        // Now the real code should also be rejected (it was invalidated)
-        let result = verify_auth(email, &real_code).await;
+        // Now the real code should also be rejected (it was burned)
+        let result = verify_auth(email, &real_code, None, None).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_lockout_after_max_failures() {
+        db::init_test().await.unwrap();
+
+        let email = "lockout@example.com";
+        let ip = Some("198.51.100.1");
+        for _ in 0..MAX_FAILURES {
+            assert!(verify_auth(email, "000000", ip, None).await.is_err());
+        }
+
+        // Both the email and the IP are locked out
+        let error = start_auth(email, Some("198.51.100.2"), Locale::En).await.unwrap_err();
+        assert!(error.to_string().contains("Too many failed attempts"));
+        let error = start_auth("other-lockout@example.com", ip, Locale::En)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Too many failed attempts"));
+        assert!(verify_magic_link("unknown", ip, None).await.is_err());
+
+        // Until the lockout ends
+        let later = chrono::Utc::now().timestamp_millis() + LOCKOUT_MS + 1;
+        let keys = throttle_keys(Some(email), ip);
+        assert!(check_lockout(&keys, later).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_magic_link() {
+        db::init_test().await.unwrap();
+
+        let email = "magic@example.com";
+        start_magic_link(email, Some("/daily"), None, Locale::Fr).await.unwrap();
+
+        let (token,): (String,) = sqlx::query_as(
+            "SELECT code FROM verification_codes WHERE email = $1 AND kind = 'magic'",
+        )
+        .bind(email)
+        .fetch_one(db::pool())
+        .await
+        .unwrap();
+        assert_eq!(token.len(), 64);
+
+        // Not a code, and wrong codes don't burn the link
+        assert!(verify_auth(email, &token, None, None).await.is_err());
+
+        let result = verify_magic_link(&token, None, None).await.unwrap();
+        let account_id = validate_session(&result.session_token).await.unwrap();
+        assert_eq!(account_id.as_ref(), Some(&result.account_id));
+        let (language,): (Option<String>,) =
+            sqlx::query_as("SELECT language FROM accounts WHERE id = $1")
+                .bind(&result.account_id)
+                .fetch_one(db::pool())
+                .await
+                .unwrap();
+        assert_eq!(language.as_deref(), Some("fr"));
+
+        // Single use
+        assert!(verify_magic_link(&token, None, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        db::init_test().await.unwrap();
+
+        let account_id = get_or_create_account("sessions@example.com").await.unwrap();
+        let laptop = create_session(&account_id, Some("Firefox")).await.unwrap();
+        let phone = create_session(&account_id, None).await.unwrap();
+
+        let sessions = list_sessions(&account_id, &laptop.session_token)
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 2);
+        let current = sessions.iter().find(|s| s.current).unwrap();
+        assert_eq!(current.user_agent.as_deref(), Some("Firefox"));
+
+        // Revoking the other session signs the phone out
+        let other = sessions.iter().find(|s| !s.current).unwrap();
+        assert!(!revoke_session("someone-else", &other.id).await.unwrap());
+        assert!(revoke_session(&account_id, &other.id).await.unwrap());
+        assert_eq!(validate_session(&phone.session_token).await.unwrap(), None);
+        let signed_in = validate_session(&laptop.session_token).await.unwrap();
+        assert_eq!(signed_in, Some(account_id));
+    }
+
+    /// Runs on Postgres too with `REWIND_TEST_DATABASE_URL`
+    #[tokio::test]
+    async fn test_list_accounts() {
+        db::init_test().await.unwrap();
+
+        let staff = get_or_create_account("staff-list@example.com")
+            .await
+            .unwrap();
+        let player = get_or_create_account("player-list@example.com")
+            .await
+            .unwrap();
+        crate::roles::grant(&staff, Role::Moderator).await.unwrap();
+        crate::roles::grant(&staff, Role::CourseEditor)
+            .await
+            .unwrap();
+        create_session(&staff, None).await.unwrap();
+
+        let accounts = list_accounts(i64::MAX, 0).await.unwrap();
+        let listed = |id: &str| accounts.iter().find(|a| a.id == id).unwrap();
+        let mut roles = listed(&staff).roles.clone();
+        roles.sort_by_key(|role| role.as_str());
+        assert_eq!(roles, vec![Role::CourseEditor, Role::Moderator]);
+        assert_eq!(listed(&staff).session_count, 1);
+        assert!(listed(&player).roles.is_empty());
+    }
+
+    #[test]
+    fn test_safe_redirect() {
+        assert_eq!(safe_redirect(Some("/daily?x=1")), "/daily?x=1");
+        assert_eq!(safe_redirect(Some("https://evil.example")), "/");
+        assert_eq!(safe_redirect(Some("//evil.example")), "/");
+        assert_eq!(safe_redirect(Some("/\\evil.example")), "/");
+        assert_eq!(safe_redirect(None), "/");
+    }
+
+    #[test]
+    fn test_landing_url() {
+        let failed = landing_url("/", &Err(anyhow!("Sign in cancelled (access_denied)"))).unwrap();
+        assert_eq!(
+            failed,
+            "http://localhost:3000/#authError=Sign+in+cancelled+%28access_denied%29"
+        );
+
+        let signed_in = Ok(AuthResult {
+            account_id: "account".to_string(),
+            session_token: "token".to_string(),
+            profiles: vec![],
+            is_admin: false,
+            roles: vec![],
+        });
+        assert_eq!(
+            landing_url("/daily", &signed_in).unwrap(),
+            "http://localhost:3000/daily#session=token"
+        );
+    }
 }
@@ -0,0 +1,292 @@
+//! Profile avatars: uploaded PNGs cropped square and resized, stored in the
+//! avatars bucket under `{profile_id}/{version}/{size}.png`.
+//!
+//! The version is the upload time, so a new avatar gets new URLs. Avatars of
+//! players in races are cached in memory, warmed when they join.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{LazyLock, RwLock};
+
+use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use object_store::ObjectStoreExt;
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+
+use crate::{config::config, db, s3};
+
+/// Side of the stored avatars, in pixels: profile and list sizes
+pub const SIZES: [u32; 2] = [256, 64];
+
+/// Size shown in race lobbies and leaderboards
+pub const SMALL: u32 = 64;
+
+/// Largest accepted upload
+pub const MAX_UPLOAD_BYTES: usize = 2 * 1024 * 1024;
+
+/// Largest accepted side of an uploaded image
+const MAX_SIDE: u32 = 4096;
+
+/// Small avatar URL by profile id, for players in races
+static RACER_AVATARS: LazyLock<RwLock<HashMap<String, Option<String>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Public URL of an avatar
+pub fn url(profile_id: &str, version: i64, size: u32) -> String {
+    config().s3.avatars_url(&key(profile_id, version, size))
+}
+
+fn key(profile_id: &str, version: i64, size: u32) -> String {
+    format!("{}/{}/{}.png", profile_id, version, size)
+}
+
+/// Decode a PNG to RGBA pixels
+fn decode_rgba(png_data: &[u8]) -> Result<(u32, u32, Vec<u8>)> {
+    let mut decoder = Decoder::new(Cursor::new(png_data));
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let (width, height) = (reader.info().width, reader.info().height);
+    if width > MAX_SIDE || height > MAX_SIDE {
+        anyhow::bail!(
+            "Avatar images cannot exceed {}x{} pixels",
+            MAX_SIDE,
+            MAX_SIDE
+        );
+    }
+    let mut data = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    data.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        ColorType::Rgba => data,
+        ColorType::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        ColorType::Grayscale => data.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        ColorType::Indexed => return Err(anyhow!("Unsupported avatar color type")),
+    };
+    Ok((width, height, rgba))
+}
+
+/// Crop the centered square of an RGBA image and shrink it to `size`,
+/// averaging the source pixels under each target pixel.
+fn square_resize(width: u32, height: u32, rgba: &[u8], size: u32) -> Vec<u8> {
+    let side = width.min(height) as f64;
+    let (left, top) = ((width as f64 - side) / 2.0, (height as f64 - side) / 2.0);
+    let scale = side / size as f64;
+    let mut out = Vec::with_capacity((size * size * 4) as usize);
+
+    for y in 0..size {
+        let y0 = (top + y as f64 * scale) as u32;
+        let y1 = ((top + (y + 1) as f64 * scale) as u32).clamp(y0 + 1, height);
+        for x in 0..size {
+            let x0 = (left + x as f64 * scale) as u32;
+            let x1 = ((left + (x + 1) as f64 * scale) as u32).clamp(x0 + 1, width);
+            let mut sum = [0u64; 4];
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    let i = ((sy * width + sx) * 4) as usize;
+                    for (c, total) in sum.iter_mut().enumerate() {
+                        *total += rgba[i + c] as u64;
+                    }
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u64;
+            out.extend(sum.map(|total| (total / count) as u8));
+        }
+    }
+    out
+}
+
+fn encode_png(rgba: &[u8], size: u32) -> Result<Bytes> {
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buffer, size, size);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(rgba)?;
+    }
+
+    Ok(Bytes::from(buffer))
+}
+
+/// Every stored size of an uploaded PNG
+fn resize_all(png_data: &[u8]) -> Result<Vec<(u32, Bytes)>> {
+    let (width, height, rgba) = decode_rgba(png_data)?;
+    SIZES
+        .iter()
+        .map(|&size| {
+            Ok((
+                size,
+                encode_png(&square_resize(width, height, &rgba, size), size)?,
+            ))
+        })
+        .collect()
+}
+
+async fn delete_files(profile_id: &str, version: i64) {
+    for size in SIZES {
+        let key = key(profile_id, version, size);
+        if let Err(e) = s3::avatars_client().delete(&key.as_str().into()).await {
+            log::warn!("Failed to delete avatar {}: {}", key, e);
+        }
+    }
+}
+
+/// Current avatar version of a profile of an account, or an error if the
+/// account has no such profile
+async fn owned_version(account_id: &str, profile_id: &str) -> Result<Option<i64>> {
+    let row: Option<(Option<i64>,)> =
+        sqlx::query_as("SELECT avatar_version FROM profiles WHERE id = $1 AND account_id = $2")
+            .bind(profile_id)
+            .bind(account_id)
+            .fetch_optional(db::pool())
+            .await?;
+    row.map(|(version,)| version)
+        .ok_or_else(|| anyhow!("Profile not found"))
+}
+
+/// Store a new avatar for a profile of an account. Returns its URL.
+pub async fn upload(account_id: &str, profile_id: &str, png_data: Bytes) -> Result<String> {
+    if png_data.len() > MAX_UPLOAD_BYTES {
+        anyhow::bail!("Avatar images cannot exceed 2 MB");
+    }
+    let previous = owned_version(account_id, profile_id).await?;
+
+    let sizes = tokio::task::spawn_blocking(move || resize_all(&png_data))
+        .await?
+        .map_err(|e| anyhow!("Invalid PNG image: {}", e))?;
+    let version = chrono::Utc::now().timestamp_millis();
+    for (size, png) in sizes {
+        s3::put_immutable(
+            s3::avatars_client(),
+            &key(profile_id, version, size),
+            png.into(),
+        )
+        .await?;
+    }
+
+    sqlx::query("UPDATE profiles SET avatar_version = $1 WHERE id = $2")
+        .bind(version)
+        .bind(profile_id)
+        .execute(db::pool())
+        .await?;
+    if let Some(previous) = previous {
+        delete_files(profile_id, previous).await;
+    }
+    remember(profile_id, Some(url(profile_id, version, SMALL)));
+
+    log::info!("Uploaded avatar of profile {}", profile_id);
+    Ok(url(profile_id, version, SIZES[0]))
+}
+
+/// Remove the avatar of a profile of an account
+pub async fn remove(account_id: &str, profile_id: &str) -> Result<()> {
+    if let Some(version) = owned_version(account_id, profile_id).await? {
+        sqlx::query("UPDATE profiles SET avatar_version = NULL WHERE id = $1")
+            .bind(profile_id)
+            .execute(db::pool())
+            .await?;
+        delete_files(profile_id, version).await;
+    }
+    remember(profile_id, None);
+    Ok(())
+}
+
+/// Delete the stored avatar files of a profile being deleted
+pub async fn delete_for_profile(profile_id: &str) -> Result<()> {
+    let row: Option<(Option<i64>,)> =
+        sqlx::query_as("SELECT avatar_version FROM profiles WHERE id = $1")
+            .bind(profile_id)
+            .fetch_optional(db::pool())
+            .await?;
+    if let Some((Some(version),)) = row {
+        delete_files(profile_id, version).await;
+    }
+    remember(profile_id, None);
+    Ok(())
+}
+
+fn remember(profile_id: &str, small_url: Option<String>) {
+    RACER_AVATARS
+        .write()
+        .unwrap()
+        .insert(profile_id.to_string(), small_url);
+}
+
+/// Look up the avatar of a player joining a race, for `racer_url`
+pub async fn load_racer(profile_id: &str) {
+    let row: Option<(Option<i64>,)> =
+        match sqlx::query_as("SELECT avatar_version FROM profiles WHERE id = $1")
+            .bind(profile_id)
+            .fetch_optional(db::pool())
+            .await
+        {
+            Ok(row) => row,
+            Err(e) => {
+                log::warn!("Failed to load avatar of {}: {}", profile_id, e);
+                return;
+            }
+        };
+    let version = row.and_then(|(version,)| version);
+    remember(profile_id, version.map(|v| url(profile_id, v, SMALL)));
+}
+
+/// Small avatar of a player in a race, None when unknown or without one
+pub fn racer_url(profile_id: &str) -> Option<String> {
+    RACER_AVATARS
+        .read()
+        .unwrap()
+        .get(profile_id)
+        .cloned()
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_resize() {
+        // 4x2 image: left half black, right half white
+        let rgba: Vec<u8> = (0..8)
+            .flat_map(|i| {
+                let v = if i % 4 < 2 { 0 } else { 255 };
+                [v, v, v, 255]
+            })
+            .collect();
+
+        // The centered 2x2 square is half black, half white
+        let out = square_resize(4, 2, &rgba, 1);
+        assert_eq!(out, vec![127, 127, 127, 255]);
+        let out = square_resize(4, 2, &rgba, 2);
+        assert_eq!(out[0..4], [0, 0, 0, 255]);
+        assert_eq!(out[4..8], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_resize_all() {
+        let png = encode_png(&[200, 10, 10, 255].repeat(300 * 300), 300).unwrap();
+        let sizes = resize_all(&png).unwrap();
+        assert_eq!(sizes.len(), SIZES.len());
+        let (width, height, rgba) = decode_rgba(&sizes[1].1).unwrap();
+        assert_eq!((width, height), (SMALL, SMALL));
+        assert_eq!(rgba[0..4], [200, 10, 10, 255]);
+        assert!(resize_all(b"not a png").is_err());
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            url("profile", 42, SMALL),
+            "http://localhost:9000/avatars/profile/42/64.png"
+        );
+    }
+}
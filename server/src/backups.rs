@@ -0,0 +1,178 @@
+//! Snapshots of the SQLite database, taken with `rewind db backup` before
+//! imports or deploys and put back with `rewind db restore`. Snapshots are
+//! consistent copies taken while the server runs (`VACUUM INTO`), kept as
+//! files and optionally in the `REWIND_S3_BACKUPS_BUCKET` bucket. Postgres
+//! databases are backed up with `pg_dump`.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+use futures::StreamExt;
+use object_store::ObjectStoreExt;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::config::config;
+use crate::s3;
+use crate::s3_multipart::S3MultipartUploader;
+
+/// Snapshot the database to `file`, and upload it to the backups bucket
+/// under its file name when `upload` is set
+pub async fn backup(file: PathBuf, upload: bool) -> Result<()> {
+    snapshot(&sqlite_options(&config().database_url)?, &file).await?;
+    log::info!("Database saved to {}", file.display());
+
+    if upload {
+        let key = object_key(&file)?;
+        upload_file(&file, &key).await?;
+        log::info!("Uploaded {} to the backups bucket", key);
+    }
+    Ok(())
+}
+
+/// Replace the database by the snapshot `file`, first downloaded from the
+/// backups bucket when `download` is set. The server must be stopped.
+pub async fn restore(file: PathBuf, download: bool) -> Result<()> {
+    let options = sqlite_options(&config().database_url)?;
+
+    if download {
+        let key = object_key(&file)?;
+        download_file(&key, &file).await?;
+        log::info!("Downloaded {} from the backups bucket", key);
+    }
+
+    put_back(&file, options.get_filename()).await?;
+    log::info!("Database restored from {}", file.display());
+    Ok(())
+}
+
+fn sqlite_options(url: &str) -> Result<SqliteConnectOptions> {
+    if url.starts_with("postgres:") || url.starts_with("postgresql:") {
+        bail!("Postgres databases are backed up with pg_dump and pg_restore");
+    }
+    Ok(SqliteConnectOptions::from_str(url)?)
+}
+
+fn object_key(file: &Path) -> Result<String> {
+    match file.file_name().and_then(|name| name.to_str()) {
+        Some(name) => Ok(name.to_string()),
+        None => bail!("No file name in {}", file.display()),
+    }
+}
+
+/// Consistent copy of the database, even while it is written to
+async fn snapshot(options: &SqliteConnectOptions, file: &Path) -> Result<()> {
+    if file.exists() {
+        bail!("{} already exists", file.display());
+    }
+    let mut conn = options.connect().await?;
+    sqlx::query("VACUUM INTO $1")
+        .bind(file.to_string_lossy().into_owned())
+        .execute(&mut conn)
+        .await?;
+    conn.close().await?;
+    Ok(())
+}
+
+/// Check the snapshot `file` and move a copy of it in place of the database
+/// at `db_path`, with its write-ahead log dropped
+async fn put_back(file: &Path, db_path: &Path) -> Result<()> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(file)
+        .read_only(true)
+        .connect()
+        .await?;
+    let check: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&mut conn)
+        .await?;
+    conn.close().await?;
+    if check != "ok" {
+        bail!("{} is not a sound snapshot: {}", file.display(), check);
+    }
+
+    let staged = db_path.with_extension("restoring");
+    tokio::fs::copy(file, &staged).await?;
+    for suffix in ["-wal", "-shm"] {
+        let mut path = db_path.as_os_str().to_owned();
+        path.push(suffix);
+        let _ = tokio::fs::remove_file(path).await;
+    }
+    tokio::fs::rename(&staged, db_path).await?;
+    Ok(())
+}
+
+async fn upload_file(file: &Path, key: &str) -> Result<()> {
+    let mut reader = tokio::fs::File::open(file).await?;
+    let mut uploader = S3MultipartUploader::new(s3::backups_client(), key).await?;
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = match reader.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                uploader.abort().await?;
+                return Err(e.into());
+            }
+        };
+        uploader.write(&buf[..n]).await?;
+    }
+    uploader.complete().await
+}
+
+async fn download_file(key: &str, file: &Path) -> Result<()> {
+    if file.exists() {
+        bail!("{} already exists", file.display());
+    }
+    let mut stream = s3::backups_client()
+        .get(&object_store::path::Path::from(key))
+        .await?
+        .into_stream();
+    let mut writer = tokio::fs::File::create(file).await?;
+    while let Some(chunk) = stream.next().await {
+        writer.write_all(&chunk?).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_snapshot_and_put_back() {
+        let dir = std::env::temp_dir().join(format!("rewind-test-backups-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("rewind.db");
+        let file = dir.join("snapshot.db");
+        let options = SqliteConnectOptions::new()
+            .filename(&db_path)
+            .create_if_missing(true);
+
+        let mut conn = options.connect().await.unwrap();
+        sqlx::raw_sql("CREATE TABLE boats (name TEXT); INSERT INTO boats VALUES ('imoca')")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        snapshot(&options, &file).await.unwrap();
+        assert!(snapshot(&options, &file).await.is_err());
+        sqlx::query("UPDATE boats SET name = 'class40'")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        conn.close().await.unwrap();
+
+        put_back(&file, &db_path).await.unwrap();
+        let mut conn = options.connect().await.unwrap();
+        let name: String = sqlx::query_scalar("SELECT name FROM boats")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(name, "imoca");
+        conn.close().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,161 @@
+//! Water depths from a coarse bathymetry grid, for the minimum depth of
+//! courses sailed over shallows.
+//!
+//! The grid is an ESRI ASCII raster (`.asc`, as exported by GEBCO or ETOPO)
+//! of elevations in meters: negative under the sea, rows from north to south.
+
+use anyhow::{Result, anyhow};
+use std::sync::OnceLock;
+
+use crate::courses::LngLat;
+
+/// Depths loaded at server start. Lookups find nothing until then.
+static BATHYMETRY: OnceLock<Bathymetry> = OnceLock::new();
+
+pub struct Bathymetry {
+    cols: usize,
+    rows: usize,
+    /// Longitude and latitude of the south west corner of the grid
+    west: f64,
+    south: f64,
+    cell_size: f64,
+    /// Elevations row by row from the north, None where the grid has no data
+    elevations: Vec<Option<f32>>,
+}
+
+/// Load the bathymetry grid. Returns the number of cells.
+pub async fn init(path: &str) -> Result<usize> {
+    let text = tokio::fs::read_to_string(path).await?;
+    let bathymetry =
+        tokio::task::spawn_blocking(move || Bathymetry::from_ascii_grid(&text)).await??;
+    let count = bathymetry.elevations.len();
+    BATHYMETRY
+        .set(bathymetry)
+        .map_err(|_| anyhow!("Bathymetry already initialized"))?;
+    Ok(count)
+}
+
+/// Depth of the water at a point in meters, negative above sea level. None
+/// without bathymetry or out of the grid.
+pub fn depth(point: &LngLat) -> Option<f64> {
+    BATHYMETRY
+        .get()
+        .and_then(|bathymetry| bathymetry.depth(point))
+}
+
+impl Bathymetry {
+    pub fn from_ascii_grid(text: &str) -> Result<Bathymetry> {
+        let mut lines = text.lines();
+        let mut header = |name: &str| -> Result<(String, f64)> {
+            let line = lines.next().ok_or_else(|| anyhow!("Missing {}", name))?;
+            let mut fields = line.split_whitespace();
+            let key = fields.next().unwrap_or_default().to_lowercase();
+            let value = fields
+                .next()
+                .ok_or_else(|| anyhow!("Missing {} value", key))?
+                .parse()?;
+            Ok((key, value))
+        };
+
+        let (_, cols) = header("ncols")?;
+        let (_, rows) = header("nrows")?;
+        let (x_key, x) = header("xllcorner")?;
+        let (y_key, y) = header("yllcorner")?;
+        let (_, cell_size) = header("cellsize")?;
+        let (_, no_data) = header("nodata_value")?;
+        let (cols, rows) = (cols as usize, rows as usize);
+
+        // Grids anchored on the center of their corner cell
+        let half_cell = |key: &str| {
+            if key.ends_with("center") {
+                cell_size / 2.0
+            } else {
+                0.0
+            }
+        };
+
+        let elevations = lines
+            .flat_map(str::split_whitespace)
+            .map(|value| {
+                let elevation: f64 = value.parse()?;
+                Ok((elevation != no_data).then_some(elevation as f32))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if elevations.len() != cols * rows {
+            return Err(anyhow!(
+                "Expected {}×{} cells, got {}",
+                cols,
+                rows,
+                elevations.len()
+            ));
+        }
+
+        Ok(Bathymetry {
+            cols,
+            rows,
+            west: x - half_cell(&x_key),
+            south: y - half_cell(&y_key),
+            cell_size,
+            elevations,
+        })
+    }
+
+    /// Depth of the cell containing the point
+    pub fn depth(&self, point: &LngLat) -> Option<f64> {
+        let col = (point.lng - self.west).rem_euclid(360.0) / self.cell_size;
+        let row = (self.south + self.rows as f64 * self.cell_size - point.lat) / self.cell_size;
+        if col >= self.cols as f64 || row < 0.0 || row >= self.rows as f64 {
+            return None;
+        }
+        self.elevations[row as usize * self.cols + col as usize].map(|elevation| -elevation as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(lng: f64, lat: f64) -> LngLat {
+        LngLat { lng, lat }
+    }
+
+    /// 0.5° cells from 5.5°W to 4.5°W and 47.5°N to 48.5°N: a shallow bank
+    /// north west, a rock north east and a hole without data
+    const GRID: &str = "ncols 2
+nrows 2
+xllcorner -5.5
+yllcorner 47.5
+cellsize 0.5
+NODATA_value -9999
+-8 2
+-120 -9999
+";
+
+    #[test]
+    fn test_depth() {
+        let bathymetry = Bathymetry::from_ascii_grid(GRID).unwrap();
+        assert_eq!(bathymetry.depth(&point(-5.2, 48.2)), Some(8.0));
+        assert_eq!(bathymetry.depth(&point(-4.7, 48.2)), Some(-2.0));
+        assert_eq!(bathymetry.depth(&point(-5.2, 47.8)), Some(120.0));
+        assert_eq!(bathymetry.depth(&point(-4.7, 47.8)), None);
+        assert_eq!(bathymetry.depth(&point(-6.0, 48.0)), None);
+        assert_eq!(bathymetry.depth(&point(-5.0, 49.0)), None);
+    }
+
+    #[test]
+    fn test_depth_across_the_antimeridian() {
+        let grid = "ncols 4\nnrows 1\nxllcenter 179.5\nyllcenter 0\ncellsize 1\nNODATA_value -9999\n-1 -2 -3 -4\n";
+        let bathymetry = Bathymetry::from_ascii_grid(grid).unwrap();
+        assert_eq!(bathymetry.depth(&point(179.5, 0.0)), Some(1.0));
+        assert_eq!(bathymetry.depth(&point(-179.5, 0.2)), Some(2.0));
+        assert_eq!(bathymetry.depth(&point(-177.5, 0.0)), Some(4.0));
+        assert_eq!(bathymetry.depth(&point(178.5, 0.0)), None);
+    }
+
+    #[test]
+    fn test_invalid_grid() {
+        assert!(Bathymetry::from_ascii_grid("ncols 2\nnrows 2\n").is_err());
+        let short = GRID.replace("-120 -9999", "-120");
+        assert!(Bathymetry::from_ascii_grid(&short).is_err());
+    }
+}
@@ -3,6 +3,8 @@ use std::path::PathBuf;
 use chrono::NaiveDate;
 use clap::{Parser, Subcommand};
 
+use crate::roles::Role;
+
 #[derive(Debug, Parser)]
 #[command(about = "Rewind CLI.")]
 pub struct Cli {
@@ -35,4 +37,83 @@ pub enum Command {
     RestoreCourses {
         file: PathBuf,
     },
+    /// Check the wind reports, rasters and polars a course needs
+    CheckCourse {
+        key: String,
+    },
+    BuildLandMask {
+        /// Coastline TopoJSON, defaults to REWIND_LAND_FILE
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Bin the stored paths of a course, or of every course with results,
+    /// and publish its heatmap
+    BuildHeatmap {
+        key: Option<String>,
+    },
+    /// Give a staff role to the account of an email, creating it if needed
+    GrantRole {
+        email: String,
+        role: Role,
+    },
+    /// Take a staff role from the account of an email
+    RevokeRole {
+        email: String,
+        role: Role,
+    },
+    /// List the accounts with a staff role
+    ListRoles,
+    /// Write the TypeScript types of the messages and courses shared with
+    /// the web client
+    ExportTypes {
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Check the stored files against the database, report their usage
+    Storage {
+        #[command(subcommand)]
+        cmd: StorageCommand,
+    },
+    /// Manage the schema of the database
+    Db {
+        #[command(subcommand)]
+        cmd: DbCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum StorageCommand {
+    /// Report the rasters and race paths without a database row, and the
+    /// rows without their file
+    Gc {
+        /// Also delete them
+        #[arg(long)]
+        delete: bool,
+    },    /// Print the object counts and sizes of the buckets by prefix: GRIBs
+    /// by year, rasters by source, race paths by course
+    Report,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DbCommand {
+    /// Apply the pending migrations
+    Migrate,
+    /// List the migrations, applied or pending
+    Status,
+    /// Undo the migrations newer than a version, 0 for all of them
+    Revert { version: i64 },
+    /// Snapshot the SQLite database to a file
+    Backup {
+        file: PathBuf,
+        /// Also upload the snapshot to the backups bucket
+        #[arg(long)]
+        upload: bool,
+    },
+    /// Replace the SQLite database by a snapshot, with the server stopped
+    Restore {
+        file: PathBuf,
+        /// Download the snapshot of this file name from the backups bucket
+        #[arg(long)]
+        download: bool,
+    },
 }
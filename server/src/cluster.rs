@@ -0,0 +1,269 @@
+//! Several server instances behind a load balancer, sharing a Redis.
+//!
+//! A race lives in the memory of the instance that created it. Instances
+//! record the races they own in Redis, and a player connected to another
+//! instance who joins or resumes one of them has their connection relayed
+//! to the owner: the whole race still runs in one place. The open races of
+//! every instance are published too, for the race list.
+//!
+//! Without `REWIND_REDIS_URL` the server runs alone and none of this applies.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use axum::extract::ws::{self, WebSocket};
+use futures::stream::SplitStream;
+use futures::{SinkExt, StreamExt};
+use redis::AsyncCommands;
+use redis::aio::ConnectionManager;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::config;
+use crate::multiplayer::{RaceInfo, RaceManager};
+
+/// Ownership records outlive a few missed heartbeats
+const OWNER_TTL_SECS: u64 = 60;
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+
+const INSTANCES_KEY: &str = "rewind:instances";
+
+fn race_key(race_id: &str) -> String {
+    format!("rewind:race:{}", race_id)
+}
+
+fn lobbies_key(instance: &str) -> String {
+    format!("rewind:lobbies:{}", instance)
+}
+
+struct Cluster {
+    redis: ConnectionManager,
+    /// Base URL of this instance for the others, also its id
+    instance: String,
+}
+
+static CLUSTER: OnceLock<Cluster> = OnceLock::new();
+
+/// Join the cluster when a Redis is configured. The instance URL defaults to
+/// the private address of the Fly machine on the port of the server.
+pub async fn init(address: std::net::SocketAddr) -> Result<()> {
+    let Some(url) = &config().redis_url else {
+        return Ok(());
+    };
+    let instance = match &config().instance_url {
+        Some(instance) => instance.trim_end_matches('/').to_string(),
+        None => {
+            let ip = std::env::var("FLY_PRIVATE_IP")
+                .map_err(|_| anyhow!("REWIND_INSTANCE_URL is required with REWIND_REDIS_URL"))?;
+            format!("http://[{}]:{}", ip, address.port())
+        }
+    };
+    let redis = redis::Client::open(url.as_str())?
+        .get_connection_manager()
+        .await?;
+    log::info!("Joined the cluster as {}", instance);
+    let _ = CLUSTER.set(Cluster { redis, instance });
+    Ok(())
+}
+
+/// Record in the background that a new race runs on this instance
+pub fn spawn_claim(race_id: String) {
+    let Some(cluster) = CLUSTER.get() else {
+        return;
+    };
+    let mut redis = cluster.redis.clone();
+    tokio::spawn(async move {
+        let result: redis::RedisResult<()> = redis
+            .set_ex(race_key(&race_id), &cluster.instance, OWNER_TTL_SECS)
+            .await;
+        if let Err(e) = result {
+            log::warn!("Failed to claim race {}: {}", race_id, e);
+        }
+    });
+}
+
+/// Base URL of the other instance running a race, None when it runs here,
+/// nowhere or without a cluster
+pub async fn owner(race_id: &str) -> Option<String> {
+    let cluster = CLUSTER.get()?;
+    let mut redis = cluster.redis.clone();
+    match redis.get::<_, Option<String>>(race_key(race_id)).await {
+        Ok(owner) => owner.filter(|owner| *owner != cluster.instance),
+        Err(e) => {
+            log::warn!("Failed to look up the owner of race {}: {}", race_id, e);
+            None
+        }
+    }
+}
+
+/// Open races of the other instances
+pub async fn remote_races() -> Vec<RaceInfo> {
+    let Some(cluster) = CLUSTER.get() else {
+        return Vec::new();
+    };
+    match fetch_remote_races(cluster).await {
+        Ok(races) => races,
+        Err(e) => {
+            log::warn!("Failed to list the races of the cluster: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+async fn fetch_remote_races(cluster: &Cluster) -> Result<Vec<RaceInfo>> {
+    let mut redis = cluster.redis.clone();
+    let instances: Vec<String> = redis.smembers(INSTANCES_KEY).await?;
+    let others: Vec<&String> = instances
+        .iter()
+        .filter(|instance| **instance != cluster.instance)
+        .collect();
+    if others.is_empty() {
+        return Ok(Vec::new());
+    }
+    let keys: Vec<String> = others
+        .iter()
+        .map(|instance| lobbies_key(instance))
+        .collect();
+    let lobbies: Vec<Option<String>> = redis.mget(&keys).await?;
+
+    let mut races = Vec::new();
+    for (instance, lobbies) in others.into_iter().zip(lobbies) {
+        match lobbies {
+            Some(json) => races.extend(serde_json::from_str::<Vec<RaceInfo>>(&json)?),
+            // Stopped without leaving
+            None => redis.srem::<_, _, ()>(INSTANCES_KEY, instance).await?,
+        }
+    }
+    Ok(races)
+}
+
+/// Renew the claims of the races of this instance and publish its open
+/// races, until the server stops
+pub async fn run_heartbeat(manager: RaceManager) {
+    let Some(cluster) = CLUSTER.get() else {
+        return;
+    };
+    loop {
+        if let Err(e) = heartbeat(cluster, &manager).await {
+            log::warn!("Cluster heartbeat failed: {}", e);
+        }
+        tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+    }
+}
+
+async fn heartbeat(cluster: &Cluster, manager: &RaceManager) -> Result<()> {
+    let lobbies = serde_json::to_string(&manager.list_races().await)?;
+    let mut pipe = redis::pipe();
+    for race_id in manager.race_ids().await {
+        pipe.set_ex(race_key(&race_id), &cluster.instance, OWNER_TTL_SECS)
+            .ignore();
+    }
+    pipe.set_ex(lobbies_key(&cluster.instance), lobbies, OWNER_TTL_SECS)
+        .ignore()
+        .sadd(INSTANCES_KEY, &cluster.instance)
+        .ignore();
+    pipe.query_async::<()>(&mut cluster.redis.clone()).await?;
+    Ok(())
+}
+
+/// Leave the cluster at shutdown: the races of this instance are over, the
+/// other instances stop relaying players to them
+pub async fn leave(race_ids: &[String]) {
+    let Some(cluster) = CLUSTER.get() else {
+        return;
+    };
+    let mut pipe = redis::pipe();
+    for race_id in race_ids {
+        pipe.del(race_key(race_id)).ignore();
+    }
+    let result: redis::RedisResult<()> = pipe
+        .del(lobbies_key(&cluster.instance))
+        .ignore()
+        .srem(INSTANCES_KEY, &cluster.instance)
+        .ignore()
+        .query_async(&mut cluster.redis.clone())
+        .await;
+    if let Err(e) = result {
+        log::warn!("Failed to leave the cluster: {}", e);
+    }
+}
+
+/// Relay a player connection to the instance owning their race, starting
+/// with the frames already received (hello, join or resume), until either
+/// side closes or the server stops
+pub async fn relay(
+    owner: &str,
+    first: Vec<ws::Message>,
+    client_rx: &mut SplitStream<WebSocket>,
+    client_tx: mpsc::UnboundedSender<ws::Message>,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let url = format!("{}/multiplayer/race", owner.replacen("http", "ws", 1));
+    let (owner_ws, _) = tokio_tungstenite::connect_async(url).await?;
+    let (mut owner_tx, mut owner_rx) = owner_ws.split();
+    for frame in first {
+        if let Some(frame) = to_owner(frame) {
+            owner_tx.send(frame).await?;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            frame = client_rx.next() => match frame {
+                Some(Ok(ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(frame)) => {
+                    if let Some(frame) = to_owner(frame) {
+                        owner_tx.send(frame).await?;
+                    }
+                }
+            },
+            frame = owner_rx.next() => match frame {
+                Some(Ok(tungstenite::Message::Close(_))) | None => break,
+                Some(Ok(frame)) => {
+                    if let Some(frame) = to_client(frame)
+                        && client_tx.send(frame).is_err()
+                    {
+                        break;
+                    }
+                }
+                Some(Err(e)) => return Err(e.into()),
+            },
+            _ = shutdown.cancelled() => break,
+        }
+    }
+    let _ = owner_tx.close().await;
+    Ok(())
+}
+
+/// Message frames cross over, pings and closes are answered on each side
+fn to_owner(frame: ws::Message) -> Option<tungstenite::Message> {
+    match frame {
+        ws::Message::Text(text) => Some(tungstenite::Message::text(text.as_str())),
+        ws::Message::Binary(bytes) => Some(tungstenite::Message::binary(bytes)),
+        _ => None,
+    }
+}
+
+fn to_client(frame: tungstenite::Message) -> Option<ws::Message> {
+    match frame {
+        tungstenite::Message::Text(text) => Some(ws::Message::text(text.as_str())),
+        tungstenite::Message::Binary(bytes) => Some(ws::Message::binary(bytes)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames() {
+        let text = to_owner(ws::Message::text("{\"type\":\"LeaveRace\"}")).unwrap();
+        assert_eq!(text, tungstenite::Message::text("{\"type\":\"LeaveRace\"}"));
+        let binary = to_client(tungstenite::Message::binary(vec![1, 2])).unwrap();
+        assert_eq!(binary, ws::Message::binary(vec![1, 2]));
+        assert!(to_owner(ws::Message::Ping(Default::default())).is_none());
+    }
+}
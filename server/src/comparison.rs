@@ -0,0 +1,222 @@
+//! Comparison of two results sailed on the same course: both paths on the
+//! clock of the time elapsed since their start, and the time between them
+//! at each gate.
+//!
+//! Results sailed in other weather than the course's start at another
+//! course time, so paths are aligned on elapsed time rather than course time.
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::analysis;
+use crate::courses::Course;
+use crate::race_results::{self, PathPoint, ResultStatus, StoredResult};
+
+/// Most aligned samples returned, whatever the length of the race
+const MAX_SAMPLES: i64 = 2000;
+
+/// Shortest time between aligned samples, as often as paths are recorded
+const MIN_STEP_MS: i64 = 1000;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparedResult {
+    pub result_id: i64,
+    pub player_name: String,
+    pub polar: String,
+    pub status: ResultStatus,
+    pub finish_time: i64,
+}
+
+/// Positions of both boats after the same time since their start, None
+/// before a path begins or after it ends
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlignedPoint {
+    pub time: i64,
+    pub a: Option<PathPoint>,
+    pub b: Option<PathPoint>,
+}
+
+/// Times after the start both results crossed a gate, the finish last
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GateDelta {
+    pub gate: usize,
+    pub a: Option<i64>,
+    pub b: Option<i64>,
+    /// Time `b` was behind `a`, negative when it was ahead
+    pub delta: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comparison {
+    pub course_key: String,
+    pub a: ComparedResult,
+    pub b: ComparedResult,
+    pub points: Vec<AlignedPoint>,
+    pub gates: Vec<GateDelta>,
+}
+
+/// Both paths sampled at the same elapsed times, `start` being the course
+/// time each one started at
+fn align(a: &[PathPoint], a_start: i64, b: &[PathPoint], b_start: i64) -> Vec<AlignedPoint> {
+    let end = |path: &[PathPoint], start: i64| path.last().map_or(0, |p| p.race_time - start);
+    let duration = end(a, a_start).max(end(b, b_start));
+    if a.is_empty() && b.is_empty() {
+        return Vec::new();
+    }
+    let step = (duration / MAX_SAMPLES).max(MIN_STEP_MS);
+
+    let mut times: Vec<i64> = (0..=duration / step).map(|i| i * step).collect();
+    if times.last() != Some(&duration) {
+        times.push(duration);
+    }
+    times
+        .into_iter()
+        .map(|time| AlignedPoint {
+            time,
+            a: race_results::interpolate_position(a, a_start + time),
+            b: race_results::interpolate_position(b, b_start + time),
+        })
+        .collect()
+}
+
+/// Deltas at each gate then at the finish, from elapsed gate times. The
+/// finish only counts for finished results.
+fn gate_deltas(
+    a: &[i64],
+    a_finish: Option<i64>,
+    b: &[i64],
+    b_finish: Option<i64>,
+) -> Vec<GateDelta> {
+    let gate_count = a.len().max(b.len());
+    (0..gate_count)
+        .map(|gate| (a.get(gate).copied(), b.get(gate).copied()))
+        .chain(std::iter::once((a_finish, b_finish)))
+        .enumerate()
+        .map(|(gate, (a, b))| GateDelta {
+            gate,
+            a,
+            b,
+            delta: a.zip(b).map(|(a, b)| b - a),
+        })
+        .collect()
+}
+
+/// Elapsed gate times of a result: its stored splits, or for results saved
+/// before splits the crossings found in its path
+fn elapsed_gate_times(
+    splits: Option<Vec<i64>>,
+    course: &Course,
+    path: &[PathPoint],
+    start: i64,
+) -> Vec<i64> {
+    match splits {
+        Some(splits) if !splits.is_empty() => splits,
+        _ => {
+            let gates = course.marks();
+            let gates = &gates[..gates.len().saturating_sub(1)];
+            analysis::gate_times(gates, path)
+                .into_iter()
+                .map(|time| time - start)
+                .collect()
+        }
+    }
+}
+
+/// Compare results `a_id` and `b_id`, both sailed on `course`
+pub async fn compare(
+    course: &Course,
+    a_id: i64,
+    a: StoredResult,
+    b_id: i64,
+    b: StoredResult,
+) -> Result<Comparison> {
+    let a_path = race_results::load_path(&a.path_s3_key).await?;
+    let b_path = race_results::load_path(&b.path_s3_key).await?;
+    let a_start = a.weather_start_time.unwrap_or(course.start_time);
+    let b_start = b.weather_start_time.unwrap_or(course.start_time);
+
+    let mut splits = race_results::get_splits(&[a_id, b_id]).await?;
+    let a_gates = elapsed_gate_times(splits.remove(&a_id), course, &a_path, a_start);
+    let b_gates = elapsed_gate_times(splits.remove(&b_id), course, &b_path, b_start);
+    let finish = |result: &StoredResult| {
+        (result.status == ResultStatus::Finished).then_some(result.finish_time)
+    };
+
+    let compared = |result_id: i64, result: StoredResult| ComparedResult {
+        result_id,
+        player_name: result.player_name,
+        polar: result.polar,
+        status: result.status,
+        finish_time: result.finish_time,
+    };
+    Ok(Comparison {
+        course_key: course.key.clone(),
+        points: align(&a_path, a_start, &b_path, b_start),
+        gates: gate_deltas(&a_gates, finish(&a), &b_gates, finish(&b)),
+        a: compared(a_id, a),
+        b: compared(b_id, b),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(race_time: i64, lng: f32) -> PathPoint {
+        PathPoint {
+            race_time,
+            lng,
+            lat: 0.0,
+            heading: 90.0,
+        }
+    }
+
+    #[test]
+    fn test_align() {
+        // Same course, b sailed in weather starting a day later and slower
+        let day = 24 * 60 * 60 * 1000;
+        let a = [point(0, 0.0), point(10_000, 1.0)];
+        let b = [point(day, 0.0), point(day + 20_000, 1.0)];
+        let points = align(&a, 0, &b, day);
+
+        assert_eq!(points.len(), 21);
+        assert_eq!(points[5].time, 5000);
+        assert_eq!(points[5].a.unwrap().lng, 0.5);
+        assert_eq!(points[5].b.unwrap().lng, 0.25);
+        assert_eq!(points[5].b.unwrap().race_time, day + 5000);
+        assert!(points[20].a.is_none());
+        assert_eq!(points[20].b.unwrap().lng, 1.0);
+        assert!(align(&[], 0, &[], 0).is_empty());
+    }
+
+    #[test]
+    fn test_align_long_race() {
+        let a = [point(0, 0.0), point(10_000_003, 1.0)];
+        let points = align(&a, 0, &[], 0);
+        assert_eq!(points.len() as i64, MAX_SAMPLES + 2);
+        assert_eq!(points.last().unwrap().time, 10_000_003);
+        assert!(points.iter().all(|p| p.b.is_none()));
+    }
+
+    #[test]
+    fn test_gate_deltas() {
+        let deltas = gate_deltas(&[1000, 3000], Some(5000), &[1500], None);
+        assert_eq!(deltas.len(), 3);
+        assert_eq!(deltas[0].delta, Some(500));
+        assert_eq!(
+            deltas[1],
+            GateDelta {
+                gate: 1,
+                a: Some(3000),
+                b: None,
+                delta: None,
+            }
+        );
+        assert_eq!(deltas[2].a, Some(5000));
+        assert_eq!(deltas[2].delta, None);
+    }
+}
@@ -0,0 +1,281 @@
+//! Course metrics and difficulty: route length, wind along the route over the
+//! course weather window and share of the route in the Southern Ocean.
+//!
+//! Metrics need wind rasters, so they are computed in the background and
+//! cached in the `course_metrics` table until the course changes.
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::courses::{self, Course, LngLat};
+use crate::simulation::{self, WindRaster};
+use crate::{db, geo, units, wind_reports};
+
+/// Spacing of the route samples, in nautical miles
+const SAMPLE_NM: f64 = 50.0;
+
+/// Most wind reports sampled over a course window, evenly spread
+const MAX_REPORTS: usize = 40;
+
+/// Latitude south of which the route counts as Southern Ocean
+const SOUTHERN_OCEAN_LAT: f64 = -40.0;
+
+/// Route length, average and max wind making the hardest score
+const HARDEST_ROUTE_NM: f64 = 25000.0;
+const HARDEST_AVG_TWS: f64 = 25.0;
+const HARDEST_MAX_TWS: f64 = 50.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CourseMetrics {
+    /// Length of the course route through its waypoints, in nautical miles
+    pub route_nm: f64,
+    /// True wind speed along the route over the course window, in knots
+    pub avg_tws: f64,
+    pub max_tws: f64,
+    /// Share of the route south of 40°S
+    pub southern_fraction: f64,
+    /// 0 (easiest) to 10 (hardest)
+    pub difficulty: f64,
+}
+
+/// Points every `SAMPLE_NM` along the route of a course, every lap included
+pub fn route_samples(course: &Course) -> Vec<LngLat> {
+    let mut samples = vec![course.start.clone()];
+    for leg in 0..=course.finish_index() {
+        for segment in course.leg_path(leg).windows(2) {
+            let (from, to) = (&segment[0], &segment[1]);
+            let steps = (geo::distance_nm(from, to) / SAMPLE_NM).ceil().max(1.0) as usize;
+            let d_lng = geo::wrap_lng(to.lng - from.lng);
+            samples.extend((1..=steps).map(|step| {
+                let t = step as f64 / steps as f64;
+                LngLat {
+                    lng: geo::wrap_lng(from.lng + d_lng * t),
+                    lat: from.lat + (to.lat - from.lat) * t,
+                }
+            }));
+        }
+    }
+    samples
+}
+
+/// Length of the course route, in nautical miles
+pub fn route_nm(course: &Course) -> f64 {
+    (0..=course.finish_index())
+        .flat_map(|leg| {
+            course
+                .leg_path(leg)
+                .windows(2)
+                .map(|segment| geo::distance_nm(&segment[0], &segment[1]))
+                .collect::<Vec<_>>()
+        })
+        .sum()
+}
+
+/// Share of the samples south of `SOUTHERN_OCEAN_LAT`
+pub fn southern_fraction(samples: &[LngLat]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let south = samples
+        .iter()
+        .filter(|point| point.lat < SOUTHERN_OCEAN_LAT)
+        .count();
+    south as f64 / samples.len() as f64
+}
+
+/// Score from 0 to 10: longer, windier and more southern courses are harder
+pub fn difficulty(route_nm: f64, avg_tws: f64, max_tws: f64, southern_fraction: f64) -> f64 {
+    let share = |value: f64, hardest: f64| (value / hardest).clamp(0.0, 1.0);
+    let score = 0.35 * share(route_nm, HARDEST_ROUTE_NM)
+        + 0.25 * share(avg_tws, HARDEST_AVG_TWS)
+        + 0.15 * share(max_tws, HARDEST_MAX_TWS)
+        + 0.25 * southern_fraction.clamp(0.0, 1.0);
+    (score * 100.0).round() / 10.0
+}
+
+/// Average and max wind speed (knots) of the rasters at the samples
+fn wind_stats<'a>(
+    rasters: impl IntoIterator<Item = &'a WindRaster>,
+    samples: &[LngLat],
+) -> (f64, f64) {
+    let (mut sum, mut max, mut count) = (0.0, 0.0_f64, 0);
+    for raster in rasters {
+        for point in samples {
+            if let Some((u, v)) = raster.speed_at(point.lng, point.lat) {
+                let tws = units::ms_to_knots(u.hypot(v));
+                sum += tws;
+                max = max.max(tws);
+                count += 1;
+            }
+        }
+    }
+    let avg = if count > 0 { sum / count as f64 } else { 0.0 };
+    (avg, max)
+}
+
+/// Compute the metrics of a course from the stored wind over its window
+pub async fn compute(course: &Course) -> Result<CourseMetrics> {
+    let samples = route_samples(course);
+    let reports =
+        wind_reports::get_reports_between(course.start_time, course.max_finish_time()).await?;
+    let stride = reports.len().div_ceil(MAX_REPORTS).max(1);
+
+    let mut rasters = Vec::new();
+    for report in reports.iter().step_by(stride) {
+        rasters.push(simulation::load_raster(&report.png_path).await?);
+    }
+    let (avg_tws, max_tws) = wind_stats(&rasters, &samples);
+
+    let route_nm = route_nm(course);
+    let southern_fraction = southern_fraction(&samples);
+    Ok(CourseMetrics {
+        route_nm,
+        avg_tws,
+        max_tws,
+        southern_fraction,
+        difficulty: difficulty(route_nm, avg_tws, max_tws, southern_fraction),
+    })
+}
+
+// ============================================================================
+// Database
+// ============================================================================
+
+/// Cached metrics of every course, by course key
+pub async fn get_all() -> Result<HashMap<String, CourseMetrics>> {
+    let rows: Vec<(String, String)> = sqlx::query_as("SELECT course_key, data FROM course_metrics")
+        .fetch_all(db::pool())
+        .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(key, data)| Some((key, serde_json::from_str(&data).ok()?)))
+        .collect())
+}
+
+async fn save(course_key: &str, metrics: &CourseMetrics) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO course_metrics (course_key, data, computed_at) VALUES ($1, $2, $3)
+         ON CONFLICT(course_key) DO UPDATE SET data = excluded.data, computed_at = excluded.computed_at",
+    )
+    .bind(course_key)
+    .bind(serde_json::to_string(metrics)?)
+    .bind(Utc::now().timestamp_millis())
+    .execute(db::pool())
+    .await?;
+    Ok(())
+}
+
+/// Drop the cached metrics of a course, out of date once it changes
+pub async fn invalidate(course_key: &str) -> Result<()> {
+    sqlx::query("DELETE FROM course_metrics WHERE course_key = $1")
+        .bind(course_key)
+        .execute(db::pool())
+        .await?;
+    Ok(())
+}
+
+/// Compute and cache the metrics of the courses that have none
+pub async fn refresh_missing() -> Result<usize> {
+    let cached = get_all().await?;
+    let mut count = 0;
+    for course in courses::get_all().await? {
+        if cached.contains_key(&course.key) {
+            continue;
+        }
+        let metrics = compute(&course).await?;
+        save(&course.key, &metrics).await?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Compute the missing metrics in the background, rasters take a while to load
+pub fn spawn_refresh() {
+    tokio::spawn(async {
+        match refresh_missing().await {
+            Ok(0) => {}
+            Ok(count) => log::info!("Computed metrics of {} courses", count),
+            Err(e) => log::warn!("Failed to compute course metrics: {}", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::courses::Gate;
+
+    fn course(laps: u8) -> Course {
+        Course {
+            key: "test".to_string(),
+            name: "Test".to_string(),
+            description: String::new(),
+            polar: "mini-650".to_string(),
+            boat_classes: vec![],
+            start_time: 0,
+            start: LngLat { lng: 0.0, lat: 0.0 },
+            start_heading: 270.0,
+            finish_line: Gate::vertical(-1.0, 0.0, 10.0),
+            gates: vec![Gate::vertical(-3.0, 0.0, 10.0)],
+            route_waypoints: vec![vec![], vec![]],
+            time_factor: 1000,
+            max_days: 10,
+            energy: None,
+            min_depth: None,
+            laps,
+            tags: vec![],
+            published: true,
+            opens_at: None,
+            closes_at: None,
+        }
+    }
+
+    #[test]
+    fn test_route() {
+        let one_lap = course(1);
+        // 3° out to the gate and 2° back to the finish
+        assert!((route_nm(&one_lap) - 5.0 * 60.04).abs() < 0.1);
+        let samples = route_samples(&one_lap);
+        assert_eq!(samples.len(), 1 + 4 + 3);
+        assert!(
+            samples
+                .windows(2)
+                .all(|w| geo::distance_nm(&w[0], &w[1]) <= SAMPLE_NM)
+        );
+
+        // The leg from the gate back to the same gate adds nothing
+        assert!((route_nm(&course(2)) - route_nm(&one_lap)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_wind_and_southern_fraction() {
+        let samples = vec![
+            LngLat {
+                lng: 0.0,
+                lat: -30.0,
+            },
+            LngLat {
+                lng: 0.0,
+                lat: -50.0,
+            },
+        ];
+        assert_eq!(southern_fraction(&samples), 0.5);
+        assert_eq!(southern_fraction(&[]), 0.0);
+
+        // 10 m/s, then calm
+        let rasters = [WindRaster::uniform(170, 128), WindRaster::uniform(128, 128)];
+        let (avg, max) = wind_stats(&rasters, &samples);
+        assert!((max - units::ms_to_knots(10.0)).abs() < 0.5);
+        assert!((avg - units::ms_to_knots(5.0)).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_difficulty() {
+        assert_eq!(difficulty(0.0, 0.0, 0.0, 0.0), 0.0);
+        assert_eq!(difficulty(50000.0, 40.0, 80.0, 1.0), 10.0);
+        assert!(difficulty(24000.0, 18.0, 45.0, 0.4) > difficulty(4000.0, 14.0, 35.0, 0.0));
+    }
+}
@@ -1,17 +1,46 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
+use chrono::DateTime;
+use futures::stream::{self, StreamExt};
+use object_store::ObjectStoreExt;
 use serde::{Deserialize, Serialize};
+use ts_rs::TS;
 
+use crate::config::config;
 use crate::db;
+use crate::energy::Energy;
+use crate::geo::{Latitude, Longitude};
+use crate::{course_metrics, gates, geo, polars, s3, wind_reports};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Distance past the start line tolerated at the gun, in nautical miles
+const START_LINE_TOLERANCE_NM: f64 = 0.01;
+
+/// Coordinates in degrees; the ones read are checked and their longitude
+/// wrapped, see `CheckedLngLat`
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[serde(from = "CheckedLngLat")]
 pub struct LngLat {
     pub lng: f64,
     pub lat: f64,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Deserialize)]
+struct CheckedLngLat {
+    lng: Longitude,
+    lat: Latitude,
+}
+
+impl From<CheckedLngLat> for LngLat {
+    fn from(checked: CheckedLngLat) -> Self {
+        LngLat {
+            lng: checked.lng.into(),
+            lat: checked.lat.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
 pub struct Gate {
     pub center: LngLat,
@@ -39,13 +68,18 @@ impl Gate {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
 pub struct Course {
     pub key: String,
     pub name: String,
     pub description: String,
+    /// Default boat class (polar name)
     pub polar: String,
+    /// Other boat classes players may pick instead of the default one
+    #[serde(default)]
+    pub boat_classes: Vec<String>,
+    #[ts(type = "number")]
     pub start_time: i64,
     pub start: LngLat,
     pub start_heading: f64,
@@ -54,9 +88,101 @@ pub struct Course {
     pub route_waypoints: Vec<Vec<LngLat>>, // waypoints for each leg (start→gate0, gate0→gate1, ..., gateN→finish)
     pub time_factor: u16,
     pub max_days: u8,
+    /// Energy budget of the boats, when the course manages energy
+    #[serde(default)]
+    pub energy: Option<Energy>,
+    /// Minimum depth in meters: boats ground in shallower water
+    #[serde(default)]
+    pub min_depth: Option<f64>,
+    /// Times the gates are sailed, in order, before the finish line
+    #[serde(default = "one_lap")]
+    pub laps: u8,
+    /// Kinds of race, to filter the course list
+    #[serde(default)]
+    pub tags: Vec<CourseTag>,
+    /// Unpublished courses are kept but no race can be created on them
+    #[serde(default = "published")]
+    pub published: bool,
+    /// Real time window races can be created in, in ms since epoch, open
+    /// ended when None (seasonal events)
+    #[serde(default)]
+    #[ts(type = "number | null")]
+    pub opens_at: Option<i64>,
+    #[serde(default)]
+    #[ts(type = "number | null")]
+    pub closes_at: Option<i64>,
+}
+
+fn one_lap() -> u8 {
+    1
+}
+
+fn published() -> bool {
+    true
+}
+
+fn format_time(time: i64) -> String {
+    DateTime::from_timestamp_millis(time)
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| time.to_string())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "lowercase")]
+pub enum CourseTag {
+    /// Ocean crossings and around the world races
+    Ocean,
+    /// Coastal and European offshore races
+    Offshore,
+    /// Short races, sailed in an hour or two
+    Sprint,
+    /// Replays of past races
+    Historic,
+}
+
+impl CourseTag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CourseTag::Ocean => "ocean",
+            CourseTag::Offshore => "offshore",
+            CourseTag::Sprint => "sprint",
+            CourseTag::Historic => "historic",
+        }
+    }
+}
+
+/// Filters of the course list, all optional
+#[derive(Debug, Default, Deserialize)]
+pub struct CourseQuery {
+    pub tag: Option<CourseTag>,
+    /// Bounds of the course `max_days`
+    pub min_days: Option<u8>,
+    pub max_days: Option<u8>,
+    /// Text searched in the course key and name
+    pub q: Option<String>,
 }
 
 impl Course {
+    /// All boat classes of the course, the default one first
+    pub fn classes(&self) -> Vec<&str> {
+        let mut classes = vec![self.polar.as_str()];
+        for class in &self.boat_classes {
+            if !classes.contains(&class.as_str()) {
+                classes.push(class);
+            }
+        }
+        classes
+    }
+
+    /// Boat class picked by a player: the default one when none is picked
+    pub fn boat_class(&self, picked: Option<String>) -> Result<String> {
+        match picked {
+            None => Ok(self.polar.clone()),
+            Some(polar) if self.classes().contains(&polar.as_str()) => Ok(polar),
+            Some(polar) => Err(anyhow!("Boat class {} is not allowed on this course", polar)),
+        }
+    }
+
     pub fn max_finish_time(&self) -> i64 {
         self.start_time + (self.max_days as i64 * 24 * 60 * 60 * 1000)
     }
@@ -64,6 +190,171 @@ impl Course {
     pub fn race_time(&self, elapsed_since_start: i64) -> i64 {
         self.start_time + elapsed_since_start * (self.time_factor as i64)
     }
+
+    /// Whether a position is on the course side of the start line, the line
+    /// through the start square to the start heading
+    pub fn over_start_line(&self, position: &LngLat) -> bool {
+        let ahead = geo::destination(&self.start, self.start_heading, 1.0);
+        geo::along_track_nm(position, &self.start, &ahead) > START_LINE_TOLERANCE_NM
+    }
+
+    /// Index of the finish line among the marks: the gates of every lap come first
+    pub fn finish_index(&self) -> usize {
+        self.gates.len() * self.laps.max(1) as usize
+    }
+
+    /// Mark `index` in sailing order: gate `index % gates.len()` on each lap,
+    /// then the finish line at `finish_index`
+    pub fn mark(&self, index: usize) -> Option<&Gate> {
+        match index.cmp(&self.finish_index()) {
+            std::cmp::Ordering::Less => Some(&self.gates[index % self.gates.len()]),
+            std::cmp::Ordering::Equal => Some(&self.finish_line),
+            std::cmp::Ordering::Greater => None,
+        }
+    }
+
+    /// Every mark in sailing order, the finish line last
+    pub fn marks(&self) -> Vec<&Gate> {
+        (0..=self.finish_index())
+            .filter_map(|index| self.mark(index))
+            .collect()
+    }
+
+    /// Whether crossing mark `index` completes a lap, on courses of several laps
+    pub fn completes_lap(&self, index: usize) -> bool {
+        self.laps > 1 && index < self.finish_index() && (index + 1).is_multiple_of(self.gates.len())
+    }
+
+    /// Route of a leg, from its starting mark through its waypoints to its end mark.
+    /// Leg `i` ends at mark `i`, the last leg (`finish_index`) ends at the finish line.
+    /// Legs of later laps follow the waypoints of the first lap.
+    pub fn leg_path(&self, leg: usize) -> Vec<LngLat> {
+        let finish = self.finish_index();
+        let leg = leg.min(finish);
+        let from = match leg.checked_sub(1).and_then(|previous| self.mark(previous)) {
+            Some(gate) => gate.center.clone(),
+            None => self.start.clone(),
+        };
+        let to = self.mark(leg).unwrap_or(&self.finish_line).center.clone();
+        let waypoints = if leg == finish {
+            self.gates.len()
+        } else {
+            leg % self.gates.len()
+        };
+
+        let mut path = vec![from];
+        if let Some(waypoints) = self.route_waypoints.get(waypoints) {
+            path.extend(waypoints.iter().cloned());
+        }
+        path.push(to);
+        path
+    }
+
+    /// Whether races can be created on the course at `now` (ms since epoch):
+    /// published, and within its opening window
+    pub fn check_open(&self, now: i64) -> Result<()> {
+        if !self.published {
+            return Err(anyhow!("{} is not published", self.name));
+        }
+        if let Some(opens_at) = self.opens_at.filter(|&opens_at| now < opens_at) {
+            return Err(anyhow!(
+                "{} opens on {} UTC",
+                self.name,
+                format_time(opens_at)
+            ));
+        }
+        if let Some(closes_at) = self.closes_at.filter(|&closes_at| now >= closes_at) {
+            return Err(anyhow!(
+                "{} closed on {} UTC",
+                self.name,
+                format_time(closes_at)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check a course from the editor: a course that can be sailed, with its
+    /// marks in order along the route
+    pub fn validate(&self) -> Result<()> {
+        if self.key.trim().is_empty() || self.name.trim().is_empty() {
+            return Err(anyhow!("Course key and name are required"));
+        }
+        if self.time_factor == 0 || self.max_days == 0 {
+            return Err(anyhow!("Time factor and max days must be positive"));
+        }
+        if self.laps == 0 || (self.laps > 1 && self.gates.is_empty()) {
+            return Err(anyhow!("Courses of several laps need at least one gate"));
+        }
+        if let (Some(opens_at), Some(closes_at)) = (self.opens_at, self.closes_at)
+            && opens_at >= closes_at
+        {
+            return Err(anyhow!("A course must open before it closes"));
+        }
+        let positions = std::iter::once(&self.start)
+            .chain(self.gates.iter().map(|gate| &gate.center))
+            .chain(std::iter::once(&self.finish_line.center))
+            .chain(self.route_waypoints.iter().flatten());
+        for position in positions {
+            if !(-180.0..=180.0).contains(&position.lng) || !(-90.0..=90.0).contains(&position.lat)
+            {
+                return Err(anyhow!(
+                    "Position {}, {} is out of -180..180, -90..90",
+                    position.lng,
+                    position.lat
+                ));
+            }
+        }
+        let marks = self
+            .gates
+            .iter()
+            .chain([&self.finish_line])
+            .collect::<Vec<_>>();
+        for (i, gate) in marks.iter().enumerate() {
+            if gate.length_nm <= 0.0 {
+                return Err(anyhow!("{} must have a positive length", self.mark_name(i)));
+            }
+        }
+        if self.route_waypoints.len() != self.gates.len() + 1 {
+            return Err(anyhow!(
+                "A course with {} gates has {} legs, got waypoints for {}",
+                self.gates.len(),
+                self.gates.len() + 1,
+                self.route_waypoints.len()
+            ));
+        }
+
+        // Gates out of order: the route of a leg goes through a later gate
+        // before reaching its own. The finish line may lie across the start
+        // of loop courses, so only gates are checked.
+        for leg in 0..self.gates.len() {
+            let path = self.leg_path(leg);
+            for segment in path.windows(2) {
+                if let Some(later) = (leg + 1..self.gates.len())
+                    .find(|&mark| gates::crosses_gate(&segment[0], &segment[1], marks[mark]))
+                {
+                    return Err(anyhow!(
+                        "The route to {} goes through {} first: gates must be in course order",
+                        self.mark_name(leg).to_lowercase(),
+                        self.mark_name(later).to_lowercase()
+                    ));
+                }
+            }
+        }
+
+        if let Some(energy) = &self.energy {
+            energy.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Name of the `i`th mark in errors, gates numbered from 1 then the finish line
+    fn mark_name(&self, i: usize) -> String {
+        if i < self.gates.len() {
+            format!("Gate {}", i + 1)
+        } else {
+            "Finish line".to_string()
+        }
+    }
 }
 
 fn seed_courses() -> Vec<Course> {
@@ -73,6 +364,7 @@ fn seed_courses() -> Vec<Course> {
             name: "Mini Transat 2023".to_string(),
             description: "Solo transatlantic race for 6.50m boats, from France to the Caribbean via the Canaries".to_string(),
             polar: "mini-650".to_string(),
+            boat_classes: vec![],
             // 2023-09-25T13:38:00Z in milliseconds
             start_time: 1695649080000,
             start: LngLat {
@@ -101,12 +393,24 @@ fn seed_courses() -> Vec<Course> {
             ],
             time_factor: 3000,
             max_days: 25,
+            energy: None,
+            min_depth: None,
+            laps: 1,
+            tags: vec![CourseTag::Ocean, CourseTag::Historic],
+            published: true,
+            opens_at: None,
+            closes_at: None,
         },
         Course {
             key: "rdr22".to_string(),
             name: "Route du Rhum 2022".to_string(),
             description: "Solo transatlantic race from Saint-Malo to Guadeloupe".to_string(),
             polar: "vr-imoca-full-pack".to_string(),
+            boat_classes: vec![
+                "imoca-classic".to_string(),
+                "imoca-foil".to_string(),
+                "mini-650".to_string(),
+            ],
             // 2022-11-09T13:15:00Z in milliseconds
             start_time: 1668002100000,
             start: LngLat {
@@ -120,12 +424,20 @@ fn seed_courses() -> Vec<Course> {
             route_waypoints: vec![vec![]], // Single leg with no intermediate waypoints
             time_factor: 5000,
             max_days: 21,
+            energy: None,
+            min_depth: None,
+            laps: 1,
+            tags: vec![CourseTag::Ocean, CourseTag::Historic],
+            published: true,
+            opens_at: None,
+            closes_at: None,
         },
         Course {
             key: "ore21".to_string(),
             name: "The Ocean Race Europe 2021".to_string(),
             description: "Offshore race from Lorient to Genoa via Cascais".to_string(),
             polar: "vr-imoca-full-pack".to_string(),
+            boat_classes: vec![],
             // 2021-05-29T11:45:00Z (13:45 CEST) in milliseconds
             start_time: 1622285100000,
             start: LngLat {
@@ -154,12 +466,20 @@ fn seed_courses() -> Vec<Course> {
             ],
             time_factor: 2000,
             max_days: 22,
+            energy: None,
+            min_depth: None,
+            laps: 1,
+            tags: vec![CourseTag::Offshore, CourseTag::Historic],
+            published: true,
+            opens_at: None,
+            closes_at: None,
         },
         Course {
             key: "vg20".to_string(),
             name: "Vendee Globe 2020".to_string(),
             description: "Solo non-stop around the world race via the three great capes".to_string(),
             polar: "vr-imoca-full-pack".to_string(),
+            boat_classes: vec![],
             // 2020-11-08T11:00:00+01:00 in milliseconds
             start_time: 1604833200000,
             start: LngLat {
@@ -206,6 +526,20 @@ fn seed_courses() -> Vec<Course> {
             ],
             time_factor: 8000,
             max_days: 90,
+            energy: Some(Energy {
+                capacity: 400.0,
+                autopilot: 8.0,
+                instruments: 2.0,
+                solar: 6.0,
+                hydro: 0.4,
+                hydro_drag: 0.03,
+            }),
+            min_depth: None,
+            laps: 1,
+            tags: vec![CourseTag::Ocean, CourseTag::Historic],
+            published: true,
+            opens_at: None,
+            closes_at: None,
         },
     ]
 }
@@ -240,8 +574,42 @@ pub async fn get_all() -> Result<Vec<Course>> {
     Ok(courses)
 }
 
+/// Courses matching every filter of the query, in list order
+pub async fn search(query: &CourseQuery) -> Result<Vec<Course>> {
+    let text = query.q.as_deref().unwrap_or_default().trim();
+    // Case insensitive on both databases, wildcards in the text are literal
+    let pattern = (!text.is_empty()).then(|| {
+        let escaped = text
+            .to_lowercase()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        format!("%{}%", escaped)
+    });
+
+    let rows: Vec<(String,)> = sqlx::query_as(
+        "SELECT data FROM courses
+         WHERE ($1 IS NULL OR key IN (SELECT course_key FROM course_tags WHERE tag = $1))
+           AND ($2 IS NULL OR max_days >= $2)
+           AND ($3 IS NULL OR max_days <= $3)
+           AND ($4 IS NULL OR LOWER(name) LIKE $4 ESCAPE '\\' OR LOWER(key) LIKE $4 ESCAPE '\\')
+         ORDER BY position, created_at",
+    )
+    .bind(query.tag.map(|tag| tag.as_str()))
+    .bind(query.min_days.map(|days| days as i64))
+    .bind(query.max_days.map(|days| days as i64))
+    .bind(pattern)
+    .fetch_all(db::pool())
+    .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(data,)| serde_json::from_str::<Course>(&data).ok())
+        .collect())
+}
+
+#[tracing::instrument]
 pub async fn get_by_key(key: &str) -> Result<Option<Course>> {
-    let row: Option<(String,)> = sqlx::query_as("SELECT data FROM courses WHERE key = ?")
+    let row: Option<(String,)> = sqlx::query_as("SELECT data FROM courses WHERE key = $1")
         .bind(key)
         .fetch_optional(db::pool())
         .await?;
@@ -254,38 +622,79 @@ pub async fn get_by_key(key: &str) -> Result<Option<Course>> {
 
 pub async fn insert(course: &Course) -> Result<()> {
     let data = serde_json::to_string(course)?;
+    let mut tx = db::pool().begin().await?;
     sqlx::query(
-        "INSERT INTO courses (key, data, position) VALUES (?, ?, (SELECT COALESCE(MAX(position), 0) + 1 FROM courses))",
+        "INSERT INTO courses (key, data, name, max_days, position) VALUES ($1, $2, $3, $4, (SELECT COALESCE(MAX(position), 0) + 1 FROM courses))",
     )
     .bind(&course.key)
     .bind(&data)
-    .execute(db::pool())
+    .bind(&course.name)
+    .bind(course.max_days as i64)
+    .execute(&mut *tx)
     .await?;
+    save_tags(&mut tx, &course.key, &course.tags).await?;
+    tx.commit().await?;
     Ok(())
 }
 
 pub async fn update(key: &str, course: &Course) -> Result<()> {
     let data = serde_json::to_string(course)?;
-    sqlx::query("UPDATE courses SET data = ?, updated_at = strftime('%s', 'now') * 1000 WHERE key = ?")
-        .bind(&data)
+    let mut tx = db::pool().begin().await?;
+    sqlx::query(
+        "UPDATE courses SET data = $1, name = $2, max_days = $3, updated_at = $4 WHERE key = $5",
+    )
+    .bind(&data)
+    .bind(&course.name)
+    .bind(course.max_days as i64)
+    .bind(chrono::Utc::now().timestamp_millis())
+    .bind(key)
+    .execute(&mut *tx)
+    .await?;
+    save_tags(&mut tx, key, &course.tags).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Replace the indexed tags of a course
+async fn save_tags(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    key: &str,
+    tags: &[CourseTag],
+) -> Result<()> {
+    sqlx::query("DELETE FROM course_tags WHERE course_key = $1")
         .bind(key)
-        .execute(db::pool())
+        .execute(&mut **tx)
         .await?;
+    for tag in tags {
+        sqlx::query(
+            "INSERT INTO course_tags (course_key, tag) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(key)
+        .bind(tag.as_str())
+        .execute(&mut **tx)
+        .await?;
+    }
     Ok(())
 }
 
 pub async fn delete(key: &str) -> Result<()> {
-    sqlx::query("DELETE FROM courses WHERE key = ?")
+    let mut tx = db::pool().begin().await?;
+    sqlx::query("DELETE FROM course_tags WHERE course_key = $1")
+        .bind(key)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM courses WHERE key = $1")
         .bind(key)
-        .execute(db::pool())
+        .execute(&mut *tx)
         .await?;
+    tx.commit().await?;
     Ok(())
 }
 
 pub async fn reorder(keys: &[String]) -> Result<()> {
     let mut tx = db::pool().begin().await?;
     for (i, key) in keys.iter().enumerate() {
-        sqlx::query("UPDATE courses SET position = ? WHERE key = ?")
+        sqlx::query("UPDATE courses SET position = $1 WHERE key = $2")
             .bind(i as i64)
             .bind(key)
             .execute(&mut *tx)
@@ -330,3 +739,316 @@ pub async fn restore(path: PathBuf) -> Result<()> {
     log::info!("Restored {} courses from {}", courses.len(), path.display());
     Ok(())
 }
+
+// ============================================================================
+// Reload
+// ============================================================================
+
+/// Store the courses of a JSON file in the `dump-courses` format that differ
+/// from the database, after checking all of them. Returns the keys of the
+/// courses inserted or updated.
+pub async fn load_file(path: &Path) -> Result<Vec<String>> {
+    let contents = tokio::fs::read_to_string(path).await?;
+    let courses = parse_courses_file(&contents)?;
+
+    let mut changed = Vec::new();
+    for course in &courses {
+        match get_by_key(&course.key).await? {
+            Some(current) if serde_json::to_value(&current)? == serde_json::to_value(course)? => {
+                continue;
+            }
+            Some(_) => update(&course.key, course).await?,
+            None => insert(course).await?,
+        }
+        changed.push(course.key.clone());
+    }
+    Ok(changed)
+}
+
+/// Courses of a courses file, failing on the first invalid one
+fn parse_courses_file(contents: &str) -> Result<Vec<Course>> {
+    let courses: Vec<Course> = serde_json::from_str(contents)?;
+    for course in &courses {
+        course
+            .validate()
+            .with_context(|| format!("Invalid course {}", course.key))?;
+    }
+    Ok(courses)
+}
+
+/// Re-read the courses file of `REWIND_COURSES_FILE` and drop the metrics
+/// of the courses it changed, to be recomputed by
+/// `course_metrics::spawn_refresh`. Races read their course from the
+/// database when created, the lobbies already open keep theirs.
+pub async fn reload() -> Result<Vec<String>> {
+    let file = config()
+        .courses_file
+        .as_ref()
+        .ok_or_else(|| anyhow!("No courses file to reload, REWIND_COURSES_FILE is unset"))?;
+    let changed = load_file(Path::new(file)).await?;
+    for key in &changed {
+        course_metrics::invalidate(key).await?;
+    }
+    Ok(changed)
+}
+
+/// Check that a course can be raced: a wind report in every slot of its race
+/// window, their rasters in S3 and its polars stored. Prints a report and
+/// returns whether every check passed.
+pub async fn check(key: &str) -> Result<bool> {
+    let course = get_by_key(key)
+        .await?
+        .ok_or_else(|| anyhow!("Unknown course {}", key))?;
+    println!("Course {} ({})", course.key, course.name);
+
+    let status = |passed: bool| if passed { "PASS" } else { "FAIL" };
+    let report = |check: &str, failures: &[String], passed_details: String| {
+        if failures.is_empty() {
+            println!("  {} {check}: {passed_details}", status(true));
+        } else {
+            let count = failures.len();
+            println!(
+                "  {} {check}: {count} missing: {}",
+                status(false),
+                failures.join(", ")
+            );
+        }
+        failures.is_empty()
+    };
+
+    let since = course.start_time - chrono::TimeDelta::days(1).num_milliseconds();
+    let reports = wind_reports::get_reports_between(since, course.max_finish_time()).await?;
+    let times = reports
+        .iter()
+        .map(|r| r.time.timestamp_millis())
+        .collect::<Vec<_>>();
+    let missing_slots = wind_reports::missing_slots(&times, &course)
+        .into_iter()
+        .map(format_time)
+        .collect::<Vec<_>>();
+    let wind_ok = report(
+        "wind reports",
+        &missing_slots,
+        format!("{} reports", reports.len()),
+    );
+
+    let missing_rasters = stream::iter(&reports)
+        .map(|r| async move {
+            let path = object_store::path::Path::from(r.png_path.as_str());
+            match s3::raster_client().head(&path).await {
+                Ok(_) => Ok(None),
+                Err(object_store::Error::NotFound { .. }) => Ok(Some(r.png_path.clone())),
+                Err(e) => Err(e),
+            }
+        })
+        .buffered(8)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .filter_map(|result| result.transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+    let rasters_ok = report(
+        "rasters",
+        &missing_rasters,
+        format!("{} in S3", reports.len()),
+    );
+
+    let mut unknown_polars = Vec::new();
+    for class in course.classes() {
+        if polars::get(class).await?.is_none() {
+            unknown_polars.push(class.to_string());
+        }
+    }
+    let polars_ok = report("polars", &unknown_polars, course.classes().join(", "));
+
+    let passed = wind_ok && rasters_ok && polars_ok;
+    println!("{}", status(passed));
+    Ok(passed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn course() -> Course {
+        Course {
+            key: "test".to_string(),
+            name: "Test".to_string(),
+            description: String::new(),
+            polar: "mini-650".to_string(),
+            boat_classes: vec![],
+            start_time: 0,
+            start: LngLat { lng: 0.0, lat: 0.0 },
+            start_heading: 270.0,
+            finish_line: Gate::vertical(-30.0, 0.0, 10.0),
+            gates: vec![
+                Gate::vertical(-10.0, 0.0, 10.0),
+                Gate::vertical(-20.0, 0.0, 10.0),
+            ],
+            route_waypoints: vec![vec![], vec![], vec![]],
+            time_factor: 1000,
+            max_days: 10,
+            energy: None,
+            min_depth: None,
+            laps: 1,
+            tags: vec![],
+            published: true,
+            opens_at: None,
+            closes_at: None,
+        }
+    }
+
+    #[test]
+    fn test_lng_lat_checked() {
+        let wrapped: LngLat = serde_json::from_str(r#"{"lng":540,"lat":45}"#).unwrap();
+        assert_eq!(wrapped.lng, -180.0);
+        assert!(serde_json::from_str::<LngLat>(r#"{"lng":0,"lat":95}"#).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(course().validate().is_ok());
+        for course in seed_courses() {
+            assert!(course.validate().is_ok(), "{}", course.key);
+        }
+
+        let mut swapped = course();
+        swapped.gates.reverse();
+        assert_eq!(
+            swapped.validate().unwrap_err().to_string(),
+            "The route to gate 1 goes through gate 2 first: gates must be in course order"
+        );
+
+        let mut missing_leg = course();
+        missing_leg.route_waypoints.pop();
+        assert_eq!(
+            missing_leg.validate().unwrap_err().to_string(),
+            "A course with 2 gates has 3 legs, got waypoints for 2"
+        );
+
+        let mut no_gates = course();
+        no_gates.gates.clear();
+        no_gates.route_waypoints = vec![vec![]];
+        assert!(no_gates.validate().is_ok());
+        no_gates.laps = 2;
+        assert!(no_gates.validate().is_err());
+
+        let mut out_of_range = course();
+        out_of_range.start.lat = 95.0;
+        assert!(out_of_range.validate().is_err());
+    }
+
+    #[test]
+    fn test_laps() {
+        let mut laps = course();
+        laps.laps = 3;
+        assert_eq!(laps.finish_index(), 6);
+        let marks = laps.marks();
+        assert_eq!(marks.len(), 7);
+        assert_eq!(marks[4].center.lng, -10.0);
+        assert_eq!(marks[5].center.lng, -20.0);
+        assert_eq!(marks[6].center.lng, -30.0);
+        assert!(laps.mark(7).is_none());
+
+        let completed = (0..=6)
+            .filter(|&i| laps.completes_lap(i))
+            .collect::<Vec<_>>();
+        assert_eq!(completed, vec![1, 3, 5]);
+        assert!(!course().completes_lap(1));
+
+        // Back to the first gate from the last one, on the waypoints of the first leg
+        laps.route_waypoints[0] = vec![LngLat {
+            lng: -15.0,
+            lat: 1.0,
+        }];
+        let lngs = |path: Vec<LngLat>| path.iter().map(|p| p.lng).collect::<Vec<_>>();
+        assert_eq!(lngs(laps.leg_path(2)), vec![-20.0, -15.0, -10.0]);
+        assert_eq!(lngs(laps.leg_path(6)), vec![-20.0, -30.0]);
+        assert_eq!(lngs(laps.leg_path(9)), vec![-20.0, -30.0]);
+    }
+
+    #[test]
+    fn test_check_open() {
+        let day = 24 * 60 * 60 * 1000;
+        let error = |course: &Course, now| course.check_open(now).unwrap_err().to_string();
+        let mut seasonal = course();
+        assert!(seasonal.check_open(0).is_ok());
+
+        // 2025-06-01 to 2025-09-01
+        let (june, september) = (1748736000000, 1756684800000);
+        seasonal.opens_at = Some(june);
+        seasonal.closes_at = Some(september);
+        assert!(seasonal.validate().is_ok());
+        assert_eq!(
+            error(&seasonal, june - day),
+            "Test opens on 2025-06-01 00:00 UTC"
+        );
+        assert!(seasonal.check_open(june).is_ok());
+        assert_eq!(
+            error(&seasonal, september),
+            "Test closed on 2025-09-01 00:00 UTC"
+        );
+
+        seasonal.published = false;
+        assert_eq!(error(&seasonal, june + day), "Test is not published");
+
+        seasonal.closes_at = seasonal.opens_at;
+        assert!(seasonal.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_courses_file() {
+        let mut courses = seed_courses();
+        let parsed = parse_courses_file(&serde_json::to_string(&courses).unwrap()).unwrap();
+        assert_eq!(parsed.len(), courses.len());
+
+        courses[1].max_days = 0;
+        let error = parse_courses_file(&serde_json::to_string(&courses).unwrap()).unwrap_err();
+        assert_eq!(error.to_string(), format!("Invalid course {}", courses[1].key));
+        assert!(parse_courses_file("{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        db::init_test().await.unwrap();
+        let keys = |query: CourseQuery| async move {
+            search(&query)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|course| course.key)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            keys(CourseQuery::default()).await,
+            vec!["mt23", "rdr22", "ore21", "vg20"]
+        );
+        let offshore = CourseQuery {
+            tag: Some(CourseTag::Offshore),
+            ..Default::default()
+        };
+        assert_eq!(keys(offshore).await, vec!["ore21"]);
+        let short = CourseQuery {
+            tag: Some(CourseTag::Historic),
+            max_days: Some(22),
+            ..Default::default()
+        };
+        assert_eq!(keys(short).await, vec!["rdr22", "ore21"]);
+        let long = CourseQuery {
+            min_days: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(keys(long).await, vec!["vg20"]);
+        let text = CourseQuery {
+            q: Some(" RHUM ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(keys(text).await, vec!["rdr22"]);
+        let wildcard = CourseQuery {
+            q: Some("%".to_string()),
+            ..Default::default()
+        };
+        assert!(keys(wildcard).await.is_empty());
+    }
+}
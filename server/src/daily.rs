@@ -0,0 +1,181 @@
+use anyhow::Result;
+use chrono::{NaiveDate, TimeDelta, Utc};
+use serde::Serialize;
+
+use crate::{courses, db, wind_reports};
+
+/// Today's challenge: a course raced in a historical weather window
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyChallenge {
+    pub date: String,
+    pub course_key: String,
+    /// Course start time for the day's weather window (Unix ms)
+    pub start_time: i64,
+    /// When the challenge and its leaderboard reset (next midnight UTC, Unix ms)
+    pub resets_at: i64,
+}
+
+/// Date key of the current UTC day
+pub fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+pub fn date_key(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+fn resets_at(date: NaiveDate) -> i64 {
+    (date + TimeDelta::days(1))
+        .and_hms_opt(0, 0, 0)
+        .map(|t| t.and_utc().timestamp_millis())
+        .unwrap_or_default()
+}
+
+/// SplitMix64: a tiny deterministic generator, so the pick for a given day
+/// doesn't depend on library versions
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Pick the course and weather window for a day. The same inputs always give
+/// the same pick. Courses without a fully covered window are skipped.
+fn pick(
+    date: NaiveDate,
+    courses: &[courses::Course],
+    report_times: &[i64],
+) -> Option<(String, i64)> {
+    let days = date.signed_duration_since(NaiveDate::default()).num_days() as u64;
+    let seed = splitmix64(days);
+
+    let candidates: Vec<(&courses::Course, Vec<i64>)> = courses
+        .iter()
+        .map(|c| (c, wind_reports::covered_start_times(report_times, c)))
+        .filter(|(_, starts)| !starts.is_empty())
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let (course, starts) = &candidates[(seed % candidates.len() as u64) as usize];
+    let start = starts[(splitmix64(seed) % starts.len() as u64) as usize];
+    Some((course.key.clone(), start))
+}
+
+/// Get the challenge of a day, picking and storing it on first request.
+pub async fn get_or_pick(date: NaiveDate) -> Result<Option<DailyChallenge>> {
+    let key = date_key(date);
+
+    let existing: Option<(String, i64)> =
+        sqlx::query_as("SELECT course_key, start_time FROM daily_challenges WHERE date = $1")
+            .bind(&key)
+            .fetch_optional(db::pool())
+            .await?;
+
+    let (course_key, start_time) = match existing {
+        Some(row) => row,
+        None => {
+            // Only courses open at the start of the day
+            let opening = resets_at(date) - TimeDelta::days(1).num_milliseconds();
+            let open_courses: Vec<courses::Course> = courses::get_all()
+                .await?
+                .into_iter()
+                .filter(|course| course.check_open(opening).is_ok())
+                .collect();
+            let times = wind_reports::get_all_times().await?;
+            let Some((course_key, start_time)) = pick(date, &open_courses, &times) else {
+                return Ok(None);
+            };
+            // Concurrent first requests pick the same thing; keep whichever lands first
+            sqlx::query(
+                "INSERT INTO daily_challenges (date, course_key, start_time) VALUES ($1, $2, $3)
+                 ON CONFLICT DO NOTHING",
+            )
+            .bind(&key)
+            .bind(&course_key)
+            .bind(start_time)
+            .execute(db::pool())
+            .await?;
+            (course_key, start_time)
+        }
+    };
+
+    Ok(Some(DailyChallenge {
+        date: key,
+        course_key,
+        start_time,
+        resets_at: resets_at(date),
+    }))
+}
+
+/// Get today's challenge
+pub async fn current() -> Result<Option<DailyChallenge>> {
+    get_or_pick(today()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::courses::{Course, Gate, LngLat};
+
+    fn course(key: &str) -> Course {
+        Course {
+            key: key.to_string(),
+            name: key.to_string(),
+            description: String::new(),
+            polar: "vr-imoca-full-pack".to_string(),
+            boat_classes: vec![],
+            start_time: 0,
+            start: LngLat { lng: 0.0, lat: 0.0 },
+            start_heading: 0.0,
+            finish_line: Gate::vertical(1.0, 0.0, 1.0),
+            gates: vec![],
+            route_waypoints: vec![vec![]],
+            time_factor: 1000,
+            max_days: 1,
+            energy: None,
+            min_depth: None,
+            laps: 1,
+            tags: vec![],
+            published: true,
+            opens_at: None,
+            closes_at: None,
+        }
+    }
+
+    #[test]
+    fn test_pick_is_deterministic() {
+        let six_hours = 6 * 60 * 60 * 1000;
+        let times: Vec<i64> = (0..40).map(|i| i * six_hours).collect();
+        let courses = vec![course("a"), course("b"), course("c")];
+        let date = NaiveDate::from_ymd_opt(2025, 3, 14).unwrap();
+
+        let first = pick(date, &courses, &times).unwrap();
+        assert_eq!(pick(date, &courses, &times).unwrap(), first);
+        assert!(wind_reports::covered_start_times(&times, &courses[0]).contains(&first.1));
+
+        // Different days eventually pick different challenges
+        let picks: std::collections::HashSet<_> = (1..=10)
+            .filter_map(|d| pick(NaiveDate::from_ymd_opt(2025, 3, d).unwrap(), &courses, &times))
+            .collect();
+        assert!(picks.len() > 1);
+
+        assert!(pick(date, &courses, &[]).is_none());
+    }
+
+    #[test]
+    fn test_resets_at_next_midnight() {
+        let date = NaiveDate::from_ymd_opt(2025, 3, 14).unwrap();
+        let midnight = NaiveDate::from_ymd_opt(2025, 3, 15)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis();
+        assert_eq!(resets_at(date), midnight);
+        assert_eq!(date_key(date), "2025-03-14");
+    }
+}
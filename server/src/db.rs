@@ -1,19 +1,98 @@
+//! Database pool of the server: SQLite by default, Postgres when
+//! `REWIND_DATABASE_URL` is a `postgres://` URL.
+//!
+//! Queries are written once for both: numbered `$1` placeholders, `ON
+//! CONFLICT` instead of `INSERT OR`, `RETURNING` for generated ids, and
+//! times as integer milliseconds bound from Rust rather than SQL clock
+//! functions. Each backend has its migrations, `migrations/` for SQLite and
+//! `migrations/postgres/` for Postgres, as `.up.sql` and `.down.sql` pairs
+//! so `rewind db revert` can undo them.
+
 use anyhow::Result;
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use sqlx::AnyPool;
+use sqlx::any::AnyPoolOptions;
+use sqlx::migrate::{Migrate, Migrator};
 use std::sync::OnceLock;
 
 use crate::config::config;
 
-static POOL: OnceLock<SqlitePool> = OnceLock::new();
+static POOL: OnceLock<AnyPool> = OnceLock::new();
 
-pub async fn init() -> Result<()> {
-    let pool = SqlitePoolOptions::new()
+static SQLITE_MIGRATIONS: Migrator = sqlx::migrate!("./migrations");
+static POSTGRES_MIGRATIONS: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Connect to the database at `url` and bring its schema up to date
+async fn connect(url: &str) -> Result<AnyPool> {
+    let pool = connect_unmigrated(url).await?;
+    migrations(url).run(&pool).await?;
+    Ok(pool)
+}
+
+/// Connect to the database at `url` as it is, for the `db` commands
+async fn connect_unmigrated(url: &str) -> Result<AnyPool> {
+    sqlx::any::install_default_drivers();
+    let pool = AnyPoolOptions::new()
         .max_connections(5)
-        .connect(&config().database_url)
+        .connect(url)
         .await?;
+    Ok(pool)
+}
 
-    // Run migrations
-    sqlx::migrate!("./migrations").run(&pool).await?;
+fn migrations(url: &str) -> &'static Migrator {
+    if is_postgres(url) {
+        &POSTGRES_MIGRATIONS
+    } else {
+        &SQLITE_MIGRATIONS
+    }
+}
+
+fn is_postgres(url: &str) -> bool {
+    url.starts_with("postgres:") || url.starts_with("postgresql:")
+}
+
+/// Apply the pending migrations, without seeding
+pub async fn migrate() -> Result<()> {
+    let url = &config().database_url;
+    let pool = connect_unmigrated(url).await?;
+    migrations(url).run(&pool).await?;
+    log::info!("Database schema up to date");
+    Ok(())
+}
+
+/// Print the migrations of the database, applied or pending
+pub async fn print_status() -> Result<()> {
+    let url = &config().database_url;
+    let pool = connect_unmigrated(url).await?;
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+
+    let ups = migrations(url)
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration());
+    for m in ups {
+        let state = if applied.iter().any(|a| a.version == m.version) {
+            "applied"
+        } else {
+            "pending"
+        };
+        println!("{}\t{}\t{}", m.version, state, m.description);
+    }
+    Ok(())
+}
+
+/// Undo the applied migrations newer than `version`, latest first. Version 0
+/// empties the database.
+pub async fn revert(version: i64) -> Result<()> {
+    let url = &config().database_url;
+    let pool = connect_unmigrated(url).await?;
+    migrations(url).undo(&pool, version).await?;
+    log::info!("Database schema reverted to version {}", version);
+    Ok(())
+}
+
+pub async fn init() -> Result<()> {
+    let pool = connect(&config().database_url).await?;
 
     POOL.set(pool)
         .map_err(|_| anyhow::anyhow!("Pool already initialized"))?;
@@ -24,10 +103,19 @@ pub async fn init() -> Result<()> {
     Ok(())
 }
 
-pub fn pool() -> &'static SqlitePool {
+pub fn pool() -> &'static AnyPool {
     POOL.get().expect("Database not initialized - call db::init() first")
 }
 
+/// Placeholders `$first, $first + 1, ...` of `count` arguments, for lists
+/// of values bound one by one
+pub fn placeholders(first: usize, count: usize) -> String {
+    (first..first + count)
+        .map(|n| format!("${}", n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[cfg(test)]
 static TEST_INIT_DONE: OnceLock<()> = OnceLock::new();
 
@@ -54,13 +142,29 @@ pub async fn init_test() -> Result<()> {
 
     // Initialize if pool not set
     if POOL.get().is_none() {
-        let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect("sqlite::memory:")
-            .await?;
-
-        // Run migrations
-        sqlx::migrate!("./migrations").run(&pool).await?;
+        let pool = match std::env::var("REWIND_TEST_DATABASE_URL") {
+            // A Postgres database, emptied first
+            Ok(url) => {
+                use sqlx::Connection;
+
+                sqlx::any::install_default_drivers();
+                let mut conn = sqlx::AnyConnection::connect(&url).await?;
+                sqlx::raw_sql("DROP SCHEMA public CASCADE; CREATE SCHEMA public")
+                    .execute(&mut conn)
+                    .await?;
+                conn.close().await?;
+                connect(&url).await?
+            }
+            // Each `sqlite::memory:` connection is a separate empty database and
+            // pooled connections get recycled across test runtimes, so back the
+            // test pool with a per-process temporary file instead.
+            Err(_) => {
+                let path =
+                    std::env::temp_dir().join(format!("rewind-test-{}.db", std::process::id()));
+                let _ = std::fs::remove_file(&path);
+                connect(&format!("sqlite:{}?mode=rwc", path.display())).await?
+            }
+        };
 
         // Try to set the pool
         let _ = POOL.set(pool);
@@ -74,3 +178,39 @@ pub async fn init_test() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_migrations_revert() {
+        let path =
+            std::env::temp_dir().join(format!("rewind-test-migrations-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pool = connect(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO race_results (course_key, player_name, player_id, finish_time, race_start_time, path_s3_key)
+             VALUES ('vg20', 'Alice', 'p1', 1000, 0, 'paths/1')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        SQLITE_MIGRATIONS.undo(&pool, 0).await.unwrap();
+        let tables: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM sqlite_master
+             WHERE type = 'table' AND name <> '_sqlx_migrations' AND name NOT LIKE 'sqlite_%'",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert!(tables.is_empty(), "left {:?}", tables);
+
+        SQLITE_MIGRATIONS.run(&pool).await.unwrap();
+        pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+}
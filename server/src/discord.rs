@@ -0,0 +1,257 @@
+//! Race events posted to a Discord channel through the webhook of
+//! `REWIND_DISCORD_WEBHOOK_URL`, for community servers: public races created
+//! or opened by the scheduler, race starts, and finishes entering the top 10
+//! of a course's Hall of Fame. `REWIND_DISCORD_EVENTS` picks the events.
+//!
+//! Messages are formatted here as embeds and posted in the background, so a
+//! slow or failing Discord never holds up a race.
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use serde_json::{Value, json};
+
+use crate::config::{DiscordEvent, config};
+use crate::{courses, units};
+
+/// Attempts at posting a message, Discord asking to slow down included
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Rank a finish must reach in the Hall of Fame to be announced
+pub const TOP_RANK: u32 = 10;
+
+/// Embed colors
+const BLUE: u32 = 0x3b82f6;
+const GREEN: u32 = 0x22c55e;
+const GOLD: u32 = 0xeab308;
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+/// A race event for Discord
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    RaceCreated {
+        course_key: String,
+        /// Player who created the race, None for scheduled races
+        creator: Option<String>,
+        /// Start of a scheduled race (unix ms)
+        start_at: Option<i64>,
+    },
+    RaceStarted {
+        course_key: String,
+        players: usize,
+    },
+    TopTen {
+        course_key: String,
+        polar: String,
+        player: String,
+        rank: u32,
+        finish_time: i64,
+        result_id: i64,
+    },
+}
+
+impl Notification {
+    fn event(&self) -> DiscordEvent {
+        match self {
+            Notification::RaceCreated { .. } => DiscordEvent::Created,
+            Notification::RaceStarted { .. } => DiscordEvent::Started,
+            Notification::TopTen { .. } => DiscordEvent::TopTen,
+        }
+    }
+
+    fn course_key(&self) -> &str {
+        match self {
+            Notification::RaceCreated { course_key, .. }
+            | Notification::RaceStarted { course_key, .. }
+            | Notification::TopTen { course_key, .. } => course_key,
+        }
+    }
+
+    /// Webhook payload of the event, on the course named `course`
+    fn payload(&self, course: &str, client_url: &str) -> Value {
+        let course = escape(course);
+        let (title, description, url, color) = match self {
+            Notification::RaceCreated {
+                creator, start_at, ..
+            } => {
+                let description = match (creator, start_at) {
+                    (_, Some(start_at)) => format!(
+                        "The lobby is open, the race starts <t:{}:R>.",
+                        start_at / 1000
+                    ),
+                    (Some(creator), None) => {
+                        format!("{} is waiting for opponents.", escape(creator))
+                    }
+                    (None, None) => "Join before the start.".to_string(),
+                };
+                (
+                    format!("New race on {}", course),
+                    description,
+                    client_url.to_string(),
+                    BLUE,
+                )
+            }
+            Notification::RaceStarted { players, .. } => (
+                format!("Race started on {}", course),
+                match players {
+                    1 => "1 boat crossed the start line.".to_string(),
+                    n => format!("{} boats crossed the start line.", n),
+                },
+                client_url.to_string(),
+                GREEN,
+            ),
+            Notification::TopTen {
+                polar,
+                player,
+                rank,
+                finish_time,
+                result_id,
+                ..
+            } => (
+                format!(
+                    "{} enters the top {} on {}",
+                    escape(player),
+                    TOP_RANK,
+                    course
+                ),
+                format!(
+                    "#{} in {} with {}",
+                    rank,
+                    escape(polar),
+                    units::format_elapsed(*finish_time)
+                ),
+                format!("{}/?replay={}", client_url, result_id),
+                GOLD,
+            ),
+        };
+        json!({
+            "username": "Re:wind",
+            // Names are the players', never ping anyone
+            "allowed_mentions": { "parse": [] },
+            "embeds": [{
+                "title": title,
+                "description": description,
+                "url": url,
+                "color": color,
+            }],
+        })
+    }
+}
+
+/// Escape the Discord markdown of a name
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#' | '[' | ']' | '<'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Whether an event is posted to Discord
+pub fn enabled(event: DiscordEvent) -> bool {
+    let cfg = config();
+    cfg.discord_webhook_url.is_some() && cfg.discord_events.contains(&event)
+}
+
+/// Post a notification in the background, when its event is enabled
+pub fn notify(notification: Notification) {
+    if !enabled(notification.event()) {
+        return;
+    }
+    tokio::spawn(async move {
+        if let Err(e) = post(&notification).await {
+            log::warn!("Failed to notify Discord of {:?}: {}", notification, e);
+        }
+    });
+}
+
+async fn post(notification: &Notification) -> Result<()> {
+    let cfg = config();
+    let Some(webhook_url) = cfg.discord_webhook_url.as_deref() else {
+        return Ok(());
+    };
+    let course = courses::get_by_key(notification.course_key())
+        .await?
+        .map_or_else(
+            || notification.course_key().to_string(),
+            |course| course.name,
+        );
+    let payload = notification.payload(&course, cfg.client_url.trim_end_matches('/'));
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let response = CLIENT.post(webhook_url).json(&payload).send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        if attempt == MAX_ATTEMPTS || !(status.as_u16() == 429 || status.is_server_error()) {
+            bail!("{}: {}", status, response.text().await.unwrap_or_default());
+        }
+        // Discord tells how long to wait when rate limiting, in seconds
+        let retry_after = response
+            .json::<Value>()
+            .await
+            .ok()
+            .and_then(|body| body["retry_after"].as_f64())
+            .unwrap_or(attempt as f64);
+        tokio::time::sleep(Duration::from_secs_f64(retry_after.min(60.0))).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("_Bob_ **"), "\\_Bob\\_ \\*\\*");
+        assert_eq!(escape("Éole"), "Éole");
+    }
+
+    #[test]
+    fn test_payloads() {
+        let created = Notification::RaceCreated {
+            course_key: "vg20".to_string(),
+            creator: Some("Alice".to_string()),
+            start_at: None,
+        };
+        let payload = created.payload("Vendée Globe", "https://rewind.example");
+        let embed = &payload["embeds"][0];
+        assert_eq!(embed["title"], "New race on Vendée Globe");
+        assert_eq!(embed["description"], "Alice is waiting for opponents.");
+        assert_eq!(payload["allowed_mentions"]["parse"], json!([]));
+
+        let scheduled = Notification::RaceCreated {
+            course_key: "vg20".to_string(),
+            creator: None,
+            start_at: Some(1_700_000_000_000),
+        };
+        assert_eq!(
+            scheduled.payload("Vendée Globe", "")["embeds"][0]["description"],
+            "The lobby is open, the race starts <t:1700000000:R>."
+        );
+
+        let top_ten = Notification::TopTen {
+            course_key: "vg20".to_string(),
+            polar: "imoca".to_string(),
+            player: "Bob".to_string(),
+            rank: 3,
+            finish_time: 4_000,
+            result_id: 42,
+        };
+        assert_eq!(top_ten.event(), DiscordEvent::TopTen);
+        let embed = &top_ten.payload("Vendée Globe", "https://rewind.example")["embeds"][0];
+        assert_eq!(embed["title"], "Bob enters the top 10 on Vendée Globe");
+        assert_eq!(embed["description"], "#3 in imoca with 0d 00:00:04");
+        assert_eq!(embed["url"], "https://rewind.example/?replay=42");
+    }
+}
@@ -1,7 +1,25 @@
+//! Emails of the server, sent by the provider chosen in the configuration:
+//! the Resend API, or any SMTP server for deployments without a Resend
+//! account. Without either, emails are logged instead (development).
+
+use std::sync::LazyLock;
+
 use anyhow::Result;
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
 use serde::Serialize;
 
-use crate::config::config;
+use crate::config::{EmailProvider, config};
+use crate::email_templates::{self, Locale, RaceSummary};
+use crate::{db, email_queue};
+
+/// Service delivering an HTML email
+#[async_trait]
+pub trait EmailSender: Send + Sync {
+    async fn send(&self, to: &str, subject: &str, html: String) -> Result<()>;
+}
 
 #[derive(Serialize)]
 struct ResendEmailRequest {
@@ -11,51 +29,182 @@ struct ResendEmailRequest {
     html: String,
 }
 
-/// Send a verification code to the given email address using Resend API.
-pub async fn send_verification_code(email: &str, code: &str) -> Result<()> {
-    let cfg = config();
-
-    // In dev mode without API key, just log the code
-    if cfg.resend_api_key.is_empty() {
+/// Queue a verification code for the given email address, sent until it
+/// expires.
+pub async fn send_verification_code(
+    email: &str,
+    code: &str,
+    locale: Locale,
+    expires_at: i64,
+) -> Result<()> {
+    // In dev mode without a provider, just log the code
+    if sender().is_none() {
         log::info!("DEV MODE: Verification code for {}: {}", email, code);
         return Ok(());
     }
 
-    let client = reqwest::Client::new();
-
-    let request = ResendEmailRequest {
-        from: cfg.email_from.clone(),
-        to: vec![email.to_string()],
-        subject: "Your Re:wind verification code".to_string(),
-        html: format!(
-            r#"<div style="font-family: sans-serif; max-width: 400px; margin: 0 auto; padding: 20px;">
-                <h1 style="color: #1e293b; font-size: 24px; margin-bottom: 16px;">Re:wind</h1>
-                <p style="color: #475569; margin-bottom: 24px;">Your verification code is:</p>
-                <div style="background: #f1f5f9; border-radius: 8px; padding: 16px; text-align: center; margin-bottom: 24px;">
-                    <span style="font-family: monospace; font-size: 32px; font-weight: bold; letter-spacing: 4px; color: #0f172a;">{}</span>
-                </div>
-                <p style="color: #94a3b8; font-size: 14px;">This code expires in 10 minutes.</p>
-            </div>"#,
-            code
-        ),
-    };
-
-    let response = client
-        .post("https://api.resend.com/emails")
-        .header("Authorization", format!("Bearer {}", cfg.resend_api_key))
-        .json(&request)
-        .send()
-        .await?;
-
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        anyhow::bail!("Failed to send email: {}", error_text);
+    let message = email_templates::verification_code(locale, code)?;
+    email_queue::enqueue(email, &message.subject, message.html, Some(expires_at)).await?;
+
+    log::info!("Verification code queued for {}", email);
+    Ok(())
+}
+
+/// Queue a one-click sign in link for the given email address, sent until
+/// it expires.
+pub async fn send_magic_link(
+    email: &str,
+    url: &str,
+    locale: Locale,
+    expires_at: i64,
+) -> Result<()> {
+    // In dev mode without a provider, just log the link
+    if sender().is_none() {
+        log::info!("DEV MODE: Sign in link for {}: {}", email, url);
+        return Ok(());
+    }
+
+    let message = email_templates::magic_link(locale, url)?;
+    email_queue::enqueue(email, &message.subject, message.html, Some(expires_at)).await?;
+
+    log::info!("Sign in link queued for {}", email);
+    Ok(())
+}
+
+/// Queue the summary of a finish for the player's email address.
+pub async fn send_race_summary(email: &str, locale: Locale, summary: &RaceSummary) -> Result<()> {
+    // In dev mode without a provider, just log the summary
+    if sender().is_none() {
+        log::info!("DEV MODE: Race summary for {}: {}", email, summary.url);
+        return Ok(());
     }
 
-    log::info!("Verification code sent to {}", email);
+    let message = email_templates::race_summary(locale, summary)?;
+    email_queue::enqueue(email, &message.subject, message.html, None).await?;
+
+    log::info!("Race summary queued for {}", email);
     Ok(())
 }
 
+/// Queue an announcement for every account not being deleted, each in its
+/// language. Returns the number of emails queued.
+pub async fn send_announcement(subject: &str, message: &str) -> Result<usize> {
+    let accounts: Vec<(String, Option<String>)> =
+        sqlx::query_as("SELECT email, language FROM accounts WHERE delete_at IS NULL")
+            .fetch_all(db::pool())
+            .await?;
+
+    // In dev mode without a provider, just log the announcement
+    if sender().is_none() {
+        log::info!(
+            "DEV MODE: Announcement to {} accounts: {}",
+            accounts.len(),
+            subject
+        );
+        return Ok(0);
+    }
+
+    for (email, language) in &accounts {
+        let locale = Locale::or_default(language.as_deref());
+        let rendered = email_templates::announcement(locale, subject, message)?;
+        email_queue::enqueue(email, &rendered.subject, rendered.html, None).await?;
+    }
+    log::info!("Announcement queued for {} accounts", accounts.len());
+    Ok(accounts.len())
+}
+
+static SENDER: LazyLock<Option<Box<dyn EmailSender>>> =
+    LazyLock::new(|| match config().email_provider? {
+        EmailProvider::Resend => Some(Box::new(Resend {
+            client: reqwest::Client::new(),
+        })),
+        EmailProvider::Smtp => {
+            let url = config().smtp_url.as_deref()?;
+            let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(url)
+                .expect("Invalid REWIND_SMTP_URL")
+                .build();
+            Some(Box::new(Smtp { transport }))
+        }
+    });
+
+/// Sender of the configured provider, None in dev mode
+pub fn sender() -> Option<&'static dyn EmailSender> {
+    SENDER.as_deref()
+}
+
+struct Resend {
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl EmailSender for Resend {
+    async fn send(&self, to: &str, subject: &str, html: String) -> Result<()> {
+        let cfg = config();
+        let request = ResendEmailRequest {
+            from: cfg.email_from.clone(),
+            to: vec![to.to_string()],
+            subject: subject.to_string(),
+            html,
+        };
+
+        let response = self
+            .client
+            .post("https://api.resend.com/emails")
+            .header("Authorization", format!("Bearer {}", cfg.resend_api_key))
+            .json(&request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Failed to send email: {}", error_text);
+        }
+        Ok(())
+    }
+}
+
+/// SMTP server, connections pooled by the transport
+struct Smtp {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+#[async_trait]
+impl EmailSender for Smtp {
+    async fn send(&self, to: &str, subject: &str, html: String) -> Result<()> {
+        let message = html_message(&config().email_from, to, subject, html)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+fn html_message(from: &str, to: &str, subject: &str, html: String) -> Result<Message> {
+    Ok(Message::builder()
+        .from(mailbox(from)?)
+        .to(mailbox(to)?)
+        .subject(subject)
+        .header(ContentType::TEXT_HTML)
+        .body(html)?)
+}
+
+/// `Name <address>` or a bare address, the name taken as is: lettre would
+/// want `Re:wind` quoted
+fn mailbox(text: &str) -> Result<Mailbox> {
+    match text
+        .trim()
+        .strip_suffix('>')
+        .and_then(|text| text.split_once('<'))
+    {
+        Some((name, address)) => {
+            let name = name.trim().trim_matches('"');
+            Ok(Mailbox::new(
+                (!name.is_empty()).then(|| name.to_string()),
+                address.trim().parse()?,
+            ))
+        }
+        None => Ok(text.trim().parse()?),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -63,7 +212,35 @@ mod tests {
     #[tokio::test]
     async fn test_send_without_api_key_logs_only() {
         // Without API key set, should just log and succeed
-        let result = send_verification_code("test@example.com", "123456").await;
+        let result = send_verification_code("test@example.com", "123456", Locale::En, 0).await;
+        assert!(result.is_ok());
+        let result = send_magic_link(
+            "test@example.com",
+            "http://localhost/auth/magic",
+            Locale::En,
+            0,
+        )
+        .await;
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_html_message() {
+        let message = html_message(
+            "Re:wind <rewind@milox.dev>",
+            "test@example.com",
+            "Hello",
+            "<p>Hi</p>".to_string(),
+        )
+        .unwrap();
+        let text = String::from_utf8(message.formatted()).unwrap();
+        assert!(text.contains("<rewind@milox.dev>"));
+        assert!(text.contains("To: test@example.com"));
+        assert!(text.contains("Content-Type: text/html"));
+        assert_eq!(
+            mailbox("Re:wind <rewind@milox.dev>").unwrap().to_string(),
+            "\"Re:wind\" <rewind@milox.dev>"
+        );
+        assert!(html_message("rewind", "test@example.com", "Hello", String::new()).is_err());
+    }
 }
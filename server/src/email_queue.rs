@@ -0,0 +1,165 @@
+//! Outgoing emails, queued in the `email_queue` table and sent by a
+//! background worker, so that a sign in doesn't fail when the email
+//! provider has a hiccup.
+//!
+//! Failed sends are retried with backoff by the `LeasedQueue` of
+//! `retry.rs`. An email past its expiry (a verification code is useless
+//! after 10 minutes) is dead-lettered without waiting for its last attempt.
+
+use anyhow::Result;
+use chrono::Utc;
+
+use crate::db;
+use crate::email::{self, EmailSender};
+use crate::retry::LeasedQueue;
+
+static QUEUE: LeasedQueue = LeasedQueue::new("email_queue");
+
+/// Queue an email for the worker, given up once `expires_at` has passed
+pub async fn enqueue(to: &str, subject: &str, html: String, expires_at: Option<i64>) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO email_queue (recipient, subject, html, next_attempt_at, expires_at)
+         VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(to)
+    .bind(subject)
+    .bind(html)
+    .bind(Utc::now().timestamp_millis())
+    .bind(expires_at)
+    .execute(db::pool())
+    .await?;
+    QUEUE.notify();
+    Ok(())
+}
+
+/// Send the due emails, returning how many were sent
+async fn process_due(sender: &dyn EmailSender, now: i64) -> Result<usize> {
+    let due: Vec<(i64, String, String, String, i64, Option<i64>)> = sqlx::query_as(
+        "SELECT id, recipient, subject, html, attempts, expires_at FROM email_queue
+         WHERE status = 'pending' AND next_attempt_at <= $1
+         ORDER BY next_attempt_at LIMIT $2",
+    )
+    .bind(now)
+    .bind(QUEUE.batch_size)
+    .fetch_all(db::pool())
+    .await?;
+
+    let mut sent = 0;
+    for (id, to, subject, html, attempts, expires_at) in due {
+        if expires_at.is_some_and(|expires_at| expires_at <= now) {
+            dead_letter(id, &to, "expired before it could be sent").await?;
+            continue;
+        }
+        if !QUEUE.lease(id, now).await? {
+            continue;
+        }
+
+        match sender.send(&to, &subject, html).await {
+            Ok(()) => {
+                QUEUE.complete(id).await?;
+                log::info!("Email {} sent to {}", id, to);
+                sent += 1;
+            }
+            Err(e) => {
+                if QUEUE.fail(id, attempts, &e.to_string(), now).await? {
+                    log::error!("Giving up on email {} to {}: {}", id, to, e);
+                } else {
+                    log::warn!(
+                        "Failed to send email {} (attempt {}): {}",
+                        id,
+                        attempts + 1,
+                        e
+                    );
+                }
+            }
+        }
+    }
+    Ok(sent)
+}
+
+async fn dead_letter(id: i64, to: &str, error: &str) -> Result<()> {
+    log::error!("Giving up on email {} to {}: {}", id, to, error);
+    QUEUE.dead_letter(id, error).await
+}
+
+/// Send the queued emails as they come and retry the failed ones, until the
+/// server stops. Nothing to do without an email provider
+pub async fn run_worker() {
+    let Some(sender) = email::sender() else {
+        return;
+    };
+    QUEUE.run_worker(|now| process_due(sender, now)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Sender failing for the recipients starting with `down`
+    #[derive(Default)]
+    struct FakeSender {
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl EmailSender for FakeSender {
+        async fn send(&self, to: &str, _subject: &str, _html: String) -> Result<()> {
+            if to.starts_with("down") {
+                anyhow::bail!("provider down");
+            }
+            self.sent.lock().unwrap().push(to.to_string());
+            Ok(())
+        }
+    }
+
+    async fn row(to: &str) -> Option<(String, i64, i64, Option<String>)> {
+        sqlx::query_as(
+            "SELECT status, attempts, next_attempt_at, last_error FROM email_queue
+             WHERE recipient = $1",
+        )
+        .bind(to)
+        .fetch_optional(db::pool())
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_process_due() {
+        db::init_test().await.unwrap();
+        let sender = FakeSender::default();
+        let expired = Utc::now().timestamp_millis() - 1;
+
+        enqueue("up@queue.test", "Hi", "<p>Hi</p>".to_string(), None)
+            .await
+            .unwrap();
+        enqueue("down@queue.test", "Hi", "<p>Hi</p>".to_string(), None)
+            .await
+            .unwrap();
+        enqueue("late@queue.test", "Hi", String::new(), Some(expired))
+            .await
+            .unwrap();
+        let now = Utc::now().timestamp_millis();
+
+        process_due(&sender, now).await.unwrap();
+        assert_eq!(*sender.sent.lock().unwrap(), vec!["up@queue.test"]);
+        assert_eq!(row("up@queue.test").await, None);
+        let (status, attempts, next_attempt_at, error) = row("down@queue.test").await.unwrap();
+        assert_eq!((status.as_str(), attempts), ("pending", 1));
+        assert_eq!(next_attempt_at, now + QUEUE.base_backoff_ms);
+        assert_eq!(error.as_deref(), Some("provider down"));
+        assert_eq!(row("late@queue.test").await.unwrap().0, "dead");
+
+        // Not due before its backoff, dead after the last attempt
+        process_due(&sender, now + 1).await.unwrap();
+        assert_eq!(row("down@queue.test").await.unwrap().1, 1);
+        let mut time = now;
+        for _ in 1..QUEUE.max_attempts {
+            time += QUEUE.max_backoff_ms;
+            process_due(&sender, time).await.unwrap();
+        }
+        let (status, attempts, _, _) = row("down@queue.test").await.unwrap();
+        assert_eq!((status.as_str(), attempts), ("dead", QUEUE.max_attempts));
+    }
+}
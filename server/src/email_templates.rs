@@ -0,0 +1,212 @@
+//! HTML emails, rendered from the templates of `templates/email`: one per
+//! email and locale, extending `base.html` and styled by the macros of
+//! `ui.html`. Templates are built into the binary.
+//!
+//! Locales are those of the client. Subjects are translated here, next to
+//! the template names.
+
+use std::sync::LazyLock;
+
+use anyhow::Result;
+use serde::Serialize;
+use tera::{Context, Tera};
+
+/// Language of the emails, English unless an account or request says
+/// otherwise
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::Fr];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Fr => "fr",
+        }
+    }
+
+    /// Locale of a language code such as `fr` or `fr-FR`, None when not
+    /// supported
+    pub fn parse(code: &str) -> Option<Locale> {
+        let language = code.split(['-', '_']).next()?.to_lowercase();
+        Locale::ALL
+            .into_iter()
+            .find(|locale| locale.as_str() == language)
+    }
+
+    /// Locale of a stored language, English when unknown or missing
+    pub fn or_default(code: Option<&str>) -> Locale {
+        code.and_then(Locale::parse).unwrap_or_default()
+    }
+}
+
+/// A rendered email
+#[derive(Debug)]
+pub struct Email {
+    pub subject: String,
+    pub html: String,
+}
+
+macro_rules! templates {
+    ($($name:literal),* $(,)?) => {
+        vec![$(($name, include_str!(concat!("../templates/email/", $name)))),*]
+    };
+}
+
+static TEMPLATES: LazyLock<Tera> = LazyLock::new(|| {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(templates![
+        "base.html",
+        "ui.html",
+        "en/verification_code.html",
+        "fr/verification_code.html",
+        "en/magic_link.html",
+        "fr/magic_link.html",
+        "en/race_summary.html",
+        "fr/race_summary.html",
+        "en/announcement.html",
+        "fr/announcement.html",
+    ])
+    .expect("Invalid email templates");
+    tera
+});
+
+/// Render the template `name` of a locale
+fn render(locale: Locale, name: &str, context: &Context) -> Result<String> {
+    let template = format!("{}/{}.html", locale.as_str(), name);
+    Ok(TEMPLATES.render(&template, context)?)
+}
+
+pub fn verification_code(locale: Locale, code: &str) -> Result<Email> {
+    let mut context = Context::new();
+    context.insert("code", code);
+    Ok(Email {
+        subject: match locale {
+            Locale::En => "Your Re:wind verification code",
+            Locale::Fr => "Votre code de vérification Re:wind",
+        }
+        .to_string(),
+        html: render(locale, "verification_code", &context)?,
+    })
+}
+
+pub fn magic_link(locale: Locale, url: &str) -> Result<Email> {
+    let mut context = Context::new();
+    context.insert("url", url);
+    Ok(Email {
+        subject: match locale {
+            Locale::En => "Sign in to Re:wind",
+            Locale::Fr => "Connexion à Re:wind",
+        }
+        .to_string(),
+        html: render(locale, "magic_link", &context)?,
+    })
+}
+
+/// Figures of a finish, formatted
+#[derive(Debug, Serialize)]
+pub struct RaceSummary {
+    pub player: String,
+    pub course: String,
+    /// Among the finishers of the race so far
+    pub rank: u32,
+    pub time: String,
+    /// Elapsed time at each gate
+    pub splits: Vec<String>,
+    pub distance_nm: Option<String>,
+    /// Knots
+    pub avg_speed: Option<String>,
+    /// Replay of the result in the client
+    pub url: String,
+}
+
+pub fn race_summary(locale: Locale, summary: &RaceSummary) -> Result<Email> {
+    let context = Context::from_serialize(summary)?;
+    Ok(Email {
+        subject: match locale {
+            Locale::En => format!("Your race on {}", summary.course),
+            Locale::Fr => format!("Votre course sur {}", summary.course),
+        },
+        html: render(locale, "race_summary", &context)?,
+    })
+}
+
+/// Announcement written by an admin, the same text in every locale: its
+/// paragraphs are separated by blank lines
+pub fn announcement(locale: Locale, subject: &str, message: &str) -> Result<Email> {
+    let paragraphs: Vec<&str> = message
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect();
+    let mut context = Context::new();
+    context.insert("paragraphs", &paragraphs);
+    Ok(Email {
+        subject: subject.to_string(),
+        html: render(locale, "announcement", &context)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_locale() {
+        assert_eq!(Locale::parse("fr-FR"), Some(Locale::Fr));
+        assert_eq!(Locale::parse("EN"), Some(Locale::En));
+        assert_eq!(Locale::parse("de"), None);
+        assert_eq!(Locale::or_default(Some("de")), Locale::En);
+    }
+
+    #[test]
+    fn test_render() {
+        let email = verification_code(Locale::Fr, "123456").unwrap();
+        assert_eq!(email.subject, "Votre code de vérification Re:wind");
+        assert!(email.html.contains("Votre code de vérification"));
+        assert!(email.html.contains(">123456</span>"));
+        assert!(email.html.contains("<h1"));
+
+        let email = magic_link(Locale::En, "http://localhost/auth/magic?token=a&b=c").unwrap();
+        assert!(email.html.contains("Sign in</a>"));
+
+        let email = announcement(Locale::En, "News", "Hello <b>sailors</b>\n\nSee you").unwrap();
+        assert!(
+            email
+                .html
+                .contains("Hello &lt;b&gt;sailors&lt;&#x2F;b&gt;</p>")
+        );
+        assert!(email.html.contains("See you</p>"));
+    }
+
+    #[test]
+    fn test_race_summary() {
+        let summary = RaceSummary {
+            player: "Alice".to_string(),
+            course: "Vendée Globe".to_string(),
+            rank: 2,
+            time: "3d 04:12:05".to_string(),
+            splits: vec!["1d 02:00:00".to_string()],
+            distance_nm: Some("2430.5".to_string()),
+            avg_speed: None,
+            url: "http://localhost:3000/?replay=1".to_string(),
+        };
+        for locale in Locale::ALL {
+            let email = race_summary(locale, &summary).unwrap();
+            assert!(email.subject.ends_with("Vendée Globe"));
+            assert!(email.html.contains("Alice"));
+            assert!(email.html.contains("3d 04:12:05"));
+            assert!(email.html.contains("1d 02:00:00"));
+            assert!(email.html.contains("2430.5"));
+            assert!(!email.html.contains("kn<") && !email.html.contains("nœuds<"));
+        }
+        let html = race_summary(Locale::En, &summary).unwrap().html;
+        assert!(html.contains("Gate 1"));
+        assert!(html.contains("in position 2."));
+    }
+}
@@ -0,0 +1,233 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Energy budget of a course: the autopilot and instruments draw on the
+/// battery, solar panels and the hydro generator recharge it. Currents are
+/// in amps, the capacity in amp hours of course time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub struct Energy {
+    pub capacity: f64,
+    pub autopilot: f64,
+    pub instruments: f64,
+    /// Solar charge with the sun overhead
+    pub solar: f64,
+    /// Hydro generator charge per knot of boat speed
+    pub hydro: f64,
+    /// Share of the boat speed lost to the drag of the hydro generator
+    pub hydro_drag: f64,
+}
+
+/// Share of the boat speed reached when hand steering, autopilot off
+pub const MANUAL_SPEED: f64 = 0.85;
+
+impl Energy {
+    pub fn validate(&self) -> Result<()> {
+        if self.capacity <= 0.0 {
+            return Err(anyhow!("Battery capacity must be positive"));
+        }
+        if [self.autopilot, self.instruments, self.solar, self.hydro]
+            .iter()
+            .any(|current| *current < 0.0)
+        {
+            return Err(anyhow!("Currents must be positive"));
+        }
+        if !(0.0..1.0).contains(&self.hydro_drag) {
+            return Err(anyhow!("Hydro drag must be within 0-1"));
+        }
+        Ok(())
+    }
+}
+
+/// Systems of the boat the skipper switches on and off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub enum System {
+    Autopilot,
+    Instruments,
+    Hydro,
+}
+
+/// Battery of a boat and the systems it powers
+#[derive(Debug, Clone)]
+pub struct Battery {
+    pub energy: Energy,
+    /// Charge in amp hours
+    pub charge: f64,
+    pub autopilot: bool,
+    pub instruments: bool,
+    pub hydro: bool,
+}
+
+impl Battery {
+    /// A full battery, every system on
+    pub fn new(energy: &Energy) -> Self {
+        Battery {
+            energy: energy.clone(),
+            charge: energy.capacity,
+            autopilot: true,
+            instruments: true,
+            hydro: true,
+        }
+    }
+
+    /// Charge left, from 0 to 1
+    pub fn level(&self) -> f64 {
+        self.charge / self.energy.capacity
+    }
+
+    /// Switch a system on or off. A flat battery powers nothing.
+    pub fn switch(&mut self, system: System, on: bool) -> Result<()> {
+        if on && system != System::Hydro && self.charge <= 0.0 {
+            return Err(anyhow!("Battery is flat"));
+        }
+        match system {
+            System::Autopilot => self.autopilot = on,
+            System::Instruments => self.instruments = on,
+            System::Hydro => self.hydro = on,
+        }
+        Ok(())
+    }
+
+    /// Share of the polar speed reached with the systems in use
+    pub fn speed_factor(&self) -> f64 {
+        let steering = if self.autopilot { 1.0 } else { MANUAL_SPEED };
+        let drag = if self.hydro {
+            1.0 - self.energy.hydro_drag
+        } else {
+            1.0
+        };
+        steering * drag
+    }
+
+    /// Draw and recharge over `hours` of course time at `boat_speed` knots,
+    /// with `daylight` the sine of the sun elevation. Systems go off when the
+    /// battery runs flat. Returns whether the state shown to the skipper
+    /// changed: a system, or the charge in whole percents.
+    pub fn run(&mut self, hours: f64, boat_speed: f64, daylight: f64) -> bool {
+        let before = self.shown();
+        let mut current = self.energy.solar * daylight.max(0.0);
+        if self.hydro {
+            current += self.energy.hydro * boat_speed;
+        }
+        if self.autopilot {
+            current -= self.energy.autopilot;
+        }
+        if self.instruments {
+            current -= self.energy.instruments;
+        }
+        self.charge = (self.charge + current * hours).clamp(0.0, self.energy.capacity);
+        if self.charge <= 0.0 {
+            self.autopilot = false;
+            self.instruments = false;
+        }
+        self.shown() != before
+    }
+
+    fn shown(&self) -> (i64, bool, bool, bool) {
+        (
+            (self.level() * 100.0).round() as i64,
+            self.autopilot,
+            self.instruments,
+            self.hydro,
+        )
+    }
+}
+
+/// Sine of the sun elevation at `time` (ms since epoch) and a position:
+/// negative at night
+pub fn daylight(time: i64, lng: f64, lat: f64) -> f64 {
+    let days = time as f64 / 86_400_000.0;
+    let day_of_year = days.rem_euclid(365.25);
+    let declination =
+        (-23.44f64).to_radians() * (std::f64::consts::TAU * (day_of_year + 10.0) / 365.25).cos();
+    // Solar time, noon at 0
+    let hour_angle = ((days.fract() * 24.0 + lng / 15.0 - 12.0) * 15.0).to_radians();
+    let lat = lat.to_radians();
+    lat.sin() * declination.sin() + lat.cos() * declination.cos() * hour_angle.cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn energy() -> Energy {
+        Energy {
+            capacity: 100.0,
+            autopilot: 6.0,
+            instruments: 2.0,
+            solar: 10.0,
+            hydro: 0.5,
+            hydro_drag: 0.04,
+        }
+    }
+
+    #[test]
+    fn test_battery_drains_and_recharges() {
+        let mut battery = Battery::new(&energy());
+        battery.hydro = false;
+
+        // 8 A drawn at night
+        assert!(battery.run(1.0, 10.0, -0.5));
+        assert!((battery.charge - 92.0).abs() < 1e-9);
+
+        // Less than a percent: nothing to show
+        assert!(!battery.run(0.01, 10.0, -0.5));
+
+        // The hydro generator at 10 knots and the noon sun make up for it
+        battery.hydro = true;
+        battery.run(1.0, 10.0, 1.0);
+        assert!((battery.charge - 98.92).abs() < 1e-9);
+        assert!((battery.speed_factor() - 0.96).abs() < 1e-9);
+
+        // Never over capacity
+        battery.run(10.0, 10.0, 1.0);
+        assert_eq!(battery.charge, 100.0);
+    }
+
+    #[test]
+    fn test_flat_battery_switches_systems_off() {
+        let mut battery = Battery::new(&energy());
+        battery.hydro = false;
+        assert!(battery.run(20.0, 0.0, -1.0));
+        assert_eq!(battery.charge, 0.0);
+        assert!(!battery.autopilot && !battery.instruments);
+        assert!((battery.speed_factor() - MANUAL_SPEED).abs() < 1e-9);
+
+        assert!(battery.switch(System::Autopilot, true).is_err());
+        assert!(battery.switch(System::Hydro, true).is_ok());
+    }
+
+    #[test]
+    fn test_daylight() {
+        // 2020-06-21T12:00:00Z: sun overhead the tropic of Cancer at Greenwich
+        let noon = 1592740800000;
+        assert!(daylight(noon, 0.0, 23.44) > 0.99);
+        // Midnight on the other side of the world
+        assert!(daylight(noon, 180.0, 0.0) < -0.9);
+        // Polar day
+        assert!(daylight(noon, 180.0, 80.0) > 0.0);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(energy().validate().is_ok());
+        assert!(
+            Energy {
+                capacity: 0.0,
+                ..energy()
+            }
+            .validate()
+            .is_err()
+        );
+        assert!(
+            Energy {
+                hydro_drag: 1.0,
+                ..energy()
+            }
+            .validate()
+            .is_err()
+        );
+    }
+}
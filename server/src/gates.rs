@@ -0,0 +1,133 @@
+//! Gate geometry: the segment of a gate and the boat moves crossing it.
+//! Mirrors `client/src/app/gate-crossing.ts`.
+//!
+//! Positions are taken relative to the gate center, with longitude differences
+//! wrapped, so gates and moves across the antimeridian work like any other.
+
+use crate::courses::{Gate, LngLat};
+use crate::geo;
+
+/// Position in degrees east and north of an origin
+type Offset = (f64, f64);
+
+/// Offset of `point` from `origin`, the shortest way around
+fn offset(origin: &LngLat, point: &LngLat) -> Offset {
+    (
+        geo::wrap_lng(point.lng - origin.lng),
+        point.lat - origin.lat,
+    )
+}
+
+/// Endpoints of a gate from its center, orientation and length
+pub fn endpoints(gate: &Gate) -> (LngLat, LngLat) {
+    let half_length = gate.length_nm / 2.0 / geo::NM_PER_DEGREE;
+    let orientation = gate.orientation.to_radians();
+    let lat_offset = half_length * orientation.cos();
+    let lng_offset = half_length * orientation.sin() / gate.center.lat.to_radians().cos();
+    (
+        LngLat {
+            lng: geo::wrap_lng(gate.center.lng - lng_offset),
+            lat: gate.center.lat - lat_offset,
+        },
+        LngLat {
+            lng: geo::wrap_lng(gate.center.lng + lng_offset),
+            lat: gate.center.lat + lat_offset,
+        },
+    )
+}
+
+/// Whether the segments p1→p2 and q1→q2 intersect, touching included
+fn segments_intersect(p1: Offset, p2: Offset, q1: Offset, q2: Offset) -> bool {
+    let cross =
+        |a: Offset, b: Offset, c: Offset| (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+    let on_segment = |a: Offset, b: Offset, c: Offset| {
+        a.0.min(b.0) <= c.0 && c.0 <= a.0.max(b.0) && a.1.min(b.1) <= c.1 && c.1 <= a.1.max(b.1)
+    };
+
+    let d1 = cross(q1, q2, p1);
+    let d2 = cross(q1, q2, p2);
+    let d3 = cross(p1, p2, q1);
+    let d4 = cross(p1, p2, q2);
+
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+    {
+        return true;
+    }
+
+    (d1 == 0.0 && on_segment(q1, q2, p1))
+        || (d2 == 0.0 && on_segment(q1, q2, p2))
+        || (d3 == 0.0 && on_segment(p1, p2, q1))
+        || (d4 == 0.0 && on_segment(p1, p2, q2))
+}
+
+/// Whether a boat moving from `from` to `to` crosses the gate line
+pub fn crosses_gate(from: &LngLat, to: &LngLat, gate: &Gate) -> bool {
+    let (g1, g2) = endpoints(gate);
+    let center = &gate.center;
+    segments_intersect(
+        offset(center, from),
+        offset(center, to),
+        offset(center, &g1),
+        offset(center, &g2),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crosses_gate() {
+        let gate = Gate::vertical(1.0, 0.0, 60.0);
+        let west = LngLat { lng: 0.5, lat: 0.0 };
+        let east = LngLat { lng: 1.5, lat: 0.0 };
+        let far_north = LngLat { lng: 1.5, lat: 2.0 };
+        assert!(crosses_gate(&west, &east, &gate));
+        assert!(crosses_gate(&east, &west, &gate));
+        assert!(!crosses_gate(&west, &LngLat { lng: 0.9, lat: 0.0 }, &gate));
+        assert!(!crosses_gate(
+            &LngLat { lng: 0.5, lat: 2.0 },
+            &far_north,
+            &gate
+        ));
+    }
+
+    #[test]
+    fn test_crosses_gate_on_the_antimeridian() {
+        let west = LngLat {
+            lng: 179.9,
+            lat: -50.0,
+        };
+        let east = LngLat {
+            lng: -179.9,
+            lat: -50.0,
+        };
+        for lng in [180.0, -180.0] {
+            let gate = Gate::vertical(lng, -50.0, 10.0);
+            assert!(crosses_gate(&west, &east, &gate));
+            assert!(crosses_gate(&east, &west, &gate));
+        }
+
+        // Just past the antimeridian, not the long way around
+        let gate = Gate::vertical(-179.95, -50.0, 10.0);
+        assert!(crosses_gate(&west, &east, &gate));
+        assert!(!crosses_gate(
+            &west,
+            &LngLat {
+                lng: 179.95,
+                lat: -50.0
+            },
+            &gate
+        ));
+    }
+
+    #[test]
+    fn test_endpoints_wrap() {
+        let gate = Gate::horizontal(179.99, 0.0, 6.0);
+        let (west, east) = endpoints(&gate);
+        assert!((west.lng - 179.94).abs() < 1e-9);
+        assert!((east.lng + 179.96).abs() < 1e-9);
+        assert!(west.lat.abs() < 1e-9);
+    }
+}
@@ -0,0 +1,289 @@
+//! Geodesy on a spherical Earth: distances in nautical miles, bearings in
+//! degrees (0 = north, clockwise), longitudes wrapped to -180..180.
+
+use serde::Deserialize;
+use ts_rs::TS;
+
+use crate::courses::LngLat;
+
+pub const EARTH_RADIUS_NM: f64 = 3440.065;
+
+/// 1 nautical mile = 1/60 degree of latitude
+pub const NM_PER_DEGREE: f64 = 60.0;
+
+/// Highest latitude boats sail to, keeping east-west moves finite near the poles
+pub const MAX_LAT: f64 = 89.0;
+
+/// Longitude (or longitude difference) wrapped to -180..180
+pub fn wrap_lng(lng: f64) -> f64 {
+    (lng + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Longitude equivalent to `lng` closest to `reference`, possibly out of -180..180
+pub fn unwrap_lng(lng: f64, reference: f64) -> f64 {
+    reference + wrap_lng(lng - reference)
+}
+
+/// Latitude kept within `MAX_LAT` of the equator
+pub fn clamp_lat(lat: f64) -> f64 {
+    lat.clamp(-MAX_LAT, MAX_LAT)
+}
+
+/// Longitude read from a client or a course, wrapped to -180..180
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, TS)]
+#[serde(try_from = "f64")]
+pub struct Longitude(f64);
+
+impl TryFrom<f64> for Longitude {
+    type Error = String;
+
+    fn try_from(lng: f64) -> Result<Self, Self::Error> {
+        if !lng.is_finite() {
+            return Err(format!("Invalid longitude {}", lng));
+        }
+        Ok(Longitude(wrap_lng(lng)))
+    }
+}
+
+impl From<Longitude> for f64 {
+    fn from(lng: Longitude) -> f64 {
+        lng.0
+    }
+}
+
+/// Latitude read from a client or a course, within -90..90
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, TS)]
+#[serde(try_from = "f64")]
+pub struct Latitude(f64);
+
+impl TryFrom<f64> for Latitude {
+    type Error = String;
+
+    fn try_from(lat: f64) -> Result<Self, Self::Error> {
+        if !(-90.0..=90.0).contains(&lat) {
+            return Err(format!("Invalid latitude {}", lat));
+        }
+        Ok(Latitude(lat))
+    }
+}
+
+impl From<Latitude> for f64 {
+    fn from(lat: Latitude) -> f64 {
+        lat.0
+    }
+}
+
+/// Heading or wind angle in degrees read from a client, a finite number
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, TS)]
+#[serde(try_from = "f64")]
+pub struct Angle(f64);
+
+impl TryFrom<f64> for Angle {
+    type Error = String;
+
+    fn try_from(angle: f64) -> Result<Self, Self::Error> {
+        if !angle.is_finite() {
+            return Err(format!("Invalid angle {}", angle));
+        }
+        Ok(Angle(angle))
+    }
+}
+
+impl From<Angle> for f64 {
+    fn from(angle: Angle) -> f64 {
+        angle.0
+    }
+}
+
+/// Great circle distance (haversine)
+pub fn distance_nm(from: &LngLat, to: &LngLat) -> f64 {
+    let (lat1, lat2) = (from.lat.to_radians(), to.lat.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lng = (to.lng - from.lng).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+    EARTH_RADIUS_NM * 2.0 * a.sqrt().asin()
+}
+
+/// Distance sailing a constant heading (rhumb line), the short way around
+pub fn rhumb_distance_nm(from: &LngLat, to: &LngLat) -> f64 {
+    let (lat1, lat2) = (from.lat.to_radians(), to.lat.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lng = wrap_lng(to.lng - from.lng).to_radians();
+    let stretched = ((std::f64::consts::FRAC_PI_4 + lat2 / 2.0).tan()
+        / (std::f64::consts::FRAC_PI_4 + lat1 / 2.0).tan())
+    .ln();
+    // East-west lines have no stretched latitude difference
+    let q = if stretched.abs() > 1e-12 {
+        d_lat / stretched
+    } else {
+        lat1.cos()
+    };
+    EARTH_RADIUS_NM * (d_lat * d_lat + q * q * d_lng * d_lng).sqrt()
+}
+
+/// Initial great circle bearing from `from` to `to`
+pub fn bearing(from: &LngLat, to: &LngLat) -> f64 {
+    let (lat1, lat2) = (from.lat.to_radians(), to.lat.to_radians());
+    let d_lng = (to.lng - from.lng).to_radians();
+    let y = d_lng.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lng.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Point reached following the great circle from `from` on `bearing` for `distance_nm`
+pub fn destination(from: &LngLat, bearing: f64, distance_nm: f64) -> LngLat {
+    let angle = distance_nm / EARTH_RADIUS_NM;
+    let bearing = bearing.to_radians();
+    let lat1 = from.lat.to_radians();
+    let lat2 = (lat1.sin() * angle.cos() + lat1.cos() * angle.sin() * bearing.cos()).asin();
+    let d_lng =
+        (bearing.sin() * angle.sin() * lat1.cos()).atan2(angle.cos() - lat1.sin() * lat2.sin());
+    LngLat {
+        lng: wrap_lng(from.lng + d_lng.to_degrees()),
+        lat: lat2.to_degrees(),
+    }
+}
+
+/// Distance from `point` to the great circle through `from` and `to`:
+/// positive on the right of the way from `from` to `to`, negative on the left
+pub fn cross_track_nm(point: &LngLat, from: &LngLat, to: &LngLat) -> f64 {
+    let angle = distance_nm(from, point) / EARTH_RADIUS_NM;
+    let d_bearing = (bearing(from, point) - bearing(from, to)).to_radians();
+    (angle.sin() * d_bearing.sin()).asin() * EARTH_RADIUS_NM
+}
+
+/// Distance from `from` to the foot of the perpendicular dropped from `point`
+/// on the great circle through `from` and `to`, negative behind `from`
+pub fn along_track_nm(point: &LngLat, from: &LngLat, to: &LngLat) -> f64 {
+    let angle = distance_nm(from, point) / EARTH_RADIUS_NM;
+    let cross = cross_track_nm(point, from, to) / EARTH_RADIUS_NM;
+    let along = (angle.cos() / cross.cos()).clamp(-1.0, 1.0).acos() * EARTH_RADIUS_NM;
+    let d_bearing = (bearing(from, point) - bearing(from, to)).to_radians();
+    if d_bearing.cos() < 0.0 { -along } else { along }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64, tolerance: f64) -> bool {
+        (a - b).abs() < tolerance
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap_lng(190.0), -170.0);
+        assert_eq!(wrap_lng(-540.0), -180.0);
+        assert_eq!(unwrap_lng(-179.0, 179.0), 181.0);
+        assert_eq!(unwrap_lng(179.0, -179.0), -181.0);
+        assert_eq!(unwrap_lng(10.0, 20.0), 10.0);
+        assert_eq!(clamp_lat(95.0), MAX_LAT);
+    }
+
+    #[test]
+    fn test_distances() {
+        let origin = LngLat { lng: 0.0, lat: 0.0 };
+        let east = LngLat { lng: 1.0, lat: 0.0 };
+        assert!(close(distance_nm(&origin, &east), 60.04, 0.01));
+        assert!(close(rhumb_distance_nm(&origin, &east), 60.04, 0.01));
+
+        // Across the antimeridian, the short way
+        let a = LngLat {
+            lng: 179.0,
+            lat: -50.0,
+        };
+        let b = LngLat {
+            lng: -179.0,
+            lat: -50.0,
+        };
+        assert!(close(distance_nm(&a, &b), 77.2, 0.1));
+        assert!(close(rhumb_distance_nm(&a, &b), 77.2, 0.1));
+
+        // The rhumb line is longer than the great circle far from the equator
+        let brest = LngLat {
+            lng: -4.5,
+            lat: 48.4,
+        };
+        let newport = LngLat {
+            lng: -71.3,
+            lat: 41.5,
+        };
+        assert!(rhumb_distance_nm(&brest, &newport) > distance_nm(&brest, &newport) + 50.0);
+    }
+
+    #[test]
+    fn test_bearing_and_destination() {
+        let origin = LngLat { lng: 0.0, lat: 0.0 };
+        assert!(close(
+            bearing(&origin, &LngLat { lng: 1.0, lat: 0.0 }),
+            90.0,
+            1e-9
+        ));
+        assert!(close(
+            bearing(
+                &origin,
+                &LngLat {
+                    lng: 0.0,
+                    lat: -1.0
+                }
+            ),
+            180.0,
+            1e-9
+        ));
+
+        let start = LngLat {
+            lng: 170.0,
+            lat: 45.0,
+        };
+        let reached = destination(&start, 60.0, 1000.0);
+        // Over the antimeridian, wrapped
+        assert!(reached.lng < 0.0);
+        assert!(close(distance_nm(&start, &reached), 1000.0, 1e-6));
+        assert!(close(bearing(&start, &reached), 60.0, 1e-6));
+    }
+
+    #[test]
+    fn test_cross_track() {
+        let from = LngLat { lng: 0.0, lat: 0.0 };
+        let to = LngLat {
+            lng: 10.0,
+            lat: 0.0,
+        };
+        let south = LngLat {
+            lng: 5.0,
+            lat: -1.0,
+        };
+        let north = LngLat { lng: 5.0, lat: 1.0 };
+        assert!(close(cross_track_nm(&south, &from, &to), 60.04, 0.01));
+        assert!(close(cross_track_nm(&north, &from, &to), -60.04, 0.01));
+        assert!(close(cross_track_nm(&to, &from, &to), 0.0, 1e-6));
+        assert!(close(along_track_nm(&north, &from, &to), 300.2, 0.1));
+
+        let behind = LngLat {
+            lng: -1.0,
+            lat: 1.0,
+        };
+        assert!(along_track_nm(&behind, &from, &to) < 0.0);
+    }
+
+    #[test]
+    fn test_longitude_wraps() {
+        assert_eq!(f64::from(Longitude::try_from(540.0).unwrap()), -180.0);
+        assert_eq!(f64::from(Longitude::try_from(-190.0).unwrap()), 170.0);
+        assert!(Longitude::try_from(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_latitude_range() {
+        assert_eq!(f64::from(Latitude::try_from(-90.0).unwrap()), -90.0);
+        assert!(Latitude::try_from(95.0).is_err());
+        assert!(Latitude::try_from(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_angle_finite() {
+        assert_eq!(f64::from(Angle::try_from(-45.0).unwrap()), -45.0);
+        assert!(Angle::try_from(f64::NAN).is_err());
+        assert!(Angle::try_from(f64::INFINITY).is_err());
+    }
+}
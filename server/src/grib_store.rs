@@ -4,11 +4,12 @@ use crate::s3;
 use crate::wind_reports;
 use chrono::{Days, NaiveDate};
 use futures::stream::{self, StreamExt};
-use object_store::{ObjectStoreExt, aws};
+use object_store::{ObjectStore, ObjectStoreExt};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// Import all GRIB files for a date range from NCAR
+#[tracing::instrument(skip(max_concurrency))]
 pub async fn import_grib_range(
     from: NaiveDate,
     to: NaiveDate,
@@ -80,7 +81,7 @@ pub async fn import_grib_range(
                     }
                     Err(e) => {
                         error_count.fetch_add(1, Ordering::Relaxed);
-                        eprintln!(
+                        log::error!(
                             "[{}/{}] {} h{:02} - error: {}",
                             done, total_tasks, day, hour, e
                         );
@@ -110,22 +111,25 @@ pub async fn import_grib_range(
 }
 
 /// Handle a single NCAR GRIB file: download, filter, convert to PNG, store
+#[tracing::instrument(skip(ncar, grib_s3, raster_s3))]
 async fn handle_ncar_grib(
     ncar: &NcarSource,
-    grib_s3: &aws::AmazonS3,
-    raster_s3: &aws::AmazonS3,
+    grib_s3: &dyn ObjectStore,
+    raster_s3: &dyn ObjectStore,
     day: NaiveDate,
     hour: u32,
 ) -> anyhow::Result<()> {
     let grib_path = ncar_grib_path(day, hour);
 
     // Check if filtered GRIB already exists in S3 cache
-    let grib_data = match grib_s3.get(&grib_path.as_str().into()).await {
-        Ok(result) => {
+    let path = grib_path.as_str().into();
+    let cached = s3::retried(|| async { grib_s3.get(&path).await?.bytes().await }).await;
+    let grib_data = match cached {
+        Ok(bytes) => {
             log::debug!("{} h{:02} - using cached GRIB", day, hour);
-            result.bytes().await?
+            bytes
         }
-        Err(_) => {
+        Err(e) if matches!(e.downcast_ref(), Some(object_store::Error::NotFound { .. })) => {
             log::debug!("{} h{:02} - downloading from NCAR", day, hour);
             // Download and filter from NCAR
             let bytes_uploaded = ncar
@@ -138,21 +142,16 @@ async fn handle_ncar_grib(
             }
 
             // Read back the uploaded data for PNG conversion
-            grib_s3
-                .get(&grib_path.as_str().into())
-                .await?
-                .bytes()
-                .await?
+            s3::retried(|| async { grib_s3.get(&path).await?.bytes().await }).await?
         }
+        Err(e) => return Err(e),
     };
 
     // Generate UV PNG from filtered GRIB
-    let png_data = grib_to_uv_png(&grib_data)?;
-    let png_path = ncar_raster_path(day, hour);
+    let png_data = tracing::info_span!("grib_to_uv_png").in_scope(|| grib_to_uv_png(&grib_data))?;
+    let png_path = ncar_raster_path(day, hour, &png_data);
 
-    raster_s3
-        .put(&png_path.as_str().into(), png_data.into())
-        .await?;
+    s3::put_immutable(raster_s3, &png_path, png_data.into()).await?;
 
     Ok(())
 }
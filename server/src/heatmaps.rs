@@ -0,0 +1,417 @@
+//! Heatmaps of where the fleet sails a course: the stored paths of its
+//! results binned on a grid over the area they cover.
+//!
+//! Each path counts once per cell it crosses, so slow boats don't weigh more
+//! than fast ones. Heatmaps are published to the paths bucket as a PNG
+//! overlay and a JSON of the counts, and rebuilt in the background once new
+//! results come in.
+
+use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use chrono::Utc;
+use object_store::ObjectStoreExt;
+use png::{BitDepth, ColorType, Encoder};
+use serde::{Deserialize, Serialize};
+
+use crate::config::config;
+use crate::race_results::{self, PathPoint};
+use crate::{courses, db, geo, s3};
+
+/// Longest side of a heatmap, in cells
+const MAX_SIDE: usize = 512;
+
+/// Smallest cell, in degrees (about 0.3 nm)
+const MIN_CELL_DEG: f64 = 0.005;
+
+/// Most recent results binned into a heatmap
+const MAX_PATHS: i64 = 2000;
+
+const REFRESH_INTERVAL_SECS: u64 = 60 * 60;
+
+/// A published heatmap, cell `(x, y)` covering longitudes from
+/// `west + x * cell_deg` and latitudes down from `north - y * cell_deg`.
+/// `west` and `east` may be out of -180..180 for courses across the
+/// antimeridian.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Heatmap {
+    pub course_key: String,
+    pub west: f64,
+    pub south: f64,
+    pub east: f64,
+    pub north: f64,
+    pub cell_deg: f64,
+    pub width: usize,
+    pub height: usize,
+    /// Number of paths binned
+    pub paths: usize,
+    /// Paths through the busiest cell
+    pub max_count: u32,
+    pub png_url: String,
+    /// Counts of the cells sailed, see `HeatmapCounts`
+    pub json_url: String,
+    pub computed_at: i64,
+}
+
+/// The published JSON: the heatmap and the `[x, y, count]` of every cell
+/// sailed through
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HeatmapCounts<'a> {
+    #[serde(flatten)]
+    heatmap: &'a Heatmap,
+    cells: Vec<[u32; 3]>,
+}
+
+/// Cells covering the paths of a course
+#[derive(Debug, Clone, PartialEq)]
+struct Grid {
+    /// Longitudes are unwrapped around this one, the course start
+    origin_lng: f64,
+    west: f64,
+    north: f64,
+    cell_deg: f64,
+    width: usize,
+    height: usize,
+}
+
+impl Grid {
+    /// The grid around every point, with a cell of margin. None without points.
+    fn covering(paths: &[Vec<PathPoint>], origin_lng: f64) -> Option<Grid> {
+        let mut points = paths.iter().flatten().peekable();
+        points.peek()?;
+        let (mut west, mut east) = (f64::MAX, f64::MIN);
+        let (mut south, mut north) = (f64::MAX, f64::MIN);
+        for point in points {
+            let lng = geo::unwrap_lng(point.lng as f64, origin_lng);
+            west = west.min(lng);
+            east = east.max(lng);
+            south = south.min(point.lat as f64);
+            north = north.max(point.lat as f64);
+        }
+
+        let span = (east - west).max(north - south);
+        let cell_deg = (span / (MAX_SIDE - 2) as f64).max(MIN_CELL_DEG);
+        Some(Grid {
+            origin_lng,
+            west: west - cell_deg,
+            north: north + cell_deg,
+            cell_deg,
+            width: ((east - west) / cell_deg).floor() as usize + 3,
+            height: ((north - south) / cell_deg).floor() as usize + 3,
+        })
+    }
+
+    /// Index of the cell of a position, None outside the grid
+    fn cell(&self, lng: f64, lat: f64) -> Option<usize> {
+        let x = (geo::unwrap_lng(lng, self.origin_lng) - self.west) / self.cell_deg;
+        let y = (self.north - lat) / self.cell_deg;
+        if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 {
+            return None;
+        }
+        Some(y as usize * self.width + x as usize)
+    }
+}
+
+/// Paths through each cell of the grid, row by row from the north
+fn bin(grid: &Grid, paths: &[Vec<PathPoint>]) -> Vec<u32> {
+    let mut counts = vec![0u32; grid.width * grid.height];
+    // Last path counted in each cell, to count a path once per cell
+    let mut counted = vec![usize::MAX; counts.len()];
+
+    for (index, path) in paths.iter().enumerate() {
+        let mut visit = |lng: f64, lat: f64| {
+            if let Some(cell) = grid.cell(lng, lat)
+                && counted[cell] != index
+            {
+                counted[cell] = index;
+                counts[cell] += 1;
+            }
+        };
+        if let [point] = path.as_slice() {
+            visit(point.lng as f64, point.lat as f64);
+        }
+        // Walk each segment by half cells, samples can be far apart
+        for segment in path.windows(2) {
+            let (from_lng, from_lat) = (segment[0].lng as f64, segment[0].lat as f64);
+            let d_lng = geo::wrap_lng(segment[1].lng as f64 - from_lng);
+            let d_lat = segment[1].lat as f64 - from_lat;
+            let steps = (d_lng.abs().max(d_lat.abs()) / (grid.cell_deg / 2.0)).ceil() as usize;
+            for step in 0..=steps.max(1) {
+                let t = step as f64 / steps.max(1) as f64;
+                visit(from_lng + d_lng * t, from_lat + d_lat * t);
+            }
+        }
+    }
+    counts
+}
+
+/// Color of a cell: transparent when never sailed, then blue to yellow to
+/// red on a log scale
+fn color(count: u32, max_count: u32) -> [u8; 4] {
+    if count == 0 || max_count == 0 {
+        return [0, 0, 0, 0];
+    }
+    let t = (count as f64).ln_1p() / (max_count as f64).ln_1p();
+    let (r, g, b) = if t < 0.5 {
+        let s = t * 2.0;
+        (s, s, 1.0 - s)
+    } else {
+        (1.0, 2.0 - t * 2.0, 0.0)
+    };
+    let channel = |v: f64| (v * 255.0).round() as u8;
+    [channel(r), channel(g), channel(b), channel(0.25 + t * 0.75)]
+}
+
+fn encode_png(counts: &[u32], grid: &Grid, max_count: u32) -> Result<Bytes> {
+    let rgba: Vec<u8> = counts
+        .iter()
+        .flat_map(|&count| color(count, max_count))
+        .collect();
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buffer, grid.width as u32, grid.height as u32);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&rgba)?;
+    }
+
+    Ok(Bytes::from(buffer))
+}
+
+fn png_path(course_key: &str) -> String {
+    format!("heatmaps/{}.png", course_key)
+}
+
+fn json_path(course_key: &str) -> String {
+    format!("heatmaps/{}.json", course_key)
+}
+
+/// Stored paths of the latest results of a course. Paths that fail to load
+/// are skipped.
+async fn load_paths(course_key: &str) -> Result<Vec<Vec<PathPoint>>> {
+    let keys: Vec<(String,)> = sqlx::query_as(
+        "SELECT path_s3_key FROM race_results WHERE course_key = $1
+         ORDER BY id DESC LIMIT $2",
+    )
+    .bind(course_key)
+    .bind(MAX_PATHS)
+    .fetch_all(db::pool())
+    .await?;
+
+    let mut paths = Vec::with_capacity(keys.len());
+    for (key,) in keys {
+        match race_results::load_path(&key).await {
+            Ok(path) => paths.push(path),
+            Err(e) => log::warn!("Skipped path {} of heatmap: {}", key, e),
+        }
+    }
+    Ok(paths)
+}
+
+/// Bin the paths of a course and publish its heatmap. None when the course
+/// has no paths yet.
+pub async fn build(course_key: &str) -> Result<Option<Heatmap>> {
+    let course = courses::get_by_key(course_key)
+        .await?
+        .ok_or_else(|| anyhow!("Course not found: {}", course_key))?;
+    let paths = load_paths(course_key).await?;
+    let Some(grid) = Grid::covering(&paths, course.start.lng) else {
+        return Ok(None);
+    };
+
+    let path_count = paths.len();
+    let binned_grid = grid.clone();
+    let (counts, png) = tokio::task::spawn_blocking(move || -> Result<_> {
+        let counts = bin(&binned_grid, &paths);
+        let max_count = counts.iter().copied().max().unwrap_or(0);
+        let png = encode_png(&counts, &binned_grid, max_count)?;
+        Ok((counts, png))
+    })
+    .await??;
+
+    let heatmap = Heatmap {
+        course_key: course_key.to_string(),
+        west: grid.west,
+        south: grid.north - grid.height as f64 * grid.cell_deg,
+        east: grid.west + grid.width as f64 * grid.cell_deg,
+        north: grid.north,
+        cell_deg: grid.cell_deg,
+        width: grid.width,
+        height: grid.height,
+        paths: path_count,
+        max_count: counts.iter().copied().max().unwrap_or(0),
+        png_url: config().s3.paths_url(&png_path(course_key)),
+        json_url: config().s3.paths_url(&json_path(course_key)),
+        computed_at: Utc::now().timestamp_millis(),
+    };
+    let cells = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(i, &count)| [(i % grid.width) as u32, (i / grid.width) as u32, count])
+        .collect();
+    let json = serde_json::to_vec(&HeatmapCounts {
+        heatmap: &heatmap,
+        cells,
+    })?;
+
+    s3::paths_client()
+        .put(&png_path(course_key).as_str().into(), png.into())
+        .await?;
+    s3::paths_client()
+        .put(
+            &json_path(course_key).as_str().into(),
+            Bytes::from(json).into(),
+        )
+        .await?;
+    save(&heatmap).await?;
+
+    log::info!(
+        "Published heatmap of {} from {} paths",
+        course_key,
+        path_count
+    );
+    Ok(Some(heatmap))
+}
+
+// ============================================================================
+// Database
+// ============================================================================
+
+/// Latest published heatmap of a course
+pub async fn get(course_key: &str) -> Result<Option<Heatmap>> {
+    let row: Option<(String,)> =
+        sqlx::query_as("SELECT data FROM course_heatmaps WHERE course_key = $1")
+            .bind(course_key)
+            .fetch_optional(db::pool())
+            .await?;
+    row.map(|(data,)| Ok(serde_json::from_str(&data)?))
+        .transpose()
+}
+
+async fn save(heatmap: &Heatmap) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO course_heatmaps (course_key, data, computed_at) VALUES ($1, $2, $3)
+         ON CONFLICT(course_key) DO UPDATE SET data = excluded.data, computed_at = excluded.computed_at",
+    )
+    .bind(&heatmap.course_key)
+    .bind(serde_json::to_string(heatmap)?)
+    .bind(heatmap.computed_at)
+    .execute(db::pool())
+    .await?;
+    Ok(())
+}
+
+/// Courses with results newer than their heatmap
+async fn stale_courses() -> Result<Vec<String>> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        "SELECT DISTINCT r.course_key FROM race_results r
+         JOIN courses c ON c.key = r.course_key
+         LEFT JOIN course_heatmaps h ON h.course_key = r.course_key
+         WHERE h.computed_at IS NULL OR r.created_at > h.computed_at",
+    )
+    .fetch_all(db::pool())
+    .await?;
+    Ok(rows.into_iter().map(|(key,)| key).collect())
+}
+
+/// Rebuild the heatmaps of a course, or of every course with results, from
+/// the CLI
+pub async fn build_from_cli(course_key: Option<String>) -> Result<()> {
+    let keys = match course_key {
+        Some(key) => vec![key],
+        None => {
+            let rows: Vec<(String,)> = sqlx::query_as(
+                "SELECT DISTINCT r.course_key FROM race_results r
+                 JOIN courses c ON c.key = r.course_key",
+            )
+            .fetch_all(db::pool())
+            .await?;
+            rows.into_iter().map(|(key,)| key).collect()
+        }
+    };
+    for key in keys {
+        match build(&key).await? {
+            Some(heatmap) => println!("{}\t{}", key, heatmap.png_url),
+            None => println!("{}\tno paths", key),
+        }
+    }
+    Ok(())
+}
+
+/// Rebuild the heatmaps of the courses with new results, periodically
+pub async fn run_refresher() {
+    loop {
+        match stale_courses().await {
+            Ok(keys) => {
+                for key in keys {
+                    if let Err(e) = build(&key).await {
+                        log::error!("Heatmap of {} failed: {}", key, e);
+                    }
+                }
+            }
+            Err(e) => log::error!("Heatmap refresh failed: {}", e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(REFRESH_INTERVAL_SECS)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(points: &[(f32, f32)]) -> Vec<PathPoint> {
+        points
+            .iter()
+            .enumerate()
+            .map(|(i, &(lng, lat))| PathPoint {
+                race_time: i as i64 * 1000,
+                lng,
+                lat,
+                heading: 0.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bin() {
+        let paths = vec![
+            path(&[(0.0, 0.0), (1.0, 0.0)]),
+            // Back and forth on the same line counts once
+            path(&[(0.0, 0.0), (1.0, 0.0), (0.0, 0.0)]),
+            path(&[(0.5, 0.5)]),
+        ];
+        let grid = Grid::covering(&paths, 0.0).unwrap();
+        assert_eq!(grid.cell_deg, MIN_CELL_DEG);
+        let counts = bin(&grid, &paths);
+
+        assert_eq!(counts[grid.cell(0.5, 0.0).unwrap()], 2);
+        assert_eq!(counts[grid.cell(0.5, 0.5).unwrap()], 1);
+        assert_eq!(counts[grid.cell(0.5, 0.25).unwrap()], 0);
+        assert_eq!(counts.iter().copied().max(), Some(2));
+        assert!(grid.cell(2.0, 0.0).is_none());
+        assert!(Grid::covering(&[], 0.0).is_none());
+    }
+
+    #[test]
+    fn test_antimeridian() {
+        let paths = vec![path(&[(179.5, -40.0), (-179.5, -40.0)])];
+        let grid = Grid::covering(&paths, 175.0).unwrap();
+        // One degree wide, not 359
+        assert!(grid.width < MAX_SIDE);
+        let counts = bin(&grid, &paths);
+        assert_eq!(counts[grid.cell(180.0, -40.0).unwrap()], 1);
+        assert_eq!(counts[grid.cell(-179.6, -40.0).unwrap()], 1);
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(color(0, 10)[3], 0);
+        assert_eq!(color(10, 10), [255, 0, 0, 255]);
+        let low = color(1, 100);
+        assert!(low[2] > low[0]);
+    }
+}
@@ -0,0 +1,396 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::courses::LngLat;
+use crate::geo;
+
+/// Land polygons loaded at server start. Land checks pass everything until then.
+static LAND: OnceLock<Land> = OnceLock::new();
+
+/// Size of the spatial index cells, in degrees
+const CELL_DEGREES: f64 = 1.0;
+const GRID_COLS: usize = (360.0 / CELL_DEGREES) as usize;
+const GRID_ROWS: usize = (180.0 / CELL_DEGREES) as usize;
+
+#[derive(Deserialize)]
+struct Topology {
+    transform: Option<Transform>,
+    objects: HashMap<String, Object>,
+    arcs: Vec<Vec<Vec<f64>>>,
+}
+
+#[derive(Deserialize)]
+struct Transform {
+    scale: [f64; 2],
+    translate: [f64; 2],
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum Object {
+    GeometryCollection {
+        geometries: Vec<Object>,
+    },
+    Polygon {
+        arcs: Vec<Vec<i64>>,
+    },
+    MultiPolygon {
+        arcs: Vec<Vec<Vec<i64>>>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// A land polygon: outer ring then holes, as `(lng, lat)` points. Longitudes
+/// are unwrapped along each ring so rings crossing the antimeridian stay
+/// continuous, and rings around a pole are closed along it.
+struct Polygon {
+    rings: Vec<Vec<(f64, f64)>>,
+    min_lng: f64,
+    max_lng: f64,
+    min_lat: f64,
+    max_lat: f64,
+}
+
+/// Coastline polygons with a grid index of the polygons overlapping each cell
+pub struct Land {
+    polygons: Vec<Polygon>,
+    grid: Vec<Vec<usize>>,
+}
+
+/// Load the land TopoJSON (Natural Earth `land` object, as bundled with the
+/// client) and build its spatial index. Returns the number of polygons.
+pub async fn init(path: &str) -> Result<usize> {
+    let json = tokio::fs::read_to_string(path).await?;
+    let land = tokio::task::spawn_blocking(move || Land::from_topojson(&json)).await??;
+    let count = land.polygons.len();
+    LAND.set(land)
+        .map_err(|_| anyhow!("Land already initialized"))?;
+    Ok(count)
+}
+
+/// Whether a point is on land. Always false when no land data is loaded.
+pub fn is_on_land(point: &LngLat) -> bool {
+    LAND.get().is_some_and(|land| land.contains(point))
+}
+
+/// Whether sailing straight from `from` to `to` is impossible: `to` is on
+/// land or the way there crosses a coastline. Moves starting on land (a start
+/// line the coastline resolution puts ashore) are only checked at `to`.
+pub fn crosses_land(from: &LngLat, to: &LngLat) -> bool {
+    LAND.get().is_some_and(|land| land.crosses(from, to))
+}
+
+impl Land {
+    pub fn from_topojson(json: &str) -> Result<Land> {
+        let topology: Topology = serde_json::from_str(json)?;
+        let arcs = decode_arcs(&topology);
+
+        let mut polygons = Vec::new();
+        for object in topology.objects.values() {
+            collect_polygons(object, &arcs, &mut polygons)?;
+        }
+        if polygons.is_empty() {
+            return Err(anyhow!("No land polygons"));
+        }
+
+        let mut grid = vec![Vec::new(); GRID_COLS * GRID_ROWS];
+        for (index, polygon) in polygons.iter().enumerate() {
+            let (row_min, row_max) = (row(polygon.min_lat), row(polygon.max_lat));
+            let col_min = (polygon.min_lng / CELL_DEGREES).floor() as i64;
+            let col_max = (polygon.max_lng / CELL_DEGREES).floor() as i64;
+            let col_max = col_max.min(col_min + GRID_COLS as i64 - 1);
+            for col in col_min..=col_max {
+                let col = (col + GRID_COLS as i64 / 2).rem_euclid(GRID_COLS as i64) as usize;
+                for row in row_min..=row_max {
+                    grid[row * GRID_COLS + col].push(index);
+                }
+            }
+        }
+
+        Ok(Land { polygons, grid })
+    }
+
+    pub fn contains(&self, point: &LngLat) -> bool {
+        self.grid[cell(point.lng, point.lat)].iter().any(|&index| {
+            let polygon = &self.polygons[index];
+            [0.0, 360.0, -360.0].iter().any(|shift| {
+                let lng = point.lng + shift;
+                polygon.bbox_contains(lng, point.lat) && polygon.contains(lng, point.lat)
+            })
+        })
+    }
+
+    pub fn crosses(&self, from: &LngLat, to: &LngLat) -> bool {
+        if self.contains(to) {
+            return true;
+        }
+        if self.contains(from) {
+            return false;
+        }
+
+        // Shortest way across the antimeridian
+        let (a, b) = (
+            (from.lng, from.lat),
+            (geo::unwrap_lng(to.lng, from.lng), to.lat),
+        );
+
+        let mut candidates: Vec<usize> = Vec::new();
+        let (lng_min, lng_max) = (a.0.min(b.0), a.0.max(b.0));
+        let (lat_min, lat_max) = (a.1.min(b.1), a.1.max(b.1));
+        let col_min = (lng_min / CELL_DEGREES).floor() as i64;
+        let col_max = (lng_max / CELL_DEGREES).floor() as i64;
+        for col in col_min..=col_max {
+            let col = (col + GRID_COLS as i64 / 2).rem_euclid(GRID_COLS as i64) as usize;
+            for row in row(lat_min)..=row(lat_max) {
+                candidates.extend(&self.grid[row * GRID_COLS + col]);
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        candidates.into_iter().any(|index| {
+            let polygon = &self.polygons[index];
+            [0.0, 360.0, -360.0].iter().any(|shift| {
+                let (a, b) = ((a.0 + shift, a.1), (b.0 + shift, b.1));
+                a.0.max(b.0) >= polygon.min_lng
+                    && a.0.min(b.0) <= polygon.max_lng
+                    && lat_max >= polygon.min_lat
+                    && lat_min <= polygon.max_lat
+                    && polygon.rings.iter().any(|ring| {
+                        ring.windows(2)
+                            .any(|edge| segments_intersect(a, b, edge[0], edge[1]))
+                    })
+            })
+        })
+    }
+}
+
+impl Polygon {
+    fn bbox_contains(&self, lng: f64, lat: f64) -> bool {
+        lng >= self.min_lng && lng <= self.max_lng && lat >= self.min_lat && lat <= self.max_lat
+    }
+
+    fn contains(&self, lng: f64, lat: f64) -> bool {
+        let mut rings = self.rings.iter();
+        rings
+            .next()
+            .is_some_and(|outer| ring_contains(outer, lng, lat))
+            && !rings.any(|hole| ring_contains(hole, lng, lat))
+    }
+}
+
+/// Absolute arc positions, undoing the quantized delta encoding
+fn decode_arcs(topology: &Topology) -> Vec<Vec<(f64, f64)>> {
+    topology
+        .arcs
+        .iter()
+        .map(|arc| match &topology.transform {
+            Some(transform) => {
+                let (mut x, mut y) = (0.0, 0.0);
+                arc.iter()
+                    .map(|position| {
+                        x += position[0];
+                        y += position[1];
+                        (
+                            x * transform.scale[0] + transform.translate[0],
+                            y * transform.scale[1] + transform.translate[1],
+                        )
+                    })
+                    .collect()
+            }
+            None => arc
+                .iter()
+                .map(|position| (position[0], position[1]))
+                .collect(),
+        })
+        .collect()
+}
+
+fn collect_polygons(
+    object: &Object,
+    arcs: &[Vec<(f64, f64)>],
+    polygons: &mut Vec<Polygon>,
+) -> Result<()> {
+    match object {
+        Object::GeometryCollection { geometries } => {
+            for object in geometries {
+                collect_polygons(object, arcs, polygons)?;
+            }
+        }
+        Object::Polygon { arcs: rings } => polygons.push(build_polygon(rings, arcs)?),
+        Object::MultiPolygon { arcs: parts } => {
+            for rings in parts {
+                polygons.push(build_polygon(rings, arcs)?);
+            }
+        }
+        Object::Other => {}
+    }
+    Ok(())
+}
+
+fn build_polygon(rings: &[Vec<i64>], arcs: &[Vec<(f64, f64)>]) -> Result<Polygon> {
+    let rings = rings
+        .iter()
+        .map(|ring| build_ring(ring, arcs))
+        .collect::<Result<Vec<_>>>()?;
+    let points = || rings.iter().flatten();
+    Ok(Polygon {
+        min_lng: points().map(|p| p.0).fold(f64::INFINITY, f64::min),
+        max_lng: points().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max),
+        min_lat: points().map(|p| p.1).fold(f64::INFINITY, f64::min),
+        max_lat: points().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max),
+        rings,
+    })
+}
+
+/// Join the arcs of a ring (negative indices are reversed arcs), then unwrap
+/// its longitudes and close it along the pole when it goes around one
+fn build_ring(indices: &[i64], arcs: &[Vec<(f64, f64)>]) -> Result<Vec<(f64, f64)>> {
+    let mut points: Vec<(f64, f64)> = Vec::new();
+    for &index in indices {
+        let arc_index = if index < 0 { !index } else { index } as usize;
+        let arc = arcs
+            .get(arc_index)
+            .ok_or(anyhow!("Invalid arc index {}", index))?;
+        let skip = usize::from(!points.is_empty());
+        if index < 0 {
+            points.extend(arc.iter().rev().skip(skip));
+        } else {
+            points.extend(arc.iter().skip(skip));
+        }
+    }
+    let Some(&first) = points.first() else {
+        return Err(anyhow!("Empty ring"));
+    };
+
+    let mut ring = vec![first];
+    for &(lng, lat) in &points[1..] {
+        let previous = ring[ring.len() - 1].0;
+        let mut lng = lng;
+        while lng - previous > 180.0 {
+            lng -= 360.0;
+        }
+        while lng - previous < -180.0 {
+            lng += 360.0;
+        }
+        ring.push((lng, lat));
+    }
+
+    let last = ring[ring.len() - 1];
+    if (last.0 - first.0).abs() > 180.0 {
+        let pole = if last.1 < 0.0 { -90.0 } else { 90.0 };
+        ring.extend([(last.0, pole), (first.0, pole), first]);
+    }
+    Ok(ring)
+}
+
+/// Even-odd ray casting
+fn ring_contains(ring: &[(f64, f64)], lng: f64, lat: f64) -> bool {
+    let mut inside = false;
+    for edge in ring.windows(2) {
+        let ((x1, y1), (x2, y2)) = (edge[0], edge[1]);
+        if (y1 > lat) != (y2 > lat) && lng < (x2 - x1) * (lat - y1) / (y2 - y1) + x1 {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn segments_intersect(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> bool {
+    let orientation = |p: (f64, f64), q: (f64, f64), r: (f64, f64)| {
+        ((q.0 - p.0) * (r.1 - p.1) - (q.1 - p.1) * (r.0 - p.0)).signum()
+    };
+    orientation(a, b, c) != orientation(a, b, d) && orientation(c, d, a) != orientation(c, d, b)
+}
+
+fn row(lat: f64) -> usize {
+    (((lat + 90.0) / CELL_DEGREES).floor().max(0.0) as usize).min(GRID_ROWS - 1)
+}
+
+fn cell(lng: f64, lat: f64) -> usize {
+    let col = (((lng + 180.0) / CELL_DEGREES).floor() as i64).rem_euclid(GRID_COLS as i64);
+    row(lat) * GRID_COLS + col as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(lng: f64, lat: f64) -> LngLat {
+        LngLat { lng, lat }
+    }
+
+    /// An island from 0 to 10 with a lagoon from 4 to 6, a strip of land
+    /// across the antimeridian and a polar cap south of -80, quantized to
+    /// whole degrees
+    const TOPOLOGY: &str = r#"{
+        "type": "Topology",
+        "transform": { "scale": [1, 1], "translate": [-180, -90] },
+        "objects": {
+            "land": {
+                "type": "GeometryCollection",
+                "geometries": [
+                    { "type": "MultiPolygon", "arcs": [[[0], [1]], [[2]]] },
+                    { "type": "Polygon", "arcs": [[3]] }
+                ]
+            }
+        },
+        "arcs": [
+            [[180, 90], [10, 0], [0, 10], [-10, 0], [0, -10]],
+            [[184, 94], [0, 2], [2, 0], [0, -2], [-2, 0]],
+            [[355, 120], [-350, 0], [0, 5], [350, 0], [0, -5]],
+            [[0, 10], [90, 0], [90, 0], [90, 0], [90, 0]]
+        ]
+    }"#;
+
+    #[test]
+    fn test_is_on_land() {
+        let land = Land::from_topojson(TOPOLOGY).unwrap();
+        assert!(land.contains(&point(2.0, 2.0)));
+        assert!(!land.contains(&point(5.0, 5.0)), "lagoon");
+        assert!(!land.contains(&point(12.0, 5.0)));
+
+        // Both sides of the antimeridian
+        assert!(land.contains(&point(178.0, 32.0)));
+        assert!(land.contains(&point(-178.0, 32.0)));
+        assert!(!land.contains(&point(170.0, 32.0)));
+
+        assert!(land.contains(&point(45.0, -85.0)));
+        assert!(land.contains(&point(-135.0, -85.0)));
+        assert!(!land.contains(&point(45.0, -75.0)));
+    }
+
+    #[test]
+    fn test_path_crossing_land() {
+        let land = Land::from_topojson(TOPOLOGY).unwrap();
+        assert!(land.crosses(&point(-5.0, 2.0), &point(15.0, 2.0)));
+        assert!(land.crosses(&point(-5.0, 2.0), &point(2.0, 2.0)));
+        assert!(!land.crosses(&point(-5.0, 2.0), &point(-5.0, 12.0)));
+        // Across the antimeridian strip, the short way
+        assert!(land.crosses(&point(178.0, 25.0), &point(-178.0, 40.0)));
+        assert!(!land.crosses(&point(170.0, 25.0), &point(-170.0, 25.0)));
+        // Leaving land is allowed
+        assert!(!land.crosses(&point(2.0, 2.0), &point(-5.0, 2.0)));
+    }
+
+    #[test]
+    fn test_bundled_coastline() {
+        let json = std::fs::read_to_string("../client/src/static/land-50m.json");
+        let Ok(json) = json else {
+            return;
+        };
+        let land = Land::from_topojson(&json).unwrap();
+        // Paris, Atlantic, Chukotka across the antimeridian, Antarctica
+        assert!(land.contains(&point(2.35, 48.85)));
+        assert!(!land.contains(&point(-30.0, 40.0)));
+        assert!(land.contains(&point(179.9, 67.0)));
+        assert!(land.contains(&point(-179.9, 67.0)));
+        assert!(land.contains(&point(0.0, -85.0)));
+        // Bay of Biscay to the Mediterranean through France
+        assert!(land.crosses(&point(-3.0, 46.0), &point(5.0, 42.5)));
+        assert!(!land.crosses(&point(-3.0, 46.0), &point(-10.0, 44.0)));
+    }
+}
@@ -0,0 +1,143 @@
+//! Land/sea mask rasters on the wind raster grids, so the client can check
+//! land and shade the coastline with the same pixels as the wind.
+//!
+//! Pixel `(x, y)` is the grid point at longitude `x * 360 / width` (0..360)
+//! and latitude `90 - y * 360 / width`, like the wind PNGs. Land is 255, sea 0.
+
+use anyhow::Result;
+use bytes::Bytes;
+use object_store::ObjectStoreExt;
+use png::{BitDepth, ColorType, Encoder};
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::config::config;
+use crate::courses::LngLat;
+use crate::grib_png::{WIDTH_05, WIDTH_025};
+use crate::land::Land;
+use crate::{geo, s3};
+
+/// Widths of the wind rasters: 0.5° (VLM) and 0.25° (NCAR)
+const WIDTHS: [usize; 2] = [WIDTH_05, WIDTH_025];
+
+const LAND: u8 = 255;
+const SEA: u8 = 0;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LandMask {
+    pub width: usize,
+    pub height: usize,
+    pub png_url: String,
+}
+
+fn raster_path(width: usize) -> String {
+    format!("land/mask-{width}.png")
+}
+
+/// Masks of every wind raster resolution, as uploaded by `build`
+pub fn list() -> Vec<LandMask> {
+    WIDTHS
+        .iter()
+        .map(|&width| LandMask {
+            width,
+            height: width / 2,
+            png_url: config().s3.raster_url(&raster_path(width)),
+        })
+        .collect()
+}
+
+/// One byte per pixel, row by row from the north pole
+pub fn rasterize(land: &Land, width: usize) -> Vec<u8> {
+    let height = width / 2;
+    let pixel_size = 360.0 / width as f64;
+    let mut mask = vec![SEA; width * height];
+    for y in 0..height {
+        let lat = 90.0 - y as f64 * pixel_size;
+        for x in 0..width {
+            let point = LngLat {
+                lng: geo::wrap_lng(x as f64 * pixel_size),
+                lat,
+            };
+            if land.contains(&point) {
+                mask[y * width + x] = LAND;
+            }
+        }
+    }
+    mask
+}
+
+fn encode_png(mask: &[u8], width: usize) -> Result<Bytes> {
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buffer, width as u32, (width / 2) as u32);
+        encoder.set_color(ColorType::Grayscale);
+        encoder.set_depth(BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(mask)?;
+    }
+
+    Ok(Bytes::from(buffer))
+}
+
+/// Rasterize the coastline TopoJSON at every wind raster resolution and
+/// upload the masks to the raster bucket
+pub async fn build(file: Option<PathBuf>) -> Result<()> {
+    let path = file.unwrap_or_else(|| PathBuf::from(&config().land_file));
+    let json = tokio::fs::read_to_string(&path).await?;
+    let land = tokio::task::spawn_blocking(move || Land::from_topojson(&json)).await??;
+    let land = std::sync::Arc::new(land);
+
+    for width in WIDTHS {
+        let land = land.clone();
+        let png = tokio::task::spawn_blocking(move || encode_png(&rasterize(&land, width), width))
+            .await??;
+        let key = raster_path(width);
+        s3::raster_client()
+            .put(&key.as_str().into(), png.into())
+            .await?;
+        log::info!(
+            "Uploaded {} land mask to {}",
+            path.display(),
+            config().s3.raster_url(&key)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A square island from 11°E to 19°E, 1°N to 9°N
+    fn island() -> Land {
+        let json = r#"{
+            "type": "Topology",
+            "objects": {"land": {"type": "GeometryCollection", "geometries": [
+                {"type": "Polygon", "arcs": [[0]]}
+            ]}},
+            "arcs": [[[11, 1], [19, 1], [19, 9], [11, 9], [11, 1]]]
+        }"#;
+        Land::from_topojson(json).unwrap()
+    }
+
+    #[test]
+    fn test_rasterize() {
+        let width = 72;
+        let mask = rasterize(&island(), width);
+        assert_eq!(mask.len(), width * width / 2);
+
+        let at = |lng: usize, lat: usize| mask[(90 - lat) / 5 * width + lng / 5];
+        assert_eq!(at(15, 5), LAND);
+        assert_eq!(at(5, 5), SEA);
+        assert_eq!(at(15, 20), SEA);
+        assert_eq!(mask.iter().filter(|&&pixel| pixel == LAND).count(), 1);
+    }
+
+    #[test]
+    fn test_encode_png() {
+        let png = encode_png(&vec![SEA; 720 * 360], 720).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+}
@@ -0,0 +1,135 @@
+//! Log output, plain text by env_logger unless `REWIND_LOG_FORMAT=json`
+//! switches to one JSON object per line for log stores (Loki, CloudWatch).
+//!
+//! In JSON, `log` records go through tracing and carry the fields of the
+//! spans they are logged in: `race_id`, `player_id` and `course_key` around
+//! player connections, race lifecycles and result saves. Both formats take
+//! their levels from `RUST_LOG`, `info` by default in JSON.
+//!
+//! When `OTEL_EXPORTER_OTLP_ENDPOINT` is set, the spans are also exported
+//! over OTLP/HTTP (Jaeger, Tempo, Honeycomb): HTTP requests, websocket
+//! messages, race creation, GRIB imports and storage calls, with the
+//! database queries as events of their spans under `sqlx::query=debug`.
+//!
+//! When `REWIND_SENTRY_DSN` is set, panics and `log::error!` records are
+//! reported to Sentry, with the request of the handler they happened in;
+//! warnings and info records of the same scope are sent as breadcrumbs.
+
+use std::env;
+
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, fmt};
+
+/// Exporter of the spans and Sentry client, flushed when dropped at the
+/// end of `main`
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+    _sentry: Option<sentry::ClientInitGuard>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush traces: {}", e);
+        }
+    }
+}
+
+/// Start logging and tracing, before anything is logged
+pub fn init() -> Telemetry {
+    let format = env::var("REWIND_LOG_FORMAT");
+    let json = format.as_deref() == Ok("json");
+    let otlp = env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some()
+        || env::var_os("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_some();
+
+    let sentry = env::var("REWIND_SENTRY_DSN").ok().map(init_sentry);
+
+    let provider = if json || otlp || sentry.is_some() {
+        init_tracing(json, otlp, sentry.is_some())
+    } else {
+        env_logger::init();
+        None
+    };
+
+    if let Ok(other) = format.as_deref()
+        && other != "json"
+        && other != "text"
+    {
+        log::warn!("Unknown REWIND_LOG_FORMAT {}, logging text", other);
+    }
+    Telemetry {
+        provider,
+        _sentry: sentry,
+    }
+}
+
+/// Sentry client, tagged with the release and the `SENTRY_ENVIRONMENT`
+fn init_sentry(dsn: String) -> sentry::ClientInitGuard {
+    sentry::init((
+        dsn,
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            ..Default::default()
+        },
+    ))
+}
+
+fn init_tracing(json: bool, otlp: bool, sentry: bool) -> Option<SdkTracerProvider> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let output = if json {
+        fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed()
+    } else {
+        fmt::layer().boxed()
+    };
+
+    let provider = if otlp {
+        match tracer_provider() {
+            Ok(provider) => Some(provider),
+            Err(e) => {
+                eprintln!(
+                    "Failed to set up the OTLP exporter, not exporting traces: {}",
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let export = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("rewind")));
+
+    // Also forwards the `log` records to tracing
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(output)
+        .with(export)
+        .with(sentry.then(sentry::integrations::tracing::layer))
+        .init();
+    provider
+}
+
+/// Batch exporter to the endpoint of the OTEL_EXPORTER_OTLP_* variables,
+/// named after OTEL_SERVICE_NAME or `rewind`
+fn tracer_provider() -> anyhow::Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder().with_http().build()?;
+    let service_name = env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "rewind".to_string());
+    let resource = Resource::builder().with_service_name(service_name).build();
+    Ok(SdkTracerProvider::builder()
+        .with_resource(resource)
+        .with_batch_exporter(exporter)
+        .build())
+}
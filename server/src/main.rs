@@ -1,39 +1,102 @@
 use chrono::Utc;
 use clap::Parser;
-use cli::{Cli, Command};
+use cli::{Cli, Command, DbCommand, StorageCommand};
 
+mod account;
+mod admin_stats;
+mod analysis;
+mod api_tokens;
+mod audit;
 mod auth;
+mod avatars;
+mod backups;
+mod bathymetry;
 mod cli;
+mod cluster;
+mod comparison;
 mod config;
+mod course_metrics;
 mod courses;
+mod daily;
 mod db;
+mod discord;
 mod email;
+mod email_queue;
+mod email_templates;
+mod energy;
+mod gates;
+mod geo;
 mod grib_png;
 mod grib_store;
 mod grib_stream;
+mod heatmaps;
+mod land;
+mod land_mask;
+mod logging;
+mod meteogram;
 mod multiplayer;
 mod ncar_source;
+mod oauth;
+mod path_export;
+mod personal_bests;
+mod polars;
 mod profiles;
+mod race_events;
 mod race_results;
+mod race_stats;
+mod race_summary;
 mod retry;
+mod roles;
+mod routing;
 mod s3;
 mod s3_multipart;
+mod scheduled_races;
+mod seasons;
+mod series;
 mod server;
+mod simulation;
+mod storage_gc;
+mod storage_usage;
+mod traced_store;
+mod typescript;
+mod units;
+mod wind_png;
+mod webhooks;
 mod wind_reports;
 
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
-    env_logger::init();
+    let _telemetry = logging::init();
+
+    let args = Cli::parse();
+
+    // Exporting the types needs neither the configuration nor the database
+    if let Command::ExportTypes { file } = args.cmd {
+        typescript::export(file).unwrap();
+        return;
+    }
 
     // Validate config early to get clear error messages on missing env vars
     config::validate();
 
+    // The schema commands handle migrations themselves
+    if let Command::Db { cmd } = args.cmd {
+        match cmd {
+            DbCommand::Migrate => db::migrate().await.unwrap(),
+            DbCommand::Status => db::print_status().await.unwrap(),
+            DbCommand::Revert { version } => db::revert(version).await.unwrap(),
+            DbCommand::Backup { file, upload } => backups::backup(file, upload).await.unwrap(),
+            DbCommand::Restore { file, download } => {
+                backups::restore(file, download).await.unwrap()
+            }
+        }
+        return;
+    }
+
     // Initialize database pool and run migrations
     db::init().await.expect("Failed to initialize database");
 
-    let args = Cli::parse();
-
     match args.cmd {
         Command::Http { address } => server::run(address).await,
         Command::RebuildDb { truncate } => {
@@ -50,5 +113,23 @@ async fn main() {
         }
         Command::DumpCourses { file } => courses::dump(file).await.unwrap(),
         Command::RestoreCourses { file } => courses::restore(file).await.unwrap(),
+        Command::CheckCourse { key } => {
+            if !courses::check(&key).await.unwrap() {
+                std::process::exit(1);
+            }
+        }
+        Command::BuildLandMask { file } => land_mask::build(file).await.unwrap(),
+        Command::GrantRole { email, role } => roles::grant_by_email(&email, role).await.unwrap(),
+        Command::RevokeRole { email, role } => roles::revoke_by_email(&email, role).await.unwrap(),
+        Command::ListRoles => roles::print_all().await.unwrap(),
+        Command::BuildHeatmap { key } => heatmaps::build_from_cli(key).await.unwrap(),
+        Command::Storage {
+            cmd: StorageCommand::Gc { delete },
+        } => storage_gc::run(delete).await.unwrap(),
+        Command::Storage {
+            cmd: StorageCommand::Report,
+        } => storage_usage::report().await.unwrap(),
+        Command::ExportTypes { .. } => unreachable!("exported before the configuration"),
+        Command::Db { .. } => unreachable!("run before the database init"),
     }
 }
@@ -0,0 +1,117 @@
+//! Wind forecast at one point: every stored wind report of a time window
+//! sampled at a position.
+
+use anyhow::Result;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::courses::LngLat;
+use crate::simulation::{self, WindRaster};
+use crate::{units, wind_reports};
+
+/// Longest window sampled, in days
+pub const MAX_DAYS: i64 = 16;
+
+/// Rasters downloaded at once
+const CONCURRENCY: usize = 4;
+
+#[derive(Debug, Deserialize)]
+pub struct MeteogramQuery {
+    pub lng: f64,
+    pub lat: f64,
+    /// Window sampled, in ms since epoch
+    pub from: i64,
+    pub to: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Sample {
+    /// Time of the wind report, in ms since epoch
+    pub time: i64,
+    /// True wind speed in knots and direction (where it blows from)
+    pub tws: f64,
+    pub twd: f64,
+}
+
+/// Wind of a raster at a position, None out of the raster
+pub fn sample(time: i64, raster: &WindRaster, position: &LngLat) -> Option<Sample> {
+    let (u, v) = raster.speed_at(position.lng, position.lat)?;
+    Some(Sample {
+        time,
+        tws: units::ms_to_knots(u.hypot(v)),
+        twd: (-u).atan2(-v).to_degrees().rem_euclid(360.0),
+    })
+}
+
+impl MeteogramQuery {
+    /// Whether the window ends after it starts and lasts at most `MAX_DAYS`
+    pub fn is_valid_window(&self) -> bool {
+        let max_window = MAX_DAYS * 24 * 60 * 60 * 1000;
+        self.to
+            .checked_sub(self.from)
+            .is_some_and(|window| (0..=max_window).contains(&window))
+    }
+}
+
+/// Wind at `position` in every stored report from `from` to `to`, in order
+pub async fn meteogram(position: &LngLat, from: i64, to: i64) -> Result<Vec<Sample>> {
+    let reports = wind_reports::get_reports_between(from, to).await?;
+    stream::iter(reports)
+        .map(|report| async move {
+            let raster = simulation::load_raster(&report.png_path).await?;
+            Ok(sample(report.time.timestamp_millis(), &raster, position))
+        })
+        .buffered(CONCURRENCY)
+        .try_filter_map(|sample| async move { Ok(sample) })
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1.0
+    }
+
+    #[test]
+    fn test_sample() {
+        let position = LngLat {
+            lng: -4.5,
+            lat: 48.0,
+        };
+
+        // Blowing south at ~10 m/s: from the north
+        let north = sample(1000, &WindRaster::uniform(128, 85), &position).unwrap();
+        assert_eq!(north.time, 1000);
+        assert!(close(north.tws, units::ms_to_knots(10.0)));
+        assert!(close(north.twd, 0.0) || close(north.twd, 360.0));
+
+        // Blowing east: from the west
+        let west = sample(1000, &WindRaster::uniform(170, 128), &position).unwrap();
+        assert!(close(west.twd, 270.0));
+
+        let pole = LngLat {
+            lng: 0.0,
+            lat: 91.0,
+        };
+        assert_eq!(sample(1000, &WindRaster::uniform(128, 128), &pole), None);
+    }
+
+    #[test]
+    fn test_window() {
+        let query = |from, to| MeteogramQuery {
+            lng: 0.0,
+            lat: 0.0,
+            from,
+            to,
+        };
+        let day = 24 * 60 * 60 * 1000;
+        assert!(query(0, MAX_DAYS * day).is_valid_window());
+        assert!(!query(0, MAX_DAYS * day + 1).is_valid_window());
+        assert!(!query(day, 0).is_valid_window());
+        assert!(!query(-1, i64::MAX).is_valid_window());
+        assert!(!query(i64::MIN, 0).is_valid_window());
+    }
+}
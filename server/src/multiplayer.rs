@@ -3,18 +3,36 @@ use axum::extract::ws::{Message, WebSocket};
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use futures::{SinkExt, StreamExt};
-use object_store::ObjectStoreExt;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{RwLock, mpsc, watch};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::Instrument;
+use ts_rs::TS;
 
 use crate::{
-    courses::{self, Course},
-    race_results::{self, PathPoint},
+    avatars, cluster,
+    config::DiscordEvent,
+    courses::{self, Course, LngLat},
+    daily,
+    discord::{self, Notification},
+    energy::{Battery, System},
+    gates,
+    geo::{self, Angle, Latitude, Longitude},
+    land, personal_bests, polars,
+    race_events::{self, NewRaceEvent, RaceEventKind},
+    race_results::{self, NewRaceResult, PathPoint, RecordedPath, ResultStatus},
+    race_stats, race_summary,
+    routing::{self, Hint},
     s3,
+    seasons::Period,
+    simulation::{self, Boat, Helm, Simulation, WindRaster},
+    webhooks::{self, WebhookEvent},
     wind_reports::{self, WindReport},
 };
 
@@ -22,44 +40,174 @@ use crate::{
 // Message Types
 // ============================================================================
 
+/// Version of the message protocol spoken by the server, bumped when a
+/// message changes in a way older clients can't read
+const PROTOCOL_VERSION: u32 = 1;
+/// Oldest client protocol the server still speaks
+const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Encoding of the messages sent to a client. Clients may send either one
+/// whatever they picked: JSON text frames or MessagePack binary frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+pub enum Encoding {
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    /// Binary frames of MessagePack maps, with the same field names as JSON
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl Encoding {
+    fn encode(self, message: &ServerMessage) -> anyhow::Result<Message> {
+        Ok(match self {
+            Encoding::Json => Message::Text(serde_json::to_string(message)?.into()),
+            Encoding::MessagePack => Message::Binary(rmp_serde::to_vec_named(message)?.into()),
+        })
+    }
+}
+
 /// Messages sent from client to server
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, TS)]
 #[serde(tag = "type", rename_all_fields = "camelCase")]
 pub enum ClientMessage {
+    /// First message of a connection, with the protocol version of the
+    /// client; clients that don't send it speak version 1
+    Hello {
+        protocol_version: u32,
+        /// Encoding of the server messages, JSON when unset
+        #[serde(default)]
+        encoding: Encoding,
+    },
     CreateRace {
         course_key: String,
         player_name: String,
         persistent_id: String,
+        /// Private single-player time trial: hidden from the race list and not joinable
+        #[serde(default)]
+        solo: bool,
+        /// Race the course in a random historical weather window instead of its own
+        #[serde(default)]
+        random_weather: bool,
+        /// Boat class (polar name) among the course classes; the course default when unset
+        #[serde(default)]
+        polar: Option<String>,
     },
     JoinRace {
         race_id: String,
         player_name: String,
         persistent_id: String,
+        /// Boat class (polar name) among the course classes; the course default when unset
+        #[serde(default)]
+        polar: Option<String>,
+    },
+    /// Race today's daily challenge alone
+    StartDaily {
+        player_name: String,
+        persistent_id: String,
+    },
+    /// Queue for an automatically formed race on a course
+    QuickMatch {
+        course_key: String,
+        player_name: String,
+        persistent_id: String,
+        /// Boat class (polar name) among the course classes; the course default when unset
+        #[serde(default)]
+        polar: Option<String>,
     },
     LeaveRace,
     StartRace,
+    RetireFromRace,
+    /// Once the player's race is over: move to a fresh race with the same
+    /// course and settings, joining the rematch if another player proposed it
+    ProposeRematch,
+    /// Add the top Hall of Fame runs of the course as ghost boats
+    AddGhosts {
+        count: usize,
+    },
     PositionUpdate {
-        lng: f32,
-        lat: f32,
-        heading: f32,
+        lng: Longitude,
+        lat: Latitude,
+        heading: Angle,
+    },
+    /// Helm input for the server-side simulation: steer `heading`, or hold
+    /// the signed true wind angle `twa` when set
+    Helm {
+        heading: Angle,
+        twa: Option<Angle>,
+    },
+    /// Hoist another sail of the boat class inventory
+    ChangeSail {
+        sail: String,
+    },
+    /// Switch a system of the boat on or off, on courses managing energy
+    ToggleSystem {
+        system: System,
+        on: bool,
     },
+    /// Ask for the best way to sail toward the next mark
+    AskCoach,
     GateCrossed {
         gate_index: usize,
+        #[ts(type = "number")]
         course_time: i64,
     },
+    /// Take back a running race after the connection dropped, as the player
+    /// it was sailed by
+    Resume {
+        race_id: String,
+        player_id: String,
+        persistent_id: String,
+    },
+}
+
+impl ClientMessage {
+    /// Name of the message, its `type` tag
+    fn kind(&self) -> &'static str {
+        match self {
+            ClientMessage::Hello { .. } => "Hello",
+            ClientMessage::CreateRace { .. } => "CreateRace",
+            ClientMessage::JoinRace { .. } => "JoinRace",
+            ClientMessage::StartDaily { .. } => "StartDaily",
+            ClientMessage::QuickMatch { .. } => "QuickMatch",
+            ClientMessage::LeaveRace => "LeaveRace",
+            ClientMessage::StartRace => "StartRace",
+            ClientMessage::RetireFromRace => "RetireFromRace",
+            ClientMessage::ProposeRematch => "ProposeRematch",
+            ClientMessage::AddGhosts { .. } => "AddGhosts",
+            ClientMessage::PositionUpdate { .. } => "PositionUpdate",
+            ClientMessage::Helm { .. } => "Helm",
+            ClientMessage::ChangeSail { .. } => "ChangeSail",
+            ClientMessage::ToggleSystem { .. } => "ToggleSystem",
+            ClientMessage::AskCoach => "AskCoach",
+            ClientMessage::GateCrossed { .. } => "GateCrossed",
+            ClientMessage::Resume { .. } => "Resume",
+        }
+    }
 }
 
 /// Messages sent from server to client
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, TS)]
 #[serde(tag = "type", rename_all_fields = "camelCase")]
 pub enum ServerMessage {
     Error {
         message: String,
     },
+    /// Answer to `Hello`: the protocol version of the connection, the lower
+    /// of the client and server ones
+    Welcome {
+        protocol_version: u32,
+        encoding: Encoding,
+    },
     RaceCreated {
         race_id: String,
         player_id: String,
         wind_raster_sources: Vec<WindRasterSource>,
+        /// Start of the race's weather window, when it differs from the course default
+        #[ts(type = "number")]
+        course_start_time: i64,
+        /// Boat class (polar name) of the player
+        polar: String,
     },
     RaceJoined {
         race_id: String,
@@ -68,6 +216,11 @@ pub enum ServerMessage {
         wind_raster_sources: Vec<WindRasterSource>,
         players: Vec<PlayerInfo>,
         is_creator: bool,
+        /// Start of the race's weather window, when it differs from the course default
+        #[ts(type = "number")]
+        course_start_time: i64,
+        /// Boat class (polar name) of the player
+        polar: String,
     },
     PlayerJoined {
         player_id: String,
@@ -76,6 +229,22 @@ pub enum ServerMessage {
     PlayerLeft {
         player_id: String,
     },
+    /// The connection took over the player's race, answering `Resume`
+    RaceResumed {
+        race_id: String,
+        player_id: String,
+        #[ts(type = "number | null")]
+        race_time: Option<i64>,
+    },
+    /// Waiting in the quick match queue; a race is formed by `match_deadline`
+    /// (ms timestamp) or as soon as `min_players` are queued
+    MatchQueued {
+        course_key: String,
+        queued_players: usize,
+        min_players: usize,
+        #[ts(type = "number")]
+        match_deadline: i64,
+    },
     RaceCountdown {
         seconds: i32,
     },
@@ -86,47 +255,134 @@ pub enum ServerMessage {
         heading: f32,
     },
     SyncRaceTime {
+        #[ts(type = "number")]
         race_time: i64,
     },
+    /// A player was on the course side of the start line at the gun (`over`),
+    /// or got back behind it. Gate 0 doesn't count until then.
+    Ocs {
+        player_id: String,
+        over: bool,
+    },
+    /// Share of the wind left to the player by the boats upwind, sent when it changes
+    WindShadow {
+        factor: f64,
+    },
+    /// Best way to sail toward the player's next mark, answering `AskCoach`
+    CoachHint {
+        hint: Hint,
+    },
+    /// Battery level (0-1) and systems of the player's boat, sent when they change
+    Energy {
+        level: f64,
+        autopilot: bool,
+        instruments: bool,
+        hydro: bool,
+    },
     RaceEnded {
         reason: String,
     },
+    /// The player's finish beat their best time on the course with this boat
+    /// class, or is their first finish on it (`previous_best` None)
+    PersonalBest {
+        course_key: String,
+        #[ts(type = "number")]
+        finish_time: i64,
+        #[ts(type = "number | null")]
+        previous_best: Option<i64>,
+    },
+    /// Invitation to the rematch of the race, sent once the player's race is over
+    RematchProposed {
+        race_id: String,
+        proposer_name: String,
+    },
     Leaderboard {
         entries: Vec<LeaderboardEntry>,
     },
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, TS)]
 #[serde(rename_all = "camelCase")]
 pub struct WindRasterSource {
     #[serde(with = "chrono::serde::ts_milliseconds")]
+    #[ts(type = "number")]
     time: DateTime<Utc>,
     png_url: String,
 }
 
-impl From<&WindReport> for WindRasterSource {
-    fn from(report: &WindReport) -> Self {
-        WindRasterSource {
-            time: report.time,
-            png_url: report.png_url(),
+impl WindRasterSource {
+    /// Sources of the reports, with their URLs pre-signed for the lifetime
+    /// configured when the rasters bucket is private
+    #[tracing::instrument(skip_all)]
+    pub async fn for_reports(reports: &[WindReport]) -> anyhow::Result<Vec<WindRasterSource>> {
+        let mut sources = Vec::with_capacity(reports.len());
+        for report in reports {
+            sources.push(WindRasterSource {
+                time: report.time,
+                png_url: report.png_url().await?,
+            });
         }
+        Ok(sources)
     }
 }
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, TS)]
 #[serde(rename_all = "camelCase")]
 pub struct LeaderboardEntry {
     pub player_id: String,
     pub player_name: String,
     pub next_gate_index: usize,
     pub distance_to_next_gate: f64,
+    pub distance_to_finish: f64,
+    #[ts(type = "number | null")]
     pub finish_time: Option<i64>,
+    pub status: PlayerStatus,
+    /// Boat class: standings are grouped by class
+    pub polar: String,
+    /// Course times each lap was completed, on courses of several laps
+    #[ts(type = "Array<number>")]
+    pub lap_times: Vec<i64>,
+}
+
+/// Race status of a player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub enum PlayerStatus {
+    Racing,
+    Finished,
+    /// Gave up before finishing (explicit retirement, or left mid-race)
+    Retired,
+    /// Did not finish before the course time limit
+    Dnf,
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl PlayerStatus {
+    /// Leaderboard ordering: finishers, then boats still racing, then DNF, then retired
+    fn rank(&self) -> u8 {
+        match self {
+            PlayerStatus::Finished => 0,
+            PlayerStatus::Racing => 1,
+            PlayerStatus::Dnf => 2,
+            PlayerStatus::Retired => 3,
+        }
+    }
+
+    fn result_status(&self) -> Option<ResultStatus> {
+        match self {
+            PlayerStatus::Racing => None,
+            PlayerStatus::Finished => Some(ResultStatus::Finished),
+            PlayerStatus::Retired => Some(ResultStatus::Retired),
+            PlayerStatus::Dnf => Some(ResultStatus::Dnf),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
 pub struct PlayerInfo {
     pub id: String,
     pub name: String,
+    /// Small avatar of the player's profile, None for ghosts
+    pub avatar_url: Option<String>,
 }
 
 // ============================================================================
@@ -138,24 +394,135 @@ pub struct Player {
     pub id: String,
     pub name: String,
     pub persistent_id: String,
+    /// Boat class (polar name)
+    pub polar: String,
     pub tx: mpsc::UnboundedSender<ServerMessage>,
     pub position: Option<(f64, f64)>, // (lng, lat)
     pub heading: f32,
-    pub next_gate_index: usize,       // 0..gates.len() for gates, gates.len() for finish
+    pub next_gate_index: usize,       // Index of the next mark, see `Course::mark`
     pub finish_time: Option<i64>,     // None = racing, Some(time) = finished
-    pub path_history: Vec<PathPoint>, // Accumulated path for replay
+    pub status: PlayerStatus,
+    /// On the course side of the start line at the gun, until back behind it
+    pub ocs: bool,
+    /// Course times each lap was completed
+    pub lap_times: Vec<i64>,
+    /// Course times each mark before the finish was crossed, in sailing order
+    pub gate_times: Vec<i64>,
+    pub path_history: RecordedPath, // Accumulated path for replay
     pub last_sample_instant: Option<Instant>, // For 100ms real-time sampling
+    /// Boat simulated by the server, once a simulated race has started
+    pub boat: Option<Boat>,
+    /// When the connection dropped (Unix ms), while the race can be resumed
+    pub disconnected_at: Option<i64>,
 }
 
 impl Player {
+    pub fn new(
+        id: String,
+        name: String,
+        persistent_id: String,
+        polar: String,
+        tx: mpsc::UnboundedSender<ServerMessage>,
+    ) -> Self {
+        Player {
+            id,
+            name,
+            persistent_id,
+            polar,
+            tx,
+            position: None,
+            heading: 0.0,
+            next_gate_index: 0,
+            finish_time: None,
+            status: PlayerStatus::Racing,
+            ocs: false,
+            lap_times: Vec::new(),
+            gate_times: Vec::new(),
+            path_history: RecordedPath::default(),
+            last_sample_instant: None,
+            boat: None,
+            disconnected_at: None,
+        }
+    }
+
+    /// Whether a crossing of `gate_index` counts: the next gate of a racing
+    /// player, gate 0 only once back behind the start line
+    fn expects_gate(&self, gate_index: usize) -> bool {
+        self.status == PlayerStatus::Racing
+            && gate_index == self.next_gate_index
+            && !(self.ocs && gate_index == 0)
+    }
+
+    /// Record the current position in the path history (100ms real-time
+    /// interval), with the helm of a simulated boat and the wind report in
+    /// effect
+    fn sample_path(&mut self, race_time: i64, wind_report: Option<i64>) {
+        let Some((lng, lat)) = self.position else {
+            return;
+        };
+        let now = Instant::now();
+        let should_sample = self
+            .last_sample_instant
+            .map(|last| now.duration_since(last) >= Duration::from_millis(100))
+            .unwrap_or(true);
+
+        if should_sample {
+            let point = PathPoint {
+                race_time,
+                lng: lng as f32,
+                lat: lat as f32,
+                heading: self.heading,
+            };
+            let helm = self.boat.as_ref().map(|boat| boat.helm);
+            self.path_history.push(point, helm, wind_report);
+            self.last_sample_instant = Some(now);
+        }
+    }
+
+    pub fn info(&self) -> PlayerInfo {
+        PlayerInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            avatar_url: avatars::racer_url(&self.persistent_id),
+        }
+    }
+}
+
+/// Replay of a stored Hall of Fame result, raced as a pseudo-player
+#[derive(Debug, Clone)]
+pub struct GhostBoat {
+    pub id: String,
+    pub result_id: i64,
+    pub name: String,
+    pub path: Vec<PathPoint>,
+}
+
+impl GhostBoat {
     pub fn info(&self) -> PlayerInfo {
         PlayerInfo {
             id: self.id.clone(),
             name: self.name.clone(),
+            avatar_url: None,
         }
     }
 }
 
+/// Maximum number of ghost boats in a race
+const MAX_GHOSTS: usize = 5;
+
+/// What a player joining a race learns: players, wind rasters, course key,
+/// whether they created it, course start time and their boat class
+type JoinedRace = (Vec<PlayerInfo>, Vec<WindRasterSource>, String, bool, i64, String);
+
+/// Settings chosen by the creator of a race
+#[derive(Debug, Clone, Default)]
+pub struct RaceOptions {
+    pub solo: bool,
+    pub random_weather: bool,
+    /// Boat class of the creator; the course default when unset
+    pub boat_class: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct Race {
     pub course: Course,
@@ -166,6 +533,30 @@ pub struct Race {
     pub race_start_time: Option<i64>,
     pub race_ended: bool,
     pub last_activity: DateTime<Utc>,
+    /// Solo time trial, raced alone through the same validation pipeline
+    pub solo: bool,
+    /// Start time of a race opened by the scheduler (no creator can start it)
+    pub scheduled_start: Option<i64>,
+    /// Date of the daily challenge this race is run for
+    pub daily: Option<String>,
+    /// Weather window start, when the course is raced in another weather than its own
+    pub weather_start_time: Option<i64>,
+    /// Replayed Hall of Fame runs, streamed as pseudo-players
+    pub ghosts: Vec<GhostBoat>,
+    /// Final standings of players who left after the start
+    pub departed: Vec<LeaderboardEntry>,
+    /// Rematch proposed by a player after the race
+    pub rematch: Option<Rematch>,
+    /// Server-side boat simulation, when the polar and wind could be loaded.
+    /// Simulated races ignore positions and gate crossings reported by clients.
+    pub simulation: Option<Simulation>,
+}
+
+/// A fresh race proposed as a rematch of a finished one
+#[derive(Debug, Clone)]
+pub struct Rematch {
+    pub race_id: String,
+    pub proposer_name: String,
 }
 
 impl Race {
@@ -179,6 +570,35 @@ impl Race {
             race_start_time: None,
             race_ended: false,
             last_activity: Utc::now(),
+            solo: false,
+            scheduled_start: None,
+            daily: None,
+            weather_start_time: None,
+            ghosts: Vec::new(),
+            departed: Vec::new(),
+            rematch: None,
+            simulation: None,
+        }
+    }
+
+    /// A fresh race with the same course and settings, created by `creator_id`
+    fn rematch_race(&self, creator_id: String) -> Race {
+        let mut race = Race::new(self.course.clone(), self.wind_raster_sources.clone(), creator_id);
+        race.max_players = self.max_players;
+        race.solo = self.solo;
+        race.daily = self.daily.clone();
+        race.weather_start_time = self.weather_start_time;
+        race.ghosts = self.ghosts.clone();
+        race
+    }
+
+    /// Invite a player whose race is over to the rematch, if one was proposed
+    fn send_rematch_invite(&self, player_id: &str) {
+        if let (Some(rematch), Some(player)) = (&self.rematch, self.players.get(player_id)) {
+            let _ = player.tx.send(ServerMessage::RematchProposed {
+                race_id: rematch.race_id.clone(),
+                proposer_name: rematch.proposer_name.clone(),
+            });
         }
     }
 
@@ -186,6 +606,16 @@ impl Race {
         self.race_start_time.is_some()
     }
 
+    /// Time of the wind report in effect at `race_time`, the last one at or
+    /// before it
+    fn wind_report_at(&self, race_time: i64) -> Option<i64> {
+        self.wind_raster_sources
+            .iter()
+            .map(|source| source.time.timestamp_millis())
+            .filter(|time| *time <= race_time)
+            .max()
+    }
+
     fn add_player(&mut self, player: Player) -> anyhow::Result<()> {
         if self.race_started() {
             return Err(anyhow!("Race has already started"));
@@ -200,7 +630,16 @@ impl Race {
 
     fn remove_player(&mut self, player_id: &str) -> Option<Player> {
         self.last_activity = Utc::now();
-        self.players.remove(player_id)
+        let player = self.players.remove(player_id)?;
+
+        // Keep the player's standing visible once the race is under way
+        if self.race_started()
+            && let Some(entry) = self.leaderboard_entry(&player)
+        {
+            self.departed.push(entry);
+        }
+
+        Some(player)
     }
 
     fn broadcast(&self, message: ServerMessage, exclude: Option<&str>) {
@@ -218,67 +657,111 @@ impl Race {
     fn get_player_infos(&self) -> Vec<PlayerInfo> {
         self.players
             .values()
-            .map(|p| PlayerInfo {
-                id: p.id.clone(),
-                name: p.name.clone(),
+            .map(|p| p.info())
+            .chain(self.ghosts.iter().map(|g| g.info()))
+            .collect()
+    }
+
+    /// Position updates for ghost boats at the current race time
+    fn ghost_positions(&self) -> Vec<ServerMessage> {
+        let Some(race_time) = self.current_race_time() else {
+            return Vec::new();
+        };
+        self.ghosts
+            .iter()
+            .filter_map(|ghost| {
+                let point = race_results::interpolate_position(&ghost.path, race_time)?;
+                Some(ServerMessage::PositionUpdate {
+                    player_id: ghost.id.clone(),
+                    lng: point.lng,
+                    lat: point.lat,
+                    heading: point.heading,
+                })
             })
             .collect()
     }
 
     fn is_expired(&self) -> bool {
+        // Scheduled lobbies stay open, even empty, until their start time
+        if !self.race_started()
+            && self
+                .scheduled_start
+                .is_some_and(|start| Utc::now().timestamp_millis() < start)
+        {
+            return false;
+        }
         let inactive_duration = Utc::now() - self.last_activity;
         self.players.is_empty() && inactive_duration.num_minutes() >= 1
     }
 
-    fn compute_leaderboard(&self) -> Vec<LeaderboardEntry> {
-        let num_gates = self.course.gates.len();
+    fn leaderboard_entry(&self, player: &Player) -> Option<LeaderboardEntry> {
+        let finish = self.course.finish_index();
+        let (lng, lat) = player.position?;
+
+        // Distance to next gate (or finish line) following the route
+        // waypoints of the current leg, then the remaining legs
+        let (distance, distance_to_finish) = if player.finish_time.is_some() {
+            (0.0, 0.0)
+        } else {
+            let leg = player.next_gate_index.min(finish);
+            let to_next = distance_along_leg(&self.course, leg, lng, lat);
+            let remaining_legs: f64 = (leg + 1..=finish)
+                .map(|l| path_length(&self.course.leg_path(l)))
+                .sum();
+            (to_next, to_next + remaining_legs)
+        };
+
+        Some(LeaderboardEntry {
+            player_id: player.id.clone(),
+            player_name: player.name.clone(),
+            next_gate_index: player.next_gate_index,
+            distance_to_next_gate: distance,
+            distance_to_finish,
+            finish_time: player.finish_time,
+            status: player.status,
+            polar: player.polar.clone(),
+            lap_times: player.lap_times.clone(),
+        })
+    }
 
+    fn compute_leaderboard(&self) -> Vec<LeaderboardEntry> {
         let mut entries: Vec<LeaderboardEntry> = self
             .players
             .values()
-            .filter_map(|player| {
-                let (lng, lat) = player.position?;
-
-                // Calculate distance to next gate (or finish line)
-                let distance = if player.next_gate_index < num_gates {
-                    // Distance to next intermediate gate
-                    let gate = &self.course.gates[player.next_gate_index];
-                    haversine_distance(lat, lng, gate.center.lat, gate.center.lng)
-                } else {
-                    // Distance to finish line
-                    let center = &self.course.finish_line.center;
-                    haversine_distance(lat, lng, center.lat, center.lng)
-                };
-
-                Some(LeaderboardEntry {
-                    player_id: player.id.clone(),
-                    player_name: player.name.clone(),
-                    next_gate_index: player.next_gate_index,
-                    distance_to_next_gate: if player.finish_time.is_some() {
-                        0.0
-                    } else {
-                        distance
-                    },
-                    finish_time: player.finish_time,
-                })
-            })
+            .filter_map(|player| self.leaderboard_entry(player))
+            .chain(self.departed.iter().cloned())
             .collect();
 
-        // Sort: finished first (by time), then by gate progress (more gates = better), then by distance
-        entries.sort_by(|a, b| match (&a.finish_time, &b.finish_time) {
-            (Some(ta), Some(tb)) => ta.cmp(tb),
-            (Some(_), None) => std::cmp::Ordering::Less,
-            (None, Some(_)) => std::cmp::Ordering::Greater,
-            (None, None) => {
-                // Higher gate index = further in race = better position
-                match b.next_gate_index.cmp(&a.next_gate_index) {
-                    std::cmp::Ordering::Equal => a
-                        .distance_to_next_gate
-                        .partial_cmp(&b.distance_to_next_gate)
-                        .unwrap_or(std::cmp::Ordering::Equal),
-                    other => other,
-                }
-            }
+        // Sort by boat class (course order), then within each class: finished
+        // first (by time), then racing, DNF and retired boats, each by gate
+        // progress (more gates = better), then by distance
+        let classes = self.course.classes();
+        let class_rank = |polar: &str| {
+            classes
+                .iter()
+                .position(|c| *c == polar)
+                .unwrap_or(classes.len())
+        };
+        entries.sort_by(|a, b| {
+            class_rank(&a.polar)
+                .cmp(&class_rank(&b.polar))
+                .then_with(|| a.status.rank().cmp(&b.status.rank()))
+                .then_with(|| {
+                    match (&a.finish_time, &b.finish_time) {
+                        (Some(ta), Some(tb)) => ta.cmp(tb),
+                        (Some(_), None) => std::cmp::Ordering::Less,
+                        (None, Some(_)) => std::cmp::Ordering::Greater,
+                        (None, None) => {
+                            // Higher gate index = further in race = better position
+                            match b.next_gate_index.cmp(&a.next_gate_index) {
+                                std::cmp::Ordering::Equal => a
+                                    .distance_to_next_gate
+                                    .total_cmp(&b.distance_to_next_gate),
+                                other => other,
+                            }
+                        }
+                    }
+                })
         });
 
         entries
@@ -290,59 +773,193 @@ impl Race {
         gate_index: usize,
         course_time: i64,
     ) -> Option<FinishedPlayer> {
-        let num_gates = self.course.gates.len();
+        let course = &self.course;
         let player = self.players.get_mut(player_id)?;
 
         // Validate gate index matches expected next gate
-        if gate_index != player.next_gate_index {
+        if !player.expects_gate(gate_index) {
             return None;
         }
 
         // Advance to next gate
         player.next_gate_index = gate_index + 1;
+        if gate_index < course.finish_index() {
+            player.gate_times.push(course_time);
+        }
+        if course.completes_lap(gate_index) {
+            player.lap_times.push(course_time);
+        }
 
         // Check if this was the finish line crossing
-        if gate_index == num_gates {
+        if gate_index == course.finish_index() {
             player.finish_time = Some(course_time);
-
-            return Some(FinishedPlayer {
-                player_id: player.id.clone(),
-                player_name: player.name.clone(),
-                persistent_id: player.persistent_id.clone(),
-                finish_time: course_time,
-                path_history: std::mem::take(&mut player.path_history),
-            });
+            player.status = PlayerStatus::Finished;
+            let finished = FinishedPlayer::take_from(player, course_time);
+            self.send_rematch_invite(player_id);
+            return Some(finished);
         }
 
         None
     }
+
+    /// Flag the players on the course side of the start line at the gun.
+    /// Returns their ids.
+    fn flag_ocs(&mut self) -> Vec<String> {
+        let course = &self.course;
+        self.players
+            .values_mut()
+            .filter(|p| {
+                p.position
+                    .is_some_and(|(lng, lat)| course.over_start_line(&LngLat { lng, lat }))
+            })
+            .map(|p| {
+                p.ocs = true;
+                p.id.clone()
+            })
+            .collect()
+    }
+
+    /// Clear the OCS flag of a player back behind the start line. Returns
+    /// whether it was cleared.
+    fn clear_ocs(&mut self, player_id: &str) -> bool {
+        let course = &self.course;
+        match self.players.get_mut(player_id) {
+            Some(player) if player.ocs => {
+                let behind = player
+                    .position
+                    .is_some_and(|(lng, lat)| !course.over_start_line(&LngLat { lng, lat }));
+                if behind {
+                    player.ocs = false;
+                }
+                behind
+            }
+            _ => false,
+        }
+    }
+
+    /// Stop a racing player's race with a final status (retired or DNF) at the
+    /// given race time. Returns the result to save, if the player was racing.
+    fn stop_player(
+        &mut self,
+        player_id: &str,
+        status: PlayerStatus,
+        race_time: i64,
+    ) -> Option<FinishedPlayer> {
+        if !self.race_started() {
+            return None;
+        }
+        let player = self.players.get_mut(player_id)?;
+        if player.status != PlayerStatus::Racing {
+            return None;
+        }
+        player.status = status;
+        let finished = FinishedPlayer::take_from(player, race_time);
+        self.send_rematch_invite(player_id);
+        Some(finished)
+    }
+
+    /// Current race time (simulated course time), if the race has started
+    fn current_race_time(&self) -> Option<i64> {
+        let start_time = self.race_start_time?;
+        Some(
+            self.course
+                .race_time(Utc::now().timestamp_millis() - start_time)
+                .min(self.course.max_finish_time()),
+        )
+    }
 }
 
-/// Data for a player who just finished, ready to be saved
+/// Data for a player whose race is over (finished, retired or DNF), ready to be saved
 #[derive(Debug)]
 struct FinishedPlayer {
     player_id: String,
     player_name: String,
     persistent_id: String,
+    polar: String,
     finish_time: i64,
-    path_history: Vec<PathPoint>,
+    status: ResultStatus,
+    gate_times: Vec<i64>,
+    path_history: RecordedPath,
+    /// To tell the player about a new personal best once saved
+    tx: mpsc::UnboundedSender<ServerMessage>,
+}
+
+impl FinishedPlayer {
+    fn take_from(player: &mut Player, finish_time: i64) -> Self {
+        FinishedPlayer {
+            player_id: player.id.clone(),
+            player_name: player.name.clone(),
+            persistent_id: player.persistent_id.clone(),
+            polar: player.polar.clone(),
+            finish_time,
+            status: player.status.result_status().unwrap_or(ResultStatus::Finished),
+            gate_times: player.gate_times.clone(),
+            path_history: std::mem::take(&mut player.path_history),
+            tx: player.tx.clone(),
+        }
+    }
+}
+
+fn energy_message(battery: &Battery) -> ServerMessage {
+    ServerMessage::Energy {
+        level: battery.level(),
+        autopilot: battery.autopilot,
+        instruments: battery.instruments,
+        hydro: battery.hydro,
+    }
+}
+
+/// Total length of a polyline in nautical miles
+fn path_length(path: &[LngLat]) -> f64 {
+    path.windows(2).map(|w| geo::distance_nm(&w[0], &w[1])).sum()
 }
 
-/// Calculate distance between two points on Earth using Haversine formula
-/// Returns distance in nautical miles
-fn haversine_distance(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
-    const EARTH_RADIUS_NM: f64 = 3440.065; // Earth radius in nautical miles
+/// Closest point to (lng, lat) on the segment a→b, using a local equirectangular
+/// approximation (good enough at route-waypoint spacing). Returns (lng, lat).
+fn closest_point_on_segment(a: &LngLat, b: &LngLat, lng: f64, lat: f64) -> (f64, f64) {
+    let wrap = geo::wrap_lng;
+    let cos_lat = ((a.lat + b.lat) / 2.0).to_radians().cos();
+
+    let dx = wrap(b.lng - a.lng) * cos_lat;
+    let dy = b.lat - a.lat;
+    let px = wrap(lng - a.lng) * cos_lat;
+    let py = lat - a.lat;
+
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 {
+        ((px * dx + py * dy) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
 
-    let lat1_rad = lat1.to_radians();
-    let lat2_rad = lat2.to_radians();
-    let delta_lat = (lat2 - lat1).to_radians();
-    let delta_lng = (lng2 - lng1).to_radians();
+    (wrap(a.lng + wrap(b.lng - a.lng) * t), a.lat + dy * t)
+}
 
-    let a = (delta_lat / 2.0).sin().powi(2)
-        + lat1_rad.cos() * lat2_rad.cos() * (delta_lng / 2.0).sin().powi(2);
-    let c = 2.0 * a.sqrt().asin();
+/// Distance in nautical miles from a position to the end of a course leg, measured
+/// to the closest point on the leg's route and then along the remaining waypoints.
+fn distance_along_leg(course: &Course, leg: usize, lng: f64, lat: f64) -> f64 {
+    let path = course.leg_path(leg);
+    let position = LngLat { lng, lat };
+
+    // Walk segments backwards so the remaining length after each segment is known
+    let mut best: Option<(f64, f64)> = None; // (offset from route, distance along route)
+    let mut remaining_after = 0.0;
+    for segment in path.windows(2).rev() {
+        let (a, b) = (&segment[0], &segment[1]);
+        let (plng, plat) = closest_point_on_segment(a, b, lng, lat);
+        let closest = LngLat {
+            lng: plng,
+            lat: plat,
+        };
+        let offset = geo::distance_nm(&position, &closest);
+        let along = geo::distance_nm(&closest, b) + remaining_after;
+        if best.is_none_or(|(best_offset, _)| offset < best_offset) {
+            best = Some((offset, along));
+        }
+        remaining_after += geo::distance_nm(a, b);
+    }
 
-    EARTH_RADIUS_NM * c
+    best.map(|(offset, along)| offset + along).unwrap_or(0.0)
 }
 
 // ============================================================================
@@ -351,20 +968,64 @@ fn haversine_distance(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
 
 pub type Races = Arc<RwLock<HashMap<String, Race>>>;
 pub type PlayerRaceMap = Arc<RwLock<HashMap<String, String>>>;
+/// Quick match queues by course key, in arrival order
+pub type MatchQueue = Arc<RwLock<HashMap<String, Vec<QueuedPlayer>>>>;
+
+/// Players needed to form a quick match right away
+const MATCH_MIN_PLAYERS: usize = 2;
+/// Longest wait in the queue before a race is formed with whoever is there
+const MATCH_TIMEOUT_MS: i64 = 30_000;
+/// Lobby time for matched players to load wind before the countdown
+const MATCH_START_DELAY_MS: i64 = 10_000;
+/// Time a racer whose connection dropped has to resume the race
+const RESUME_GRACE_MS: i64 = 30_000;
+/// Wait before loading again a wind raster that failed to load
+const RASTER_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub struct QueuedPlayer {
+    pub player: Player,
+    pub queued_at: i64,
+}
 
 #[derive(Clone)]
 pub struct RaceManager {
     races: Races,
     player_races: PlayerRaceMap,
+    match_queue: MatchQueue,
+    events_tx: mpsc::UnboundedSender<NewRaceEvent>,
+    /// Player connections and result saves, waited for at shutdown
+    tasks: TaskTracker,
+    /// Open player connections
+    connections: Arc<AtomicUsize>,
+    /// Wind rasters that failed to load, with the time to try them again
+    failed_rasters: Arc<RwLock<HashMap<String, Instant>>>,
+    shutdown: CancellationToken,
 }
 
 impl RaceManager {
     pub fn new() -> Self {
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel::<NewRaceEvent>();
         let manager = RaceManager {
             races: Arc::new(RwLock::new(HashMap::new())),
             player_races: Arc::new(RwLock::new(HashMap::new())),
+            match_queue: Arc::new(RwLock::new(HashMap::new())),
+            events_tx,
+            tasks: TaskTracker::new(),
+            connections: Arc::new(AtomicUsize::new(0)),
+            failed_rasters: Arc::new(RwLock::new(HashMap::new())),
+            shutdown: CancellationToken::new(),
         };
 
+        // Spawn race event writer (single task keeps the log in order)
+        tokio::spawn(async move {
+            while let Some(event) = events_rx.recv().await {
+                if let Err(e) = race_events::record(&event).await {
+                    log::error!("Failed to record race event: {}", e);
+                }
+            }
+        });
+
         // Spawn cleanup task
         let races_clone = manager.races.clone();
         tokio::spawn(async move {
@@ -377,12 +1038,13 @@ impl RaceManager {
 
         // Spawn race time update task
         let races_clone = manager.races.clone();
+        let manager_clone = manager.clone();
         tokio::spawn(async move {
             loop {
                 tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
 
                 // Collect ended races under read lock
-                let mut ended_races: Vec<(String, ServerMessage)> = Vec::new();
+                let mut ended_races: Vec<String> = Vec::new();
                 {
                     let races = races_clone.read().await;
                     for (race_id, race) in races.iter() {
@@ -394,12 +1056,7 @@ impl RaceManager {
                                 race.broadcast_all(ServerMessage::SyncRaceTime { race_time });
 
                                 if race_time >= race.course.max_finish_time() {
-                                    ended_races.push((
-                                        race_id.clone(),
-                                        ServerMessage::RaceEnded {
-                                            reason: "Time limit reached".to_string(),
-                                        },
-                                    ));
+                                    ended_races.push(race_id.clone());
                                 }
                             }
                             _ => {}
@@ -409,12 +1066,8 @@ impl RaceManager {
                 // Read lock is now dropped
 
                 // Apply mutations under write lock
-                for (race_id, end_msg) in ended_races {
-                    let mut races = races_clone.write().await;
-                    if let Some(race) = races.get_mut(&race_id) {
-                        race.race_ended = true;
-                        race.broadcast_all(end_msg);
-                    }
+                for race_id in ended_races {
+                    manager_clone.end_race(&race_id, "Time limit reached").await;
                 }
             }
         });
@@ -438,49 +1091,188 @@ impl RaceManager {
             }
         });
 
+        // Spawn quick match timeout task
+        let manager_clone = manager.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+                let now = Utc::now().timestamp_millis();
+                let expired: Vec<String> = {
+                    let queue = manager_clone.match_queue.read().await;
+                    queue
+                        .iter()
+                        .filter(|(_, players)| {
+                            players
+                                .first()
+                                .is_some_and(|p| now - p.queued_at >= MATCH_TIMEOUT_MS)
+                        })
+                        .map(|(course_key, _)| course_key.clone())
+                        .collect()
+                };
+                for course_key in expired {
+                    manager_clone.form_match(&course_key).await;
+                }
+            }
+        });
+
+        // Spawn boat simulation task
+        let manager_clone = manager.clone();
+        tokio::spawn(async move {
+            let mut last_tick = Instant::now();
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_millis(simulation::TICK_MS)).await;
+                let now = Instant::now();
+                manager_clone
+                    .simulate(now.duration_since(last_tick).as_secs_f64())
+                    .await;
+                last_tick = now;
+            }
+        });
+
+        // Spawn ghost position broadcast task
+        let races_clone = manager.races.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
+
+                let races = races_clone.read().await;
+                for race in races.values() {
+                    if race.race_ended || race.ghosts.is_empty() {
+                        continue;
+                    }
+                    for message in race.ghost_positions() {
+                        race.broadcast_all(message);
+                    }
+                }
+            }
+        });
+
         manager
     }
 
+    /// Queue an event for the persistent race log
+    fn log_event(
+        &self,
+        race_id: &str,
+        course_key: &str,
+        kind: RaceEventKind,
+        player: Option<(&str, &str)>,
+        payload: serde_json::Value,
+    ) {
+        let _ = self.events_tx.send(NewRaceEvent {
+            race_id: race_id.to_string(),
+            course_key: course_key.to_string(),
+            kind,
+            player_id: player.map(|(id, _)| id.to_string()),
+            player_name: player.map(|(_, name)| name.to_string()),
+            payload,
+        });
+    }
+
+    /// Tell the race a player is over the start line early, or back behind it
+    fn announce_ocs(&self, race_id: &str, race: &Race, player_id: &str, over: bool) {
+        race.broadcast_all(ServerMessage::Ocs {
+            player_id: player_id.to_string(),
+            over,
+        });
+        if let Some(player) = race.players.get(player_id) {
+            self.log_event(
+                race_id,
+                &race.course.key,
+                RaceEventKind::Ocs,
+                Some((&player.id, &player.name)),
+                serde_json::json!({ "over": over }),
+            );
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(course_key = %course_key, player_id = %player_id))]
     pub async fn create_race(
         &self,
         course_key: String,
         player_id: String,
         player_name: String,
         persistent_id: String,
+        options: RaceOptions,
         tx: mpsc::UnboundedSender<ServerMessage>,
-    ) -> anyhow::Result<(String, Vec<WindRasterSource>)> {
-        let course = courses::get_by_key(&course_key)
+    ) -> anyhow::Result<(String, Vec<WindRasterSource>, i64, String)> {
+        let mut course = courses::get_by_key(&course_key)
             .await?
             .ok_or(anyhow!("Course not found"))?;
+        course.check_open(Utc::now().timestamp_millis())?;
+        let polar = course.boat_class(options.boat_class)?;
+
+        let weather_start_time = if options.random_weather {
+            let start_time = wind_reports::random_start_time(&course)
+                .await?
+                .ok_or(anyhow!("No other weather window available for this course"))?;
+            course.start_time = start_time;
+            Some(start_time)
+        } else {
+            None
+        };
+        let course_start_time = course.start_time;
 
         let reports = wind_reports::get_reports_for_course(&course).await?;
-        let rasters: Vec<WindRasterSource> = reports.iter().map(|r| r.into()).collect();
+        let rasters = WindRasterSource::for_reports(&reports).await?;
 
         let race_id = generate_race_id();
         let mut race = Race::new(course, rasters.clone(), player_id.clone());
+        race.weather_start_time = weather_start_time;
+        if options.solo {
+            race.solo = true;
+            race.max_players = 1;
+        }
 
-        let player = Player {
-            id: player_id.clone(),
-            name: player_name,
+        let player = Player::new(
+            player_id.clone(),
+            player_name.clone(),
             persistent_id,
+            polar.clone(),
             tx,
-            position: None,
-            heading: 0.0,
-            next_gate_index: 0,
-            finish_time: None,
-            path_history: Vec::new(),
-            last_sample_instant: None,
-        };
+        );
         race.add_player(player)?;
 
+        if !options.solo {
+            discord::notify(Notification::RaceCreated {
+                course_key: course_key.clone(),
+                creator: Some(player_name.clone()),
+                start_at: None,
+            });
+            webhooks::dispatch(
+                WebhookEvent::RaceCreated,
+                serde_json::json!({
+                    "raceId": race_id,
+                    "courseKey": course_key,
+                    "creator": player_name,
+                    "scheduledStart": null,
+                }),
+            );
+        }
+
+        self.log_event(
+            &race_id,
+            &course_key,
+            RaceEventKind::Join,
+            Some((&player_id, &player_name)),
+            serde_json::json!({
+                "creator": true,
+                "solo": options.solo,
+                "weather_start_time": weather_start_time,
+                "polar": polar,
+            }),
+        );
+
         let mut races = self.races.write().await;
         races.insert(race_id.clone(), race);
         drop(races);
+        cluster::spawn_claim(race_id.clone());
 
         let mut player_races = self.player_races.write().await;
         player_races.insert(player_id, race_id.clone());
 
-        Ok((race_id, rasters))
+        Ok((race_id, rasters, course_start_time, polar))
     }
 
     pub async fn join_race(
@@ -489,52 +1281,464 @@ impl RaceManager {
         player_id: String,
         player_name: String,
         persistent_id: String,
+        polar: Option<String>,
         tx: mpsc::UnboundedSender<ServerMessage>,
-    ) -> anyhow::Result<(Vec<PlayerInfo>, Vec<WindRasterSource>, String, bool)> {
+    ) -> anyhow::Result<JoinedRace> {
         let mut races = self.races.write().await;
         let race = races.get_mut(race_id).ok_or(anyhow!("Race not found"))?;
+        if race.solo {
+            return Err(anyhow!("Race is private"));
+        }
 
-        let player = Player {
-            id: player_id.clone(),
-            name: player_name.clone(),
+        let polar = race.course.boat_class(polar)?;
+        let player = Player::new(
+            player_id.clone(),
+            player_name.clone(),
             persistent_id,
+            polar.clone(),
             tx,
-            position: None,
-            heading: 0.0,
-            next_gate_index: 0,
-            finish_time: None,
-            path_history: Vec::new(),
-            last_sample_instant: None,
-        };
-
-        // Notify existing players before adding new one
-        race.broadcast_all(ServerMessage::PlayerJoined {
-            player_id: player_id.clone(),
-            player_name,
-        });
+        );
 
         let is_creator = race.creator_id == player_id;
         let course_key = race.course.key.clone();
+        let course_start_time = race.course.start_time;
         race.add_player(player)?;
 
-        let players = race.get_player_infos();
-        let rasters = race.wind_raster_sources.clone();
-        drop(races);
-
+        // Notify existing players (the new player learns about them via RaceJoined)
+        race.broadcast(
+            ServerMessage::PlayerJoined {
+                player_id: player_id.clone(),
+                player_name: player_name.clone(),
+            },
+            Some(&player_id),
+        );
+
+        self.log_event(
+            race_id,
+            &course_key,
+            RaceEventKind::Join,
+            Some((&player_id, &player_name)),
+            serde_json::json!({ "creator": is_creator, "polar": polar }),
+        );
+
+        let players = race.get_player_infos();
+        let rasters = race.wind_raster_sources.clone();
+        drop(races);
+
         let mut player_races = self.player_races.write().await;
         player_races.insert(player_id, race_id.to_string());
 
-        Ok((players, rasters, course_key, is_creator))
+        Ok((players, rasters, course_key, is_creator, course_start_time, polar))
+    }
+
+    /// Create a private race on today's daily challenge: its course, raced
+    /// in the day's weather window. Results go to the daily leaderboard.
+    pub async fn start_daily(
+        &self,
+        player_id: String,
+        player_name: String,
+        persistent_id: String,
+        tx: mpsc::UnboundedSender<ServerMessage>,
+    ) -> anyhow::Result<(String, String, i64, Vec<PlayerInfo>, Vec<WindRasterSource>, String)> {
+        if self.player_races.read().await.contains_key(&player_id) {
+            return Err(anyhow!("Player already in a race"));
+        }
+        let challenge = daily::current()
+            .await?
+            .ok_or(anyhow!("No daily challenge available"))?;
+        let mut course = courses::get_by_key(&challenge.course_key)
+            .await?
+            .ok_or(anyhow!("Course not found"))?;
+        course.check_open(Utc::now().timestamp_millis())?;
+        course.start_time = challenge.start_time;
+        // Everyone races the daily challenge in the default class
+        let polar = course.polar.clone();
+
+        let reports = wind_reports::get_reports_for_course(&course).await?;
+        let rasters = WindRasterSource::for_reports(&reports).await?;
+
+        let race_id = generate_race_id();
+        let mut race = Race::new(course, rasters.clone(), player_id.clone());
+        race.solo = true;
+        race.max_players = 1;
+        race.daily = Some(challenge.date.clone());
+        race.weather_start_time = Some(challenge.start_time);
+        race.add_player(Player::new(
+            player_id.clone(),
+            player_name.clone(),
+            persistent_id,
+            polar.clone(),
+            tx,
+        ))?;
+        let players = race.get_player_infos();
+
+        self.log_event(
+            &race_id,
+            &challenge.course_key,
+            RaceEventKind::Join,
+            Some((&player_id, &player_name)),
+            serde_json::json!({ "creator": true, "solo": true, "daily": challenge.date }),
+        );
+
+        self.races.write().await.insert(race_id.clone(), race);
+        self.player_races.write().await.insert(player_id, race_id.clone());
+        cluster::spawn_claim(race_id.clone());
+
+        Ok((race_id, challenge.course_key, challenge.start_time, players, rasters, polar))
+    }
+
+    /// Move a player whose race is over to its rematch: a fresh race on the
+    /// same course with the same settings. The first player to propose creates
+    /// it and invites the others once their race is over; later proposals join it.
+    pub async fn propose_rematch(
+        &self,
+        player_id: &str,
+    ) -> anyhow::Result<(String, JoinedRace)> {
+        let race_id = self
+            .player_races
+            .read()
+            .await
+            .get(player_id)
+            .cloned()
+            .ok_or(anyhow!("Player not in a race"))?;
+
+        let mut races = self.races.write().await;
+        let race = races.get(&race_id).ok_or(anyhow!("Race not found"))?;
+        let player = race.players.get(player_id).ok_or(anyhow!("Player not in a race"))?;
+        if !race.race_started() || player.status == PlayerStatus::Racing {
+            return Err(anyhow!("Rematch is only available once your race is over"));
+        }
+        let (player_name, persistent_id, polar, tx) = (
+            player.name.clone(),
+            player.persistent_id.clone(),
+            player.polar.clone(),
+            player.tx.clone(),
+        );
+
+        // Join the rematch another player proposed, unless it's gone or under way
+        let open_rematch = race
+            .rematch
+            .as_ref()
+            .map(|r| r.race_id.clone())
+            .filter(|id| races.get(id).is_some_and(|r| !r.race_started()));
+
+        if let Some(rematch_id) = open_rematch {
+            drop(races);
+            self.remove_from_race(player_id, RaceEventKind::Leave).await;
+            let joined = self
+                .join_race(
+                    &rematch_id,
+                    player_id.to_string(),
+                    player_name,
+                    persistent_id,
+                    Some(polar),
+                    tx,
+                )
+                .await?;
+            return Ok((rematch_id, joined));
+        }
+
+        let mut rematch = race.rematch_race(player_id.to_string());
+        rematch.add_player(Player::new(
+            player_id.to_string(),
+            player_name.clone(),
+            persistent_id,
+            polar.clone(),
+            tx,
+        ))?;
+        let rematch_id = generate_race_id();
+        let players = rematch.get_player_infos();
+        let rasters = rematch.wind_raster_sources.clone();
+        let course_key = rematch.course.key.clone();
+        let course_start_time = rematch.course.start_time;
+        races.insert(rematch_id.clone(), rematch);
+        cluster::spawn_claim(rematch_id.clone());
+
+        let race = races.get_mut(&race_id).ok_or(anyhow!("Race not found"))?;
+        race.rematch = Some(Rematch {
+            race_id: rematch_id.clone(),
+            proposer_name: player_name.clone(),
+        });
+        let done: Vec<String> = race
+            .players
+            .values()
+            .filter(|p| p.id != player_id && p.status != PlayerStatus::Racing)
+            .map(|p| p.id.clone())
+            .collect();
+        for id in done {
+            race.send_rematch_invite(&id);
+        }
+        drop(races);
+
+        self.log_event(
+            &rematch_id,
+            &course_key,
+            RaceEventKind::Join,
+            Some((player_id, &player_name)),
+            serde_json::json!({ "creator": true, "rematchOf": race_id }),
+        );
+
+        self.remove_from_race(player_id, RaceEventKind::Leave).await;
+        self.player_races
+            .write()
+            .await
+            .insert(player_id.to_string(), rematch_id.clone());
+
+        Ok((rematch_id, (players, rasters, course_key, true, course_start_time, polar)))
     }
 
     pub async fn leave_race(&self, player_id: &str) {
+        self.leave_queue(player_id).await;
+        self.remove_from_race(player_id, RaceEventKind::Leave).await;
+    }
+
+    /// Put a player in the quick match queue of a course. A race is formed as
+    /// soon as enough players are queued, or when the first one has waited
+    /// long enough.
+    pub async fn quick_match(
+        &self,
+        course_key: String,
+        player_id: String,
+        player_name: String,
+        persistent_id: String,
+        polar: Option<String>,
+        tx: mpsc::UnboundedSender<ServerMessage>,
+    ) -> anyhow::Result<()> {
+        if self.player_races.read().await.contains_key(&player_id) {
+            return Err(anyhow!("Player already in a race"));
+        }
+        let course = courses::get_by_key(&course_key)
+            .await?
+            .ok_or(anyhow!("Course not found"))?;
+        course.check_open(Utc::now().timestamp_millis())?;
+        let polar = course.boat_class(polar)?;
+
+        // A player can only wait in one queue
+        self.leave_queue(&player_id).await;
+
+        let ready = {
+            let mut queue = self.match_queue.write().await;
+            let players = queue.entry(course_key.clone()).or_default();
+            players.push(QueuedPlayer {
+                player: Player::new(player_id, player_name, persistent_id, polar, tx),
+                queued_at: Utc::now().timestamp_millis(),
+            });
+
+            let match_deadline = players[0].queued_at + MATCH_TIMEOUT_MS;
+            for queued in players.iter() {
+                let _ = queued.player.tx.send(ServerMessage::MatchQueued {
+                    course_key: course_key.clone(),
+                    queued_players: players.len(),
+                    min_players: MATCH_MIN_PLAYERS,
+                    match_deadline,
+                });
+            }
+            players.len() >= MATCH_MIN_PLAYERS
+        };
+
+        if ready {
+            let manager = self.clone();
+            tokio::spawn(async move { manager.form_match(&course_key).await });
+        }
+        Ok(())
+    }
+
+    async fn leave_queue(&self, player_id: &str) {
+        let mut queue = self.match_queue.write().await;
+        for players in queue.values_mut() {
+            players.retain(|p| p.player.id != player_id);
+        }
+        queue.retain(|_, players| !players.is_empty());
+    }
+
+    /// Form a race from the queued players of a course (up to a full race)
+    /// and start it after a short lobby.
+    #[tracing::instrument(skip(self))]
+    async fn form_match(&self, course_key: &str) {
+        let queued: Vec<QueuedPlayer> = {
+            let mut queue = self.match_queue.write().await;
+            let Some(players) = queue.get_mut(course_key) else {
+                return;
+            };
+            let take = players.len().min(10);
+            let queued: Vec<QueuedPlayer> = players.drain(..take).collect();
+            if players.is_empty() {
+                queue.remove(course_key);
+            }
+            queued
+        };
+        if queued.is_empty() {
+            return;
+        }
+
+        let start_time = Utc::now().timestamp_millis() + MATCH_START_DELAY_MS;
+        let race_id = match self.open_scheduled_race(course_key, start_time).await {
+            Ok(race_id) => race_id,
+            Err(e) => {
+                log::error!("Failed to form quick match on {}: {}", course_key, e);
+                for q in &queued {
+                    let _ = q.player.tx.send(ServerMessage::Error {
+                        message: e.to_string(),
+                    });
+                }
+                return;
+            }
+        };
+
+        {
+            let mut races = self.races.write().await;
+            let mut player_races = self.player_races.write().await;
+            let Some(race) = races.get_mut(&race_id) else {
+                return;
+            };
+            for q in queued {
+                let (id, name) = (q.player.id.clone(), q.player.name.clone());
+                if race.add_player(q.player).is_ok() {
+                    player_races.insert(id.clone(), race_id.clone());
+                    self.log_event(
+                        &race_id,
+                        course_key,
+                        RaceEventKind::Join,
+                        Some((&id, &name)),
+                        serde_json::json!({ "quickMatch": true }),
+                    );
+                }
+            }
+
+            let players = race.get_player_infos();
+            for (player_id, player) in &race.players {
+                let _ = player.tx.send(ServerMessage::RaceJoined {
+                    race_id: race_id.clone(),
+                    player_id: player_id.clone(),
+                    course_key: course_key.to_string(),
+                    wind_raster_sources: race.wind_raster_sources.clone(),
+                    players: players.clone(),
+                    is_creator: false,
+                    course_start_time: race.course.start_time,
+                    polar: player.polar.clone(),
+                });
+            }
+        }
+
+        log::info!("Formed quick match {} on {}", race_id, course_key);
+
+        let manager = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(tokio::time::Duration::from_millis(MATCH_START_DELAY_MS as u64))
+                .await;
+            if let Err(e) = manager.start_scheduled_race(&race_id).await {
+                log::info!("Quick match {} did not start: {}", race_id, e);
+            }
+        });
+    }
+
+    /// Remove a player whose connection dropped without leaving the race.
+    /// Racers keep their boat for a while, to resume from a new connection.
+    pub async fn disconnect(&self, player_id: &str) {
+        self.leave_queue(player_id).await;
+        if let Some(disconnected_at) = self.hold_for_resume(player_id).await {
+            let manager = self.clone();
+            let player_id = player_id.to_string();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(RESUME_GRACE_MS as u64)).await;
+                manager
+                    .expire_disconnected(&player_id, disconnected_at)
+                    .await;
+            });
+            return;
+        }
+        self.remove_from_race(player_id, RaceEventKind::Disconnect)
+            .await;
+    }
+
+    /// Mark a player still racing as disconnected, returning the time it happened
+    async fn hold_for_resume(&self, player_id: &str) -> Option<i64> {
+        let race_id = self.player_races.read().await.get(player_id).cloned()?;
+        let mut races = self.races.write().await;
+        let race = races.get_mut(&race_id)?;
+        if !race.race_started() || race.race_ended {
+            return None;
+        }
+        let player = race.players.get_mut(player_id)?;
+        if player.status != PlayerStatus::Racing {
+            return None;
+        }
+        let now = Utc::now().timestamp_millis();
+        player.disconnected_at = Some(now);
+        Some(now)
+    }
+
+    /// Remove a disconnected player who didn't resume since `disconnected_at`
+    async fn expire_disconnected(&self, player_id: &str, disconnected_at: i64) {
+        let Some(race_id) = self.player_races.read().await.get(player_id).cloned() else {
+            return;
+        };
+        let away = self
+            .races
+            .read()
+            .await
+            .get(&race_id)
+            .and_then(|race| race.players.get(player_id))
+            .is_some_and(|player| player.disconnected_at == Some(disconnected_at));
+        if away {
+            self.remove_from_race(player_id, RaceEventKind::Disconnect)
+                .await;
+        }
+    }
+
+    /// Hand the race of a disconnected player over to a new connection.
+    /// Returns the current race time.
+    pub async fn resume(
+        &self,
+        race_id: &str,
+        player_id: &str,
+        persistent_id: &str,
+        tx: mpsc::UnboundedSender<ServerMessage>,
+    ) -> anyhow::Result<Option<i64>> {
+        let mut races = self.races.write().await;
+        let race = races.get_mut(race_id).ok_or(anyhow!("Race not found"))?;
+        if race.race_ended {
+            return Err(anyhow!("Race is over"));
+        }
+        let player = race
+            .players
+            .get_mut(player_id)
+            .filter(|p| p.persistent_id == persistent_id && p.disconnected_at.is_some())
+            .ok_or(anyhow!("No race to resume"))?;
+        player.tx = tx;
+        player.disconnected_at = None;
+        let (id, name) = (player.id.clone(), player.name.clone());
+
+        self.log_event(
+            race_id,
+            &race.course.key,
+            RaceEventKind::Resume,
+            Some((&id, &name)),
+            serde_json::json!({}),
+        );
+        Ok(race.current_race_time())
+    }
+
+    async fn remove_from_race(&self, player_id: &str, kind: RaceEventKind) {
         let mut player_races = self.player_races.write().await;
         if let Some(race_id) = player_races.remove(player_id) {
             drop(player_races);
 
             let mut races = self.races.write().await;
             if let Some(race) = races.get_mut(&race_id) {
-                race.remove_player(player_id);
+                // Leaving mid-race counts as a retirement
+                if let Some(pending) = self.stop_player(&race_id, race, player_id, PlayerStatus::Retired) {
+                    self.spawn_save(pending);
+                }
+                if let Some(player) = race.remove_player(player_id) {
+                    self.log_event(
+                        &race_id,
+                        &race.course.key,
+                        kind,
+                        Some((&player.id, &player.name)),
+                        serde_json::json!({ "nextGateIndex": player.next_gate_index }),
+                    );
+                }
                 if race.players.is_empty() {
                     races.remove(&race_id);
                 } else {
@@ -572,36 +1776,52 @@ impl RaceManager {
                 return;
             };
 
+            // The server moves simulated boats itself
+            if race.simulation.is_some() {
+                return;
+            }
+
             // Update player position and sample path
+            let race_time = race
+                .course
+                .race_time(Utc::now().timestamp_millis() - race_start_time.unwrap_or(0));
+            let wind_report = race.wind_report_at(race_time);
             if let Some(player) = race.players.get_mut(player_id) {
-                player.position = Some((lng as f64, lat as f64));
+                let racing = race_started && player.status == PlayerStatus::Racing;
+
+                // Reject racing moves onto land or across a coastline
+                let to = LngLat {
+                    lng: lng as f64,
+                    lat: lat as f64,
+                };
+                let impossible = match player.position {
+                    Some((from_lng, from_lat)) => land::crosses_land(
+                        &LngLat {
+                            lng: from_lng,
+                            lat: from_lat,
+                        },
+                        &to,
+                    ),
+                    None => land::is_on_land(&to),
+                };
+                if racing && impossible {
+                    log::debug!("Rejected position of {} in race {}: land", player_id, race_id);
+                    return;
+                }
+
+                player.position = Some((to.lng, to.lat));
                 player.heading = heading;
 
-                // Sample path if race has started (100ms real-time interval)
-                if race_started {
-                    let now = Instant::now();
-                    let should_sample = player
-                        .last_sample_instant
-                        .map(|last| now.duration_since(last) >= Duration::from_millis(100))
-                        .unwrap_or(true);
-
-                    if should_sample {
-                        // Calculate race time
-                        let elapsed =
-                            Utc::now().timestamp_millis() - race_start_time.unwrap_or(0);
-                        let race_time = race.course.race_time(elapsed);
-
-                        player.path_history.push(PathPoint {
-                            race_time,
-                            lng,
-                            lat,
-                            heading,
-                        });
-                        player.last_sample_instant = Some(now);
-                    }
+                // Sample path while racing
+                if racing {
+                    player.sample_path(race_time, wind_report);
                 }
             }
 
+            if race_started && race.clear_ocs(player_id) {
+                self.announce_ocs(&race_id, race, player_id, false);
+            }
+
             // Collect senders for all players except the sender
             race.players
                 .iter()
@@ -634,32 +1854,520 @@ impl RaceManager {
         };
         drop(player_races);
 
-        let finished_to_save: Option<(String, i64, i64, FinishedPlayer)> = {
+        let finished_to_save: Option<PendingResult> = {
             let mut races = self.races.write().await;
             let Some(race) = races.get_mut(&race_id) else {
                 return;
             };
+            // Simulated races detect crossings on the server
+            if race.simulation.is_some() {
+                return;
+            }
+            self.apply_gate_crossing(&race_id, race, player_id, gate_index, course_time)
+        };
 
-            if let Some(finished) = race.record_gate_crossing(player_id, gate_index, course_time) {
-                Some((
-                    race.course.key.clone(),
-                    race.race_start_time.expect("race must be started"),
-                    race.course.start_time,
-                    finished,
-                ))
+        // Save finished player outside the lock
+        if let Some(pending) = finished_to_save {
+            self.spawn_save(pending);
+        }
+    }
+
+    /// Record a gate crossing and log it. Returns the result to save when the
+    /// player finished.
+    fn apply_gate_crossing(
+        &self,
+        race_id: &str,
+        race: &mut Race,
+        player_id: &str,
+        gate_index: usize,
+        course_time: i64,
+    ) -> Option<PendingResult> {
+        let accepted = race
+            .players
+            .get(player_id)
+            .is_some_and(|p| p.expects_gate(gate_index));
+        let finished = race.record_gate_crossing(player_id, gate_index, course_time);
+
+        if accepted && let Some(player) = race.players.get(player_id) {
+            let kind = if finished.is_some() {
+                RaceEventKind::Finish
             } else {
-                None
+                RaceEventKind::GateCrossed
+            };
+            self.log_event(
+                race_id,
+                &race.course.key,
+                kind,
+                Some((&player.id, &player.name)),
+                serde_json::json!({ "gateIndex": gate_index, "courseTime": course_time }),
+            );
+        }
+
+        finished.map(|finished| PendingResult::new(race_id, race, finished))
+    }
+
+    /// Set the helm of a player's simulated boat
+    pub async fn set_helm(&self, player_id: &str, helm: Helm) {
+        let Some(race_id) = self.player_races.read().await.get(player_id).cloned() else {
+            return;
+        };
+        let mut races = self.races.write().await;
+        if let Some(boat) = races
+            .get_mut(&race_id)
+            .and_then(|race| race.players.get_mut(player_id))
+            .and_then(|player| player.boat.as_mut())
+        {
+            boat.set_helm(helm);
+        }
+    }
+
+    /// Change the sail of a player's simulated boat. Races sailed on client
+    /// positions leave sail changes to the client.
+    pub async fn change_sail(&self, player_id: &str, sail: &str) -> anyhow::Result<()> {
+        let Some(race_id) = self.player_races.read().await.get(player_id).cloned() else {
+            return Ok(());
+        };
+        let mut races = self.races.write().await;
+        let Some(race) = races.get_mut(&race_id) else {
+            return Ok(());
+        };
+        let Some(simulation) = race.simulation.as_ref() else {
+            return Ok(());
+        };
+        let Some(player) = race.players.get_mut(player_id) else {
+            return Ok(());
+        };
+        if !simulation.has_sail(&player.polar, sail) {
+            return Err(anyhow!("Unknown sail: {}", sail));
+        }
+        if let Some(boat) = player.boat.as_mut() {
+            boat.change_sail(sail);
+        }
+        Ok(())
+    }
+
+    /// Switch a system of a player's simulated boat, then send them its new
+    /// energy state. Races sailed on client positions don't manage energy.
+    pub async fn toggle_system(
+        &self,
+        player_id: &str,
+        system: System,
+        on: bool,
+    ) -> anyhow::Result<()> {
+        let Some(race_id) = self.player_races.read().await.get(player_id).cloned() else {
+            return Ok(());
+        };
+        let mut races = self.races.write().await;
+        let Some(player) = races
+            .get_mut(&race_id)
+            .and_then(|race| race.players.get_mut(player_id))
+        else {
+            return Ok(());
+        };
+        let Some(boat) = player.boat.as_mut() else {
+            return Ok(());
+        };
+        boat.switch(system, on)?;
+        if let Some(battery) = &boat.battery {
+            let _ = player.tx.send(energy_message(battery));
+        }
+        Ok(())
+    }
+
+    /// Best way for a racing player to sail toward their next mark, in the
+    /// wind of the simulation when loaded, else of the stored wind reports
+    pub async fn coach(&self, player_id: &str) -> anyhow::Result<Hint> {
+        let (position, mark, race_time, polar, wind) = {
+            let Some(race_id) = self.player_races.read().await.get(player_id).cloned() else {
+                return Err(anyhow!("Not in a race"));
+            };
+            let races = self.races.read().await;
+            let race = races.get(&race_id).ok_or(anyhow!("Race not found"))?;
+            let player = race
+                .players
+                .get(player_id)
+                .ok_or(anyhow!("Player not found"))?;
+            let race_time = race
+                .current_race_time()
+                .ok_or(anyhow!("Race not started"))?;
+            let (lng, lat) = player.position.ok_or(anyhow!("No position yet"))?;
+            let mark = race
+                .course
+                .mark(player.next_gate_index)
+                .unwrap_or(&race.course.finish_line)
+                .center
+                .clone();
+            let wind = race
+                .simulation
+                .as_ref()
+                .and_then(|simulation| simulation.wind.wind_at(race_time, lng, lat));
+            (
+                LngLat { lng, lat },
+                mark,
+                race_time,
+                player.polar.clone(),
+                wind,
+            )
+        };
+        let wind = match wind {
+            Some(wind) => wind,
+            None => routing::wind_at(race_time, &position)
+                .await?
+                .ok_or(anyhow!("No wind at this time"))?,
+        };
+        let polar = polars::get(&polar)
+            .await?
+            .ok_or(anyhow!("Polar not found: {}", polar))?;
+        Ok(routing::best_vmg(&polar, wind, &position, &mark))
+    }
+
+    /// Advance the simulated boats of all running races by `dt` seconds of
+    /// real time, then broadcast their positions.
+    async fn simulate(&self, dt: f64) {
+        // Find wind rasters to load, without holding the lock during downloads.
+        // Those that just failed wait for their retry instead of every tick
+        let now = Instant::now();
+        let missing: HashMap<i64, String> = {
+            let mut failed = self.failed_rasters.write().await;
+            failed.retain(|_, retry_at| *retry_at > now);
+            let races = self.races.read().await;
+            races
+                .values()
+                .filter(|race| !race.race_ended)
+                .filter_map(|race| Some((race.simulation.as_ref()?, race.current_race_time()?)))
+                .flat_map(|(simulation, race_time)| simulation.wind.missing(race_time))
+                .filter(|(_, png_path)| !failed.contains_key(png_path))
+                .collect()
+        };
+        let mut loaded: HashMap<i64, Arc<WindRaster>> = HashMap::new();
+        for (time, png_path) in missing {
+            match simulation::load_raster(&png_path).await {
+                Ok(raster) => {
+                    loaded.insert(time, Arc::new(raster));
+                }
+                Err(e) => {
+                    log::error!("Failed to load wind raster {}: {}", png_path, e);
+                    self.failed_rasters
+                        .write()
+                        .await
+                        .insert(png_path, now + RASTER_RETRY_DELAY);
+                }
+            }
+        }
+
+        let pending: Vec<PendingResult> = {
+            let mut races = self.races.write().await;
+            let mut pending = Vec::new();
+            for (race_id, race) in races.iter_mut() {
+                if race.race_ended {
+                    continue;
+                }
+                let Some(race_time) = race.current_race_time() else {
+                    continue;
+                };
+                let time_factor = race.course.time_factor as f64;
+                let wind_report = race.wind_report_at(race_time);
+                let Some(simulation) = race.simulation.as_mut() else {
+                    continue;
+                };
+                for (time, raster) in &loaded {
+                    if simulation
+                        .wind
+                        .missing(race_time)
+                        .iter()
+                        .any(|(t, _)| t == time)
+                    {
+                        simulation.wind.insert(*time, raster.clone(), race_time);
+                    }
+                }
+
+                // Racing boats cast wind shadows on each other
+                let fleet: Vec<(String, LngLat)> = race
+                    .players
+                    .values()
+                    .filter(|p| p.status == PlayerStatus::Racing)
+                    .filter_map(|p| Some((p.id.clone(), p.boat.as_ref()?.position())))
+                    .collect();
+
+                let mut crossings: Vec<(String, usize)> = Vec::new();
+                for player in race.players.values_mut() {
+                    if player.status != PlayerStatus::Racing {
+                        continue;
+                    }
+                    let Some(boat) = player.boat.as_mut() else {
+                        continue;
+                    };
+                    let Some(polar) = simulation.polar(&player.polar, boat.sail.as_deref())
+                    else {
+                        continue;
+                    };
+                    // Boats wait for the wind to be loaded
+                    let Some(wind) = simulation.wind.wind_at(race_time, boat.lng, boat.lat) else {
+                        continue;
+                    };
+                    let from = boat.position();
+                    let others = fleet
+                        .iter()
+                        .filter(|(id, _)| *id != player.id)
+                        .map(|(_, position)| position);
+                    let shadow =
+                        (simulation::wind_shadow(&from, wind, others) * 100.0).round() / 100.0;
+                    if shadow != boat.wind_shadow {
+                        boat.wind_shadow = shadow;
+                        let _ = player.tx.send(ServerMessage::WindShadow { factor: shadow });
+                    }
+                    boat.step(wind, polar, dt, time_factor);
+                    if boat.run_battery(dt, time_factor, race_time)
+                        && let Some(battery) = &boat.battery
+                    {
+                        let _ = player.tx.send(energy_message(battery));
+                    }
+                    let to = boat.position();
+                    player.position = Some((boat.lng, boat.lat));
+                    player.heading = boat.heading as f32;
+                    player.sample_path(race_time, wind_report);
+
+                    if let Some(gate) = race.course.mark(player.next_gate_index)
+                        && gates::crosses_gate(&from, &to, gate)
+                    {
+                        crossings.push((player.id.clone(), player.next_gate_index));
+                    }
+                }
+
+                for (player_id, gate_index) in crossings {
+                    pending.extend(
+                        self.apply_gate_crossing(race_id, race, &player_id, gate_index, race_time),
+                    );
+                }
+
+                let positions: Vec<ServerMessage> = race
+                    .players
+                    .values()
+                    .filter(|p| p.boat.is_some())
+                    .filter_map(|p| {
+                        let (lng, lat) = p.position?;
+                        Some(ServerMessage::PositionUpdate {
+                            player_id: p.id.clone(),
+                            lng: lng as f32,
+                            lat: lat as f32,
+                            heading: p.heading,
+                        })
+                    })
+                    .collect();
+                for message in positions {
+                    race.broadcast_all(message);
+                }
             }
+            pending
         };
 
-        // Save finished player outside the lock
-        if let Some((course_key, race_start_time, course_start_time, finished)) = finished_to_save {
-            tokio::spawn(save_race_result(
-                course_key,
-                race_start_time,
-                course_start_time,
-                finished,
-            ));
+        for result in pending {
+            self.spawn_save(result);
+        }
+    }
+
+    /// Load the top `count` Hall of Fame runs of the race's course as ghost
+    /// boats. Runs already in the race are skipped.
+    pub async fn add_ghosts(&self, player_id: &str, count: usize) -> anyhow::Result<()> {
+        let player_races = self.player_races.read().await;
+        let race_id = player_races
+            .get(player_id)
+            .ok_or(anyhow!("Player not in a race"))?
+            .clone();
+        drop(player_races);
+
+        let (course_key, polar, existing) = {
+            let races = self.races.read().await;
+            let race = races.get(&race_id).ok_or(anyhow!("Race not found"))?;
+            let player = race.players.get(player_id).ok_or(anyhow!("Player not in a race"))?;
+            if race.race_started() {
+                return Err(anyhow!("Race has already started"));
+            }
+            // Hall of Fame runs were sailed in the course's own weather
+            if race.weather_start_time.is_some() {
+                return Err(anyhow!("Ghosts are not available in another weather window"));
+            }
+            let existing: Vec<i64> = race.ghosts.iter().map(|g| g.result_id).collect();
+            (race.course.key.clone(), player.polar.clone(), existing)
+        };
+
+        // Fetch paths without holding the races lock; ghosts sail the
+        // requester's boat class
+        let count = count.min(MAX_GHOSTS);
+        let entries =
+            race_results::get_leaderboard(&course_key, &polar, Period::ALL_TIME, count as u32)
+                .await?;
+        let mut ghosts = Vec::new();
+        for entry in entries.into_iter().filter(|e| !existing.contains(&e.id)) {
+            match load_ghost_path(entry.id).await {
+                Ok(path) if !path.is_empty() => ghosts.push(GhostBoat {
+                    id: format!("ghost:{}", entry.id),
+                    result_id: entry.id,
+                    name: entry.player_name,
+                    path,
+                }),
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to load ghost path for result {}: {}", entry.id, e),
+            }
+        }
+
+        let mut races = self.races.write().await;
+        let race = races.get_mut(&race_id).ok_or(anyhow!("Race not found"))?;
+        if race.race_started() {
+            return Err(anyhow!("Race has already started"));
+        }
+        for ghost in ghosts {
+            if race.ghosts.len() >= MAX_GHOSTS
+                || race.ghosts.iter().any(|g| g.result_id == ghost.result_id)
+            {
+                continue;
+            }
+            race.broadcast_all(ServerMessage::PlayerJoined {
+                player_id: ghost.id.clone(),
+                player_name: ghost.name.clone(),
+            });
+            race.ghosts.push(ghost);
+        }
+        Ok(())
+    }
+
+    /// Retire a racing player: their race stops where they are and the
+    /// result is stored as retired. The player stays in the race as a spectator.
+    pub async fn retire(&self, player_id: &str) -> anyhow::Result<()> {
+        let player_races = self.player_races.read().await;
+        let race_id = player_races
+            .get(player_id)
+            .ok_or(anyhow!("Player not in a race"))?
+            .clone();
+        drop(player_races);
+
+        let pending = {
+            let mut races = self.races.write().await;
+            let race = races.get_mut(&race_id).ok_or(anyhow!("Race not found"))?;
+            if !race.race_started() {
+                return Err(anyhow!("Race has not started"));
+            }
+            self.stop_player(&race_id, race, player_id, PlayerStatus::Retired)
+                .ok_or(anyhow!("Player is not racing"))?
+        };
+
+        self.spawn_save(pending);
+        Ok(())
+    }
+
+    /// Stop a racing player with a final status and log it. Returns the result
+    /// to save once the races lock is released.
+    fn stop_player(
+        &self,
+        race_id: &str,
+        race: &mut Race,
+        player_id: &str,
+        status: PlayerStatus,
+    ) -> Option<PendingResult> {
+        let race_time = race.current_race_time()?;
+        let finished = race.stop_player(player_id, status, race_time)?;
+
+        let kind = match status {
+            PlayerStatus::Dnf => RaceEventKind::Dnf,
+            _ => RaceEventKind::Retire,
+        };
+        self.log_event(
+            race_id,
+            &race.course.key,
+            kind,
+            Some((&finished.player_id, &finished.player_name)),
+            serde_json::json!({ "courseTime": race_time }),
+        );
+
+        Some(PendingResult::new(race_id, race, finished))
+    }
+
+    /// End a race: boats still racing are marked DNF and their results saved,
+    /// then players get the final standings and the end notification.
+    #[tracing::instrument(skip(self))]
+    async fn end_race(&self, race_id: &str, reason: &str) {
+        let pending: Vec<PendingResult> = {
+            let mut races = self.races.write().await;
+            let Some(race) = races.get_mut(race_id) else {
+                return;
+            };
+            if race.race_ended {
+                return;
+            }
+
+            let racing: Vec<String> = race
+                .players
+                .values()
+                .filter(|p| p.status == PlayerStatus::Racing)
+                .map(|p| p.id.clone())
+                .collect();
+            let pending = racing
+                .iter()
+                .filter_map(|id| self.stop_player(race_id, race, id, PlayerStatus::Dnf))
+                .collect();
+
+            race.race_ended = true;
+            race.broadcast_all(ServerMessage::Leaderboard {
+                entries: race.compute_leaderboard(),
+            });
+            race.broadcast_all(ServerMessage::RaceEnded {
+                reason: reason.to_string(),
+            });
+            if !race.solo {
+                webhooks::dispatch(
+                    WebhookEvent::RaceEnded,
+                    serde_json::json!({
+                        "raceId": race_id,
+                        "courseKey": race.course.key,
+                        "reason": reason,
+                    }),
+                );
+            }
+            pending
+        };
+
+        for result in pending {
+            self.spawn_save(result);
+        }
+    }
+
+    /// Save a result in the background, before the server exits
+    fn spawn_save(&self, pending: PendingResult) {
+        self.tasks.spawn(pending.save());
+    }
+
+    /// Run a player connection, waited for before the server exits
+    pub async fn track_connection(&self, connection: impl Future<Output = ()>) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        self.tasks.track_future(connection).await;
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Number of open player connections
+    pub fn connection_count(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// Stop the races for a restart: they are ended like races out of time,
+    /// boats still racing being saved as DNF (by `drain`), then players are
+    /// disconnected
+    pub async fn shut_down(&self) {
+        for race_id in self.race_ids().await {
+            self.end_race(&race_id, "Server restarting").await;
+        }
+        // Drops the message senders of the players
+        self.races.write().await.clear();
+        self.player_races.write().await.clear();
+        self.match_queue.write().await.clear();
+        self.shutdown.cancel();
+    }
+
+    /// Wait for the connections to close and the results to be saved, up to
+    /// a timeout
+    pub async fn drain(&self, timeout: Duration) {
+        self.tasks.close();
+        if tokio::time::timeout(timeout, self.tasks.wait()).await.is_err() {
+            log::warn!("{} tasks still running at shutdown", self.tasks.len());
         }
     }
 
@@ -671,20 +2379,93 @@ impl RaceManager {
             .clone();
         drop(player_races);
 
-        // Validate and mark race as started
+        // Validate and mark race as started
+        {
+            let mut races = self.races.write().await;
+            let race = races.get_mut(&race_id).ok_or(anyhow!("Race not found"))?;
+
+            if race.creator_id != player_id {
+                return Err(anyhow!("Only the race creator can start the race"));
+            }
+
+            if race.race_started() {
+                return Err(anyhow!("Race has already started"));
+            }
+        }
+
+        self.run_countdown(&race_id).await
+    }
+
+    /// Open the lobby of a scheduled race. It has no creator and is started
+    /// by the scheduler at `start_time`.
+    pub async fn open_scheduled_race(
+        &self,
+        course_key: &str,
+        start_time: i64,
+    ) -> anyhow::Result<String> {
+        let course = courses::get_by_key(course_key)
+            .await?
+            .ok_or(anyhow!("Course not found"))?;
+        // Scheduled races are held in the window of the course at their start
+        course.check_open(start_time)?;
+
+        let reports = wind_reports::get_reports_for_course(&course).await?;
+        let rasters = WindRasterSource::for_reports(&reports).await?;
+
+        let race_id = generate_race_id();
+        let mut race = Race::new(course, rasters, String::new());
+        race.scheduled_start = Some(start_time);
+        webhooks::dispatch(
+            WebhookEvent::RaceCreated,
+            serde_json::json!({
+                "raceId": race_id,
+                "courseKey": course_key,
+                "creator": null,
+                "scheduledStart": start_time,
+            }),
+        );
+
+        self.races.write().await.insert(race_id.clone(), race);
+        cluster::spawn_claim(race_id.clone());
+        Ok(race_id)
+    }
+
+    /// Start a scheduled race. A lobby nobody joined is closed instead.
+    pub async fn start_scheduled_race(&self, race_id: &str) -> anyhow::Result<()> {
         {
             let mut races = self.races.write().await;
-            let race = races.get_mut(&race_id).ok_or(anyhow!("Race not found"))?;
-
-            if race.creator_id != player_id {
-                return Err(anyhow!("Only the race creator can start the race"));
-            }
-
+            let race = races.get(race_id).ok_or(anyhow!("Race not found"))?;
             if race.race_started() {
                 return Err(anyhow!("Race has already started"));
             }
+            if race.players.is_empty() {
+                races.remove(race_id);
+                return Err(anyhow!("No players joined"));
+            }
         }
 
+        self.run_countdown(race_id).await
+    }
+
+    /// 3-2-1 countdown, then mark the race as started
+    #[tracing::instrument(skip(self))]
+    async fn run_countdown(&self, race_id: &str) -> anyhow::Result<()> {
+        let race_id = race_id.to_string();
+
+        // Load the polar and wind index for the server-side simulation.
+        // Without them, the race falls back to positions reported by clients.
+        let course = match self.races.read().await.get(&race_id) {
+            Some(race) => race.course.clone(),
+            None => return Err(anyhow!("Race not found")),
+        };
+        let mut simulation = match Simulation::load(&course).await {
+            Ok(simulation) => Some(simulation),
+            Err(e) => {
+                log::warn!("Race {} not simulated: {}", race_id, e);
+                None
+            }
+        };
+
         // Countdown (release lock between each second)
         for seconds in (1..=3).rev() {
             {
@@ -705,7 +2486,53 @@ impl RaceManager {
             if let Some(race) = races.get_mut(&race_id) {
                 let start_time = Utc::now().timestamp_millis();
                 race.race_start_time = Some(start_time);
+                race.simulation = simulation.take();
+                if let Some(simulation) = race.simulation.as_ref() {
+                    for player in race.players.values_mut() {
+                        let mut boat = Boat::new(&race.course);
+                        boat.sail = simulation.first_sail(&player.polar);
+                        if let Some(battery) = &boat.battery {
+                            let _ = player.tx.send(energy_message(battery));
+                        }
+                        player.boat = Some(boat);
+                    }
+                }
                 race.broadcast_all(ServerMessage::RaceCountdown { seconds: 0 });
+
+                // Boats sailed by clients may have crossed the start line early
+                if race.simulation.is_none() {
+                    for player_id in race.flag_ocs() {
+                        self.announce_ocs(&race_id, race, &player_id, true);
+                    }
+                }
+
+                self.log_event(
+                    &race_id,
+                    &race.course.key,
+                    RaceEventKind::Start,
+                    None,
+                    serde_json::json!({
+                        "raceStartTime": start_time,
+                        "players": race.players.len(),
+                        "solo": race.solo,
+                        "simulated": race.simulation.is_some(),
+                    }),
+                );
+                if !race.solo {
+                    discord::notify(Notification::RaceStarted {
+                        course_key: race.course.key.clone(),
+                        players: race.players.len(),
+                    });
+                    webhooks::dispatch(
+                        WebhookEvent::RaceStarted,
+                        serde_json::json!({
+                            "raceId": race_id,
+                            "courseKey": race.course.key,
+                            "raceStartTime": start_time,
+                            "players": race.players.len(),
+                        }),
+                    );
+                }
             }
         }
 
@@ -714,7 +2541,7 @@ impl RaceManager {
 }
 
 /// Public race info for listing
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RaceInfo {
     pub id: String,
     pub course_key: String,
@@ -722,6 +2549,7 @@ pub struct RaceInfo {
     pub max_players: usize,
     pub race_started: bool,
     pub creator_id: String,
+    pub scheduled_start: Option<i64>,
 }
 
 impl RaceManager {
@@ -729,17 +2557,40 @@ impl RaceManager {
         let races = self.races.read().await;
         races
             .iter()
-            .filter(|(_, race)| !race.race_started()) // Only show races that haven't started
+            .filter(|(_, race)| !race.race_started() && !race.solo) // Only show open races that haven't started
             .map(|(id, race)| RaceInfo {
                 id: id.clone(),
                 course_key: race.course.key.clone(),
                 max_players: race.max_players,
                 race_started: race.race_started(),
                 creator_id: race.creator_id.clone(),
+                scheduled_start: race.scheduled_start,
                 players: race.players.values().map(|player| player.info()).collect(),
             })
             .collect::<Vec<_>>()
     }
+
+    /// Races running on this instance
+    pub async fn race_ids(&self) -> Vec<String> {
+        self.races.read().await.keys().cloned().collect()
+    }
+
+    /// Instance of the cluster running the race a message joins or
+    /// resumes, when it's not this one
+    async fn remote_owner(
+        &self,
+        message: &Option<anyhow::Result<ClientMessage>>,
+    ) -> Option<String> {
+        let (Some(Ok(ClientMessage::JoinRace { race_id, .. }))
+        | Some(Ok(ClientMessage::Resume { race_id, .. }))) = message
+        else {
+            return None;
+        };
+        if self.races.read().await.contains_key(race_id) {
+            return None;
+        }
+        cluster::owner(race_id).await
+    }
 }
 
 fn generate_id() -> String {
@@ -751,13 +2602,71 @@ fn generate_race_id() -> String {
     generate_id()[..6].to_string()
 }
 
-/// Save a finished player's race result to database and S3
+/// A race result to persist once the races lock is released
+struct PendingResult {
+    race_id: String,
+    course_key: String,
+    race_start_time: i64,
+    course_start_time: i64,
+    daily_date: Option<String>,
+    weather_start_time: Option<i64>,
+    finished: FinishedPlayer,
+}
+
+impl PendingResult {
+    fn new(race_id: &str, race: &Race, finished: FinishedPlayer) -> Self {
+        PendingResult {
+            race_id: race_id.to_string(),
+            course_key: race.course.key.clone(),
+            race_start_time: race.race_start_time.expect("race must be started"),
+            course_start_time: race.course.start_time,
+            daily_date: race.daily.clone(),
+            weather_start_time: race.weather_start_time,
+            finished,
+        }
+    }
+
+    async fn save(self) {
+        save_race_result(
+            self.race_id,
+            self.course_key,
+            self.race_start_time,
+            self.course_start_time,
+            self.daily_date,
+            self.weather_start_time,
+            self.finished,
+        )
+        .await
+    }
+}
+
+/// Download and decode the stored path of a race result
+async fn load_ghost_path(result_id: i64) -> anyhow::Result<Vec<PathPoint>> {
+    let key = race_results::get_path_key(result_id)
+        .await?
+        .ok_or(anyhow!("Result not found"))?;
+    race_results::load_path(&key).await
+}
+
+/// Save a player's race result (finished, retired or DNF) to database and S3
+#[tracing::instrument(
+    skip_all,
+    fields(race_id = %race_id, course_key = %course_key, player_id = %finished.persistent_id)
+)]
 async fn save_race_result(
+    race_id: String,
     course_key: String,
     race_start_time: i64,
     course_start_time: i64,
+    daily_date: Option<String>,
+    weather_start_time: Option<i64>,
     finished: FinishedPlayer,
 ) {
+    // Boats that never left the start line don't get a result
+    if finished.status != ResultStatus::Finished && finished.path_history.points.is_empty() {
+        return;
+    }
+
     let s3_key = format!(
         "paths/{}/{}_{}.bin",
         course_key, race_start_time, finished.player_id
@@ -767,13 +2676,8 @@ async fn save_race_result(
     let path_data = race_results::encode_path(&finished.path_history);
 
     // Upload to S3
-    let client = s3::paths_client();
-    if let Err(e) = client
-        .put(
-            &object_store::path::Path::from(s3_key.clone()),
-            Bytes::from(path_data).into(),
-        )
-        .await
+    if let Err(e) =
+        s3::put_immutable(s3::paths_client(), &s3_key, Bytes::from(path_data).into()).await
     {
         log::error!("Failed to upload race path to S3: {}", e);
         return;
@@ -782,24 +2686,98 @@ async fn save_race_result(
     // Calculate race duration (simulated elapsed time)
     let race_duration = finished.finish_time - course_start_time;
 
+    // Finishes ranked in the Hall of Fame are compared to the player's best
+    let ranked = finished.status == ResultStatus::Finished
+        && daily_date.is_none()
+        && weather_start_time.is_none();
+    let previous_best = if ranked {
+        personal_bests::best_time(&finished.persistent_id, &course_key, &finished.polar)
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to load personal best: {}", e);
+                None
+            })
+    } else {
+        None
+    };
+
+    let stats = race_stats::compute(&finished.path_history.points)
+        .await
+        .inspect_err(|e| log::warn!("Failed to compute race stats: {}", e))
+        .ok();
+
     // Save to database
-    if let Err(e) = race_results::save_result(
-        &course_key,
-        &finished.player_name,
-        &finished.persistent_id,
-        race_duration,
+    let result = NewRaceResult {
+        race_id,
+        course_key: course_key.clone(),
+        player_name: finished.player_name.clone(),
+        player_id: finished.persistent_id.clone(),
+        finish_time: race_duration,
         race_start_time,
-        &s3_key,
-    )
-    .await
-    {
-        log::error!("Failed to save race result to database: {}", e);
-        return;
+        path_s3_key: s3_key,
+        status: finished.status,
+        daily_date,
+        weather_start_time,
+        polar: finished.polar.clone(),
+        splits: finished
+            .gate_times
+            .iter()
+            .map(|time| time - course_start_time)
+            .collect(),
+        stats,
+    };
+    let result_id = match race_results::save_result(&result).await {
+        Ok(id) => id,
+        Err(e) => {
+            log::error!("Failed to save race result to database: {}", e);
+            return;
+        }
+    };
+    webhooks::dispatch(
+        WebhookEvent::ResultSaved,
+        serde_json::json!({
+            "resultId": result_id,
+            "raceId": result.race_id,
+            "courseKey": result.course_key,
+            "playerName": result.player_name,
+            "playerId": result.player_id,
+            "status": result.status.as_str(),
+            "finishTime": result.finish_time,
+            "polar": result.polar,
+            "dailyDate": result.daily_date,
+            "weatherStartTime": result.weather_start_time,
+        }),
+    );
+    if let Err(e) = race_summary::send(result_id, &result).await {
+        log::warn!("Failed to queue the race summary: {}", e);
+    }
+    if ranked && discord::enabled(DiscordEvent::TopTen) {
+        match race_results::hall_of_fame_rank(&course_key, &finished.polar, race_duration).await {
+            Ok(rank) if rank <= discord::TOP_RANK => discord::notify(Notification::TopTen {
+                course_key: course_key.clone(),
+                polar: finished.polar.clone(),
+                player: finished.player_name.clone(),
+                rank,
+                finish_time: race_duration,
+                result_id,
+            }),
+            Ok(_) => {}
+            Err(e) => log::warn!("Failed to rank the result for Discord: {}", e),
+        }
+    }
+
+    if ranked && previous_best.is_none_or(|best| race_duration < best) {
+        let _ = finished.tx.send(ServerMessage::PersonalBest {
+            course_key: course_key.clone(),
+            finish_time: race_duration,
+            previous_best,
+        });
     }
 
     log::info!(
-        "Saved race result: {} finished {} in {}ms",
+        "Saved race result: {} {} {} after {}ms",
         finished.player_name,
+        finished.status.as_str(),
         course_key,
         race_duration
     );
@@ -809,69 +2787,210 @@ async fn save_race_result(
 // WebSocket Handler
 // ============================================================================
 
+/// Client message of a text (JSON) or binary (MessagePack) frame, None for
+/// other frames
+fn decode_client_message(msg: Message) -> Option<anyhow::Result<ClientMessage>> {
+    match msg {
+        Message::Text(text) => Some(serde_json::from_str(&text).map_err(Into::into)),
+        Message::Binary(bytes) => Some(rmp_serde::from_slice(&bytes).map_err(Into::into)),
+        _ => None,
+    }
+}
+
+/// Protocol version of a connection from the one of the client: newer
+/// clients are served the server version, older ones their own while supported
+fn negotiate_protocol(client_version: u32) -> anyhow::Result<u32> {
+    if client_version < MIN_PROTOCOL_VERSION {
+        return Err(anyhow!(
+            "Protocol version {} is no longer supported, please reload the game",
+            client_version
+        ));
+    }
+    Ok(client_version.min(PROTOCOL_VERSION))
+}
+
+#[tracing::instrument(skip_all, fields(player_id = tracing::field::Empty))]
 pub async fn handle_websocket(ws: WebSocket, manager: RaceManager) {
     let (mut ws_tx, mut ws_rx) = ws.split();
     let (tx, mut rx) = mpsc::unbounded_channel::<ServerMessage>();
+    // Frames of the instance running the race, when relayed
+    let (frame_tx, mut frame_rx) = mpsc::unbounded_channel::<Message>();
 
-    let player_id = generate_id();
+    let mut player_id = generate_id();
+    tracing::Span::current().record("player_id", &player_id);
+    let (encoding_tx, encoding_rx) = watch::channel(Encoding::Json);
 
     // Task to forward server messages to WebSocket
     let forward_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            let Ok(json) = serde_json::to_string(&msg) else {
-                continue;
+        loop {
+            let frame = tokio::select! {
+                Some(msg) = rx.recv() => {
+                    let encoding = *encoding_rx.borrow();
+                    let Ok(frame) = encoding.encode(&msg) else {
+                        continue;
+                    };
+                    frame
+                }
+                Some(frame) = frame_rx.recv() => frame,
+                else => break,
             };
-            if ws_tx.send(Message::Text(json.into())).await.is_err() {
+            if ws_tx.send(frame).await.is_err() {
                 break;
             }
         }
     });
+    let mut hello = None;
 
-    // Process incoming messages
-    while let Some(result) = ws_rx.next().await {
+    // Process incoming messages until the player or the server leaves
+    while let Some(result) = tokio::select! {
+        result = ws_rx.next() => result,
+        _ = manager.shutdown.cancelled() => None,
+    } {
         match result {
-            Ok(msg) => match msg {
-                Message::Text(text) => match serde_json::from_str::<ClientMessage>(&text) {
-                    Ok(client_msg) => {
-                        handle_client_message(&manager, &player_id, tx.clone(), client_msg).await;
+            Ok(Message::Close(_)) | Err(_) => break,
+            Ok(msg) => {
+                let frame = msg.clone();
+                let message = decode_client_message(msg);
+                // Races of other instances are played there
+                if let Some(owner) = manager.remote_owner(&message).await {
+                    let first = hello.take().into_iter().chain([frame]).collect();
+                    let relayed =
+                        cluster::relay(&owner, first, &mut ws_rx, frame_tx.clone(), &manager.shutdown);
+                    match relayed.await {
+                        Ok(()) => break,
+                        Err(e) => {
+                            log::warn!("Failed to relay player to {}: {}", owner, e);
+                            let _ = tx.send(ServerMessage::Error {
+                                message: "Race not reachable".to_string(),
+                            });
+                            continue;
+                        }
+                    }
+                }
+                match message {
+                    Some(Ok(ClientMessage::Hello {
+                        protocol_version,
+                        encoding,
+                    })) => {
+                        hello = Some(frame);
+                        let reply = match negotiate_protocol(protocol_version) {
+                            Ok(protocol_version) => {
+                                let _ = encoding_tx.send(encoding);
+                                ServerMessage::Welcome {
+                                    protocol_version,
+                                    encoding,
+                                }
+                            }
+                            Err(error) => ServerMessage::Error {
+                                message: error.to_string(),
+                            },
+                        };
+                        let _ = tx.send(reply);
+                    }
+                    // The connection takes over the player id of the dropped one
+                    Some(Ok(ClientMessage::Resume {
+                        race_id,
+                        player_id: resumed_id,
+                        persistent_id,
+                    })) => match manager
+                        .resume(&race_id, &resumed_id, &persistent_id, tx.clone())
+                        .await
+                    {
+                        Ok(race_time) => {
+                            player_id = resumed_id;
+                            tracing::Span::current().record("player_id", &player_id);
+                            let _ = tx.send(ServerMessage::RaceResumed {
+                                race_id,
+                                player_id: player_id.clone(),
+                                race_time,
+                            });
+                        }
+                        Err(error) => {
+                            let _ = tx.send(ServerMessage::Error {
+                                message: error.to_string(),
+                            });
+                        }
+                    },
+                    Some(Ok(client_msg)) => {
+                        let span = message_span(&client_msg);
+                        handle_client_message(&manager, &player_id, tx.clone(), client_msg)
+                            .instrument(span)
+                            .await;
                     }
-                    Err(err) => {
+                    Some(Err(err)) => {
                         log::error!("Failed to decode message: {}", err);
                     }
-                },
-                Message::Close(_) => break,
-                _ => {}
-            },
-            Err(_) => break,
+                    // Pings and pongs
+                    None => {}
+                }
+            }
         }
     }
 
+    if manager.shutdown.is_cancelled() {
+        // Let the last messages out, the races dropped their senders
+        drop(tx);
+        drop(frame_tx);
+        let _ = tokio::time::timeout(Duration::from_secs(2), forward_task).await;
+        return;
+    }
+
     // Cleanup on disconnect
-    manager.leave_race(&player_id).await;
+    manager.disconnect(&player_id).await;
     forward_task.abort();
 }
 
+/// Span of a client message, at debug level for the position updates sent
+/// every few seconds by each player
+fn message_span(message: &ClientMessage) -> tracing::Span {
+    let kind = message.kind();
+    if let ClientMessage::PositionUpdate { .. } = message {
+        tracing::debug_span!("message", otel.name = kind, kind)
+    } else {
+        tracing::info_span!("message", otel.name = kind, kind)
+    }
+}
+
 async fn handle_client_message(
     manager: &RaceManager,
     player_id: &str,
     tx: mpsc::UnboundedSender<ServerMessage>,
     message: ClientMessage,
 ) {
+    // Avatars of players entering a race are shown to the others
+    if let ClientMessage::CreateRace { persistent_id, .. }
+    | ClientMessage::JoinRace { persistent_id, .. }
+    | ClientMessage::StartDaily { persistent_id, .. }
+    | ClientMessage::QuickMatch { persistent_id, .. } = &message
+    {
+        avatars::load_racer(persistent_id).await;
+    }
+
     let result: anyhow::Result<()> = match message {
         ClientMessage::CreateRace {
             course_key,
             player_name,
             persistent_id,
+            solo,
+            random_weather,
+            polar,
         } => {
+            let options = RaceOptions {
+                solo,
+                random_weather,
+                boat_class: polar,
+            };
             match manager
-                .create_race(course_key, player_id.to_string(), player_name, persistent_id, tx.clone())
+                .create_race(course_key, player_id.to_string(), player_name, persistent_id, options, tx.clone())
                 .await
             {
-                Ok((race_id, rasters)) => {
+                Ok((race_id, rasters, course_start_time, polar)) => {
                     let _ = tx.send(ServerMessage::RaceCreated {
                         race_id,
                         player_id: player_id.to_string(),
                         wind_raster_sources: rasters,
+                        course_start_time,
+                        polar,
                     });
                     Ok(())
                 }
@@ -883,12 +3002,20 @@ async fn handle_client_message(
             race_id,
             player_name,
             persistent_id,
+            polar,
         } => {
             match manager
-                .join_race(&race_id, player_id.to_string(), player_name, persistent_id, tx.clone())
+                .join_race(
+                    &race_id,
+                    player_id.to_string(),
+                    player_name,
+                    persistent_id,
+                    polar,
+                    tx.clone(),
+                )
                 .await
             {
-                Ok((players, rasters, course_key, is_creator)) => {
+                Ok((players, rasters, course_key, is_creator, course_start_time, polar)) => {
                     let _ = tx.send(ServerMessage::RaceJoined {
                         race_id,
                         player_id: player_id.to_string(),
@@ -896,6 +3023,33 @@ async fn handle_client_message(
                         wind_raster_sources: rasters,
                         players,
                         is_creator,
+                        course_start_time,
+                        polar,
+                    });
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        }
+
+        ClientMessage::StartDaily {
+            player_name,
+            persistent_id,
+        } => {
+            match manager
+                .start_daily(player_id.to_string(), player_name, persistent_id, tx.clone())
+                .await
+            {
+                Ok((race_id, course_key, course_start_time, players, rasters, polar)) => {
+                    let _ = tx.send(ServerMessage::RaceJoined {
+                        race_id,
+                        player_id: player_id.to_string(),
+                        course_key,
+                        wind_raster_sources: rasters,
+                        players,
+                        is_creator: true,
+                        course_start_time,
+                        polar,
                     });
                     Ok(())
                 }
@@ -903,6 +3057,41 @@ async fn handle_client_message(
             }
         }
 
+        ClientMessage::ProposeRematch => match manager.propose_rematch(player_id).await {
+            Ok((race_id, (players, rasters, course_key, is_creator, course_start_time, polar))) => {
+                let _ = tx.send(ServerMessage::RaceJoined {
+                    race_id,
+                    player_id: player_id.to_string(),
+                    course_key,
+                    wind_raster_sources: rasters,
+                    players,
+                    is_creator,
+                    course_start_time,
+                    polar,
+                });
+                Ok(())
+            }
+            Err(e) => Err(e),
+        },
+
+        ClientMessage::QuickMatch {
+            course_key,
+            player_name,
+            persistent_id,
+            polar,
+        } => {
+            manager
+                .quick_match(
+                    course_key,
+                    player_id.to_string(),
+                    player_name,
+                    persistent_id,
+                    polar,
+                    tx.clone(),
+                )
+                .await
+        }
+
         ClientMessage::LeaveRace => {
             manager.leave_race(player_id).await;
             Ok(())
@@ -910,13 +3099,37 @@ async fn handle_client_message(
 
         ClientMessage::StartRace => manager.start_race(player_id).await,
 
+        ClientMessage::RetireFromRace => manager.retire(player_id).await,
+
+        ClientMessage::AddGhosts { count } => manager.add_ghosts(player_id, count).await,
+
         ClientMessage::PositionUpdate { lng, lat, heading } => {
+            let (lng, lat) = (f64::from(lng) as f32, f64::from(lat) as f32);
             manager
-                .broadcast_position(player_id, lng, lat, heading)
+                .broadcast_position(player_id, lng, lat, f64::from(heading) as f32)
                 .await;
             Ok(())
         }
 
+        ClientMessage::Helm { heading, twa } => {
+            let helm = match twa {
+                Some(twa) => Helm::Twa(twa.into()),
+                None => Helm::Heading(heading.into()),
+            };
+            manager.set_helm(player_id, helm).await;
+            Ok(())
+        }
+
+        ClientMessage::ChangeSail { sail } => manager.change_sail(player_id, &sail).await,
+
+        ClientMessage::ToggleSystem { system, on } => {
+            manager.toggle_system(player_id, system, on).await
+        }
+
+        ClientMessage::AskCoach => manager.coach(player_id).await.map(|hint| {
+            let _ = tx.send(ServerMessage::CoachHint { hint });
+        }),
+
         ClientMessage::GateCrossed {
             gate_index,
             course_time,
@@ -926,6 +3139,9 @@ async fn handle_client_message(
                 .await;
             Ok(())
         }
+
+        // Handled by the connection, see `handle_websocket`
+        ClientMessage::Hello { .. } | ClientMessage::Resume { .. } => Ok(()),
     };
 
     if let Err(error) = result {
@@ -940,6 +3156,78 @@ async fn handle_client_message(
 mod tests {
     use super::*;
 
+    // =========================================================================
+    // Protocol negotiation tests
+    // =========================================================================
+
+    #[test]
+    fn test_negotiate_protocol() {
+        assert_eq!(negotiate_protocol(1).unwrap(), 1);
+        // Newer clients are served the server version
+        assert_eq!(negotiate_protocol(99).unwrap(), PROTOCOL_VERSION);
+        assert!(negotiate_protocol(MIN_PROTOCOL_VERSION - 1).is_err());
+    }
+
+    #[test]
+    fn test_hello_message() {
+        let json = r#"{"type":"Hello","protocolVersion":3}"#;
+        let message: ClientMessage = serde_json::from_str(json).unwrap();
+        let ClientMessage::Hello {
+            protocol_version,
+            encoding,
+        } = message
+        else {
+            panic!("Expected Hello");
+        };
+        assert_eq!(protocol_version, 3);
+        assert_eq!(encoding, Encoding::Json);
+    }
+
+    #[test]
+    fn test_message_kind() {
+        for json in [r#"{"type":"LeaveRace"}"#, r#"{"type":"Hello","protocolVersion":3}"#] {
+            let message: ClientMessage = serde_json::from_str(json).unwrap();
+            let tag: serde_json::Value = serde_json::from_str(json).unwrap();
+            assert_eq!(message.kind(), tag["type"]);
+        }
+    }
+
+    #[test]
+    fn test_msgpack_messages() {
+        let message = ServerMessage::RaceCountdown { seconds: 3 };
+        let Message::Binary(bytes) = Encoding::MessagePack.encode(&message).unwrap() else {
+            panic!("Expected a binary frame");
+        };
+        // Same shape as the JSON messages
+        let value: serde_json::Value = rmp_serde::from_slice(&bytes).unwrap();
+        let expected = serde_json::json!({ "type": "RaceCountdown", "seconds": 3 });
+        assert_eq!(value, expected);
+
+        let helm = serde_json::json!({ "type": "Helm", "heading": 90, "twa": null });
+        let frame = Message::Binary(rmp_serde::to_vec_named(&helm).unwrap().into());
+        let Some(Ok(ClientMessage::Helm { heading, twa })) = decode_client_message(frame) else {
+            panic!("Expected Helm");
+        };
+        assert_eq!(f64::from(heading), 90.0);
+        assert_eq!(twa, None);
+
+        // NaN angles would spread to the simulated positions
+        #[derive(serde::Serialize)]
+        struct RawHelm {
+            #[serde(rename = "type")]
+            kind: &'static str,
+            heading: f64,
+            twa: Option<f64>,
+        }
+        let nan = RawHelm {
+            kind: "Helm",
+            heading: 90.0,
+            twa: Some(f64::NAN),
+        };
+        let frame = Message::Binary(rmp_serde::to_vec_named(&nan).unwrap().into());
+        assert!(matches!(decode_client_message(frame), Some(Err(_))));
+    }
+
     // =========================================================================
     // generate_id / generate_lobby_id tests
     // =========================================================================
@@ -978,18 +3266,13 @@ mod tests {
 
     fn make_test_player(id: &str, name: &str) -> Player {
         let (tx, _rx) = mpsc::unbounded_channel();
-        Player {
-            id: id.to_string(),
-            name: name.to_string(),
-            persistent_id: format!("persistent-{id}"),
+        Player::new(
+            id.to_string(),
+            name.to_string(),
+            format!("persistent-{id}"),
+            "vr-imoca-full-pack".to_string(),
             tx,
-            position: None,
-            heading: 0.0,
-            next_gate_index: 0,
-            finish_time: None,
-            path_history: Vec::new(),
-            last_sample_instant: None,
-        }
+        )
     }
 
     fn make_test_course() -> Course {
@@ -999,6 +3282,7 @@ mod tests {
             name: "Vendee Globe 2020".to_string(),
             description: "Test course".to_string(),
             polar: "vr-imoca-full-pack".to_string(),
+            boat_classes: vec![],
             start_time: 1604833200000,
             start: crate::courses::LngLat {
                 lng: -1.788,
@@ -1010,6 +3294,13 @@ mod tests {
             route_waypoints: vec![vec![]],
             time_factor: 2000,
             max_days: 90,
+            energy: None,
+            min_depth: None,
+            laps: 1,
+            tags: vec![],
+            published: true,
+            opens_at: None,
+            closes_at: None,
         }
     }
 
@@ -1131,21 +3422,247 @@ mod tests {
         let mut race = make_test_race();
         race.last_activity = Utc::now() - chrono::Duration::minutes(6);
 
-        // Add a player
-        let player = make_test_player("player-1", "Alice");
-        race.players.insert("player-1".to_string(), player);
+        // Add a player
+        let player = make_test_player("player-1", "Alice");
+        race.players.insert("player-1".to_string(), player);
+
+        // Should not be expired because it has players
+        assert!(!race.is_expired());
+    }
+
+    #[test]
+    fn test_race_is_not_expired_recent_activity() {
+        let race = make_test_race();
+        // Fresh race with no players
+
+        // Should not be expired because activity is recent
+        assert!(!race.is_expired());
+    }
+
+    #[test]
+    fn test_race_scheduled_lobby_not_expired_before_start() {
+        let mut race = make_test_race();
+        race.last_activity = Utc::now() - chrono::Duration::minutes(5);
+        race.scheduled_start = Some(Utc::now().timestamp_millis() + 60_000);
+        assert!(!race.is_expired());
+
+        race.scheduled_start = Some(Utc::now().timestamp_millis() - 1);
+        assert!(race.is_expired());
+    }
+
+    #[test]
+    fn test_distance_along_leg_follows_waypoints() {
+        use crate::courses::Gate;
+        let mut course = make_test_course();
+        course.start = LngLat { lng: 0.0, lat: 0.0 };
+        course.finish_line = Gate::vertical(10.0, 0.0, 10.0);
+        course.route_waypoints = vec![vec![LngLat { lng: 5.0, lat: 10.0 }]];
+
+        let route_length = path_length(&course.leg_path(0));
+        let straight = geo::distance_nm(&course.start, &course.finish_line.center);
+        assert!(route_length > straight);
+
+        // At the start, the remaining distance is the whole route
+        let at_start = distance_along_leg(&course, 0, 0.0, 0.0);
+        assert!((at_start - route_length).abs() < 1e-6);
+
+        // On the waypoint, half the route remains
+        let at_waypoint = distance_along_leg(&course, 0, 5.0, 10.0);
+        assert!((at_waypoint - route_length / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_distance_along_leg_across_antimeridian() {
+        use crate::courses::Gate;
+        let mut course = make_test_course();
+        course.start = LngLat { lng: 170.0, lat: -50.0 };
+        course.finish_line = Gate::vertical(-170.0, -50.0, 10.0);
+        course.route_waypoints = vec![vec![]];
+
+        let remaining = distance_along_leg(&course, 0, 179.0, -50.0);
+        let direct = geo::distance_nm(
+            &LngLat {
+                lng: 179.0,
+                lat: -50.0,
+            },
+            &course.finish_line.center,
+        );
+        assert!((remaining - direct).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_leaderboard_ranks_by_route_distance() {
+        use crate::courses::Gate;
+        let mut race = make_test_race();
+        race.course.start = LngLat { lng: 0.0, lat: 0.0 };
+        race.course.finish_line = Gate::vertical(10.0, 0.0, 10.0);
+        race.course.route_waypoints = vec![vec![LngLat { lng: 5.0, lat: 10.0 }]];
+
+        // Alice is near the route's turning point; Bob is closer to the finish
+        // in a straight line but has barely started along the route.
+        let mut alice = make_test_player("p1", "Alice");
+        alice.position = Some((5.0, 9.0));
+        let mut bob = make_test_player("p2", "Bob");
+        bob.position = Some((3.0, 0.0));
+        race.add_player(alice).unwrap();
+        race.add_player(bob).unwrap();
+
+        let entries = race.compute_leaderboard();
+        assert_eq!(entries[0].player_name, "Alice");
+        assert_eq!(entries[1].player_name, "Bob");
+        assert_eq!(entries[0].distance_to_finish, entries[0].distance_to_next_gate);
+    }
+
+    #[test]
+    fn test_leaderboard_grouped_by_boat_class() {
+        let mut race = make_test_race();
+        race.course.boat_classes = vec!["mini-650".to_string()];
+        assert_eq!(race.course.boat_class(None).unwrap(), "vr-imoca-full-pack");
+        assert_eq!(race.course.boat_class(Some("mini-650".to_string())).unwrap(), "mini-650");
+        assert!(race.course.boat_class(Some("class40".to_string())).is_err());
+
+        // The mini leads on the water but is ranked in its own class, after the IMOCAs
+        let mut mini = make_test_player("p1", "Mini");
+        mini.polar = "mini-650".to_string();
+        mini.position = Some((-10.0, 40.0));
+        let mut imoca = make_test_player("p2", "Imoca");
+        imoca.position = Some((-10.0, 40.0));
+        race.add_player(mini).unwrap();
+        race.add_player(imoca).unwrap();
+        race.race_start_time = Some(Utc::now().timestamp_millis());
+        race.record_gate_crossing("p1", 0, 20).unwrap();
+
+        let entries = race.compute_leaderboard();
+        let names: Vec<&str> = entries.iter().map(|e| e.player_name.as_str()).collect();
+        assert_eq!(names, vec!["Imoca", "Mini"]);
+        assert_eq!(entries[1].polar, "mini-650");
+    }
+
+    #[test]
+    fn test_stop_player_requires_started_race() {
+        let mut race = make_test_race();
+        race.add_player(make_test_player("p1", "Alice")).unwrap();
+
+        assert!(race.stop_player("p1", PlayerStatus::Retired, 0).is_none());
+        assert_eq!(race.players["p1"].status, PlayerStatus::Racing);
+    }
+
+    #[test]
+    fn test_retired_player_cannot_cross_gates() {
+        let mut race = make_test_race();
+        race.add_player(make_test_player("p1", "Alice")).unwrap();
+        race.race_start_time = Some(Utc::now().timestamp_millis());
+
+        let stopped = race.stop_player("p1", PlayerStatus::Retired, 42).unwrap();
+        assert_eq!(stopped.status, ResultStatus::Retired);
+        assert_eq!(stopped.finish_time, 42);
+
+        // Crossing the finish line (gate 0 on a course without gates) is ignored
+        assert!(race.record_gate_crossing("p1", 0, 100).is_none());
+        assert!(race.players["p1"].finish_time.is_none());
+
+        // A player can only be stopped once
+        assert!(race.stop_player("p1", PlayerStatus::Dnf, 50).is_none());
+    }
+
+    #[test]
+    fn test_ocs_blocks_first_gate() {
+        use crate::courses::Gate;
+        let mut race = make_test_race();
+        // Start heading west: west of the start is the course side
+        race.course.gates = vec![Gate::vertical(-5.0, 46.47, 10.0)];
+        let mut alice = make_test_player("p1", "Alice");
+        alice.position = Some((-1.9, 46.47));
+        let mut bob = make_test_player("p2", "Bob");
+        bob.position = Some((-1.7, 46.47));
+        race.add_player(alice).unwrap();
+        race.add_player(bob).unwrap();
+
+        assert_eq!(race.flag_ocs(), vec!["p1".to_string()]);
+        assert!(race.record_gate_crossing("p1", 0, 100).is_none());
+        assert_eq!(race.players["p1"].next_gate_index, 0);
+
+        // Still over: the flag stays
+        assert!(!race.clear_ocs("p1"));
+
+        // Back behind the line, gate 0 counts again
+        race.players.get_mut("p1").unwrap().position = Some((-1.7, 46.5));
+        assert!(race.clear_ocs("p1"));
+        race.record_gate_crossing("p1", 0, 200);
+        assert_eq!(race.players["p1"].next_gate_index, 1);
+
+        race.record_gate_crossing("p2", 0, 150);
+        assert_eq!(race.players["p2"].next_gate_index, 1);
+    }
+
+    #[test]
+    fn test_laps() {
+        use crate::courses::Gate;
+        let mut race = make_test_race();
+        race.course.gates = vec![
+            Gate::vertical(-5.0, 46.47, 10.0),
+            Gate::vertical(-6.0, 46.47, 10.0),
+        ];
+        race.course.route_waypoints = vec![vec![], vec![], vec![]];
+        race.course.laps = 2;
+        let mut alice = make_test_player("p1", "Alice");
+        alice.position = Some((-5.5, 46.47));
+        race.add_player(alice).unwrap();
+
+        // Windward and leeward gates twice, then the finish
+        for (gate, time) in [(0, 100), (1, 200), (2, 300), (3, 400)] {
+            assert!(race.record_gate_crossing("p1", gate, time).is_none());
+        }
+        assert_eq!(race.players["p1"].lap_times, vec![200, 400]);
+        let entry = race.leaderboard_entry(&race.players["p1"]).unwrap();
+        assert_eq!(entry.lap_times, vec![200, 400]);
+        let finished = race.record_gate_crossing("p1", 4, 500).unwrap();
+        assert_eq!(finished.gate_times, vec![100, 200, 300, 400]);
+    }
+
+    #[test]
+    fn test_leaderboard_orders_by_status() {
+        let mut race = make_test_race();
+        for (id, name) in [("p1", "Alice"), ("p2", "Bob"), ("p3", "Carol"), ("p4", "Dan")] {
+            let mut player = make_test_player(id, name);
+            player.position = Some((-10.0, 40.0));
+            race.add_player(player).unwrap();
+        }
+        race.race_start_time = Some(Utc::now().timestamp_millis());
+
+        race.stop_player("p1", PlayerStatus::Retired, 10).unwrap();
+        race.stop_player("p2", PlayerStatus::Dnf, 10).unwrap();
+        race.record_gate_crossing("p4", 0, 20).unwrap();
 
-        // Should not be expired because it has players
-        assert!(!race.is_expired());
+        let entries = race.compute_leaderboard();
+        let names: Vec<&str> = entries.iter().map(|e| e.player_name.as_str()).collect();
+        assert_eq!(names, vec!["Dan", "Carol", "Bob", "Alice"]);
+        let statuses: Vec<PlayerStatus> = entries.iter().map(|e| e.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                PlayerStatus::Finished,
+                PlayerStatus::Racing,
+                PlayerStatus::Dnf,
+                PlayerStatus::Retired,
+            ]
+        );
     }
 
     #[test]
-    fn test_race_is_not_expired_recent_activity() {
-        let race = make_test_race();
-        // Fresh race with no players
+    fn test_departed_players_stay_on_leaderboard() {
+        let mut race = make_test_race();
+        let mut alice = make_test_player("p1", "Alice");
+        alice.position = Some((-10.0, 40.0));
+        race.add_player(alice).unwrap();
+        race.race_start_time = Some(Utc::now().timestamp_millis());
 
-        // Should not be expired because activity is recent
-        assert!(!race.is_expired());
+        race.stop_player("p1", PlayerStatus::Retired, 10).unwrap();
+        race.remove_player("p1");
+
+        let entries = race.compute_leaderboard();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].status, PlayerStatus::Retired);
     }
 
     #[test]
@@ -1162,6 +3679,37 @@ mod tests {
         assert!(names.contains(&"Bob"));
     }
 
+    #[test]
+    fn test_race_ghost_positions() {
+        let mut race = make_test_race();
+        race.add_player(make_test_player("p1", "Alice")).unwrap();
+        let start = race.course.start_time;
+        race.ghosts.push(GhostBoat {
+            id: "ghost:7".to_string(),
+            result_id: 7,
+            name: "Fast Eddie".to_string(),
+            path: vec![
+                PathPoint { race_time: start, lng: 0.0, lat: 0.0, heading: 90.0 },
+                PathPoint { race_time: start + 1_000_000_000, lng: 1.0, lat: 0.0, heading: 90.0 },
+            ],
+        });
+
+        // Ghosts are listed with players but don't move before the start
+        assert_eq!(race.get_player_infos().len(), 2);
+        assert!(race.ghost_positions().is_empty());
+
+        race.race_start_time = Some(Utc::now().timestamp_millis());
+        let positions = race.ghost_positions();
+        assert_eq!(positions.len(), 1);
+        match &positions[0] {
+            ServerMessage::PositionUpdate { player_id, lat, .. } => {
+                assert_eq!(player_id, "ghost:7");
+                assert_eq!(*lat, 0.0);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
     // =========================================================================
     // RaceManager tests (async)
     // =========================================================================
@@ -1179,12 +3727,13 @@ mod tests {
                 "player-1".to_string(),
                 "Alice".to_string(),
                 "persistent-1".to_string(),
+                RaceOptions::default(),
                 tx,
             )
             .await;
 
         assert!(result.is_ok());
-        let (race_id, _) = result.unwrap();
+        let (race_id, _, _, _) = result.unwrap();
         assert_eq!(race_id.len(), 6);
 
         // Verify race exists
@@ -1201,12 +3750,13 @@ mod tests {
         let (tx2, _rx2) = mpsc::unbounded_channel();
 
         // Create race
-        let (race_id, _) = manager
+        let (race_id, _, _, _) = manager
             .create_race(
                 "vg20".to_string(),
                 "player-1".to_string(),
                 "Alice".to_string(),
                 "persistent-1".to_string(),
+                RaceOptions::default(),
                 tx1,
             )
             .await
@@ -1214,24 +3764,83 @@ mod tests {
 
         // Join race
         let result = manager
-            .join_race(&race_id, "player-2".to_string(), "Bob".to_string(), "persistent-2".to_string(), tx2)
+            .join_race(&race_id, "player-2".to_string(), "Bob".to_string(), "persistent-2".to_string(), None, tx2)
             .await;
 
         assert!(result.is_ok());
-        let (players, rasters, course_key, is_creator) = result.unwrap();
+        let (players, rasters, course_key, is_creator, _, _) = result.unwrap();
         assert_eq!(course_key, "vg20");
         assert!(rasters.is_empty());
         assert!(!is_creator);
         assert_eq!(players.len(), 2); // Alice and Bob
     }
 
+    #[tokio::test]
+    async fn test_race_manager_shut_down() {
+        crate::db::init_test().await.unwrap();
+
+        let manager = RaceManager::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (race_id, _, _, _) = manager
+            .create_race(
+                "vg20".to_string(),
+                "player-1".to_string(),
+                "Alice".to_string(),
+                "persistent-shutdown".to_string(),
+                RaceOptions::default(),
+                tx,
+            )
+            .await
+            .unwrap();
+
+        // Racing, away from the start line
+        {
+            let mut races = manager.races.write().await;
+            let race = races.get_mut(&race_id).unwrap();
+            race.race_start_time = Some(Utc::now().timestamp_millis() - 1000);
+            let player = race.players.get_mut("player-1").unwrap();
+            player.path_history.push(
+                PathPoint {
+                    race_time: 0,
+                    lng: -1.5,
+                    lat: 46.0,
+                    heading: 270.0,
+                },
+                None,
+                None,
+            );
+        }
+
+        manager.shut_down().await;
+        assert!(manager.races.read().await.is_empty());
+        assert!(manager.shutdown.is_cancelled());
+
+        // The end notice is the last message, the race dropped its sender
+        let mut last = None;
+        while let Some(message) = rx.recv().await {
+            last = Some(message);
+        }
+        assert!(matches!(
+            last,
+            Some(ServerMessage::RaceEnded { reason }) if reason == "Server restarting"
+        ));
+        manager.drain(Duration::from_secs(5)).await;
+
+        // The boat still racing was saved as DNF before the exit
+        let results = race_results::list_for_player("persistent-shutdown")
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, ResultStatus::Dnf);
+    }
+
     #[tokio::test]
     async fn test_race_manager_join_nonexistent_race() {
         let manager = RaceManager::new();
         let (tx, _rx) = mpsc::unbounded_channel();
 
         let result = manager
-            .join_race("AAAAAA", "player-1".to_string(), "Alice".to_string(), "persistent-1".to_string(), tx)
+            .join_race("AAAAAA", "player-1".to_string(), "Alice".to_string(), "persistent-1".to_string(), None, tx)
             .await;
 
         assert!(result.is_err());
@@ -1248,12 +3857,13 @@ mod tests {
         let manager = RaceManager::new();
         let (tx, _rx) = mpsc::unbounded_channel();
 
-        let (race_id, _) = manager
+        let (race_id, _, _, _) = manager
             .create_race(
                 "vg20".to_string(),
                 "player-1".to_string(),
                 "Alice".to_string(),
                 "persistent-1".to_string(),
+                RaceOptions::default(),
                 tx,
             )
             .await
@@ -1267,6 +3877,55 @@ mod tests {
         assert!(!races.contains_key(&race_id));
     }
 
+    #[tokio::test]
+    async fn test_race_manager_logs_events() {
+        crate::db::init_test().await.unwrap();
+
+        let manager = RaceManager::new();
+        let (tx1, _rx1) = mpsc::unbounded_channel();
+        let (tx2, _rx2) = mpsc::unbounded_channel();
+
+        let (race_id, _, _, _) = manager
+            .create_race(
+                "vg20".to_string(),
+                "player-1".to_string(),
+                "Alice".to_string(),
+                "persistent-1".to_string(),
+                RaceOptions::default(),
+                tx1,
+            )
+            .await
+            .unwrap();
+        manager
+            .join_race(&race_id, "player-2".to_string(), "Bob".to_string(), "persistent-2".to_string(), None, tx2)
+            .await
+            .unwrap();
+        manager.leave_race("player-2").await;
+        manager.disconnect("player-1").await;
+
+        // Events are written by a background task
+        let mut events = Vec::new();
+        for _ in 0..50 {
+            events = race_events::list_for_race(&race_id).await.unwrap();
+            if events.len() == 4 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let kinds: Vec<RaceEventKind> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                RaceEventKind::Join,
+                RaceEventKind::Join,
+                RaceEventKind::Leave,
+                RaceEventKind::Disconnect,
+            ]
+        );
+        assert_eq!(events[1].player_name.as_deref(), Some("Bob"));
+    }
+
     #[tokio::test]
     async fn test_race_manager_list_races() {
         crate::db::init_test().await.unwrap();
@@ -1281,6 +3940,7 @@ mod tests {
                 "player-1".to_string(),
                 "Alice".to_string(),
                 "persistent-1".to_string(),
+                RaceOptions::default(),
                 tx1,
             )
             .await
@@ -1292,6 +3952,7 @@ mod tests {
                 "player-2".to_string(),
                 "Bob".to_string(),
                 "persistent-2".to_string(),
+                RaceOptions::default(),
                 tx2,
             )
             .await
@@ -1309,12 +3970,13 @@ mod tests {
         let manager = RaceManager::new();
         let (tx, _rx) = mpsc::unbounded_channel();
 
-        let (race_id, _) = manager
+        let (race_id, _, _, _) = manager
             .create_race(
                 "vg20".to_string(),
                 "player-1".to_string(),
                 "Alice".to_string(),
                 "persistent-1".to_string(),
+                RaceOptions::default(),
                 tx,
             )
             .await
@@ -1330,4 +3992,334 @@ mod tests {
 
         assert!(races.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_race_manager_scheduled_race_without_players_is_closed() {
+        crate::db::init_test().await.unwrap();
+
+        let manager = RaceManager::new();
+        let start = Utc::now().timestamp_millis() + 60_000;
+        let race_id = manager.open_scheduled_race("vg20", start).await.unwrap();
+
+        let races = manager.list_races().await;
+        let info = races.iter().find(|r| r.id == race_id).unwrap();
+        assert_eq!(info.scheduled_start, Some(start));
+        assert!(info.creator_id.is_empty());
+
+        let result = manager.start_scheduled_race(&race_id).await;
+        assert_eq!(result.unwrap_err().to_string(), "No players joined");
+        assert!(!manager.races.read().await.contains_key(&race_id));
+    }
+
+    #[tokio::test]
+    async fn test_race_manager_quick_match_forms_race() {
+        crate::db::init_test().await.unwrap();
+
+        let manager = RaceManager::new();
+        let (tx1, mut rx1) = mpsc::unbounded_channel();
+        let (tx2, _rx2) = mpsc::unbounded_channel();
+
+        manager
+            .quick_match("vg20".to_string(), "player-1".to_string(), "Alice".to_string(), "persistent-1".to_string(), None, tx1)
+            .await
+            .unwrap();
+        assert!(matches!(
+            rx1.recv().await,
+            Some(ServerMessage::MatchQueued { queued_players: 1, .. })
+        ));
+        assert!(!manager.player_races.read().await.contains_key("player-1"));
+
+        manager
+            .quick_match("vg20".to_string(), "player-2".to_string(), "Bob".to_string(), "persistent-2".to_string(), None, tx2)
+            .await
+            .unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+
+        let player_races = manager.player_races.read().await;
+        let race_id = player_races.get("player-1").unwrap();
+        assert_eq!(player_races.get("player-2"), Some(race_id));
+        let races = manager.races.read().await;
+        let race = races.get(race_id).unwrap();
+        assert_eq!(race.players.len(), 2);
+        assert!(race.scheduled_start.is_some());
+        assert!(manager.match_queue.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_race_manager_rematch() {
+        crate::db::init_test().await.unwrap();
+
+        let manager = RaceManager::new();
+        let (tx1, _rx1) = mpsc::unbounded_channel();
+        let (tx2, mut rx2) = mpsc::unbounded_channel();
+
+        let (race_id, _, _, _) = manager
+            .create_race(
+                "vg20".to_string(),
+                "player-1".to_string(),
+                "Alice".to_string(),
+                "persistent-1".to_string(),
+                RaceOptions::default(),
+                tx1,
+            )
+            .await
+            .unwrap();
+        manager
+            .join_race(&race_id, "player-2".to_string(), "Bob".to_string(), "persistent-2".to_string(), None, tx2)
+            .await
+            .unwrap();
+        {
+            let mut races = manager.races.write().await;
+            races.get_mut(&race_id).unwrap().race_start_time = Some(Utc::now().timestamp_millis());
+        }
+
+        // Only players whose race is over can propose
+        assert!(manager.propose_rematch("player-1").await.is_err());
+        manager.retire("player-1").await.unwrap();
+        let (rematch_id, (_, _, _, is_creator, _, _)) = manager.propose_rematch("player-1").await.unwrap();
+        assert_ne!(rematch_id, race_id);
+        assert!(is_creator);
+
+        // Bob is invited once his own race is over
+        manager.retire("player-2").await.unwrap();
+        let mut invited = false;
+        while let Ok(message) = rx2.try_recv() {
+            if let ServerMessage::RematchProposed { race_id, proposer_name } = message {
+                assert_eq!(race_id, rematch_id);
+                assert_eq!(proposer_name, "Alice");
+                invited = true;
+            }
+        }
+        assert!(invited);
+
+        let (joined_id, (players, _, _, is_creator, _, _)) = manager.propose_rematch("player-2").await.unwrap();
+        assert_eq!(joined_id, rematch_id);
+        assert!(!is_creator);
+        assert_eq!(players.len(), 2);
+        assert!(!manager.races.read().await.contains_key(&race_id));
+    }
+
+    #[tokio::test]
+    async fn test_race_manager_resume_after_disconnect() {
+        crate::db::init_test().await.unwrap();
+
+        let manager = RaceManager::new();
+        let (tx, _rx) = mpsc::unbounded_channel();
+
+        let (race_id, _, _, _) = manager
+            .create_race(
+                "vg20".to_string(),
+                "player-1".to_string(),
+                "Alice".to_string(),
+                "persistent-1".to_string(),
+                RaceOptions::default(),
+                tx,
+            )
+            .await
+            .unwrap();
+        {
+            let mut races = manager.races.write().await;
+            races.get_mut(&race_id).unwrap().race_start_time = Some(Utc::now().timestamp_millis());
+        }
+
+        // A live connection can't be taken over
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        assert!(
+            manager
+                .resume(&race_id, "player-1", "persistent-1", tx.clone())
+                .await
+                .is_err()
+        );
+
+        // The racer stays in the race once the connection drops
+        manager.disconnect("player-1").await;
+        assert!(
+            manager.races.read().await[&race_id]
+                .players
+                .contains_key("player-1")
+        );
+        assert!(
+            manager
+                .resume(&race_id, "player-1", "persistent-2", tx.clone())
+                .await
+                .is_err()
+        );
+
+        let race_time = manager
+            .resume(&race_id, "player-1", "persistent-1", tx)
+            .await
+            .unwrap();
+        assert!(race_time.is_some());
+        manager.races.read().await[&race_id]
+            .broadcast_all(ServerMessage::SyncRaceTime { race_time: 0 });
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(ServerMessage::SyncRaceTime { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_race_manager_leave_quick_match_queue() {
+        crate::db::init_test().await.unwrap();
+
+        let manager = RaceManager::new();
+        let (tx, _rx) = mpsc::unbounded_channel();
+
+        manager
+            .quick_match("vg20".to_string(), "player-1".to_string(), "Alice".to_string(), "persistent-1".to_string(), None, tx)
+            .await
+            .unwrap();
+        assert_eq!(manager.match_queue.read().await.get("vg20").map(|q| q.len()), Some(1));
+
+        manager.leave_race("player-1").await;
+        assert!(manager.match_queue.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_race_manager_solo_race_is_private() {
+        crate::db::init_test().await.unwrap();
+
+        let manager = RaceManager::new();
+        let (tx1, _rx1) = mpsc::unbounded_channel();
+        let (tx2, _rx2) = mpsc::unbounded_channel();
+
+        let (race_id, _, _, _) = manager
+            .create_race(
+                "vg20".to_string(),
+                "player-1".to_string(),
+                "Alice".to_string(),
+                "persistent-1".to_string(),
+                RaceOptions { solo: true, ..Default::default() },
+                tx1,
+            )
+            .await
+            .unwrap();
+
+        assert!(manager.list_races().await.is_empty());
+
+        let result = manager
+            .join_race(&race_id, "player-2".to_string(), "Bob".to_string(), "persistent-2".to_string(), None, tx2)
+            .await;
+        assert_eq!(result.unwrap_err().to_string(), "Race is private");
+
+        let races = manager.races.read().await;
+        let race = races.get(&race_id).unwrap();
+        assert!(race.solo);
+        assert_eq!(race.players.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_race_manager_simulates_boats() {
+        use crate::polars::Polar;
+        use crate::simulation::RaceWind;
+
+        crate::db::init_test().await.unwrap();
+
+        let manager = RaceManager::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (race_id, _, course_start_time, _) = manager
+            .create_race(
+                "vg20".to_string(),
+                "player-1".to_string(),
+                "Alice".to_string(),
+                "persistent-1".to_string(),
+                RaceOptions::default(),
+                tx,
+            )
+            .await
+            .unwrap();
+
+        // Uniform westerly wind, boat on a beam reach
+        let start = {
+            let mut races = manager.races.write().await;
+            let race = races.get_mut(&race_id).unwrap();
+            race.race_start_time = Some(Utc::now().timestamp_millis());
+            let mut wind = RaceWind::new(vec![(course_start_time - 1, "wind.png".into())]);
+            wind.insert(
+                course_start_time - 1,
+                Arc::new(WindRaster::uniform(255, 128)),
+                course_start_time,
+            );
+            let polar =
+                Polar::from_json(r#"{ "0": { "0": 0, "180": 0 }, "40": { "0": 0, "180": 20 } }"#)
+                    .unwrap();
+            let polars = HashMap::from([(race.course.polar.clone(), polar)]);
+            race.simulation = Some(Simulation {
+                polars,
+                sails: HashMap::new(),
+                wind,
+            });
+            let mut boat = Boat::new(&race.course);
+            boat.heading = 0.0;
+            boat.helm = Helm::Heading(0.0);
+            race.players.get_mut("player-1").unwrap().boat = Some(boat);
+            race.course.start.clone()
+        };
+
+        // Client positions are ignored in simulated races
+        manager
+            .broadcast_position("player-1", 10.0, 10.0, 0.0)
+            .await;
+        manager.set_helm("player-1", Helm::Heading(0.0)).await;
+        // The course polar has no sails
+        assert!(manager.change_sail("player-1", "jib").await.is_err());
+        manager.simulate(1.0).await;
+
+        let races = manager.races.read().await;
+        let player = &races.get(&race_id).unwrap().players["player-1"];
+        let (lng, lat) = player.position.unwrap();
+        assert!((lng - start.lng).abs() < 1e-6);
+        assert!(lat > start.lat);
+        assert_eq!(player.path_history.points.len(), 1);
+        assert_eq!(player.path_history.helm, vec![Helm::Heading(0.0)]);
+
+        // The owner gets the server position too
+        let mut updated = false;
+        while let Ok(message) = rx.try_recv() {
+            if let ServerMessage::PositionUpdate { player_id, .. } = message {
+                assert_eq!(player_id, "player-1");
+                updated = true;
+            }
+        }
+        assert!(updated);
+    }
+
+    #[tokio::test]
+    async fn test_race_manager_waits_for_failed_rasters() {
+        use crate::simulation::RaceWind;
+
+        crate::db::init_test().await.unwrap();
+
+        let manager = RaceManager::new();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let (race_id, _, course_start_time, _) = manager
+            .create_race(
+                "vg20".to_string(),
+                "player-1".to_string(),
+                "Alice".to_string(),
+                "persistent-rasters".to_string(),
+                RaceOptions::default(),
+                tx,
+            )
+            .await
+            .unwrap();
+        {
+            let mut races = manager.races.write().await;
+            let race = races.get_mut(&race_id).unwrap();
+            race.race_start_time = Some(Utc::now().timestamp_millis());
+            race.simulation = Some(Simulation {
+                polars: HashMap::new(),
+                sails: HashMap::new(),
+                wind: RaceWind::new(vec![(course_start_time - 1, "missing.png".into())]),
+            });
+        }
+
+        manager.simulate(1.0).await;
+        let retry_at = manager.failed_rasters.read().await["missing.png"];
+        assert!(retry_at > Instant::now());
+
+        // Not loaded again before its retry
+        manager.simulate(1.0).await;
+        assert_eq!(manager.failed_rasters.read().await["missing.png"], retry_at);
+    }
 }
@@ -4,12 +4,13 @@
 //! streaming and filtering for wind components only.
 
 use crate::grib_stream::{Grib2StreamParser, is_wind_message};
-use crate::retry::{RetryConfig, RetryError, with_retry};
+use crate::retry::{CircuitOpen, RetryConfig, RetryError, with_retry};
 use crate::s3_multipart::S3MultipartUploader;
 use anyhow::Result;
 use chrono::NaiveDate;
 use futures::StreamExt;
-use object_store::aws::AmazonS3;
+use object_store::ObjectStore;
+use sha2::{Digest, Sha256};
 
 /// NCAR THREDDS base URL for GFS 0.25° data (ds084.1 dataset).
 const NCAR_BASE_URL: &str = "https://thredds.rda.ucar.edu/thredds/fileServer/files/g/d084001";
@@ -54,11 +55,12 @@ impl NcarSource {
     /// Uses exponential backoff with jitter for retrying on network errors
     /// and server errors (5xx). Will retry up to MAX_RETRIES times.
     /// Retries cover both the initial connection and mid-stream failures.
+    #[tracing::instrument(skip(self, s3_client))]
     pub async fn download_wind_data(
         &self,
         date: NaiveDate,
         hour: u32,
-        s3_client: &AmazonS3,
+        s3_client: &dyn ObjectStore,
         s3_key: &str,
     ) -> Result<Option<usize>> {
         let url = Self::build_url(date, hour);
@@ -76,7 +78,7 @@ impl NcarSource {
     async fn try_download_wind_data(
         &self,
         url: &str,
-        s3_client: &AmazonS3,
+        s3_client: &dyn ObjectStore,
         s3_key: &str,
     ) -> std::result::Result<Option<usize>, RetryError> {
         // Initiate the HTTP request
@@ -104,7 +106,7 @@ impl NcarSource {
 
         let mut uploader = S3MultipartUploader::new(s3_client, s3_key)
             .await
-            .map_err(|e| RetryError::Retryable(anyhow::anyhow!("S3 upload init failed: {}", e)))?;
+            .map_err(|e| s3_error("S3 upload init failed", e))?;
 
         let mut parser = Grib2StreamParser::new();
         let mut stream = response.bytes_stream();
@@ -125,9 +127,10 @@ impl NcarSource {
                 for msg in messages {
                     // Filter for wind messages only
                     if is_wind_message(&msg) {
-                        uploader.write(&msg).await.map_err(|e| {
-                            RetryError::Retryable(anyhow::anyhow!("S3 write failed: {}", e))
-                        })?;
+                        uploader
+                            .write(&msg)
+                            .await
+                            .map_err(|e| s3_error("S3 write failed", e))?;
                         total_uploaded += msg.len();
                     }
                 }
@@ -147,12 +150,12 @@ impl NcarSource {
             uploader
                 .complete()
                 .await
-                .map_err(|e| RetryError::Retryable(anyhow::anyhow!("S3 complete failed: {}", e)))?;
+                .map_err(|e| s3_error("S3 complete failed", e))?;
         } else {
             uploader
                 .abort()
                 .await
-                .map_err(|e| RetryError::Retryable(anyhow::anyhow!("S3 abort failed: {}", e)))?;
+                .map_err(|e| s3_error("S3 abort failed", e))?;
         }
 
         Ok(Some(total_uploaded))
@@ -165,6 +168,15 @@ impl Default for NcarSource {
     }
 }
 
+/// Storage failures are retried, unless the storage is known to be down
+fn s3_error(what: &str, e: anyhow::Error) -> RetryError {
+    if e.is::<CircuitOpen>() {
+        RetryError::NonRetryable(e.context(what.to_string()))
+    } else {
+        RetryError::Retryable(anyhow::anyhow!("{}: {}", what, e))
+    }
+}
+
 /// S3 path for NCAR GRIB files (filtered wind data).
 ///
 /// Path structure: `ncar/{year}/{mmdd}/{hour}/wind.grib2`
@@ -172,11 +184,17 @@ pub fn ncar_grib_path(day: NaiveDate, hour: u32) -> String {
     format!("ncar/{}/{}/wind.grib2", day.format("%Y/%m%d"), hour)
 }
 
-/// S3 path for NCAR UV PNG rasters.
+/// S3 path for NCAR UV PNG rasters, named after a hash of their content so
+/// that they can be cached forever and still be replaced.
 ///
-/// Path structure: `ncar/{year}/{mmdd}/{hour}/uv.png`
-pub fn ncar_raster_path(day: NaiveDate, hour: u32) -> String {
-    format!("ncar/{}/{}/uv.png", day.format("%Y/%m%d"), hour)
+/// Path structure: `ncar/{year}/{mmdd}/{hour}/uv-{hash}.png`, rasters stored
+/// before hashing being `uv.png`
+pub fn ncar_raster_path(day: NaiveDate, hour: u32, png: &[u8]) -> String {
+    let hash: String = Sha256::digest(png)[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("ncar/{}/{}/uv-{}.png", day.format("%Y/%m%d"), hour, hash)
 }
 
 #[cfg(test)]
@@ -220,7 +238,8 @@ mod tests {
     #[test]
     fn test_ncar_raster_path() {
         let day = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
-        let path = ncar_raster_path(day, 6);
-        assert_eq!(path, "ncar/2024/0115/6/uv.png");
+        let path = ncar_raster_path(day, 6, b"png");
+        assert_eq!(path, "ncar/2024/0115/6/uv-8f8cbb7dcf46e0bc.png");
+        assert_ne!(ncar_raster_path(day, 6, b"other png"), path);
     }
 }
@@ -0,0 +1,383 @@
+//! Sign in with Google or GitHub (OAuth 2 authorization code flow), next to
+//! the email codes of `auth.rs`.
+//!
+//! Provider accounts are linked to Re:wind accounts by their verified email
+//! on first sign in, then by provider user id.
+
+use anyhow::{Result, anyhow};
+use rand::Rng;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::config::{OAuthClient, config};
+use crate::{auth, db};
+
+const STATE_EXPIRATION_MS: i64 = 10 * 60 * 1000; // 10 minutes
+
+/// Provider APIs reject requests without a user agent (GitHub)
+const USER_AGENT: &str = "rewind";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    Google,
+    GitHub,
+}
+
+impl Provider {
+    pub const ALL: [Provider; 2] = [Provider::Google, Provider::GitHub];
+
+    pub fn parse(name: &str) -> Option<Provider> {
+        Provider::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Provider::Google => "google",
+            Provider::GitHub => "github",
+        }
+    }
+
+    fn client(&self) -> Option<&'static OAuthClient> {
+        match self {
+            Provider::Google => config().google_oauth.as_ref(),
+            Provider::GitHub => config().github_oauth.as_ref(),
+        }
+    }
+
+    fn authorize_url(&self) -> &'static str {
+        match self {
+            Provider::Google => "https://accounts.google.com/o/oauth2/v2/auth",
+            Provider::GitHub => "https://github.com/login/oauth/authorize",
+        }
+    }
+
+    fn token_url(&self) -> &'static str {
+        match self {
+            Provider::Google => "https://oauth2.googleapis.com/token",
+            Provider::GitHub => "https://github.com/login/oauth/access_token",
+        }
+    }
+
+    /// Scopes reading the user id and email addresses
+    fn scope(&self) -> &'static str {
+        match self {
+            Provider::Google => "openid email",
+            Provider::GitHub => "read:user user:email",
+        }
+    }
+
+    /// Where the provider sends the user back, registered with the OAuth app
+    fn redirect_uri(&self) -> String {
+        format!(
+            "{}/auth/oauth/{}/callback",
+            config().public_url,
+            self.name()
+        )
+    }
+}
+
+/// Provider redirect back to the server, with an error when the user declined
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Providers with credentials, offered to players
+pub fn enabled() -> Vec<&'static str> {
+    Provider::ALL
+        .into_iter()
+        .filter(|p| p.client().is_some())
+        .map(|p| p.name())
+        .collect()
+}
+
+/// Start a sign in: the provider page to send the user to, with a new state.
+pub async fn authorize_url(provider: Provider) -> Result<String> {
+    let client = provider
+        .client()
+        .ok_or_else(|| anyhow!("{} sign in is not configured", provider.name()))?;
+
+    let state = generate_state();
+    let expires_at = chrono::Utc::now().timestamp_millis() + STATE_EXPIRATION_MS;
+    sqlx::query("INSERT INTO oauth_states (state, provider, expires_at) VALUES ($1, $2, $3)")
+        .bind(&state)
+        .bind(provider.name())
+        .bind(expires_at)
+        .execute(db::pool())
+        .await?;
+
+    let url = Url::parse_with_params(
+        provider.authorize_url(),
+        &[
+            ("client_id", client.client_id.as_str()),
+            ("redirect_uri", provider.redirect_uri().as_str()),
+            ("response_type", "code"),
+            ("scope", provider.scope()),
+            ("state", state.as_str()),
+        ],
+    )?;
+    Ok(url.to_string())
+}
+
+/// Finish a sign in from the provider callback: check the state, get the
+/// user email from the provider and create a session for `user_agent`.
+pub async fn callback(
+    provider: Provider,
+    code: &str,
+    state: &str,
+    user_agent: Option<&str>,
+) -> Result<auth::AuthResult> {
+    let client = provider
+        .client()
+        .ok_or_else(|| anyhow!("{} sign in is not configured", provider.name()))?;
+    consume_state(provider, state).await?;
+
+    let token = exchange_code(provider, client, code).await?;
+    let identity = fetch_identity(provider, &token).await?;
+    let account_id = link_account(provider, &identity).await?;
+    auth::create_session(&account_id, user_agent).await
+}
+
+/// Generate an unguessable state, binding the callback to this server.
+fn generate_state() -> String {
+    let bytes: [u8; 16] = rand::rng().random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Use up a state, failing if it is unknown, expired or already used.
+async fn consume_state(provider: Provider, state: &str) -> Result<()> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let deleted = sqlx::query(
+        "DELETE FROM oauth_states WHERE state = $1 AND provider = $2 AND expires_at > $3",
+    )
+    .bind(state)
+    .bind(provider.name())
+    .bind(now)
+    .execute(db::pool())
+    .await?
+    .rows_affected();
+
+    if deleted == 0 {
+        anyhow::bail!("Sign in expired, please try again");
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Trade the authorization code for an access token.
+async fn exchange_code(provider: Provider, client: &OAuthClient, code: &str) -> Result<String> {
+    let response: TokenResponse = reqwest::Client::new()
+        .post(provider.token_url())
+        .header("Accept", "application/json")
+        .form(&[
+            ("client_id", client.client_id.as_str()),
+            ("client_secret", client.client_secret.as_str()),
+            ("code", code),
+            ("redirect_uri", provider.redirect_uri().as_str()),
+            ("grant_type", "authorization_code"),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(response.access_token)
+}
+
+/// A provider account: its user id and verified email
+#[derive(Debug, PartialEq)]
+struct Identity {
+    subject: String,
+    email: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GoogleUser {
+    sub: String,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubUser {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+/// The primary email of a GitHub user, if verified
+fn github_email(emails: &[GitHubEmail]) -> Option<String> {
+    emails
+        .iter()
+        .find(|e| e.primary && e.verified)
+        .map(|e| e.email.clone())
+}
+
+async fn fetch_identity(provider: Provider, token: &str) -> Result<Identity> {
+    let http = reqwest::Client::new();
+    let get = |url: &str| {
+        http.get(url)
+            .bearer_auth(token)
+            .header("User-Agent", USER_AGENT)
+            .header("Accept", "application/json")
+    };
+
+    let (subject, email) = match provider {
+        Provider::Google => {
+            let user: GoogleUser = get("https://openidconnect.googleapis.com/v1/userinfo")
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let email = user.email.filter(|_| user.email_verified);
+            (user.sub, email)
+        }
+        Provider::GitHub => {
+            let user: GitHubUser = get("https://api.github.com/user")
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let emails: Vec<GitHubEmail> = get("https://api.github.com/user/emails")
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            (user.id.to_string(), github_email(&emails))
+        }
+    };
+
+    let email = email.ok_or_else(|| anyhow!("Your {} email is not verified", provider.name()))?;
+    Ok(Identity {
+        subject,
+        email: email.to_lowercase().trim().to_string(),
+    })
+}
+
+/// Account of a provider identity: the one it was linked to, else the account
+/// of its email (created if needed), linked from now on.
+async fn link_account(provider: Provider, identity: &Identity) -> Result<String> {
+    let linked: Option<(String,)> = sqlx::query_as(
+        "SELECT account_id FROM account_identities WHERE provider = $1 AND subject = $2",
+    )
+    .bind(provider.name())
+    .bind(&identity.subject)
+    .fetch_optional(db::pool())
+    .await?;
+    if let Some((account_id,)) = linked {
+        return Ok(account_id);
+    }
+
+    let account_id = auth::get_or_create_account(&identity.email).await?;
+    sqlx::query(
+        "INSERT INTO account_identities (provider, subject, account_id, email) VALUES ($1, $2, $3, $4)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(provider.name())
+    .bind(&identity.subject)
+    .bind(&account_id)
+    .bind(&identity.email)
+    .execute(db::pool())
+    .await?;
+    log::info!(
+        "Linked {} account {} to account {}",
+        provider.name(),
+        identity.subject,
+        account_id
+    );
+    Ok(account_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_email() {
+        let email = |address: &str, primary, verified| GitHubEmail {
+            email: address.to_string(),
+            primary,
+            verified,
+        };
+        let emails = vec![
+            email("other@example.com", false, true),
+            email("main@example.com", true, true),
+        ];
+        assert_eq!(github_email(&emails).as_deref(), Some("main@example.com"));
+        assert_eq!(
+            github_email(&[email("main@example.com", true, false)]),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_link_account() {
+        db::init_test().await.unwrap();
+
+        let email = "oauth@example.com";
+        let existing = auth::get_or_create_account(email).await.unwrap();
+
+        // Linked to the account of the email on first sign in
+        let github = Identity {
+            subject: "42".to_string(),
+            email: email.to_string(),
+        };
+        assert_eq!(
+            link_account(Provider::GitHub, &github).await.unwrap(),
+            existing
+        );
+
+        // Then by user id, even once the provider email changes
+        let renamed = Identity {
+            subject: "42".to_string(),
+            email: "renamed@example.com".to_string(),
+        };
+        assert_eq!(
+            link_account(Provider::GitHub, &renamed).await.unwrap(),
+            existing
+        );
+
+        // The same user id of another provider is another identity
+        let google = Identity {
+            subject: "42".to_string(),
+            email: "google@example.com".to_string(),
+        };
+        assert_ne!(
+            link_account(Provider::Google, &google).await.unwrap(),
+            existing
+        );
+    }
+
+    #[tokio::test]
+    async fn test_state_is_used_once() {
+        db::init_test().await.unwrap();
+
+        let state = generate_state();
+        sqlx::query("INSERT INTO oauth_states (state, provider, expires_at) VALUES ($1, $2, $3)")
+            .bind(&state)
+            .bind("google")
+            .bind(i64::MAX)
+            .execute(db::pool())
+            .await
+            .unwrap();
+
+        assert!(consume_state(Provider::GitHub, &state).await.is_err());
+        assert!(consume_state(Provider::Google, &state).await.is_ok());
+        assert!(consume_state(Provider::Google, &state).await.is_err());
+    }
+}
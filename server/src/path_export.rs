@@ -0,0 +1,170 @@
+//! Export of recorded race paths to GPX and TCX, to load virtual races in
+//! chart plotters and analysis tools. Timestamps are course times: the real
+//! dates of the weather the race was sailed in.
+
+use chrono::{DateTime, SecondsFormat};
+use serde::Deserialize;
+
+use crate::units::METERS_PER_NM;
+use crate::{courses::LngLat, geo, race_results::PathPoint};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// GPS Exchange Format track
+    #[default]
+    Gpx,
+    /// Garmin Training Center activity
+    Tcx,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Gpx => "application/gpx+xml",
+            ExportFormat::Tcx => "application/vnd.garmin.tcx+xml",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Gpx => "gpx",
+            ExportFormat::Tcx => "tcx",
+        }
+    }
+
+    /// Document of a path named `name`
+    pub fn render(&self, name: &str, points: &[PathPoint]) -> String {
+        match self {
+            ExportFormat::Gpx => to_gpx(name, points),
+            ExportFormat::Tcx => to_tcx(name, points),
+        }
+    }
+}
+
+fn timestamp(time: i64) -> String {
+    DateTime::from_timestamp_millis(time)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Path as a GPX 1.1 track with a timestamp on each point
+pub fn to_gpx(name: &str, points: &[PathPoint]) -> String {
+    let mut lines = vec![
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+        r#"<gpx version="1.1" creator="Rewind" xmlns="http://www.topografix.com/GPX/1/1">"#
+            .to_string(),
+        format!("<trk><name>{}</name><trkseg>", escape(name)),
+    ];
+    for point in points {
+        lines.push(format!(
+            r#"<trkpt lat="{:.6}" lon="{:.6}"><time>{}</time></trkpt>"#,
+            point.lat,
+            point.lng,
+            timestamp(point.race_time)
+        ));
+    }
+    lines.push("</trkseg></trk>".to_string());
+    lines.push("</gpx>\n".to_string());
+    lines.join("\n")
+}
+
+/// Path as a TCX activity of a single lap, with the distance sailed at each
+/// point
+pub fn to_tcx(name: &str, points: &[PathPoint]) -> String {
+    let mut distance = 0.0;
+    let mut previous: Option<LngLat> = None;
+    let mut trackpoints = Vec::with_capacity(points.len());
+    for point in points {
+        let position = LngLat {
+            lng: point.lng as f64,
+            lat: point.lat as f64,
+        };
+        if let Some(previous) = &previous {
+            distance += geo::distance_nm(previous, &position) * METERS_PER_NM;
+        }
+        trackpoints.push(format!(
+            "<Trackpoint><Time>{}</Time><Position><LatitudeDegrees>{:.6}</LatitudeDegrees>\
+             <LongitudeDegrees>{:.6}</LongitudeDegrees></Position>\
+             <DistanceMeters>{:.1}</DistanceMeters></Trackpoint>",
+            timestamp(point.race_time),
+            point.lat,
+            point.lng,
+            distance
+        ));
+        previous = Some(position);
+    }
+
+    let start = points.first().map_or(0, |p| p.race_time);
+    let end = points.last().map_or(0, |p| p.race_time);
+    let mut lines = vec![
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+        r#"<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">"#
+            .to_string(),
+        r#"<Activities><Activity Sport="Other">"#.to_string(),
+        format!("<Id>{}</Id>", timestamp(start)),
+        format!(r#"<Lap StartTime="{}">"#, timestamp(start)),
+        format!(
+            "<TotalTimeSeconds>{:.3}</TotalTimeSeconds>",
+            (end - start) as f64 / 1000.0
+        ),
+        format!("<DistanceMeters>{:.1}</DistanceMeters>", distance),
+        "<Intensity>Active</Intensity><TriggerMethod>Manual</TriggerMethod>".to_string(),
+        "<Track>".to_string(),
+    ];
+    lines.extend(trackpoints);
+    lines.push("</Track>".to_string());
+    lines.push(format!("<Notes>{}</Notes>", escape(name)));
+    lines.push("</Lap></Activity></Activities>".to_string());
+    lines.push("</TrainingCenterDatabase>\n".to_string());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path() -> Vec<PathPoint> {
+        vec![
+            PathPoint {
+                race_time: 1_604_846_040_000,
+                lng: -1.8,
+                lat: 46.5,
+                heading: 270.0,
+            },
+            PathPoint {
+                race_time: 1_604_849_640_000,
+                lng: -1.8,
+                lat: 46.4,
+                heading: 180.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_gpx() {
+        let gpx = to_gpx("Alice & Bob", &path());
+        assert!(gpx.contains("<name>Alice &amp; Bob</name>"));
+        assert!(gpx.contains(
+            "<trkpt lat=\"46.500000\" lon=\"-1.800000\"><time>2020-11-08T14:34:00.000Z</time></trkpt>"
+        ));
+        assert_eq!(gpx.matches("<trkpt").count(), 2);
+    }
+
+    #[test]
+    fn test_tcx() {
+        let tcx = to_tcx("Alice", &path());
+        assert!(tcx.contains("<Id>2020-11-08T14:34:00.000Z</Id>"));
+        assert!(tcx.contains("<TotalTimeSeconds>3600.000</TotalTimeSeconds>"));
+        // 0.1° of latitude is 6 nm
+        assert!(tcx.contains("<DistanceMeters>11119."));
+        assert_eq!(tcx.matches("<Trackpoint>").count(), 2);
+    }
+}
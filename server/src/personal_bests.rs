@@ -0,0 +1,234 @@
+//! Personal bests and progression of profiles (or guest player ids).
+//!
+//! Bests are kept per course and boat class, over the results that would
+//! enter the Hall of Fame: finished, outside the daily challenge and in the
+//! course weather.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::db;
+
+/// Results comparable with each other on a course, as in the Hall of Fame
+const ELIGIBLE: &str = "status = 'finished' AND daily_date IS NULL AND weather_start_time IS NULL";
+
+/// Best time of a profile on a course with a boat class
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonalBest {
+    pub course_key: String,
+    pub polar: String,
+    pub best_time: i64,
+    /// Result of the best time, for its replay
+    pub result_id: i64,
+    pub race_date: i64,
+    /// Time of the first finish, to measure the improvement since
+    pub first_time: i64,
+    pub finishes: u32,
+}
+
+/// A result that set a new personal best
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Improvement {
+    pub course_key: String,
+    pub polar: String,
+    pub finish_time: i64,
+    pub race_date: i64,
+    /// Best time it beat, None on the first finish
+    pub previous_best: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileStats {
+    pub profile_id: String,
+    pub name: String,
+    /// Every stored result, whatever its status
+    pub race_count: i64,
+    pub finished_count: i64,
+    /// By course key, then boat class
+    pub personal_bests: Vec<PersonalBest>,
+    /// New personal bests, oldest first
+    pub progression: Vec<Improvement>,
+}
+
+/// Best time of a profile on a course with a boat class, None before its
+/// first finish
+pub async fn best_time(profile_id: &str, course_key: &str, polar: &str) -> Result<Option<i64>> {
+    let sql = format!(
+        "SELECT MIN(finish_time) FROM race_results
+         WHERE player_id = $1 AND course_key = $2 AND polar = $3 AND {}",
+        ELIGIBLE
+    );
+    let (best,): (Option<i64>,) = sqlx::query_as(&sql)
+        .bind(profile_id)
+        .bind(course_key)
+        .bind(polar)
+        .fetch_one(db::pool())
+        .await?;
+    Ok(best)
+}
+
+/// Stats of a profile, None when it has neither a profile nor results
+pub async fn stats(profile_id: &str) -> Result<Option<ProfileStats>> {
+    // Guests have no profile, their name is the one of their last result
+    let name: Option<(String,)> = sqlx::query_as(
+        "SELECT name FROM profiles WHERE id = $1
+         UNION ALL
+         SELECT player_name FROM (
+             SELECT player_name FROM race_results WHERE player_id = $2
+             ORDER BY race_start_time DESC LIMIT 1
+         ) AS last_result
+         LIMIT 1",
+    )
+    .bind(profile_id)
+    .bind(profile_id)
+    .fetch_optional(db::pool())
+    .await?;
+    let Some((name,)) = name else {
+        return Ok(None);
+    };
+
+    let (race_count, finished_count): (i64, i64) = sqlx::query_as(
+        "SELECT COUNT(*), COALESCE(SUM(CASE WHEN status = 'finished' THEN 1 ELSE 0 END), 0)
+         FROM race_results WHERE player_id = $1",
+    )
+    .bind(profile_id)
+    .fetch_one(db::pool())
+    .await?;
+
+    let sql = format!(
+        "SELECT id, course_key, polar, finish_time, race_start_time
+         FROM race_results
+         WHERE player_id = $1 AND {}
+         ORDER BY race_start_time, id",
+        ELIGIBLE
+    );
+    let rows: Vec<(i64, String, String, i64, i64)> = sqlx::query_as(&sql)
+        .bind(profile_id)
+        .fetch_all(db::pool())
+        .await?;
+    let (personal_bests, progression) = progress(rows);
+
+    Ok(Some(ProfileStats {
+        profile_id: profile_id.to_string(),
+        name,
+        race_count,
+        finished_count,
+        personal_bests,
+        progression,
+    }))
+}
+
+/// Personal bests and their improvements from finishes in race order
+fn progress(rows: Vec<(i64, String, String, i64, i64)>) -> (Vec<PersonalBest>, Vec<Improvement>) {
+    let mut bests: HashMap<(String, String), PersonalBest> = HashMap::new();
+    let mut progression = Vec::new();
+
+    for (result_id, course_key, polar, finish_time, race_date) in rows {
+        let key = (course_key.clone(), polar.clone());
+        let previous_best = bests.get(&key).map(|best| best.best_time);
+        let best = bests.entry(key).or_insert_with(|| PersonalBest {
+            course_key: course_key.clone(),
+            polar: polar.clone(),
+            best_time: finish_time,
+            result_id,
+            race_date,
+            first_time: finish_time,
+            finishes: 0,
+        });
+        best.finishes += 1;
+
+        if previous_best.is_none_or(|previous| finish_time < previous) {
+            best.best_time = finish_time;
+            best.result_id = result_id;
+            best.race_date = race_date;
+            progression.push(Improvement {
+                course_key,
+                polar,
+                finish_time,
+                race_date,
+                previous_best,
+            });
+        }
+    }
+
+    let mut personal_bests: Vec<PersonalBest> = bests.into_values().collect();
+    personal_bests.sort_by(|a, b| (&a.course_key, &a.polar).cmp(&(&b.course_key, &b.polar)));
+    (personal_bests, progression)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::race_results::{self, NewRaceResult, ResultStatus};
+
+    #[test]
+    fn test_progress() {
+        let row = |id: i64, course: &str, time: i64| {
+            (id, course.to_string(), "imoca".to_string(), time, id * 1000)
+        };
+        let (bests, progression) = progress(vec![
+            row(1, "vg20", 5000),
+            row(2, "vg20", 6000),
+            row(3, "mt23", 900),
+            row(4, "vg20", 4000),
+        ]);
+
+        assert_eq!(bests.len(), 2);
+        assert_eq!(bests[1].course_key, "vg20");
+        assert_eq!(bests[1].best_time, 4000);
+        assert_eq!(bests[1].first_time, 5000);
+        assert_eq!(bests[1].result_id, 4);
+        assert_eq!(bests[1].finishes, 3);
+
+        let times: Vec<i64> = progression.iter().map(|i| i.finish_time).collect();
+        assert_eq!(times, vec![5000, 900, 4000]);
+        assert_eq!(progression[2].previous_best, Some(5000));
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        db::init_test().await.unwrap();
+
+        for (time, status, daily_date) in [
+            (3000, ResultStatus::Finished, None),
+            (2000, ResultStatus::Finished, None),
+            (1000, ResultStatus::Finished, Some("2001-01-01")),
+            (500, ResultStatus::Retired, None),
+        ] {
+            race_results::save_result(&NewRaceResult {
+                race_id: "PB001".to_string(),
+                course_key: "pb-test".to_string(),
+                player_name: "Best".to_string(),
+                player_id: "pb-player".to_string(),
+                finish_time: time,
+                race_start_time: 1_700_000_000_000 - time,
+                path_s3_key: "paths/test.bin".to_string(),
+                status,
+                daily_date: daily_date.map(str::to_string),
+                weather_start_time: None,
+                polar: "imoca".to_string(),
+                splits: vec![],
+                stats: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let player = stats("pb-player").await.unwrap().unwrap();
+        assert_eq!(player.name, "Best");
+        assert_eq!(player.race_count, 4);
+        assert_eq!(player.finished_count, 3);
+        assert_eq!(player.personal_bests.len(), 1);
+        assert_eq!(player.personal_bests[0].best_time, 2000);
+        assert_eq!(
+            best_time("pb-player", "pb-test", "imoca").await.unwrap(),
+            Some(2000)
+        );
+        assert!(stats("pb-nobody").await.unwrap().is_none());
+    }
+}
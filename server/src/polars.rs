@@ -0,0 +1,516 @@
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::db;
+
+/// Boat speed table: TWS (knots) × TWA (degrees, 0-180) → boat speed (knots)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polar {
+    tws_values: Vec<f64>,
+    twa_values: Vec<f64>,
+    /// `speeds[tws_index][twa_index]`
+    speeds: Vec<Vec<f64>>,
+    pub foiling: Option<Foiling>,
+}
+
+/// Foiling regime of a polar: from `min_tws` and between `min_twa` and
+/// `max_twa`, the boat lifts on its foils and gains up to `boost` of its polar
+/// speed. From `wave_tws` the sea makes the flight unstable, losing up to
+/// `instability` of the polar speed over each swell.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Foiling {
+    pub min_tws: f64,
+    pub min_twa: f64,
+    pub max_twa: f64,
+    pub boost: f64,
+    pub wave_tws: f64,
+    pub instability: f64,
+}
+
+/// Wind over which the foils lift fully past `min_tws`, in knots
+const FOIL_RAMP_KNOTS: f64 = 4.0;
+
+/// Wind over which waves build up past `wave_tws`, in knots
+const WAVE_RAMP_KNOTS: f64 = 10.0;
+
+/// Swell period, in seconds of real time
+const WAVE_PERIOD_SECONDS: f64 = 5.0;
+
+/// Polar foiling defaults, by polar name, in the polars directory
+const FOILING_FILE: &str = "foiling.json";
+
+/// Table format served to clients: `{ "<tws>": { "<twa>": <bsp>, ... }, ... }`
+pub type PolarTable = BTreeMap<String, BTreeMap<String, f64>>;
+
+/// TWA folded to 0-180: polars are symmetric
+fn fold_twa(twa: f64) -> f64 {
+    let twa = twa.abs() % 360.0;
+    if twa > 180.0 { 360.0 - twa } else { twa }
+}
+
+fn parse_number(value: &str) -> Result<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| anyhow!("Invalid polar value: {}", value))
+}
+
+impl Polar {
+    /// Build a polar from its rows: `(tws, [(twa, bsp), ...])` in any order
+    fn from_rows(mut rows: Vec<(f64, Vec<(f64, f64)>)>) -> Result<Polar> {
+        for (_, cells) in rows.iter_mut() {
+            cells.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+        rows.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let twa_values: Vec<f64> = rows
+            .first()
+            .ok_or(anyhow!("Empty polar"))?
+            .1
+            .iter()
+            .map(|(twa, _)| *twa)
+            .collect();
+        if twa_values.is_empty() {
+            return Err(anyhow!("Empty polar"));
+        }
+        if rows.iter().any(|(_, cells)| {
+            cells
+                .iter()
+                .map(|(twa, _)| *twa)
+                .ne(twa_values.iter().copied())
+        }) {
+            return Err(anyhow!("All polar rows must have the same TWA values"));
+        }
+
+        Ok(Polar {
+            tws_values: rows.iter().map(|(tws, _)| *tws).collect(),
+            twa_values,
+            speeds: rows
+                .into_iter()
+                .map(|(_, cells)| cells.into_iter().map(|(_, bsp)| bsp).collect())
+                .collect(),
+            foiling: None,
+        })
+    }
+
+    /// Parse a polar in the JSON table format served to clients
+    pub fn from_json(json: &str) -> Result<Polar> {
+        let table: PolarTable = serde_json::from_str(json)?;
+        let rows = table
+            .iter()
+            .map(|(tws, row)| {
+                let cells = row
+                    .iter()
+                    .map(|(twa, bsp)| Ok((parse_number(twa)?, *bsp)))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok((parse_number(tws)?, cells))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Polar::from_rows(rows)
+    }
+
+    /// Parse a standard polar file: a header row `TWA\TWS;<tws>;...` then one
+    /// row per TWA `<twa>;<bsp>;...`. Fields are separated by semicolons, tabs
+    /// or commas.
+    pub fn from_pol(text: &str) -> Result<Polar> {
+        let mut lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        let split = |line: &str| -> Vec<String> {
+            line.split([';', '\t', ','])
+                .map(|field| field.trim().to_string())
+                .collect()
+        };
+
+        let header = split(lines.next().ok_or(anyhow!("Empty polar"))?);
+        let tws_values = header[1..]
+            .iter()
+            .map(|tws| parse_number(tws))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut rows: Vec<(f64, Vec<(f64, f64)>)> =
+            tws_values.iter().map(|tws| (*tws, Vec::new())).collect();
+        for line in lines {
+            let fields = split(line);
+            if fields.len() != header.len() {
+                return Err(anyhow!("Polar row doesn't match header: {}", line));
+            }
+            let twa = parse_number(&fields[0])?;
+            for (row, bsp) in rows.iter_mut().zip(&fields[1..]) {
+                row.1.push((twa, parse_number(bsp)?));
+            }
+        }
+        Polar::from_rows(rows)
+    }
+
+    /// Table format served to clients
+    pub fn to_table(&self) -> PolarTable {
+        self.tws_values
+            .iter()
+            .zip(&self.speeds)
+            .map(|(tws, speeds)| {
+                let row = self
+                    .twa_values
+                    .iter()
+                    .zip(speeds)
+                    .map(|(twa, bsp)| (twa.to_string(), *bsp))
+                    .collect();
+                (tws.to_string(), row)
+            })
+            .collect()
+    }
+
+    /// Boat speed in knots, bilinear interpolation clamped to the table bounds.
+    /// The polar is symmetric: the sign of `twa` is ignored.
+    pub fn speed(&self, tws: f64, twa: f64) -> f64 {
+        let twa = fold_twa(twa);
+        let (i0, i1, tws_frac) = bracket(&self.tws_values, tws);
+        let (j0, j1, twa_frac) = bracket(&self.twa_values, twa);
+
+        let low = lerp(self.speeds[i0][j0], self.speeds[i0][j1], twa_frac);
+        let high = lerp(self.speeds[i1][j0], self.speeds[i1][j1], twa_frac);
+        lerp(low, high, tws_frac)
+    }
+
+    /// Boat speed in knots including the foiling regime, `clock` seconds of
+    /// real time into the race
+    pub fn boat_speed(&self, tws: f64, twa: f64, clock: f64) -> f64 {
+        let factor = self
+            .foiling
+            .as_ref()
+            .map_or(1.0, |foiling| foiling.factor(tws, twa, clock));
+        self.speed(tws, twa) * factor
+    }
+}
+
+impl Foiling {
+    pub fn validate(&self) -> Result<()> {
+        if self.min_tws < 0.0 || self.wave_tws < 0.0 {
+            return Err(anyhow!("Wind thresholds must be positive"));
+        }
+        if !(0.0..=180.0).contains(&self.min_twa)
+            || !(0.0..=180.0).contains(&self.max_twa)
+            || self.min_twa > self.max_twa
+        {
+            return Err(anyhow!("Foiling TWA range must be within 0-180"));
+        }
+        if self.boost < 0.0 || !(0.0..=1.0).contains(&self.instability) {
+            return Err(anyhow!("Boost must be positive and instability within 0-1"));
+        }
+        Ok(())
+    }
+
+    /// Factor of the polar speed at `clock` seconds of real time
+    pub fn factor(&self, tws: f64, twa: f64, clock: f64) -> f64 {
+        let twa = fold_twa(twa);
+        if tws < self.min_tws || twa < self.min_twa || twa > self.max_twa {
+            return 1.0;
+        }
+        let lift = ((tws - self.min_tws) / FOIL_RAMP_KNOTS).min(1.0);
+        let waves = ((tws - self.wave_tws) / WAVE_RAMP_KNOTS).clamp(0.0, 1.0);
+        let swell = 0.5 + 0.5 * (std::f64::consts::TAU * clock / WAVE_PERIOD_SECONDS).sin();
+        1.0 + self.boost * lift - self.instability * waves * swell
+    }
+}
+
+/// Indices of the values surrounding `x` and its fraction between them,
+/// clamped to the first and last values
+fn bracket(values: &[f64], x: f64) -> (usize, usize, f64) {
+    let last = values.len() - 1;
+    if x <= values[0] {
+        return (0, 0, 0.0);
+    }
+    if x >= values[last] {
+        return (last, last, 0.0);
+    }
+    let i = values.partition_point(|&v| v <= x) - 1;
+    let (low, high) = (values[i], values[i + 1]);
+    (i, i + 1, (x - low) / (high - low))
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Get a polar by name (the `Course.polar` key), with its foiling regime
+pub async fn get(name: &str) -> Result<Option<Polar>> {
+    let row: Option<(String, Option<String>)> =
+        sqlx::query_as("SELECT data, foiling FROM polars WHERE name = $1")
+            .bind(name)
+            .fetch_optional(db::pool())
+            .await?;
+    row.map(|(data, foiling)| {
+        let mut polar = Polar::from_json(&data)?;
+        polar.foiling = foiling.map(|f| serde_json::from_str(&f)).transpose()?;
+        Ok(polar)
+    })
+    .transpose()
+}
+
+/// Set or clear the foiling regime of a polar. Returns false if the polar doesn't exist.
+pub async fn set_foiling(name: &str, foiling: Option<&Foiling>) -> Result<bool> {
+    if let Some(foiling) = foiling {
+        foiling.validate()?;
+    }
+    let result = sqlx::query("UPDATE polars SET foiling = $1, updated_at = $2 WHERE name = $3")
+        .bind(foiling.map(serde_json::to_string).transpose()?)
+        .bind(Utc::now().timestamp_millis())
+        .bind(name)
+        .execute(db::pool())
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Get the stored JSON table of a polar
+pub async fn get_table(name: &str) -> Result<Option<String>> {
+    let row: Option<(String,)> = sqlx::query_as("SELECT data FROM polars WHERE name = $1")
+        .bind(name)
+        .fetch_optional(db::pool())
+        .await?;
+    Ok(row.map(|(data,)| data))
+}
+
+/// Name of the polar of a boat class sail: `<class>.<sail>`
+pub fn sail_polar_name(class: &str, sail: &str) -> String {
+    format!("{}.{}", class, sail)
+}
+
+/// Sail inventory of a boat class, ordered by sail name, with the polar of
+/// each sail. Classes without sail polars sail on their own polar.
+pub async fn sails(class: &str) -> Result<Vec<(String, Polar)>> {
+    let prefix = sail_polar_name(class, "");
+    let rows: Vec<(String, String)> = sqlx::query_as(
+        "SELECT name, data FROM polars WHERE substr(name, 1, $1) = $2 ORDER BY name",
+    )
+    // An INTEGER length: Postgres has no substr of a BIGINT one
+    .bind(prefix.len() as i32)
+    .bind(&prefix)
+    .fetch_all(db::pool())
+    .await?;
+    rows.into_iter()
+        .map(|(name, data)| Ok((name[prefix.len()..].to_string(), Polar::from_json(&data)?)))
+        .collect()
+}
+
+/// Names of all stored polars
+pub async fn list() -> Result<Vec<String>> {
+    let rows: Vec<(String,)> = sqlx::query_as("SELECT name FROM polars ORDER BY name")
+        .fetch_all(db::pool())
+        .await?;
+    Ok(rows.into_iter().map(|(name,)| name).collect())
+}
+
+/// Insert or replace a polar
+pub async fn save(name: &str, polar: &Polar) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO polars (name, data, updated_at) VALUES ($1, $2, $3)
+         ON CONFLICT(name) DO UPDATE SET data = excluded.data, updated_at = excluded.updated_at",
+    )
+    .bind(name)
+    .bind(serde_json::to_string(&polar.to_table())?)
+    .bind(Utc::now().timestamp_millis())
+    .execute(db::pool())
+    .await?;
+    Ok(())
+}
+
+/// Parse a polar file by extension: `.json` tables or `.pol`/`.csv`/`.txt`
+/// standard files
+fn parse_file(path: &Path, text: &str) -> Option<Result<Polar>> {
+    match path.extension()?.to_str()? {
+        "json" => Some(Polar::from_json(text)),
+        "pol" | "csv" | "txt" => Some(Polar::from_pol(text)),
+        _ => None,
+    }
+}
+
+/// Store the polar files of a directory that are not in the database yet,
+/// keyed by file stem. JSON tables win over standard files of the same name.
+/// The foiling regimes of `foiling.json` apply to polars without one.
+pub async fn seed_from_dir(dir: &str) -> Result<usize> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    let mut paths = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        paths.push(entry.path());
+    }
+    paths.sort_by_key(|path| (path.extension().is_none_or(|ext| ext != "json"), path.clone()));
+
+    let existing = list().await?;
+    let mut seeded: Vec<String> = Vec::new();
+    let mut foiling_file = None;
+    for path in paths {
+        if path.file_name().is_some_and(|name| name == FOILING_FILE) {
+            foiling_file = Some(path);
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        if existing.iter().any(|n| n == name) || seeded.iter().any(|n| n == name) {
+            continue;
+        }
+        let text = tokio::fs::read_to_string(&path).await?;
+        match parse_file(&path, &text) {
+            Some(Ok(polar)) => {
+                save(name, &polar).await?;
+                seeded.push(name.to_string());
+            }
+            Some(Err(e)) => log::warn!("Skipping polar {}: {}", path.display(), e),
+            None => {}
+        }
+    }
+
+    if let Some(path) = foiling_file {
+        let regimes: HashMap<String, Foiling> =
+            serde_json::from_str(&tokio::fs::read_to_string(&path).await?)?;
+        for (name, foiling) in regimes {
+            foiling.validate()?;
+            sqlx::query("UPDATE polars SET foiling = $1 WHERE name = $2 AND foiling IS NULL")
+                .bind(serde_json::to_string(&foiling)?)
+                .bind(&name)
+                .execute(db::pool())
+                .await?;
+        }
+    }
+    Ok(seeded.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLAR_JSON: &str = r#"{
+        "0": { "0": 0, "90": 0, "180": 0 },
+        "10": { "0": 0, "90": 10, "180": 6 },
+        "20": { "0": 0, "90": 16, "180": 12 }
+    }"#;
+
+    #[test]
+    fn test_polar_speed_interpolates_and_clamps() {
+        let polar = Polar::from_json(POLAR_JSON).unwrap();
+        assert_eq!(polar.speed(10.0, 90.0), 10.0);
+        assert_eq!(polar.speed(15.0, 90.0), 13.0);
+        assert_eq!(polar.speed(10.0, 135.0), 8.0);
+        // Symmetric and clamped to the table
+        assert_eq!(polar.speed(10.0, -90.0), 10.0);
+        assert_eq!(polar.speed(10.0, 270.0), 10.0);
+        assert_eq!(polar.speed(40.0, 90.0), 16.0);
+
+        assert!(Polar::from_json("{}").is_err());
+        assert!(Polar::from_json(r#"{ "10": { "0": 0, "90": 1 }, "20": { "0": 0 } }"#).is_err());
+    }
+
+    /// Speeds the client must compute to the bit, see client `polar.test.ts`
+    #[test]
+    fn test_speed_matches_client_fixture() {
+        #[derive(Deserialize)]
+        struct Fixture {
+            cases: Vec<[f64; 3]>,
+        }
+        let polar = Polar::from_json(include_str!(
+            "../../client/public/static/vr-imoca-full-pack.json"
+        ))
+        .unwrap();
+        let fixture: Fixture =
+            serde_json::from_str(include_str!("../../client/src/app/polar.fixture.json")).unwrap();
+        for [tws, twa, bsp] in fixture.cases {
+            assert_eq!(polar.speed(tws, twa), bsp, "tws {tws} twa {twa}");
+        }
+    }
+
+    #[test]
+    fn test_pol_file_matches_json_table() {
+        let pol = "TWA\\TWS;0;10;20\n0;0;0;0\n90;0;10;16\n180;0;6;12\n";
+        let polar = Polar::from_pol(pol).unwrap();
+        assert_eq!(polar, Polar::from_json(POLAR_JSON).unwrap());
+        assert_eq!(Polar::from_pol(&pol.replace(';', "\t")).unwrap(), polar);
+
+        // Round trip through the client table
+        let json = serde_json::to_string(&polar.to_table()).unwrap();
+        assert!(json.contains(r#""10":{"0":0.0,"180":6.0,"90":10.0}"#));
+        assert_eq!(Polar::from_json(&json).unwrap(), polar);
+
+        assert!(Polar::from_pol("TWA\\TWS;10;20\n90;10\n").is_err());
+        assert!(Polar::from_pol("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_save_and_get() {
+        crate::db::init_test().await.unwrap();
+
+        let polar = Polar::from_json(POLAR_JSON).unwrap();
+        save("test-polar", &polar).await.unwrap();
+        assert_eq!(get("test-polar").await.unwrap(), Some(polar));
+        assert!(list().await.unwrap().contains(&"test-polar".to_string()));
+        assert!(get("missing-polar").await.unwrap().is_none());
+
+        let foiling = Foiling {
+            min_tws: 12.0,
+            min_twa: 60.0,
+            max_twa: 150.0,
+            boost: 0.2,
+            wave_tws: 25.0,
+            instability: 0.3,
+        };
+        assert!(set_foiling("test-polar", Some(&foiling)).await.unwrap());
+        assert_eq!(get("test-polar").await.unwrap().unwrap().foiling, Some(foiling));
+        assert!(set_foiling("test-polar", None).await.unwrap());
+        assert!(get("test-polar").await.unwrap().unwrap().foiling.is_none());
+        assert!(!set_foiling("missing-polar", None).await.unwrap());
+    }
+
+    #[test]
+    fn test_foiling_regime() {
+        let mut polar = Polar::from_json(POLAR_JSON).unwrap();
+        assert_eq!(polar.boat_speed(20.0, 90.0, 0.0), 16.0);
+
+        polar.foiling = Some(Foiling {
+            min_tws: 12.0,
+            min_twa: 60.0,
+            max_twa: 150.0,
+            boost: 0.25,
+            wave_tws: 20.0,
+            instability: 0.5,
+        });
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        // Lifting from 12 knots, full boost from 16
+        assert_eq!(polar.boat_speed(10.0, 90.0, 0.0), 10.0);
+        assert!(close(polar.boat_speed(14.0, 90.0, 0.0), 12.4 * 1.125));
+        assert!(close(polar.boat_speed(18.0, -90.0, 0.0), 14.8 * 1.25));
+        assert_eq!(polar.boat_speed(18.0, 170.0, 0.0), polar.speed(18.0, 170.0));
+
+        // Unstable in waves over each swell
+        let foiling = polar.foiling.as_ref().unwrap();
+        assert!(close(foiling.factor(30.0, 90.0, 0.0), 1.0));
+        assert!(close(foiling.factor(30.0, 90.0, 1.25), 0.75));
+        assert!(close(foiling.factor(30.0, 90.0, 3.75), 1.25));
+
+        assert!(foiling.validate().is_ok());
+        let inverted = Foiling {
+            min_twa: 160.0,
+            ..foiling.clone()
+        };
+        assert!(inverted.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sail_inventory() {
+        crate::db::init_test().await.unwrap();
+
+        let polar = Polar::from_json(POLAR_JSON).unwrap();
+        save("test-class", &polar).await.unwrap();
+        save("test-class.spinnaker", &polar).await.unwrap();
+        save("test-class.jib", &polar).await.unwrap();
+        save("test-class-other.jib", &polar).await.unwrap();
+
+        let sails = sails("test-class").await.unwrap();
+        let names: Vec<&str> = sails.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["jib", "spinnaker"]);
+        assert!(self::sails("test-no-sails").await.unwrap().is_empty());
+    }
+}
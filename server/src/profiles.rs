@@ -1,19 +1,21 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-use crate::db;
+use crate::{avatars, db};
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Profile {
     pub id: String,
     pub name: String,
+    /// Large avatar, None until one is uploaded
+    pub avatar_url: Option<String>,
 }
 
 /// List all profiles for an account.
 pub async fn list_profiles(account_id: &str) -> Result<Vec<Profile>> {
-    let rows: Vec<(String, String)> = sqlx::query_as(
-        "SELECT id, name FROM profiles WHERE account_id = ? ORDER BY created_at",
+    let rows: Vec<(String, String, Option<i64>)> = sqlx::query_as(
+        "SELECT id, name, avatar_version FROM profiles WHERE account_id = $1 ORDER BY created_at",
     )
     .bind(account_id)
     .fetch_all(db::pool())
@@ -21,7 +23,11 @@ pub async fn list_profiles(account_id: &str) -> Result<Vec<Profile>> {
 
     Ok(rows
         .into_iter()
-        .map(|(id, name)| Profile { id, name })
+        .map(|(id, name, avatar_version)| Profile {
+            avatar_url: avatar_version.map(|v| avatars::url(&id, v, avatars::SIZES[0])),
+            id,
+            name,
+        })
         .collect())
 }
 
@@ -37,7 +43,7 @@ pub async fn create_profile(account_id: &str, name: &str) -> Result<Profile> {
 
     // Check profile count limit (max 10 profiles per account)
     let (count,): (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM profiles WHERE account_id = ?",
+        "SELECT COUNT(*) FROM profiles WHERE account_id = $1",
     )
     .bind(account_id)
     .fetch_one(db::pool())
@@ -49,7 +55,7 @@ pub async fn create_profile(account_id: &str, name: &str) -> Result<Profile> {
 
     let profile_id = uuid::Uuid::new_v4().to_string();
 
-    sqlx::query("INSERT INTO profiles (id, account_id, name) VALUES (?, ?, ?)")
+    sqlx::query("INSERT INTO profiles (id, account_id, name) VALUES ($1, $2, $3)")
         .bind(&profile_id)
         .bind(account_id)
         .bind(name)
@@ -61,6 +67,7 @@ pub async fn create_profile(account_id: &str, name: &str) -> Result<Profile> {
     Ok(Profile {
         id: profile_id,
         name: name.to_string(),
+        avatar_url: None,
     })
 }
 
@@ -75,22 +82,23 @@ pub async fn update_profile(account_id: &str, profile_id: &str, name: &str) -> R
     }
 
     // Verify the profile belongs to this account
-    let result = sqlx::query(
-        "UPDATE profiles SET name = ? WHERE id = ? AND account_id = ?",
+    let row: Option<(Option<i64>,)> = sqlx::query_as(
+        "UPDATE profiles SET name = $1 WHERE id = $2 AND account_id = $3 RETURNING avatar_version",
     )
     .bind(name)
     .bind(profile_id)
     .bind(account_id)
-    .execute(db::pool())
+    .fetch_optional(db::pool())
     .await?;
 
-    if result.rows_affected() == 0 {
+    let Some((avatar_version,)) = row else {
         anyhow::bail!("Profile not found");
-    }
+    };
 
     Ok(Profile {
         id: profile_id.to_string(),
         name: name.to_string(),
+        avatar_url: avatar_version.map(|v| avatars::url(profile_id, v, avatars::SIZES[0])),
     })
 }
 
@@ -99,7 +107,7 @@ pub async fn update_profile(account_id: &str, profile_id: &str, name: &str) -> R
 pub async fn delete_profile(account_id: &str, profile_id: &str) -> Result<()> {
     // Don't allow deleting the last profile
     let (count,): (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM profiles WHERE account_id = ?",
+        "SELECT COUNT(*) FROM profiles WHERE account_id = $1",
     )
     .bind(account_id)
     .fetch_one(db::pool())
@@ -110,8 +118,9 @@ pub async fn delete_profile(account_id: &str, profile_id: &str) -> Result<()> {
     }
 
     // Verify the profile belongs to this account and delete
+    avatars::remove(account_id, profile_id).await?;
     let result = sqlx::query(
-        "DELETE FROM profiles WHERE id = ? AND account_id = ?",
+        "DELETE FROM profiles WHERE id = $1 AND account_id = $2",
     )
     .bind(profile_id)
     .bind(account_id)
@@ -143,24 +152,25 @@ pub struct UpdateProfileRequest {
 mod tests {
     use super::*;
     use crate::auth;
+    use crate::email_templates::Locale;
 
     async fn setup_test_account() -> (String, String) {
         db::init_test().await.unwrap();
 
         // Create a test account with verification
         let email = format!("test{}@example.com", uuid::Uuid::new_v4());
-        auth::start_auth(&email).await.unwrap();
+        auth::start_auth(&email, None, Locale::En).await.unwrap();
 
         // Get the code
         let (code,): (String,) = sqlx::query_as(
-            "SELECT code FROM verification_codes WHERE email = ? ORDER BY created_at DESC LIMIT 1",
+            "SELECT code FROM verification_codes WHERE email = $1 ORDER BY created_at DESC LIMIT 1",
         )
         .bind(&email)
         .fetch_one(db::pool())
         .await
         .unwrap();
 
-        let result = auth::verify_auth(&email, &code).await.unwrap();
+        let result = auth::verify_auth(&email, &code, None, None).await.unwrap();
         (result.account_id, result.profiles[0].id.clone())
     }
 
@@ -0,0 +1,193 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+
+/// Kind of event recorded in a race's log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RaceEventKind {
+    Join,
+    Leave,
+    Start,
+    GateCrossed,
+    Finish,
+    Retire,
+    Dnf,
+    Disconnect,
+    Resume,
+    Ocs,
+}
+
+impl RaceEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RaceEventKind::Join => "join",
+            RaceEventKind::Leave => "leave",
+            RaceEventKind::Start => "start",
+            RaceEventKind::GateCrossed => "gate_crossed",
+            RaceEventKind::Finish => "finish",
+            RaceEventKind::Retire => "retire",
+            RaceEventKind::Dnf => "dnf",
+            RaceEventKind::Disconnect => "disconnect",
+            RaceEventKind::Resume => "resume",
+            RaceEventKind::Ocs => "ocs",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "join" => Some(RaceEventKind::Join),
+            "leave" => Some(RaceEventKind::Leave),
+            "start" => Some(RaceEventKind::Start),
+            "gate_crossed" => Some(RaceEventKind::GateCrossed),
+            "finish" => Some(RaceEventKind::Finish),
+            "retire" => Some(RaceEventKind::Retire),
+            "dnf" => Some(RaceEventKind::Dnf),
+            "disconnect" => Some(RaceEventKind::Disconnect),
+            "resume" => Some(RaceEventKind::Resume),
+            "ocs" => Some(RaceEventKind::Ocs),
+            _ => None,
+        }
+    }
+}
+
+/// An event to be appended to a race's log
+#[derive(Debug, Clone)]
+pub struct NewRaceEvent {
+    pub race_id: String,
+    pub course_key: String,
+    pub kind: RaceEventKind,
+    pub player_id: Option<String>,
+    pub player_name: Option<String>,
+    pub payload: serde_json::Value,
+}
+
+/// A stored race event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RaceEvent {
+    pub id: i64,
+    pub race_id: String,
+    pub course_key: String,
+    pub kind: RaceEventKind,
+    pub player_id: Option<String>,
+    pub player_name: Option<String>,
+    pub payload: serde_json::Value,
+    pub created_at: i64,
+}
+
+/// Append an event to the race log
+pub async fn record(event: &NewRaceEvent) -> Result<i64> {
+    let payload = serde_json::to_string(&event.payload)?;
+    let (id,): (i64,) = sqlx::query_as(
+        "INSERT INTO race_events (race_id, course_key, kind, player_id, player_name, payload)
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+    )
+    .bind(&event.race_id)
+    .bind(&event.course_key)
+    .bind(event.kind.as_str())
+    .bind(&event.player_id)
+    .bind(&event.player_name)
+    .bind(&payload)
+    .fetch_one(db::pool())
+    .await?;
+    Ok(id)
+}
+
+type RaceEventRow = (i64, String, String, String, Option<String>, Option<String>, String, i64);
+
+/// List all events of a race in the order they were recorded
+pub async fn list_for_race(race_id: &str) -> Result<Vec<RaceEvent>> {
+    let rows: Vec<RaceEventRow> = sqlx::query_as(
+        "SELECT id, race_id, course_key, kind, player_id, player_name, payload, created_at
+         FROM race_events
+         WHERE race_id = $1
+         ORDER BY id",
+    )
+    .bind(race_id)
+    .fetch_all(db::pool())
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(
+            |(id, race_id, course_key, kind, player_id, player_name, payload, created_at)| {
+                Some(RaceEvent {
+                    id,
+                    race_id,
+                    course_key,
+                    kind: RaceEventKind::parse(&kind)?,
+                    player_id,
+                    player_name,
+                    payload: serde_json::from_str(&payload).unwrap_or_default(),
+                    created_at,
+                })
+            },
+        )
+        .collect())
+}
+
+/// Remove a player from the race logs, keeping the events
+pub async fn anonymize_player(player_id: &str) -> Result<()> {
+    sqlx::query("UPDATE race_events SET player_id = NULL, player_name = NULL WHERE player_id = $1")
+        .bind(player_id)
+        .execute(db::pool())
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_roundtrip() {
+        for kind in [
+            RaceEventKind::Join,
+            RaceEventKind::Leave,
+            RaceEventKind::Start,
+            RaceEventKind::GateCrossed,
+            RaceEventKind::Finish,
+            RaceEventKind::Retire,
+            RaceEventKind::Dnf,
+            RaceEventKind::Disconnect,
+        ] {
+            assert_eq!(RaceEventKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(RaceEventKind::parse("unknown"), None);
+    }
+
+    #[tokio::test]
+    async fn test_record_and_list() {
+        db::init_test().await.unwrap();
+
+        let race_id = "EVT001";
+        for (kind, payload) in [
+            (RaceEventKind::Join, serde_json::json!({})),
+            (
+                RaceEventKind::GateCrossed,
+                serde_json::json!({ "gateIndex": 0, "courseTime": 1234 }),
+            ),
+        ] {
+            record(&NewRaceEvent {
+                race_id: race_id.to_string(),
+                course_key: "vg20".to_string(),
+                kind,
+                player_id: Some("p1".to_string()),
+                player_name: Some("Alice".to_string()),
+                payload,
+            })
+            .await
+            .unwrap();
+        }
+
+        let events = list_for_race(race_id).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, RaceEventKind::Join);
+        assert_eq!(events[1].kind, RaceEventKind::GateCrossed);
+        assert_eq!(events[1].payload["gateIndex"], 0);
+
+        assert!(list_for_race("NOPE00").await.unwrap().is_empty());
+    }
+}
@@ -1,10 +1,17 @@
-use anyhow::Result;
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, SecondsFormat};
+use futures::stream::{self, Stream, StreamExt};
+use object_store::ObjectStoreExt;
 use serde::Serialize;
 
-use crate::db;
+use crate::units::format_elapsed;
+use crate::{avatars, db, geo, race_stats::RaceStats, s3, seasons::Period, simulation::Helm};
 
 /// A point in the recorded path
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PathPoint {
     pub race_time: i64, // Race time in ms
     pub lng: f32,
@@ -12,6 +19,33 @@ pub struct PathPoint {
     pub heading: f32,
 }
 
+/// How a stored race ended for the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ResultStatus {
+    Finished,
+    Retired,
+    Dnf,
+}
+
+impl ResultStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResultStatus::Finished => "finished",
+            ResultStatus::Retired => "retired",
+            ResultStatus::Dnf => "dnf",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "retired" => ResultStatus::Retired,
+            "dnf" => ResultStatus::Dnf,
+            _ => ResultStatus::Finished,
+        }
+    }
+}
+
 /// Leaderboard entry for hall of fame display
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -20,76 +54,494 @@ pub struct HallOfFameEntry {
     pub rank: u32,
     pub player_name: String,
     pub player_id: Option<String>,
+    /// Small avatar of the player's profile
+    pub avatar_url: Option<String>,
     pub finish_time: i64,
     pub race_date: i64, // Unix timestamp ms
+    /// Elapsed time at each mark before the finish, in sailing order
+    pub splits: Vec<i64>,
+    /// None for results saved before stats were computed
+    pub stats: Option<RaceStats>,
 }
 
-/// Save a race result to the database
-pub async fn save_result(
+type LeaderboardRow = (i64, String, Option<String>, Option<i64>, i64, i64);
+
+/// A race result to be saved.
+/// For retired and DNF results, `finish_time` is the elapsed time when the race stopped.
+#[derive(Debug, Clone)]
+pub struct NewRaceResult {
+    pub race_id: String,
+    pub course_key: String,
+    pub player_name: String,
+    pub player_id: String,
+    pub finish_time: i64,
+    pub race_start_time: i64,
+    pub path_s3_key: String,
+    pub status: ResultStatus,
+    /// Set for daily challenge runs, which are ranked apart from the Hall of Fame
+    pub daily_date: Option<String>,
+    /// Course start time of the weather window, when it isn't the course default
+    pub weather_start_time: Option<i64>,
+    /// Boat class (polar name) sailed
+    pub polar: String,
+    /// Elapsed time at each mark crossed before the finish, in sailing order
+    pub splits: Vec<i64>,
+    /// Statistics of the path, None when they couldn't be computed
+    pub stats: Option<RaceStats>,
+}
+
+/// Save a race result with its split times and stats to the database.
+pub async fn save_result(result: &NewRaceResult) -> Result<i64> {
+    let stats = result.stats.as_ref();
+    let (id,): (i64,) = sqlx::query_as(
+        "INSERT INTO race_results (race_id, course_key, player_name, player_id, finish_time, race_start_time, path_s3_key, status, daily_date, weather_start_time, polar, distance_nm, avg_speed, max_speed, tacks, gybes)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+         RETURNING id",
+    )
+    .bind(&result.race_id)
+    .bind(&result.course_key)
+    .bind(&result.player_name)
+    .bind(&result.player_id)
+    .bind(result.finish_time)
+    .bind(result.race_start_time)
+    .bind(&result.path_s3_key)
+    .bind(result.status.as_str())
+    .bind(&result.daily_date)
+    .bind(result.weather_start_time)
+    .bind(&result.polar)
+    .bind(stats.map(|stats| stats.distance_nm))
+    .bind(stats.map(|stats| stats.avg_speed))
+    .bind(stats.map(|stats| stats.max_speed))
+    .bind(stats.map(|stats| stats.tacks as i64))
+    .bind(stats.map(|stats| stats.gybes as i64))
+    .fetch_one(db::pool())
+    .await?;
+
+    for (gate_index, split_time) in result.splits.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO race_splits (result_id, gate_index, split_time) VALUES ($1, $2, $3)",
+        )
+        .bind(id)
+        .bind(gate_index as i64)
+        .bind(split_time)
+        .execute(db::pool())
+        .await?;
+    }
+    Ok(id)
+}
+
+/// Split times of results, by result id
+pub async fn get_splits(result_ids: &[i64]) -> Result<HashMap<i64, Vec<i64>>> {
+    let mut splits: HashMap<i64, Vec<i64>> = HashMap::new();
+    if result_ids.is_empty() {
+        return Ok(splits);
+    }
+
+    let sql = format!(
+        "SELECT result_id, split_time FROM race_splits WHERE result_id IN ({})
+         ORDER BY result_id, gate_index",
+        db::placeholders(1, result_ids.len())
+    );
+    let mut query = sqlx::query_as(&sql);
+    for id in result_ids {
+        query = query.bind(id);
+    }
+    let rows: Vec<(i64, i64)> = query.fetch_all(db::pool()).await?;
+
+    for (result_id, split_time) in rows {
+        splits.entry(result_id).or_default().push(split_time);
+    }
+    Ok(splits)
+}
+
+/// Stats of results, by result id. Results saved before stats have none.
+pub async fn get_stats(result_ids: &[i64]) -> Result<HashMap<i64, RaceStats>> {
+    if result_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let sql = format!(
+        "SELECT id, distance_nm, avg_speed, max_speed, tacks, gybes FROM race_results
+         WHERE distance_nm IS NOT NULL AND id IN ({})",
+        db::placeholders(1, result_ids.len())
+    );
+    let mut query = sqlx::query_as(&sql);
+    for id in result_ids {
+        query = query.bind(id);
+    }
+    let rows: Vec<(i64, f64, f64, f64, i64, i64)> = query.fetch_all(db::pool()).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, distance_nm, avg_speed, max_speed, tacks, gybes)| {
+            let stats = RaceStats {
+                distance_nm,
+                avg_speed,
+                max_speed,
+                tacks: tacks as u32,
+                gybes: gybes as u32,
+            };
+            (id, stats)
+        })
+        .collect())
+}
+
+/// Get the hall of fame leaderboard of a boat class on a course, over the
+/// results raced in `period`
+pub async fn get_leaderboard(
     course_key: &str,
-    player_name: &str,
-    player_id: &str,
-    finish_time: i64,
-    race_start_time: i64,
-    path_s3_key: &str,
-) -> Result<i64> {
-    let result = sqlx::query(
-        "INSERT INTO race_results (course_key, player_name, player_id, finish_time, race_start_time, path_s3_key)
-         VALUES (?, ?, ?, ?, ?, ?)",
+    polar: &str,
+    period: Period,
+    limit: u32,
+) -> Result<Vec<HallOfFameEntry>> {
+    let rows: Vec<LeaderboardRow> = sqlx::query_as(
+        "SELECT r.id, r.player_name, r.player_id, p.avatar_version, r.finish_time, r.race_start_time
+         FROM race_results r LEFT JOIN profiles p ON p.id = r.player_id
+         WHERE r.course_key = $1 AND r.polar = $2 AND r.status = 'finished'
+           AND r.daily_date IS NULL AND r.weather_start_time IS NULL
+           AND r.race_start_time >= $3 AND r.race_start_time < $4
+         ORDER BY r.finish_time ASC
+         LIMIT $5",
     )
     .bind(course_key)
-    .bind(player_name)
-    .bind(player_id)
-    .bind(finish_time)
-    .bind(race_start_time)
-    .bind(path_s3_key)
-    .execute(db::pool())
+    .bind(polar)
+    .bind(period.from.unwrap_or(i64::MIN))
+    .bind(period.until.unwrap_or(i64::MAX))
+    .bind(limit as i64)
+    .fetch_all(db::pool())
     .await?;
-    Ok(result.last_insert_rowid())
+
+    with_details(rank_entries(rows)).await
 }
 
-/// Get the hall of fame leaderboard for a course
-pub async fn get_leaderboard(course_key: &str, limit: u32) -> Result<Vec<HallOfFameEntry>> {
-    let rows: Vec<(i64, String, Option<String>, i64, i64)> = sqlx::query_as(
-        "SELECT id, player_name, player_id, finish_time, race_start_time
-         FROM race_results
-         WHERE course_key = ?
-         ORDER BY finish_time ASC
-         LIMIT ?",
+/// All time Hall of Fame rank of a finish time of a boat class on a course
+pub async fn hall_of_fame_rank(course_key: &str, polar: &str, finish_time: i64) -> Result<u32> {
+    let (ahead,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM race_results
+         WHERE course_key = $1 AND polar = $2 AND status = 'finished'
+           AND daily_date IS NULL AND weather_start_time IS NULL AND finish_time < $3",
     )
     .bind(course_key)
-    .bind(limit)
+    .bind(polar)
+    .bind(finish_time)
+    .fetch_one(db::pool())
+    .await?;
+    Ok(ahead as u32 + 1)
+}
+
+/// Get the leaderboard of a day's daily challenge
+pub async fn get_daily_leaderboard(date: &str, limit: u32) -> Result<Vec<HallOfFameEntry>> {
+    let rows: Vec<LeaderboardRow> = sqlx::query_as(
+        "SELECT r.id, r.player_name, r.player_id, p.avatar_version, r.finish_time, r.race_start_time
+         FROM race_results r LEFT JOIN profiles p ON p.id = r.player_id
+         WHERE r.daily_date = $1 AND r.status = 'finished'
+         ORDER BY r.finish_time ASC
+         LIMIT $2",
+    )
+    .bind(date)
+    .bind(limit as i64)
     .fetch_all(db::pool())
     .await?;
 
-    let entries = rows
+    with_details(rank_entries(rows)).await
+}
+
+/// Fill the splits and stats of entries
+async fn with_details(mut entries: Vec<HallOfFameEntry>) -> Result<Vec<HallOfFameEntry>> {
+    let ids: Vec<i64> = entries.iter().map(|entry| entry.id).collect();
+    let mut splits = get_splits(&ids).await?;
+    let mut stats = get_stats(&ids).await?;
+    for entry in &mut entries {
+        entry.splits = splits.remove(&entry.id).unwrap_or_default();
+        entry.stats = stats.remove(&entry.id);
+    }
+    Ok(entries)
+}
+
+fn rank_entries(rows: Vec<LeaderboardRow>) -> Vec<HallOfFameEntry> {
+    rows
         .into_iter()
         .enumerate()
         .map(
-            |(i, (id, player_name, player_id, finish_time, race_start_time))| HallOfFameEntry {
-                id,
-                rank: (i + 1) as u32,
-                player_name,
-                player_id,
-                finish_time, // Already stored as duration
-                race_date: race_start_time,
+            |(i, (id, player_name, player_id, avatar_version, finish_time, race_start_time))| {
+                let avatar_url = player_id
+                    .as_deref()
+                    .zip(avatar_version)
+                    .map(|(player_id, v)| avatars::url(player_id, v, avatars::SMALL));
+                HallOfFameEntry {
+                    id,
+                    rank: (i + 1) as u32,
+                    player_name,
+                    player_id,
+                    avatar_url,
+                    finish_time, // Already stored as duration
+                    race_date: race_start_time,
+                    splits: Vec::new(),
+                    stats: None,
+                }
             },
         )
-        .collect();
+        .collect()
+}
 
-    Ok(entries)
+/// Columns of the leaderboard CSV
+const CSV_HEADER: &str = "rank,polar,player_name,player_id,status,finish_time_ms,finish_time,\
+race_date,daily_date,weather_start_time,distance_nm,avg_speed,max_speed,tacks,gybes,result_id\r\n";
+
+type StandingRow = (
+    i64,
+    String,
+    String,
+    Option<String>,
+    String,
+    i64,
+    i64,
+    Option<String>,
+    Option<i64>,
+    Option<f64>,
+    Option<f64>,
+    Option<f64>,
+    Option<i64>,
+    Option<i64>,
+);
+
+/// A CSV field, quoted when needed. Text that starts like a formula is
+/// prefixed with a quote so spreadsheets don't evaluate it.
+fn csv_field(text: &str) -> String {
+    let text = if text.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", text)
+    } else {
+        text.to_string()
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+fn format_date(ms: i64) -> String {
+    DateTime::from_timestamp_millis(ms)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Every result of a course as CSV lines, header first, streamed from the
+/// database. By boat class, the Hall of Fame finishes come first with their
+/// rank, then the other results (daily challenge, other weather, retired,
+/// DNF) without one.
+pub fn leaderboard_csv(course_key: String) -> impl Stream<Item = Result<String>> {
+    let rows = sqlx::query_as::<_, StandingRow>(
+        "SELECT id, polar, player_name, player_id, status, finish_time, race_start_time,
+                daily_date, weather_start_time, distance_nm, avg_speed, max_speed, tacks, gybes
+         FROM race_results WHERE course_key = $1
+         ORDER BY polar,
+                  status = 'finished' AND daily_date IS NULL AND weather_start_time IS NULL DESC,
+                  status, finish_time, id",
+    )
+    .bind(course_key)
+    .fetch(db::pool());
+
+    // Rank of the last Hall of Fame finish of the current boat class
+    let mut rank: (String, u32) = (String::new(), 0);
+    let lines = rows.map(move |row| {
+        let (
+            id,
+            polar,
+            player_name,
+            player_id,
+            status,
+            finish_time,
+            race_start_time,
+            daily_date,
+            weather_start_time,
+            distance_nm,
+            avg_speed,
+            max_speed,
+            tacks,
+            gybes,
+        ) = row?;
+        if rank.0 != polar {
+            rank = (polar.clone(), 0);
+        }
+        let ranked = status == ResultStatus::Finished.as_str()
+            && daily_date.is_none()
+            && weather_start_time.is_none();
+        if ranked {
+            rank.1 += 1;
+        }
+
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        let fields = [
+            optional(ranked.then(|| rank.1.to_string())),
+            csv_field(&polar),
+            csv_field(&player_name),
+            csv_field(&player_id.unwrap_or_default()),
+            csv_field(&status),
+            finish_time.to_string(),
+            format_elapsed(finish_time),
+            format_date(race_start_time),
+            csv_field(&daily_date.unwrap_or_default()),
+            optional(weather_start_time.map(format_date)),
+            optional(distance_nm.map(|v| format!("{:.1}", v))),
+            optional(avg_speed.map(|v| format!("{:.2}", v))),
+            optional(max_speed.map(|v| format!("{:.2}", v))),
+            optional(tacks.map(|v| v.to_string())),
+            optional(gybes.map(|v| v.to_string())),
+            id.to_string(),
+        ];
+        Ok(fields.join(",") + "\r\n")
+    });
+    stream::once(async { Ok(CSV_HEADER.to_string()) }).chain(lines)
 }
 
 /// Get the S3 path key for a race result
 pub async fn get_path_key(result_id: i64) -> Result<Option<String>> {
     let row: Option<(String,)> =
-        sqlx::query_as("SELECT path_s3_key FROM race_results WHERE id = ?")
+        sqlx::query_as("SELECT path_s3_key FROM race_results WHERE id = $1")
             .bind(result_id)
             .fetch_optional(db::pool())
             .await?;
     Ok(row.map(|(key,)| key))
 }
 
+/// A stored result, with what it takes to sail it again
+#[derive(Debug, Clone)]
+pub struct StoredResult {
+    pub course_key: String,
+    pub player_name: String,
+    pub status: ResultStatus,
+    pub finish_time: i64,
+    pub path_s3_key: String,
+    pub weather_start_time: Option<i64>,
+    pub polar: String,
+}
+
+type StoredResultRow = (String, String, String, i64, String, Option<i64>, String);
+
+/// Get a race result by id
+pub async fn get_result(result_id: i64) -> Result<Option<StoredResult>> {
+    let row: Option<StoredResultRow> = sqlx::query_as(
+        "SELECT course_key, player_name, status, finish_time, path_s3_key, weather_start_time, polar
+         FROM race_results WHERE id = $1",
+    )
+    .bind(result_id)
+    .fetch_optional(db::pool())
+    .await?;
+    Ok(row.map(
+        |(course_key, player_name, status, finish_time, path_s3_key, weather_start_time, polar)| {
+            StoredResult {
+                course_key,
+                player_name,
+                status: ResultStatus::parse(&status),
+                finish_time,
+                path_s3_key,
+                weather_start_time,
+                polar,
+            }
+        },
+    ))
+}
+
+/// A result of a player, as exported with their account data
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerResult {
+    pub id: i64,
+    pub race_id: Option<String>,
+    pub course_key: String,
+    pub player_id: String,
+    pub player_name: String,
+    pub status: ResultStatus,
+    pub finish_time: i64,
+    pub race_start_time: i64,
+    pub daily_date: Option<String>,
+    pub polar: Option<String>,
+    pub path_s3_key: String,
+}
+
+type PlayerResultRow = (
+    i64,
+    Option<String>,
+    String,
+    String,
+    String,
+    i64,
+    i64,
+    Option<String>,
+    Option<String>,
+    String,
+);
+
+/// List the results of a player (profile), oldest first
+pub async fn list_for_player(player_id: &str) -> Result<Vec<PlayerResult>> {
+    let rows: Vec<PlayerResultRow> = sqlx::query_as(
+        "SELECT id, race_id, course_key, player_name, status, finish_time, race_start_time, daily_date, polar, path_s3_key
+         FROM race_results
+         WHERE player_id = $1
+         ORDER BY race_start_time, id",
+    )
+    .bind(player_id)
+    .fetch_all(db::pool())
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(
+                id,
+                race_id,
+                course_key,
+                player_name,
+                status,
+                finish_time,
+                race_start_time,
+                daily_date,
+                polar,
+                path_s3_key,
+            )| PlayerResult {
+                id,
+                race_id,
+                course_key,
+                player_id: player_id.to_string(),
+                player_name,
+                status: ResultStatus::parse(&status),
+                finish_time,
+                race_start_time,
+                daily_date,
+                polar,
+                path_s3_key,
+            },
+        )
+        .collect())
+}
+
+/// Player name of the results of deleted accounts
+pub const ANONYMOUS_NAME: &str = "Anonymous";
+
+/// Detach the results of a player from them: times stay on the leaderboards
+/// and replays under an anonymous name. Returns the number of results.
+pub async fn anonymize_player(player_id: &str) -> Result<u64> {
+    let updated = sqlx::query(
+        "UPDATE race_results SET player_name = $1, player_id = NULL WHERE player_id = $2",
+    )
+    .bind(ANONYMOUS_NAME)
+    .bind(player_id)
+    .execute(db::pool())
+    .await?;
+    Ok(updated.rows_affected())
+}
+
+/// Download and decode the points of a stored path
+pub async fn load_path(path_s3_key: &str) -> Result<Vec<PathPoint>> {
+    let data = s3::paths_client()
+        .get(&object_store::path::Path::from(path_s3_key))
+        .await?
+        .bytes()
+        .await?;
+    decode_path(&data)
+}
+
 // ============================================================================
 // Admin functions
 // ============================================================================
@@ -106,23 +558,36 @@ pub struct AdminRaceResult {
     pub race_start_time: i64,
     pub path_s3_key: String,
     pub created_at: Option<i64>,
+    pub status: ResultStatus,
 }
 
+type AdminRaceResultRow = (
+    i64,
+    String,
+    String,
+    Option<String>,
+    i64,
+    i64,
+    String,
+    Option<i64>,
+    String,
+);
+
 /// List all race results with optional course filter, ordered by created_at DESC.
 pub async fn list_all(
     limit: i64,
     offset: i64,
     course_key_filter: Option<&str>,
 ) -> Result<Vec<AdminRaceResult>> {
-    let rows: Vec<(i64, String, String, Option<String>, i64, i64, String, Option<i64>)> =
+    let rows: Vec<AdminRaceResultRow> =
         match course_key_filter {
             Some(key) => {
                 sqlx::query_as(
-                    "SELECT id, course_key, player_name, player_id, finish_time, race_start_time, path_s3_key, created_at
+                    "SELECT id, course_key, player_name, player_id, finish_time, race_start_time, path_s3_key, created_at, status
                      FROM race_results
-                     WHERE course_key = ?
+                     WHERE course_key = $1
                      ORDER BY created_at DESC
-                     LIMIT ? OFFSET ?",
+                     LIMIT $2 OFFSET $3",
                 )
                 .bind(key)
                 .bind(limit)
@@ -132,10 +597,10 @@ pub async fn list_all(
             }
             None => {
                 sqlx::query_as(
-                    "SELECT id, course_key, player_name, player_id, finish_time, race_start_time, path_s3_key, created_at
+                    "SELECT id, course_key, player_name, player_id, finish_time, race_start_time, path_s3_key, created_at, status
                      FROM race_results
                      ORDER BY created_at DESC
-                     LIMIT ? OFFSET ?",
+                     LIMIT $1 OFFSET $2",
                 )
                 .bind(limit)
                 .bind(offset)
@@ -147,7 +612,7 @@ pub async fn list_all(
     Ok(rows
         .into_iter()
         .map(
-            |(id, course_key, player_name, player_id, finish_time, race_start_time, path_s3_key, created_at)| {
+            |(id, course_key, player_name, player_id, finish_time, race_start_time, path_s3_key, created_at, status)| {
                 AdminRaceResult {
                     id,
                     course_key,
@@ -157,6 +622,7 @@ pub async fn list_all(
                     race_start_time,
                     path_s3_key,
                     created_at,
+                    status: ResultStatus::parse(&status),
                 }
             },
         )
@@ -167,7 +633,7 @@ pub async fn list_all(
 pub async fn count_all(course_key_filter: Option<&str>) -> Result<i64> {
     let (count,): (i64,) = match course_key_filter {
         Some(key) => {
-            sqlx::query_as("SELECT COUNT(*) FROM race_results WHERE course_key = ?")
+            sqlx::query_as("SELECT COUNT(*) FROM race_results WHERE course_key = $1")
                 .bind(key)
                 .fetch_one(db::pool())
                 .await?
@@ -183,8 +649,12 @@ pub async fn count_all(course_key_filter: Option<&str>) -> Result<i64> {
 
 /// Delete a race result and return the S3 path key for cleanup.
 pub async fn delete_result(id: i64) -> Result<Option<String>> {
+    sqlx::query("DELETE FROM race_splits WHERE result_id = $1")
+        .bind(id)
+        .execute(db::pool())
+        .await?;
     let row: Option<(String,)> = sqlx::query_as(
-        "DELETE FROM race_results WHERE id = ? RETURNING path_s3_key",
+        "DELETE FROM race_results WHERE id = $1 RETURNING path_s3_key",
     )
     .bind(id)
     .fetch_optional(db::pool())
@@ -197,23 +667,547 @@ pub async fn delete_result(id: i64) -> Result<Option<String>> {
 // Binary path encoding/decoding
 // ============================================================================
 
-const PATH_VERSION: u32 = 1;
+/// Version written. Version 1 files only have points.
+const PATH_VERSION: u32 = 2;
 
-/// Encode path points to binary format for S3 storage
-pub fn encode_path(points: &[PathPoint]) -> Vec<u8> {
-    let mut buf = Vec::with_capacity(8 + points.len() * 20);
+/// Flags of the optional sections of a version 2 path
+const WITH_HELM: u32 = 1;
+const WITH_WIND_REPORTS: u32 = 2;
+
+const POINT_SIZE: usize = 20;
+const HELM_SIZE: usize = 5;
+const WIND_REPORT_SIZE: usize = 8;
+
+/// A recorded path, with what is known of how it was sailed: enough to
+/// simulate it again on the server.
+///
+/// `helm` and `wind_reports` are either empty or have one entry per point.
+#[derive(Debug, Clone, Default)]
+pub struct RecordedPath {
+    pub points: Vec<PathPoint>,
+    /// Helm input at each point, for boats simulated by the server
+    pub helm: Vec<Helm>,
+    /// Time of the wind report in effect at each point, its unique key
+    pub wind_reports: Vec<i64>,
+}
+
+impl RecordedPath {
+    /// Add a point. Inputs and wind reports are only kept while known at
+    /// every point.
+    pub fn push(&mut self, point: PathPoint, helm: Option<Helm>, wind_report: Option<i64>) {
+        match helm {
+            Some(helm) if self.helm.len() == self.points.len() => self.helm.push(helm),
+            _ => self.helm.clear(),
+        }
+        match wind_report {
+            Some(time) if self.wind_reports.len() == self.points.len() => {
+                self.wind_reports.push(time)
+            }
+            _ => self.wind_reports.clear(),
+        }
+        self.points.push(point);
+    }
+
+    pub fn has_helm(&self) -> bool {
+        !self.points.is_empty() && self.helm.len() == self.points.len()
+    }
+
+    pub fn has_wind_reports(&self) -> bool {
+        !self.points.is_empty() && self.wind_reports.len() == self.points.len()
+    }
+}
+
+/// Encode a path to the binary format for S3 storage:
+///
+/// - header: version, point count and section flags (u32 LE each)
+/// - points: race time (i64), lng, lat and heading (f32)
+/// - with `WITH_HELM`: helm kind (u8, 0 heading, 1 TWA) and angle (f32)
+/// - with `WITH_WIND_REPORTS`: wind report time (i64)
+pub fn encode_path(path: &RecordedPath) -> Vec<u8> {
+    let (with_helm, with_wind_reports) = (path.has_helm(), path.has_wind_reports());
+    let mut flags = 0;
+    let mut point_size = POINT_SIZE;
+    if with_helm {
+        flags |= WITH_HELM;
+        point_size += HELM_SIZE;
+    }
+    if with_wind_reports {
+        flags |= WITH_WIND_REPORTS;
+        point_size += WIND_REPORT_SIZE;
+    }
+    let mut buf = Vec::with_capacity(12 + path.points.len() * point_size);
 
     // Header
     buf.extend_from_slice(&PATH_VERSION.to_le_bytes());
-    buf.extend_from_slice(&(points.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&(path.points.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&flags.to_le_bytes());
 
     // Points
-    for point in points {
+    for point in &path.points {
         buf.extend_from_slice(&point.race_time.to_le_bytes());
         buf.extend_from_slice(&point.lng.to_le_bytes());
         buf.extend_from_slice(&point.lat.to_le_bytes());
         buf.extend_from_slice(&point.heading.to_le_bytes());
     }
 
+    if with_helm {
+        for helm in &path.helm {
+            let (kind, angle) = match *helm {
+                Helm::Heading(heading) => (0u8, heading),
+                Helm::Twa(twa) => (1u8, twa),
+            };
+            buf.push(kind);
+            buf.extend_from_slice(&(angle as f32).to_le_bytes());
+        }
+    }
+    if with_wind_reports {
+        for time in &path.wind_reports {
+            buf.extend_from_slice(&time.to_le_bytes());
+        }
+    }
+
     buf
 }
+
+/// Decode the points of a path written by `encode_path`
+pub fn decode_path(data: &[u8]) -> Result<Vec<PathPoint>> {
+    Ok(decode_recorded_path(data)?.points)
+}
+
+/// Decode a path written by `encode_path`, or a version 1 path of points only
+pub fn decode_recorded_path(data: &[u8]) -> Result<RecordedPath> {
+    if data.len() < 8 {
+        return Err(anyhow!("Invalid path data: too short"));
+    }
+    let version = u32::from_le_bytes(data[0..4].try_into()?);
+    let count = u32::from_le_bytes(data[4..8].try_into()?) as usize;
+    let (flags, header_size) = match version {
+        1 => (0, 8),
+        2 if data.len() >= 12 => (u32::from_le_bytes(data[8..12].try_into()?), 12),
+        2 => return Err(anyhow!("Invalid path data: too short")),
+        _ => return Err(anyhow!("Unsupported path version: {}", version)),
+    };
+
+    let helm_start = header_size + count * POINT_SIZE;
+    let section = |flag: u32, size: usize| if flags & flag != 0 { count * size } else { 0 };
+    let wind_reports_start = helm_start + section(WITH_HELM, HELM_SIZE);
+    let end = wind_reports_start + section(WITH_WIND_REPORTS, WIND_REPORT_SIZE);
+    if data.len() < end {
+        return Err(anyhow!("Invalid path data: truncated"));
+    }
+
+    let points = data[header_size..helm_start]
+        .chunks_exact(POINT_SIZE)
+        .map(|chunk| PathPoint {
+            race_time: i64::from_le_bytes(chunk[0..8].try_into().unwrap()),
+            lng: f32::from_le_bytes(chunk[8..12].try_into().unwrap()),
+            lat: f32::from_le_bytes(chunk[12..16].try_into().unwrap()),
+            heading: f32::from_le_bytes(chunk[16..20].try_into().unwrap()),
+        })
+        .collect();
+    let helm = data[helm_start..wind_reports_start]
+        .chunks_exact(HELM_SIZE)
+        .map(|chunk| {
+            let angle = f32::from_le_bytes(chunk[1..5].try_into().unwrap()) as f64;
+            match chunk[0] {
+                1 => Helm::Twa(angle),
+                _ => Helm::Heading(angle),
+            }
+        })
+        .collect();
+    let wind_reports = data[wind_reports_start..end]
+        .chunks_exact(WIND_REPORT_SIZE)
+        .map(|chunk| i64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+
+    Ok(RecordedPath {
+        points,
+        helm,
+        wind_reports,
+    })
+}
+
+/// Interpolate a recorded path at the given race time.
+/// Returns None if the time is outside the recorded path.
+pub fn interpolate_position(points: &[PathPoint], race_time: i64) -> Option<PathPoint> {
+    let first = points.first()?;
+    let last = points.last()?;
+    if race_time < first.race_time || race_time > last.race_time {
+        return None;
+    }
+
+    let idx = points.partition_point(|p| p.race_time <= race_time);
+    if idx == 0 || idx >= points.len() {
+        return Some(points[idx.saturating_sub(1)]);
+    }
+
+    let a = points[idx - 1];
+    let b = points[idx];
+    let span = (b.race_time - a.race_time) as f32;
+    let t = if span > 0.0 {
+        (race_time - a.race_time) as f32 / span
+    } else {
+        0.0
+    };
+    let mut dh = b.heading - a.heading;
+    if dh > 180.0 {
+        dh -= 360.0;
+    } else if dh < -180.0 {
+        dh += 360.0;
+    }
+
+    // The short way across the antimeridian
+    let d_lng = geo::wrap_lng((b.lng - a.lng) as f64) as f32;
+
+    Some(PathPoint {
+        race_time,
+        lng: geo::wrap_lng((a.lng + d_lng * t) as f64) as f32,
+        lat: a.lat + (b.lat - a.lat) * t,
+        heading: (a.heading + dh * t).rem_euclid(360.0),
+    })
+}
+
+/// Simplify a recorded path with the Douglas-Peucker algorithm: points
+/// closer than `tolerance_nm` to the line between the kept points around
+/// them are dropped. The first and last points are always kept.
+pub fn simplify_path(points: &[PathPoint], tolerance_nm: f64) -> Vec<PathPoint> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    // Ranges still to split, iterative to bear paths of 100k points
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = ranges.pop() {
+        let (farthest, distance) = (first + 1..last)
+            .map(|i| (i, offset_nm(&points[i], &points[first], &points[last])))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or((first, 0.0));
+        if distance > tolerance_nm {
+            keep[farthest] = true;
+            ranges.push((first, farthest));
+            ranges.push((farthest, last));
+        }
+    }
+
+    points
+        .iter()
+        .zip(keep)
+        .filter_map(|(point, kept)| kept.then_some(*point))
+        .collect()
+}
+
+/// Distance from `point` to the segment a→b, on a local equirectangular
+/// projection around a (good enough between close path points)
+fn offset_nm(point: &PathPoint, a: &PathPoint, b: &PathPoint) -> f64 {
+    let scale = (a.lat as f64).to_radians().cos();
+    let project = |p: &PathPoint| {
+        (
+            geo::wrap_lng((p.lng - a.lng) as f64) * scale * geo::NM_PER_DEGREE,
+            (p.lat - a.lat) as f64 * geo::NM_PER_DEGREE,
+        )
+    };
+    let (px, py) = project(point);
+    let (bx, by) = project(b);
+
+    let length2 = bx * bx + by * by;
+    let t = if length2 > 0.0 {
+        ((px * bx + py * by) / length2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (px - t * bx).hypot(py - t * by)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(race_time: i64, lng: f32, lat: f32, heading: f32) -> PathPoint {
+        PathPoint {
+            race_time,
+            lng,
+            lat,
+            heading,
+        }
+    }
+
+    #[test]
+    fn test_path_roundtrip() {
+        let mut path = RecordedPath::default();
+        path.push(point(0, -1.5, 46.0, 270.0), None, Some(1000));
+        path.push(point(1000, -1.6, 46.1, 265.5), None, Some(1000));
+        let decoded = decode_recorded_path(&encode_path(&path)).unwrap();
+        assert_eq!(decoded.points.len(), 2);
+        assert_eq!(decoded.points[1].race_time, 1000);
+        assert_eq!(decoded.points[1].lng, -1.6);
+        assert_eq!(decoded.points[1].heading, 265.5);
+        assert!(decoded.helm.is_empty());
+        assert_eq!(decoded.wind_reports, vec![1000, 1000]);
+
+        assert!(decode_path(&[1, 0, 0]).is_err());
+        assert!(decode_path(&[2, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_path_helm() {
+        let mut path = RecordedPath::default();
+        path.push(point(0, 0.0, 0.0, 90.0), Some(Helm::Heading(90.0)), None);
+        path.push(point(100, 0.1, 0.0, 85.0), Some(Helm::Twa(-45.5)), None);
+        let decoded = decode_recorded_path(&encode_path(&path)).unwrap();
+        assert_eq!(decoded.helm, vec![Helm::Heading(90.0), Helm::Twa(-45.5)]);
+        assert!(decoded.wind_reports.is_empty());
+
+        // Inputs missing at a point are dropped for the whole path
+        path.push(point(200, 0.2, 0.0, 85.0), None, None);
+        path.push(point(300, 0.3, 0.0, 85.0), Some(Helm::Twa(-45.5)), None);
+        assert!(!path.has_helm());
+        let decoded = decode_recorded_path(&encode_path(&path)).unwrap();
+        assert!(decoded.helm.is_empty());
+    }
+
+    #[test]
+    fn test_decode_version_1() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&500i64.to_le_bytes());
+        for value in [-1.5f32, 46.0, 270.0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let decoded = decode_recorded_path(&data).unwrap();
+        assert_eq!(decoded.points.len(), 1);
+        assert_eq!(decoded.points[0].race_time, 500);
+        assert_eq!(decoded.points[0].lat, 46.0);
+        assert!(decoded.helm.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_daily_results_ranked_apart() {
+        db::init_test().await.unwrap();
+
+        for (player, daily_date) in [("daily-1", Some("2025-03-14")), ("hof-1", None)] {
+            save_result(&NewRaceResult {
+                race_id: "DAY001".to_string(),
+                course_key: "daily-test".to_string(),
+                player_name: player.to_string(),
+                player_id: player.to_string(),
+                finish_time: 1000,
+                race_start_time: 1_700_000_000_000,
+                path_s3_key: "paths/test.bin".to_string(),
+                status: ResultStatus::Finished,
+                daily_date: daily_date.map(str::to_string),
+                weather_start_time: None,
+                polar: "vr-imoca-full-pack".to_string(),
+                splits: vec![],
+                stats: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let hall_of_fame =
+            get_leaderboard("daily-test", "vr-imoca-full-pack", Period::ALL_TIME, 10)
+                .await
+                .unwrap();
+        assert_eq!(hall_of_fame.len(), 1);
+        assert_eq!(hall_of_fame[0].player_name, "hof-1");
+
+        let daily = get_daily_leaderboard("2025-03-14", 10).await.unwrap();
+        assert_eq!(daily.len(), 1);
+        assert_eq!(daily[0].player_name, "daily-1");
+        assert!(get_daily_leaderboard("2025-03-15", 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_random_weather_results_not_in_hall_of_fame() {
+        db::init_test().await.unwrap();
+
+        for (player, weather_start_time) in [("random-1", Some(1_600_000_000_000)), ("hof-1", None)] {
+            save_result(&NewRaceResult {
+                race_id: "RND001".to_string(),
+                course_key: "weather-test".to_string(),
+                player_name: player.to_string(),
+                player_id: player.to_string(),
+                finish_time: 1000,
+                race_start_time: 1_700_000_000_000,
+                path_s3_key: "paths/test.bin".to_string(),
+                status: ResultStatus::Finished,
+                daily_date: None,
+                weather_start_time,
+                polar: "vr-imoca-full-pack".to_string(),
+                splits: vec![],
+                stats: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let hall_of_fame =
+            get_leaderboard("weather-test", "vr-imoca-full-pack", Period::ALL_TIME, 10)
+                .await
+                .unwrap();
+        assert_eq!(hall_of_fame.len(), 1);
+        assert_eq!(hall_of_fame[0].player_name, "hof-1");
+    }
+
+    #[tokio::test]
+    async fn test_hall_of_fame_per_boat_class() {
+        db::init_test().await.unwrap();
+
+        for (player, polar, time) in [("imoca-1", "imoca", 1000), ("mini-1", "mini", 500)] {
+            save_result(&NewRaceResult {
+                race_id: "CLS001".to_string(),
+                course_key: "class-test".to_string(),
+                player_name: player.to_string(),
+                player_id: player.to_string(),
+                finish_time: time,
+                race_start_time: 1_700_000_000_000,
+                path_s3_key: "paths/test.bin".to_string(),
+                status: ResultStatus::Finished,
+                daily_date: None,
+                weather_start_time: None,
+                polar: polar.to_string(),
+                splits: vec![],
+                stats: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let imoca = get_leaderboard("class-test", "imoca", Period::ALL_TIME, 10)
+            .await
+            .unwrap();
+        assert_eq!(imoca.len(), 1);
+        assert_eq!(imoca[0].player_name, "imoca-1");
+        assert_eq!(imoca[0].rank, 1);
+        assert_eq!(
+            get_leaderboard("class-test", "mini", Period::ALL_TIME, 10)
+                .await
+                .unwrap()[0]
+                .player_name,
+            "mini-1"
+        );
+        assert_eq!(hall_of_fame_rank("class-test", "imoca", 1000).await.unwrap(), 1);
+        assert_eq!(hall_of_fame_rank("class-test", "imoca", 1500).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_splits_in_leaderboard() {
+        db::init_test().await.unwrap();
+
+        let id = save_result(&NewRaceResult {
+            race_id: "SPL001".to_string(),
+            course_key: "splits-test".to_string(),
+            player_name: "splitter".to_string(),
+            player_id: "splitter".to_string(),
+            finish_time: 3000,
+            race_start_time: 1_700_000_000_000,
+            path_s3_key: "paths/test.bin".to_string(),
+            status: ResultStatus::Finished,
+            daily_date: None,
+            weather_start_time: None,
+            polar: "imoca".to_string(),
+            splits: vec![1000, 2000],
+            stats: Some(RaceStats {
+                distance_nm: 12.5,
+                avg_speed: 6.2,
+                max_speed: 9.8,
+                tacks: 3,
+                gybes: 1,
+            }),
+        })
+        .await
+        .unwrap();
+
+        let entries = get_leaderboard("splits-test", "imoca", Period::ALL_TIME, 10)
+            .await
+            .unwrap();
+        assert_eq!(entries[0].splits, vec![1000, 2000]);
+        assert_eq!(entries[0].stats.as_ref().map(|stats| stats.tacks), Some(3));
+
+        delete_result(id).await.unwrap();
+        assert!(get_splits(&[id]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_leaderboard_csv() {
+        db::init_test().await.unwrap();
+
+        for (name, time, status) in [
+            ("Slow, \"Bob\"", 5000, ResultStatus::Finished),
+            ("=HYPERLINK()", 4000, ResultStatus::Finished),
+            ("Quitter", 1000, ResultStatus::Retired),
+        ] {
+            save_result(&NewRaceResult {
+                race_id: "CSV001".to_string(),
+                course_key: "csv-test".to_string(),
+                player_name: name.to_string(),
+                player_id: format!("csv-{}", time),
+                finish_time: time,
+                race_start_time: 1_700_000_000_000,
+                path_s3_key: "paths/test.bin".to_string(),
+                status,
+                daily_date: None,
+                weather_start_time: None,
+                polar: "imoca".to_string(),
+                splits: vec![],
+                stats: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let lines: Vec<String> = leaderboard_csv("csv-test".to_string())
+            .map(|line| line.unwrap())
+            .collect()
+            .await;
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("rank,polar,player_name,"));
+        assert!(lines[1].starts_with("1,imoca,'=HYPERLINK(),csv-4000,finished,4000,0d 00:00:04,"));
+        assert!(lines[2].starts_with("2,imoca,\"Slow, \"\"Bob\"\"\",csv-5000,"));
+        assert!(lines[3].starts_with(",imoca,Quitter,csv-1000,retired,1000,"));
+        assert!(lines[3].ends_with("\r\n"));
+    }
+
+    #[test]
+    fn test_simplify_path() {
+        // A straight line east with a 1 nm bump north in the middle
+        let mut points: Vec<PathPoint> = (0..=100)
+            .map(|i| point(i * 1000, i as f32 * 0.01, 0.0, 90.0))
+            .collect();
+        points[50].lat = 1.0 / 60.0;
+
+        let simplified = simplify_path(&points, 0.1);
+        let times: Vec<i64> = simplified.iter().map(|p| p.race_time).collect();
+        assert_eq!(times, vec![0, 49_000, 50_000, 51_000, 100_000]);
+
+        // Within the tolerance only the ends are left
+        assert_eq!(simplify_path(&points, 2.0).len(), 2);
+        assert_eq!(simplify_path(&points[..2], 2.0).len(), 2);
+    }
+
+    #[test]
+    fn test_interpolate_position() {
+        let points = vec![point(0, 0.0, 0.0, 350.0), point(1000, 1.0, 2.0, 10.0)];
+
+        let mid = interpolate_position(&points, 500).unwrap();
+        assert!((mid.lng - 0.5).abs() < 1e-6);
+        assert!((mid.lat - 1.0).abs() < 1e-6);
+        assert!(mid.heading.abs() < 1e-3 || (mid.heading - 360.0).abs() < 1e-3);
+
+        assert_eq!(interpolate_position(&points, 1000).unwrap().lat, 2.0);
+        assert!(interpolate_position(&points, 1001).is_none());
+        assert!(interpolate_position(&[], 0).is_none());
+
+        // Across the antimeridian
+        let points = vec![point(0, 179.5, 0.0, 90.0), point(1000, -179.5, 0.0, 90.0)];
+        let mid = interpolate_position(&points, 250).unwrap();
+        assert!((mid.lng - 179.75).abs() < 1e-4);
+        let mid = interpolate_position(&points, 750).unwrap();
+        assert!((mid.lng + 179.75).abs() < 1e-4);
+    }
+}
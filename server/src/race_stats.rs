@@ -0,0 +1,279 @@
+//! Statistics of a sailed path, computed when its result is saved: distance,
+//! average and max speed, tacks and gybes.
+//!
+//! Speeds are in knots of course time. Turns are found in the headings of the
+//! path, then told apart with the wind of the report in effect: a turn
+//! sweeping the bow through the wind is a tack, the stern a gybe.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::courses::LngLat;
+use crate::race_results::PathPoint;
+use crate::simulation::{self, WindRaster};
+use crate::{geo, wind_reports};
+
+const HOUR_MS: f64 = 60.0 * 60.0 * 1000.0;
+
+/// Shortest course time the max speed is measured over, so position
+/// rounding between close samples doesn't make up speed
+const SPEED_WINDOW_MS: i64 = 60 * 1000;
+
+/// Heading change between two samples of a boat turning. Boats turn at
+/// least 35° a second and are sampled every 100 ms.
+const TURNING_DEG: f64 = 1.0;
+
+/// Smallest turn that may be a tack or a gybe
+const MIN_TURN_DEG: f64 = 20.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RaceStats {
+    pub distance_nm: f64,
+    pub avg_speed: f64,
+    pub max_speed: f64,
+    pub tacks: u32,
+    pub gybes: u32,
+}
+
+/// A continuous turn of the boat
+#[derive(Debug, Clone)]
+struct Turn {
+    race_time: i64,
+    position: LngLat,
+    heading_before: f64,
+    /// Signed, positive to starboard
+    angle: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Maneuver {
+    Tack,
+    Gybe,
+}
+
+fn position(point: &PathPoint) -> LngLat {
+    LngLat {
+        lng: point.lng as f64,
+        lat: point.lat as f64,
+    }
+}
+
+fn signed_angle(degrees: f64) -> f64 {
+    (degrees + 540.0).rem_euclid(360.0) - 180.0
+}
+
+/// Distance sailed, average and max speed
+fn distance_and_speeds(path: &[PathPoint]) -> (f64, f64, f64) {
+    let distance: f64 = path
+        .windows(2)
+        .map(|w| geo::distance_nm(&position(&w[0]), &position(&w[1])))
+        .sum();
+    let duration = match (path.first(), path.last()) {
+        (Some(first), Some(last)) => (last.race_time - first.race_time) as f64,
+        _ => 0.0,
+    };
+    let avg_speed = if duration > 0.0 {
+        distance / (duration / HOUR_MS)
+    } else {
+        0.0
+    };
+
+    // Speed over the shortest stretch of samples lasting the window
+    let mut max_speed: f64 = 0.0;
+    let mut from = 0;
+    let mut stretch = 0.0;
+    for to in 1..path.len() {
+        stretch += geo::distance_nm(&position(&path[to - 1]), &position(&path[to]));
+        while from + 1 < to && path[to].race_time - path[from + 1].race_time >= SPEED_WINDOW_MS {
+            stretch -= geo::distance_nm(&position(&path[from]), &position(&path[from + 1]));
+            from += 1;
+        }
+        let elapsed = path[to].race_time - path[from].race_time;
+        if elapsed >= SPEED_WINDOW_MS {
+            max_speed = max_speed.max(stretch / (elapsed as f64 / HOUR_MS));
+        }
+    }
+
+    // Paths shorter than the window only have their average
+    (distance, avg_speed, max_speed.max(avg_speed))
+}
+
+/// Turns of at least `MIN_TURN_DEG`: runs of samples turning the same way
+fn turns(path: &[PathPoint]) -> Vec<Turn> {
+    let mut turns = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut angle: f64 = 0.0;
+
+    let close = |start: usize, end: usize, angle: f64, turns: &mut Vec<Turn>| {
+        if angle.abs() >= MIN_TURN_DEG {
+            let middle = &path[(start + end) / 2];
+            turns.push(Turn {
+                race_time: middle.race_time,
+                position: position(middle),
+                heading_before: path[start].heading as f64,
+                angle,
+            });
+        }
+    };
+
+    for i in 1..path.len() {
+        let change = signed_angle((path[i].heading - path[i - 1].heading) as f64);
+        let turning = change.abs() >= TURNING_DEG;
+        match start {
+            Some(_) if turning && change.signum() == angle.signum() => angle += change,
+            Some(from) => {
+                close(from, i - 1, angle, &mut turns);
+                (start, angle) = if turning {
+                    (Some(i - 1), change)
+                } else {
+                    (None, 0.0)
+                };
+            }
+            None if turning => (start, angle) = (Some(i - 1), change),
+            None => {}
+        }
+    }
+    if let Some(from) = start {
+        close(from, path.len() - 1, angle, &mut turns);
+    }
+    turns
+}
+
+/// Tacks and gybes of a turn, with the wind blowing from `wind_direction`.
+/// A full circle makes both.
+fn maneuvers(turn: &Turn, wind_direction: f64) -> Vec<Maneuver> {
+    // Angle to sweep from the heading before the turn to a direction
+    let sweep_to = |direction: f64| {
+        let offset = direction - turn.heading_before;
+        if turn.angle > 0.0 {
+            offset.rem_euclid(360.0)
+        } else {
+            (-offset).rem_euclid(360.0)
+        }
+    };
+    let mut maneuvers = Vec::new();
+    if sweep_to(wind_direction) <= turn.angle.abs() {
+        maneuvers.push(Maneuver::Tack);
+    }
+    if sweep_to(wind_direction + 180.0) <= turn.angle.abs() {
+        maneuvers.push(Maneuver::Gybe);
+    }
+    maneuvers
+}
+
+/// Direction the wind blows from, in degrees
+fn wind_direction(raster: &WindRaster, position: &LngLat) -> Option<f64> {
+    let (u, v) = raster.speed_at(position.lng, position.lat)?;
+    Some((-u).atan2(-v).to_degrees().rem_euclid(360.0))
+}
+
+/// Statistics of a path. Turns are only counted as tacks or gybes when the
+/// wind at their time loads.
+pub async fn compute(path: &[PathPoint]) -> Result<RaceStats> {
+    let (distance_nm, avg_speed, max_speed) = distance_and_speeds(path);
+    let turns = turns(path);
+
+    let (mut tacks, mut gybes) = (0, 0);
+    if let (Some(first), Some(last)) = (turns.first(), turns.last()) {
+        let day = 24 * 60 * 60 * 1000;
+        let reports =
+            wind_reports::get_reports_between(first.race_time - day, last.race_time).await?;
+        let mut rasters: HashMap<String, WindRaster> = HashMap::new();
+        for turn in &turns {
+            // The report in effect: the last one at or before the turn
+            let Some(report) = reports
+                .iter()
+                .rev()
+                .find(|report| report.time.timestamp_millis() <= turn.race_time)
+            else {
+                continue;
+            };
+            if !rasters.contains_key(&report.png_path) {
+                match simulation::load_raster(&report.png_path).await {
+                    Ok(raster) => {
+                        rasters.insert(report.png_path.clone(), raster);
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to load wind for race stats: {}", e);
+                        continue;
+                    }
+                }
+            }
+            let Some(direction) = wind_direction(&rasters[&report.png_path], &turn.position) else {
+                continue;
+            };
+            for maneuver in maneuvers(turn, direction) {
+                match maneuver {
+                    Maneuver::Tack => tacks += 1,
+                    Maneuver::Gybe => gybes += 1,
+                }
+            }
+        }
+    }
+
+    Ok(RaceStats {
+        distance_nm,
+        avg_speed,
+        max_speed,
+        tacks,
+        gybes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One point a minute sailing `headings` at 6 knots (0.1 nm a minute)
+    fn sailed(headings: &[f32]) -> Vec<PathPoint> {
+        let mut position = LngLat { lng: 0.0, lat: 0.0 };
+        headings
+            .iter()
+            .enumerate()
+            .map(|(i, &heading)| {
+                if i > 0 {
+                    position = geo::destination(&position, heading as f64, 0.1);
+                }
+                PathPoint {
+                    race_time: i as i64 * 60 * 1000,
+                    lng: position.lng as f32,
+                    lat: position.lat as f32,
+                    heading,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_distance_and_speeds() {
+        let (distance, avg, max) = distance_and_speeds(&sailed(&[0.0; 61]));
+        assert!((distance - 6.0).abs() < 0.01);
+        assert!((avg - 6.0).abs() < 0.01);
+        assert!((max - 6.0).abs() < 0.01);
+        assert_eq!(distance_and_speeds(&[]), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_turns() {
+        // Close hauled on starboard (heading 45°, wind from the north), a
+        // tack to 315°, a small course change then a bear away to 200°
+        let headings = [
+            45.0, 45.0, 30.0, 10.0, 350.0, 330.0, 315.0, 315.0, 320.0, 320.0,
+        ]
+        .into_iter()
+        .chain([260.0, 200.0, 200.0]);
+        let turns = turns(&sailed(&headings.collect::<Vec<f32>>()));
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].heading_before, 45.0);
+        assert_eq!(turns[0].angle, -90.0);
+        assert_eq!(turns[1].angle, -120.0);
+
+        assert_eq!(maneuvers(&turns[0], 0.0), vec![Maneuver::Tack]);
+        // From 320° to 200° the stern sweeps through the wind from 90°
+        assert_eq!(maneuvers(&turns[1], 90.0), vec![Maneuver::Gybe]);
+        assert!(maneuvers(&turns[1], 0.0).is_empty());
+    }
+}
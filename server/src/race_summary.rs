@@ -0,0 +1,125 @@
+//! Summary emailed to a signed in player once their finish is saved: race
+//! time, rank at the finish, gate splits and a link to the replay, in the
+//! language of their account. Accounts opt out with `race_summary_emails`.
+
+use anyhow::Result;
+
+use crate::config::config;
+use crate::email_templates::{Locale, RaceSummary};
+use crate::race_results::{NewRaceResult, ResultStatus};
+use crate::{courses, db, email, units};
+
+/// Email of the account owning a profile, with its language, when it wants
+/// race summaries
+async fn recipient(profile_id: &str) -> Result<Option<(String, Option<String>)>> {
+    Ok(sqlx::query_as(
+        "SELECT accounts.email, accounts.language FROM profiles
+         JOIN accounts ON accounts.id = profiles.account_id
+         WHERE profiles.id = $1 AND accounts.race_summary_emails <> 0
+         AND accounts.delete_at IS NULL",
+    )
+    .bind(profile_id)
+    .fetch_optional(db::pool())
+    .await?)
+}
+
+/// Rank of a finish among the finishers of its race saved so far
+async fn rank_at_finish(result: &NewRaceResult) -> Result<u32> {
+    let (ahead,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM race_results
+         WHERE race_id = $1 AND status = 'finished' AND finish_time < $2",
+    )
+    .bind(&result.race_id)
+    .bind(result.finish_time)
+    .fetch_one(db::pool())
+    .await?;
+    Ok(ahead as u32 + 1)
+}
+
+/// Queue the summary of a saved result, for finishes of signed in players
+/// who didn't opt out
+pub async fn send(result_id: i64, result: &NewRaceResult) -> Result<()> {
+    if result.status != ResultStatus::Finished {
+        return Ok(());
+    }
+    let Some((email_addr, language)) = recipient(&result.player_id).await? else {
+        return Ok(());
+    };
+
+    let course = courses::get_by_key(&result.course_key)
+        .await?
+        .map_or_else(|| result.course_key.clone(), |course| course.name);
+    let summary = RaceSummary {
+        player: result.player_name.clone(),
+        course,
+        rank: rank_at_finish(result).await?,
+        time: units::format_elapsed(result.finish_time),
+        splits: result
+            .splits
+            .iter()
+            .map(|split| units::format_elapsed(*split))
+            .collect(),
+        distance_nm: result
+            .stats
+            .as_ref()
+            .map(|stats| format!("{:.1}", stats.distance_nm)),
+        avg_speed: result
+            .stats
+            .as_ref()
+            .map(|stats| format!("{:.1}", stats.avg_speed)),
+        url: format!(
+            "{}/?replay={}",
+            config().client_url.trim_end_matches('/'),
+            result_id
+        ),
+    };
+    let locale = Locale::or_default(language.as_deref());
+    email::send_race_summary(&email_addr, locale, &summary).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{account, auth, profiles, race_results};
+
+    #[tokio::test]
+    async fn test_recipient_and_rank() {
+        db::init_test().await.unwrap();
+        let account_id = auth::get_or_create_account("summary@example.com")
+            .await
+            .unwrap();
+        let profile = profiles::list_profiles(&account_id).await.unwrap()[0]
+            .id
+            .clone();
+
+        let result = |player: &str, finish_time| NewRaceResult {
+            race_id: "summary-race".to_string(),
+            course_key: "vg20".to_string(),
+            player_name: player.to_string(),
+            player_id: player.to_string(),
+            finish_time,
+            race_start_time: 7,
+            path_s3_key: format!("paths/{}.bin", player),
+            status: ResultStatus::Finished,
+            daily_date: None,
+            weather_start_time: None,
+            polar: "imoca".to_string(),
+            splits: vec![],
+            stats: None,
+        };
+        race_results::save_result(&result("summary-first", 1000))
+            .await
+            .unwrap();
+        assert_eq!(rank_at_finish(&result(&profile, 2000)).await.unwrap(), 2);
+
+        assert_eq!(
+            recipient(&profile).await.unwrap(),
+            Some(("summary@example.com".to_string(), None))
+        );
+        account::set_race_summary_emails(&account_id, false)
+            .await
+            .unwrap();
+        assert_eq!(recipient(&profile).await.unwrap(), None);
+        assert!(!account::race_summary_emails(&account_id).await.unwrap());
+    }
+}
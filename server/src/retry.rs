@@ -1,8 +1,13 @@
 use rand::Rng;
+use std::fmt;
 use std::future::Future;
-use std::time::Duration;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
 use tokio::time::sleep;
 
+use crate::db;
+
 /// Internal error type to distinguish retryable vs non-retryable failures.
 pub enum RetryError {
     /// Retryable error (network issues, server errors, mid-stream failures)
@@ -68,3 +73,283 @@ fn backoff_with_jitter(attempt: u32, config: &RetryConfig) -> Duration {
     let delay_ms = (base_delay as i64 + jitter).max(0) as u64;
     Duration::from_millis(delay_ms)
 }
+
+/// Error of the calls refused while a circuit breaker is open
+#[derive(Debug)]
+pub struct CircuitOpen;
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Service unavailable, not retrying for now")
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+/// Fails calls fast after repeated retryable failures of a service, for a
+/// cooldown, instead of waiting on a service that is down.
+pub struct CircuitBreaker {
+    /// Consecutive failures opening the circuit
+    threshold: u32,
+    /// Time the circuit stays open before letting a call through again
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub const fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            state: Mutex::new(BreakerState {
+                failures: 0,
+                open_until: None,
+            }),
+        }
+    }
+
+    /// Whether calls are refused
+    pub fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.open_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = 0;
+        state.open_until = None;
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        if state.failures >= self.threshold {
+            if state.open_until.is_none_or(|until| Instant::now() >= until) {
+                log::error!(
+                    "{} consecutive failures, failing calls for {:?}",
+                    state.failures,
+                    self.cooldown
+                );
+            }
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+
+    /// Run a single attempt through the breaker: refused while open,
+    /// retryable failures counted toward opening it
+    pub async fn call<Fut, T>(&self, fut: Fut) -> Result<T, RetryError>
+    where
+        Fut: Future<Output = Result<T, RetryError>>,
+    {
+        if self.is_open() {
+            return Err(RetryError::NonRetryable(CircuitOpen.into()));
+        }
+        let result = fut.await;
+        match &result {
+            Ok(_) | Err(RetryError::NonRetryable(_)) => self.record_success(),
+            Err(RetryError::Retryable(_)) => self.record_failure(),
+        }
+        result
+    }
+}
+
+/// `with_retry` through a circuit breaker: attempts stop as soon as it opens
+pub async fn with_retry_breaker<F, Fut, T>(
+    breaker: &CircuitBreaker,
+    func: F,
+    config: &RetryConfig,
+) -> Result<T, RetryError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, RetryError>>,
+{
+    with_retry(|| breaker.call(func()), config).await
+}
+
+/// A table of jobs run by background workers and retried with exponential
+/// backoff, for outgoing emails and webhooks. Its rows have an `id`, a
+/// `status` (`pending`, or `dead` once given up on), `attempts`,
+/// `next_attempt_at` and `last_error`. Done jobs are deleted, dead ones kept
+/// with their last error.
+///
+/// Workers of several instances share a table: a worker leases each job for
+/// `lease_ms` before running it.
+pub struct LeasedQueue {
+    table: &'static str,
+    /// Attempts before a job is dead-lettered
+    pub max_attempts: i64,
+    /// Delay after the first failure, doubled after each one
+    pub base_backoff_ms: i64,
+    pub max_backoff_ms: i64,
+    /// Time a worker has to run a job before others may try it again
+    pub lease_ms: i64,
+    /// Interval between checks for due retries, new jobs waking the worker
+    pub poll_interval: Duration,
+    /// Jobs fetched by each check
+    pub batch_size: i64,
+    queued: Notify,
+}
+
+impl LeasedQueue {
+    /// Queue of a table: 8 attempts, backoff from 30 s to an hour, jobs
+    /// leased for 5 minutes and checked for 20 at a time every 15 s
+    pub const fn new(table: &'static str) -> Self {
+        LeasedQueue {
+            table,
+            max_attempts: 8,
+            base_backoff_ms: 30 * 1000,
+            max_backoff_ms: 60 * 60 * 1000,
+            lease_ms: 5 * 60 * 1000,
+            poll_interval: Duration::from_secs(15),
+            batch_size: 20,
+            queued: Notify::const_new(),
+        }
+    }
+
+    /// Same queue with jobs leased for `lease_ms`
+    pub const fn with_lease(mut self, lease_ms: i64) -> Self {
+        self.lease_ms = lease_ms;
+        self
+    }
+
+    /// Wake the worker for a new job
+    pub fn notify(&self) {
+        self.queued.notify_one();
+    }
+
+    /// Delay before the next attempt after `attempts` failures
+    pub fn backoff_ms(&self, attempts: i64) -> i64 {
+        let exponent = (attempts - 1).clamp(0, 20) as u32;
+        (self.base_backoff_ms * 2_i64.pow(exponent)).min(self.max_backoff_ms)
+    }
+
+    /// Lease a due job, false when another worker just did
+    pub async fn lease(&self, id: i64, now: i64) -> anyhow::Result<bool> {
+        let leased = sqlx::query(&format!(
+            "UPDATE {} SET next_attempt_at = $1
+             WHERE id = $2 AND status = 'pending' AND next_attempt_at <= $3",
+            self.table
+        ))
+        .bind(now + self.lease_ms)
+        .bind(id)
+        .bind(now)
+        .execute(db::pool())
+        .await?;
+        Ok(leased.rows_affected() > 0)
+    }
+
+    /// Delete a job once done
+    pub async fn complete(&self, id: i64) -> anyhow::Result<()> {
+        sqlx::query(&format!("DELETE FROM {} WHERE id = $1", self.table))
+            .bind(id)
+            .execute(db::pool())
+            .await?;
+        Ok(())
+    }
+
+    /// Record a failed attempt of a job tried `attempts` times before: it is
+    /// retried after its backoff, or dead-lettered after the last attempt.
+    /// Returns whether it was dead-lettered.
+    pub async fn fail(
+        &self,
+        id: i64,
+        attempts: i64,
+        error: &str,
+        now: i64,
+    ) -> anyhow::Result<bool> {
+        let attempts = attempts + 1;
+        if attempts >= self.max_attempts {
+            self.dead_letter(id, error).await?;
+            return Ok(true);
+        }
+        sqlx::query(&format!(
+            "UPDATE {} SET attempts = $1, next_attempt_at = $2, last_error = $3 WHERE id = $4",
+            self.table
+        ))
+        .bind(attempts)
+        .bind(now + self.backoff_ms(attempts))
+        .bind(error)
+        .bind(id)
+        .execute(db::pool())
+        .await?;
+        Ok(false)
+    }
+
+    /// Give up on a job, keeping it with its error
+    pub async fn dead_letter(&self, id: i64, error: &str) -> anyhow::Result<()> {
+        sqlx::query(&format!(
+            "UPDATE {} SET status = 'dead', attempts = attempts + 1, last_error = $1
+             WHERE id = $2",
+            self.table
+        ))
+        .bind(error)
+        .bind(id)
+        .execute(db::pool())
+        .await?;
+        Ok(())
+    }
+
+    /// Run the due jobs with `process` as they are queued and when retries
+    /// are due, until the server stops
+    pub async fn run_worker<F, Fut>(&self, process: F)
+    where
+        F: Fn(i64) -> Fut,
+        Fut: Future<Output = anyhow::Result<usize>>,
+    {
+        loop {
+            if let Err(e) = process(chrono::Utc::now().timestamp_millis()).await {
+                log::error!("Jobs of {} failed: {}", self.table, e);
+            }
+            tokio::select! {
+                _ = sleep(self.poll_interval) => {}
+                _ = self.queued.notified() => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_backoff() {
+        let queue = LeasedQueue::new("email_queue");
+        assert_eq!(queue.backoff_ms(1), queue.base_backoff_ms);
+        assert_eq!(queue.backoff_ms(3), 4 * queue.base_backoff_ms);
+        assert_eq!(
+            queue.backoff_ms(queue.max_attempts * 10),
+            queue.max_backoff_ms
+        );
+    }
+
+    async fn failing() -> Result<(), RetryError> {
+        Err(RetryError::Retryable(anyhow::anyhow!("timeout")))
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(50));
+        assert!(breaker.call(failing()).await.is_err());
+        assert!(!breaker.is_open());
+        assert!(breaker.call(failing()).await.is_err());
+        assert!(breaker.is_open());
+
+        // Refused without running the call
+        let refused = breaker.call(async { Ok(()) }).await;
+        assert!(matches!(
+            refused,
+            Err(RetryError::NonRetryable(e)) if e.is::<CircuitOpen>()
+        ));
+
+        sleep(Duration::from_millis(60)).await;
+        assert!(breaker.call(async { Ok(()) }).await.is_ok());
+        assert!(breaker.call(failing()).await.is_err());
+        assert!(!breaker.is_open());
+    }
+}
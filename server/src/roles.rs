@@ -0,0 +1,269 @@
+//! Staff roles of accounts, and the permissions they grant on the admin
+//! endpoints. Roles are managed with the CLI or by admins through the API.
+//!
+//! `REWIND_ADMIN_EMAIL` only bootstraps the first admin at startup.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audit::{self, Action},
+    auth, db,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// Everything, including accounts and roles
+    Admin,
+    /// Moderates results, schedules races and series
+    Moderator,
+    /// Edits courses and polars
+    CourseEditor,
+}
+
+/// What a role allows on the admin endpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// List and delete accounts, grant and revoke roles
+    ManageAccounts,
+    /// List and delete race results
+    ModerateResults,
+    /// Schedule races and manage series
+    ManageRaces,
+    /// Create, edit and reorder courses, tune polars
+    ManageCourses,
+    /// See the ops dashboard figures
+    ViewStats,
+    /// Register and remove the outgoing webhooks
+    ManageWebhooks,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Admin, Role::Moderator, Role::CourseEditor];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Moderator => "moderator",
+            Role::CourseEditor => "course-editor",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Role> {
+        Role::ALL.into_iter().find(|role| role.as_str() == s)
+    }
+
+    pub fn grants(&self, permission: Permission) -> bool {
+        match self {
+            Role::Admin => true,
+            Role::Moderator => matches!(
+                permission,
+                Permission::ModerateResults | Permission::ManageRaces
+            ),
+            Role::CourseEditor => permission == Permission::ManageCourses,
+        }
+    }
+}
+
+/// A role given to an account
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleGrant {
+    pub account_id: String,
+    pub email: String,
+    pub role: Role,
+    pub created_at: i64,
+}
+
+/// Roles of an account
+pub async fn roles_of(account_id: &str) -> Result<Vec<Role>> {
+    let rows: Vec<(String,)> = sqlx::query_as("SELECT role FROM roles WHERE account_id = $1")
+        .bind(account_id)
+        .fetch_all(db::pool())
+        .await?;
+    let granted: Vec<Role> = rows
+        .iter()
+        .filter_map(|(role,)| Role::parse(role))
+        .collect();
+    Ok(Role::ALL
+        .into_iter()
+        .filter(|role| granted.contains(role))
+        .collect())
+}
+
+/// Whether any role of an account grants a permission
+pub async fn has_permission(account_id: &str, permission: Permission) -> Result<bool> {
+    let roles = roles_of(account_id).await?;
+    Ok(roles.iter().any(|role| role.grants(permission)))
+}
+
+/// Every role given, by account email
+pub async fn list() -> Result<Vec<RoleGrant>> {
+    let rows: Vec<(String, String, String, i64)> = sqlx::query_as(
+        "SELECT r.account_id, a.email, r.role, r.created_at
+         FROM roles r JOIN accounts a ON a.id = r.account_id
+         ORDER BY a.email, r.role",
+    )
+    .fetch_all(db::pool())
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(account_id, email, role, created_at)| {
+            Some(RoleGrant {
+                account_id,
+                email,
+                role: Role::parse(&role)?,
+                created_at,
+            })
+        })
+        .collect())
+}
+
+/// Give a role to an account. Returns false if it already had it.
+pub async fn grant(account_id: &str, role: Role) -> Result<bool> {
+    let result =
+        sqlx::query("INSERT INTO roles (account_id, role) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(account_id)
+            .bind(role.as_str())
+            .execute(db::pool())
+            .await?;
+    if result.rows_affected() > 0 {
+        log::info!("Granted role {} to account {}", role.as_str(), account_id);
+    }
+    Ok(result.rows_affected() > 0)
+}
+
+/// Take a role from an account. The last admin can't be removed. Returns
+/// false if the account didn't have the role.
+pub async fn revoke(account_id: &str, role: Role) -> Result<bool> {
+    if role == Role::Admin {
+        let (admins,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM roles WHERE role = $1 AND account_id != $2")
+                .bind(Role::Admin.as_str())
+                .bind(account_id)
+                .fetch_one(db::pool())
+                .await?;
+        if admins == 0 {
+            anyhow::bail!("Cannot remove the last admin");
+        }
+    }
+
+    let result = sqlx::query("DELETE FROM roles WHERE account_id = $1 AND role = $2")
+        .bind(account_id)
+        .bind(role.as_str())
+        .execute(db::pool())
+        .await?;
+    if result.rows_affected() > 0 {
+        log::info!("Revoked role {} from account {}", role.as_str(), account_id);
+    }
+    Ok(result.rows_affected() > 0)
+}
+
+/// Make the account of `email` an admin, creating it if needed. Used at
+/// startup so a new deployment has someone to grant the other roles.
+pub async fn bootstrap_admin(email: &str) -> Result<()> {
+    let account_id = auth::get_or_create_account(&email.to_lowercase()).await?;
+    if grant(&account_id, Role::Admin).await? {
+        let payload = serde_json::json!({ "role": Role::Admin });
+        audit::record(None, Action::RoleGrant, &account_id, payload).await?;
+    }
+    Ok(())
+}
+
+/// Give a role to the account of an email, from the CLI.
+pub async fn grant_by_email(email: &str, role: Role) -> Result<()> {
+    let account_id = auth::get_or_create_account(&email.to_lowercase()).await?;
+    if !grant(&account_id, role).await? {
+        println!("{} is already {}", email, role.as_str());
+        return Ok(());
+    }
+    let payload = serde_json::json!({ "role": role });
+    audit::record(None, Action::RoleGrant, &account_id, payload).await
+}
+
+/// Take a role from the account of an email, from the CLI.
+pub async fn revoke_by_email(email: &str, role: Role) -> Result<()> {
+    let account_id = auth::find_account_by_email(&email.to_lowercase())
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No account for {}", email))?;
+    if !revoke(&account_id, role).await? {
+        println!("{} is not {}", email, role.as_str());
+        return Ok(());
+    }
+    let payload = serde_json::json!({ "role": role });
+    audit::record(None, Action::RoleRevoke, &account_id, payload).await
+}
+
+/// Print every role given, from the CLI.
+pub async fn print_all() -> Result<()> {
+    for grant in list().await? {
+        println!("{}\t{}", grant.email, grant.role.as_str());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permissions() {
+        assert!(Role::Admin.grants(Permission::ManageAccounts));
+        assert!(Role::Moderator.grants(Permission::ModerateResults));
+        assert!(!Role::Moderator.grants(Permission::ManageCourses));
+        assert!(Role::CourseEditor.grants(Permission::ManageCourses));
+        assert!(!Role::CourseEditor.grants(Permission::ManageAccounts));
+        let role: Role = serde_json::from_str(r#""course-editor""#).unwrap();
+        assert_eq!(role, Role::CourseEditor);
+    }
+
+    #[tokio::test]
+    async fn test_grant_and_revoke() {
+        db::init_test().await.unwrap();
+
+        let editor = auth::get_or_create_account("editor@example.com")
+            .await
+            .unwrap();
+        assert!(
+            !has_permission(&editor, Permission::ManageCourses)
+                .await
+                .unwrap()
+        );
+
+        assert!(grant(&editor, Role::CourseEditor).await.unwrap());
+        assert!(!grant(&editor, Role::CourseEditor).await.unwrap());
+        assert!(
+            has_permission(&editor, Permission::ManageCourses)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !has_permission(&editor, Permission::ModerateResults)
+                .await
+                .unwrap()
+        );
+        assert_eq!(roles_of(&editor).await.unwrap(), vec![Role::CourseEditor]);
+
+        assert!(revoke(&editor, Role::CourseEditor).await.unwrap());
+        assert!(roles_of(&editor).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_last_admin_is_kept() {
+        db::init_test().await.unwrap();
+
+        let first = auth::get_or_create_account("first-admin@example.com")
+            .await
+            .unwrap();
+        let second = auth::get_or_create_account("second-admin@example.com")
+            .await
+            .unwrap();
+        grant(&first, Role::Admin).await.unwrap();
+        grant(&second, Role::Admin).await.unwrap();
+
+        assert!(revoke(&second, Role::Admin).await.unwrap());
+        assert!(revoke(&first, Role::Admin).await.is_err());
+    }
+}
@@ -0,0 +1,553 @@
+//! Isochrone weather routing over the stored wind reports.
+//!
+//! From the origin, every heading is sailed for a time step; of the points
+//! reached, the furthest from the origin in each bearing sector form the next
+//! isochrone. The first step in which a point of the isochrone can sail
+//! straight to the destination gives the route and its ETA.
+//!
+//! Steps are CPU bound and run on blocking threads, the rasters they need
+//! loaded before each one. At most `MAX_SEARCHES` searches run at once.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use ts_rs::TS;
+
+use crate::courses::LngLat;
+use crate::geo::{self, bearing, distance_nm};
+use crate::polars::Polar;
+use crate::simulation::{self, RaceWind};
+use crate::{land, units, wind_reports};
+
+/// Time between isochrones, in hours of course time
+const STEP_HOURS: f64 = 1.0;
+
+/// Angle between the headings tried from each point, in degrees
+const HEADING_STEP: f64 = 5.0;
+
+/// Bearing sectors from the origin keeping one point each
+const SECTORS: usize = 180;
+
+/// Longest route searched, in days of course time
+pub const MAX_DAYS: u32 = 10;
+
+/// Most marks rounded by a route
+pub const MAX_MARKS: usize = 50;
+
+/// Searches running at once, others being turned away
+const MAX_SEARCHES: usize = 4;
+
+static SEARCHES: Semaphore = Semaphore::const_new(MAX_SEARCHES);
+
+const HOUR_MS: i64 = 60 * 60 * 1000;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteRequest {
+    pub origin: LngLat,
+    pub destination: LngLat,
+    /// Departure, in ms since epoch
+    pub start_time: i64,
+    /// Polar name of the boat class
+    pub polar: String,
+    /// Longest route searched, `MAX_DAYS` when absent
+    #[serde(default)]
+    pub max_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutePoint {
+    pub lng: f64,
+    pub lat: f64,
+    pub time: i64,
+    /// Heading and speed (knots) sailed from this point, None at the destination
+    pub heading: Option<f64>,
+    pub speed: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Route {
+    pub path: Vec<RoutePoint>,
+    /// Arrival at the destination, in ms since epoch
+    pub eta: i64,
+    pub distance_nm: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HintRequest {
+    pub position: LngLat,
+    pub mark: LngLat,
+    /// Course time, in ms since epoch
+    pub time: i64,
+    /// Polar name of the boat class
+    pub polar: String,
+}
+
+/// Best way to sail toward a mark in the wind of the moment
+#[derive(Debug, Clone, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+pub struct Hint {
+    /// Signed true wind angle, heading = wind direction - TWA
+    pub twa: f64,
+    pub heading: f64,
+    /// Boat speed in knots
+    pub speed: f64,
+    /// Speed made good toward the mark, in knots
+    pub vmg: f64,
+    /// Bearing of the mark
+    pub bearing: f64,
+    /// True wind speed in knots and direction (where it blows from)
+    pub tws: f64,
+    pub twd: f64,
+}
+
+/// Position after sailing `distance_nm` on `heading`, moving like the boats
+/// of the simulation
+fn sail(from: &LngLat, heading: f64, distance_nm: f64) -> LngLat {
+    let distance_km = units::nm_to_km(distance_nm);
+    let heading = heading.to_radians();
+    let lng = from.lng + distance_km * heading.sin() / (111.0 * from.lat.to_radians().cos());
+    LngLat {
+        lng: geo::wrap_lng(lng),
+        lat: geo::clamp_lat(from.lat + distance_km * heading.cos() / 111.0),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    position: LngLat,
+    /// Index of the point it was sailed from in the previous isochrone
+    parent: usize,
+    /// Heading and speed sailed from the parent
+    heading: f64,
+    speed: f64,
+    /// Distance from the origin, in nautical miles
+    reach: f64,
+}
+
+/// Error of the searches turned away while `MAX_SEARCHES` are running
+#[derive(Debug)]
+pub struct Busy;
+
+impl fmt::Display for Busy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Too many routes being searched, please try again shortly"
+        )
+    }
+}
+
+impl std::error::Error for Busy {}
+
+/// Isochrones from an origin, advanced one step at a time in the wind
+pub struct Router {
+    polar: Arc<Polar>,
+    origin: LngLat,
+    destination: LngLat,
+    start_time: i64,
+    isochrones: Vec<Vec<Node>>,
+}
+
+impl Router {
+    pub fn new(polar: Arc<Polar>, origin: LngLat, destination: LngLat, start_time: i64) -> Self {
+        let start = Node {
+            position: origin.clone(),
+            parent: 0,
+            heading: 0.0,
+            speed: 0.0,
+            reach: 0.0,
+        };
+        Router {
+            polar,
+            origin,
+            destination,
+            start_time,
+            isochrones: vec![vec![start]],
+        }
+    }
+
+    /// Time of the last isochrone
+    pub fn time(&self) -> i64 {
+        self.start_time + (self.isochrones.len() - 1) as i64 * (STEP_HOURS * HOUR_MS as f64) as i64
+    }
+
+    /// Whether no point is left to sail from: all blocked by land or out of the wind
+    pub fn is_stuck(&self) -> bool {
+        self.isochrones.last().is_none_or(|nodes| nodes.is_empty())
+    }
+
+    /// Boat speed on `heading` in the wind (u, v), the swell of the foiling
+    /// regime averaged out
+    fn speed(&self, wind: (f64, f64), heading: f64) -> f64 {
+        let (u, v) = wind;
+        let wind_direction = (-u).atan2(-v).to_degrees();
+        let twa = (wind_direction - heading + 540.0).rem_euclid(360.0) - 180.0;
+        self.polar
+            .boat_speed(units::ms_to_knots(u.hypot(v)), twa, 0.0)
+    }
+
+    /// Sail one step from the last isochrone, in the wind at its time given by
+    /// `wind(lng, lat)`. Returns the route once the destination is in reach.
+    pub fn step(&mut self, wind: impl Fn(f64, f64) -> Option<(f64, f64)>) -> Option<Route> {
+        let last = self.isochrones.last()?;
+
+        // Earliest arrival of the points that can sail straight to the destination
+        let mut arrival: Option<(f64, usize, f64, f64)> = None;
+        for (index, node) in last.iter().enumerate() {
+            let Some(wind) = wind(node.position.lng, node.position.lat) else {
+                continue;
+            };
+            let heading = bearing(&node.position, &self.destination);
+            let speed = self.speed(wind, heading);
+            if speed <= 0.0 {
+                continue;
+            }
+            let hours = distance_nm(&node.position, &self.destination) / speed;
+            if hours <= STEP_HOURS
+                && arrival.is_none_or(|(best, ..)| hours < best)
+                && !land::crosses_land(&node.position, &self.destination)
+            {
+                arrival = Some((hours, index, heading, speed));
+            }
+        }
+        if let Some((hours, index, heading, speed)) = arrival {
+            return Some(self.route(index, heading, speed, hours));
+        }
+
+        let sector_size = 360.0 / SECTORS as f64;
+        let mut sectors: Vec<Option<Node>> = vec![None; SECTORS];
+        for (index, node) in last.iter().enumerate() {
+            let Some(wind) = wind(node.position.lng, node.position.lat) else {
+                continue;
+            };
+            for k in 0..(360.0 / HEADING_STEP) as usize {
+                let heading = k as f64 * HEADING_STEP;
+                let speed = self.speed(wind, heading);
+                if speed <= 0.0 {
+                    continue;
+                }
+                let position = sail(&node.position, heading, speed * STEP_HOURS);
+                let sector = (bearing(&self.origin, &position) / sector_size) as usize % SECTORS;
+                let reach = distance_nm(&self.origin, &position);
+                if sectors[sector]
+                    .as_ref()
+                    .is_some_and(|best| best.reach >= reach)
+                    || land::crosses_land(&node.position, &position)
+                {
+                    continue;
+                }
+                sectors[sector] = Some(Node {
+                    position,
+                    parent: index,
+                    heading,
+                    speed,
+                    reach,
+                });
+            }
+        }
+        self.isochrones
+            .push(sectors.into_iter().flatten().collect());
+        None
+    }
+
+    /// Route through the point `index` of the last isochrone, sailing from it
+    /// to the destination on `heading` at `speed` for `hours`
+    fn route(&self, index: usize, heading: f64, speed: f64, hours: f64) -> Route {
+        let step_ms = STEP_HOURS * HOUR_MS as f64;
+        let mut path = Vec::with_capacity(self.isochrones.len() + 1);
+        let mut next = (heading, speed);
+        let mut index = index;
+        for (step, isochrone) in self.isochrones.iter().enumerate().rev() {
+            let node = &isochrone[index];
+            path.push(RoutePoint {
+                lng: node.position.lng,
+                lat: node.position.lat,
+                time: self.start_time + (step as f64 * step_ms) as i64,
+                heading: Some(next.0),
+                speed: Some(next.1),
+            });
+            next = (node.heading, node.speed);
+            index = node.parent;
+        }
+        path.reverse();
+
+        let eta = self.time() + (hours * HOUR_MS as f64) as i64;
+        path.push(RoutePoint {
+            lng: self.destination.lng,
+            lat: self.destination.lat,
+            time: eta,
+            heading: None,
+            speed: None,
+        });
+        // Legs are sailed on a constant heading
+        let distance_nm = path
+            .windows(2)
+            .map(|w| {
+                let from = LngLat {
+                    lng: w[0].lng,
+                    lat: w[0].lat,
+                };
+                let to = LngLat {
+                    lng: w[1].lng,
+                    lat: w[1].lat,
+                };
+                geo::rhumb_distance_nm(&from, &to)
+            })
+            .sum();
+        Route {
+            path,
+            eta,
+            distance_nm,
+        }
+    }
+}
+
+/// Heading making the best speed toward `mark` from `position` in the wind
+/// (u, v), trying every degree
+pub fn best_vmg(polar: &Polar, wind: (f64, f64), position: &LngLat, mark: &LngLat) -> Hint {
+    let (u, v) = wind;
+    let tws = units::ms_to_knots(u.hypot(v));
+    let twd = (-u).atan2(-v).to_degrees().rem_euclid(360.0);
+    let bearing = bearing(position, mark);
+
+    let (heading, twa, speed, vmg) = (0..360)
+        .map(|heading| {
+            let heading = heading as f64;
+            let twa = (twd - heading + 540.0).rem_euclid(360.0) - 180.0;
+            let speed = polar.boat_speed(tws, twa, 0.0);
+            let vmg = speed * (heading - bearing).to_radians().cos();
+            (heading, twa, speed, vmg)
+        })
+        .max_by(|a, b| a.3.total_cmp(&b.3))
+        .unwrap_or((bearing, 0.0, 0.0, 0.0));
+    Hint {
+        twa,
+        heading,
+        speed,
+        vmg,
+        bearing,
+        tws,
+        twd,
+    }
+}
+
+/// Stored wind reports from `since` to `until` (ms since epoch), none loaded yet
+async fn stored_wind(since: i64, until: i64) -> Result<RaceWind> {
+    let reports = wind_reports::get_reports_between(since, until)
+        .await?
+        .into_iter()
+        .map(|r| (r.time.timestamp_millis(), r.png_path))
+        .collect::<Vec<_>>();
+    Ok(RaceWind::new(reports))
+}
+
+/// Load the rasters needed at `time`
+async fn load_rasters(wind: &mut RaceWind, time: i64) -> Result<()> {
+    for (report_time, png_path) in wind.missing(time) {
+        let raster = simulation::load_raster(&png_path).await?;
+        wind.insert(report_time, Arc::new(raster), time);
+    }
+    Ok(())
+}
+
+/// Wind (u, v) at a time and position in the stored wind reports
+pub async fn wind_at(time: i64, position: &LngLat) -> Result<Option<(f64, f64)>> {
+    let mut wind = stored_wind(time - 24 * HOUR_MS, time + 24 * HOUR_MS).await?;
+    load_rasters(&mut wind, time).await?;
+    Ok(wind.wind_at(time, position.lng, position.lat))
+}
+
+/// Slot of a search, `Busy` when `MAX_SEARCHES` are running
+fn search_permit() -> Result<SemaphorePermit<'static>> {
+    Ok(SEARCHES.try_acquire().map_err(|_| Busy)?)
+}
+
+/// Route a boat on `polar` in the stored wind reports. None when the
+/// destination is out of reach within the days searched or the wind coverage.
+pub async fn route(polar: &Polar, request: &RouteRequest) -> Result<Option<Route>> {
+    let _permit = search_permit()?;
+    let max_days = request.max_days.unwrap_or(MAX_DAYS).min(MAX_DAYS);
+    let until = request.start_time + max_days as i64 * 24 * HOUR_MS;
+    let mut wind = stored_wind(request.start_time - 24 * HOUR_MS, until).await?;
+    sail_leg(
+        &mut wind,
+        Arc::new(polar.clone()),
+        &request.origin,
+        &request.destination,
+        request.start_time,
+        until,
+    )
+    .await
+}
+
+/// Route rounding `marks` in turn from `origin`, each leg leaving at the
+/// arrival of the previous one, until `until` at the latest. None when a
+/// mark is out of reach.
+pub async fn route_marks(
+    polar: &Polar,
+    origin: &LngLat,
+    marks: &[LngLat],
+    start_time: i64,
+    until: i64,
+) -> Result<Option<Vec<Route>>> {
+    anyhow::ensure!(marks.len() <= MAX_MARKS, "More than {} marks", MAX_MARKS);
+    let _permit = search_permit()?;
+    let polar = Arc::new(polar.clone());
+    let mut wind = stored_wind(start_time - 24 * HOUR_MS, until).await?;
+    let mut legs = Vec::with_capacity(marks.len());
+    let (mut from, mut time) = (origin.clone(), start_time);
+    for mark in marks {
+        let Some(leg) = sail_leg(&mut wind, polar.clone(), &from, mark, time, until).await? else {
+            return Ok(None);
+        };
+        from = mark.clone();
+        time = leg.eta;
+        legs.push(leg);
+    }
+    Ok(Some(legs))
+}
+
+/// Route from `origin` to `destination` leaving at `start_time`, loading the
+/// rasters of `wind` as the isochrones advance
+async fn sail_leg(
+    wind: &mut RaceWind,
+    polar: Arc<Polar>,
+    origin: &LngLat,
+    destination: &LngLat,
+    start_time: i64,
+    until: i64,
+) -> Result<Option<Route>> {
+    let mut router = Router::new(polar, origin.clone(), destination.clone(), start_time);
+    while router.time() < until && !router.is_stuck() {
+        let time = router.time();
+        load_rasters(wind, time).await?;
+        // The router and wind go to the blocking thread and back
+        let stepped = std::mem::take(wind);
+        let (stepped_router, stepped_wind, route) = tokio::task::spawn_blocking(move || {
+            let route = router.step(|lng, lat| stepped.wind_at(time, lng, lat));
+            (router, stepped, route)
+        })
+        .await?;
+        (router, *wind) = (stepped_router, stepped_wind);
+        if route.is_some() {
+            return Ok(route);
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLAR_JSON: &str = r#"{
+        "0": { "0": 0, "45": 0, "90": 0, "180": 0 },
+        "10": { "0": 0, "45": 8, "90": 10, "180": 6 },
+        "20": { "0": 0, "45": 12, "90": 16, "180": 12 }
+    }"#;
+
+    /// Route in a steady 10 knots northerly
+    fn route_to(destination: LngLat) -> Route {
+        let polar = Polar::from_json(POLAR_JSON).unwrap();
+        let origin = LngLat { lng: 0.0, lat: 0.0 };
+        let mut router = Router::new(Arc::new(polar), origin, destination, 0);
+        let wind = (0.0, -10.0 / units::MS_TO_KNOTS);
+        for _ in 0..48 {
+            if let Some(route) = router.step(|_, _| Some(wind)) {
+                return route;
+            }
+        }
+        panic!("No route found");
+    }
+
+    #[test]
+    fn test_route_on_a_beam_reach() {
+        // 60 nm east at 10 knots
+        let route = route_to(LngLat { lng: 1.0, lat: 0.0 });
+        let hours = route.eta as f64 / HOUR_MS as f64;
+        assert!((hours - 6.0).abs() < 0.1, "ETA {hours}h");
+        assert!((route.distance_nm - 60.0).abs() < 1.0);
+
+        let first = &route.path[0];
+        assert_eq!((first.lng, first.lat, first.time), (0.0, 0.0, 0));
+        assert!((first.heading.unwrap() - 90.0).abs() <= HEADING_STEP);
+        let last = route.path.last().unwrap();
+        assert_eq!((last.lng, last.lat, last.time), (1.0, 0.0, route.eta));
+        assert!(last.heading.is_none());
+    }
+
+    #[test]
+    fn test_route_upwind_tacks() {
+        // 60 nm straight upwind: beating at 45° makes 8 × cos(45°) knots toward it
+        let route = route_to(LngLat { lng: 0.0, lat: 1.0 });
+        let hours = route.eta as f64 / HOUR_MS as f64;
+        assert!(hours > 10.0 && hours < 11.5, "ETA {hours}h");
+        assert!(route.distance_nm > 80.0);
+        assert!(
+            route
+                .path
+                .iter()
+                .filter_map(|p| p.heading)
+                .all(|heading| (40.0..=320.0).contains(&heading))
+        );
+    }
+
+    #[test]
+    fn test_best_vmg() {
+        let polar = Polar::from_json(POLAR_JSON).unwrap();
+        let wind = (0.0, -10.0 / units::MS_TO_KNOTS);
+        let origin = LngLat { lng: 0.0, lat: 0.0 };
+
+        // Beam reach: straight to the mark
+        let hint = best_vmg(&polar, wind, &origin, &LngLat { lng: 1.0, lat: 0.0 });
+        assert_eq!(hint.heading, 90.0);
+        assert_eq!(hint.twa, -90.0);
+        assert!((hint.vmg - 10.0).abs() < 1e-9);
+        assert!((hint.tws - 10.0).abs() < 1e-9);
+        assert_eq!(hint.twd, 0.0);
+
+        // Dead upwind: beat at 45°
+        let hint = best_vmg(&polar, wind, &origin, &LngLat { lng: 0.0, lat: 1.0 });
+        assert_eq!(hint.twa.abs(), 45.0);
+        assert!((hint.vmg - 8.0 * 45f64.to_radians().cos()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sail_across_antimeridian() {
+        let moved = sail(
+            &LngLat {
+                lng: 179.9,
+                lat: 0.0,
+            },
+            90.0,
+            60.0,
+        );
+        assert!(moved.lng < -179.0);
+    }
+
+    #[tokio::test]
+    async fn test_searches_limit() {
+        crate::db::init_test().await.unwrap();
+        let polar = Polar::from_json(POLAR_JSON).unwrap();
+        // No stored wind that long ago
+        let request = RouteRequest {
+            origin: LngLat { lng: 0.0, lat: 0.0 },
+            destination: LngLat { lng: 1.0, lat: 0.0 },
+            start_time: 0,
+            polar: "test".to_string(),
+            max_days: Some(1),
+        };
+
+        let permits = SEARCHES.try_acquire_many(MAX_SEARCHES as u32).unwrap();
+        let error = route(&polar, &request).await.unwrap_err();
+        assert!(error.is::<Busy>());
+        drop(permits);
+        assert!(route(&polar, &request).await.unwrap().is_none());
+    }
+}
@@ -1,13 +1,77 @@
-use std::sync::LazyLock;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
 
-use crate::config::config;
-use object_store::aws;
+use crate::config::{S3Config, StorageProvider, config};
+use crate::retry::{CircuitBreaker, RetryConfig, RetryError, with_retry_breaker};
+use crate::traced_store::TracedStore;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path as ObjectPath;
+use object_store::signer::Signer;
+use object_store::{
+    Attribute, AttributeValue, ObjectStore, PutOptions, PutPayload, aws, azure, gcp,
+};
+use reqwest::Method;
 
-fn client_for_bucket(bucket: &str) -> aws::AmazonS3 {
+/// Store of a bucket, with the signer of its URLs when it is private
+struct Bucket {
+    store: Arc<dyn ObjectStore>,
+    signer: Option<Arc<dyn Signer>>,
+}
+
+impl Bucket {
+    fn new<T: ObjectStore + Signer>(store: T) -> Bucket {
+        let store = Arc::new(store);
+        let signer = config()
+            .s3
+            .presign_secs
+            .map(|_| store.clone() as Arc<dyn Signer>);
+        Bucket { store, signer }
+    }
+}
+
+/// Bucket with its calls traced
+fn client_for_bucket(bucket: &str) -> Bucket {
+    let Bucket { store, signer } = open_bucket(bucket);
+    Bucket {
+        store: Arc::new(TracedStore::new(bucket, store)),
+        signer,
+    }
+}
+
+fn open_bucket(bucket: &str) -> Bucket {
     let s3 = &config().s3;
-    aws::AmazonS3Builder::new()
+    if let Some(dir) = &s3.local_dir {
+        return Bucket {
+            store: Arc::new(local_client(&Path::new(dir).join(bucket))),
+            signer: None,
+        };
+    }
+    match s3.provider {
+        StorageProvider::S3 => Bucket::new(s3_client(s3, bucket)),
+        StorageProvider::Gcs => Bucket::new(
+            gcp::GoogleCloudStorageBuilder::from_env()
+                .with_bucket_name(bucket)
+                .with_retry(store_retry_config())
+                .build()
+                .unwrap(),
+        ),
+        StorageProvider::Azure => Bucket::new(
+            azure::MicrosoftAzureBuilder::from_env()
+                .with_account(&s3.access_key)
+                .with_access_key(&s3.secret_key)
+                .with_container_name(bucket)
+                .with_retry(store_retry_config())
+                .build()
+                .unwrap(),
+        ),
+    }
+}
+
+fn s3_client(s3: &S3Config, bucket: &str) -> aws::AmazonS3 {
+    let mut builder = aws::AmazonS3Builder::new()
         .with_region(&s3.region)
-        .with_endpoint(&s3.endpoint)
         .with_bucket_name(bucket)
         .with_access_key_id(&s3.access_key)
         .with_secret_access_key(&s3.secret_key)
@@ -15,27 +79,168 @@ fn client_for_bucket(bucket: &str) -> aws::AmazonS3 {
         // Use path-style URLs (http://localhost:9000/bucket/key) instead of
         // virtual-hosted style (http://bucket.localhost:9000/key) for MinIO
         .with_virtual_hosted_style_request(false)
-        .build()
+        .with_retry(store_retry_config());
+    if !s3.endpoint.is_empty() {
+        builder = builder.with_endpoint(&s3.endpoint);
+    }
+    builder.build().unwrap()
+}
+
+/// Retries of each request by object_store, kept short: operations are
+/// retried again by `retried`, through the circuit breaker
+fn store_retry_config() -> object_store::RetryConfig {
+    object_store::RetryConfig {
+        max_retries: 2,
+        retry_timeout: Duration::from_secs(30),
+        ..Default::default()
+    }
+}
+
+/// Bucket kept as a directory, created if missing, for offline development
+fn local_client(dir: &Path) -> LocalFileSystem {
+    std::fs::create_dir_all(dir).unwrap();
+    LocalFileSystem::new_with_prefix(dir)
         .unwrap()
+        .with_automatic_cleanup(true)
 }
 
-static GRIB_CLIENT: LazyLock<aws::AmazonS3> =
+static GRIB_CLIENT: LazyLock<Bucket> =
     LazyLock::new(|| client_for_bucket(&config().s3.grib_bucket));
 
-static RASTER_CLIENT: LazyLock<aws::AmazonS3> =
+static RASTER_CLIENT: LazyLock<Bucket> =
     LazyLock::new(|| client_for_bucket(&config().s3.raster_bucket));
 
-static PATHS_CLIENT: LazyLock<aws::AmazonS3> =
+static PATHS_CLIENT: LazyLock<Bucket> =
     LazyLock::new(|| client_for_bucket(&config().s3.paths_bucket));
 
-pub fn grib_client() -> &'static aws::AmazonS3 {
-    &GRIB_CLIENT
+static AVATARS_CLIENT: LazyLock<Bucket> =
+    LazyLock::new(|| client_for_bucket(&config().s3.avatars_bucket));
+
+static BACKUPS_CLIENT: LazyLock<Bucket> =
+    LazyLock::new(|| client_for_bucket(&config().s3.backups_bucket));
+
+pub fn grib_client() -> &'static dyn ObjectStore {
+    GRIB_CLIENT.store.as_ref()
+}
+
+pub fn raster_client() -> &'static dyn ObjectStore {
+    RASTER_CLIENT.store.as_ref()
 }
 
-pub fn raster_client() -> &'static aws::AmazonS3 {
-    &RASTER_CLIENT
+pub fn paths_client() -> &'static dyn ObjectStore {
+    PATHS_CLIENT.store.as_ref()
 }
 
-pub fn paths_client() -> &'static aws::AmazonS3 {
-    &PATHS_CLIENT
+pub fn avatars_client() -> &'static dyn ObjectStore {
+    AVATARS_CLIENT.store.as_ref()
+}
+
+pub fn backups_client() -> &'static dyn ObjectStore {
+    BACKUPS_CLIENT.store.as_ref()
+}
+
+/// Opened by storage failures of any bucket, all on the same service
+static BREAKER: CircuitBreaker = CircuitBreaker::new(5, Duration::from_secs(60));
+
+/// Network and server errors are retried, the others are answers
+fn retry_error(e: object_store::Error) -> RetryError {
+    match e {
+        object_store::Error::Generic { .. } => RetryError::Retryable(e.into()),
+        _ => RetryError::NonRetryable(e.into()),
+    }
+}
+
+fn into_anyhow(e: RetryError) -> anyhow::Error {
+    match e {
+        RetryError::Retryable(err) | RetryError::NonRetryable(err) => err,
+    }
+}
+
+/// Run an operation on a store, retried with backoff on network and server
+/// errors, and failing fast with `CircuitOpen` while the storage is down.
+/// The operation must be idempotent.
+pub async fn retried<F, Fut, T>(op: F) -> anyhow::Result<T>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = object_store::Result<T>>,
+{
+    let attempt = || async { op().await.map_err(retry_error) };
+    with_retry_breaker(&BREAKER, attempt, &RetryConfig::default())
+        .await
+        .map_err(into_anyhow)
+}
+
+/// Run a single attempt of an operation through the circuit breaker, for
+/// the steps that can't be repeated such as multipart upload parts
+pub async fn guarded<T>(op: impl Future<Output = object_store::Result<T>>) -> anyhow::Result<T> {
+    BREAKER
+        .call(async { op.await.map_err(retry_error) })
+        .await
+        .map_err(into_anyhow)
+}
+
+/// Cache-Control of the files never rewritten under the same key
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// Store a file never rewritten under the same key, cached for a year by
+/// browsers and CDNs. Local directories don't keep the header.
+pub async fn put_immutable(
+    client: &dyn ObjectStore,
+    path: &str,
+    payload: PutPayload,
+) -> anyhow::Result<()> {
+    let mut opts = PutOptions::default();
+    if config().s3.local_dir.is_none() {
+        opts.attributes
+            .insert(Attribute::CacheControl, AttributeValue::from(IMMUTABLE));
+    }
+    let path = ObjectPath::from(path);
+    retried(|| client.put_opts(&path, payload.clone(), opts.clone())).await?;
+    Ok(())
+}
+
+/// URL of a wind raster for the client, pre-signed when the bucket is private
+pub async fn raster_url(path: &str) -> anyhow::Result<String> {
+    match &RASTER_CLIENT.signer {
+        Some(signer) => signed_url(signer.as_ref(), path).await,
+        None => Ok(config().s3.raster_url(path)),
+    }
+}
+
+/// URL of a race path for the client, pre-signed when the bucket is private
+pub async fn paths_url(path: &str) -> anyhow::Result<String> {
+    match &PATHS_CLIENT.signer {
+        Some(signer) => signed_url(signer.as_ref(), path).await,
+        None => Ok(config().s3.paths_url(path)),
+    }
+}
+
+async fn signed_url(signer: &dyn Signer, path: &str) -> anyhow::Result<String> {
+    let expires_in = Duration::from_secs(config().s3.presign_secs.unwrap_or_default());
+    let url = signer
+        .signed_url(Method::GET, &ObjectPath::from(path), expires_in)
+        .await?;
+    Ok(url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::ObjectStoreExt;
+
+    #[tokio::test]
+    async fn test_local_client_round_trip() {
+        let dir = std::env::temp_dir().join(format!("rewind-test-s3-{}", std::process::id()));
+        let client = local_client(&dir.join("race-paths"));
+        let path = ObjectPath::from("races/1/path.bin");
+
+        client.put(&path, vec![1u8, 2, 3].into()).await.unwrap();
+        let data = client.get(&path).await.unwrap().bytes().await.unwrap();
+        assert_eq!(data.as_ref(), &[1, 2, 3]);
+        assert!(dir.join("race-paths/races/1/path.bin").exists());
+
+        client.delete(&path).await.unwrap();
+        assert!(client.head(&path).await.is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -3,8 +3,9 @@
 //! Uses object_store's multipart upload API to efficiently upload
 //! data in chunks without buffering the entire file in memory.
 
+use crate::s3;
 use anyhow::{Context, Result};
-use object_store::aws::AmazonS3;
+use object_store::ObjectStore;
 use object_store::path::Path;
 use object_store::{MultipartUpload, ObjectStoreExt, PutPayload};
 
@@ -26,10 +27,9 @@ pub struct S3MultipartUploader {
 
 impl S3MultipartUploader {
     /// Create a new multipart upload for the given key.
-    pub async fn new(client: &AmazonS3, key: &str) -> Result<Self> {
+    pub async fn new(client: &dyn ObjectStore, key: &str) -> Result<Self> {
         let path = Path::from(key);
-        let upload = client
-            .put_multipart(&path)
+        let upload = s3::retried(|| client.put_multipart(&path))
             .await
             .context("Failed to initiate multipart upload")?;
 
@@ -53,8 +53,7 @@ impl S3MultipartUploader {
             let remainder = self.buffer.split_off(MIN_PART_SIZE);
             let part_data = std::mem::replace(&mut self.buffer, remainder);
 
-            self.upload
-                .put_part(PutPayload::from(part_data))
+            s3::guarded(self.upload.put_part(PutPayload::from(part_data)))
                 .await
                 .context("Failed to upload part")?;
         }
@@ -70,14 +69,12 @@ impl S3MultipartUploader {
         // Flush any remaining data
         if !self.buffer.is_empty() {
             let remaining = std::mem::take(&mut self.buffer);
-            self.upload
-                .put_part(PutPayload::from(remaining))
+            s3::guarded(self.upload.put_part(PutPayload::from(remaining)))
                 .await
                 .context("Failed to upload final part")?;
         }
 
-        self.upload
-            .complete()
+        s3::guarded(self.upload.complete())
             .await
             .context("Failed to complete multipart upload")?;
 
@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::config::config;
+use crate::courses::{self, Course};
+use crate::discord::{self, Notification};
+use crate::{db, multiplayer::RaceManager, series};
+
+/// How often the scheduler checks for lobbies to open and races to start
+const SCHEDULER_INTERVAL_SECS: u64 = 5;
+
+/// Most upcoming races a player may have scheduled at once
+const MAX_UPCOMING_PER_ACCOUNT: i64 = 3;
+
+/// Past races kept in the calendar feed, so subscribed calendars don't drop
+/// them as soon as they start
+const CALENDAR_PAST_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+
+/// Length of the calendar events of races on unknown courses
+const DEFAULT_EVENT_MS: i64 = 60 * 60 * 1000;
+
+/// Lifecycle of a scheduled race
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScheduleStatus {
+    /// Waiting for the lobby to open
+    Pending,
+    /// Lobby is open for players to join
+    Open,
+    Started,
+    /// Failed to open, or nobody joined before the start
+    Cancelled,
+}
+
+impl ScheduleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleStatus::Pending => "pending",
+            ScheduleStatus::Open => "open",
+            ScheduleStatus::Started => "started",
+            ScheduleStatus::Cancelled => "cancelled",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "open" => ScheduleStatus::Open,
+            "started" => ScheduleStatus::Started,
+            "cancelled" => ScheduleStatus::Cancelled,
+            _ => ScheduleStatus::Pending,
+        }
+    }
+}
+
+/// A race the server opens and starts by itself at a fixed time
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledRace {
+    pub id: i64,
+    pub course_key: String,
+    pub start_at: i64,
+    pub lobby_minutes: i64,
+    /// When the lobby opens for players to join
+    pub lobby_opens_at: i64,
+    pub series_id: Option<i64>,
+    pub race_id: Option<String>,
+    pub status: ScheduleStatus,
+}
+
+fn lobby_opens_at(start_at: i64, lobby_minutes: i64) -> i64 {
+    start_at - lobby_minutes * 60 * 1000
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateScheduledRaceRequest {
+    pub course_key: String,
+    pub start_at: i64,
+    #[serde(default = "default_lobby_minutes")]
+    pub lobby_minutes: i64,
+    pub series_id: Option<i64>,
+}
+
+fn default_lobby_minutes() -> i64 {
+    10
+}
+
+type ScheduledRaceRow = (i64, String, i64, i64, Option<i64>, Option<String>, String);
+
+const SELECT_COLUMNS: &str =
+    "SELECT id, course_key, start_at, lobby_minutes, series_id, race_id, status FROM scheduled_races";
+
+fn from_row(
+    (id, course_key, start_at, lobby_minutes, series_id, race_id, status): ScheduledRaceRow,
+) -> ScheduledRace {
+    ScheduledRace {
+        id,
+        course_key,
+        start_at,
+        lobby_minutes,
+        lobby_opens_at: lobby_opens_at(start_at, lobby_minutes),
+        series_id,
+        race_id,
+        status: ScheduleStatus::parse(&status),
+    }
+}
+
+/// Schedule a new race, by an admin or by the player of `account_id`.
+/// Players may not add races to series.
+pub async fn create(
+    request: &CreateScheduledRaceRequest,
+    account_id: Option<&str>,
+) -> Result<ScheduledRace> {
+    if request.start_at <= Utc::now().timestamp_millis() {
+        anyhow::bail!("Start time must be in the future");
+    }
+    if !(0..=24 * 60).contains(&request.lobby_minutes) {
+        anyhow::bail!("Lobby must open between 0 and 1440 minutes before the start");
+    }
+    let Some(course) = crate::courses::get_by_key(&request.course_key).await? else {
+        anyhow::bail!("Course not found");
+    };
+    course.check_open(request.start_at)?;
+
+    if let Some(account_id) = account_id {
+        if request.series_id.is_some() {
+            anyhow::bail!("Only admins can schedule series races");
+        }
+        let (upcoming,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM scheduled_races
+             WHERE account_id = $1 AND status IN ('pending', 'open')",
+        )
+        .bind(account_id)
+        .fetch_one(db::pool())
+        .await?;
+        if upcoming >= MAX_UPCOMING_PER_ACCOUNT {
+            anyhow::bail!(
+                "You can have at most {} upcoming scheduled races",
+                MAX_UPCOMING_PER_ACCOUNT
+            );
+        }
+    }
+
+    let (id,): (i64,) = sqlx::query_as(
+        "INSERT INTO scheduled_races (course_key, start_at, lobby_minutes, series_id, account_id)
+         VALUES ($1, $2, $3, $4, $5) RETURNING id",
+    )
+    .bind(&request.course_key)
+    .bind(request.start_at)
+    .bind(request.lobby_minutes)
+    .bind(request.series_id)
+    .bind(account_id)
+    .fetch_one(db::pool())
+    .await?;
+
+    Ok(ScheduledRace {
+        id,
+        course_key: request.course_key.clone(),
+        start_at: request.start_at,
+        lobby_minutes: request.lobby_minutes,
+        lobby_opens_at: lobby_opens_at(request.start_at, request.lobby_minutes),
+        series_id: request.series_id,
+        race_id: None,
+        status: ScheduleStatus::Pending,
+    })
+}
+
+/// List races that are waiting to open or have an open lobby, soonest first.
+pub async fn list_upcoming() -> Result<Vec<ScheduledRace>> {
+    let rows: Vec<ScheduledRaceRow> = sqlx::query_as(&format!(
+        "{} WHERE status IN ('pending', 'open') ORDER BY start_at",
+        SELECT_COLUMNS
+    ))
+    .fetch_all(db::pool())
+    .await?;
+    Ok(rows.into_iter().map(from_row).collect())
+}
+
+/// Delete a scheduled race that has not started yet.
+/// Returns false if there was no such race.
+pub async fn delete(id: i64) -> Result<bool> {
+    let result =
+        sqlx::query("DELETE FROM scheduled_races WHERE id = $1 AND status IN ('pending', 'open')")
+            .bind(id)
+            .execute(db::pool())
+            .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+async fn set_status(id: i64, status: ScheduleStatus, race_id: Option<&str>) -> Result<()> {
+    sqlx::query("UPDATE scheduled_races SET status = $1, race_id = COALESCE($2, race_id) WHERE id = $3")
+        .bind(status.as_str())
+        .bind(race_id)
+        .bind(id)
+        .execute(db::pool())
+        .await?;
+    Ok(())
+}
+
+/// iCalendar feed of the scheduled races of the last 30 days and upcoming
+/// ones, cancelled races marked as such. Events last the longest a race on
+/// the course can take in real time.
+pub async fn calendar(now: i64) -> Result<String> {
+    let rows: Vec<ScheduledRaceRow> = sqlx::query_as(&format!(
+        "{} WHERE start_at >= $1 ORDER BY start_at",
+        SELECT_COLUMNS
+    ))
+    .bind(now - CALENDAR_PAST_MS)
+    .fetch_all(db::pool())
+    .await?;
+    let courses: HashMap<String, Course> = courses::get_all()
+        .await?
+        .into_iter()
+        .map(|course| (course.key.clone(), course))
+        .collect();
+    let series: HashMap<i64, String> = series::list()
+        .await?
+        .into_iter()
+        .map(|series| (series.id, series.name))
+        .collect();
+
+    let cfg = config();
+    let host = Url::parse(&cfg.public_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| "rewind".to_string());
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Re:wind//Scheduled races//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        "X-WR-CALNAME:Re:wind races".to_string(),
+        "REFRESH-INTERVAL;VALUE=DURATION:PT1H".to_string(),
+        "X-PUBLISHED-TTL:PT1H".to_string(),
+    ];
+    for race in rows.into_iter().map(from_row) {
+        let course = courses.get(&race.course_key);
+        let course_name = course.map_or(race.course_key.as_str(), |course| &course.name);
+        let summary = match race.series_id.and_then(|id| series.get(&id)) {
+            Some(series) => format!("{}: {}", series, course_name),
+            None => format!("Race on {}", course_name),
+        };
+        let duration = course
+            .map(|course| course.max_days as i64 * 24 * 60 * 60 * 1000 / course.time_factor as i64)
+            .filter(|duration| *duration > 0)
+            .unwrap_or(DEFAULT_EVENT_MS);
+        let description = format!(
+            "The lobby opens {} minutes before the start.",
+            race.lobby_minutes
+        );
+        let status = match race.status {
+            ScheduleStatus::Cancelled => "CANCELLED",
+            _ => "CONFIRMED",
+        };
+
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:scheduled-race-{}@{}", race.id, host),
+            format!("DTSTAMP:{}", ics_time(now)),
+            format!("DTSTART:{}", ics_time(race.start_at)),
+            format!("DTEND:{}", ics_time(race.start_at + duration)),
+            format!("SUMMARY:{}", ics_text(&summary)),
+            format!("DESCRIPTION:{}", ics_text(&description)),
+            format!("URL:{}", cfg.client_url),
+            format!("STATUS:{}", status),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    Ok(lines.iter().map(|line| fold(line) + "\r\n").collect())
+}
+
+/// UTC date-time of the iCalendar format
+fn ics_time(time: i64) -> String {
+    DateTime::from_timestamp_millis(time)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// Escape an iCalendar text value
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Fold a content line at 75 octets, continuation lines starting with a
+/// space, without splitting characters
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+/// Races whose lobby should be open by `now`
+async fn due_to_open(now: i64) -> Result<Vec<ScheduledRace>> {
+    let rows: Vec<ScheduledRaceRow> = sqlx::query_as(&format!(
+        "{} WHERE status = 'pending' AND start_at - lobby_minutes * 60000 <= $1",
+        SELECT_COLUMNS
+    ))
+    .bind(now)
+    .fetch_all(db::pool())
+    .await?;
+    Ok(rows.into_iter().map(from_row).collect())
+}
+
+/// Open races whose start time has come
+async fn due_to_start(now: i64) -> Result<Vec<ScheduledRace>> {
+    let rows: Vec<ScheduledRaceRow> = sqlx::query_as(&format!(
+        "{} WHERE status = 'open' AND start_at <= $1",
+        SELECT_COLUMNS
+    ))
+    .bind(now)
+    .fetch_all(db::pool())
+    .await?;
+    Ok(rows.into_iter().map(from_row).collect())
+}
+
+/// Run the scheduler forever: open lobbies ahead of time and start races on time.
+pub async fn run_scheduler(manager: RaceManager) {
+    loop {
+        if let Err(e) = tick(&manager).await {
+            log::error!("Race scheduler failed: {}", e);
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(SCHEDULER_INTERVAL_SECS)).await;
+    }
+}
+
+async fn tick(manager: &RaceManager) -> Result<()> {
+    let now = Utc::now().timestamp_millis();
+
+    for scheduled in due_to_open(now).await? {
+        match manager
+            .open_scheduled_race(&scheduled.course_key, scheduled.start_at)
+            .await
+        {
+            Ok(race_id) => {
+                set_status(scheduled.id, ScheduleStatus::Open, Some(&race_id)).await?;
+                if let Some(series_id) = scheduled.series_id
+                    && let Err(e) = series::add_race(series_id, &race_id).await
+                {
+                    log::warn!("Failed to add race {} to series {}: {}", race_id, series_id, e);
+                }
+                log::info!(
+                    "Opened scheduled race {} on {} (starts at {})",
+                    race_id,
+                    scheduled.course_key,
+                    scheduled.start_at
+                );
+                discord::notify(Notification::RaceCreated {
+                    course_key: scheduled.course_key.clone(),
+                    creator: None,
+                    start_at: Some(scheduled.start_at),
+                });
+            }
+            Err(e) => {
+                log::error!("Failed to open scheduled race {}: {}", scheduled.id, e);
+                set_status(scheduled.id, ScheduleStatus::Cancelled, None).await?;
+            }
+        }
+    }
+
+    for scheduled in due_to_start(now).await? {
+        let Some(race_id) = scheduled.race_id else {
+            continue;
+        };
+        set_status(scheduled.id, ScheduleStatus::Started, None).await?;
+
+        // The countdown takes a few seconds, don't hold up the scheduler
+        let manager = manager.clone();
+        tokio::spawn(async move {
+            if let Err(e) = manager.start_scheduled_race(&race_id).await {
+                log::info!("Scheduled race {} did not start: {}", race_id, e);
+                if let Err(e) = set_status(scheduled.id, ScheduleStatus::Cancelled, None).await {
+                    log::error!("Failed to cancel scheduled race {}: {}", scheduled.id, e);
+                }
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_schedule_lifecycle() {
+        db::init_test().await.unwrap();
+
+        let now = Utc::now().timestamp_millis();
+        let request = CreateScheduledRaceRequest {
+            course_key: "vg20".to_string(),
+            start_at: now + 30 * 60 * 1000,
+            lobby_minutes: 10,
+            series_id: None,
+        };
+        let scheduled = create(&request, None).await.unwrap();
+        assert_eq!(scheduled.lobby_opens_at, now + 20 * 60 * 1000);
+
+        assert!(list_upcoming().await.unwrap().iter().any(|r| r.id == scheduled.id));
+        assert!(!due_to_open(now).await.unwrap().iter().any(|r| r.id == scheduled.id));
+        assert!(
+            due_to_open(now + 20 * 60 * 1000)
+                .await
+                .unwrap()
+                .iter()
+                .any(|r| r.id == scheduled.id)
+        );
+
+        set_status(scheduled.id, ScheduleStatus::Open, Some("SCH001")).await.unwrap();
+        let due = due_to_start(now + 30 * 60 * 1000).await.unwrap();
+        let opened = due.iter().find(|r| r.id == scheduled.id).unwrap();
+        assert_eq!(opened.race_id.as_deref(), Some("SCH001"));
+        assert_eq!(opened.status, ScheduleStatus::Open);
+
+        set_status(scheduled.id, ScheduleStatus::Started, None).await.unwrap();
+        assert!(!list_upcoming().await.unwrap().iter().any(|r| r.id == scheduled.id));
+        assert!(!delete(scheduled.id).await.unwrap());
+    }
+
+    #[test]
+    fn test_ics_format() {
+        assert_eq!(ics_time(1_700_000_000_000), "20231114T221320Z");
+        assert_eq!(
+            ics_text("Cape Horn; Rio, 2025\n"),
+            "Cape Horn\\; Rio\\, 2025\\n"
+        );
+        let folded = fold(&format!("SUMMARY:{}", "é".repeat(60)));
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= 75));
+        assert!(lines[1].starts_with(' '));
+    }
+
+    #[tokio::test]
+    async fn test_calendar() {
+        db::init_test().await.unwrap();
+
+        let now = Utc::now().timestamp_millis();
+        let request = |start_at| CreateScheduledRaceRequest {
+            course_key: "vg20".to_string(),
+            start_at,
+            lobby_minutes: 15,
+            series_id: None,
+        };
+        let upcoming = create(&request(now + 60 * 60 * 1000), None).await.unwrap();
+        let cancelled = create(&request(now + 2 * 60 * 60 * 1000), None)
+            .await
+            .unwrap();
+        set_status(cancelled.id, ScheduleStatus::Cancelled, None)
+            .await
+            .unwrap();
+
+        let ics = calendar(now).await.unwrap();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        let event = |id: i64| {
+            let uid = format!("UID:scheduled-race-{}@localhost", id);
+            let start = ics.find(&uid).unwrap();
+            ics[start..start + ics[start..].find("END:VEVENT").unwrap()].to_string()
+        };
+        let course = courses::get_by_key("vg20").await.unwrap().unwrap();
+        let upcoming = event(upcoming.id);
+        assert!(upcoming.contains(&format!("SUMMARY:Race on {}", ics_text(&course.name))));
+        assert!(upcoming.contains("lobby opens 15 minutes"));
+        assert!(upcoming.contains("STATUS:CONFIRMED"));
+        assert!(event(cancelled.id).contains("STATUS:CANCELLED"));
+    }
+
+    #[tokio::test]
+    async fn test_create_rejects_past_start() {
+        db::init_test().await.unwrap();
+
+        let request = CreateScheduledRaceRequest {
+            course_key: "vg20".to_string(),
+            start_at: Utc::now().timestamp_millis() - 1000,
+            lobby_minutes: 10,
+            series_id: None,
+        };
+        let result = create(&request, None).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_players_schedule_a_few_races() {
+        db::init_test().await.unwrap();
+
+        let start_at = Utc::now().timestamp_millis() + 60 * 60 * 1000;
+        let request = |series_id| CreateScheduledRaceRequest {
+            course_key: "vg20".to_string(),
+            start_at,
+            lobby_minutes: 10,
+            series_id,
+        };
+        let account = Some("scheduling-player");
+
+        assert!(create(&request(Some(1)), account).await.is_err());
+        for _ in 0..MAX_UPCOMING_PER_ACCOUNT {
+            create(&request(None), account).await.unwrap();
+        }
+        let error = create(&request(None), account).await.unwrap_err();
+        assert!(error.to_string().contains("at most"));
+
+        // Admins are not limited
+        create(&request(None), None).await.unwrap();
+    }
+}
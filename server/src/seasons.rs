@@ -0,0 +1,253 @@
+//! Seasons and the periods leaderboards are scoped to.
+//!
+//! Besides the all-time Hall of Fame, leaderboards rank the results of a
+//! calendar month (UTC) or of a season: a named range of race dates set by
+//! admins. Seasons don't overlap, so at most one is current.
+
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Season {
+    pub id: i64,
+    pub name: String,
+    /// First race start time of the season (Unix ms)
+    pub start_time: i64,
+    /// End of the season, excluded (Unix ms)
+    pub end_time: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateSeasonRequest {
+    pub name: String,
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+/// Period a leaderboard is asked for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeriodKind {
+    #[default]
+    All,
+    Month,
+    Season,
+}
+
+/// Range of race start times ranked by a leaderboard, the end excluded.
+/// Unbounded sides are None.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Period {
+    pub from: Option<i64>,
+    pub until: Option<i64>,
+}
+
+impl Period {
+    pub const ALL_TIME: Period = Period {
+        from: None,
+        until: None,
+    };
+
+    /// The UTC calendar month of `date`
+    pub fn month(date: NaiveDate) -> Period {
+        let first = date.with_day(1).unwrap_or(date);
+        let next = first
+            .checked_add_months(chrono::Months::new(1))
+            .unwrap_or(first);
+        let millis = |day: NaiveDate| {
+            day.and_hms_opt(0, 0, 0)
+                .map(|t| t.and_utc().timestamp_millis())
+        };
+        Period {
+            from: millis(first),
+            until: millis(next),
+        }
+    }
+
+    pub fn season(season: &Season) -> Period {
+        Period {
+            from: Some(season.start_time),
+            until: Some(season.end_time),
+        }
+    }
+}
+
+/// Period of a month given as `YYYY-MM`, or of the current one
+pub fn month_period(month: Option<&str>) -> Result<Period> {
+    let date = match month {
+        Some(month) => NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+            .map_err(|_| anyhow::anyhow!("Months are given as YYYY-MM"))?,
+        None => Utc::now().date_naive(),
+    };
+    Ok(Period::month(date))
+}
+
+type SeasonRow = (i64, String, i64, i64, i64);
+
+fn from_row((id, name, start_time, end_time, created_at): SeasonRow) -> Season {
+    Season {
+        id,
+        name,
+        start_time,
+        end_time,
+        created_at,
+    }
+}
+
+/// Create a season. Fails if it overlaps another one.
+pub async fn create(request: &CreateSeasonRequest) -> Result<Season> {
+    let name = request.name.trim();
+    if name.is_empty() {
+        anyhow::bail!("Season name cannot be empty");
+    }
+    if name.len() > 50 {
+        anyhow::bail!("Season name cannot exceed 50 characters");
+    }
+    if request.end_time <= request.start_time {
+        anyhow::bail!("Season must end after it starts");
+    }
+
+    let overlapping: Option<(String,)> =
+        sqlx::query_as("SELECT name FROM seasons WHERE start_time < $1 AND end_time > $2 LIMIT 1")
+            .bind(request.end_time)
+            .bind(request.start_time)
+            .fetch_optional(db::pool())
+            .await?;
+    if let Some((other,)) = overlapping {
+        anyhow::bail!("Season overlaps season {}", other);
+    }
+
+    let row: SeasonRow = sqlx::query_as(
+        "INSERT INTO seasons (name, start_time, end_time) VALUES ($1, $2, $3)
+         RETURNING id, name, start_time, end_time, created_at",
+    )
+    .bind(name)
+    .bind(request.start_time)
+    .bind(request.end_time)
+    .fetch_one(db::pool())
+    .await?;
+    Ok(from_row(row))
+}
+
+/// All seasons, latest first
+pub async fn list() -> Result<Vec<Season>> {
+    let rows: Vec<SeasonRow> = sqlx::query_as(
+        "SELECT id, name, start_time, end_time, created_at FROM seasons ORDER BY start_time DESC",
+    )
+    .fetch_all(db::pool())
+    .await?;
+    Ok(rows.into_iter().map(from_row).collect())
+}
+
+pub async fn get(season_id: i64) -> Result<Option<Season>> {
+    let row: Option<SeasonRow> = sqlx::query_as(
+        "SELECT id, name, start_time, end_time, created_at FROM seasons WHERE id = $1",
+    )
+    .bind(season_id)
+    .fetch_optional(db::pool())
+    .await?;
+    Ok(row.map(from_row))
+}
+
+/// The season running at `time` (Unix ms), if any
+pub async fn at(time: i64) -> Result<Option<Season>> {
+    let row: Option<SeasonRow> = sqlx::query_as(
+        "SELECT id, name, start_time, end_time, created_at FROM seasons
+         WHERE start_time <= $1 AND end_time > $2",
+    )
+    .bind(time)
+    .bind(time)
+    .fetch_optional(db::pool())
+    .await?;
+    Ok(row.map(from_row))
+}
+
+/// Delete a season. Results are kept. Returns false if it did not exist.
+pub async fn delete(season_id: i64) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM seasons WHERE id = $1")
+        .bind(season_id)
+        .execute(db::pool())
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::race_results::{self, NewRaceResult, ResultStatus};
+
+    #[test]
+    fn test_month_period() {
+        let period = month_period(Some("2024-12")).unwrap();
+        assert_eq!(period.from, Some(1_733_011_200_000));
+        // January 1st 2025
+        assert_eq!(period.until, Some(1_735_689_600_000));
+        assert!(month_period(Some("December")).is_err());
+        assert!(month_period(None).unwrap().from.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_season_leaderboard() {
+        db::init_test().await.unwrap();
+
+        let request = |name: &str, start_time: i64, end_time: i64| CreateSeasonRequest {
+            name: name.to_string(),
+            start_time,
+            end_time,
+        };
+        let season = create(&request("Season test", 2_000_000, 3_000_000))
+            .await
+            .unwrap();
+        assert!(
+            create(&request("Overlap", 2_500_000, 4_000_000))
+                .await
+                .is_err()
+        );
+        assert!(
+            create(&request("Backwards", 5_000_000, 4_000_000))
+                .await
+                .is_err()
+        );
+        assert_eq!(at(2_000_000).await.unwrap().unwrap().id, season.id);
+        assert!(at(3_000_000).await.unwrap().is_none());
+
+        // The faster result was sailed before the season
+        for (time, race_start_time) in [(1000, 1_000_000), (2000, 2_500_000)] {
+            race_results::save_result(&NewRaceResult {
+                race_id: "SEA001".to_string(),
+                course_key: "season-test".to_string(),
+                player_name: format!("sailor-{}", time),
+                player_id: format!("season-{}", time),
+                finish_time: time,
+                race_start_time,
+                path_s3_key: "paths/test.bin".to_string(),
+                status: ResultStatus::Finished,
+                daily_date: None,
+                weather_start_time: None,
+                polar: "imoca".to_string(),
+                splits: vec![],
+                stats: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let leaderboard =
+            |period| race_results::get_leaderboard("season-test", "imoca", period, 10);
+        let all_time = leaderboard(Period::ALL_TIME).await.unwrap();
+        assert_eq!(all_time.len(), 2);
+        let in_season = leaderboard(Period::season(&season)).await.unwrap();
+        assert_eq!(in_season.len(), 1);
+        assert_eq!(in_season[0].finish_time, 2000);
+        assert_eq!(in_season[0].rank, 1);
+
+        assert!(delete(season.id).await.unwrap());
+        assert!(get(season.id).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,394 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::db;
+
+/// Points awarded by finishing position; finishers beyond the table score 0,
+/// as do retired and DNF boats.
+const POINTS_TABLE: [u32; 10] = [25, 18, 15, 12, 10, 8, 6, 4, 2, 1];
+
+/// A group of races scored into cumulative standings (e.g. a weekly league)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Series {
+    pub id: i64,
+    pub name: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateSeriesRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddSeriesRaceRequest {
+    pub race_id: String,
+}
+
+/// A player's result in one race of a series
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesRaceResult {
+    pub player_id: String,
+    pub player_name: String,
+    pub rank: Option<u32>,
+    pub status: String,
+    pub finish_time: i64,
+    pub points: u32,
+}
+
+/// A race of a series with its scored results
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesRace {
+    pub race_id: String,
+    pub course_key: Option<String>,
+    pub results: Vec<SeriesRaceResult>,
+}
+
+/// Cumulative standing of a player across the races of a series
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesStanding {
+    pub rank: u32,
+    pub player_id: String,
+    pub player_name: String,
+    pub points: u32,
+    pub races: u32,
+}
+
+/// Full view of a series: its races and the standings table
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesDetail {
+    #[serde(flatten)]
+    pub series: Series,
+    pub races: Vec<SeriesRace>,
+    pub standings: Vec<SeriesStanding>,
+}
+
+/// Points for a finishing position (1-based)
+pub fn race_points(rank: u32) -> u32 {
+    rank.checked_sub(1)
+        .and_then(|i| POINTS_TABLE.get(i as usize))
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Create a new series.
+pub async fn create(name: &str) -> Result<Series> {
+    let name = name.trim();
+    if name.is_empty() {
+        anyhow::bail!("Series name cannot be empty");
+    }
+    if name.len() > 50 {
+        anyhow::bail!("Series name cannot exceed 50 characters");
+    }
+
+    let (id, created_at): (i64, i64) =
+        sqlx::query_as("INSERT INTO series (name) VALUES ($1) RETURNING id, created_at")
+            .bind(name)
+            .fetch_one(db::pool())
+            .await?;
+
+    Ok(Series {
+        id,
+        name: name.to_string(),
+        created_at,
+    })
+}
+
+/// List all series, most recent first.
+pub async fn list() -> Result<Vec<Series>> {
+    let rows: Vec<(i64, String, i64)> =
+        sqlx::query_as("SELECT id, name, created_at FROM series ORDER BY created_at DESC, id DESC")
+            .fetch_all(db::pool())
+            .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, name, created_at)| Series {
+            id,
+            name,
+            created_at,
+        })
+        .collect())
+}
+
+/// Delete a series and its race links. Race results are kept.
+/// Returns false if the series did not exist.
+pub async fn delete(series_id: i64) -> Result<bool> {
+    sqlx::query("DELETE FROM series_races WHERE series_id = $1")
+        .bind(series_id)
+        .execute(db::pool())
+        .await?;
+    let result = sqlx::query("DELETE FROM series WHERE id = $1")
+        .bind(series_id)
+        .execute(db::pool())
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Add a multiplayer race to a series. Its results are scored as soon as
+/// they are saved, so races can be linked before or after they run.
+pub async fn add_race(series_id: i64, race_id: &str) -> Result<()> {
+    let exists: Option<(i64,)> = sqlx::query_as("SELECT id FROM series WHERE id = $1")
+        .bind(series_id)
+        .fetch_optional(db::pool())
+        .await?;
+    if exists.is_none() {
+        anyhow::bail!("Series not found");
+    }
+
+    sqlx::query(
+        "INSERT INTO series_races (series_id, race_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(series_id)
+    .bind(race_id)
+    .execute(db::pool())
+    .await?;
+    Ok(())
+}
+
+/// Remove a race from a series.
+pub async fn remove_race(series_id: i64, race_id: &str) -> Result<()> {
+    sqlx::query("DELETE FROM series_races WHERE series_id = $1 AND race_id = $2")
+        .bind(series_id)
+        .bind(race_id)
+        .execute(db::pool())
+        .await?;
+    Ok(())
+}
+
+type SeriesResultRow = (i64, String, String, Option<String>, String, i64, String);
+
+/// `(player_id, player_name, finish_time, status)` of a stored result
+type RawResult = (String, String, i64, String);
+
+/// Get a series with its scored races and standings.
+pub async fn get(series_id: i64) -> Result<Option<SeriesDetail>> {
+    let row: Option<(i64, String, i64)> =
+        sqlx::query_as("SELECT id, name, created_at FROM series WHERE id = $1")
+            .bind(series_id)
+            .fetch_optional(db::pool())
+            .await?;
+    let Some((id, name, created_at)) = row else {
+        return Ok(None);
+    };
+
+    let race_ids: Vec<(String,)> =
+        sqlx::query_as("SELECT race_id FROM series_races WHERE series_id = $1 ORDER BY id")
+            .bind(series_id)
+            .fetch_all(db::pool())
+            .await?;
+
+    let rows: Vec<SeriesResultRow> = sqlx::query_as(
+        "SELECT r.id, r.race_id, r.course_key, r.player_id, r.player_name, r.finish_time, r.status
+         FROM race_results r
+         JOIN series_races s ON s.race_id = r.race_id
+         WHERE s.series_id = $1
+         ORDER BY r.finish_time ASC",
+    )
+    .bind(series_id)
+    .fetch_all(db::pool())
+    .await?;
+
+    let mut by_race: HashMap<String, (String, Vec<RawResult>)> = HashMap::new();
+    for (id, race_id, course_key, player_id, player_name, finish_time, status) in rows {
+        let entry = by_race
+            .entry(race_id)
+            .or_insert_with(|| (course_key, Vec::new()));
+        entry.1.push((
+            // Anonymized results share their name, not their player
+            player_id.unwrap_or_else(|| format!("result-{}", id)),
+            player_name,
+            finish_time,
+            status,
+        ));
+    }
+
+    let races: Vec<SeriesRace> = race_ids
+        .into_iter()
+        .map(|(race_id,)| match by_race.remove(&race_id) {
+            Some((course_key, results)) => SeriesRace {
+                race_id,
+                course_key: Some(course_key),
+                results: score_race(results),
+            },
+            None => SeriesRace {
+                race_id,
+                course_key: None,
+                results: Vec::new(),
+            },
+        })
+        .collect();
+
+    let standings = compute_standings(&races);
+
+    Ok(Some(SeriesDetail {
+        series: Series {
+            id,
+            name,
+            created_at,
+        },
+        races,
+        standings,
+    }))
+}
+
+/// Rank and score the results of one race.
+/// Input is sorted by finish time.
+fn score_race(results: Vec<RawResult>) -> Vec<SeriesRaceResult> {
+    let (finished, others): (Vec<_>, Vec<_>) =
+        results.into_iter().partition(|(_, _, _, status)| status == "finished");
+
+    finished
+        .into_iter()
+        .enumerate()
+        .map(|(i, (player_id, player_name, finish_time, status))| {
+            let rank = (i + 1) as u32;
+            SeriesRaceResult {
+                player_id,
+                player_name,
+                rank: Some(rank),
+                status,
+                finish_time,
+                points: race_points(rank),
+            }
+        })
+        .chain(
+            others
+                .into_iter()
+                .map(|(player_id, player_name, finish_time, status)| SeriesRaceResult {
+                    player_id,
+                    player_name,
+                    rank: None,
+                    status,
+                    finish_time,
+                    points: 0,
+                }),
+        )
+        .collect()
+}
+
+/// Sum points per player across races. Ties are broken by number of races
+/// sailed, then by name for a stable order.
+fn compute_standings(races: &[SeriesRace]) -> Vec<SeriesStanding> {
+    let mut totals: HashMap<&str, SeriesStanding> = HashMap::new();
+    for result in races.iter().flat_map(|r| &r.results) {
+        let standing = totals
+            .entry(&result.player_id)
+            .or_insert_with(|| SeriesStanding {
+                rank: 0,
+                player_id: result.player_id.clone(),
+                player_name: result.player_name.clone(),
+                points: 0,
+                races: 0,
+            });
+        // Latest name wins (players can rename their profile)
+        standing.player_name = result.player_name.clone();
+        standing.points += result.points;
+        standing.races += 1;
+    }
+
+    let mut standings: Vec<SeriesStanding> = totals.into_values().collect();
+    standings.sort_by(|a, b| {
+        b.points
+            .cmp(&a.points)
+            .then(b.races.cmp(&a.races))
+            .then(a.player_name.cmp(&b.player_name))
+    });
+    for (i, standing) in standings.iter_mut().enumerate() {
+        standing.rank = (i + 1) as u32;
+    }
+    standings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::race_results::{self, NewRaceResult, ResultStatus};
+
+    #[test]
+    fn test_race_points() {
+        assert_eq!(race_points(1), 25);
+        assert_eq!(race_points(10), 1);
+        assert_eq!(race_points(11), 0);
+        assert_eq!(race_points(0), 0);
+    }
+
+    #[test]
+    fn test_score_race_ranks_finishers_only() {
+        let results = score_race(vec![
+            ("p2".into(), "Bob".into(), 500, "retired".into()),
+            ("p1".into(), "Alice".into(), 1000, "finished".into()),
+            ("p3".into(), "Carol".into(), 2000, "finished".into()),
+        ]);
+        assert_eq!(results[0].player_id, "p1");
+        assert_eq!(results[0].points, 25);
+        assert_eq!(results[1].rank, Some(2));
+        assert_eq!(results[1].points, 18);
+        assert_eq!(results[2].rank, None);
+        assert_eq!(results[2].points, 0);
+    }
+
+    #[tokio::test]
+    async fn test_series_standings() {
+        db::init_test().await.unwrap();
+
+        let series = create("Weekly league").await.unwrap();
+        add_race(series.id, "SER001").await.unwrap();
+        add_race(series.id, "SER002").await.unwrap();
+        add_race(series.id, "SER001").await.unwrap(); // ignored duplicate
+
+        for (race_id, player, time, status) in [
+            ("SER001", "alice", 1000, ResultStatus::Finished),
+            ("SER001", "bob", 2000, ResultStatus::Finished),
+            ("SER002", "bob", 1500, ResultStatus::Finished),
+            ("SER002", "alice", 900, ResultStatus::Retired),
+        ] {
+            race_results::save_result(&NewRaceResult {
+                race_id: race_id.to_string(),
+                course_key: "vg20".to_string(),
+                player_name: player.to_string(),
+                player_id: player.to_string(),
+                finish_time: time,
+                race_start_time: 1_700_000_000_000 + time,
+                path_s3_key: "paths/test.bin".to_string(),
+                status,
+                daily_date: None,
+                weather_start_time: None,
+                polar: "vr-imoca-full-pack".to_string(),
+                splits: vec![],
+                stats: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let detail = get(series.id).await.unwrap().unwrap();
+        assert_eq!(detail.races.len(), 2);
+        assert_eq!(detail.races[1].results[0].player_id, "bob");
+        assert_eq!(detail.standings[0].player_id, "bob");
+        assert_eq!(detail.standings[0].points, 43);
+        assert_eq!(detail.standings[1].points, 25);
+        assert_eq!(detail.standings[1].races, 2);
+
+        // Anonymous results of deleted accounts aren't added up
+        race_results::anonymize_player("alice").await.unwrap();
+        race_results::anonymize_player("bob").await.unwrap();
+        let detail = get(series.id).await.unwrap().unwrap();
+        assert_eq!(detail.standings.len(), 4);
+        assert!(detail.standings.iter().all(|s| s.races == 1));
+        assert_eq!(detail.standings[0].points, 25);
+
+        assert!(create("  ").await.is_err());
+        assert!(add_race(9999, "SER003").await.is_err());
+        assert!(delete(series.id).await.unwrap());
+        assert!(get(series.id).await.unwrap().is_none());
+    }
+}
@@ -1,39 +1,46 @@
 use axum::{
     Json, Router,
-    extract::{Path, Query, State, ws::WebSocketUpgrade},
-    http::{header, HeaderMap, Method, StatusCode},
-    response::{IntoResponse, Response},
+    body::Body,
+    extract::{ConnectInfo, FromRequestParts, MatchedPath, Path, Query, State, ws::WebSocketUpgrade},
+    http::{header, request::Parts, HeaderMap, Method, Request, StatusCode},
+    response::{IntoResponse, Redirect, Response},
     routing::{any, delete, get, post, put},
 };
 use bytes::Bytes;
 use object_store::ObjectStoreExt;
 use object_store::path::Path as S3Path;
 use serde::Deserialize;
-use tower_http::{compression::CompressionLayer, cors::CorsLayer};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use sentry::integrations::tower::{NewSentryLayer, SentryHttpLayer};
+use tower_http::{
+    compression::CompressionLayer, cors::CorsLayer, services::ServeDir, trace::TraceLayer,
+};
 
 use crate::{
-    auth, config::config, courses,
-    multiplayer::{RaceManager, handle_websocket},
-    profiles, race_results, wind_reports,
+    account, admin_stats, analysis, api_tokens, audit::{self, Action}, auth, avatars, bathymetry, cluster, comparison, config::config, course_metrics, courses, daily, email, email_queue, email_templates::Locale, heatmaps,
+    multiplayer::{RaceManager, WindRasterSource, handle_websocket},
+    land, land_mask, meteogram, oauth, path_export, personal_bests,
+    polars::{self, Foiling, PolarTable},
+    profiles, race_events, race_results,
+    roles::{self, Permission, Role},
+    routing, scheduled_races, seasons, series, webhooks, wind_reports,
 };
 
 use super::s3;
 
-/// Check if the request is from an admin user.
-/// Requires a valid session token for an account with the admin email.
-async fn check_admin(headers: &HeaderMap) -> Result<(), AppError> {
-    let admin_email = &config().admin_email;
-    if admin_email.is_empty() {
-        return Err(AppError::Unauthorized);
-    }
-
+/// Check that the request is from a staff member allowed to do something.
+/// Requires a valid session token for an account with a role granting
+/// `permission`. Returns the account id.
+async fn require_permission(
+    headers: &HeaderMap,
+    permission: Permission,
+) -> Result<String, AppError> {
     let account_id = require_auth(headers).await?;
-    let email = auth::get_account_email(&account_id)
-        .await?
-        .ok_or(AppError::Unauthorized)?;
-
-    if email.to_lowercase() == admin_email.to_lowercase() {
-        Ok(())
+    if roles::has_permission(&account_id, permission).await? {
+        Ok(account_id)
     } else {
         Err(AppError::Unauthorized)
     }
@@ -48,6 +55,8 @@ enum AppError {
     NotFound,
     /// Authentication required or insufficient permissions (401).
     Unauthorized,
+    /// Server busy with requests of the kind, to try again later (429).
+    TooManyRequests(String),
 }
 
 impl IntoResponse for AppError {
@@ -66,6 +75,7 @@ impl IntoResponse for AppError {
             }
             AppError::NotFound => StatusCode::NOT_FOUND.into_response(),
             AppError::Unauthorized => StatusCode::UNAUTHORIZED.into_response(),
+            AppError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg).into_response(),
         }
     }
 }
@@ -83,7 +93,11 @@ async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(race_manager): State<RaceManager>,
 ) -> Response {
-    ws.on_upgrade(move |socket| handle_websocket(socket, race_manager))
+    ws.on_upgrade(move |socket| async move {
+        race_manager
+            .track_connection(handle_websocket(socket, race_manager.clone()))
+            .await
+    })
 }
 
 pub async fn run(address: std::net::SocketAddr) {
@@ -92,7 +106,58 @@ pub async fn run(address: std::net::SocketAddr) {
         log::warn!("Failed to clean up expired auth data: {}", e);
     }
 
+    // Store the bundled polar files missing from the database
+    match polars::seed_from_dir(&config().polars_dir).await {
+        Ok(0) => {}
+        Ok(count) => log::info!("Seeded {} polars from {}", count, config().polars_dir),
+        Err(e) => log::warn!("Failed to seed polars: {}", e),
+    }
+
+    // Coastline for land collisions, checks are skipped without it
+    match land::init(&config().land_file).await {
+        Ok(count) => log::info!("Loaded {} land polygons from {}", count, config().land_file),
+        Err(e) => log::warn!("Failed to load land data, land collisions disabled: {}", e),
+    }
+
+    // Depths for courses with a minimum depth, shallows are ignored without it
+    if let Some(file) = &config().bathymetry_file {
+        match bathymetry::init(file).await {
+            Ok(count) => log::info!("Loaded {} bathymetry cells from {}", count, file),
+            Err(e) => log::warn!("Failed to load bathymetry, groundings disabled: {}", e),
+        }
+    }
+
+    // Courses of the courses file, reloaded on SIGHUP
+    if config().courses_file.is_some() {
+        match courses::reload().await {
+            Ok(changed) if changed.is_empty() => {}
+            Ok(changed) => log::info!("Loaded courses {}", changed.join(", ")),
+            Err(e) => log::warn!("Failed to load the courses file: {}", e),
+        }
+        tokio::spawn(reload_courses_on_hangup());
+    }
+
+    course_metrics::spawn_refresh();
+
     let race_manager = RaceManager::new();
+    // Instances sharing a Redis relay players to the instance of their race
+    if let Err(e) = cluster::init(address).await {
+        log::error!("Failed to join the cluster, running alone: {}", e);
+    }
+    tokio::spawn(cluster::run_heartbeat(race_manager.clone()));
+    tokio::spawn(scheduled_races::run_scheduler(race_manager.clone()));
+    tokio::spawn(account::run_purger());
+    tokio::spawn(email_queue::run_worker());
+    tokio::spawn(webhooks::run_worker());
+    tokio::spawn(heatmaps::run_refresher());
+
+    // First admin of a new deployment, who grants the other roles
+    let admin_email = &config().admin_email;
+    if !admin_email.is_empty()
+        && let Err(e) = roles::bootstrap_admin(admin_email).await
+    {
+        log::warn!("Failed to grant admin role to {}: {}", admin_email, e);
+    }
 
     let cors = CorsLayer::new()
         .allow_origin(tower_http::cors::Any)
@@ -107,6 +172,7 @@ pub async fn run(address: std::net::SocketAddr) {
         .route("/editor/verify", get(verify_editor_access_handler))
         .route("/courses", get(courses_handler).post(create_course_handler))
         .route("/courses/reorder", put(reorder_courses_handler))
+        .route("/admin/courses/reload", post(reload_courses_handler))
         .route(
             "/courses/{key}",
             put(update_course_handler).delete(delete_course_handler),
@@ -114,28 +180,200 @@ pub async fn run(address: std::net::SocketAddr) {
         .route("/wind/random", get(random_wind_handler))
         .route("/multiplayer/races", get(races_handler))
         .route("/multiplayer/race", any(websocket_handler))
+        .route("/multiplayer/races/{race_id}/events", get(race_events_handler))
+        .route("/courses/{key}/heatmap", get(heatmap_handler))
+        .route("/courses/{key}/leaderboard.csv", get(leaderboard_csv_handler))
         .route("/leaderboard/{course_key}", get(leaderboard_handler))
+        .route("/profiles/{id}/stats", get(profile_stats_handler))
+        .route("/land-mask", get(land_mask_handler))
+        .route("/depth", get(depth_handler))
+        .route("/meteogram", get(meteogram_handler))
+        .route("/polars", get(polars_handler))
+        .route("/polars/{name}", get(polar_handler))
+        .route("/polars/{name}/sails", get(sails_handler))
+        .route("/polars/{name}/foiling", get(foiling_handler))
+        .route("/route", post(route_handler))
+        .route("/coach", post(coach_handler))
+        .route("/analyze", post(analyze_handler))
+        .route("/daily", get(daily_handler))
+        .route("/daily/leaderboard", get(daily_leaderboard_handler))
         .route("/replay/{result_id}", get(replay_handler))
+        .route("/replay/{result_id}/export", get(replay_export_handler))
+        .route("/results/compare", get(compare_results_handler))
+        .route(
+            "/scheduled-races",
+            get(scheduled_races_handler).post(create_scheduled_race_handler),
+        )
+        .route("/races/schedule.ics", get(schedule_calendar_handler))
+        .route("/seasons", get(seasons_handler))
+        .route("/series", get(series_list_handler))
+        .route("/series/{id}", get(series_detail_handler))
         // Auth routes
         .route("/auth/start", post(start_auth_handler))
         .route("/auth/verify", post(verify_auth_handler))
+        .route("/auth/magic", get(magic_link_handler))
         .route("/auth/logout", post(logout_handler))
+        .route("/auth/sessions", get(list_sessions_handler))
+        .route("/auth/sessions/{id}", delete(revoke_session_handler))
+        .route("/auth/providers", get(auth_providers_handler))
+        .route("/auth/oauth/{provider}", get(oauth_authorize_handler))
+        .route(
+            "/auth/oauth/{provider}/callback",
+            get(oauth_callback_handler),
+        )
         // Account routes (requires auth)
         .route("/account/me", get(account_me_handler))
+        .route("/account/export", get(account_export_handler))
+        .route("/account/email-preferences", put(email_preferences_handler))
+        .route("/account/results", get(account_results_handler))
+        .route(
+            "/account/tokens",
+            get(list_tokens_handler).post(create_token_handler),
+        )
+        .route("/account/tokens/{id}", delete(revoke_token_handler))
+        .route(
+            "/account/deletion",
+            post(request_deletion_handler).delete(cancel_deletion_handler),
+        )
         .route("/account/profiles", get(list_profiles_handler).post(create_profile_handler))
         .route("/account/profiles/{id}", put(update_profile_handler).delete(delete_profile_handler))
+        .route(
+            "/account/profiles/{id}/avatar",
+            put(upload_avatar_handler).delete(delete_avatar_handler),
+        )
         // Admin routes (requires admin)
         .route("/admin/accounts", get(admin_list_accounts_handler))
         .route("/admin/accounts/{id}", delete(admin_delete_account_handler))
+        .route("/admin/roles", get(admin_list_roles_handler))
+        .route(
+            "/admin/accounts/{id}/roles/{role}",
+            put(admin_grant_role_handler).delete(admin_revoke_role_handler),
+        )
+        .route("/admin/audit", get(admin_audit_log_handler))
+        .route("/admin/stats", get(admin_stats_handler))
+        .route("/admin/announcements", post(admin_announcement_handler))
         .route("/admin/results", get(admin_list_results_handler))
         .route("/admin/results/{id}", delete(admin_delete_result_handler))
+        .route("/admin/scheduled-races", post(admin_create_scheduled_race_handler))
+        .route(
+            "/admin/scheduled-races/{id}",
+            delete(admin_delete_scheduled_race_handler),
+        )
+        .route("/admin/polars/{name}/foiling", put(admin_set_foiling_handler))
+        .route("/admin/seasons", post(admin_create_season_handler))
+        .route("/admin/seasons/{id}", delete(admin_delete_season_handler))
+        .route("/admin/series", post(admin_create_series_handler))
+        .route("/admin/series/{id}", delete(admin_delete_series_handler))
+        .route("/admin/series/{id}/races", post(admin_add_series_race_handler))
+        .route(
+            "/admin/series/{id}/races/{race_id}",
+            delete(admin_remove_series_race_handler),
+        )
+        .route(
+            "/admin/webhooks",
+            get(admin_list_webhooks_handler).post(admin_create_webhook_handler),
+        )
+        .route("/admin/webhooks/{id}", delete(admin_delete_webhook_handler))
+        .merge(local_storage_routes())
         .layer(CompressionLayer::new())
         .layer(cors)
-        .with_state(race_manager);
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        // Request context of the errors reported to Sentry, inert without it
+        .layer(SentryHttpLayer::new())
+        .layer(NewSentryLayer::new_from_top())
+        .with_state(race_manager.clone());
 
     let listener = tokio::net::TcpListener::bind(address).await.unwrap();
     log::info!("Server listening on {}", address);
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(race_manager.clone()))
+    .await
+    .unwrap();
+
+    race_manager.drain(SHUTDOWN_TIMEOUT).await;
+    log::info!("Server stopped");
+}
+
+/// Files of the public buckets of `REWIND_S3_LOCAL_DIR`, at
+/// `/storage/{bucket}/{path}` where their URLs point by default. Grib files
+/// and backups aren't served
+fn local_storage_routes() -> Router<RaceManager> {
+    let s3 = &config().s3;
+    let Some(dir) = &s3.local_dir else {
+        return Router::new();
+    };
+    [&s3.raster_bucket, &s3.paths_bucket, &s3.avatars_bucket]
+        .into_iter()
+        .fold(Router::new(), |router, bucket| {
+            router.nest_service(
+                &format!("/storage/{}", bucket),
+                ServeDir::new(std::path::Path::new(dir).join(bucket)),
+            )
+        })
+}
+
+/// Span of a request, named after its route rather than its path so that
+/// the traces of a handler are grouped
+fn request_span<B>(request: &Request<B>) -> tracing::Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(request.uri().path(), |path| path.as_str());
+    tracing::info_span!(
+        "request",
+        otel.name = format!("{} {}", request.method(), route),
+        method = %request.method(),
+        route,
+    )
+}
+
+/// Reload the courses file on each SIGHUP
+async fn reload_courses_on_hangup() {
+    #[cfg(unix)]
+    {
+        let mut hangup =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).unwrap();
+        while hangup.recv().await.is_some() {
+            match courses::reload().await {
+                Ok(changed) => {
+                    log::info!("Courses reloaded: {} changed", changed.len());
+                    course_metrics::spawn_refresh();
+                }
+                Err(e) => log::error!("Failed to reload courses: {}", e),
+            }
+        }
+    }
+}
+
+/// Longest wait for the player connections and result saves at shutdown
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Resolves on SIGINT or SIGTERM, once the races are stopped
+async fn shutdown_signal(race_manager: RaceManager) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.unwrap();
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .unwrap()
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    log::info!("Shutting down");
+    let race_ids = race_manager.race_ids().await;
+    race_manager.shut_down().await;
+    cluster::leave(&race_ids).await;
 }
 
 async fn health_handler() -> Result<String, AppError> {
@@ -153,22 +391,68 @@ async fn health_handler() -> Result<String, AppError> {
 async fn verify_editor_access_handler(
     headers: HeaderMap,
 ) -> Result<impl IntoResponse, AppError> {
-    check_admin(&headers).await?;
+    require_permission(&headers, Permission::ManageCourses).await?;
     Ok(StatusCode::OK)
 }
 
-async fn courses_handler() -> Result<impl IntoResponse, AppError> {
-    let courses = courses::get_all().await?;
-    Ok(Json(courses))
+/// A course with its cached metrics, None until they are computed
+#[derive(serde::Serialize)]
+struct CourseListing {
+    #[serde(flatten)]
+    course: courses::Course,
+    metrics: Option<course_metrics::CourseMetrics>,
+}
+
+async fn courses_handler(
+    Query(query): Query<courses::CourseQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let courses = courses::search(&query).await?;
+    let mut metrics = course_metrics::get_all().await?;
+    let listings: Vec<CourseListing> = courses
+        .into_iter()
+        .map(|course| CourseListing {
+            metrics: metrics.remove(&course.key),
+            course,
+        })
+        .collect();
+    Ok(Json(listings))
+}
+
+async fn validate_course(course: &courses::Course) -> Result<(), AppError> {
+    course
+        .validate()
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let times = wind_reports::get_all_times().await?;
+    if !wind_reports::covers(&times, course.start_time) {
+        return Err(AppError::BadRequest(
+            "No stored wind at the course start time".to_string(),
+        ));
+    }
+    Ok(())
 }
 
 async fn create_course_handler(
     headers: HeaderMap,
     Json(course): Json<courses::Course>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_admin(&headers).await?;
+    let actor = require_permission(&headers, Permission::ManageCourses).await?;
+    validate_course(&course).await?;
+    if courses::get_by_key(&course.key).await?.is_some() {
+        return Err(AppError::BadRequest(format!(
+            "A course with key {} already exists",
+            course.key
+        )));
+    }
     log::info!("Course created: {} ({})", course.name, course.key);
     courses::insert(&course).await?;
+    audit::record(
+        Some(&actor),
+        Action::CourseCreate,
+        &course.key,
+        json!(course),
+    )
+    .await?;
+    course_metrics::spawn_refresh();
     Ok(StatusCode::CREATED)
 }
 
@@ -177,9 +461,19 @@ async fn update_course_handler(
     Path(key): Path<String>,
     Json(course): Json<courses::Course>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_admin(&headers).await?;
+    let actor = require_permission(&headers, Permission::ManageCourses).await?;
+    if course.key != key {
+        return Err(AppError::BadRequest(
+            "The key of a course can't be changed".to_string(),
+        ));
+    }
+    validate_course(&course).await?;
+    courses::get_by_key(&key).await?.ok_or(AppError::NotFound)?;
     log::info!("Course updated: {} ({})", course.name, key);
     courses::update(&key, &course).await?;
+    audit::record(Some(&actor), Action::CourseUpdate, &key, json!(course)).await?;
+    course_metrics::invalidate(&key).await?;
+    course_metrics::spawn_refresh();
     Ok(StatusCode::OK)
 }
 
@@ -187,29 +481,93 @@ async fn delete_course_handler(
     headers: HeaderMap,
     Path(key): Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_admin(&headers).await?;
+    let actor = require_permission(&headers, Permission::ManageCourses).await?;
+    let course = courses::get_by_key(&key).await?.ok_or(AppError::NotFound)?;
+    log::info!("Course deleted: {}", key);
     courses::delete(&key).await?;
+    audit::record(Some(&actor), Action::CourseDelete, &key, json!(course)).await?;
+    course_metrics::invalidate(&key).await?;
     Ok(StatusCode::OK)
 }
 
+/// Re-read the courses file, answering the keys of the changed courses
+async fn reload_courses_handler(headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageCourses).await?;
+    if config().courses_file.is_none() {
+        return Err(AppError::BadRequest(
+            "No courses file to reload, courses are edited in the database".to_string(),
+        ));
+    }
+    // A courses file that doesn't read, parse or validate is for the admin to fix
+    let changed = courses::reload()
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    log::info!("Courses reloaded: {} changed", changed.len());
+    audit::record(Some(&actor), Action::CourseReload, "courses", json!(changed)).await?;
+    course_metrics::spawn_refresh();
+    Ok(Json(json!({ "changed": changed })))
+}
+
 async fn reorder_courses_handler(
     headers: HeaderMap,
     Json(keys): Json<Vec<String>>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_admin(&headers).await?;
+    let actor = require_permission(&headers, Permission::ManageCourses).await?;
+    let known = courses::get_all().await?;
+    let is_known = |key: &String| known.iter().any(|course| &course.key == key);
+    if let Some(unknown) = keys.iter().find(|key| !is_known(key)) {
+        return Err(AppError::BadRequest(format!("Unknown course {}", unknown)));
+    }
     courses::reorder(&keys).await?;
+    audit::record(Some(&actor), Action::CourseReorder, "courses", json!(keys)).await?;
     Ok(StatusCode::OK)
 }
 
 async fn races_handler(State(race_manager): State<RaceManager>) -> impl IntoResponse {
-    let races = race_manager.list_races().await;
+    let mut races = race_manager.list_races().await;
+    races.extend(cluster::remote_races().await);
     Json(races)
 }
 
+async fn race_events_handler(Path(race_id): Path<String>) -> Result<impl IntoResponse, AppError> {
+    let events = race_events::list_for_race(&race_id).await?;
+    if events.is_empty() {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(events))
+}
+
 #[derive(Deserialize)]
 struct LeaderboardQuery {
     #[serde(default = "default_limit")]
     limit: u32,
+    /// Boat class of the Hall of Fame; the course default when unset
+    polar: Option<String>,
+    /// Race dates ranked: all time, a month or a season
+    #[serde(default)]
+    period: seasons::PeriodKind,
+    /// Month of a monthly leaderboard as `YYYY-MM`, the current one when unset
+    month: Option<String>,
+    /// Season of a season leaderboard, the current one when unset
+    season: Option<i64>,
+}
+
+/// Race dates a leaderboard query asks for
+async fn leaderboard_period(query: &LeaderboardQuery) -> Result<seasons::Period, AppError> {
+    match query.period {
+        seasons::PeriodKind::All => Ok(seasons::Period::ALL_TIME),
+        seasons::PeriodKind::Month => seasons::month_period(query.month.as_deref())
+            .map_err(|e| AppError::BadRequest(e.to_string())),
+        seasons::PeriodKind::Season => {
+            let season = match query.season {
+                Some(id) => seasons::get(id).await?,
+                None => seasons::at(chrono::Utc::now().timestamp_millis()).await?,
+            };
+            season
+                .map(|season| seasons::Period::season(&season))
+                .ok_or(AppError::NotFound)
+        }
+    }
 }
 
 fn default_limit() -> u32 {
@@ -220,7 +578,183 @@ async fn leaderboard_handler(
     Path(course_key): Path<String>,
     Query(query): Query<LeaderboardQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    let entries = race_results::get_leaderboard(&course_key, query.limit).await?;
+    let period = leaderboard_period(&query).await?;
+    let polar = match query.polar {
+        Some(polar) => polar,
+        None => courses::get_by_key(&course_key).await?.ok_or(AppError::NotFound)?.polar,
+    };
+    let entries = race_results::get_leaderboard(&course_key, &polar, period, query.limit).await?;
+    Ok(Json(entries))
+}
+
+async fn profile_stats_handler(
+    Path(profile_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let stats = personal_bests::stats(&profile_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(stats))
+}
+
+async fn polars_handler() -> Result<impl IntoResponse, AppError> {
+    Ok(Json(polars::list().await?))
+}
+
+async fn polar_handler(Path(name): Path<String>) -> Result<impl IntoResponse, AppError> {
+    let data = polars::get_table(&name).await?.ok_or(AppError::NotFound)?;
+    Ok(([(header::CONTENT_TYPE, "application/json")], data))
+}
+
+async fn sails_handler(Path(name): Path<String>) -> Result<impl IntoResponse, AppError> {
+    let sails: BTreeMap<String, PolarTable> = polars::sails(&name)
+        .await?
+        .into_iter()
+        .map(|(sail, polar)| (sail, polar.to_table()))
+        .collect();
+    Ok(Json(sails))
+}
+
+async fn foiling_handler(Path(name): Path<String>) -> Result<impl IntoResponse, AppError> {
+    let polar = polars::get(&name).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(polar.foiling))
+}
+
+async fn route_handler(
+    Json(request): Json<routing::RouteRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if request
+        .max_days
+        .is_some_and(|days| days == 0 || days > routing::MAX_DAYS)
+    {
+        return Err(AppError::BadRequest(format!(
+            "maxDays must be within 1-{}",
+            routing::MAX_DAYS
+        )));
+    }
+    let polar = polars::get(&request.polar)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let route = routing::route(&polar, &request)
+        .await
+        .map_err(search_error)?
+        .ok_or_else(|| {
+            AppError::BadRequest("No route to the destination with the stored wind".to_string())
+        })?;
+    Ok(Json(route))
+}
+
+/// Error of a route search, 429 when too many are running
+fn search_error(e: anyhow::Error) -> AppError {
+    match e.downcast::<routing::Busy>() {
+        Ok(busy) => AppError::TooManyRequests(busy.to_string()),
+        Err(e) => AppError::Internal(e),
+    }
+}
+
+async fn coach_handler(
+    Json(request): Json<routing::HintRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let polar = polars::get(&request.polar)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let wind = routing::wind_at(request.time, &request.position)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("No stored wind at this time".to_string()))?;
+    Ok(Json(routing::best_vmg(
+        &polar,
+        wind,
+        &request.position,
+        &request.mark,
+    )))
+}
+
+async fn meteogram_handler(
+    Query(query): Query<meteogram::MeteogramQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    if !query.is_valid_window() {
+        return Err(AppError::BadRequest(format!(
+            "The window must end after it starts and last at most {} days",
+            meteogram::MAX_DAYS
+        )));
+    }
+    let position = courses::LngLat {
+        lng: query.lng,
+        lat: query.lat,
+    };
+    Ok(Json(meteogram::meteogram(&position, query.from, query.to).await?))
+}
+
+async fn analyze_handler(
+    Json(request): Json<analysis::AnalyzeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let result = race_results::get_result(request.result_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    if result.status != race_results::ResultStatus::Finished {
+        return Err(AppError::BadRequest(
+            "Only finished results can be analyzed".to_string(),
+        ));
+    }
+    let course = courses::get_by_key(&result.course_key)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    if course.marks().len() > routing::MAX_MARKS {
+        return Err(AppError::BadRequest(format!(
+            "Only courses of at most {} marks can be analyzed",
+            routing::MAX_MARKS
+        )));
+    }
+    let polar = polars::get(&result.polar)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let analysis = analysis::analyze(request.result_id, &result, &course, &polar)
+        .await
+        .map_err(search_error)?
+        .ok_or_else(|| {
+            AppError::BadRequest("No route around the course with the stored wind".to_string())
+        })?;
+    Ok(Json(analysis))
+}
+
+#[derive(Deserialize)]
+struct CompareQuery {
+    a: i64,
+    b: i64,
+}
+
+async fn compare_results_handler(
+    Query(query): Query<CompareQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let a = race_results::get_result(query.a)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let b = race_results::get_result(query.b)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    if a.course_key != b.course_key {
+        return Err(AppError::BadRequest(
+            "Only results of the same course can be compared".to_string(),
+        ));
+    }
+    let course = courses::get_by_key(&a.course_key)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let comparison = comparison::compare(&course, query.a, a, query.b, b).await?;
+    Ok(Json(comparison))
+}
+
+async fn daily_handler() -> Result<impl IntoResponse, AppError> {
+    match daily::current().await? {
+        Some(challenge) => Ok(Json(challenge)),
+        None => Err(AppError::NotFound),
+    }
+}
+
+async fn daily_leaderboard_handler(
+    Query(query): Query<LeaderboardQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let date = daily::date_key(daily::today());
+    let entries = race_results::get_daily_leaderboard(&date, query.limit).await?;
     Ok(Json(entries))
 }
 
@@ -228,16 +762,183 @@ async fn leaderboard_handler(
 #[serde(rename_all = "camelCase")]
 struct ReplayResponse {
     path_url: String,
+    /// Simplified path, when a tolerance is asked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    points: Option<Vec<race_results::PathPoint>>,
+    course_key: String,
+    player_name: String,
+    /// Course time the race started at
+    start_time: i64,
+    finish_time: i64,
+    /// Wind the race was sailed in, to play it back
+    wind_raster_sources: Vec<WindRasterSource>,
+}
+
+#[derive(Deserialize)]
+struct ReplayQuery {
+    /// Simplify the path, dropping points within this distance (nm) of the
+    /// simplified line
+    tolerance: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    format: path_export::ExportFormat,
+}
+
+/// Every result of a course as a CSV download, for race organizers
+async fn leaderboard_csv_handler(Path(key): Path<String>) -> Result<impl IntoResponse, AppError> {
+    courses::get_by_key(&key).await?.ok_or(AppError::NotFound)?;
+    let disposition = format!("attachment; filename=\"rewind-{}-leaderboard.csv\"", key);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(race_results::leaderboard_csv(key)),
+    ))
+}
+
+/// Where the fleet sails a course, for an overlay
+async fn heatmap_handler(Path(key): Path<String>) -> Result<impl IntoResponse, AppError> {
+    let heatmap = heatmaps::get(&key).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(heatmap))
+}
+
+async fn land_mask_handler() -> impl IntoResponse {
+    Json(land_mask::list())
+}
+
+#[derive(serde::Serialize)]
+struct DepthResponse {
+    depth: Option<f64>,
 }
 
-async fn replay_handler(Path(result_id): Path<i64>) -> Result<impl IntoResponse, AppError> {
-    let path_key = race_results::get_path_key(result_id).await?;
+async fn depth_handler(Query(position): Query<courses::LngLat>) -> impl IntoResponse {
+    Json(DepthResponse {
+        depth: bathymetry::depth(&position),
+    })
+}
+
+async fn replay_handler(
+    Path(result_id): Path<i64>,
+    Query(query): Query<ReplayQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let result = race_results::get_result(result_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let course = courses::get_by_key(&result.course_key)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let key = result.path_s3_key;
 
-    match path_key {
-        Some(key) => {
-            let url = config().s3.paths_url(&key);
-            Ok(Json(ReplayResponse { path_url: url }))
+    let points = match query.tolerance {
+        Some(tolerance) if !tolerance.is_finite() || tolerance <= 0.0 => {
+            return Err(AppError::BadRequest(
+                "tolerance must be a positive distance in nm".to_string(),
+            ));
+        }
+        Some(tolerance) => {
+            let path = race_results::load_path(&key).await?;
+            Some(race_results::simplify_path(&path, tolerance))
         }
+        None => None,
+    };
+
+    // Reports from the day before the start, as for races
+    let start_time = result.weather_start_time.unwrap_or(course.start_time);
+    let reports = wind_reports::get_reports_between(
+        start_time - 24 * 60 * 60 * 1000,
+        start_time + result.finish_time,
+    )
+    .await?;
+
+    Ok(Json(ReplayResponse {
+        path_url: s3::paths_url(&key).await?,
+        points,
+        course_key: result.course_key,
+        player_name: result.player_name,
+        start_time,
+        finish_time: result.finish_time,
+        wind_raster_sources: WindRasterSource::for_reports(&reports).await?,
+    }))
+}
+
+/// Path of a result as a GPX or TCX file, for chart and analysis tools
+async fn replay_export_handler(
+    Path(result_id): Path<i64>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let result = race_results::get_result(result_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let path = race_results::load_path(&result.path_s3_key).await?;
+
+    let format = query.format;
+    let disposition = format!(
+        "attachment; filename=\"rewind-{}-{}.{}\"",
+        result.course_key,
+        result_id,
+        format.extension()
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        format.render(&result.player_name, &path),
+    ))
+}
+
+/// Schedule a race as a player, or a bot with the `create:race` scope
+async fn create_scheduled_race_handler(
+    caller: Authenticated,
+    Json(request): Json<scheduled_races::CreateScheduledRaceRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let account_id = caller.require(api_tokens::Scope::CreateRace)?;
+    let scheduled = scheduled_races::create(&request, Some(account_id))
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    log::info!(
+        "Account {} scheduled race {} on {} at {}",
+        account_id,
+        scheduled.id,
+        scheduled.course_key,
+        scheduled.start_at
+    );
+    Ok((StatusCode::CREATED, Json(scheduled)))
+}
+
+async fn scheduled_races_handler() -> Result<impl IntoResponse, AppError> {
+    let upcoming = scheduled_races::list_upcoming().await?;
+    Ok(Json(upcoming))
+}
+
+/// Calendar of the scheduled races, for players to subscribe to
+async fn schedule_calendar_handler() -> Result<impl IntoResponse, AppError> {
+    let ics = scheduled_races::calendar(chrono::Utc::now().timestamp_millis()).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "inline; filename=\"rewind-races.ics\""),
+        ],
+        ics,
+    ))
+}
+
+async fn seasons_handler() -> Result<impl IntoResponse, AppError> {
+    Ok(Json(seasons::list().await?))
+}
+
+async fn series_list_handler() -> Result<impl IntoResponse, AppError> {
+    let list = series::list().await?;
+    Ok(Json(list))
+}
+
+async fn series_detail_handler(Path(series_id): Path<i64>) -> Result<impl IntoResponse, AppError> {
+    match series::get(series_id).await? {
+        Some(detail) => Ok(Json(detail)),
         None => Err(AppError::NotFound),
     }
 }
@@ -252,38 +953,188 @@ async fn random_wind_handler() -> Result<impl IntoResponse, AppError> {
     let report = wind_reports::get_random_report().await?;
 
     match report {
-        Some(r) => Ok(Json(RandomWindResponse { png_url: r.png_url() })),
+        Some(r) => Ok(Json(RandomWindResponse {
+            png_url: r.png_url().await?,
+        })),
         None => Err(AppError::NotFound),
     }
 }
 
 // ===== Auth handlers =====
 
+/// Client address for throttling: the X-Forwarded-For hop appended by a
+/// trusted proxy, else the peer address.
+fn client_ip(headers: &HeaderMap, addr: SocketAddr) -> String {
+    forwarded_ip(headers, config().trust_proxy).unwrap_or_else(|| addr.ip().to_string())
+}
+
+/// Last X-Forwarded-For hop, the one added by the proxy in front of the
+/// server. Earlier hops come from the client, which can write anything there.
+fn forwarded_ip(headers: &HeaderMap, trust_proxy: bool) -> Option<String> {
+    if !trust_proxy {
+        return None;
+    }
+    let ip: std::net::IpAddr = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .next_back()?
+        .rsplit(',')
+        .next()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(ip.to_string())
+}
+
+/// Browser of a sign in, listed with the account sessions
+fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+}
+
 async fn start_auth_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(request): Json<auth::StartAuthRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    auth::start_auth(&request.email)
-        .await
-        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let ip = client_ip(&headers, addr);
+    let locale = Locale::or_default(request.language.as_deref());
+    match request.mode {
+        auth::AuthMode::Code => auth::start_auth(&request.email, Some(&ip), locale).await,
+        auth::AuthMode::Magic => {
+            let redirect = request.redirect.as_deref();
+            auth::start_magic_link(&request.email, redirect, Some(&ip), locale).await
+        }
+    }
+    .map_err(|e| AppError::BadRequest(e.to_string()))?;
     Ok(StatusCode::OK)
 }
 
+/// Magic link from a sign in email: land on the client signed in, or with the error
+async fn magic_link_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<auth::MagicLinkQuery>,
+) -> Result<Response, AppError> {
+    let ip = client_ip(&headers, addr);
+    let result = auth::verify_magic_link(&query.token, Some(&ip), user_agent(&headers)).await;
+    match &result {
+        Ok(signed_in) => record_sign_in(signed_in, "magic", Some(&ip), &headers).await?,
+        Err(e) => log::warn!("Magic link sign in failed: {}", e),
+    }
+    let path = auth::safe_redirect(query.redirect.as_deref());
+    let url = auth::landing_url(path, &result)?;
+    Ok(Redirect::to(&url).into_response())
+}
+
 async fn verify_auth_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(request): Json<auth::VerifyAuthRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    let result = auth::verify_auth(&request.email, &request.code)
-        .await
-        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let ip = client_ip(&headers, addr);
+    let result = auth::verify_auth(
+        &request.email,
+        &request.code,
+        Some(&ip),
+        user_agent(&headers),
+    )
+    .await
+    .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    record_sign_in(&result, "code", Some(&ip), &headers).await?;
     Ok(Json(result))
 }
 
+/// Audit a sign in, with the method: "code", "magic" or the OAuth provider
+async fn record_sign_in(
+    signed_in: &auth::AuthResult,
+    method: &str,
+    ip: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<(), AppError> {
+    let payload = json!({ "method": method, "ip": ip, "userAgent": user_agent(headers) });
+    audit::record(
+        Some(&signed_in.account_id),
+        Action::SignIn,
+        &signed_in.account_id,
+        payload,
+    )
+    .await?;
+    Ok(())
+}
+
 async fn logout_handler(headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
     if let Some(token) = extract_session_token(&headers) {
+        if let Some(account_id) = auth::validate_session(&token).await? {
+            audit::record(Some(&account_id), Action::SignOut, &account_id, Value::Null).await?;
+        }
         auth::logout(&token).await?;
     }
     Ok(StatusCode::OK)
 }
 
+async fn list_sessions_handler(headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
+    let account_id = require_auth(&headers).await?;
+    let token = extract_session_token(&headers).unwrap_or_default();
+    Ok(Json(auth::list_sessions(&account_id, &token).await?))
+}
+
+async fn revoke_session_handler(
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let account_id = require_auth(&headers).await?;
+    if !auth::revoke_session(&account_id, &session_id).await? {
+        return Err(AppError::NotFound);
+    }
+    audit::record(
+        Some(&account_id),
+        Action::SessionRevoke,
+        &session_id,
+        Value::Null,
+    )
+    .await?;
+    Ok(StatusCode::OK)
+}
+
+async fn auth_providers_handler() -> impl IntoResponse {
+    Json(oauth::enabled())
+}
+
+async fn oauth_authorize_handler(Path(provider): Path<String>) -> Result<Response, AppError> {
+    let provider = oauth::Provider::parse(&provider).ok_or(AppError::NotFound)?;
+    let url = oauth::authorize_url(provider)
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    Ok(Redirect::to(&url).into_response())
+}
+
+/// Back from the provider: land on the client signed in, or with the error
+async fn oauth_callback_handler(
+    headers: HeaderMap,
+    Path(provider): Path<String>,
+    Query(query): Query<oauth::CallbackQuery>,
+) -> Result<Response, AppError> {
+    let provider = oauth::Provider::parse(&provider).ok_or(AppError::NotFound)?;
+    let result = match (query.code, query.state, query.error) {
+        (Some(code), Some(state), None) => {
+            oauth::callback(provider, &code, &state, user_agent(&headers)).await
+        }
+        (_, _, error) => Err(anyhow::anyhow!(
+            "Sign in cancelled{}",
+            error.map(|e| format!(" ({})", e)).unwrap_or_default()
+        )),
+    };
+    match &result {
+        Ok(signed_in) => record_sign_in(signed_in, provider.name(), None, &headers).await?,
+        Err(e) => log::warn!("{} sign in failed: {}", provider.name(), e),
+    }
+    let url = auth::landing_url("/", &result)?;
+    Ok(Redirect::to(&url).into_response())
+}
+
 /// Extract session token from Authorization header (Bearer token).
 fn extract_session_token(headers: &HeaderMap) -> Option<String> {
     headers
@@ -302,31 +1153,206 @@ async fn require_auth(headers: &HeaderMap) -> Result<String, AppError> {
     Ok(account_id)
 }
 
+/// Who a request is from: a signed in player, with every scope, or the
+/// account of an API token, limited to its scopes.
+struct Authenticated {
+    account_id: String,
+    /// Scopes of the API token, None for sessions
+    scopes: Option<Vec<api_tokens::Scope>>,
+}
+
+impl Authenticated {
+    /// The account, if the request may act with `scope`
+    fn require(&self, scope: api_tokens::Scope) -> Result<&str, AppError> {
+        match &self.scopes {
+            Some(scopes) if !scopes.contains(&scope) => Err(AppError::Unauthorized),
+            _ => Ok(&self.account_id),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Authenticated {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, AppError> {
+        let token = extract_session_token(&parts.headers).ok_or(AppError::Unauthorized)?;
+        if api_tokens::is_api_token(&token) {
+            let (account_id, scopes) = api_tokens::validate(&token)
+                .await?
+                .ok_or(AppError::Unauthorized)?;
+            return Ok(Authenticated {
+                account_id,
+                scopes: Some(scopes),
+            });
+        }
+        let account_id = auth::validate_session(&token)
+            .await?
+            .ok_or(AppError::Unauthorized)?;
+        Ok(Authenticated {
+            account_id,
+            scopes: None,
+        })
+    }
+}
+
 // ===== Account handlers =====
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 struct AccountMeResponse {
+    email: String,
+    profiles: Vec<profiles::Profile>,
     is_admin: bool,
+    /// Staff roles, giving access to parts of the admin
+    roles: Vec<Role>,
+    /// Set while the account is scheduled for deletion
+    delete_at: Option<i64>,
+    /// Summary emailed after each finish
+    race_summary_emails: bool,
 }
 
-async fn account_me_handler(headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
-    let account_id = require_auth(&headers).await?;
-    let email = auth::get_account_email(&account_id)
+async fn account_me_handler(caller: Authenticated) -> Result<impl IntoResponse, AppError> {
+    let account_id = caller.require(api_tokens::Scope::ReadAccount)?;
+    let email = auth::get_account_email(account_id)
         .await?
         .ok_or(AppError::Unauthorized)?;
 
-    let admin_email = &config().admin_email;
-    let is_admin = !admin_email.is_empty() && email.to_lowercase() == admin_email.to_lowercase();
+    let roles = roles::roles_of(account_id).await?;
+    let profiles = profiles::list_profiles(account_id).await?;
+    let delete_at = account::deletion_date(account_id).await?;
+    let race_summary_emails = account::race_summary_emails(account_id).await?;
 
-    Ok(Json(AccountMeResponse { is_admin }))
+    Ok(Json(AccountMeResponse {
+        email,
+        profiles,
+        is_admin: roles.contains(&Role::Admin),
+        roles,
+        delete_at,
+        race_summary_emails,
+    }))
 }
 
-// ===== Profile handlers =====
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EmailPreferences {
+    race_summaries: bool,
+}
 
-async fn list_profiles_handler(headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
+async fn email_preferences_handler(
+    headers: HeaderMap,
+    Json(preferences): Json<EmailPreferences>,
+) -> Result<impl IntoResponse, AppError> {
+    let account_id = require_auth(&headers).await?;
+    account::set_race_summary_emails(&account_id, preferences.race_summaries).await?;
+    Ok(StatusCode::OK)
+}
+
+/// All the data of the account, downloaded as a JSON file
+async fn account_export_handler(headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
+    let account_id = require_auth(&headers).await?;
+    let data = account::export(&account_id).await?;
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"rewind-account.json\"",
+        )],
+        Json(data),
+    ))
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeletionResponse {
+    delete_at: i64,
+}
+
+async fn request_deletion_handler(headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
+    let account_id = require_auth(&headers).await?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let delete_at = account::request_deletion(&account_id, now).await?;
+    let payload = json!({ "deleteAt": delete_at });
+    audit::record(
+        Some(&account_id),
+        Action::AccountDeletionRequest,
+        &account_id,
+        payload,
+    )
+    .await?;
+    Ok(Json(DeletionResponse { delete_at }))
+}
+
+/// Results of every profile of the account
+async fn account_results_handler(caller: Authenticated) -> Result<impl IntoResponse, AppError> {
+    let account_id = caller.require(api_tokens::Scope::ReadResults)?;
+    let mut results = Vec::new();
+    for profile in profiles::list_profiles(account_id).await? {
+        results.extend(race_results::list_for_player(&profile.id).await?);
+    }
+    Ok(Json(results))
+}
+
+// ===== API token handlers (sessions only) =====
+
+async fn list_tokens_handler(headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
+    let account_id = require_auth(&headers).await?;
+    Ok(Json(api_tokens::list(&account_id).await?))
+}
+
+async fn create_token_handler(
+    headers: HeaderMap,
+    Json(request): Json<api_tokens::CreateTokenRequest>,
+) -> Result<impl IntoResponse, AppError> {
     let account_id = require_auth(&headers).await?;
-    let profiles = profiles::list_profiles(&account_id).await?;
+    let created = api_tokens::create(&account_id, &request.name, &request.scopes)
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let payload = json!({ "name": created.token.name, "scopes": created.token.scopes });
+    audit::record(
+        Some(&account_id),
+        Action::TokenCreate,
+        &created.token.id,
+        payload,
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+async fn revoke_token_handler(
+    headers: HeaderMap,
+    Path(token_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let account_id = require_auth(&headers).await?;
+    if !api_tokens::revoke(&account_id, &token_id).await? {
+        return Err(AppError::NotFound);
+    }
+    audit::record(
+        Some(&account_id),
+        Action::TokenRevoke,
+        &token_id,
+        Value::Null,
+    )
+    .await?;
+    Ok(StatusCode::OK)
+}
+
+async fn cancel_deletion_handler(headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
+    let account_id = require_auth(&headers).await?;
+    account::cancel_deletion(&account_id).await?;
+    audit::record(
+        Some(&account_id),
+        Action::AccountDeletionCancel,
+        &account_id,
+        Value::Null,
+    )
+    .await?;
+    Ok(StatusCode::OK)
+}
+
+// ===== Profile handlers =====
+
+async fn list_profiles_handler(caller: Authenticated) -> Result<impl IntoResponse, AppError> {
+    let account_id = caller.require(api_tokens::Scope::ReadAccount)?;
+    let profiles = profiles::list_profiles(account_id).await?;
     Ok(Json(profiles))
 }
 
@@ -364,6 +1390,30 @@ async fn delete_profile_handler(
     Ok(StatusCode::OK)
 }
 
+/// Set the avatar of a profile from a PNG body
+async fn upload_avatar_handler(
+    headers: HeaderMap,
+    Path(profile_id): Path<String>,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let account_id = require_auth(&headers).await?;
+    let avatar_url = avatars::upload(&account_id, &profile_id, body)
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    Ok(Json(json!({ "avatarUrl": avatar_url })))
+}
+
+async fn delete_avatar_handler(
+    headers: HeaderMap,
+    Path(profile_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let account_id = require_auth(&headers).await?;
+    avatars::remove(&account_id, &profile_id)
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    Ok(StatusCode::OK)
+}
+
 // ===== Admin handlers =====
 
 #[derive(Deserialize)]
@@ -389,22 +1439,133 @@ async fn admin_list_accounts_handler(
     headers: HeaderMap,
     Query(query): Query<AdminPaginationQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_admin(&headers).await?;
+    require_permission(&headers, Permission::ManageAccounts).await?;
     let accounts = auth::list_accounts(query.limit, query.offset).await?;
     let total = auth::count_accounts().await?;
     Ok(Json(AdminAccountsResponse { accounts, total }))
 }
 
+async fn admin_stats_handler(
+    headers: HeaderMap,
+    State(race_manager): State<RaceManager>,
+) -> Result<impl IntoResponse, AppError> {
+    require_permission(&headers, Permission::ViewStats).await?;
+    Ok(Json(admin_stats::stats(&race_manager).await?))
+}
+
+#[derive(Deserialize)]
+struct AnnouncementRequest {
+    subject: String,
+    /// Plain text, paragraphs separated by blank lines
+    message: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AnnouncementResponse {
+    queued: usize,
+}
+
+async fn admin_announcement_handler(
+    headers: HeaderMap,
+    Json(request): Json<AnnouncementRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageAccounts).await?;
+    if request.subject.trim().is_empty() || request.message.trim().is_empty() {
+        return Err(AppError::BadRequest(
+            "An announcement needs a subject and a message".to_string(),
+        ));
+    }
+    let queued = email::send_announcement(request.subject.trim(), &request.message).await?;
+    let payload = json!({ "subject": request.subject, "queued": queued });
+    audit::record(Some(&actor), Action::Announcement, "accounts", payload).await?;
+    Ok(Json(AnnouncementResponse { queued }))
+}
+
 async fn admin_delete_account_handler(
     headers: HeaderMap,
     Path(account_id): Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_admin(&headers).await?;
-    auth::delete_account(&account_id).await?;
+    let actor = require_permission(&headers, Permission::ManageAccounts).await?;
+    let email = auth::get_account_email(&account_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    account::delete(&account_id).await?;
+    let payload = json!({ "email": email });
+    audit::record(Some(&actor), Action::AccountDelete, &account_id, payload).await?;
     log::info!("Admin deleted account: {}", account_id);
     Ok(StatusCode::OK)
 }
 
+async fn admin_list_roles_handler(headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
+    require_permission(&headers, Permission::ManageAccounts).await?;
+    Ok(Json(roles::list().await?))
+}
+
+async fn admin_grant_role_handler(
+    headers: HeaderMap,
+    Path((account_id, role)): Path<(String, Role)>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageAccounts).await?;
+    auth::get_account_email(&account_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    if roles::grant(&account_id, role).await? {
+        let payload = json!({ "role": role });
+        audit::record(Some(&actor), Action::RoleGrant, &account_id, payload).await?;
+    }
+    Ok(StatusCode::OK)
+}
+
+async fn admin_revoke_role_handler(
+    headers: HeaderMap,
+    Path((account_id, role)): Path<(String, Role)>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageAccounts).await?;
+    if !roles::revoke(&account_id, role)
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?
+    {
+        return Err(AppError::NotFound);
+    }
+    let payload = json!({ "role": role });
+    audit::record(Some(&actor), Action::RoleRevoke, &account_id, payload).await?;
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+struct AdminAuditQuery {
+    #[serde(default = "default_admin_limit")]
+    limit: i64,
+    #[serde(default)]
+    offset: i64,
+    actor: Option<String>,
+    action: Option<String>,
+    target: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AdminAuditResponse {
+    entries: Vec<audit::AuditEntry>,
+    total: i64,
+}
+
+async fn admin_audit_log_handler(
+    headers: HeaderMap,
+    Query(query): Query<AdminAuditQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    require_permission(&headers, Permission::ManageAccounts).await?;
+    let filter = audit::AuditFilter {
+        actor_id: query.actor.as_deref(),
+        action: query.action.as_deref(),
+        target: query.target.as_deref(),
+    };
+    let entries = audit::list(&filter, query.limit, query.offset).await?;
+    let total = audit::count(&filter).await?;
+    Ok(Json(AdminAuditResponse { entries, total }))
+}
+
 #[derive(Deserialize)]
 struct AdminResultsQuery {
     #[serde(default = "default_admin_limit")]
@@ -425,7 +1586,7 @@ async fn admin_list_results_handler(
     headers: HeaderMap,
     Query(query): Query<AdminResultsQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_admin(&headers).await?;
+    require_permission(&headers, Permission::ModerateResults).await?;
     let results =
         race_results::list_all(query.limit, query.offset, query.course_key.as_deref()).await?;
     let total = race_results::count_all(query.course_key.as_deref()).await?;
@@ -436,19 +1597,299 @@ async fn admin_delete_result_handler(
     headers: HeaderMap,
     Path(result_id): Path<i64>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_admin(&headers).await?;
+    let actor = require_permission(&headers, Permission::ModerateResults).await?;
     let path_key = race_results::delete_result(result_id).await?;
+    let payload = json!({ "pathS3Key": path_key });
+    audit::record(
+        Some(&actor),
+        Action::ResultDelete,
+        &result_id.to_string(),
+        payload,
+    )
+    .await?;
 
     // Delete the S3 path file if it existed
-    if let Some(key) = &path_key {
-        if let Err(e) = s3::paths_client()
+    if let Some(key) = &path_key
+        && let Err(e) = s3::paths_client()
             .delete(&S3Path::from(key.as_str()))
             .await
-        {
-            log::warn!("Failed to delete S3 path file {}: {}", key, e);
-        }
+    {
+        log::warn!("Failed to delete S3 path file {}: {}", key, e);
     }
 
     log::info!("Admin deleted race result: {}", result_id);
     Ok(StatusCode::OK)
 }
+
+async fn admin_create_scheduled_race_handler(
+    headers: HeaderMap,
+    Json(request): Json<scheduled_races::CreateScheduledRaceRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageRaces).await?;
+    let scheduled = scheduled_races::create(&request, None)
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    audit::record(
+        Some(&actor),
+        Action::RaceSchedule,
+        &scheduled.id.to_string(),
+        json!(scheduled),
+    )
+    .await?;
+    log::info!(
+        "Admin scheduled race {} on {} at {}",
+        scheduled.id,
+        scheduled.course_key,
+        scheduled.start_at
+    );
+    Ok((StatusCode::CREATED, Json(scheduled)))
+}
+
+async fn admin_delete_scheduled_race_handler(
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageRaces).await?;
+    if !scheduled_races::delete(id).await? {
+        return Err(AppError::NotFound);
+    }
+    audit::record(
+        Some(&actor),
+        Action::RaceDelete,
+        &id.to_string(),
+        Value::Null,
+    )
+    .await?;
+    Ok(StatusCode::OK)
+}
+
+async fn admin_set_foiling_handler(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Json(foiling): Json<Option<Foiling>>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageCourses).await?;
+    if let Some(foiling) = &foiling {
+        foiling
+            .validate()
+            .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    }
+    if !polars::set_foiling(&name, foiling.as_ref()).await? {
+        return Err(AppError::NotFound);
+    }
+    log::info!("Admin set foiling of polar {}: {:?}", name, foiling);
+    audit::record(Some(&actor), Action::PolarFoiling, &name, json!(foiling)).await?;
+    Ok(StatusCode::OK)
+}
+
+async fn admin_create_season_handler(
+    headers: HeaderMap,
+    Json(request): Json<seasons::CreateSeasonRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageRaces).await?;
+    let created = seasons::create(&request)
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    log::info!("Admin created season {}: {}", created.id, created.name);
+    let payload = json!({
+        "name": created.name,
+        "startTime": created.start_time,
+        "endTime": created.end_time,
+    });
+    audit::record(
+        Some(&actor),
+        Action::SeasonCreate,
+        &created.id.to_string(),
+        payload,
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+async fn admin_delete_season_handler(
+    headers: HeaderMap,
+    Path(season_id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageRaces).await?;
+    if !seasons::delete(season_id).await? {
+        return Err(AppError::NotFound);
+    }
+    audit::record(
+        Some(&actor),
+        Action::SeasonDelete,
+        &season_id.to_string(),
+        Value::Null,
+    )
+    .await?;
+    log::info!("Admin deleted season: {}", season_id);
+    Ok(StatusCode::OK)
+}
+
+async fn admin_create_series_handler(
+    headers: HeaderMap,
+    Json(request): Json<series::CreateSeriesRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageRaces).await?;
+    let created = series::create(&request.name)
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    log::info!("Admin created series {}: {}", created.id, created.name);
+    let payload = json!({ "name": created.name });
+    audit::record(
+        Some(&actor),
+        Action::SeriesCreate,
+        &created.id.to_string(),
+        payload,
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+async fn admin_delete_series_handler(
+    headers: HeaderMap,
+    Path(series_id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageRaces).await?;
+    if !series::delete(series_id).await? {
+        return Err(AppError::NotFound);
+    }
+    audit::record(
+        Some(&actor),
+        Action::SeriesDelete,
+        &series_id.to_string(),
+        Value::Null,
+    )
+    .await?;
+    log::info!("Admin deleted series: {}", series_id);
+    Ok(StatusCode::OK)
+}
+
+async fn admin_add_series_race_handler(
+    headers: HeaderMap,
+    Path(series_id): Path<i64>,
+    Json(request): Json<series::AddSeriesRaceRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageRaces).await?;
+    series::add_race(series_id, &request.race_id)
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let payload = json!({ "raceId": request.race_id });
+    audit::record(
+        Some(&actor),
+        Action::SeriesAddRace,
+        &series_id.to_string(),
+        payload,
+    )
+    .await?;
+    Ok(StatusCode::OK)
+}
+
+async fn admin_remove_series_race_handler(
+    headers: HeaderMap,
+    Path((series_id, race_id)): Path<(i64, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageRaces).await?;
+    series::remove_race(series_id, &race_id).await?;
+    let payload = json!({ "raceId": race_id });
+    audit::record(
+        Some(&actor),
+        Action::SeriesRemoveRace,
+        &series_id.to_string(),
+        payload,
+    )
+    .await?;
+    Ok(StatusCode::OK)
+}
+
+async fn admin_list_webhooks_handler(headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
+    require_permission(&headers, Permission::ManageWebhooks).await?;
+    Ok(Json(webhooks::list().await?))
+}
+
+async fn admin_create_webhook_handler(
+    headers: HeaderMap,
+    Json(request): Json<webhooks::CreateWebhookRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageWebhooks).await?;
+    let created = webhooks::create(&request, &actor)
+        .await
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    audit::record(
+        Some(&actor),
+        Action::WebhookCreate,
+        &created.webhook.id.to_string(),
+        json!(created.webhook),
+    )
+    .await?;
+    log::info!("Admin registered webhook {}: {}", created.webhook.id, created.webhook.url);
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+async fn admin_delete_webhook_handler(
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let actor = require_permission(&headers, Permission::ManageWebhooks).await?;
+    if !webhooks::delete(id).await? {
+        return Err(AppError::NotFound);
+    }
+    audit::record(
+        Some(&actor),
+        Action::WebhookDelete,
+        &id.to_string(),
+        Value::Null,
+    )
+    .await?;
+    log::info!("Admin deleted webhook {}", id);
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forwarded_ip() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(forwarded_ip(&headers, true), None);
+
+        // Hops written by the client are ignored, the proxy appends the last
+        headers.insert(
+            "x-forwarded-for",
+            "6.6.6.6, 7.7.7.7, 203.0.113.9".parse().unwrap(),
+        );
+        assert_eq!(forwarded_ip(&headers, true).as_deref(), Some("203.0.113.9"));
+        assert_eq!(forwarded_ip(&headers, false), None);
+
+        headers.append("x-forwarded-for", "198.51.100.4".parse().unwrap());
+        assert_eq!(forwarded_ip(&headers, true).as_deref(), Some("198.51.100.4"));
+
+        headers.insert("x-forwarded-for", "1.2.3.4, garbage".parse().unwrap());
+        assert_eq!(forwarded_ip(&headers, true), None);
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_routes() {
+        for client in [s3::raster_client(), s3::grib_client()] {
+            client
+                .put(&S3Path::from("storage-test/uv.png"), vec![1u8, 2, 3].into())
+                .await
+                .unwrap();
+        }
+        let app = local_storage_routes().with_state(RaceManager::new());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}/storage", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let response = reqwest::get(format!("{}/wind-rasters/storage-test/uv.png", base))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), &[1, 2, 3]);
+        // Private buckets aren't served
+        let response = reqwest::get(format!("{}/grib-files/storage-test/uv.png", base))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
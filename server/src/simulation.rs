@@ -0,0 +1,587 @@
+use anyhow::{Result, anyhow};
+use object_store::ObjectStoreExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::courses::{Course, LngLat};
+use crate::energy::{self, Battery, System};
+use crate::polars::{self, Polar};
+use crate::{bathymetry, geo, land, s3, units, wind_reports};
+
+pub use crate::wind_png::WindRaster;
+
+/// How often boats are advanced (real time)
+pub const TICK_MS: u64 = 200;
+
+/// Turn rate toward the helm heading, in degrees per second (real time).
+/// Mirrors the tack rate of the client.
+const TURN_RATE: f64 = 90.0;
+
+/// Inertia time constant in seconds (real time), as on the client
+const INERTIA_TAU: f64 = 1.0;
+
+/// Duration of a sail change in seconds (real time), as on the client
+pub const SAIL_CHANGE_SECONDS: f64 = 4.0;
+
+/// Share of the polar speed reached while changing sails
+const SAIL_CHANGE_SPEED: f64 = 0.5;
+
+/// Length of the wind shadow cone downwind of a boat, in nautical miles
+const SHADOW_LENGTH_NM: f64 = 3.0;
+
+/// Half angle of the wind shadow cone, in degrees
+const SHADOW_HALF_ANGLE: f64 = 20.0;
+
+/// Share of the wind lost right behind a boat, fading along and across the cone
+const SHADOW_MAX_LOSS: f64 = 0.25;
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Download and decode a wind raster from the raster bucket
+pub async fn load_raster(png_path: &str) -> Result<WindRaster> {
+    let data = s3::raster_client()
+        .get(&object_store::path::Path::from(png_path))
+        .await?
+        .bytes()
+        .await?;
+    WindRaster::decode(&data)
+}
+
+/// Wind of a race over time. Only the rasters around the current race time
+/// are kept in memory; the simulation loop loads the missing ones.
+#[derive(Debug, Default)]
+pub struct RaceWind {
+    /// `(time, png_path)` of the race's wind reports, sorted by time
+    reports: Vec<(i64, String)>,
+    rasters: HashMap<i64, Arc<WindRaster>>,
+}
+
+impl RaceWind {
+    pub fn new(mut reports: Vec<(i64, String)>) -> Self {
+        reports.sort_by_key(|(time, _)| *time);
+        RaceWind {
+            reports,
+            rasters: HashMap::new(),
+        }
+    }
+
+    /// Reports in effect at `time`: the last one at or before it, and the next one
+    fn window(&self, time: i64) -> &[(i64, String)] {
+        let next = self.reports.partition_point(|(t, _)| *t <= time);
+        let from = next.saturating_sub(1);
+        let until = (next + 1).min(self.reports.len());
+        &self.reports[from..until]
+    }
+
+    /// Reports needed at `time` that are not loaded yet
+    pub fn missing(&self, time: i64) -> Vec<(i64, String)> {
+        self.window(time)
+            .iter()
+            .filter(|(t, _)| !self.rasters.contains_key(t))
+            .cloned()
+            .collect()
+    }
+
+    /// Store a loaded raster and drop the ones no longer needed at `time`
+    pub fn insert(&mut self, time: i64, raster: Arc<WindRaster>, now: i64) {
+        self.rasters.insert(time, raster);
+        let keep: Vec<i64> = self.window(now).iter().map(|(t, _)| *t).collect();
+        self.rasters.retain(|t, _| keep.contains(t));
+    }
+
+    /// Wind (u, v) in m/s, interpolated in time between the surrounding rasters.
+    /// None until the raster in effect is loaded.
+    pub fn wind_at(&self, time: i64, lng: f64, lat: f64) -> Option<(f64, f64)> {
+        let window = self.window(time);
+        let (current_time, _) = window.first()?;
+        let current = self.rasters.get(current_time)?.speed_at(lng, lat)?;
+
+        let next = window
+            .get(1)
+            .filter(|(next_time, _)| next_time > current_time && time > *current_time)
+            .and_then(|(next_time, _)| {
+                let wind = self.rasters.get(next_time)?.speed_at(lng, lat)?;
+                Some((*next_time, wind))
+            });
+        Some(match next {
+            Some((next_time, (u, v))) => {
+                let t = ((time - current_time) as f64 / (next_time - current_time) as f64)
+                    .clamp(0.0, 1.0);
+                (lerp(current.0, u, t), lerp(current.1, v, t))
+            }
+            None => current,
+        })
+    }
+}
+
+/// Polars and wind needed to simulate the boats of a race
+#[derive(Debug)]
+pub struct Simulation {
+    /// Polar of each boat class of the course, and of each of their sails
+    /// by sail polar name
+    pub polars: HashMap<String, Polar>,
+    /// Sail inventory of each boat class, empty for classes without sails
+    pub sails: HashMap<String, Vec<String>>,
+    pub wind: RaceWind,
+}
+
+impl Simulation {
+    pub async fn load(course: &Course) -> Result<Simulation> {
+        let mut polars = HashMap::new();
+        let mut sails = HashMap::new();
+        for class in course.classes() {
+            let polar = polars::get(class)
+                .await?
+                .ok_or(anyhow!("Polar not found: {}", class))?;
+            let foiling = polar.foiling.clone();
+            polars.insert(class.to_string(), polar);
+
+            let mut class_sails = Vec::new();
+            for (sail, mut polar) in polars::sails(class).await? {
+                // Sails fly on the foils of their boat
+                polar.foiling = foiling.clone();
+                polars.insert(polars::sail_polar_name(class, &sail), polar);
+                class_sails.push(sail);
+            }
+            sails.insert(class.to_string(), class_sails);
+        }
+        let reports = wind_reports::get_reports_for_course(course)
+            .await?
+            .into_iter()
+            .map(|r| (r.time.timestamp_millis(), r.png_path))
+            .collect::<Vec<_>>();
+        if reports.is_empty() {
+            return Err(anyhow!("No wind reports for course {}", course.key));
+        }
+        Ok(Simulation {
+            polars,
+            sails,
+            wind: RaceWind::new(reports),
+        })
+    }
+
+    /// Sail a boat of the class starts with: the first of its inventory
+    pub fn first_sail(&self, class: &str) -> Option<String> {
+        self.sails.get(class)?.first().cloned()
+    }
+
+    pub fn has_sail(&self, class: &str, sail: &str) -> bool {
+        self.sails
+            .get(class)
+            .is_some_and(|sails| sails.iter().any(|s| s == sail))
+    }
+
+    /// Polar a boat of the class sails on: its sail's, or the class polar
+    pub fn polar(&self, class: &str, sail: Option<&str>) -> Option<&Polar> {
+        match sail {
+            Some(sail) => self.polars.get(&polars::sail_polar_name(class, sail)),
+            None => self.polars.get(class),
+        }
+    }
+}
+
+/// What the skipper asks for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Helm {
+    /// Steer a compass heading (degrees)
+    Heading(f64),
+    /// Hold a signed true wind angle: heading = wind direction - TWA
+    Twa(f64),
+}
+
+/// State of a simulated boat
+#[derive(Debug, Clone)]
+pub struct Boat {
+    pub lng: f64,
+    pub lat: f64,
+    pub heading: f64,
+    /// Boat speed in knots
+    pub speed: f64,
+    pub helm: Helm,
+    /// Sail in use, None for classes without sails
+    pub sail: Option<String>,
+    /// Remaining sail change time in seconds (real time)
+    pub sail_change: f64,
+    /// Seconds sailed (real time), the phase of the swell when foiling
+    pub clock: f64,
+    /// Share of the wind left by the boats upwind, see `wind_shadow`
+    pub wind_shadow: f64,
+    /// Battery and systems, on courses managing energy
+    pub battery: Option<Battery>,
+    /// Course minimum depth in meters, see `Course::min_depth`
+    pub min_depth: Option<f64>,
+}
+
+impl Boat {
+    /// A boat on the start line, heading the course start heading
+    pub fn new(course: &Course) -> Self {
+        Boat {
+            lng: course.start.lng,
+            lat: course.start.lat,
+            heading: course.start_heading,
+            speed: 0.0,
+            helm: Helm::Heading(course.start_heading),
+            sail: None,
+            sail_change: 0.0,
+            clock: 0.0,
+            wind_shadow: 1.0,
+            battery: course.energy.as_ref().map(Battery::new),
+            min_depth: course.min_depth,
+        }
+    }
+
+    /// Whether the water at `position` is shallower than the course minimum depth
+    fn aground(&self, position: &LngLat) -> bool {
+        self.min_depth.is_some_and(|min_depth| {
+            bathymetry::depth(position).is_some_and(|depth| depth < min_depth)
+        })
+    }
+
+    /// Holding a wind angle takes the autopilot and the wind instruments
+    fn holds_wind_angle(&self) -> bool {
+        self.battery
+            .as_ref()
+            .is_none_or(|battery| battery.autopilot && battery.instruments)
+    }
+
+    /// Steer as asked, keeping the heading when the boat can't hold a wind angle
+    pub fn set_helm(&mut self, helm: Helm) {
+        self.helm = helm;
+        self.hand_steer();
+    }
+
+    fn hand_steer(&mut self) {
+        if !self.holds_wind_angle() && matches!(self.helm, Helm::Twa(_)) {
+            self.helm = Helm::Heading(self.heading);
+        }
+    }
+
+    /// Switch a system of the boat on or off
+    pub fn switch(&mut self, system: System, on: bool) -> Result<()> {
+        self.battery
+            .as_mut()
+            .ok_or(anyhow!("No energy management on this course"))?
+            .switch(system, on)?;
+        self.hand_steer();
+        Ok(())
+    }
+
+    /// Run the battery for `dt` seconds of real time at `race_time`. Returns
+    /// whether its state shown to the skipper changed.
+    pub fn run_battery(&mut self, dt: f64, time_factor: f64, race_time: i64) -> bool {
+        let Some(battery) = self.battery.as_mut() else {
+            return false;
+        };
+        let changed = battery.run(
+            dt * time_factor / 3600.0,
+            self.speed,
+            energy::daylight(race_time, self.lng, self.lat),
+        );
+        self.hand_steer();
+        changed
+    }
+
+    /// Hoist another sail, sailing slower for the time of the change
+    pub fn change_sail(&mut self, sail: &str) {
+        if self.sail.as_deref() != Some(sail) {
+            self.sail = Some(sail.to_string());
+            self.sail_change = SAIL_CHANGE_SECONDS;
+        }
+    }
+
+    pub fn position(&self) -> LngLat {
+        LngLat {
+            lng: self.lng,
+            lat: self.lat,
+        }
+    }
+
+    /// Advance the boat by `dt` seconds of real time in the wind `(u, v)` (m/s).
+    /// Mirrors the client `tick`: turn toward the helm, approach the polar speed
+    /// with inertia, then move for `dt × time_factor` seconds of course time.
+    /// `polar` is the one of the sail in use, slowed down during sail changes.
+    /// The wind speed is reduced by the boat's `wind_shadow`, the speed by its
+    /// systems when it has a battery.
+    /// Like the client, the boat stops instead of moving onto land.
+    pub fn step(&mut self, wind: (f64, f64), polar: &Polar, dt: f64, time_factor: f64) {
+        let (u, v) = wind;
+        let wind_direction = (-u).atan2(-v).to_degrees().rem_euclid(360.0);
+
+        let target = match self.helm {
+            Helm::Heading(heading) => heading,
+            Helm::Twa(twa) => wind_direction - twa,
+        }
+        .rem_euclid(360.0);
+        let diff = (target - self.heading + 540.0).rem_euclid(360.0) - 180.0;
+        let max_turn = TURN_RATE * dt;
+        self.heading = if diff.abs() <= max_turn {
+            target
+        } else {
+            (self.heading + max_turn.copysign(diff)).rem_euclid(360.0)
+        };
+
+        let tws = units::ms_to_knots(u.hypot(v)) * self.wind_shadow;
+        let twa = (wind_direction - self.heading + 540.0).rem_euclid(360.0) - 180.0;
+        self.clock += dt;
+        let mut target_speed = polar.boat_speed(tws, twa, self.clock);
+        if self.sail_change > 0.0 {
+            target_speed *= SAIL_CHANGE_SPEED;
+            self.sail_change = (self.sail_change - dt).max(0.0);
+        }
+        if let Some(battery) = &self.battery {
+            target_speed *= battery.speed_factor();
+        }
+        let alpha = 1.0 - (-dt / INERTIA_TAU).exp();
+        self.speed += (target_speed - self.speed) * alpha;
+
+        let distance_km = units::nm_to_km(self.speed) * (dt * time_factor / 3600.0);
+        let heading_rad = self.heading.to_radians();
+        let lng =
+            self.lng + distance_km * heading_rad.sin() / (111.0 * self.lat.to_radians().cos());
+        let next = LngLat {
+            lng: geo::wrap_lng(lng),
+            lat: geo::clamp_lat(self.lat + distance_km * heading_rad.cos() / 111.0),
+        };
+        if land::is_on_land(&next) || self.aground(&next) {
+            self.speed = 0.0;
+            return;
+        }
+        self.lng = next.lng;
+        self.lat = next.lat;
+    }
+}
+
+/// Share of the wind (u, v) left at `position` by the boats at `others`: each
+/// boat casts a shadow cone downwind, strongest right behind it
+pub fn wind_shadow<'a>(
+    position: &LngLat,
+    wind: (f64, f64),
+    others: impl IntoIterator<Item = &'a LngLat>,
+) -> f64 {
+    let (u, v) = wind;
+    let speed = u.hypot(v);
+    if speed == 0.0 {
+        return 1.0;
+    }
+    // Bearing the wind blows to
+    let downwind = u.atan2(v).to_degrees();
+
+    let strongest = others
+        .into_iter()
+        .map(|other| {
+            let tail = geo::destination(other, downwind, SHADOW_LENGTH_NM);
+            let along = geo::along_track_nm(position, other, &tail);
+            if along <= 0.0 || along > SHADOW_LENGTH_NM {
+                return 0.0;
+            }
+            let across = geo::cross_track_nm(position, other, &tail).abs();
+            let angle = across.atan2(along).to_degrees();
+            if angle > SHADOW_HALF_ANGLE {
+                return 0.0;
+            }
+            (1.0 - along / SHADOW_LENGTH_NM) * (1.0 - angle / SHADOW_HALF_ANGLE)
+        })
+        .fold(0.0, f64::max);
+    1.0 - SHADOW_MAX_LOSS * strongest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLAR_JSON: &str = r#"{
+        "0": { "0": 0, "90": 0, "180": 0 },
+        "10": { "0": 0, "90": 10, "180": 6 },
+        "20": { "0": 0, "90": 16, "180": 12 }
+    }"#;
+
+    #[test]
+    fn test_race_wind_interpolates_in_time() {
+        let mut wind = RaceWind::new(vec![
+            (2000, "b".into()),
+            (1000, "a".into()),
+            (3000, "c".into()),
+        ]);
+        assert_eq!(wind.missing(1500).len(), 2);
+        assert!(wind.wind_at(1500, 0.0, 0.0).is_none());
+
+        wind.insert(1000, Arc::new(WindRaster::uniform(0, 128)), 1500);
+        wind.insert(2000, Arc::new(WindRaster::uniform(255, 128)), 1500);
+        assert!(wind.missing(1500).is_empty());
+        let (u, _) = wind.wind_at(1500, 0.0, 0.0).unwrap();
+        assert!(u.abs() < 1e-9);
+
+        // Moving on drops the rasters no longer needed
+        wind.insert(3000, Arc::new(WindRaster::uniform(0, 128)), 2500);
+        assert!(!wind.rasters.contains_key(&1000));
+    }
+
+    #[test]
+    fn test_boat_steps_toward_helm_and_moves() {
+        let polar = Polar::from_json(POLAR_JSON).unwrap();
+        let mut boat = Boat {
+            lng: 0.0,
+            lat: 0.0,
+            heading: 0.0,
+            speed: 0.0,
+            helm: Helm::Heading(90.0),
+            sail: None,
+            sail_change: 0.0,
+            clock: 0.0,
+            wind_shadow: 1.0,
+            battery: None,
+            min_depth: None,
+        };
+        // Wind from the north (blowing south) at ~10 knots
+        let wind = (0.0, -10.0 / units::MS_TO_KNOTS);
+
+        boat.step(wind, &polar, 0.5, 1000.0);
+        assert_eq!(boat.heading, 45.0);
+        for _ in 0..20 {
+            boat.step(wind, &polar, 0.5, 1000.0);
+        }
+        assert_eq!(boat.heading, 90.0);
+        assert!((boat.speed - 10.0).abs() < 0.1);
+        assert!(boat.lng > 0.0);
+        assert!(boat.lat.abs() < 0.5);
+
+        // Holding a TWA follows the wind
+        boat.helm = Helm::Twa(-90.0);
+        boat.step(wind, &polar, 10.0, 1000.0);
+        assert_eq!(boat.heading, 90.0);
+        boat.helm = Helm::Twa(90.0);
+        boat.step(wind, &polar, 10.0, 1000.0);
+        assert_eq!(boat.heading, 270.0);
+    }
+
+    #[test]
+    fn test_sail_change_slows_the_boat() {
+        let polar = Polar::from_json(POLAR_JSON).unwrap();
+        let mut boat = Boat {
+            lng: 0.0,
+            lat: 0.0,
+            heading: 90.0,
+            speed: 10.0,
+            helm: Helm::Heading(90.0),
+            sail: Some("jib".to_string()),
+            sail_change: 0.0,
+            clock: 0.0,
+            wind_shadow: 1.0,
+            battery: None,
+            min_depth: None,
+        };
+        let wind = (0.0, -10.0 / units::MS_TO_KNOTS);
+
+        // Same sail: no change
+        boat.change_sail("jib");
+        assert_eq!(boat.sail_change, 0.0);
+
+        boat.change_sail("spinnaker");
+        assert_eq!(boat.sail.as_deref(), Some("spinnaker"));
+        for _ in 0..8 {
+            boat.step(wind, &polar, 0.5, 1000.0);
+        }
+        assert_eq!(boat.sail_change, 0.0);
+        assert!(boat.speed < 6.0);
+
+        for _ in 0..20 {
+            boat.step(wind, &polar, 0.5, 1000.0);
+        }
+        assert!((boat.speed - 10.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_autopilot_off_hand_steers() {
+        let polar = Polar::from_json(POLAR_JSON).unwrap();
+        let energy = crate::energy::Energy {
+            capacity: 10.0,
+            autopilot: 6.0,
+            instruments: 4.0,
+            solar: 0.0,
+            hydro: 0.0,
+            hydro_drag: 0.0,
+        };
+        let mut boat = Boat {
+            lng: 0.0,
+            lat: 0.0,
+            heading: 90.0,
+            speed: 10.0,
+            helm: Helm::Twa(90.0),
+            sail: None,
+            sail_change: 0.0,
+            clock: 0.0,
+            wind_shadow: 1.0,
+            battery: Some(Battery::new(&energy)),
+            min_depth: None,
+        };
+        let wind = (0.0, -10.0 / units::MS_TO_KNOTS);
+
+        boat.switch(System::Autopilot, false).unwrap();
+        assert_eq!(boat.helm, Helm::Heading(90.0));
+        boat.set_helm(Helm::Twa(-90.0));
+        assert_eq!(boat.helm, Helm::Heading(90.0));
+        for _ in 0..20 {
+            boat.step(wind, &polar, 0.5, 1000.0);
+        }
+        assert!((boat.speed - 10.0 * energy::MANUAL_SPEED).abs() < 0.1);
+
+        // The autopilot and instruments drain the battery in an hour
+        boat.switch(System::Autopilot, true).unwrap();
+        boat.set_helm(Helm::Twa(-90.0));
+        assert!(boat.run_battery(3.6, 1000.0, 0));
+        let battery = boat.battery.as_ref().unwrap();
+        assert_eq!(battery.charge, 0.0);
+        assert!(!battery.autopilot);
+        assert_eq!(boat.helm, Helm::Heading(90.0));
+        assert!(boat.switch(System::Autopilot, true).is_err());
+    }
+
+    #[test]
+    fn test_wind_shadow_downwind_of_boats() {
+        // Wind from the north: shadows extend south
+        let wind = (0.0, -10.0);
+        let upwind = LngLat { lng: 0.0, lat: 0.0 };
+        // A nautical mile of latitude
+        let nm = (1.0 / geo::EARTH_RADIUS_NM).to_degrees();
+
+        let behind = LngLat { lng: 0.0, lat: -nm };
+        let shadow = wind_shadow(&behind, wind, [&upwind]);
+        assert!((shadow - (1.0 - SHADOW_MAX_LOSS * 2.0 / 3.0)).abs() < 1e-9);
+
+        // Fading away from the axis, none out of the cone
+        let aside = LngLat {
+            lng: 0.2 * nm,
+            lat: -nm,
+        };
+        assert!(wind_shadow(&aside, wind, [&upwind]) > shadow);
+        assert_eq!(
+            wind_shadow(&LngLat { lng: nm, lat: -nm }, wind, [&upwind]),
+            1.0
+        );
+        assert_eq!(
+            wind_shadow(
+                &LngLat {
+                    lng: 0.0,
+                    lat: -4.0 * nm
+                },
+                wind,
+                [&upwind]
+            ),
+            1.0
+        );
+        assert_eq!(
+            wind_shadow(&LngLat { lng: 0.0, lat: nm }, wind, [&upwind]),
+            1.0
+        );
+        assert_eq!(wind_shadow(&behind, (0.0, 0.0), [&upwind]), 1.0);
+
+        // The strongest shadow wins
+        let closer = LngLat {
+            lng: 0.0,
+            lat: -0.5 * nm,
+        };
+        assert!(wind_shadow(&behind, wind, [&upwind, &closer]) < shadow);
+    }
+}
@@ -0,0 +1,158 @@
+//! Consistency check of the stored files against the database, run with
+//! `rewind storage gc`: wind rasters under `ncar/` against `wind_reports`
+//! and race paths under `paths/` against `race_results`. Other files of the
+//! buckets (land masks, heatmaps) are not tracked in the database and left
+//! alone.
+//!
+//! Files with no row are orphans, rows with no file are dangling. Both are
+//! reported, and deleted with `--delete`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use chrono::{TimeDelta, Utc};
+use futures::TryStreamExt;
+use object_store::path::Path;
+use object_store::{ObjectStore, ObjectStoreExt};
+
+use crate::{db, race_results, s3};
+
+/// Files younger than this are skipped: race paths are uploaded before
+/// their result is saved
+const GRACE_PERIOD: TimeDelta = TimeDelta::hours(1);
+
+/// Files and rows of a prefix that don't match
+#[derive(Debug, PartialEq)]
+struct Mismatch<T> {
+    orphans: Vec<String>,
+    dangling: Vec<T>,
+}
+
+/// Compare the keys of the stored files to the rows referencing them
+fn diff<T: Clone>(files: &BTreeSet<String>, rows: &BTreeMap<String, T>) -> Mismatch<T> {
+    Mismatch {
+        orphans: files
+            .iter()
+            .filter(|key| !rows.contains_key(*key))
+            .cloned()
+            .collect(),
+        dangling: rows
+            .iter()
+            .filter(|(key, _)| !files.contains(*key))
+            .map(|(_, row)| row.clone())
+            .collect(),
+    }
+}
+
+/// Keys of the files under a prefix, with those written during the grace
+/// period apart: neither orphans nor missing
+async fn list_files(
+    client: &dyn ObjectStore,
+    prefix: &str,
+) -> Result<(BTreeSet<String>, BTreeSet<String>)> {
+    let since = Utc::now() - GRACE_PERIOD;
+    let objects: Vec<_> = client.list(Some(&Path::from(prefix))).try_collect().await?;
+    let (old, recent): (Vec<_>, Vec<_>) = objects
+        .into_iter()
+        .partition(|meta| meta.last_modified < since);
+    let keys = |metas: Vec<object_store::ObjectMeta>| {
+        metas
+            .into_iter()
+            .map(|meta| meta.location.to_string())
+            .collect()
+    };
+    Ok((keys(old), keys(recent)))
+}
+
+/// Report the orphaned files and dangling rows, deleting them when `delete`
+/// is set
+pub async fn run(delete: bool) -> Result<()> {
+    let (rasters, recent_rasters) = list_files(s3::raster_client(), "ncar").await?;
+    let reports: Vec<(String,)> = sqlx::query_as("SELECT png_path FROM wind_reports")
+        .fetch_all(db::pool())
+        .await?;
+    let reports: BTreeMap<String, String> = reports
+        .into_iter()
+        .filter(|(path,)| !recent_rasters.contains(path))
+        .map(|(path,)| (path.clone(), path))
+        .collect();
+    let winds = diff(&rasters, &reports);
+
+    let (paths, recent_paths) = list_files(s3::paths_client(), "paths").await?;
+    let results: Vec<(i64, String)> = sqlx::query_as("SELECT id, path_s3_key FROM race_results")
+        .fetch_all(db::pool())
+        .await?;
+    let results: BTreeMap<String, i64> = results
+        .into_iter()
+        .filter(|(_, key)| !recent_paths.contains(key))
+        .map(|(id, key)| (key, id))
+        .collect();
+    let races = diff(&paths, &results);
+
+    for key in &winds.orphans {
+        println!("Orphaned raster: {}", key);
+    }
+    for path in &winds.dangling {
+        println!("Wind report without raster: {}", path);
+    }
+    for key in &races.orphans {
+        println!("Orphaned race path: {}", key);
+    }
+    for id in &races.dangling {
+        println!("Race result without path: {}", id);
+    }
+    println!(
+        "{} orphaned rasters, {} wind reports without raster, {} orphaned race paths, {} race results without path",
+        winds.orphans.len(),
+        winds.dangling.len(),
+        races.orphans.len(),
+        races.dangling.len()
+    );
+
+    if !delete {
+        return Ok(());
+    }
+    for key in &winds.orphans {
+        s3::raster_client()
+            .delete(&Path::from(key.as_str()))
+            .await?;
+    }
+    for path in &winds.dangling {
+        sqlx::query("DELETE FROM wind_reports WHERE png_path = $1")
+            .bind(path)
+            .execute(db::pool())
+            .await?;
+    }
+    for key in &races.orphans {
+        s3::paths_client().delete(&Path::from(key.as_str())).await?;
+    }
+    for id in &races.dangling {
+        race_results::delete_result(*id).await?;
+    }
+    println!("Deleted");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let files: BTreeSet<String> = ["paths/a.bin", "paths/b.bin"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let rows = BTreeMap::from([
+            ("paths/b.bin".to_string(), 2),
+            ("paths/c.bin".to_string(), 3),
+        ]);
+        assert_eq!(
+            diff(&files, &rows),
+            Mismatch {
+                orphans: vec!["paths/a.bin".to_string()],
+                dangling: vec![3],
+            }
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Object counts and sizes of the buckets, from listings of every file.
+//!
+//! Listings take a while on large buckets, so the totals shown by
+//! `/admin/stats` are kept for an hour. `rewind storage report` lists them
+//! again and breaks them down by prefix: GRIBs by source and year, rasters
+//! by source, race paths by course.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures::TryStreamExt;
+use object_store::ObjectStore;
+use serde::Serialize;
+
+use crate::config::config;
+use crate::s3;
+
+/// Age of the cached totals before they are listed again
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketUsage {
+    pub bucket: String,
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+static CACHE: Mutex<Option<(Instant, Vec<BucketUsage>)>> = Mutex::new(None);
+
+/// Buckets of the server with their stores, and the number of leading
+/// segments of their keys grouped together in the report
+fn buckets() -> [(&'static str, &'static dyn ObjectStore, usize); 5] {
+    let s3 = &config().s3;
+    [
+        // ncar/{year}/...
+        (s3.grib_bucket.as_str(), s3::grib_client(), 2),
+        // ncar/..., land/..., heatmaps/...
+        (s3.raster_bucket.as_str(), s3::raster_client(), 1),
+        // paths/{course}/...
+        (s3.paths_bucket.as_str(), s3::paths_client(), 2),
+        (s3.avatars_bucket.as_str(), s3::avatars_client(), 0),
+        (s3.backups_bucket.as_str(), s3::backups_client(), 0),
+    ]
+}
+
+/// Totals of every bucket, listed at most once an hour
+pub async fn usage() -> Result<Vec<BucketUsage>> {
+    if let Some((listed_at, usage)) = CACHE.lock().unwrap().as_ref()
+        && listed_at.elapsed() < CACHE_TTL
+    {
+        return Ok(usage.clone());
+    }
+    let mut usage = Vec::new();
+    for (bucket, client, _) in buckets() {
+        usage.push(bucket_usage(bucket, client).await?);
+    }
+    *CACHE.lock().unwrap() = Some((Instant::now(), usage.clone()));
+    Ok(usage)
+}
+
+/// Totals of the last listing, however old, none before the first one
+pub fn last_usage() -> Vec<BucketUsage> {
+    CACHE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|(_, usage)| usage.clone())
+        .unwrap_or_default()
+}
+
+async fn bucket_usage(bucket: &str, client: &dyn ObjectStore) -> Result<BucketUsage> {
+    let mut usage = BucketUsage {
+        bucket: bucket.to_string(),
+        objects: 0,
+        bytes: 0,
+    };
+    let mut objects = client.list(None);
+    while let Some(meta) = objects.try_next().await? {
+        usage.objects += 1;
+        usage.bytes += meta.size;
+    }
+    Ok(usage)
+}
+
+/// Totals of the keys of a bucket by their first `depth` directories
+async fn prefix_usage(
+    client: &dyn ObjectStore,
+    depth: usize,
+) -> Result<BTreeMap<String, (u64, u64)>> {
+    let mut prefixes = BTreeMap::new();
+    let mut objects = client.list(None);
+    while let Some(meta) = objects.try_next().await? {
+        let key = meta.location.to_string();
+        let (objects, bytes) = prefixes.entry(prefix(&key, depth)).or_insert((0, 0));
+        *objects += 1;
+        *bytes += meta.size;
+    }
+    Ok(prefixes)
+}
+
+/// First `depth` directories of a key, the file name excluded
+fn prefix(key: &str, depth: usize) -> String {
+    let dirs: Vec<&str> = key.split('/').collect();
+    let dirs = &dirs[..dirs.len() - 1];
+    dirs[..depth.min(dirs.len())].join("/")
+}
+
+/// Sizes in binary units, `1.5 GiB`
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Print the object counts and sizes of every bucket by prefix
+pub async fn report() -> Result<()> {
+    println!(
+        "{:<24} {:<32} {:>10} {:>12}",
+        "BUCKET", "PREFIX", "OBJECTS", "SIZE"
+    );
+    let (mut total_objects, mut total_bytes) = (0, 0);
+    for (bucket, client, depth) in buckets() {
+        let prefixes = prefix_usage(client, depth).await?;
+        let (mut bucket_objects, mut bucket_bytes) = (0, 0);
+        for (prefix, (objects, bytes)) in &prefixes {
+            let prefix = if prefix.is_empty() { "/" } else { prefix };
+            println!(
+                "{:<24} {:<32} {:>10} {:>12}",
+                bucket,
+                prefix,
+                objects,
+                format_bytes(*bytes)
+            );
+            bucket_objects += objects;
+            bucket_bytes += bytes;
+        }
+        if prefixes.len() != 1 {
+            println!(
+                "{:<24} {:<32} {:>10} {:>12}",
+                bucket,
+                "(total)",
+                bucket_objects,
+                format_bytes(bucket_bytes)
+            );
+        }
+        total_objects += bucket_objects;
+        total_bytes += bucket_bytes;
+    }
+    println!(
+        "{:<24} {:<32} {:>10} {:>12}",
+        "(total)",
+        "",
+        total_objects,
+        format_bytes(total_bytes)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::{ObjectStoreExt, PutPayload};
+
+    #[tokio::test]
+    async fn test_bucket_usage() {
+        let store = InMemory::new();
+        for (key, size) in [("a/1.png", 3), ("a/2.png", 5), ("b.bin", 10)] {
+            let payload = PutPayload::from(vec![0u8; size]);
+            store.put(&Path::from(key), payload).await.unwrap();
+        }
+        assert_eq!(
+            bucket_usage("rasters", &store).await.unwrap(),
+            BucketUsage {
+                bucket: "rasters".to_string(),
+                objects: 3,
+                bytes: 18,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prefix_usage() {
+        let store = InMemory::new();
+        for (key, size) in [
+            ("paths/vg20/1_a.bin", 3),
+            ("paths/vg20/2_b.bin", 5),
+            ("paths/fastnet/1_a.bin", 10),
+            ("healthcheck", 1),
+        ] {
+            let payload = PutPayload::from(vec![0u8; size]);
+            store.put(&Path::from(key), payload).await.unwrap();
+        }
+        assert_eq!(
+            prefix_usage(&store, 2).await.unwrap(),
+            BTreeMap::from([
+                (String::new(), (1, 1)),
+                ("paths/fastnet".to_string(), (1, 10)),
+                ("paths/vg20".to_string(), (2, 8)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(prefix("ncar/2024/0115/6/wind.grib2", 2), "ncar/2024");
+        assert_eq!(prefix("land/mask-4096.png", 2), "land");
+        assert_eq!(prefix("1/3/64.png", 0), "");
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
@@ -0,0 +1,134 @@
+//! Object store wrapper recording a span for each storage call, with the
+//! bucket and the key, so that slow uploads and listings show in traces.
+
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use object_store::path::Path;
+use object_store::{
+    CopyOptions, GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore,
+    PutMultipartOptions, PutOptions, PutPayload, PutResult, RenameOptions, Result,
+};
+use tracing::Instrument;
+
+#[derive(Debug)]
+pub struct TracedStore {
+    bucket: String,
+    inner: Arc<dyn ObjectStore>,
+}
+
+impl TracedStore {
+    pub fn new(bucket: &str, inner: Arc<dyn ObjectStore>) -> TracedStore {
+        TracedStore {
+            bucket: bucket.to_string(),
+            inner,
+        }
+    }
+
+    fn span(&self, operation: &'static str, location: Option<&Path>) -> tracing::Span {
+        tracing::info_span!(
+            "storage",
+            otel.name = format!("storage {}", operation),
+            bucket = %self.bucket,
+            key = location.map(|path| path.as_ref()),
+        )
+    }
+}
+
+impl fmt::Display for TracedStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Traced({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for TracedStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> Result<PutResult> {
+        self.inner
+            .put_opts(location, payload, opts)
+            .instrument(self.span("put", Some(location)))
+            .await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOptions,
+    ) -> Result<Box<dyn MultipartUpload>> {
+        self.inner
+            .put_multipart_opts(location, opts)
+            .instrument(self.span("put_multipart", Some(location)))
+            .await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> Result<GetResult> {
+        self.inner
+            .get_opts(location, options)
+            .instrument(self.span("get", Some(location)))
+            .await
+    }
+
+    async fn get_ranges(&self, location: &Path, ranges: &[Range<u64>]) -> Result<Vec<Bytes>> {
+        self.inner
+            .get_ranges(location, ranges)
+            .instrument(self.span("get_ranges", Some(location)))
+            .await
+    }
+
+    fn delete_stream(
+        &self,
+        locations: BoxStream<'static, Result<Path>>,
+    ) -> BoxStream<'static, Result<Path>> {
+        // The span lasts as long as the stream
+        let span = self.span("delete", None);
+        self.inner
+            .delete_stream(locations)
+            .map(move |result| {
+                let _entered = span.enter();
+                result
+            })
+            .boxed()
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'static, Result<ObjectMeta>> {
+        let span = self.span("list", prefix);
+        self.inner
+            .list(prefix)
+            .map(move |result| {
+                let _entered = span.enter();
+                result
+            })
+            .boxed()
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> Result<ListResult> {
+        self.inner
+            .list_with_delimiter(prefix)
+            .instrument(self.span("list", prefix))
+            .await
+    }
+
+    async fn copy_opts(&self, from: &Path, to: &Path, options: CopyOptions) -> Result<()> {
+        self.inner
+            .copy_opts(from, to, options)
+            .instrument(self.span("copy", Some(from)))
+            .await
+    }
+
+    async fn rename_opts(&self, from: &Path, to: &Path, options: RenameOptions) -> Result<()> {
+        self.inner
+            .rename_opts(from, to, options)
+            .instrument(self.span("rename", Some(from)))
+            .await
+    }
+}
@@ -0,0 +1,75 @@
+//! TypeScript declarations of the types the web client shares with the
+//! server: the multiplayer messages and the courses. `rewind export-types`
+//! writes them to keep the client in sync with the Rust types.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use ts_rs::TS;
+
+use crate::courses::{Course, CourseTag, Gate, LngLat};
+use crate::energy::{Energy, System};
+use crate::geo::{Angle, Latitude, Longitude};
+use crate::multiplayer::{
+    ClientMessage, Encoding, LeaderboardEntry, PlayerInfo, PlayerStatus, ServerMessage,
+    WindRasterSource,
+};
+use crate::routing::Hint;
+
+/// Declarations of the shared types, the types they refer to first
+pub fn declarations() -> String {
+    let decls = [
+        LngLat::decl(),
+        Gate::decl(),
+        Energy::decl(),
+        CourseTag::decl(),
+        Course::decl(),
+        System::decl(),
+        Encoding::decl(),
+        WindRasterSource::decl(),
+        PlayerInfo::decl(),
+        PlayerStatus::decl(),
+        LeaderboardEntry::decl(),
+        Hint::decl(),
+        Longitude::decl(),
+        Latitude::decl(),
+        Angle::decl(),
+        ClientMessage::decl(),
+        ServerMessage::decl(),
+    ];
+
+    let mut ts = String::from("// Generated by `rewind export-types`, do not edit.\n");
+    for decl in decls {
+        ts.push_str("\nexport ");
+        ts.push_str(&decl);
+        ts.push('\n');
+    }
+    ts
+}
+
+pub fn export(path: Option<PathBuf>) -> Result<()> {
+    let ts = declarations();
+    match path {
+        Some(p) => {
+            std::fs::write(&p, &ts)?;
+            log::info!("Exported TypeScript types to {}", p.display());
+        }
+        None => print!("{ts}"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declarations() {
+        let ts = declarations();
+        assert!(ts.contains("export type ClientMessage = "));
+        assert!(ts.contains("export type ServerMessage = "));
+        assert!(ts.contains("\"type\": \"Hello\""));
+        // Times are numbers of milliseconds on the client, not bigints
+        assert!(!ts.contains("bigint"));
+    }
+}
@@ -0,0 +1,48 @@
+//! Units of the game: wind in m/s, boat speeds in knots, distances in
+//! nautical miles, race times in milliseconds. The client mirrors the
+//! conversions in `utils.ts`, with the same factors so boats move alike.
+
+/// Knots in one m/s, rounded as on the client
+pub const MS_TO_KNOTS: f64 = 1.944;
+
+pub const KM_PER_NM: f64 = 1.852;
+
+pub const METERS_PER_NM: f64 = 1852.0;
+
+pub fn ms_to_knots(ms: f64) -> f64 {
+    ms * MS_TO_KNOTS
+}
+
+pub fn nm_to_km(nm: f64) -> f64 {
+    nm * KM_PER_NM
+}
+
+/// Elapsed time in milliseconds as "3d 04:12:05"
+pub fn format_elapsed(ms: i64) -> String {
+    let seconds = ms / 1000;
+    format!(
+        "{}d {:02}:{:02}:{:02}",
+        seconds / 86400,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert!((ms_to_knots(10.0) - 19.44).abs() < 1e-9);
+        assert!((nm_to_km(1.0) * 1000.0 - METERS_PER_NM).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(4_000), "0d 00:00:04");
+        let ms = (((3 * 24 + 4) * 60 + 12) * 60 + 5) * 1000;
+        assert_eq!(format_elapsed(ms), "3d 04:12:05");
+    }
+}
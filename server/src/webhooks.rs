@@ -0,0 +1,449 @@
+//! Outgoing webhooks: URLs registered by admins get a signed JSON POST for
+//! each race and result event they subscribe to, so external sites can
+//! follow races without polling the API.
+//!
+//! Events are queued in `webhook_deliveries`, one per subscribed webhook, and
+//! posted by a background worker of the `LeasedQueue` of `retry.rs`, which
+//! retries failed deliveries before dead-lettering them.
+//!
+//! Each POST carries `X-Rewind-Event`, `X-Rewind-Delivery` and
+//! `X-Rewind-Signature: t={unix seconds},v1={hex}`, the HMAC-SHA256 of
+//! `{t}.{body}` keyed by the webhook secret, shown once at creation.
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::Sha256;
+
+use crate::db;
+use crate::retry::LeasedQueue;
+
+/// Start of every webhook secret
+const SECRET_PREFIX: &str = "whsec_";
+
+const MAX_WEBHOOKS: i64 = 20;
+
+/// Time a receiver has to answer
+const TIMEOUT_SECS: u64 = 10;
+
+/// Deliveries are leased for twice the time a receiver has to answer
+static QUEUE: LeasedQueue = LeasedQueue::new("webhook_deliveries").with_lease(60 * 1000);
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(TIMEOUT_SECS))
+        .build()
+        .expect("Failed to build the webhook HTTP client")
+});
+
+/// What a webhook is notified of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    /// Public race created by a player, or lobby opened by the scheduler
+    #[serde(rename = "race.created")]
+    RaceCreated,
+    #[serde(rename = "race.started")]
+    RaceStarted,
+    /// Race closed, boats still racing being DNF
+    #[serde(rename = "race.ended")]
+    RaceEnded,
+    /// Finish, retirement or DNF of a player saved
+    #[serde(rename = "result.saved")]
+    ResultSaved,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::RaceCreated,
+        WebhookEvent::RaceStarted,
+        WebhookEvent::RaceEnded,
+        WebhookEvent::ResultSaved,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::RaceCreated => "race.created",
+            WebhookEvent::RaceStarted => "race.started",
+            WebhookEvent::RaceEnded => "race.ended",
+            WebhookEvent::ResultSaved => "result.saved",
+        }
+    }
+
+    fn parse(s: &str) -> Option<WebhookEvent> {
+        WebhookEvent::ALL
+            .into_iter()
+            .find(|event| event.as_str() == s)
+    }
+}
+
+fn parse_events(events: &str) -> Vec<WebhookEvent> {
+    events
+        .split_whitespace()
+        .filter_map(WebhookEvent::parse)
+        .collect()
+}
+
+/// A registered webhook, without its secret
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    /// Events posted to the webhook, all of them when empty
+    pub events: Vec<WebhookEvent>,
+    pub created_at: i64,
+    /// Deliveries given up on
+    pub dead_deliveries: i64,
+}
+
+/// A new webhook, with its secret shown this once
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedWebhook {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+}
+
+fn generate_secret() -> String {
+    let bytes: [u8; 24] = rand::rng().random();
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", SECRET_PREFIX, hex)
+}
+
+/// Register a webhook for some events, all of them when none is given
+pub async fn create(request: &CreateWebhookRequest, created_by: &str) -> Result<CreatedWebhook> {
+    let url = request.url.trim();
+    match Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+        _ => bail!("Webhook URL must be an http or https URL"),
+    }
+
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM webhooks")
+        .fetch_one(db::pool())
+        .await?;
+    if count >= MAX_WEBHOOKS {
+        bail!("Maximum of {} webhooks", MAX_WEBHOOKS);
+    }
+
+    let events: Vec<WebhookEvent> = WebhookEvent::ALL
+        .into_iter()
+        .filter(|event| request.events.contains(event))
+        .collect();
+    let event_names: Vec<&str> = events.iter().map(|e| e.as_str()).collect();
+    let secret = generate_secret();
+
+    let (id, created_at): (i64, i64) = sqlx::query_as(
+        "INSERT INTO webhooks (url, secret, events, created_by) VALUES ($1, $2, $3, $4)
+         RETURNING id, created_at",
+    )
+    .bind(url)
+    .bind(&secret)
+    .bind(event_names.join(" "))
+    .bind(created_by)
+    .fetch_one(db::pool())
+    .await?;
+
+    Ok(CreatedWebhook {
+        webhook: Webhook {
+            id,
+            url: url.to_string(),
+            events,
+            created_at,
+            dead_deliveries: 0,
+        },
+        secret,
+    })
+}
+
+pub async fn list() -> Result<Vec<Webhook>> {
+    let rows: Vec<(i64, String, String, i64, i64)> = sqlx::query_as(
+        "SELECT w.id, w.url, w.events, w.created_at,
+           (SELECT COUNT(*) FROM webhook_deliveries d WHERE d.webhook_id = w.id AND d.status = 'dead')
+         FROM webhooks w ORDER BY w.id",
+    )
+    .fetch_all(db::pool())
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, url, events, created_at, dead_deliveries)| Webhook {
+            id,
+            url,
+            events: parse_events(&events),
+            created_at,
+            dead_deliveries,
+        })
+        .collect())
+}
+
+/// Delete a webhook and its pending deliveries. Returns false when not found
+pub async fn delete(id: i64) -> Result<bool> {
+    sqlx::query("DELETE FROM webhook_deliveries WHERE webhook_id = $1")
+        .bind(id)
+        .execute(db::pool())
+        .await?;
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+        .bind(id)
+        .execute(db::pool())
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Queue an event for the webhooks subscribed to it, in the background
+pub fn dispatch(event: WebhookEvent, data: Value) {
+    tokio::spawn(async move {
+        if let Err(e) = enqueue(event, data, Utc::now().timestamp_millis()).await {
+            log::error!("Failed to queue webhook event {}: {}", event.as_str(), e);
+        }
+    });
+}
+
+/// Queue an event for the webhooks subscribed to it, returning their number
+async fn enqueue(event: WebhookEvent, data: Value, now: i64) -> Result<usize> {
+    let webhooks: Vec<(i64, String)> = sqlx::query_as("SELECT id, events FROM webhooks")
+        .fetch_all(db::pool())
+        .await?;
+    let subscribed: Vec<i64> = webhooks
+        .into_iter()
+        .filter(|(_, events)| {
+            let events = parse_events(events);
+            events.is_empty() || events.contains(&event)
+        })
+        .map(|(id, _)| id)
+        .collect();
+    if subscribed.is_empty() {
+        return Ok(0);
+    }
+
+    let payload = json!({
+        "event": event.as_str(),
+        "createdAt": now,
+        "data": data,
+    })
+    .to_string();
+    for webhook_id in &subscribed {
+        sqlx::query(
+            "INSERT INTO webhook_deliveries (webhook_id, event, payload, next_attempt_at)
+             VALUES ($1, $2, $3, $4)",
+        )
+        .bind(webhook_id)
+        .bind(event.as_str())
+        .bind(&payload)
+        .bind(now)
+        .execute(db::pool())
+        .await?;
+    }
+    QUEUE.notify();
+    Ok(subscribed.len())
+}
+
+/// Value of the `X-Rewind-Signature` header of a body sent at `timestamp`
+/// (unix seconds)
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("t={},v1={}", timestamp, hex)
+}
+
+/// Post a delivery, failing on errors and non 2xx answers
+async fn post(id: i64, event: &str, payload: String, url: &str, secret: &str) -> Result<()> {
+    let timestamp = Utc::now().timestamp();
+    let response = CLIENT
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("User-Agent", "Rewind-Webhooks")
+        .header("X-Rewind-Event", event)
+        .header("X-Rewind-Delivery", id.to_string())
+        .header("X-Rewind-Signature", signature(secret, timestamp, &payload))
+        .body(payload)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        bail!("{}", response.status());
+    }
+    Ok(())
+}
+
+type DeliveryRow = (i64, String, String, i64, String, String);
+
+/// Post the due deliveries, returning how many were delivered
+async fn process_due(now: i64) -> Result<usize> {
+    let due: Vec<DeliveryRow> = sqlx::query_as(
+        "SELECT d.id, d.event, d.payload, d.attempts, w.url, w.secret
+         FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+         WHERE d.status = 'pending' AND d.next_attempt_at <= $1
+         ORDER BY d.next_attempt_at LIMIT $2",
+    )
+    .bind(now)
+    .bind(QUEUE.batch_size)
+    .fetch_all(db::pool())
+    .await?;
+
+    let mut delivered = 0;
+    for (id, event, payload, attempts, url, secret) in due {
+        if !QUEUE.lease(id, now).await? {
+            continue;
+        }
+
+        match post(id, &event, payload, &url, &secret).await {
+            Ok(()) => {
+                QUEUE.complete(id).await?;
+                delivered += 1;
+            }
+            Err(e) => {
+                if QUEUE.fail(id, attempts, &e.to_string(), now).await? {
+                    log::error!("Giving up on webhook delivery {} to {}: {}", id, url, e);
+                } else {
+                    log::warn!(
+                        "Failed to deliver webhook {} to {} (attempt {}): {}",
+                        id,
+                        url,
+                        attempts + 1,
+                        e
+                    );
+                }
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+/// Post the queued events as they come and retry the failed ones, until the
+/// server stops
+pub async fn run_worker() {
+    QUEUE.run_worker(process_due).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, http::HeaderMap, routing::post};
+    use std::sync::{Arc, Mutex};
+
+    type Received = Arc<Mutex<Vec<(String, String, String)>>>;
+
+    /// Receiver recording the event, signature and body of the requests
+    async fn receiver() -> (String, Received) {
+        let received = Received::default();
+        let recorded = received.clone();
+        let app = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: String| async move {
+                let header = |name| headers[name].to_str().unwrap().to_string();
+                recorded.lock().unwrap().push((
+                    header("x-rewind-event"),
+                    header("x-rewind-signature"),
+                    body,
+                ));
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, received)
+    }
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(
+            signature("secret", 1_700_000_000, "{}"),
+            "t=1700000000,v1=b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deliveries() {
+        db::init_test().await.unwrap();
+        let (url, received) = receiver().await;
+
+        assert!(
+            create(
+                &CreateWebhookRequest {
+                    url: "ftp://example.com".to_string(),
+                    events: vec![],
+                },
+                "admin",
+            )
+            .await
+            .is_err()
+        );
+        let up = create(
+            &CreateWebhookRequest {
+                url,
+                events: vec![WebhookEvent::ResultSaved],
+            },
+            "admin",
+        )
+        .await
+        .unwrap();
+        let down = create(
+            &CreateWebhookRequest {
+                url: "http://127.0.0.1:1/hook".to_string(),
+                events: vec![],
+            },
+            "admin",
+        )
+        .await
+        .unwrap();
+
+        let now = Utc::now().timestamp_millis();
+        let data = json!({ "resultId": 1 });
+        assert_eq!(
+            enqueue(WebhookEvent::RaceStarted, data.clone(), now)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            enqueue(WebhookEvent::ResultSaved, data, now).await.unwrap(),
+            2
+        );
+
+        assert_eq!(process_due(now).await.unwrap(), 1);
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received.len(), 1);
+        let (event, signature, body) = &received[0];
+        assert_eq!(event, "result.saved");
+        let timestamp: i64 = signature[2..signature.find(',').unwrap()].parse().unwrap();
+        assert_eq!(*signature, super::signature(&up.secret, timestamp, body));
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["data"]["resultId"], 1);
+
+        // Deliveries to the unreachable webhook are retried, then dead
+        let mut time = now;
+        for _ in 0..QUEUE.max_attempts {
+            time += QUEUE.max_backoff_ms;
+            process_due(time).await.unwrap();
+        }
+        let webhooks = list().await.unwrap();
+        let listed = webhooks.iter().find(|w| w.id == down.webhook.id).unwrap();
+        assert_eq!(listed.dead_deliveries, 2);
+        assert!(listed.events.is_empty());
+
+        assert!(delete(down.webhook.id).await.unwrap());
+        assert!(!delete(down.webhook.id).await.unwrap());
+        assert!(delete(up.webhook.id).await.unwrap());
+    }
+}
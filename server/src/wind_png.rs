@@ -0,0 +1,268 @@
+//! Wind raster PNGs: the format GRIB reports are converted to, read by the
+//! server simulation and by the client (`client/src/wind-raster.ts`).
+//!
+//! - R is the U (eastward) component and G the V (northward) component, in
+//!   m/s mapped linearly from -30..30 to 0..255; B is 0
+//! - Pixels are square: the width covers 360° of longitude and the height
+//!   180° of latitude
+//! - Row 0 is latitude 90°, column 0 is longitude 0°, eastward
+//!
+//! The client decodes with the same constants; change both together.
+
+use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use png::{BitDepth, ColorType, Encoder};
+use std::io::Cursor;
+
+/// Wind component range encoded in the channels (m/s)
+const WIND_MIN: f32 = -30.0;
+const WIND_MAX: f32 = 30.0;
+
+/// Encode U and V wind components (m/s) of a `width` × `height` grid,
+/// row by row from the north, as a raster PNG
+pub fn encode(u: &[f32], v: &[f32], width: usize, height: usize) -> Result<Bytes> {
+    if u.len() != width * height || v.len() != width * height {
+        return Err(anyhow!("Wind grid doesn't match {}x{}", width, height));
+    }
+    let rgb_data: Vec<u8> = u
+        .iter()
+        .zip(v)
+        .flat_map(|(u, v)| [normalize_wind(*u), normalize_wind(*v), 0])
+        .collect();
+    encode_png(&rgb_data, width, height)
+}
+
+/// Wind component in m/s of an encoded channel value
+pub fn decode_component(n: u8) -> f64 {
+    let (min, max) = (WIND_MIN as f64, WIND_MAX as f64);
+    min + n as f64 * (max - min) / 255.0
+}
+
+/// A decoded wind raster
+#[derive(Debug)]
+pub struct WindRaster {
+    width: usize,
+    height: usize,
+    channels: usize,
+    data: Vec<u8>,
+}
+
+impl WindRaster {
+    pub fn decode(png_data: &[u8]) -> Result<WindRaster> {
+        let decoder = png::Decoder::new(Cursor::new(png_data));
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0u8; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data)?;
+        if info.bit_depth != png::BitDepth::Eight {
+            return Err(anyhow!("Unsupported wind raster bit depth"));
+        }
+        data.truncate(info.buffer_size());
+        Ok(WindRaster {
+            width: info.width as usize,
+            height: info.height as usize,
+            channels: info.color_type.samples(),
+            data,
+        })
+    }
+
+    /// Raster with the same encoded wind in every pixel
+    #[cfg(test)]
+    pub fn uniform(u: u8, v: u8) -> WindRaster {
+        let (width, height) = (8, 4);
+        WindRaster {
+            width,
+            height,
+            channels: 3,
+            data: [u, v, 0].repeat(width * height),
+        }
+    }
+
+    /// Wind (u, v) in m/s at a position, bilinear between pixels like the client
+    pub fn speed_at(&self, lng: f64, lat: f64) -> Option<(f64, f64)> {
+        if !(-90.0..=90.0).contains(&lat) {
+            return None;
+        }
+        let pixel_size = 360.0 / self.width as f64;
+        let grib_lng = lng.rem_euclid(360.0);
+        let x = grib_lng / pixel_size;
+        let y = (90.0 - lat) / pixel_size;
+
+        let component = |offset: usize| {
+            let value = |px: f64, py: f64| {
+                let px = px as usize % self.width;
+                let py = (py as usize).min(self.height - 1);
+                let n = self.data[(py * self.width + px) * self.channels + offset];
+                decode_component(n)
+            };
+            let (xf, xc, yf, yc) = (x.floor(), x.ceil(), y.floor(), y.ceil());
+            let (top, bottom) = if xf == xc {
+                (value(xf, yf), value(xf, yc))
+            } else {
+                (
+                    value(xf, yf) * (xc - x) + value(xc, yf) * (x - xf),
+                    value(xf, yc) * (xc - x) + value(xc, yc) * (x - xf),
+                )
+            };
+            if yf == yc {
+                top
+            } else {
+                top * (yc - y) + bottom * (y - yf)
+            }
+        };
+
+        Some((component(0), component(1)))
+    }
+}
+
+/// Encode a wind component, clamped to -30..30 m/s, as 0..255
+fn normalize_wind(value: f32) -> u8 {
+    let clamped = value.clamp(WIND_MIN, WIND_MAX);
+    let normalized = (clamped - WIND_MIN) / (WIND_MAX - WIND_MIN);
+    (normalized * 255.0).round() as u8
+}
+
+fn encode_png(rgb_data: &[u8], width: usize, height: usize) -> Result<Bytes> {
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buffer, width as u32, height as u32);
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_depth(BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(rgb_data)?;
+    }
+
+    Ok(Bytes::from(buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // =========================================================================
+    // normalize_wind tests
+    // =========================================================================
+
+    #[test]
+    fn test_normalize_wind_boundaries() {
+        assert_eq!(normalize_wind(-30.0), 0);
+        assert_eq!(normalize_wind(30.0), 255);
+    }
+
+    #[test]
+    fn test_normalize_wind_zero() {
+        // 0 m/s should map to middle of range: (0 - (-30)) / 60 * 255 = 127.5 → 128
+        assert_eq!(normalize_wind(0.0), 128);
+    }
+
+    #[test]
+    fn test_normalize_wind_clamping() {
+        // Values outside -30..30 should be clamped
+        assert_eq!(normalize_wind(-50.0), 0);
+        assert_eq!(normalize_wind(-100.0), 0);
+        assert_eq!(normalize_wind(50.0), 255);
+        assert_eq!(normalize_wind(100.0), 255);
+    }
+
+    #[test]
+    fn test_normalize_wind_negative_values() {
+        // -15 m/s: (-15 - (-30)) / 60 * 255 = 15/60 * 255 = 63.75 → 64
+        assert_eq!(normalize_wind(-15.0), 64);
+    }
+
+    #[test]
+    fn test_normalize_wind_positive_values() {
+        // 15 m/s: (15 - (-30)) / 60 * 255 = 45/60 * 255 = 191.25 → 191
+        assert_eq!(normalize_wind(15.0), 191);
+    }
+
+    #[test]
+    fn test_normalize_wind_typical_sailing_speeds() {
+        // Light wind: 5 m/s (~10 knots)
+        let light = normalize_wind(5.0);
+        assert!(light > 128 && light < 180);
+
+        // Moderate wind: 10 m/s (~20 knots)
+        let moderate = normalize_wind(10.0);
+        assert!(moderate > 150 && moderate < 200);
+
+        // Strong wind: 20 m/s (~40 knots)
+        let strong = normalize_wind(20.0);
+        assert!(strong > 200 && strong < 255);
+    }
+
+    #[test]
+    fn test_normalize_wind_nan_handling() {
+        // NaN.clamp returns NaN, and (NaN * 255.0).round() as u8 = 0
+        // Just verify it doesn't panic - the result is a valid u8 by type definition
+        let _result = normalize_wind(f32::NAN);
+    }
+
+    #[test]
+    fn test_normalize_wind_infinity() {
+        // Positive infinity should clamp to max
+        assert_eq!(normalize_wind(f32::INFINITY), 255);
+        // Negative infinity should clamp to min
+        assert_eq!(normalize_wind(f32::NEG_INFINITY), 0);
+    }
+
+    // =========================================================================
+    // Encoding and sampling tests
+    // =========================================================================
+
+    #[test]
+    fn test_encode_png_small_image() {
+        let rgb_data = vec![255u8; 10 * 10 * 3]; // White 10x10 image
+        let result = encode_png(&rgb_data, 10, 10);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_encode_rejects_mismatched_grid() {
+        assert!(encode(&[0.0; 8], &[0.0; 8], 4, 3).is_err());
+    }
+
+    #[test]
+    fn test_decode_component_inverts_encoding() {
+        for value in [-30.0, -12.5, 0.0, 7.3, 30.0] {
+            let decoded = decode_component(normalize_wind(value));
+            // Within half a step of the 60 m/s range over 255 values
+            assert!((decoded - value as f64).abs() <= 60.0 / 255.0 / 2.0 + 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_raster_decode_and_sample() {
+        let mut png_data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png_data, 4, 2);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[255, 0, 0].repeat(8)).unwrap();
+        }
+        let raster = WindRaster::decode(&png_data).unwrap();
+        let (u, v) = raster.speed_at(-10.0, 45.0).unwrap();
+        assert!((u - 30.0).abs() < 1e-9);
+        assert!((v + 30.0).abs() < 1e-9);
+        assert!(raster.speed_at(0.0, 95.0).is_none());
+    }
+
+    #[test]
+    fn test_encode_then_sample() {
+        // 1° pixels: 10 m/s westerly and 5 m/s southerly at 45°N 10°E
+        let (width, height) = (360, 180);
+        let mut u = vec![0.0f32; width * height];
+        let mut v = vec![0.0f32; width * height];
+        let pixel = 45 * width + 10;
+        u[pixel] = 10.0;
+        v[pixel] = 5.0;
+
+        let raster = WindRaster::decode(&encode(&u, &v, width, height).unwrap()).unwrap();
+        let (u, v) = raster.speed_at(10.0, 45.0).unwrap();
+        assert!((u - 10.0).abs() < 0.2);
+        assert!((v - 5.0).abs() < 0.2);
+        let (u, _) = raster.speed_at(-10.0, 45.0).unwrap();
+        assert!(u.abs() < 0.2);
+    }
+}
@@ -1,4 +1,3 @@
-use crate::config::config;
 use crate::courses::Course;
 use crate::db;
 use crate::s3;
@@ -6,7 +5,7 @@ use anyhow::Result;
 use chrono::serde::ts_milliseconds;
 use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
 use futures::TryStreamExt;
-use object_store::ObjectStore;
+use rand::seq::IndexedRandom;
 use serde::{Deserialize, Serialize};
 
 /// GFS data source identifier
@@ -28,8 +27,8 @@ fn default_source() -> String {
 }
 
 impl WindReport {
-    pub fn png_url(&self) -> String {
-        config().s3.raster_url(&self.png_path)
+    pub async fn png_url(&self) -> Result<String> {
+        s3::raster_url(&self.png_path).await
     }
 }
 
@@ -42,6 +41,7 @@ pub async fn get_report_count() -> Result<i64> {
 }
 
 /// Get existing report times by listing PNG files in S3 (stateless, no DB needed)
+#[tracing::instrument]
 pub async fn get_existing_times_from_s3() -> Result<std::collections::HashSet<i64>> {
     let client = s3::raster_client();
     let prefix = object_store::path::Path::from("ncar");
@@ -50,7 +50,7 @@ pub async fn get_existing_times_from_s3() -> Result<std::collections::HashSet<i6
     let mut times = std::collections::HashSet::new();
     for meta in objects {
         let path = meta.location.to_string();
-        if !path.ends_with("/uv.png") {
+        if !is_uv_png(&path) {
             continue;
         }
         if let Some(report) = parse_ncar_png_path(&path) {
@@ -65,7 +65,7 @@ pub async fn get_existing_times_from_s3() -> Result<std::collections::HashSet<i6
 pub async fn upsert_wind_report(report: &WindReport) -> Result<bool> {
     let time_ms = report.time.timestamp_millis();
     let result = sqlx::query(
-        "INSERT INTO wind_reports (time, grib_path, png_path, source) VALUES (?, ?, ?, ?)
+        "INSERT INTO wind_reports (time, grib_path, png_path, source) VALUES ($1, $2, $3, $4)
          ON CONFLICT(time) DO UPDATE SET grib_path=excluded.grib_path, png_path=excluded.png_path, source=excluded.source",
     )
     .bind(time_ms)
@@ -97,13 +97,17 @@ pub async fn get_random_report() -> Result<Option<WindReport>> {
 }
 
 /// Get reports for a given course (within time range)
+#[tracing::instrument(skip_all, fields(course_key = %course.key))]
 pub async fn get_reports_for_course(course: &Course) -> Result<Vec<WindReport>> {
     let since = course.start_time - TimeDelta::days(1).num_milliseconds();
-    let until = course.max_finish_time();
+    get_reports_between(since, course.max_finish_time()).await
+}
 
+/// Get reports from `since` to `until` (ms since epoch), in order
+pub async fn get_reports_between(since: i64, until: i64) -> Result<Vec<WindReport>> {
     let rows: Vec<(i64, String, String, String)> = sqlx::query_as(
         "SELECT time, grib_path, png_path, source FROM wind_reports
-         WHERE time >= ? AND time <= ?
+         WHERE time >= $1 AND time <= $2
          ORDER BY time",
     )
     .bind(since)
@@ -146,17 +150,19 @@ pub async fn rebuild_from_s3(truncate: bool) -> Result<()> {
     // List all objects in the raster bucket under ncar/ prefix
     let prefix = object_store::path::Path::from("ncar");
     let list = client.list(Some(&prefix));
-    let objects: Vec<_> = list.try_collect().await?;
+    let mut objects: Vec<_> = list.try_collect().await?;
+    // The latest raster of a time wins when it was replaced
+    objects.sort_by_key(|meta| meta.last_modified);
 
     for meta in objects {
         let path = meta.location.to_string();
 
         // Skip non-PNG files
-        if !path.ends_with("/uv.png") {
+        if !is_uv_png(&path) {
             continue;
         }
 
-        // Parse path: ncar/YYYY/MMDD/hour/uv.png
+        // Parse path: ncar/YYYY/MMDD/hour/uv-{hash}.png
         match parse_ncar_png_path(&path) {
             Some(report) => {
                 upsert_wind_report(&report).await?;
@@ -177,9 +183,20 @@ pub async fn rebuild_from_s3(truncate: bool) -> Result<()> {
     Ok(())
 }
 
-/// Parse an NCAR PNG path like "ncar/2020/1101/0/uv.png" into a WindReport
+/// Whether a raster path is a UV PNG, hashed `uv-{hash}.png` or `uv.png`
+fn is_uv_png(path: &str) -> bool {
+    match path.rsplit_once('/') {
+        Some((_, name)) => {
+            name == "uv.png" || (name.starts_with("uv-") && name.ends_with(".png"))
+        }
+        None => false,
+    }
+}
+
+/// Parse an NCAR PNG path like "ncar/2020/1101/0/uv-{hash}.png" into a
+/// WindReport
 fn parse_ncar_png_path(path: &str) -> Option<WindReport> {
-    // Expected format: ncar/YYYY/MMDD/hour/uv.png
+    // Expected format: ncar/YYYY/MMDD/hour/uv-{hash}.png
     let parts: Vec<&str> = path.split('/').collect();
     if parts.len() != 5 || parts[0] != "ncar" {
         return None;
@@ -208,10 +225,156 @@ fn parse_ncar_png_path(path: &str) -> Option<WindReport> {
     })
 }
 
+/// Largest gap between consecutive wind reports for a period to count as covered
+pub const MAX_REPORT_GAP_MS: i64 = 6 * 60 * 60 * 1000;
+
+/// Get the times of all wind reports, in order
+pub async fn get_all_times() -> Result<Vec<i64>> {
+    let rows: Vec<(i64,)> = sqlx::query_as("SELECT time FROM wind_reports ORDER BY time")
+        .fetch_all(db::pool())
+        .await?;
+    Ok(rows.into_iter().map(|(time,)| time).collect())
+}
+
+/// Report slots (every `MAX_REPORT_GAP_MS`) of the race window of a course,
+/// the day before the start until the max finish time, missing from `times`
+pub fn missing_slots(times: &[i64], course: &Course) -> Vec<i64> {
+    let since = course.start_time - TimeDelta::days(1).num_milliseconds();
+    let first = since - since.rem_euclid(MAX_REPORT_GAP_MS);
+    (0..)
+        .map(|i| first + i * MAX_REPORT_GAP_MS)
+        .take_while(|&slot| slot <= course.max_finish_time())
+        .filter(|slot| times.binary_search(slot).is_err())
+        .collect()
+}
+
+/// Whether the wind at `time` is known: there is a report at or before it and
+/// the next one follows within `MAX_REPORT_GAP_MS`. `times` must be sorted.
+pub fn covers(times: &[i64], time: i64) -> bool {
+    let next = times.partition_point(|&t| t <= time);
+    next > 0
+        && (times[next - 1] == time
+            || times
+                .get(next)
+                .is_some_and(|&after| after - times[next - 1] <= MAX_REPORT_GAP_MS))
+}
+
+/// Report times that can be used as a course start: the whole race window
+/// (the day before the start until the max finish time) has wind reports
+/// with no gap longer than `MAX_REPORT_GAP_MS`. `times` must be sorted.
+pub fn covered_start_times(times: &[i64], course: &Course) -> Vec<i64> {
+    let before = TimeDelta::days(1).num_milliseconds();
+    let after = course.max_finish_time() - course.start_time;
+
+    // gaps_before[i] = number of oversized gaps between times[0] and times[i]
+    let mut gaps_before = vec![0usize; times.len()];
+    for i in 1..times.len() {
+        let gap = times[i] - times[i - 1] > MAX_REPORT_GAP_MS;
+        gaps_before[i] = gaps_before[i - 1] + gap as usize;
+    }
+
+    times
+        .iter()
+        .copied()
+        .filter(|&start| {
+            let (from, until) = (start - before, start + after);
+            let first = times.partition_point(|&t| t <= from);
+            let last = times.partition_point(|&t| t < until);
+            first > 0 && last < times.len() && gaps_before[last] == gaps_before[first - 1]
+        })
+        .collect()
+}
+
+/// Pick a random covered start time for a course, other than its own, so the
+/// course can be raced in another historical weather window.
+#[tracing::instrument(skip_all, fields(course_key = %course.key))]
+pub async fn random_start_time(course: &Course) -> Result<Option<i64>> {
+    let times = get_all_times().await?;
+    let candidates: Vec<i64> = covered_start_times(&times, course)
+        .into_iter()
+        .filter(|&t| t != course.start_time)
+        .collect();
+    Ok(candidates.choose(&mut rand::rng()).copied())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // =========================================================================
+    // covered_start_times tests
+    // =========================================================================
+
+    fn course_with_days(max_days: u8) -> Course {
+        Course {
+            key: "test".to_string(),
+            name: "Test".to_string(),
+            description: String::new(),
+            polar: "vr-imoca-full-pack".to_string(),
+            boat_classes: vec![],
+            start_time: 0,
+            start: crate::courses::LngLat { lng: 0.0, lat: 0.0 },
+            start_heading: 0.0,
+            finish_line: crate::courses::Gate::vertical(1.0, 0.0, 1.0),
+            gates: vec![],
+            route_waypoints: vec![vec![]],
+            time_factor: 1000,
+            max_days,
+            energy: None,
+            min_depth: None,
+            laps: 1,
+            tags: vec![],
+            published: true,
+            opens_at: None,
+            closes_at: None,
+        }
+    }
+
+    #[test]
+    fn test_covered_start_times() {
+        let six_hours = MAX_REPORT_GAP_MS;
+        // Five days of reports every 6h, with a missing report on day 4
+        let times: Vec<i64> = (0..20)
+            .map(|i| i * six_hours)
+            .filter(|&t| t != 14 * six_hours)
+            .collect();
+
+        let starts = covered_start_times(&times, &course_with_days(1));
+        // Needs a day before (4 reports) and a day after, without crossing the gap
+        let expected: Vec<i64> = (4..=9).map(|i| i * six_hours).collect();
+        assert_eq!(starts, expected);
+
+        assert!(covered_start_times(&times, &course_with_days(10)).is_empty());
+        assert!(covered_start_times(&[], &course_with_days(1)).is_empty());
+    }
+
+    #[test]
+    fn test_missing_slots() {
+        let six_hours = MAX_REPORT_GAP_MS;
+        let mut course = course_with_days(1);
+        course.start_time = 5 * six_hours + 1;
+        // From the slot before start - 1 day (slot 1) to start + 1 day (slot 9)
+        let times: Vec<i64> = (0..20)
+            .map(|i| i * six_hours)
+            .filter(|&t| t != 3 * six_hours)
+            .collect();
+        assert_eq!(missing_slots(&times, &course), vec![3 * six_hours]);
+        assert_eq!(missing_slots(&[], &course).len(), 9);
+    }
+
+    #[test]
+    fn test_covers() {
+        let six_hours = MAX_REPORT_GAP_MS;
+        let times = [0, six_hours, 3 * six_hours];
+        assert!(covers(&times, 0));
+        assert!(covers(&times, six_hours / 2));
+        assert!(!covers(&times, -1));
+        assert!(!covers(&times, 2 * six_hours), "gap");
+        assert!(covers(&times, 3 * six_hours));
+        assert!(!covers(&times, 3 * six_hours + 1));
+        assert!(!covers(&[], 0));
+    }
+
     // =========================================================================
     // parse_ncar_png_path tests
     // =========================================================================
@@ -227,6 +390,18 @@ mod tests {
         assert_eq!(report.time.to_rfc3339(), "2020-11-01T00:00:00+00:00");
     }
 
+    #[test]
+    fn test_parse_ncar_png_path_hashed() {
+        let path = "ncar/2020/1101/6/uv-8f8cbb7dcf46e0bc.png";
+        assert!(is_uv_png(path));
+        assert!(is_uv_png("ncar/2020/1101/6/uv.png"));
+        assert!(!is_uv_png("ncar/2020/1101/6/wind.grib2"));
+
+        let report = parse_ncar_png_path(path).unwrap();
+        assert_eq!(report.png_path, path);
+        assert_eq!(report.time.to_rfc3339(), "2020-11-01T06:00:00+00:00");
+    }
+
     #[test]
     fn test_parse_ncar_png_path_different_hour() {
         let report = parse_ncar_png_path("ncar/2020/1115/12/uv.png").unwrap();